@@ -110,8 +110,11 @@ impl Value {
         use am::Value::*;
 
         if let Self::Value(Scalar(scalar)) = &self {
-            if let Str(smol_str) = scalar.as_ref() {
-                return Ok(smol_str.into());
+            match scalar.as_ref() {
+                Str(smol_str) => return Ok(smol_str.into()),
+                BigInt(n) => return Ok(n.as_str().as_bytes().into()),
+                Decimal(n) => return Ok(n.as_str().as_bytes().into()),
+                _ => {}
             }
         }
         Err(InvalidValueType {
@@ -126,8 +129,10 @@ impl Value {
         use am::Value::*;
 
         if let Self::Value(Scalar(scalar)) = &self {
-            if let Timestamp(timestamp) = scalar.as_ref() {
-                return Ok(*timestamp);
+            match scalar.as_ref() {
+                Timestamp(timestamp) => return Ok(*timestamp),
+                DateTime(datetime) => return Ok(datetime.unix_millis()),
+                _ => {}
             }
         }
         Err(InvalidValueType {
@@ -169,7 +174,20 @@ impl From<am::Cursor> for Value {
 
 impl From<&am::ScalarValue> for Value {
     fn from(value: &am::ScalarValue) -> Self {
-        Self::Value(am::Value::Scalar(Cow::Owned(value.clone())))
+        Self::Value(am::Value::Scalar(Cow::Owned(contiguous(value.clone()))))
+    }
+}
+
+/// Copy the segments of chunked bytes into a single byte array which a byte span can point at,
+/// and replace a blob reference with the hash of the blob and a link with the document ID
+fn contiguous(value: am::ScalarValue) -> am::ScalarValue {
+    match value {
+        am::ScalarValue::Chunked(chunked) => {
+            am::ScalarValue::Bytes(chunked.to_vec().unwrap_or_default())
+        }
+        am::ScalarValue::BlobRef(blob) => am::ScalarValue::Str(blob.hash.to_string().into()),
+        am::ScalarValue::Link(doc) => am::ScalarValue::Str(doc.to_string().into()),
+        value => value,
     }
 }
 
@@ -193,7 +211,12 @@ impl From<am::sync::State> for Value {
 
 impl From<am::Value<'static>> for Value {
     fn from(value: am::Value<'static>) -> Self {
-        Self::Value(value)
+        match value {
+            am::Value::Scalar(scalar) if scalar.is_chunked() => Self::Value(am::Value::Scalar(
+                Cow::Owned(contiguous(scalar.into_owned())),
+            )),
+            value => Self::Value(value),
+        }
     }
 }
 
@@ -1348,13 +1371,16 @@ impl From<&am::Value<'static>> for AMvalType {
             Object(_) => Self::ObjType,
             Scalar(scalar) => match scalar.as_ref() {
                 Boolean(_) => Self::Bool,
-                Bytes(_) => Self::Bytes,
+                Bytes(_) | Chunked(_) => Self::Bytes,
                 Counter(_) => Self::Counter,
                 F64(_) => Self::F64,
                 Int(_) => Self::Int,
                 Null => Self::Null,
-                Str(_) => Self::Str,
-                Timestamp(_) => Self::Timestamp,
+                // big numbers are read as their decimal digits, blob references as their hash and
+                // links as the document ID
+                Str(_) | BigInt(_) | Decimal(_) | BlobRef(_) | Link(_) => Self::Str,
+                // a date time is read as a timestamp in milliseconds
+                Timestamp(_) | DateTime(_) => Self::Timestamp,
                 Uint(_) => Self::Uint,
                 Unknown { .. } => Self::Unknown,
             },
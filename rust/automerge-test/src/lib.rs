@@ -228,9 +228,19 @@ pub enum OrdScalarValue {
     F64(decorum::Total<f64>),
     Counter(i64),
     Timestamp(i64),
+    DateTime(automerge::DateTime),
+    BigInt(automerge::BigInt),
+    Decimal(automerge::Decimal),
+    /// The contents of a [`automerge::ChunkedBytes`]
+    Chunked(Vec<u8>),
+    BlobRef(automerge::blob::BlobRef),
+    Link(automerge::link::DocId),
     Boolean(bool),
     Null,
-    Unknown { type_code: u8, bytes: Vec<u8> },
+    Unknown {
+        type_code: u8,
+        bytes: Vec<u8>,
+    },
 }
 
 impl From<automerge::ScalarValue> for OrdScalarValue {
@@ -243,6 +253,14 @@ impl From<automerge::ScalarValue> for OrdScalarValue {
             automerge::ScalarValue::F64(v) => OrdScalarValue::F64(decorum::Total::from(v)),
             automerge::ScalarValue::Counter(c) => OrdScalarValue::Counter(c.into()),
             automerge::ScalarValue::Timestamp(v) => OrdScalarValue::Timestamp(v),
+            automerge::ScalarValue::DateTime(v) => OrdScalarValue::DateTime(v),
+            automerge::ScalarValue::BigInt(v) => OrdScalarValue::BigInt(v),
+            automerge::ScalarValue::Decimal(v) => OrdScalarValue::Decimal(v),
+            automerge::ScalarValue::Chunked(v) => {
+                OrdScalarValue::Chunked(v.to_vec().expect("missing segment"))
+            }
+            automerge::ScalarValue::BlobRef(v) => OrdScalarValue::BlobRef(*v),
+            automerge::ScalarValue::Link(v) => OrdScalarValue::Link(v),
             automerge::ScalarValue::Boolean(v) => OrdScalarValue::Boolean(v),
             automerge::ScalarValue::Null => OrdScalarValue::Null,
             automerge::ScalarValue::Unknown { type_code, bytes } => {
@@ -262,6 +280,14 @@ impl From<&OrdScalarValue> for automerge::ScalarValue {
             OrdScalarValue::F64(v) => automerge::ScalarValue::F64(v.into_inner()),
             OrdScalarValue::Counter(v) => automerge::ScalarValue::counter(*v),
             OrdScalarValue::Timestamp(v) => automerge::ScalarValue::Timestamp(*v),
+            OrdScalarValue::DateTime(v) => automerge::ScalarValue::DateTime(*v),
+            OrdScalarValue::BigInt(v) => automerge::ScalarValue::BigInt(v.clone()),
+            OrdScalarValue::Decimal(v) => automerge::ScalarValue::Decimal(v.clone()),
+            OrdScalarValue::Chunked(v) => {
+                automerge::ScalarValue::Chunked(automerge::ChunkedBytes::new(v))
+            }
+            OrdScalarValue::BlobRef(v) => automerge::ScalarValue::BlobRef(Box::new(v.clone())),
+            OrdScalarValue::Link(v) => automerge::ScalarValue::Link(v.clone()),
             OrdScalarValue::Boolean(v) => automerge::ScalarValue::Boolean(*v),
             OrdScalarValue::Null => automerge::ScalarValue::Null,
             OrdScalarValue::Unknown { type_code, bytes } => automerge::ScalarValue::Unknown {
@@ -289,6 +315,20 @@ impl serde::Serialize for OrdScalarValue {
             OrdScalarValue::Timestamp(v) => {
                 serializer.serialize_str(format!("Timestamp({})", v).as_str())
             }
+            OrdScalarValue::DateTime(v) => {
+                serializer.serialize_str(format!("DateTime({})", v).as_str())
+            }
+            OrdScalarValue::BigInt(v) => {
+                serializer.serialize_str(format!("BigInt({})", v).as_str())
+            }
+            OrdScalarValue::Decimal(v) => {
+                serializer.serialize_str(format!("Decimal({})", v).as_str())
+            }
+            OrdScalarValue::Chunked(v) => serializer.serialize_bytes(v),
+            OrdScalarValue::BlobRef(v) => {
+                serializer.serialize_str(format!("BlobRef({})", v).as_str())
+            }
+            OrdScalarValue::Link(v) => serializer.serialize_str(format!("Link({})", v).as_str()),
             OrdScalarValue::Boolean(v) => serializer.serialize_bool(*v),
             OrdScalarValue::Null => serializer.serialize_none(),
             OrdScalarValue::Unknown { type_code, .. } => serializer
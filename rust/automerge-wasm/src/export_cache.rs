@@ -251,6 +251,10 @@ impl<'a> ExportCache<'a> {
     fn export_scalar(&self, value: &am::ScalarValue) -> Result<JsValue, error::Export> {
         let (datatype, js_value) = match value {
             am::ScalarValue::Bytes(v) => (Datatype::Bytes, Uint8Array::from(v.as_slice()).into()),
+            am::ScalarValue::Chunked(v) => (
+                Datatype::Bytes,
+                Uint8Array::from(v.to_vec().unwrap_or_default().as_slice()).into(),
+            ),
             am::ScalarValue::Str(v) => (Datatype::Str, v.to_string().into()),
             am::ScalarValue::Int(v) => (Datatype::Int, (*v as f64).into()),
             am::ScalarValue::Uint(v) => (Datatype::Uint, (*v as f64).into()),
@@ -260,6 +264,14 @@ impl<'a> ExportCache<'a> {
                 Datatype::Timestamp,
                 js_sys::Date::new(&(*v as f64).into()).into(),
             ),
+            am::ScalarValue::DateTime(d) => (
+                Datatype::Timestamp,
+                js_sys::Date::new(&(d.unix_millis() as f64).into()).into(),
+            ),
+            am::ScalarValue::BigInt(v) => (Datatype::Str, v.as_str().into()),
+            am::ScalarValue::Decimal(v) => (Datatype::Str, v.as_str().into()),
+            am::ScalarValue::BlobRef(v) => (Datatype::Str, v.hash.to_string().into()),
+            am::ScalarValue::Link(v) => (Datatype::Str, v.to_string().into()),
             am::ScalarValue::Boolean(v) => (Datatype::Boolean, (*v).into()),
             am::ScalarValue::Null => (Datatype::Null, JsValue::null()),
             am::ScalarValue::Unknown { bytes, type_code } => (
@@ -53,6 +53,7 @@ impl From<JS> for JsValue {
 
 impl From<am::sync::State> for JS {
     fn from(state: am::sync::State) -> Self {
+        let peer_resets = state.peer_resets;
         let shared_heads: JS = state.shared_heads.into();
         let last_sent_heads: JS = state.last_sent_heads.into();
         let their_heads: JS = state.their_heads.into();
@@ -74,6 +75,7 @@ impl From<am::sync::State> for JS {
         Reflect::set(&result, &"sentHashes".into(), &sent_hashes.0).unwrap();
         Reflect::set(&result, &"inFlight".into(), &state.in_flight.into()).unwrap();
         Reflect::set(&result, &"haveResponded".into(), &have_responded).unwrap();
+        Reflect::set(&result, &"peerResets".into(), &(peer_resets as f64).into()).unwrap();
         if let Some(caps) = state.their_capabilities {
             Reflect::set(
                 &result,
@@ -316,6 +318,11 @@ impl TryFrom<JS> for am::sync::State {
             .0
             .as_bool()
             .unwrap_or(false);
+        let peer_resets = js_get(&value, "peerResets")?
+            .0
+            .as_f64()
+            .map(|n| n as usize)
+            .unwrap_or(0);
         let their_capabilities = {
             let caps_obj = js_get(&value, "theirCapabilities")?;
             if !caps_obj.is_undefined() {
@@ -336,6 +343,11 @@ impl TryFrom<JS> for am::sync::State {
             in_flight,
             have_responded,
             their_capabilities,
+            peer_resets,
+            // options and ephemeral data belong to the connection, not the state
+            options: Default::default(),
+            ephemeral_to_send: Vec::new(),
+            ephemeral_received: Vec::new(),
         })
     }
 }
@@ -438,12 +450,24 @@ impl TryFrom<JS> for am::sync::Message {
             .try_into()
             .map_err(error::BadSyncMessage::BadJSChanges)?;
 
+        let ephemeral_obj = js_get(&value.0, "ephemeral")?;
+        let ephemeral = if ephemeral_obj.is_undefined() {
+            Vec::new()
+        } else {
+            ChunkList::try_from(ephemeral_obj)
+                .map_err(error::BadSyncMessage::BadEphemeral)?
+                .iter()
+                .map(<[u8]>::to_vec)
+                .collect()
+        };
+
         Ok(am::sync::Message {
             heads,
             need,
             have,
             changes,
             supported_capabilities,
+            ephemeral,
             version,
         })
     }
@@ -529,7 +553,8 @@ impl From<&[am::sync::Capability]> for AR {
             .filter_map(|c| match c {
                 am::sync::Capability::MessageV1 => Some(JsValue::from_str("message-v1")),
                 am::sync::Capability::MessageV2 => Some(JsValue::from_str("message-v2")),
-                am::sync::Capability::Unknown(_) => None,
+                am::sync::Capability::Ephemeral => Some(JsValue::from_str("ephemeral")),
+                _ => None,
             })
             .collect())
     }
@@ -587,6 +612,7 @@ impl TryFrom<JS> for Vec<Capability> {
                 match as_str.as_str() {
                     "message-v1" => Ok(Capability::MessageV1),
                     "message-v2" => Ok(Capability::MessageV2),
+                    "ephemeral" => Ok(Capability::Ephemeral),
                     other => Err(error::BadCapabilities::ElemNotValid(i, other.to_string())),
                 }
             })
@@ -1017,6 +1043,11 @@ impl Automerge {
             PatchAction::Insert { index, values, .. } => {
                 self.sub_splice(array, *index, 0, values, meta, cache)
             }
+            PatchAction::Splice {
+                index,
+                deleted,
+                inserted,
+            } => self.sub_splice(array, *index, *deleted, inserted, meta, cache),
             PatchAction::Increment { prop, value, .. } => {
                 if let Prop::Seq(index) = prop {
                     let index = *index as f64;
@@ -1110,7 +1141,9 @@ impl Automerge {
             }
             PatchAction::Conflict { .. } => Ok(()),
             PatchAction::Insert { .. } => Err(error::ApplyPatch::InsertInMap),
-            PatchAction::DeleteSeq { .. } => Err(error::ApplyPatch::SpliceInMap),
+            PatchAction::DeleteSeq { .. } | PatchAction::Splice { .. } => {
+                Err(error::ApplyPatch::SpliceInMap)
+            }
             PatchAction::SpliceText { .. } => Err(error::ApplyPatch::SpliceTextInMap),
             PatchAction::PutSeq { .. } => Err(error::ApplyPatch::PutIdxInMap),
             PatchAction::Mark { .. } => Err(error::ApplyPatch::MarkInMap),
@@ -1377,6 +1410,10 @@ pub(crate) fn alloc(value: &Value<'_>, text_rep: TextRepresentation) -> (Datatyp
 pub(crate) fn alloc_scalar(value: &am::ScalarValue) -> (Datatype, JsValue) {
     match value {
         am::ScalarValue::Bytes(v) => (Datatype::Bytes, Uint8Array::from(v.as_slice()).into()),
+        am::ScalarValue::Chunked(v) => (
+            Datatype::Bytes,
+            Uint8Array::from(v.to_vec().unwrap_or_default().as_slice()).into(),
+        ),
         am::ScalarValue::Str(v) => (Datatype::Str, v.to_string().into()),
         am::ScalarValue::Int(v) => (Datatype::Int, (*v as f64).into()),
         am::ScalarValue::Uint(v) => (Datatype::Uint, (*v as f64).into()),
@@ -1386,6 +1423,14 @@ pub(crate) fn alloc_scalar(value: &am::ScalarValue) -> (Datatype, JsValue) {
             Datatype::Timestamp,
             js_sys::Date::new(&(*v as f64).into()).into(),
         ),
+        am::ScalarValue::DateTime(d) => (
+            Datatype::Timestamp,
+            js_sys::Date::new(&(d.unix_millis() as f64).into()).into(),
+        ),
+        am::ScalarValue::BigInt(v) => (Datatype::Str, v.as_str().into()),
+        am::ScalarValue::Decimal(v) => (Datatype::Str, v.as_str().into()),
+        am::ScalarValue::BlobRef(v) => (Datatype::Str, v.hash.to_string().into()),
+        am::ScalarValue::Link(v) => (Datatype::Str, v.to_string().into()),
         am::ScalarValue::Boolean(v) => (Datatype::Boolean, (*v).into()),
         am::ScalarValue::Null => (Datatype::Null, JsValue::null()),
         am::ScalarValue::Unknown { bytes, type_code } => (
@@ -1504,10 +1549,46 @@ pub(crate) fn export_patches<I: IntoIterator<Item = Patch>>(
     patches
         .into_iter()
         // removing update block for now
+        .flat_map(split_splice)
         .map(|p| export_patch(externals, p))
         .collect()
 }
 
+/// Splices are exported as the deletion and insertion they are made of
+fn split_splice(p: Patch) -> Vec<Patch> {
+    let PatchAction::Splice {
+        index,
+        deleted,
+        inserted,
+    } = p.action
+    else {
+        return vec![p];
+    };
+    let mut patches = Vec::new();
+    if deleted > 0 {
+        patches.push(Patch {
+            obj: p.obj.clone(),
+            path: p.path.clone(),
+            action: PatchAction::DeleteSeq {
+                index,
+                length: deleted,
+            },
+        });
+    }
+    if !inserted.is_empty() {
+        let mut values = am::SequenceTree::new();
+        for value in inserted {
+            values.push(value);
+        }
+        patches.push(Patch {
+            obj: p.obj,
+            path: p.path,
+            action: PatchAction::Insert { index, values },
+        });
+    }
+    patches
+}
+
 fn export_patch(
     externals: &HashMap<Datatype, ExternalTypeConstructor>,
     p: Patch,
@@ -1653,6 +1734,7 @@ fn export_patch(
             js_set(&result, "path", export_path(path, &prop))?;
             Ok(result.into())
         }
+        PatchAction::Splice { .. } => unreachable!("splices are split before they are exported"),
     }
 }
 
@@ -1948,6 +2030,8 @@ pub(crate) mod error {
         MissingChanges,
         #[error("bad supported_capabilities: {0}")]
         BadSupportedCapabilities(BadCapabilities),
+        #[error("could not read ephemeral: {0}")]
+        BadEphemeral(BadChunkList),
         #[error("wholeDoc cannot be used in a type: v1 message")]
         WholeDocInV1,
     }
@@ -1036,7 +1036,11 @@ impl Automerge {
     #[wasm_bindgen(js_name = emptyChange)]
     pub fn empty_change(&mut self, message: Option<String>, time: Option<f64>) -> JsValue {
         let time = time.map(|f| f as i64);
-        let options = CommitOptions { message, time };
+        let options = CommitOptions {
+            message,
+            time,
+            ..Default::default()
+        };
         let hash = self.doc.empty_change(options);
         JsValue::from_str(&hex::encode(hash))
     }
@@ -1338,11 +1342,20 @@ pub fn decode_sync_message(msg: Uint8Array) -> Result<JsValue, error::BadSyncMes
         }
     };
 
-    if let Some(caps) = msg.supported_capabilities {
+    if let Some(caps) = &msg.supported_capabilities {
         let caps = AR::from(caps.as_slice());
         js_set(&obj, "supportedCapabilities", caps).unwrap();
     }
 
+    if !msg.ephemeral.is_empty() {
+        let ephemeral = msg
+            .ephemeral
+            .iter()
+            .map(|data| Uint8Array::from(data.as_slice()))
+            .collect::<Array>();
+        js_set(&obj, "ephemeral", ephemeral).unwrap();
+    }
+
     Ok(obj)
 }
 
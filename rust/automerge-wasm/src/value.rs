@@ -55,13 +55,20 @@ impl std::fmt::Display for Datatype {
 impl From<&ScalarValue> for Datatype {
     fn from(s: &ScalarValue) -> Self {
         match s {
-            ScalarValue::Bytes(_) => Self::Bytes,
+            ScalarValue::Bytes(_) | ScalarValue::Chunked(_) => Self::Bytes,
             ScalarValue::Str(_) => Self::Str,
             ScalarValue::Int(_) => Self::Int,
             ScalarValue::Uint(_) => Self::Uint,
             ScalarValue::F64(_) => Self::F64,
             ScalarValue::Counter(_) => Self::Counter,
-            ScalarValue::Timestamp(_) => Self::Timestamp,
+            // js dates have millisecond precision and no offset, so date times are timestamps
+            ScalarValue::Timestamp(_) | ScalarValue::DateTime(_) => Self::Timestamp,
+            // big numbers are exported as strings so that no digits are lost, blob references as
+            // the hash of the blob and links as the ID of the document
+            ScalarValue::BigInt(_)
+            | ScalarValue::Decimal(_)
+            | ScalarValue::BlobRef(_)
+            | ScalarValue::Link(_) => Self::Str,
             ScalarValue::Boolean(_) => Self::Boolean,
             ScalarValue::Null => Self::Null,
             ScalarValue::Unknown { type_code, .. } => Self::Unknown(*type_code),
@@ -1,4 +1,4 @@
-use automerge::{transaction::Transactable, Automerge, ReadDoc, ROOT};
+use automerge::{transaction::Transactable, Automerge, ObjId, ObjType, ReadDoc, ROOT};
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 
 fn doc(n: u64) -> Automerge {
@@ -11,6 +11,30 @@ fn doc(n: u64) -> Automerge {
     doc
 }
 
+fn list_doc(n: usize) -> (Automerge, ObjId) {
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    let list = tx.put_object(ROOT, "list", ObjType::List).unwrap();
+    for i in 0..n {
+        tx.insert(&list, i, i as i64).unwrap();
+    }
+    tx.commit();
+    (doc, list)
+}
+
+fn get_each_index(doc: &Automerge, list: &ObjId) {
+    for i in 0..doc.length(list) {
+        black_box(doc.get(list, i).unwrap());
+    }
+}
+
+fn get_each_index_with_handle(doc: &Automerge, list: &ObjId) {
+    let handle = doc.handle(list).unwrap();
+    for i in 0..handle.len() {
+        black_box(handle.get(i).unwrap());
+    }
+}
+
 fn range(doc: &Automerge) {
     let range = doc.values(ROOT);
     range.for_each(drop);
@@ -30,6 +54,13 @@ fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function(&format!("range_at {}", n), |b| {
         b.iter(|| range_at(black_box(&doc)))
     });
+    let (doc, list) = list_doc(10_000);
+    c.bench_function("get each index 10000", |b| {
+        b.iter(|| get_each_index(black_box(&doc), &list))
+    });
+    c.bench_function("get each index with handle 10000", |b| {
+        b.iter(|| get_each_index_with_handle(black_box(&doc), &list))
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -82,6 +82,14 @@ fn get_changes(_doc: &Automerge, patches: Vec<Patch>) {
                 "delete {:?} in obj {:?}, object path {:?}",
                 index, obj, path,
             ),
+            PatchAction::Splice {
+                index,
+                deleted,
+                inserted,
+            } => println!(
+                "splice {:?} replacing {:?} at {:?} in obj {:?}, object path {:?}",
+                inserted, deleted, index, obj, path,
+            ),
             PatchAction::Mark { marks } => {
                 println!("mark {:?} in obj {:?}, object path {:?}", marks, obj, path,)
             }
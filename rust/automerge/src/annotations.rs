@@ -0,0 +1,165 @@
+//! Annotations on ranges of text which are anchored to the text they annotate
+//!
+//! Unlike [marks](crate::marks), which format text, annotations are for attaching things like
+//! comments to a range of text. Each end of the range is stored as a [`Cursor`], so the range
+//! moves with the text it annotates as the text is edited and merged. Annotations are stored as
+//! ordinary maps in a map object of the document, so they are saved, synced and merged along
+//! with the rest of the document.
+//!
+//! # Example
+//!
+//! ```
+//! # use automerge::{AutoCommit, ObjType, ROOT};
+//! # use automerge::annotations::Annotations;
+//! # use automerge::transaction::Transactable;
+//! let mut doc = AutoCommit::new();
+//! let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+//! let comments = doc.put_object(ROOT, "comments", ObjType::Map).unwrap();
+//! doc.splice_text(&text, 0, 0, "hello world").unwrap();
+//!
+//! let annotations = Annotations::new(comments, text.clone());
+//! annotations.add(&mut doc, "c1", 6..11, "which world?").unwrap();
+//! doc.splice_text(&text, 0, 0, "oh, ").unwrap();
+//!
+//! let found = annotations.overlapping(&doc, 9..12).unwrap();
+//! assert_eq!(found[0].id, "c1");
+//! assert_eq!(found[0].range, 10..15);
+//! ```
+use std::ops::Range;
+
+use crate::exid::ExId;
+use crate::transaction::Transactable;
+use crate::{AutomergeError, Cursor, ObjType, ReadDoc, ScalarValue, Value};
+
+const START: &str = "start";
+const END: &str = "end";
+const VALUE: &str = "value";
+
+/// An annotation read back from the document
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    /// The key the annotation is stored under
+    pub id: String,
+    /// The range of the text the annotation currently covers
+    ///
+    /// If all of the annotated text has been deleted this is an empty range at the position the
+    /// text used to be.
+    pub range: Range<usize>,
+    /// The value attached to the annotation
+    pub value: ScalarValue,
+}
+
+/// The annotations on one text object, stored in a map object of the same document
+///
+/// Each annotation is a map in `store`, keyed by an ID chosen by the application, containing the
+/// cursors of the first and last characters of the range along with the value of the
+/// annotation. Text which is inserted at either end of the range is not part of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotations {
+    store: ExId,
+    text: ExId,
+}
+
+impl Annotations {
+    /// The annotations on `text` which are stored in the map `store`
+    pub fn new(store: ExId, text: ExId) -> Self {
+        Self { store, text }
+    }
+
+    /// Annotate `range` of the text with `value`, replacing any annotation with the same `id`
+    ///
+    /// Returns [`AutomergeError::InvalidIndex`] if `range` is empty or extends past the end of
+    /// the text.
+    pub fn add<T: Transactable, V: Into<ScalarValue>>(
+        &self,
+        doc: &mut T,
+        id: &str,
+        range: Range<usize>,
+        value: V,
+    ) -> Result<ExId, AutomergeError> {
+        if range.is_empty() {
+            return Err(AutomergeError::InvalidIndex(range.start));
+        }
+        let start = doc.get_cursor(&self.text, range.start, None)?;
+        let end = doc.get_cursor(&self.text, range.end - 1, None)?;
+        let annotation = doc.put_object(&self.store, id, ObjType::Map)?;
+        doc.put(&annotation, START, start.to_string())?;
+        doc.put(&annotation, END, end.to_string())?;
+        doc.put(&annotation, VALUE, value)?;
+        Ok(annotation)
+    }
+
+    /// Remove the annotation stored under `id`
+    pub fn remove<T: Transactable>(&self, doc: &mut T, id: &str) -> Result<(), AutomergeError> {
+        doc.delete(&self.store, id)
+    }
+
+    /// The annotation stored under `id`, if there is one
+    pub fn get<R: ReadDoc>(&self, doc: &R, id: &str) -> Result<Option<Annotation>, AutomergeError> {
+        match doc.get(&self.store, id)? {
+            Some((Value::Object(ObjType::Map), annotation)) => self.read(doc, id, &annotation),
+            _ => Ok(None),
+        }
+    }
+
+    /// Every annotation, ordered by ID
+    ///
+    /// Entries of the store which are not annotations are skipped.
+    pub fn all<R: ReadDoc>(&self, doc: &R) -> Result<Vec<Annotation>, AutomergeError> {
+        let mut annotations = Vec::new();
+        for item in doc.map_range(&self.store, ..) {
+            if let Value::Object(ObjType::Map) = item.value {
+                if let Some(annotation) = self.read(doc, item.key, &item.id)? {
+                    annotations.push(annotation);
+                }
+            }
+        }
+        Ok(annotations)
+    }
+
+    /// The annotations which cover at least one character of `range`, ordered by ID
+    ///
+    /// An empty `range` finds the annotations covering the character at its start.
+    pub fn overlapping<R: ReadDoc>(
+        &self,
+        doc: &R,
+        range: Range<usize>,
+    ) -> Result<Vec<Annotation>, AutomergeError> {
+        let range = range.start..range.end.max(range.start.saturating_add(1));
+        let mut annotations = self.all(doc)?;
+        annotations.retain(|a| a.range.start < range.end && range.start < a.range.end);
+        Ok(annotations)
+    }
+
+    fn read<R: ReadDoc>(
+        &self,
+        doc: &R,
+        id: &str,
+        annotation: &ExId,
+    ) -> Result<Option<Annotation>, AutomergeError> {
+        let cursor = |key| match doc.get(annotation, key)? {
+            Some((Value::Scalar(s), _)) => match s.to_str() {
+                Some(s) => Cursor::try_from(s).map(Some),
+                None => Ok(None),
+            },
+            _ => Ok(None),
+        };
+        let (Some(start), Some(end)) = (cursor(START)?, cursor(END)?) else {
+            return Ok(None);
+        };
+        let value = match doc.get(annotation, VALUE)? {
+            Some((Value::Scalar(s), _)) => s.into_owned(),
+            _ => ScalarValue::Null,
+        };
+        let start = doc.get_cursor_position(&self.text, &start, None)?;
+        let end_pos = doc.get_cursor_position(&self.text, &end, None)?;
+        // a cursor to a deleted character resolves to the position of the next character
+        let end_visible = doc.get_cursor(&self.text, end_pos, None).ok() == Some(end);
+        let end = if end_visible { end_pos + 1 } else { end_pos };
+        Ok(Some(Annotation {
+            id: id.to_string(),
+            range: start..end.max(start),
+            value,
+        }))
+    }
+}
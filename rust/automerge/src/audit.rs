@@ -0,0 +1,263 @@
+//! Records of every change applied to a document, for audit logs
+//!
+//! An [`AuditSink`] set with [`crate::Automerge::set_audit_sink()`] is given an [`AuditRecord`]
+//! for every change which is applied to the document, whether it was made locally or received
+//! from another peer. The record describes the change in terms of the paths it touched, so it
+//! can be written to a log without the consumer having to decode the change.
+//!
+//! Records implement [`serde::Serialize`], and [`JsonLinesSink`] writes them as one JSON object
+//! per line. Their [`std::fmt::Display`] implementation is a single line suitable for syslog.
+//!
+//! This module is available with the `audit-log` feature.
+//!
+//! # Example
+//!
+//! ```
+//! # use automerge::{AutoCommit, ObjType, ROOT};
+//! # use automerge::audit::JsonLinesSink;
+//! # use automerge::transaction::Transactable;
+//! # use std::sync::Arc;
+//! let sink = Arc::new(JsonLinesSink::new(Vec::new()));
+//! let mut doc = AutoCommit::new();
+//! doc.set_audit_sink(sink.clone());
+//! let todos = doc.put_object(ROOT, "todos", ObjType::List).unwrap();
+//! doc.insert(&todos, 0, "write docs").unwrap();
+//! doc.commit();
+//!
+//! let log = String::from_utf8(sink.take()).unwrap();
+//! let record: serde_json::Value = serde_json::from_str(log.trim_end()).unwrap();
+//! assert_eq!(record["paths"], serde_json::json!(["/todos"]));
+//! assert_eq!(record["ops"]["inserts"], 1);
+//! ```
+use std::fmt;
+use std::io::Write;
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::{ActorId, ChangeHash};
+
+/// A description of a change which has been applied to a document
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditRecord {
+    pub hash: ChangeHash,
+    pub actor: ActorId,
+    pub seq: u64,
+    /// The time the change was made, in milliseconds since the unix epoch
+    pub timestamp: i64,
+    pub message: Option<String>,
+    /// Whether the change was made by a transaction on this document rather than received
+    pub local: bool,
+    /// The paths the change touched, as JSON pointers, sorted and without duplicates
+    ///
+    /// For a change to a map this is the path of the key which was changed. For a change to a
+    /// list or text object it is the path of the object, as inserting or deleting moves every
+    /// later element. Objects which have since been deleted are reported at the path they had.
+    pub paths: Vec<String>,
+    pub ops: OpCounts,
+}
+
+/// The number of operations of each kind in a change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct OpCounts {
+    /// Values and objects put into a map or at an existing index of a list
+    pub puts: usize,
+    /// Values and objects inserted into a list or text object
+    pub inserts: usize,
+    pub deletes: usize,
+    pub increments: usize,
+    /// The beginnings and ends of marks
+    pub marks: usize,
+}
+
+impl OpCounts {
+    pub fn total(&self) -> usize {
+        self.puts + self.inserts + self.deletes + self.increments + self.marks
+    }
+}
+
+impl fmt::Display for AuditRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} change {} by {} seq {} at {}: {} ops on {}",
+            if self.local { "local" } else { "remote" },
+            self.hash,
+            self.actor,
+            self.seq,
+            self.timestamp,
+            self.ops.total(),
+            self.paths.join(" ")
+        )?;
+        if let Some(message) = &self.message {
+            write!(f, " ({:?})", message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Receives a record of every change applied to a document, see
+/// [`crate::Automerge::set_audit_sink()`]
+///
+/// This is implemented for closures taking an [`AuditRecord`].
+pub trait AuditSink: Send + Sync {
+    fn record(&self, record: &AuditRecord);
+}
+
+impl<F: Fn(&AuditRecord) + Send + Sync> AuditSink for F {
+    fn record(&self, record: &AuditRecord) {
+        self(record)
+    }
+}
+
+/// An [`AuditSink`] which writes each record to `W` as a line of JSON
+///
+/// Errors writing to `W` are logged with `tracing` and otherwise ignored, so that a failing log
+/// does not prevent changes from being applied.
+#[derive(Debug)]
+pub struct JsonLinesSink<W> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> JsonLinesSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<W: Write + Send + Default> JsonLinesSink<W> {
+    /// Take the writer, leaving a new default one in its place
+    pub fn take(&self) -> W {
+        std::mem::take(&mut *self.writer.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl<W: Write + Send> AuditSink for JsonLinesSink<W> {
+    fn record(&self, record: &AuditRecord) {
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let result = serde_json::to_writer(&mut *writer, record)
+            .map_err(std::io::Error::from)
+            .and_then(|_| writer.write_all(b"\n"));
+        if let Err(e) = result {
+            tracing::warn!(err=?e, hash=?record.hash, "failed to write audit record");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{AuditRecord, OpCounts};
+    use crate::transaction::Transactable;
+    use crate::{AutoCommit, ObjType, ROOT};
+
+    fn recording(doc: &mut AutoCommit) -> Arc<Mutex<Vec<AuditRecord>>> {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink = records.clone();
+        doc.set_audit_sink(Arc::new(move |record: &AuditRecord| {
+            sink.lock().unwrap().push(record.clone())
+        }));
+        records
+    }
+
+    #[test]
+    fn records_local_and_remote_changes() {
+        let mut doc = AutoCommit::new();
+        let records = recording(&mut doc);
+        let config = doc.put_object(ROOT, "config", ObjType::Map).unwrap();
+        doc.put(&config, "a/b", 1).unwrap();
+        doc.put(ROOT, "count", crate::ScalarValue::counter(0))
+            .unwrap();
+        let hash = doc.commit().unwrap();
+
+        let mut remote = doc.fork();
+        remote.increment(ROOT, "count", 2).unwrap();
+        remote.delete(&config, "a/b").unwrap();
+        remote.commit_with(crate::transaction::CommitOptions::default().with_message("tidy"));
+        doc.merge(&mut remote).unwrap();
+
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].hash, hash);
+        assert!(records[0].local);
+        assert_eq!(records[0].paths, vec!["/config", "/config/a~1b", "/count"]);
+        assert_eq!(
+            records[0].ops,
+            OpCounts {
+                puts: 3,
+                ..Default::default()
+            }
+        );
+        assert!(!records[1].local);
+        assert_eq!(records[1].actor, *remote.get_actor());
+        assert_eq!(records[1].message.as_deref(), Some("tidy"));
+        assert_eq!(records[1].paths, vec!["/config/a~1b", "/count"]);
+        assert_eq!(records[1].ops.increments, 1);
+        assert_eq!(records[1].ops.deletes, 1);
+    }
+
+    #[test]
+    fn paths_of_deleted_objects_are_where_they_were() {
+        let mut doc = AutoCommit::new();
+        let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+        let item = doc.insert_object(&list, 0, ObjType::Map).unwrap();
+        doc.commit();
+        let mut remote = doc.fork();
+        doc.delete(&list, 0).unwrap();
+        doc.commit();
+        let records = recording(&mut doc);
+        remote.put(&item, "done", true).unwrap();
+        remote.commit();
+        doc.merge(&mut remote).unwrap();
+        assert_eq!(records.lock().unwrap()[0].paths, vec!["/list/0/done"]);
+    }
+
+    #[test]
+    fn records_changes_loaded_into_an_empty_document() {
+        use crate::sync::{self, SyncDoc};
+
+        let mut source = AutoCommit::new();
+        source.put(ROOT, "a", 1).unwrap();
+        let first = source.commit().unwrap();
+        source.put(ROOT, "b", 2).unwrap();
+        let second = source.commit().unwrap();
+
+        let mut loaded = AutoCommit::new();
+        let records = recording(&mut loaded);
+        loaded.load_incremental(&source.save()).unwrap();
+        let hashes = records
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| r.hash)
+            .collect::<Vec<_>>();
+        assert_eq!(hashes, vec![first, second]);
+
+        let mut synced = AutoCommit::new();
+        let records = recording(&mut synced);
+        let (mut s1, mut s2) = (sync::State::new(), sync::State::new());
+        loop {
+            let m1 = source.sync().generate_sync_message(&mut s1);
+            if let Some(m) = m1.clone() {
+                synced.sync().receive_sync_message(&mut s2, m).unwrap();
+            }
+            let m2 = synced.sync().generate_sync_message(&mut s2);
+            if let Some(m) = m2.clone() {
+                source.sync().receive_sync_message(&mut s1, m).unwrap();
+            }
+            if m1.is_none() && m2.is_none() {
+                break;
+            }
+        }
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|r| !r.local));
+    }
+}
@@ -1,22 +1,31 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::io;
 use std::ops::RangeBounds;
+use std::sync::Arc;
 
-use crate::automerge::SaveOptions;
 use crate::automerge::{current_state, diff};
+use crate::automerge::{SaveOptions, TransactionContext, WriteRequest};
 use crate::exid::ExId;
-use crate::iter::Spans;
-use crate::iter::{Keys, ListRange, MapRange, Values};
+use crate::extra_bytes::Field;
+use crate::iter::{Keys, ListRange, MapEntriesAll, MapRange, Values, ValuesRef};
+use crate::iter::{Spans, TextChars};
 use crate::marks::{ExpandMark, Mark, MarkSet};
-use crate::patches::{PatchLog, TextRepresentation};
+use crate::patches::{PatchLog, PatchObserver, TextRepresentation, TextUnitTranslator};
 use crate::sync::SyncDoc;
-use crate::transaction::{CommitOptions, Transactable};
+use crate::transaction::{CommitOptions, PendingChange, Savepoint, Transactable};
 use crate::types::Clock;
-use crate::{hydrate, OnPartialLoad};
-use crate::{sync, ObjType, Parents, Patch, ReadDoc, ScalarValue};
+use crate::{hydrate, OnPartialLoad, TextUnit};
+use crate::{sync, ObjType, Parents, Patch, ReadDoc, ScalarValue, ValueRef};
 use crate::{
     transaction::TransactionInner, ActorId, Automerge, AutomergeError, Change, ChangeHash, Cursor,
     Prop, Value,
 };
-use crate::{LoadOptions, VerificationMode};
+use crate::{
+    ActorInfo, CancellationToken, ChangeReader, ChangeSummary, Checkout, CloneOptions,
+    ConflictPolicy, ConflictStats, Conflicts, ContentDifference, DepsStrategy, History,
+    LoadOptions, MergeReport, ObjHandle, StateHash, VerificationMode,
+};
 
 /// An automerge document that automatically manages transactions.
 ///
@@ -62,6 +71,23 @@ pub struct AutoCommit {
     diff_cache: Option<(OpRange, Vec<Patch>)>,
     save_cursor: Vec<ChangeHash>,
     isolation: Option<Vec<ChangeHash>>,
+    context: Option<TransactionContext>,
+    rotation: ActorRotation,
+    /// The unit text indexes are measured in, see [`Self::set_text_unit()`]
+    text_unit: TextUnit,
+}
+
+/// When to rotate the actor ID of an [`AutoCommit`], see [`AutoCommit::rotate_actor_every()`]
+#[derive(Debug, Clone, Default)]
+struct ActorRotation {
+    /// Rotate after this many changes, or never if zero
+    every: usize,
+    /// Whether to record the previous actor ID in the first change made after rotating
+    link: bool,
+    /// The number of changes made by the current actor ID
+    changes: usize,
+    /// The actor ID to record in the next change
+    rotated_from: Option<ActorId>,
 }
 
 /// An autocommit document with an inactive [`PatchLog`]
@@ -77,6 +103,9 @@ impl Default for AutoCommit {
             diff_cache: None,
             save_cursor: Vec::new(),
             isolation: None,
+            context: None,
+            rotation: ActorRotation::default(),
+            text_unit: TextUnit::Native,
         }
     }
 }
@@ -96,6 +125,9 @@ impl AutoCommit {
             diff_cache: None,
             save_cursor: Vec::new(),
             isolation: None,
+            context: None,
+            rotation: ActorRotation::default(),
+            text_unit: TextUnit::Native,
         })
     }
 
@@ -109,6 +141,9 @@ impl AutoCommit {
             diff_cache: None,
             save_cursor: Vec::new(),
             isolation: None,
+            context: None,
+            rotation: ActorRotation::default(),
+            text_unit: TextUnit::Native,
         })
     }
 
@@ -139,6 +174,34 @@ impl AutoCommit {
             diff_cache: None,
             save_cursor: Vec::new(),
             isolation: None,
+            context: None,
+            rotation: ActorRotation::default(),
+            text_unit: TextUnit::Native,
+        })
+    }
+
+    /// See [`Automerge::load_from()`]
+    pub fn load_from<R: io::Read>(reader: R) -> Result<Self, AutomergeError> {
+        Self::load_from_with_options(reader, LoadOptions::default())
+    }
+
+    /// See [`Automerge::load_from_with_options()`]
+    pub fn load_from_with_options<R: io::Read>(
+        reader: R,
+        options: LoadOptions<'_>,
+    ) -> Result<Self, AutomergeError> {
+        let doc = Automerge::load_from_with_options(reader, options)?;
+        Ok(Self {
+            doc,
+            transaction: None,
+            patch_log: PatchLog::inactive(TextRepresentation::default()),
+            diff_cursor: Vec::new(),
+            diff_cache: None,
+            save_cursor: Vec::new(),
+            isolation: None,
+            context: None,
+            rotation: ActorRotation::default(),
+            text_unit: TextUnit::Native,
         })
     }
 
@@ -226,7 +289,8 @@ impl AutoCommit {
         {
             self.patch_log.make_patches(&self.doc)
         } else if range.before().is_empty() && range.after() == heads {
-            let mut patch_log = PatchLog::active(self.patch_log.text_rep());
+            let mut patch_log =
+                PatchLog::active(self.patch_log.text_rep()).with_splices(self.patch_log.splices());
             // This if statement is only active if the current heads are the same as `after`
             // so we don't need to tell the patch log to target a specific heads and consequently
             // it wll be able to generate patches very fast as it doesn't need to make any clocks
@@ -236,11 +300,14 @@ impl AutoCommit {
         } else {
             let before_clock = self.doc.clock_at(range.before());
             let after_clock = self.doc.clock_at(range.after());
-            let mut patch_log = PatchLog::active(self.patch_log.text_rep());
+            let mut patch_log =
+                PatchLog::active(self.patch_log.text_rep()).with_splices(self.patch_log.splices());
             patch_log.heads = Some(range.after().to_vec());
             diff::log_diff(&self.doc, &before_clock, &after_clock, &mut patch_log);
             patch_log.make_patches(&self.doc)
         };
+        let patches =
+            TextUnitTranslator::new(&self.doc, range.before(), self.text_unit).translate(patches);
         self.diff_cache = Some((range, patches));
         self.diff_cache.as_ref().unwrap().1.clone()
     }
@@ -274,9 +341,45 @@ impl AutoCommit {
             diff_cache: None,
             save_cursor: vec![],
             isolation: None,
+            context: None,
+            rotation: ActorRotation::default(),
+            text_unit: self.text_unit,
+        }
+    }
+
+    /// Clone this document, see [`CloneOptions`]
+    ///
+    /// Unlike [`Clone::clone()`] this commits any open transaction first.
+    pub fn clone_with(&mut self, options: CloneOptions) -> Self {
+        self.ensure_transaction_closed();
+        let keep_patch_log = options.keep_patch_log;
+        let copy_caches = options.copy_caches;
+        let mut clone = Self {
+            doc: self.doc.clone_with(options),
+            transaction: None,
+            patch_log: self.patch_log.clone(),
+            diff_cursor: self.diff_cursor.clone(),
+            diff_cache: self.diff_cache.clone(),
+            save_cursor: self.save_cursor.clone(),
+            isolation: self.isolation.clone(),
+            context: self.context.clone(),
+            rotation: self.rotation.clone(),
+            text_unit: self.text_unit,
+        };
+        if !keep_patch_log {
+            clone.patch_log = PatchLog::inactive(self.patch_log.text_rep());
+            clone.diff_cursor = vec![];
+            clone.diff_cache = None;
         }
+        if !copy_caches {
+            clone.diff_cache = None;
+        }
+        clone
     }
 
+    /// See [`Automerge::fork_at()`]
+    ///
+    /// This closes the transaction first, if one is in progress.
     pub fn fork_at(&mut self, heads: &[ChangeHash]) -> Result<Self, AutomergeError> {
         self.ensure_transaction_closed();
         Ok(Self {
@@ -287,6 +390,9 @@ impl AutoCommit {
             diff_cache: None,
             save_cursor: vec![],
             isolation: None,
+            context: None,
+            rotation: ActorRotation::default(),
+            text_unit: self.text_unit,
         })
     }
 
@@ -325,21 +431,156 @@ impl AutoCommit {
         self.isolation = None;
     }
 
+    /// Attach `context` to subsequent changes, or remove it with `None`
+    ///
+    /// The context is passed to the authorizer set with [`Self::set_authorizer()`] for every
+    /// write, so it can be used to attribute and authorize the writes of a particular user. Any
+    /// pending operations are committed first so that they keep the context they were made with.
+    pub fn set_context<C: Any + Send + Sync>(&mut self, context: Option<C>) {
+        self.ensure_transaction_closed();
+        self.context = context.map(|c| Arc::new(c) as TransactionContext);
+    }
+
+    /// Check every local write with `authorizer`, see [`Automerge::set_authorizer()`]
+    pub fn set_authorizer<F>(&mut self, authorizer: F)
+    where
+        F: Fn(&WriteRequest<'_>) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.doc.set_authorizer(authorizer);
+    }
+
+    /// Stop checking local writes, see [`Automerge::clear_authorizer()`]
+    pub fn clear_authorizer(&mut self) {
+        self.doc.clear_authorizer();
+    }
+
+    /// Tell `metrics` about the work this document does, see [`Automerge::set_metrics()`]
+    pub fn set_metrics(&mut self, metrics: Arc<dyn crate::metrics::Metrics>) {
+        self.doc.set_metrics(metrics);
+    }
+
+    /// Stop reporting metrics, see [`Automerge::clear_metrics()`]
+    pub fn clear_metrics(&mut self) {
+        self.doc.clear_metrics();
+    }
+
+    /// Give `sink` a record of every change applied to this document, see
+    /// [`Automerge::set_audit_sink()`]
+    #[cfg(feature = "audit-log")]
+    pub fn set_audit_sink(&mut self, sink: Arc<dyn crate::audit::AuditSink>) {
+        self.doc.set_audit_sink(sink);
+    }
+
+    /// Stop recording applied changes, see [`Automerge::clear_audit_sink()`]
+    #[cfg(feature = "audit-log")]
+    pub fn clear_audit_sink(&mut self) {
+        self.doc.clear_audit_sink();
+    }
+
+    /// The actor, sequence number, start op and dependencies of the change the pending
+    /// operations will be committed as
+    ///
+    /// Returns `None` if there are no pending operations.
+    pub fn pending_change(&self) -> Option<PendingChange> {
+        self.transaction
+            .as_ref()
+            .map(|(_, tx)| tx.pending_change(self.doc.osd()))
+    }
+
     fn ensure_transaction_open(&mut self) {
         if self.transaction.is_none() {
             let args = self.doc.transaction_args(self.isolation.as_deref());
-            let inner = TransactionInner::new(args);
+            let mut inner = TransactionInner::new(args);
+            inner.set_context(self.context.clone());
+            self.link_rotation(&mut inner);
             self.transaction = Some((self.patch_log.branch(), inner))
         }
     }
 
     fn ensure_transaction_closed(&mut self) {
         if let Some((patch_log, tx)) = self.transaction.take() {
-            self.patch_log.merge(patch_log);
             let hash = tx.commit(&mut self.doc, None, None);
+            // a transaction which violated the schema was rolled back
+            if hash.is_some() {
+                self.patch_log.merge(patch_log);
+            }
             if self.isolation.is_some() && hash.is_some() {
                 self.isolation = hash.map(|h| vec![h])
             }
+            self.count_change(hash);
+        }
+    }
+
+    /// Rotate to a new random actor ID after every `changes` changes, or stop rotating if
+    /// `changes` is zero
+    ///
+    /// Rotating the actor ID makes it harder to tell which changes in the history of a document
+    /// were made by the same device. The new actor ID is not linked to the old one unless
+    /// [`Self::link_actor_rotations()`] is enabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = AutoCommit::new();
+    /// doc.rotate_actor_every(2);
+    /// for i in 0..4 {
+    ///     doc.put(ROOT, "count", i).unwrap();
+    ///     doc.commit();
+    /// }
+    /// let actors = doc.get_changes(&[]).iter().map(|c| c.actor_id().clone()).collect::<Vec<_>>();
+    /// assert_eq!(actors[0], actors[1]);
+    /// assert_ne!(actors[1], actors[2]);
+    /// assert_eq!(actors[2], actors[3]);
+    /// ```
+    pub fn rotate_actor_every(&mut self, changes: usize) -> &mut Self {
+        self.rotation.every = changes;
+        self
+    }
+
+    /// Whether to record the previous actor ID in the first change made after the actor ID is
+    /// rotated
+    ///
+    /// The previous actor ID can be read with [`Change::rotated_from()`]. This is off by default,
+    /// as linking rotations lets anyone with the document connect the changes made by each
+    /// actor ID.
+    pub fn link_actor_rotations(&mut self, link: bool) -> &mut Self {
+        self.rotation.link = link;
+        self
+    }
+
+    /// Commit any open transaction and switch to a new random actor ID, for example at the
+    /// start of each session, see [`Self::rotate_actor_every()`]
+    pub fn rotate_actor(&mut self) -> &mut Self {
+        self.ensure_transaction_closed();
+        self.rotate_actor_now();
+        self
+    }
+
+    fn rotate_actor_now(&mut self) {
+        let previous = self.doc.get_actor().clone();
+        self.doc.set_actor(ActorId::random());
+        self.rotation.changes = 0;
+        if self.rotation.link && self.rotation.rotated_from.is_none() {
+            self.rotation.rotated_from = Some(previous);
+        }
+    }
+
+    fn link_rotation(&self, tx: &mut TransactionInner) {
+        if let Some(previous) = &self.rotation.rotated_from {
+            tx.set_extra_field(Field::RotatedFrom, previous.to_bytes().to_vec());
+        }
+    }
+
+    fn count_change(&mut self, hash: Option<ChangeHash>) {
+        if hash.is_none() {
+            return;
+        }
+        self.rotation.rotated_from = None;
+        self.rotation.changes += 1;
+        if self.rotation.every != 0 && self.rotation.changes >= self.rotation.every {
+            self.rotate_actor_now();
         }
     }
 
@@ -375,16 +616,132 @@ impl AutoCommit {
         }
     }
 
+    /// Like [`Self::apply_changes()`] but check `cancellation` before applying each change, see
+    /// [`Automerge::apply_changes_cancellable()`]
+    pub fn apply_changes_cancellable(
+        &mut self,
+        changes: impl IntoIterator<Item = Change>,
+        cancellation: &CancellationToken,
+    ) -> Result<(), AutomergeError> {
+        self.ensure_transaction_closed();
+        if self.isolation.is_some() {
+            self.doc
+                .apply_changes_inner(changes, &mut PatchLog::null(), Some(cancellation))
+        } else {
+            self.doc
+                .apply_changes_inner(changes, &mut self.patch_log, Some(cancellation))
+        }
+    }
+
     /// Takes all the changes in `other` which are not in `self` and applies them
     pub fn merge(&mut self, other: &mut AutoCommit) -> Result<Vec<ChangeHash>, AutomergeError> {
+        other.ensure_transaction_closed();
+        self.merge_doc(&other.doc)
+    }
+
+    /// Takes all the committed changes in `other` which are not in `self` and applies them
+    ///
+    /// Unlike [`Self::merge()`] this does not commit the open transaction of `other`, so it only
+    /// needs a shared reference to `other`. Any operations in that transaction are not merged.
+    pub fn merge_read(&mut self, other: &AutoCommit) -> Result<Vec<ChangeHash>, AutomergeError> {
+        self.merge_doc(&other.doc)
+    }
+
+    /// Takes all the changes in `other` which are not in `self` and applies them, see
+    /// [`Self::merge_read()`]
+    pub fn merge_doc(&mut self, other: &Automerge) -> Result<Vec<ChangeHash>, AutomergeError> {
+        self.ensure_transaction_closed();
+        if self.isolation.is_some() {
+            self.doc.merge_and_log_patches(other, &mut PatchLog::null())
+        } else {
+            self.doc.merge_and_log_patches(other, &mut self.patch_log)
+        }
+    }
+
+    /// See [`Automerge::apply_changes_from()`]
+    pub fn apply_changes_from<R: std::io::Read>(
+        &mut self,
+        reader: R,
+    ) -> Result<usize, AutomergeError> {
+        self.apply_changes_streamed(ChangeReader::new(reader))
+    }
+
+    /// See [`Automerge::apply_changes_iter()`]
+    pub fn apply_changes_iter<I: IntoIterator<Item = Change>>(
+        &mut self,
+        changes: I,
+    ) -> Result<(), AutomergeError> {
+        self.apply_changes_streamed(changes.into_iter().map(Ok))?;
+        Ok(())
+    }
+
+    fn apply_changes_streamed<I>(&mut self, changes: I) -> Result<usize, AutomergeError>
+    where
+        I: IntoIterator<Item = Result<Change, AutomergeError>>,
+    {
         self.ensure_transaction_closed();
+        if self.isolation.is_some() {
+            self.doc
+                .apply_changes_streamed(changes, &mut PatchLog::null())
+        } else {
+            self.doc
+                .apply_changes_streamed(changes, &mut self.patch_log)
+        }
+    }
+
+    /// See [`Automerge::merge_with()`]
+    ///
+    /// While the document is isolated (see [`Self::isolate()`]) the merged changes are not
+    /// visible, so no patches are reported.
+    pub fn merge_with<O: PatchObserver + ?Sized>(
+        &mut self,
+        other: &mut AutoCommit,
+        observer: &mut O,
+    ) -> Result<Vec<ChangeHash>, AutomergeError> {
         other.ensure_transaction_closed();
+        let changes = self
+            .doc
+            .get_changes_added(&other.doc)
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        self.apply_changes_with(changes, observer)?;
+        Ok(self.doc.get_heads())
+    }
+
+    /// See [`Automerge::apply_changes_with()`] and [`Self::merge_with()`]
+    pub fn apply_changes_with<I, O>(
+        &mut self,
+        changes: I,
+        observer: &mut O,
+    ) -> Result<(), AutomergeError>
+    where
+        I: IntoIterator<Item = Change>,
+        O: PatchObserver + ?Sized,
+    {
+        self.ensure_transaction_closed();
+        if self.isolation.is_some() {
+            self.doc
+                .apply_changes_log_patches(changes, &mut PatchLog::null())
+        } else {
+            self.doc
+                .apply_changes_observed(changes, observer, &mut self.patch_log)
+        }
+    }
+
+    /// See [`Automerge::merge_with_report()`]
+    pub fn merge_with_report(
+        &mut self,
+        other: &mut AutoCommit,
+    ) -> Result<MergeReport, AutomergeError> {
+        other.ensure_transaction_closed();
+        self.ensure_transaction_closed();
         if self.isolation.is_some() {
             self.doc
-                .merge_and_log_patches(&mut other.doc, &mut PatchLog::null())
+                .merge_with_report_and_log_patches(&other.doc, &mut PatchLog::null())
         } else {
             self.doc
-                .merge_and_log_patches(&mut other.doc, &mut self.patch_log)
+                .merge_with_report_and_log_patches(&other.doc, &mut self.patch_log)
         }
     }
 
@@ -393,6 +750,18 @@ impl AutoCommit {
         self.save_with_options(SaveOptions::default())
     }
 
+    /// See [`Automerge::seal()`]
+    pub fn seal(&mut self) -> Vec<u8> {
+        self.ensure_transaction_closed();
+        self.doc.seal()
+    }
+
+    /// See [`Automerge::seal_signed()`]
+    pub fn seal_signed<F: FnOnce(&[u8]) -> Vec<u8>>(&mut self, sign: F) -> Vec<u8> {
+        self.ensure_transaction_closed();
+        self.doc.seal_signed(sign)
+    }
+
     pub fn save_with_options(&mut self, options: SaveOptions) -> Vec<u8> {
         self.ensure_transaction_closed();
         let bytes = self.doc.save_with_options(options);
@@ -402,6 +771,29 @@ impl AutoCommit {
         bytes
     }
 
+    /// See [`Automerge::save_to()`]
+    ///
+    /// This closes the transaction first, if one is in progress.
+    pub fn save_to<W: io::Write>(&mut self, out: W) -> io::Result<usize> {
+        self.save_to_with_options(SaveOptions::default(), out)
+    }
+
+    /// See [`Automerge::save_to_with_options()`]
+    ///
+    /// This closes the transaction first, if one is in progress.
+    pub fn save_to_with_options<W: io::Write>(
+        &mut self,
+        options: SaveOptions,
+        out: W,
+    ) -> io::Result<usize> {
+        self.ensure_transaction_closed();
+        let written = self.doc.save_to_with_options(options, out)?;
+        if written > 0 {
+            self.save_cursor = self.doc.get_heads()
+        }
+        Ok(written)
+    }
+
     /// Save the document and attempt to load it before returning - slow!
     pub fn save_and_verify(&mut self) -> Result<Vec<u8>, AutomergeError> {
         let bytes = self.save();
@@ -409,6 +801,17 @@ impl AutoCommit {
         Ok(bytes)
     }
 
+    /// Save the entirety of this document so that the same history always produces the same
+    /// bytes, see [`Automerge::save_canonical()`]
+    ///
+    /// This closes the transaction first, if one is in progress.
+    pub fn save_canonical(&mut self) -> Vec<u8> {
+        self.save_with_options(SaveOptions {
+            canonical: true,
+            ..Default::default()
+        })
+    }
+
     /// Save this document, but don't run it through DEFLATE afterwards
     pub fn save_nocompress(&mut self) -> Vec<u8> {
         self.save_with_options(SaveOptions {
@@ -449,16 +852,204 @@ impl AutoCommit {
         self.doc.get_last_local_change()
     }
 
+    /// See [`Automerge::conflicts()`]
+    pub fn conflicts(&self) -> Conflicts<'_> {
+        self.doc.conflicts()
+    }
+
+    /// See [`Automerge::conflicts_in()`]
+    pub fn conflicts_in<O: AsRef<ExId>>(&self, obj: O) -> Result<Conflicts<'_>, AutomergeError> {
+        self.doc.conflicts_in(obj)
+    }
+
+    /// See [`Automerge::set_blob_store()`]
+    pub fn set_blob_store(&mut self, store: Arc<dyn crate::blob::BlobStore>) {
+        self.doc.set_blob_store(store);
+    }
+
+    /// See [`Automerge::clear_blob_store()`]
+    pub fn clear_blob_store(&mut self) {
+        self.doc.clear_blob_store();
+    }
+
+    /// See [`Automerge::store_blob()`]
+    pub fn store_blob(&self, data: &[u8]) -> Result<crate::blob::BlobRef, crate::blob::BlobError> {
+        self.doc.store_blob(data)
+    }
+
+    /// See [`Automerge::read_blob()`]
+    pub fn read_blob(
+        &self,
+        blob: &crate::blob::BlobRef,
+    ) -> Result<Vec<u8>, crate::blob::BlobError> {
+        self.doc.read_blob(blob)
+    }
+
+    /// See [`Automerge::set_link_resolver()`]
+    pub fn set_link_resolver(&mut self, resolver: Arc<dyn crate::link::LinkResolver>) {
+        self.doc.set_link_resolver(resolver);
+    }
+
+    /// See [`Automerge::clear_link_resolver()`]
+    pub fn clear_link_resolver(&mut self) {
+        self.doc.clear_link_resolver();
+    }
+
+    /// See [`Automerge::resolve_link()`]
+    pub fn resolve_link(
+        &self,
+        link: &crate::link::DocId,
+    ) -> Result<Automerge, crate::link::LinkError> {
+        self.doc.resolve_link(link)
+    }
+
+    /// See [`Automerge::set_schema()`]
+    pub fn set_schema<S: Into<crate::schema::Schema>>(&mut self, schema: S) {
+        self.doc.set_schema(schema);
+    }
+
+    /// See [`Automerge::clear_schema()`]
+    pub fn clear_schema(&mut self) {
+        self.doc.clear_schema();
+    }
+
+    /// See [`Automerge::schema()`]
+    pub fn schema(&self) -> Option<&crate::schema::Schema> {
+        self.doc.schema()
+    }
+
+    /// See [`Automerge::set_schema_policy()`]
+    pub fn set_schema_policy<F>(&mut self, policy: F)
+    where
+        F: Fn(&Change, &crate::schema::SchemaViolation) -> crate::schema::SchemaAction
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.doc.set_schema_policy(policy);
+    }
+
+    /// See [`Automerge::clear_schema_policy()`]
+    pub fn clear_schema_policy(&mut self) {
+        self.doc.clear_schema_policy();
+    }
+
+    /// The hashes of changes received from other peers which violated the schema, see
+    /// [`Automerge::schema_violations()`]
+    pub fn schema_violations(&self) -> &[ChangeHash] {
+        self.doc.schema_violations()
+    }
+
+    /// Return and clear the list of changes which violated the schema
+    pub fn take_schema_violations(&mut self) -> Vec<ChangeHash> {
+        self.doc.take_schema_violations()
+    }
+
+    /// See [`Automerge::set_conflict_policy()`]
+    pub fn set_conflict_policy(&mut self, policy: ConflictPolicy) -> &mut Self {
+        self.doc.set_conflict_policy(policy);
+        self
+    }
+
+    /// See [`Automerge::conflict_policy()`]
+    pub fn conflict_policy(&self) -> &ConflictPolicy {
+        self.doc.conflict_policy()
+    }
+
+    /// See [`Automerge::set_actor_info()`]
+    pub fn set_actor_info(&mut self, info: &ActorInfo) -> ChangeHash {
+        self.ensure_transaction_closed();
+        self.doc.set_actor_info(info)
+    }
+
+    /// See [`Automerge::actor_info()`]
+    pub fn actor_info(&self, actor: &ActorId) -> Option<ActorInfo> {
+        self.doc.actor_info(actor)
+    }
+
+    /// See [`Automerge::actor_infos()`]
+    pub fn actor_infos(&self) -> BTreeMap<ActorId, ActorInfo> {
+        self.doc.actor_infos()
+    }
+
+    /// See [`Automerge::last_modified()`]
+    ///
+    /// Any outstanding operations are committed first, so that they belong to a change.
+    pub fn last_modified<O: AsRef<ExId>, P: Into<Prop>>(
+        &mut self,
+        obj: O,
+        prop: P,
+    ) -> Result<Option<ChangeSummary<'_>>, AutomergeError> {
+        self.ensure_transaction_closed();
+        self.doc.last_modified(obj, prop)
+    }
+
+    /// See [`Automerge::get_change_by_actor_seq()`]
+    pub fn get_change_by_actor_seq(&mut self, actor: &ActorId, seq: u64) -> Option<&Change> {
+        self.ensure_transaction_closed();
+        self.doc.get_change_by_actor_seq(actor, seq)
+    }
+
     pub fn get_changes(&mut self, have_deps: &[ChangeHash]) -> Vec<&Change> {
         self.ensure_transaction_closed();
         self.doc.get_changes(have_deps)
     }
 
+    /// See [`Automerge::history()`]
+    ///
+    /// This closes the transaction first, if one is in progress.
+    pub fn history(&mut self) -> History<'_> {
+        self.ensure_transaction_closed();
+        self.doc.history()
+    }
+
+    /// See [`Automerge::history_at()`]
+    ///
+    /// This closes the transaction first, if one is in progress.
+    pub fn history_at(&mut self, heads: &[ChangeHash]) -> History<'_> {
+        self.ensure_transaction_closed();
+        self.doc.history_at(heads)
+    }
+
+    /// See [`Automerge::handle()`]
+    ///
+    /// Pending operations are visible through the handle.
+    pub fn handle<O: AsRef<ExId>>(&self, obj: O) -> Result<ObjHandle<'_>, AutomergeError> {
+        ObjHandle::new(&self.doc, obj.as_ref(), self.get_scope(None))
+    }
+
+    /// See [`Automerge::checkout()`]
+    ///
+    /// This closes the transaction first, if one is in progress.
+    pub fn checkout(&mut self, heads: &[ChangeHash]) -> Result<Checkout<'_>, AutomergeError> {
+        self.ensure_transaction_closed();
+        self.doc.checkout(heads)
+    }
+
     pub fn get_change_by_hash(&mut self, hash: &ChangeHash) -> Option<&Change> {
         self.ensure_transaction_closed();
         self.doc.get_change_by_hash(hash)
     }
 
+    /// Like [`Self::get_changes()`] but without committing the pending transaction, so the
+    /// pending operations are not included
+    ///
+    /// This and the other `committed_` methods only need a shared reference to the document.
+    /// The [`ReadDoc`] methods never commit the pending transaction either.
+    pub fn committed_changes(&self, have_deps: &[ChangeHash]) -> Vec<&Change> {
+        self.doc.get_changes(have_deps)
+    }
+
+    /// Like [`Self::get_change_by_actor_seq()`] but without committing the pending transaction
+    pub fn committed_change_by_actor_seq(&self, actor: &ActorId, seq: u64) -> Option<&Change> {
+        self.doc.get_change_by_actor_seq(actor, seq)
+    }
+
+    /// Like [`Self::history()`] but without committing the pending transaction
+    pub fn committed_history(&self) -> History<'_> {
+        self.doc.history()
+    }
+
     /// Get changes in `other` that are not in `self`
     pub fn get_changes_added<'a>(&mut self, other: &'a mut Self) -> Vec<&'a Change> {
         self.ensure_transaction_closed();
@@ -493,6 +1084,62 @@ impl AutoCommit {
         self.doc.visualise_optree(objects)
     }
 
+    /// Whether this document and `other` have the same content, see
+    /// [`Automerge::equals_content()`]
+    ///
+    /// Pending operations are included, this does not close the transaction.
+    pub fn equals_content<R: ReadDoc>(&self, other: &R) -> bool {
+        self.doc.equals_content(other)
+    }
+
+    /// Every place where the content of this document and `other` differs, see
+    /// [`Automerge::content_differences()`]
+    pub fn content_differences<R: ReadDoc>(&self, other: &R) -> Vec<ContentDifference> {
+        self.doc.content_differences(other)
+    }
+
+    /// A hash of the current content of the document which does not depend on its history, see
+    /// [`Automerge::state_hash()`]
+    ///
+    /// Pending operations are included, this does not close the transaction.
+    pub fn state_hash(&self) -> StateHash {
+        self.doc.state_hash()
+    }
+
+    /// Create a new document whose root has the content of the map `obj` in this document, see
+    /// [`Automerge::extract()`]
+    ///
+    /// Pending operations are included, this does not close the transaction.
+    pub fn extract(&self, obj: &ExId) -> Result<AutoCommit, AutomergeError> {
+        let doc = self.doc.extract(obj)?;
+        Ok(Self {
+            doc,
+            transaction: None,
+            patch_log: PatchLog::inactive(self.patch_log.text_rep()),
+            diff_cursor: Vec::new(),
+            diff_cache: None,
+            save_cursor: Vec::new(),
+            isolation: None,
+            context: None,
+            rotation: ActorRotation::default(),
+            text_unit: self.text_unit,
+        })
+    }
+
+    /// Return structured diagnostics about the op trees of the document, see
+    /// [`Automerge::optree_stats()`]
+    pub fn optree_stats(&self, objects: Option<Vec<ExId>>) -> crate::op_tree::OpTreeStats {
+        self.doc.optree_stats(objects)
+    }
+
+    /// Return a graphviz representation of the graph of changes in this document, see
+    /// [`Automerge::visualise_change_graph()`]
+    ///
+    /// Changes in the transaction which is in progress are not included.
+    pub fn visualise_change_graph(&self) -> String {
+        self.doc.visualise_change_graph()
+    }
+
     /// Get the current heads of the document.
     ///
     /// This closes the transaction first, if one is in progress.
@@ -505,6 +1152,18 @@ impl AutoCommit {
         }
     }
 
+    /// Get the heads of the document without committing the transaction in progress
+    ///
+    /// Unlike [`Self::get_heads()`] this does not split the pending operations into a separate
+    /// change, so it can be called as often as needed. The pending operations are not covered by
+    /// the heads returned.
+    pub fn committed_heads(&self) -> Vec<ChangeHash> {
+        match &self.isolation {
+            Some(heads) => heads.clone(),
+            None => self.doc.get_heads(),
+        }
+    }
+
     pub fn set_text_rep(&mut self, text_rep: TextRepresentation) {
         self.patch_log.set_text_rep(text_rep)
     }
@@ -518,6 +1177,41 @@ impl AutoCommit {
         self
     }
 
+    /// Measure the indexes of text objects in `unit` throughout this document
+    ///
+    /// This applies to [`Self::splice_text()`], [`ReadDoc::length()`] and [`ReadDoc::length_at()`]
+    /// of text objects and to the indexes in the text patches returned by [`Self::diff()`] and
+    /// [`Self::diff_incremental()`], which are translated as by [`TextUnitTranslator`]. Splicing
+    /// with [`TextUnit::Grapheme`] stores one grapheme cluster per sequence element, so concurrent
+    /// edits cannot split them. The other methods which take an index, such as marks, cursors and
+    /// [`Self::splice()`], keep using [`TextUnit::Native`], and any other unit requires reading
+    /// the whole text to translate an index.
+    ///
+    /// The unit is not saved with the document, it is kept by [`Self::fork()`] and clones.
+    pub fn set_text_unit(&mut self, unit: TextUnit) -> &mut Self {
+        self.text_unit = unit;
+        self.diff_cache = None;
+        self
+    }
+
+    /// The unit text indexes are measured in, see [`Self::set_text_unit()`]
+    pub fn text_unit(&self) -> TextUnit {
+        self.text_unit
+    }
+
+    /// See [`Self::set_text_unit()`]
+    pub fn with_text_unit(mut self, unit: TextUnit) -> Self {
+        self.set_text_unit(unit);
+        self
+    }
+
+    /// Whether the patches returned by [`Self::diff()`] and [`Self::diff_incremental()`] report
+    /// edits to lists as [`crate::PatchAction::Splice`], see [`PatchLog::with_splices()`]
+    pub fn set_splice_patches(&mut self, splices: bool) {
+        self.patch_log.set_splices(splices);
+        self.diff_cache = None;
+    }
+
     /// Commit any uncommitted changes
     ///
     /// Returns [`None`] if there were no operations to commit
@@ -543,15 +1237,41 @@ impl AutoCommit {
     /// doc.commit_with(CommitOptions::default().with_message("Create todos list").with_time(now));
     /// ```
     pub fn commit_with(&mut self, options: CommitOptions) -> Option<ChangeHash> {
+        self.try_commit_with(options).unwrap_or_else(|e| {
+            tracing::warn!(error=%e, "rolled back transaction");
+            None
+        })
+    }
+
+    /// Commit any uncommitted changes, failing with [`AutomergeError::SchemaViolation`] if they
+    /// leave the document in a state its schema does not allow
+    ///
+    /// See [`Automerge::set_schema()`]. The uncommitted changes are rolled back if this fails.
+    /// [`Self::commit()`], and the implicit commit before most other operations, roll them back
+    /// too but do not say why.
+    pub fn try_commit(&mut self) -> Result<Option<ChangeHash>, AutomergeError> {
+        self.try_commit_with(CommitOptions::default())
+    }
+
+    /// Like [`Self::commit_with()`] but fail if the changes violate the schema, see
+    /// [`Self::try_commit()`]
+    pub fn try_commit_with(
+        &mut self,
+        options: CommitOptions,
+    ) -> Result<Option<ChangeHash>, AutomergeError> {
         // ensure that even no changes triggers a change
         self.ensure_transaction_open();
-        let (patch_log, tx) = self.transaction.take().unwrap();
+        let (patch_log, mut tx) = self.transaction.take().unwrap();
+        if let Some(extra_bytes) = options.extra_bytes {
+            tx.set_extra_bytes(extra_bytes);
+        }
+        let hash = tx.try_commit(&mut self.doc, options.message, options.time)?;
         self.patch_log.merge(patch_log);
-        let hash = tx.commit(&mut self.doc, options.message, options.time);
         if self.isolation.is_some() && hash.is_some() {
             self.isolation = hash.map(|h| vec![h])
         }
-        hash
+        self.count_change(hash);
+        Ok(hash)
     }
 
     /// Remove any changes that have been made in the current transaction from the document
@@ -562,6 +1282,65 @@ impl AutoCommit {
             .unwrap_or(0)
     }
 
+    /// Run `f` in a transaction of its own, committing the transaction if `f` succeeds and
+    /// rolling it back if `f` returns an error
+    ///
+    /// Operations which are pending when this is called are committed first so that they are
+    /// not rolled back. Anything `f` commits itself, for example by calling [`Self::commit()`],
+    /// is not rolled back either.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, AutomergeError, ObjType, ReadDoc, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = AutoCommit::new();
+    /// let result = doc.transact(|doc| {
+    ///     let todos = doc.put_object(ROOT, "todos", ObjType::List)?;
+    ///     doc.insert(&todos, 0, "buy milk")?;
+    ///     doc.insert(&todos, 5, "buy eggs")
+    /// });
+    /// assert_eq!(result, Err(AutomergeError::InvalidIndex(5)));
+    /// assert_eq!(doc.get(ROOT, "todos").unwrap(), None);
+    /// ```
+    pub fn transact<F, O, E>(&mut self, f: F) -> Result<O, E>
+    where
+        F: FnOnce(&mut Self) -> Result<O, E>,
+    {
+        self.ensure_transaction_closed();
+        match f(self) {
+            Ok(result) => {
+                self.commit();
+                Ok(result)
+            }
+            Err(e) => {
+                self.rollback();
+                Err(e)
+            }
+        }
+    }
+
+    /// Mark the current point in the pending transaction, see
+    /// [`crate::transaction::Transaction::savepoint()`]
+    ///
+    /// Anything which commits the pending transaction, including reading the heads or saving
+    /// the document, invalidates the savepoint.
+    pub fn savepoint(&mut self) -> Savepoint {
+        self.ensure_transaction_open();
+        let (patch_log, tx) = self.transaction.as_mut().unwrap();
+        tx.savepoint(patch_log)
+    }
+
+    /// Undo the operations added since `savepoint` was created, see
+    /// [`crate::transaction::Transaction::rollback_to()`]
+    pub fn rollback_to(&mut self, savepoint: Savepoint) -> Result<usize, AutomergeError> {
+        let (patch_log, tx) = self
+            .transaction
+            .as_mut()
+            .ok_or(AutomergeError::InvalidSavepoint)?;
+        tx.rollback_to(&mut self.doc, patch_log, savepoint)
+    }
+
     /// Generate an empty change
     ///
     /// The main reason to do this is if you wish to create a "merge commit" which has all the
@@ -574,7 +1353,32 @@ impl AutoCommit {
     pub fn empty_change(&mut self, options: CommitOptions) -> ChangeHash {
         self.ensure_transaction_closed();
         let args = self.doc.transaction_args(None);
-        TransactionInner::empty(&mut self.doc, args, options.message, options.time)
+        let mut tx = TransactionInner::new(args);
+        self.link_rotation(&mut tx);
+        if let Some(extra_bytes) = options.extra_bytes {
+            tx.set_extra_bytes(extra_bytes);
+        }
+        let hash = tx.commit_impl(&mut self.doc, options.message, options.time);
+        self.count_change(Some(hash));
+        hash
+    }
+
+    /// See [`Automerge::tag()`]
+    ///
+    /// Any outstanding operations are committed first, so they are included in the tagged heads.
+    pub fn tag(&mut self, name: &str) -> ChangeHash {
+        self.ensure_transaction_closed();
+        self.doc.tag(name)
+    }
+
+    /// See [`Automerge::heads_for_tag()`]
+    pub fn heads_for_tag(&self, name: &str) -> Option<Vec<ChangeHash>> {
+        self.doc.heads_for_tag(name)
+    }
+
+    /// See [`Automerge::tags()`]
+    pub fn tags(&self) -> BTreeMap<String, Vec<ChangeHash>> {
+        self.doc.tags()
     }
 
     /// An implementation of [`crate::sync::SyncDoc`] for this autocommit
@@ -596,6 +1400,137 @@ impl AutoCommit {
         self.doc.hash_for_opid(opid)
     }
 
+    /// Freeze the object `obj` and everything beneath it, see [`Automerge::freeze()`]
+    pub fn freeze<O: AsRef<ExId>>(&mut self, obj: O) -> Result<(), AutomergeError> {
+        self.doc.freeze(obj)
+    }
+
+    /// Remove an object previously frozen with [`Self::freeze()`], see [`Automerge::unfreeze()`]
+    pub fn unfreeze<O: AsRef<ExId>>(&mut self, obj: O) -> Result<bool, AutomergeError> {
+        self.doc.unfreeze(obj)
+    }
+
+    /// Whether `obj`, or any object containing it, is frozen
+    pub fn is_frozen<O: AsRef<ExId>>(&self, obj: O) -> bool {
+        self.doc.is_frozen(obj)
+    }
+
+    /// The hashes of changes received from other peers which modified a frozen object
+    pub fn frozen_violations(&self) -> &[ChangeHash] {
+        self.doc.frozen_violations()
+    }
+
+    /// Return and clear the list of changes which modified a frozen object
+    pub fn take_frozen_violations(&mut self) -> Vec<ChangeHash> {
+        self.doc.take_frozen_violations()
+    }
+
+    /// Hold `changes` in the quarantine rather than applying them, see
+    /// [`Automerge::quarantine_changes()`]
+    pub fn quarantine_changes(&mut self, changes: impl IntoIterator<Item = Change>) {
+        self.doc.quarantine_changes(changes)
+    }
+
+    /// Whether the change with hash `hash` is in the quarantine
+    pub fn is_quarantined(&self, hash: &ChangeHash) -> bool {
+        self.doc.is_quarantined(hash)
+    }
+
+    /// The changes currently held in the quarantine
+    pub fn quarantined_changes(&self) -> &[Change] {
+        self.doc.quarantined_changes()
+    }
+
+    /// See [`Automerge::get_ref()`]
+    pub fn get_ref<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Option<(ValueRef<'_>, ExId)>, AutomergeError> {
+        self.doc
+            .get_ref_for(obj.as_ref(), prop.into(), self.get_scope(None))
+    }
+
+    /// See [`Automerge::values_ref()`]
+    pub fn values_ref<O: AsRef<ExId>>(&self, obj: O) -> ValuesRef<'_> {
+        self.doc.values_ref_for(obj.as_ref(), self.get_scope(None))
+    }
+
+    /// See [`Automerge::set_compact_history()`]
+    pub fn set_compact_history(&mut self, compact: bool) -> &mut Self {
+        self.doc.set_compact_history(compact);
+        self
+    }
+
+    /// See [`Automerge::compact_history()`]
+    ///
+    /// This closes the transaction first, if one is in progress.
+    pub fn compact_history(&mut self) {
+        self.ensure_transaction_closed();
+        self.doc.compact_history();
+    }
+
+    /// See [`Automerge::resident_history_bytes()`]
+    pub fn resident_history_bytes(&self) -> usize {
+        self.doc.resident_history_bytes()
+    }
+
+    /// See [`Automerge::set_track_conflicts()`]
+    pub fn set_track_conflicts(&mut self, track: bool) -> &mut Self {
+        self.doc.set_track_conflicts(track);
+        self
+    }
+
+    /// See [`Automerge::conflict_stats()`]
+    pub fn conflict_stats(&self) -> &ConflictStats {
+        self.doc.conflict_stats()
+    }
+
+    /// See [`Automerge::take_conflict_stats()`]
+    pub fn take_conflict_stats(&mut self) -> ConflictStats {
+        self.doc.take_conflict_stats()
+    }
+
+    /// See [`Automerge::set_deps_strategy()`]
+    pub fn set_deps_strategy(&mut self, strategy: DepsStrategy) -> &mut Self {
+        self.doc.set_deps_strategy(strategy);
+        self
+    }
+
+    /// See [`Automerge::set_quarantine_frozen_violations()`]
+    pub fn set_quarantine_frozen_violations(&mut self, quarantine: bool) -> &mut Self {
+        self.doc.set_quarantine_frozen_violations(quarantine);
+        self
+    }
+
+    /// Remove the changes with the given hashes from the quarantine and apply them, see
+    /// [`Automerge::accept_quarantined()`]
+    pub fn accept_quarantined(&mut self, hashes: &[ChangeHash]) -> Result<(), AutomergeError> {
+        self.ensure_transaction_closed();
+        if self.isolation.is_some() {
+            self.doc
+                .accept_quarantined_log_patches(hashes, &mut PatchLog::null())
+        } else {
+            self.doc
+                .accept_quarantined_log_patches(hashes, &mut self.patch_log)
+        }
+    }
+
+    /// Remove the changes with the given hashes from the quarantine without applying them
+    pub fn reject_quarantined(&mut self, hashes: &[ChangeHash]) -> Vec<Change> {
+        self.doc.reject_quarantined(hashes)
+    }
+
+    /// Encode the quarantined changes, see [`Automerge::save_quarantine()`]
+    pub fn save_quarantine(&self) -> Vec<u8> {
+        self.doc.save_quarantine()
+    }
+
+    /// Restore changes saved with [`Self::save_quarantine()`] to the quarantine
+    pub fn load_quarantine(&mut self, data: &[u8]) -> Result<usize, AutomergeError> {
+        self.doc.load_quarantine(data)
+    }
+
     fn get_scope(&self, heads: Option<&[ChangeHash]>) -> Option<Clock> {
         // heads arg takes priority
         if let Some(h) = heads {
@@ -610,6 +1545,17 @@ impl AutoCommit {
         }
     }
 
+    /// Like [`Self::get_scope()`] with explicit heads, but uses the cached indexes of the op
+    /// tree when `heads` are the current heads of the document. This is only possible when there
+    /// is no open transaction, as the ops of an open transaction are not covered by the heads.
+    fn get_index_scope(&self, heads: &[ChangeHash]) -> Option<Clock> {
+        if self.transaction.is_none() {
+            self.doc.scope_at(heads)
+        } else {
+            Some(self.doc.clock_at(heads))
+        }
+    }
+
     fn patch_to(&mut self, after: &[ChangeHash]) {
         // we may be isolated so we dont use self.doc.get_heads()
         let before = self.get_heads();
@@ -696,13 +1642,47 @@ impl ReadDoc for AutoCommit {
             .values_for(obj.as_ref(), self.get_scope(Some(heads)))
     }
 
+    fn map_entries_all<O: AsRef<ExId>>(&self, obj: O) -> MapEntriesAll<'_> {
+        self.doc
+            .map_entries_all_for(obj.as_ref(), self.get_scope(None))
+    }
+
+    fn map_entries_all_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> MapEntriesAll<'_> {
+        self.doc
+            .map_entries_all_for(obj.as_ref(), self.get_scope(Some(heads)))
+    }
+
     fn length<O: AsRef<ExId>>(&self, obj: O) -> usize {
-        self.doc.length_for(obj.as_ref(), self.get_scope(None))
+        self.length_in(obj, self.text_unit)
     }
 
     fn length_at<O: AsRef<ExId>>(&self, obj: O, heads: &[ChangeHash]) -> usize {
-        self.doc
-            .length_for(obj.as_ref(), self.get_scope(Some(heads)))
+        self.length_in_at(obj, self.text_unit, heads)
+    }
+
+    fn length_in<O: AsRef<ExId>>(&self, obj: O, unit: TextUnit) -> usize {
+        match self.object_type(obj.as_ref()) {
+            Ok(ObjType::Text) if unit != TextUnit::Native => {
+                self.text(obj).map(|text| unit.len(&text)).unwrap_or(0)
+            }
+            _ => self.doc.length_for(obj.as_ref(), self.get_scope(None)),
+        }
+    }
+
+    fn length_in_at<O: AsRef<ExId>>(&self, obj: O, unit: TextUnit, heads: &[ChangeHash]) -> usize {
+        match self.object_type(obj.as_ref()) {
+            Ok(ObjType::Text) if unit != TextUnit::Native => self
+                .text_at(obj, heads)
+                .map(|text| unit.len(&text))
+                .unwrap_or(0),
+            _ => self
+                .doc
+                .length_for(obj.as_ref(), self.get_index_scope(heads)),
+        }
     }
 
     fn object_type<O: AsRef<ExId>>(&self, obj: O) -> Result<ObjType, AutomergeError> {
@@ -744,6 +1724,19 @@ impl ReadDoc for AutoCommit {
         self.doc.text_for(obj.as_ref(), self.get_scope(Some(heads)))
     }
 
+    fn text_chars<O: AsRef<ExId>>(&self, obj: O) -> Result<TextChars<'_>, AutomergeError> {
+        self.doc.text_chars_for(obj.as_ref(), self.get_scope(None))
+    }
+
+    fn text_chars_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<TextChars<'_>, AutomergeError> {
+        self.doc
+            .text_chars_for(obj.as_ref(), self.get_scope(Some(heads)))
+    }
+
     fn spans<O: AsRef<ExId>>(&self, obj: O) -> Result<Spans<'_>, AutomergeError> {
         self.doc.spans_for(obj.as_ref(), self.get_scope(None))
     }
@@ -801,7 +1794,7 @@ impl ReadDoc for AutoCommit {
         heads: &[ChangeHash],
     ) -> Result<Option<(Value<'_>, ExId)>, AutomergeError> {
         self.doc
-            .get_for(obj.as_ref(), prop.into(), self.get_scope(Some(heads)))
+            .get_for(obj.as_ref(), prop.into(), self.get_index_scope(heads))
     }
 
     fn get_all<O: AsRef<ExId>, P: Into<Prop>>(
@@ -820,7 +1813,7 @@ impl ReadDoc for AutoCommit {
         heads: &[ChangeHash],
     ) -> Result<Vec<(Value<'_>, ExId)>, AutomergeError> {
         self.doc
-            .get_all_for(obj.as_ref(), prop.into(), self.get_scope(Some(heads)))
+            .get_all_for(obj.as_ref(), prop.into(), self.get_index_scope(heads))
     }
 
     fn get_missing_deps(&self, heads: &[ChangeHash]) -> Vec<ChangeHash> {
@@ -888,6 +1881,36 @@ impl Transactable for AutoCommit {
         tx.insert_object(&mut self.doc, patch_log, obj.as_ref(), index, value)
     }
 
+    fn push<O: AsRef<ExId>, V: Into<ScalarValue>>(
+        &mut self,
+        obj: O,
+        value: V,
+    ) -> Result<(), AutomergeError> {
+        self.ensure_transaction_open();
+        let (patch_log, tx) = self.transaction.as_mut().unwrap();
+        tx.push(&mut self.doc, patch_log, obj.as_ref(), value)
+    }
+
+    fn push_object<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        value: ObjType,
+    ) -> Result<ExId, AutomergeError> {
+        self.ensure_transaction_open();
+        let (patch_log, tx) = self.transaction.as_mut().unwrap();
+        tx.push_object(&mut self.doc, patch_log, obj.as_ref(), value)
+    }
+
+    fn extend<O: AsRef<ExId>, V: IntoIterator<Item = ScalarValue>>(
+        &mut self,
+        obj: O,
+        values: V,
+    ) -> Result<(), AutomergeError> {
+        self.ensure_transaction_open();
+        let (patch_log, tx) = self.transaction.as_mut().unwrap();
+        tx.extend(&mut self.doc, patch_log, obj.as_ref(), values)
+    }
+
     fn increment<O: AsRef<ExId>, P: Into<Prop>>(
         &mut self,
         obj: O,
@@ -930,12 +1953,28 @@ impl Transactable for AutoCommit {
         del: isize,
         text: &str,
     ) -> Result<(), AutomergeError> {
+        if self.text_unit != TextUnit::Native {
+            return self.splice_text_in(obj, pos, del, text, self.text_unit);
+        }
         self.ensure_transaction_open();
         let (patch_log, tx) = self.transaction.as_mut().unwrap();
         tx.splice_text(&mut self.doc, patch_log, obj.as_ref(), pos, del, text)?;
         Ok(())
     }
 
+    fn splice_text_in<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: isize,
+        text: &str,
+        unit: TextUnit,
+    ) -> Result<(), AutomergeError> {
+        self.ensure_transaction_open();
+        let (patch_log, tx) = self.transaction.as_mut().unwrap();
+        tx.splice_text_in(&mut self.doc, patch_log, obj.as_ref(), pos, del, text, unit)
+    }
+
     fn mark<O: AsRef<ExId>>(
         &mut self,
         obj: O,
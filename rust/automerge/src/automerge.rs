@@ -1,38 +1,96 @@
 use std::cmp::Ordering;
 use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt::Debug;
+use std::io;
 use std::num::NonZeroU64;
 use std::ops::RangeBounds;
+use std::sync::Arc;
 
 use itertools::Itertools;
 
 use crate::change_graph::ChangeGraph;
 use crate::columnar::Key as EncodedKey;
 use crate::exid::ExId;
-use crate::iter::{Keys, ListRange, MapRange, Spans, Values};
+use crate::extra_bytes::Field;
+use crate::instrument::{record, Timer};
+use crate::iter::{Keys, ListRange, MapEntriesAll, MapRange, Spans, TextChars, Values, ValuesRef};
 use crate::marks::{Mark, MarkAccumulator, MarkSet, MarkStateMachine};
-use crate::op_set::{OpSet, OpSetData};
+use crate::op_set::{OpIdxRange, OpSet, OpSetData};
 use crate::parents::Parents;
-use crate::patches::{Patch, PatchLog, TextRepresentation};
-use crate::query;
+use crate::patches::{Patch, PatchLog, PatchObserver, TextRepresentation};
+use crate::query::{self, OpIdSearch};
 use crate::read::ReadDocInternal;
-use crate::storage::{self, load, CompressConfig, VerificationMode};
+use crate::schema::{Schema, SchemaAction};
+use crate::storage::{self, load, ChangeOp, CompressConfig, ReadChangeOpError, VerificationMode};
 use crate::transaction::{
     self, CommitOptions, Failure, Success, Transactable, Transaction, TransactionArgs,
+    TransactionInner,
 };
 use crate::types::{
     ActorId, ChangeHash, Clock, ElemId, Export, Exportable, Key, ListEncoding, MarkData, ObjId,
     ObjMeta, OpBuilder, OpId, OpIds, OpType, Value,
 };
-use crate::{hydrate, ScalarValue};
-use crate::{AutomergeError, Change, Cursor, ObjType, Prop, ReadDoc};
-
+use crate::{hydrate, ScalarValue, ValueRef};
+use crate::{AutomergeError, CancellationToken, Change, Cursor, ObjType, Prop, ReadDoc};
+
+mod actor_info;
+#[cfg(feature = "audit-log")]
+mod audit;
+mod authorize;
+mod blobs;
+mod checkout;
+mod clone;
+mod compact;
+mod conflict_policy;
+mod conflict_stats;
+mod conflicts;
+mod content;
 pub(crate) mod current_state;
 pub(crate) mod diff;
+mod extract;
+mod history;
+mod links;
+mod merge_report;
+mod metrics;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod obj_handle;
+#[cfg(feature = "tokio")]
+mod offload;
+mod quarantine;
+mod schema;
+mod seal;
+mod state_hash;
+mod stream;
+mod tags;
+mod verify;
 
 #[cfg(test)]
 mod tests;
 
+pub use actor_info::ActorInfo;
+#[cfg(feature = "audit-log")]
+pub(crate) use audit::AuditSinkRef;
+pub(crate) use authorize::Authorizer;
+pub use authorize::{TransactionContext, WriteRequest};
+pub(crate) use blobs::BlobStoreRef;
+pub use checkout::Checkout;
+pub use clone::{CloneActor, CloneOptions};
+pub use conflict_policy::ConflictPolicy;
+pub use conflict_stats::{ConflictCounts, ConflictStats};
+pub use conflicts::{Conflict, Conflicts};
+pub use content::ContentDifference;
+pub use history::{ChangeSummary, History};
+pub(crate) use links::LinkResolverRef;
+pub use merge_report::MergeReport;
+pub(crate) use metrics::MetricsRef;
+pub use obj_handle::ObjHandle;
+pub(crate) use schema::SchemaPolicy;
+pub use seal::{SealError, SealedDocument};
+pub use state_hash::StateHash;
+pub use stream::ChangeReader;
+pub use verify::{VerificationProblem, VerificationReport};
+
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) enum Actor {
     Unused(ActorId),
@@ -48,6 +106,18 @@ pub enum OnPartialLoad {
     Error,
 }
 
+/// How the dependencies of a new change are chosen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DepsStrategy {
+    /// Only the changes which are not ancestors of any other dependency. Every other change the
+    /// new change depends on is implied by these, so this produces the smallest changes.
+    Minimal,
+    /// The heads of the document along with the previous change by the same actor, even when
+    /// that change is implied by the heads.
+    #[default]
+    IncludeOwnPrevious,
+}
+
 /// Whether to convert [`ScalarValue::Str`]s in the loaded document to [`ObjType::Text`]
 #[derive(Debug)]
 pub enum StringMigration {
@@ -63,6 +133,9 @@ pub struct LoadOptions<'a> {
     verification_mode: VerificationMode,
     string_migration: StringMigration,
     patch_log: Option<&'a mut PatchLog>,
+    cancellation: Option<CancellationToken>,
+    /// Whether a change with malformed ops is an error even when partial loads are ignored
+    reject_malformed_changes: bool,
 }
 
 impl<'a> LoadOptions<'a> {
@@ -123,6 +196,25 @@ impl<'a> LoadOptions<'a> {
             ..self
         }
     }
+
+    /// A [`CancellationToken`] which can be used to abort loading
+    ///
+    /// If the token is cancelled whilst loading then loading fails with
+    /// [`AutomergeError::Cancelled`]. The default is to not be cancellable.
+    pub fn cancellation(self, token: CancellationToken) -> Self {
+        Self {
+            cancellation: Some(token),
+            ..self
+        }
+    }
+
+    /// Fail to load if a change has malformed ops, whatever [`Self::on_partial_load()`] says
+    pub(crate) fn reject_malformed_changes(self) -> Self {
+        Self {
+            reject_malformed_changes: true,
+            ..self
+        }
+    }
 }
 
 impl std::default::Default for LoadOptions<'static> {
@@ -132,6 +224,8 @@ impl std::default::Default for LoadOptions<'static> {
             verification_mode: VerificationMode::Check,
             patch_log: None,
             string_migration: StringMigration::NoMigration,
+            cancellation: None,
+            reject_malformed_changes: false,
         }
     }
 }
@@ -168,10 +262,11 @@ impl std::default::Default for LoadOptions<'static> {
 pub struct Automerge {
     /// The list of unapplied changes that are not causally ready.
     queue: Vec<Change>,
-    /// The history of changes that form this document, topologically sorted too.
-    history: Vec<Change>,
+    /// The history of changes that form this document, topologically sorted too. This is
+    /// structurally shared between forks of the document.
+    history: im::Vector<Change>,
     /// Mapping from change hash to index into the history list.
-    history_index: HashMap<ChangeHash, usize>,
+    history_index: im::HashMap<ChangeHash, usize>,
     /// Graph of changes
     change_graph: ChangeGraph,
     /// Mapping from actor index to list of seqs seen for them.
@@ -184,6 +279,43 @@ pub struct Automerge {
     actor: Actor,
     /// The maximum operation counter this document has seen.
     max_op: u64,
+    /// Objects which may not be modified by local transactions.
+    frozen: HashSet<ObjId>,
+    /// Hashes of remote changes which modified a frozen object.
+    frozen_violations: Vec<ChangeHash>,
+    /// Changes which have been received but are held back from being applied.
+    quarantine: Vec<Change>,
+    /// The hashes of the changes in `quarantine`.
+    quarantined: HashSet<ChangeHash>,
+    /// Whether remote changes which modify a frozen object are quarantined.
+    quarantine_frozen_violations: bool,
+    /// Checks local writes, see [`Self::set_authorizer()`]
+    authorizer: Option<Authorizer>,
+    /// How the dependencies of local changes are chosen
+    deps_strategy: DepsStrategy,
+    /// Whether to count conflicts, see [`Self::set_track_conflicts()`]
+    track_conflicts: bool,
+    /// How reads choose among conflicting values, see [`Self::set_conflict_policy()`]
+    conflict_policy: ConflictPolicy,
+    conflict_stats: ConflictStats,
+    /// Where the data of blob references is kept, see [`Self::set_blob_store()`]
+    blob_store: Option<BlobStoreRef>,
+    /// Finds the documents links refer to, see [`Self::set_link_resolver()`]
+    link_resolver: Option<LinkResolverRef>,
+    /// The shape changes must leave the document in, see [`Self::set_schema()`]
+    schema: Option<Arc<Schema>>,
+    /// What to do with remote changes which violate the schema
+    schema_policy: Option<SchemaPolicy>,
+    /// Hashes of remote changes which violated the schema.
+    schema_violations: Vec<ChangeHash>,
+    /// Whether changes are compacted as they are added to the history, see
+    /// [`Self::set_compact_history()`]
+    compact_history: bool,
+    /// Told about the work the document does, see [`Self::set_metrics()`]
+    metrics: Option<MetricsRef>,
+    /// Receives a record of every applied change, see [`Self::set_audit_sink()`]
+    #[cfg(feature = "audit-log")]
+    audit_sink: Option<AuditSinkRef>,
 }
 
 impl Automerge {
@@ -191,14 +323,33 @@ impl Automerge {
     pub fn new() -> Self {
         Automerge {
             queue: vec![],
-            history: vec![],
-            history_index: HashMap::new(),
+            history: im::Vector::new(),
+            history_index: im::HashMap::new(),
             change_graph: ChangeGraph::new(),
             states: HashMap::new(),
             ops: Default::default(),
             deps: Default::default(),
             actor: Actor::Unused(ActorId::random()),
             max_op: 0,
+            frozen: HashSet::new(),
+            frozen_violations: Vec::new(),
+            quarantine: Vec::new(),
+            quarantined: HashSet::new(),
+            quarantine_frozen_violations: false,
+            authorizer: None,
+            deps_strategy: DepsStrategy::default(),
+            track_conflicts: false,
+            conflict_stats: ConflictStats::default(),
+            conflict_policy: ConflictPolicy::default(),
+            blob_store: None,
+            link_resolver: None,
+            schema: None,
+            schema_policy: None,
+            schema_violations: Vec::new(),
+            compact_history: false,
+            metrics: None,
+            #[cfg(feature = "audit-log")]
+            audit_sink: None,
         }
     }
 
@@ -214,6 +365,11 @@ impl Automerge {
         &self.ops.osd
     }
 
+    /// The number of changes waiting for their dependencies
+    pub(crate) fn queued_changes(&self) -> usize {
+        self.queue.len()
+    }
+
     /// Whether this document has any operations
     pub fn is_empty(&self) -> bool {
         self.history.is_empty() && self.queue.is_empty()
@@ -283,6 +439,20 @@ impl Automerge {
         Transaction::new(self, args, patch_log)
     }
 
+    /// Set how the dependencies of changes made by this document are chosen
+    ///
+    /// Defaults to [`DepsStrategy::IncludeOwnPrevious`]. Changing it changes the dependencies, and
+    /// so the hashes, of new changes.
+    pub fn set_deps_strategy(&mut self, strategy: DepsStrategy) -> &mut Self {
+        self.deps_strategy = strategy;
+        self
+    }
+
+    /// How the dependencies of changes made by this document are chosen
+    pub fn deps_strategy(&self) -> DepsStrategy {
+        self.deps_strategy
+    }
+
     pub(crate) fn transaction_args(&mut self, heads: Option<&[ChangeHash]>) -> TransactionArgs {
         let actor_index;
         let seq;
@@ -291,6 +461,9 @@ impl Automerge {
         match heads {
             Some(heads) => {
                 deps = heads.to_vec();
+                if self.deps_strategy == DepsStrategy::Minimal {
+                    self.change_graph.remove_redundant(&mut deps);
+                }
                 let isolation = self.isolate_actor(heads);
                 actor_index = isolation.actor_index;
                 seq = isolation.seq;
@@ -301,7 +474,7 @@ impl Automerge {
                 seq = self.states.get(&actor_index).map_or(0, |v| v.len()) as u64 + 1;
                 deps = self.get_heads();
                 scope = None;
-                if seq > 1 {
+                if seq > 1 && self.deps_strategy == DepsStrategy::IncludeOwnPrevious {
                     let last_hash = self.get_hash(actor_index, seq - 1).unwrap();
                     if !deps.contains(&last_hash) {
                         deps.push(last_hash);
@@ -441,18 +614,41 @@ impl Automerge {
         Transaction::empty(self, args, opts)
     }
 
+    /// Commit an empty change which stores `value` in `field` of its extra bytes
+    pub(crate) fn empty_commit_with_extra(&mut self, field: Field, value: Vec<u8>) -> ChangeHash {
+        let args = self.transaction_args(None);
+        let mut tx = TransactionInner::new(args);
+        tx.set_extra_field(field, value);
+        tx.commit_impl(self, None, None)
+    }
+
     /// Fork this document at the current point for use by a different actor.
     ///
     /// This will create a new actor ID for the forked document
+    ///
+    /// The history and the current state (the op set) of the document are shared between the fork
+    /// and the original. Changes made to either document after the fork are copied on write: the
+    /// op tree of each object is copied when that object is first modified, and the ops
+    /// themselves are copied in chunks of a thousand or so as they are updated.
     pub fn fork(&self) -> Self {
         let mut f = self.clone();
         f.set_actor(ActorId::random());
+        #[cfg(feature = "audit-log")]
+        f.clear_audit_sink();
         f
     }
 
     /// Fork this document at the given heads
     ///
     /// This will create a new actor ID for the forked document
+    ///
+    /// The fork only contains the changes which `heads` depend on, so it is an independent
+    /// document starting from that version. Changes made to the original after `heads` are not
+    /// included, though they can be merged in later.
+    ///
+    /// # Errors
+    ///
+    /// [`AutomergeError::InvalidHash`] if one of `heads` is not in this document
     pub fn fork_at(&self, heads: &[ChangeHash]) -> Result<Self, AutomergeError> {
         let mut seen = heads.iter().cloned().collect::<HashSet<_>>();
         let mut heads = heads.to_vec();
@@ -473,7 +669,13 @@ impl Automerge {
         }
         let mut f = Self::new();
         f.set_actor(ActorId::random());
+        f.blob_store = self.blob_store.clone();
+        f.link_resolver = self.link_resolver.clone();
+        f.schema = self.schema.clone();
+        f.schema_policy = self.schema_policy.clone();
+        f.compact_history = self.compact_history;
         f.apply_changes(changes.into_iter().rev().cloned())?;
+        f.metrics = self.metrics.clone();
         Ok(f)
     }
 
@@ -527,6 +729,121 @@ impl Automerge {
         self.get_obj_meta(obj)
     }
 
+    /// Like [`Self::exid_to_obj()`] but fails if the object is frozen, see [`Self::freeze()`]
+    pub(crate) fn exid_to_writable_obj(&self, id: &ExId) -> Result<ObjMeta, AutomergeError> {
+        let obj = self.exid_to_obj(id)?;
+        if let Some(frozen) = self.frozen_ancestor(&obj.id) {
+            return Err(AutomergeError::Frozen(self.id_to_exid(frozen.0)));
+        }
+        Ok(obj)
+    }
+
+    /// Fail if any of `ops` (which a local operation is about to overwrite) created a frozen
+    /// object, see [`Self::freeze()`]
+    pub(crate) fn check_not_frozen<'a, I: IntoIterator<Item = &'a OpId>>(
+        &self,
+        ops: I,
+    ) -> Result<(), AutomergeError> {
+        if self.frozen.is_empty() {
+            return Ok(());
+        }
+        match ops
+            .into_iter()
+            .find(|id| self.frozen.contains(&ObjId(**id)))
+        {
+            Some(id) => Err(AutomergeError::Frozen(self.id_to_exid(*id))),
+            None => Ok(()),
+        }
+    }
+
+    /// Find the closest frozen object which is `obj` or one of its ancestors
+    fn frozen_ancestor(&self, obj: &ObjId) -> Option<ObjId> {
+        if self.frozen.is_empty() {
+            return None;
+        }
+        let mut current = *obj;
+        loop {
+            if self.frozen.contains(&current) {
+                return Some(current);
+            }
+            current = self
+                .ops
+                .parent_object(&current, TextRepresentation::default(), None)?
+                .obj;
+        }
+    }
+
+    /// Freeze the object `obj` and everything beneath it
+    ///
+    /// Local transactions which attempt to modify a frozen object, or to overwrite or delete it
+    /// in its parent, will fail with [`AutomergeError::Frozen`]. Changes from other peers are
+    /// still applied (unless [`Self::set_quarantine_frozen_violations()`] is set), but the hashes
+    /// of any which modify a frozen object are recorded and can be retrieved with
+    /// [`Self::frozen_violations()`].
+    ///
+    /// Freezing is a local policy, it is not saved with the document or sent to other peers.
+    pub fn freeze<O: AsRef<ExId>>(&mut self, obj: O) -> Result<(), AutomergeError> {
+        let obj = self.exid_to_obj(obj.as_ref())?;
+        self.frozen.insert(obj.id);
+        Ok(())
+    }
+
+    /// Remove an object previously frozen with [`Self::freeze()`]
+    ///
+    /// Returns whether the object was frozen. Note that the object may still be frozen if one of
+    /// its ancestors is frozen.
+    pub fn unfreeze<O: AsRef<ExId>>(&mut self, obj: O) -> Result<bool, AutomergeError> {
+        let obj = self.exid_to_obj(obj.as_ref())?;
+        Ok(self.frozen.remove(&obj.id))
+    }
+
+    /// Whether `obj`, or any object containing it, is frozen
+    pub fn is_frozen<O: AsRef<ExId>>(&self, obj: O) -> bool {
+        self.exid_to_obj(obj.as_ref())
+            .map(|obj| self.frozen_ancestor(&obj.id).is_some())
+            .unwrap_or(false)
+    }
+
+    /// The hashes of changes received from other peers which modified a frozen object
+    ///
+    /// See [`Self::freeze()`]
+    pub fn frozen_violations(&self) -> &[ChangeHash] {
+        &self.frozen_violations
+    }
+
+    /// Return and clear the list of changes which modified a frozen object
+    pub fn take_frozen_violations(&mut self) -> Vec<ChangeHash> {
+        std::mem::take(&mut self.frozen_violations)
+    }
+
+    /// Whether any of `ops`, the ops of `change`, modify a frozen object
+    ///
+    /// This only looks up the actors of `change` rather than adding them to the document, an
+    /// actor the document has never seen cannot have created a frozen object.
+    fn touches_frozen(&self, change: &Change, ops: &[ChangeOp]) -> bool {
+        if self.frozen.is_empty() {
+            return false;
+        }
+        let actors = std::iter::once(change.actor_id())
+            .chain(change.other_actor_ids())
+            .map(|a| self.ops.osd.actors.lookup(a))
+            .collect::<Vec<_>>();
+        let resolve = |id: &OpId| Some(ObjId(OpId::new(id.counter(), actors[id.actor()]?)));
+        ops.iter().any(|op| {
+            let obj = if op.obj.is_root() {
+                Some(ObjId::root())
+            } else {
+                resolve(op.obj.opid())
+            };
+            obj.is_some_and(|obj| self.frozen_ancestor(&obj).is_some())
+                || op
+                    .pred
+                    .iter()
+                    .filter_map(resolve)
+                    .any(|p| self.frozen.contains(&p))
+        })
+    }
+
     pub(crate) fn id_to_exid(&self, id: OpId) -> ExId {
         self.ops.id_to_exid(id)
     }
@@ -580,11 +897,21 @@ impl Automerge {
     /// # Arguments
     /// * `data` - The data to load
     /// * `options` - The options to use when loading
-    #[tracing::instrument(skip(data), err)]
+    #[tracing::instrument(
+        skip(data),
+        fields(
+            bytes = data.len(),
+            changes = tracing::field::Empty,
+            ops = tracing::field::Empty,
+            duration_us = tracing::field::Empty,
+        ),
+        err
+    )]
     pub fn load_with_options<'a, 'b>(
         data: &'a [u8],
         options: LoadOptions<'b>,
     ) -> Result<Self, AutomergeError> {
+        let _timer = Timer::start();
         if data.is_empty() {
             tracing::trace!("no data, initializing empty document");
             return Ok(Self::new());
@@ -602,7 +929,7 @@ impl Automerge {
             storage::Chunk::Document(d) => {
                 tracing::trace!("first chunk is document chunk, inflating");
                 first_chunk_was_doc = true;
-                reconstruct_document(&d, options.verification_mode)?
+                reconstruct_document(&d, options.verification_mode, options.cancellation.as_ref())?
             }
             storage::Chunk::Change(stored_change) => {
                 tracing::trace!("first chunk is change chunk");
@@ -624,10 +951,28 @@ impl Automerge {
                 Self::new()
             }
         };
+        if let Some(token) = &options.cancellation {
+            token.check()?;
+        }
         tracing::trace!("loading change chunks");
         match load::load_changes(remaining.reset()) {
             load::LoadedChanges::Complete(c) => {
-                am.apply_changes(change.into_iter().chain(c))?;
+                // the ops of change chunks are only checked when they are applied. A malformed
+                // change fails the whole batch before any of it is applied, so treat it like a
+                // partial load
+                match am.apply_changes_inner(
+                    change.into_iter().chain(c),
+                    &mut PatchLog::inactive(TextRepresentation::default()),
+                    options.cancellation.as_ref(),
+                ) {
+                    Err(AutomergeError::Load(load::Error::InvalidChangeColumns(error)))
+                        if options.on_partial_load == OnPartialLoad::Ignore
+                            && !options.reject_malformed_changes =>
+                    {
+                        tracing::warn!(%error, "ignoring change with malformed ops");
+                    }
+                    result => result?,
+                }
                 // Only allow missing deps if the first chunk was a document chunk
                 // See https://github.com/automerge/automerge/pull/599#issuecomment-1549667472
                 if !am.queue.is_empty()
@@ -651,6 +996,7 @@ impl Automerge {
                 current_state::log_current_state_patches(&am, patch_log);
             }
         }
+        record!(changes = am.history.len(), ops = am.ops.len());
         Ok(am)
     }
 
@@ -675,7 +1021,12 @@ impl Automerge {
     /// Load an incremental save of a document.
     ///
     /// Unlike [`Self::load()`] this imports changes into an existing document. It will work with
-    /// both the output of [`Self::save()`] and [`Self::save_after()`]
+    /// both the output of [`Self::save()`] and [`Self::save_after()`]. Everything set locally,
+    /// such as the actor, the authorizer, the schema and the conflict policy, is kept, even when
+    /// this document is empty.
+    ///
+    /// If a change has malformed ops then an error is returned and none of the changes are
+    /// applied.
     ///
     /// The return value is the number of ops which were applied, this is not useful and will
     /// change in future.
@@ -688,23 +1039,36 @@ impl Automerge {
 
     /// Like [`Self::load_incremental()`] but log the changes to the current state of the document
     /// to [`PatchLog`]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip_all,
+            fields(bytes = data.len(), duration_us = tracing::field::Empty),
+            err
+        )
+    )]
     pub fn load_incremental_log_patches(
         &mut self,
         data: &[u8],
         patch_log: &mut PatchLog,
     ) -> Result<usize, AutomergeError> {
-        if self.is_empty() {
-            let mut doc = Self::load_with_options(
+        let _timer = Timer::start();
+        if self.is_empty() && !self.checks_each_change() {
+            let doc = Self::load_with_options(
                 data,
                 LoadOptions::new()
                     .on_partial_load(OnPartialLoad::Ignore)
+                    .reject_malformed_changes()
                     .verification_mode(VerificationMode::Check),
             )?;
-            doc = doc.with_actor(self.actor_id());
+            self.take_document_state(doc);
+            self.report(|m| {
+                m.ops_applied(self.ops.len());
+                m.changes_received(self.history.len());
+            });
             if patch_log.is_active() {
-                current_state::log_current_state_patches(&doc, patch_log);
+                current_state::log_current_state_patches(self, patch_log);
             }
-            *self = doc;
             return Ok(self.ops.len());
         }
         let changes = match load::load_changes(storage::parse::Input::new(data)) {
@@ -720,6 +1084,47 @@ impl Automerge {
         Ok(delta)
     }
 
+    /// Whether a local policy has to see every change as it is applied, in which case changes
+    /// loaded into an empty document can't be taken from a reconstructed document all at once
+    fn checks_each_change(&self) -> bool {
+        #[cfg(feature = "audit-log")]
+        if self.audit_sink.is_some() {
+            return true;
+        }
+        !self.frozen.is_empty()
+            || self.schema_policy.is_some()
+            || self.track_conflicts
+            || self.compact_history
+    }
+
+    /// Replace the changes and ops of this document, which is empty, with those of `doc`
+    ///
+    /// Everything which was set locally, such as the actor, the authorizer, the schema and the
+    /// conflict policy, is kept.
+    fn take_document_state(&mut self, doc: Self) {
+        let actor = self.actor_id();
+        let Self {
+            queue,
+            history,
+            history_index,
+            change_graph,
+            states,
+            deps,
+            ops,
+            max_op,
+            ..
+        } = doc;
+        self.queue = queue;
+        self.history = history;
+        self.history_index = history_index;
+        self.change_graph = change_graph;
+        self.states = states;
+        self.deps = deps;
+        self.ops = ops;
+        self.max_op = max_op;
+        self.actor = Actor::Unused(actor);
+    }
+
     fn duplicate_seq(&self, change: &Change) -> bool {
         let mut dup = false;
         if let Some(actor_index) = self.ops.osd.actors.lookup(change.actor_id()) {
@@ -755,8 +1160,113 @@ impl Automerge {
         // the final state after all the changes have been applied. We can only do this for an
         // empty document right now, once we have logic to produce the diffs between arbitrary
         // states of the OpSet we can make this cleaner.
+        self.apply_changes_inner(changes, patch_log, None)
+    }
+
+    /// Like [`Self::apply_changes()`] but report the patches produced by each change to
+    /// `observer` as the change is applied
+    ///
+    /// Changes are applied one at a time so that the observer sees the document as each change
+    /// is applied, which is slower than applying them all at once with
+    /// [`Self::apply_changes_log_patches()`].
+    pub fn apply_changes_with<I, O>(
+        &mut self,
+        changes: I,
+        observer: &mut O,
+    ) -> Result<(), AutomergeError>
+    where
+        I: IntoIterator<Item = Change>,
+        O: PatchObserver + ?Sized,
+    {
+        self.apply_changes_observed(
+            changes,
+            observer,
+            &mut PatchLog::inactive(TextRepresentation::default()),
+        )
+    }
+
+    /// Apply `changes` one at a time, reporting the patches produced to `observer` and then
+    /// logging them to `patch_log` if it is active
+    ///
+    /// The patches are generated with the text representation of `patch_log`.
+    pub(crate) fn apply_changes_observed<I, O>(
+        &mut self,
+        changes: I,
+        observer: &mut O,
+        patch_log: &mut PatchLog,
+    ) -> Result<(), AutomergeError>
+    where
+        I: IntoIterator<Item = Change>,
+        O: PatchObserver + ?Sized,
+    {
+        for change in changes {
+            let mut log = PatchLog::active(patch_log.text_rep()).with_splices(patch_log.splices());
+            self.apply_changes_inner(Some(change), &mut log, None)?;
+            let patches = log.make_patches(self);
+            if !patches.is_empty() {
+                observer.observe(&patches);
+            }
+            if patch_log.is_active() {
+                patch_log.merge(log);
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`Self::apply_changes()`] but check `cancellation` before applying each change
+    ///
+    /// If the token is cancelled then this returns [`AutomergeError::Cancelled`]. Changes are
+    /// applied one at a time so the changes which were applied before cancellation remain in the
+    /// document, [`Self::get_missing_deps()`] and [`Self::get_heads()`] can be used to determine
+    /// which changes still need to be applied.
+    pub fn apply_changes_cancellable(
+        &mut self,
+        changes: impl IntoIterator<Item = Change>,
+        cancellation: &CancellationToken,
+    ) -> Result<(), AutomergeError> {
+        self.apply_changes_inner(
+            changes,
+            &mut PatchLog::inactive(TextRepresentation::default()),
+            Some(cancellation),
+        )
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                received = tracing::field::Empty,
+                changes = tracing::field::Empty,
+                ops = tracing::field::Empty,
+                queued = tracing::field::Empty,
+                duration_us = tracing::field::Empty,
+            ),
+            err
+        )
+    )]
+    pub(crate) fn apply_changes_inner<I: IntoIterator<Item = Change>>(
+        &mut self,
+        changes: I,
+        patch_log: &mut PatchLog,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<(), AutomergeError> {
+        let _timer = Timer::start();
+        #[cfg(feature = "tracing")]
+        let before = (self.history.len(), self.ops.len());
+        let check = || cancellation.map(|c| c.check()).unwrap_or(Ok(()));
+        check()?;
+        let mut changes = changes.into_iter().collect::<Vec<_>>();
+        record!(received = changes.len());
+        let (history_index, quarantined) = (&self.history_index, &self.quarantined);
+        let mut decoded = decode_ops(&mut changes, |c| {
+            !history_index.contains_key(&c.hash()) && !quarantined.contains(&c.hash())
+        })
+        .map_err(|e| load::Error::InvalidChangeColumns(Box::new(e)))?;
+        check()?;
         for c in changes {
-            if !self.history_index.contains_key(&c.hash()) {
+            if !self.history_index.contains_key(&c.hash()) && !self.is_quarantined(&c.hash()) {
                 if self.duplicate_seq(&c) {
                     return Err(AutomergeError::DuplicateSeqNumber(
                         c.seq(),
@@ -764,7 +1274,9 @@ impl Automerge {
                     ));
                 }
                 if self.is_causally_ready(&c) {
-                    self.apply_change(c, patch_log)?;
+                    check()?;
+                    let ops = decoded.remove(&c.hash());
+                    self.apply_change(c, ops, patch_log)?;
                 } else {
                     self.queue.push(c);
                 }
@@ -772,25 +1284,109 @@ impl Automerge {
         }
         while let Some(c) = self.pop_next_causally_ready_change() {
             if !self.history_index.contains_key(&c.hash()) {
-                self.apply_change(c, patch_log)?;
+                if let Err(e) = check() {
+                    // put the change back so it is applied by the next call
+                    self.queue.push(c);
+                    return Err(e);
+                }
+                let ops = decoded.remove(&c.hash());
+                self.apply_change(c, ops, patch_log)?;
             }
         }
+        record!(
+            changes = self.history.len() - before.0,
+            ops = self.ops.len() - before.1,
+            queued = self.queue.len(),
+        );
         Ok(())
     }
 
+    /// Apply `change`, using the already decoded `decoded` ops if present
     fn apply_change(
         &mut self,
-        change: Change,
+        mut change: Change,
+        decoded: Option<Vec<ChangeOp>>,
         patch_log: &mut PatchLog,
     ) -> Result<(), AutomergeError> {
-        let ops = self.import_ops(&change);
-        self.update_history(change, ops.len());
-        for (obj, op, pred) in ops {
-            self.insert_op(&obj, op, &pred, patch_log)?;
+        let ops = match decoded {
+            Some(ops) => ops,
+            None if !change.ops_checked() => change
+                .try_decode_ops()
+                .map_err(|e| load::Error::InvalidChangeColumns(Box::new(e)))?,
+            None => change.iter_stored_ops().collect(),
+        };
+        // decide whether to quarantine the change before its actors and keys are added to the
+        // document
+        if self.touches_frozen(&change, &ops) {
+            tracing::warn!(hash=?change.hash(), "change modifies a frozen object");
+            self.frozen_violations.push(change.hash());
+            if self.quarantine_frozen_violations {
+                self.quarantine_change(change);
+                return Ok(());
+            }
         }
+        let ops = self.import_ops(&change, ops);
+        let num_ops = ops.len();
+        match self.insert_ops_checked(&change, ops, patch_log)? {
+            Some(SchemaAction::Quarantine) => {
+                self.quarantine_change(change);
+                return Ok(());
+            }
+            Some(_) => return Ok(()),
+            None => {}
+        }
+        #[cfg(feature = "audit-log")]
+        let hash = change.hash();
+        self.update_history(change, num_ops);
+        self.report(|m| m.changes_received(1));
+        #[cfg(feature = "audit-log")]
+        self.audit(hash, false);
         Ok(())
     }
 
+    /// Remove the ops in `range` from the document, undoing their effect on the ops they
+    /// overwrite
+    pub(crate) fn remove_ops(&mut self, range: OpIdxRange) {
+        // remove in reverse order so sets are removed before makes etc...
+        let encoding = ListEncoding::List; // encoding doesnt matter here - we dont care what the index is
+        let ops: Vec<_> = self
+            .ops
+            .osd
+            .get_ops(range)
+            .rev()
+            .map(|op| {
+                (
+                    op.idx(),
+                    *op.obj(),
+                    *op.id(),
+                    op.pred().map(|op| *op.id()).collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        for (idx, obj, opid, pred) in ops.into_iter() {
+            for pred_id in &pred {
+                if let Some(p) = self
+                    .ops
+                    .search(&obj, OpIdSearch::opid(*pred_id, encoding, None))
+                    .found()
+                {
+                    self.ops.remove_succ(&obj, p, idx);
+                }
+            }
+            if let Some(pos) = self
+                .ops
+                .search(&obj, OpIdSearch::opid(opid, encoding, None))
+                .found()
+            {
+                self.ops.remove(&obj, pos);
+            }
+        }
+        self.ops
+            .osd
+            .segments
+            .remove_carried_by(|idx| range.contains(idx));
+    }
+
     fn is_causally_ready(&self, change: &Change) -> bool {
         change
             .deps()
@@ -809,7 +1405,11 @@ impl Automerge {
         None
     }
 
-    fn import_ops(&mut self, change: &Change) -> Vec<(ObjId, OpBuilder, OpIds)> {
+    fn import_ops<I: IntoIterator<Item = ChangeOp>>(
+        &mut self,
+        change: &Change,
+        ops: I,
+    ) -> Vec<(ObjId, OpBuilder, OpIds)> {
         let actor = self.ops.osd.actors.cache(change.actor_id().clone());
         let mut actors = Vec::with_capacity(change.other_actor_ids().len() + 1);
         actors.push(actor);
@@ -820,8 +1420,7 @@ impl Automerge {
                 .map(|a| self.ops.osd.actors.cache(a.clone()))
                 .collect::<Vec<_>>(),
         );
-        change
-            .iter_ops()
+        ops.into_iter()
             .enumerate()
             .map(|(i, c)| {
                 let id = OpId::new(change.start_op().get() + i as u64, actor);
@@ -872,11 +1471,22 @@ impl Automerge {
         )
     }
 
+    /// Takes all the changes in `other` which are not in `self` and applies them
+    ///
+    /// This is the same as [`Self::merge()`] but only needs a shared reference to `other`, so a
+    /// document behind shared ownership can be merged without locking it for writing.
+    pub fn merge_read(&mut self, other: &Self) -> Result<Vec<ChangeHash>, AutomergeError> {
+        self.merge_and_log_patches(
+            other,
+            &mut PatchLog::inactive(TextRepresentation::default()),
+        )
+    }
+
     /// Takes all the changes in `other` which are not in `self` and applies them whilst logging
     /// the resulting changes to the current state of the document to `patch_log`
     pub fn merge_and_log_patches(
         &mut self,
-        other: &mut Self,
+        other: &Self,
         patch_log: &mut PatchLog,
     ) -> Result<Vec<ChangeHash>, AutomergeError> {
         // TODO: Make this fallible and figure out how to do this transactionally
@@ -890,10 +1500,56 @@ impl Automerge {
         Ok(self.get_heads())
     }
 
+    /// Takes all the changes in `other` which are not in `self` and applies them, reporting the
+    /// patches produced by each change to `observer`, see [`Self::apply_changes_with()`]
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{Automerge, ROOT};
+    /// # use automerge::patches::{Patch, PatchAction};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = Automerge::new();
+    /// let mut other = doc.fork();
+    /// other.transact(|tx| tx.put(ROOT, "key", "value")).unwrap();
+    ///
+    /// let mut patches: Vec<Patch> = Vec::new();
+    /// doc.merge_with(&other, &mut patches).unwrap();
+    /// assert!(matches!(patches[0].action, PatchAction::PutMap { .. }));
+    /// ```
+    pub fn merge_with<O: PatchObserver + ?Sized>(
+        &mut self,
+        other: &Self,
+        observer: &mut O,
+    ) -> Result<Vec<ChangeHash>, AutomergeError> {
+        let changes = self
+            .get_changes_added(other)
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        self.apply_changes_with(changes, observer)?;
+        Ok(self.get_heads())
+    }
+
     /// Save the entirety of this document in a compact form.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip_all,
+            fields(
+                changes = self.history.len(),
+                ops = self.ops.len(),
+                deflate = options.deflate,
+                bytes = tracing::field::Empty,
+                duration_us = tracing::field::Empty,
+            )
+        )
+    )]
     pub fn save_with_options(&self, options: SaveOptions) -> Vec<u8> {
+        let _timer = Timer::start();
         let heads = self.get_heads();
-        let c = self.history.iter();
+        let (changes, orphans) = self.changes_to_save(&options);
+        let c = changes.iter().copied();
         let compress = if options.deflate {
             None
         } else {
@@ -907,12 +1563,111 @@ impl Automerge {
             &heads,
             compress,
         );
+        for orphaned in orphans {
+            bytes.extend(orphaned.raw_bytes());
+        }
+        record!(bytes = bytes.len());
+        self.report(|m| m.bytes_saved(bytes.len()));
+        bytes
+    }
+
+    /// The changes in the document in the order `options` saves them, and the orphaned changes
+    /// to save after the document
+    fn changes_to_save(&self, options: &SaveOptions) -> (Vec<&Change>, Vec<&Change>) {
+        let mut changes = self.history.iter().collect::<Vec<_>>();
+        if options.canonical {
+            changes = crate::storage::save::canonical_order(changes);
+        }
+        let mut orphans = Vec::new();
         if options.retain_orphans {
-            for orphaned in self.queue.iter() {
-                bytes.extend(orphaned.raw_bytes());
+            orphans = self.queue.iter().collect::<Vec<_>>();
+            if options.canonical {
+                orphans.sort_by_key(|c| c.hash());
+                orphans.dedup_by_key(|c| c.hash());
             }
         }
-        bytes
+        (changes, orphans)
+    }
+
+    /// Save the entirety of this document to `out`
+    ///
+    /// This writes the same bytes as [`Self::save()`], but rather than building the whole
+    /// document in a `Vec<u8>` and returning it, the parts of the document are written to `out`
+    /// as they are encoded. The encoded columns are still held in memory while they are written,
+    /// as the header of the document contains their checksum, but they are not copied into a
+    /// buffer for the whole document, which is what makes [`Self::save()`] expensive for large
+    /// documents.
+    ///
+    /// # Returns
+    /// The number of bytes written
+    ///
+    /// # Errors
+    /// Any error returned by `out`, in which case some of the document may have been written
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{Automerge, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = Automerge::new();
+    /// let mut tx = doc.transaction();
+    /// tx.put(ROOT, "key", "value").unwrap();
+    /// tx.commit();
+    ///
+    /// let mut file = Vec::new();
+    /// let written = doc.save_to(&mut file).unwrap();
+    /// assert_eq!(written, file.len());
+    /// assert_eq!(file, doc.save());
+    /// ```
+    pub fn save_to<W: io::Write>(&self, out: W) -> io::Result<usize> {
+        self.save_to_with_options(SaveOptions::default(), out)
+    }
+
+    /// Save the entirety of this document to `out`, see [`Self::save_to()`] and
+    /// [`Self::save_with_options()`]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip_all,
+            fields(
+                changes = self.history.len(),
+                ops = self.ops.len(),
+                deflate = options.deflate,
+                bytes = tracing::field::Empty,
+                duration_us = tracing::field::Empty,
+            )
+        )
+    )]
+    pub fn save_to_with_options<W: io::Write>(
+        &self,
+        options: SaveOptions,
+        mut out: W,
+    ) -> io::Result<usize> {
+        let _timer = Timer::start();
+        let heads = self.get_heads();
+        let (changes, orphans) = self.changes_to_save(&options);
+        let c = changes.iter().copied();
+        let compress = if options.deflate {
+            None
+        } else {
+            Some(CompressConfig::None)
+        };
+        let mut written = crate::storage::save::save_document_to(
+            &mut out,
+            c,
+            self.ops.iter().map(|(objid, _, op)| (objid, op)),
+            &self.ops.osd.actors,
+            &self.ops.osd.props,
+            &heads,
+            compress,
+        )?;
+        for orphaned in orphans {
+            out.write_all(orphaned.raw_bytes())?;
+            written += orphaned.raw_bytes().len();
+        }
+        record!(bytes = written);
+        self.report(|m| m.bytes_saved(written));
+        Ok(written)
     }
 
     /// Save the entirety of this document in a compact form.
@@ -927,6 +1682,42 @@ impl Automerge {
         Ok(bytes)
     }
 
+    /// Save the entirety of this document so that the same history always produces the same
+    /// bytes
+    ///
+    /// The output of [`Self::save()`] depends on the order in which changes were applied, so two
+    /// replicas with the same changes usually save different bytes. This saves the changes in
+    /// a canonical order, a topological sort of the change graph which breaks ties by change
+    /// hash, so any two documents with the same changes save byte-identical output which can
+    /// be content addressed and deduplicated. Changes which are missing dependencies are
+    /// saved in hash order after the document.
+    ///
+    /// The output is an ordinary document which can be loaded with [`Self::load()`]. It is
+    /// only guaranteed to be identical for the same version of automerge.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc1 = AutoCommit::new();
+    /// doc1.put(ROOT, "a", 1).unwrap();
+    /// let mut doc2 = AutoCommit::new();
+    /// doc2.put(ROOT, "b", 2).unwrap();
+    ///
+    /// let mut merged1 = doc1.fork();
+    /// merged1.merge(&mut doc2).unwrap();
+    /// let mut merged2 = doc2.fork();
+    /// merged2.merge(&mut doc1).unwrap();
+    /// assert_eq!(merged1.save_canonical(), merged2.save_canonical());
+    /// ```
+    pub fn save_canonical(&self) -> Vec<u8> {
+        self.save_with_options(SaveOptions {
+            canonical: true,
+            ..Default::default()
+        })
+    }
+
     /// Save this document, but don't run it through `DEFLATE` afterwards
     pub fn save_nocompress(&self) -> Vec<u8> {
         self.save_with_options(SaveOptions {
@@ -941,12 +1732,27 @@ impl Automerge {
     /// changes. This is useful if you know you have only made a small change since the last
     /// [`Self::save()`] and you want to immediately send it somewhere (e.g. you've inserted a
     /// single character in a text object).
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip_all,
+            fields(
+                changes = tracing::field::Empty,
+                bytes = tracing::field::Empty,
+                duration_us = tracing::field::Empty,
+            )
+        )
+    )]
     pub fn save_after(&self, heads: &[ChangeHash]) -> Vec<u8> {
+        let _timer = Timer::start();
         let changes = self.get_changes(heads);
+        record!(changes = changes.len());
         let mut bytes = vec![];
         for c in changes {
             bytes.extend(c.raw_bytes());
         }
+        record!(bytes = bytes.len());
+        self.report(|m| m.bytes_saved(bytes.len()));
         bytes
     }
 
@@ -1006,10 +1812,34 @@ impl Automerge {
             .find(|c| c.actor_id() == self.get_actor());
     }
 
+    /// Get the change made by `actor` with sequence number `seq`
+    ///
+    /// Sequence numbers start at 1. This uses the index of changes by actor which the document
+    /// maintains, so it does not scan the history.
+    pub fn get_change_by_actor_seq(&self, actor: &ActorId, seq: u64) -> Option<&Change> {
+        let actor_index = self.ops.osd.actors.lookup(actor)?;
+        let index = usize::try_from(seq.checked_sub(1)?).ok()?;
+        let history_index = self.states.get(&actor_index)?.get(index)?;
+        self.history.get(*history_index)
+    }
+
     pub(crate) fn clock_at(&self, heads: &[ChangeHash]) -> Clock {
         self.change_graph.clock_for_heads(heads)
     }
 
+    /// The clock to use when reading the document as at `heads`
+    ///
+    /// Reading as at the current heads is the same as reading the current state, which can be
+    /// answered from the cached indexes of the op tree rather than by walking every op, so in
+    /// that case this returns `None`.
+    pub(crate) fn scope_at(&self, heads: &[ChangeHash]) -> Option<Clock> {
+        if heads.len() == self.deps.len() && heads.iter().all(|h| self.deps.contains(h)) {
+            None
+        } else {
+            Some(self.clock_at(heads))
+        }
+    }
+
     fn get_isolated_actor_index(&mut self, level: usize) -> usize {
         if level == 0 {
             self.get_actor_index()
@@ -1061,6 +1891,7 @@ impl Automerge {
     }
 
     pub(crate) fn update_history(&mut self, change: Change, num_ops: usize) -> usize {
+        self.report(|m| m.ops_applied(num_ops));
         self.max_op = std::cmp::max(self.max_op, change.start_op().get() + num_ops as u64 - 1);
 
         self.update_deps(&change);
@@ -1078,7 +1909,11 @@ impl Automerge {
             .add_change(&change, actor_index)
             .expect("Change's deps should already be in the document");
 
-        self.history.push(change);
+        if self.compact_history {
+            self.history.push_back(change.compacted());
+        } else {
+            self.history.push_back(change);
+        }
 
         history_index
     }
@@ -1202,6 +2037,80 @@ impl Automerge {
         self.ops.visualise(objects)
     }
 
+    /// Return structured diagnostics about the op trees of the document
+    ///
+    /// This reports the number of ops, the depth and the node occupancy of the tree for each
+    /// object, in a form which can be serialized, for dashboards and tests which want to check
+    /// the health of the trees without reading the output of `visualise_optree`.
+    ///
+    /// # Arguments
+    ///
+    /// * objects: An optional list of object IDs to report on, if not specified all objects
+    ///   are reported
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ObjType, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = AutoCommit::new();
+    /// let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    /// for i in 0..100 {
+    ///     doc.insert(&list, i, i as i64).unwrap();
+    /// }
+    /// let stats = doc.optree_stats(Some(vec![list.clone()]));
+    /// assert_eq!(stats.objects[0].obj, list);
+    /// assert_eq!(stats.objects[0].ops, 100);
+    /// assert!(stats.objects[0].depth > 1);
+    /// ```
+    pub fn optree_stats(&self, objects: Option<Vec<ExId>>) -> crate::op_tree::OpTreeStats {
+        let objects = objects.map(|os| {
+            os.iter()
+                .filter_map(|o| self.exid_to_obj(o).ok())
+                .map(|o| o.id)
+                .collect()
+        });
+        self.ops.optree_stats(objects)
+    }
+
+    /// Return a graphviz representation of the graph of changes in this document
+    ///
+    /// Each change is a node labelled with the first eight characters of its hash, its actor,
+    /// its sequence number and its message, with an edge to each of its dependencies. The heads
+    /// of the document are drawn with a double outline.
+    pub fn visualise_change_graph(&self) -> String {
+        let heads = self.get_heads();
+        let mut out = String::from("digraph changes {\n    rankdir=BT;\n    node [shape=box];\n");
+        for change in &self.history {
+            let hash = change.hash().to_string();
+            let actor = change.actor_id().to_hex_string();
+            let mut label = format!(
+                "{}\\n{}/{}",
+                &hash[..8],
+                &actor[..actor.len().min(8)],
+                change.seq()
+            );
+            if let Some(message) = change.message() {
+                label.push_str("\\n");
+                label.push_str(&message.replace('\\', "\\\\").replace('"', "\\\""));
+            }
+            let outline = if heads.contains(&change.hash()) {
+                ", peripheries=2"
+            } else {
+                ""
+            };
+            out.push_str(&format!(
+                "    \"{}\" [label=\"{}\"{}];\n",
+                hash, label, outline
+            ));
+            for dep in change.deps() {
+                out.push_str(&format!("    \"{}\" -> \"{}\";\n", hash, dep));
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
     pub(crate) fn insert_op(
         &mut self,
         obj: &ObjId,
@@ -1228,11 +2137,17 @@ impl Automerge {
             (found.pos, found.succ)
         };
 
+        let conflicts = self.conflicts_before(obj, pos, idx);
+
         self.ops.add_succ(obj, &succ, idx);
 
         if !is_delete {
             self.ops.insert(pos, obj, idx);
         }
+
+        if let Some(conflicts) = conflicts {
+            self.record_conflicts(obj, pos, conflicts);
+        }
         Ok(())
     }
 
@@ -1441,9 +2356,26 @@ impl Automerge {
     }
 
     pub(crate) fn values_for(&self, obj: &ExId, clock: Option<Clock>) -> Values<'_> {
+        let Ok(obj) = self.exid_to_obj(obj) else {
+            return Values::default();
+        };
+        if let Some(ops) = self.resolved_ops(&obj, clock.as_ref()) {
+            let values = ops.iter().map(|op| op.tagged_value(clock.as_ref()));
+            return Values::resolved(values.collect());
+        }
+        Values::new(self.ops.top_ops(&obj.id, clock.clone()), clock)
+    }
+
+    pub(crate) fn map_entries_all_for(
+        &self,
+        obj: &ExId,
+        clock: Option<Clock>,
+    ) -> MapEntriesAll<'_> {
         self.exid_to_obj(obj)
             .ok()
-            .map(|obj| Values::new(self.ops.top_ops(&obj.id, clock.clone()), clock))
+            .filter(|obj| !obj.typ.is_sequence())
+            .and_then(|obj| self.ops.op_iter(&obj.id))
+            .map(|iter| MapEntriesAll::new(iter, &self.ops.osd, clock))
             .unwrap_or_default()
     }
 
@@ -1466,6 +2398,15 @@ impl Automerge {
         Ok(self.ops.text(&obj.id, clock))
     }
 
+    pub(crate) fn text_chars_for(
+        &self,
+        obj: &ExId,
+        clock: Option<Clock>,
+    ) -> Result<TextChars<'_>, AutomergeError> {
+        let obj = self.exid_to_obj(obj)?;
+        Ok(TextChars::new(self.ops.top_ops(&obj.id, clock)))
+    }
+
     pub(crate) fn spans_for(
         &self,
         obj: &ExId,
@@ -1528,6 +2469,53 @@ impl Automerge {
         self.calculate_marks(obj, clock)
     }
 
+    /// Like [`ReadDoc::get()`] but the value borrows its string and bytes data from the document
+    /// rather than cloning it
+    pub fn get_ref<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Option<(ValueRef<'_>, ExId)>, AutomergeError> {
+        self.get_ref_for(obj.as_ref(), prop.into(), None)
+    }
+
+    /// Like [`ReadDoc::values()`] but the values borrow their string and bytes data from the
+    /// document rather than cloning it
+    pub fn values_ref<O: AsRef<ExId>>(&self, obj: O) -> ValuesRef<'_> {
+        self.values_ref_for(obj.as_ref(), None)
+    }
+
+    pub(crate) fn get_ref_for(
+        &self,
+        obj: &ExId,
+        prop: Prop,
+        clock: Option<Clock>,
+    ) -> Result<Option<(ValueRef<'_>, ExId)>, AutomergeError> {
+        let obj = self.exid_to_obj(obj)?;
+        let found = self.ops.seek_ops_by_prop(
+            &obj.id,
+            prop,
+            TextRepresentation::String.encoding(obj.typ),
+            clock.as_ref(),
+        );
+        Ok(self
+            .resolve_conflict(&obj, &found.ops, clock.as_ref())
+            .map(|op| (op.value_ref_at(clock.as_ref()), op.exid())))
+    }
+
+    pub(crate) fn values_ref_for(&self, obj: &ExId, clock: Option<Clock>) -> ValuesRef<'_> {
+        let Ok(obj) = self.exid_to_obj(obj) else {
+            return ValuesRef::default();
+        };
+        if let Some(ops) = self.resolved_ops(&obj, clock.as_ref()) {
+            let values = ops
+                .iter()
+                .map(|op| (op.value_ref_at(clock.as_ref()), op.exid()));
+            return ValuesRef::resolved(values.collect());
+        }
+        ValuesRef::new(self.ops.top_ops(&obj.id, clock.clone()), clock)
+    }
+
     pub(crate) fn get_for(
         &self,
         obj: &ExId,
@@ -1535,18 +2523,24 @@ impl Automerge {
         clock: Option<Clock>,
     ) -> Result<Option<(Value<'_>, ExId)>, AutomergeError> {
         let obj = self.exid_to_obj(obj)?;
-        Ok(self
-            .ops
-            .seek_ops_by_prop(
-                &obj.id,
-                prop,
-                TextRepresentation::String.encoding(obj.typ),
-                clock.as_ref(),
-            )
-            .ops
-            .into_iter()
-            .last()
-            .map(|op| op.tagged_value(clock.as_ref())))
+        Ok(self.get_in(&obj, prop, clock.as_ref()))
+    }
+
+    /// Like [`Self::get_for()`] for an object which has already been resolved
+    pub(crate) fn get_in(
+        &self,
+        obj: &ObjMeta,
+        prop: Prop,
+        clock: Option<&Clock>,
+    ) -> Option<(Value<'_>, ExId)> {
+        let found = self.ops.seek_ops_by_prop(
+            &obj.id,
+            prop,
+            TextRepresentation::String.encoding(obj.typ),
+            clock,
+        );
+        self.resolve_conflict(obj, &found.ops, clock)
+            .map(|op| op.tagged_value(clock))
     }
 
     pub(crate) fn get_all_for<O: AsRef<ExId>, P: Into<Prop>>(
@@ -1784,19 +2778,44 @@ impl ReadDoc for Automerge {
         self.values_for(obj.as_ref(), Some(clock))
     }
 
+    fn map_entries_all<O: AsRef<ExId>>(&self, obj: O) -> MapEntriesAll<'_> {
+        self.map_entries_all_for(obj.as_ref(), None)
+    }
+
+    fn map_entries_all_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> MapEntriesAll<'_> {
+        let clock = self.clock_at(heads);
+        self.map_entries_all_for(obj.as_ref(), Some(clock))
+    }
+
     fn length<O: AsRef<ExId>>(&self, obj: O) -> usize {
         self.length_for(obj.as_ref(), None)
     }
 
     fn length_at<O: AsRef<ExId>>(&self, obj: O, heads: &[ChangeHash]) -> usize {
-        let clock = self.clock_at(heads);
-        self.length_for(obj.as_ref(), Some(clock))
+        self.length_for(obj.as_ref(), self.scope_at(heads))
     }
 
     fn text<O: AsRef<ExId>>(&self, obj: O) -> Result<String, AutomergeError> {
         self.text_for(obj.as_ref(), None)
     }
 
+    fn text_chars<O: AsRef<ExId>>(&self, obj: O) -> Result<TextChars<'_>, AutomergeError> {
+        self.text_chars_for(obj.as_ref(), None)
+    }
+
+    fn text_chars_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<TextChars<'_>, AutomergeError> {
+        let clock = self.clock_at(heads);
+        self.text_chars_for(obj.as_ref(), Some(clock))
+    }
+
     fn spans<O: AsRef<ExId>>(&self, obj: O) -> Result<Spans<'_>, AutomergeError> {
         self.spans_for(obj.as_ref(), None)
     }
@@ -1890,8 +2909,7 @@ impl ReadDoc for Automerge {
         prop: P,
         heads: &[ChangeHash],
     ) -> Result<Option<(Value<'_>, ExId)>, AutomergeError> {
-        let clock = Some(self.clock_at(heads));
-        self.get_for(obj.as_ref(), prop.into(), clock)
+        self.get_for(obj.as_ref(), prop.into(), self.scope_at(heads))
     }
 
     fn get_all<O: AsRef<ExId>, P: Into<Prop>>(
@@ -1908,8 +2926,7 @@ impl ReadDoc for Automerge {
         prop: P,
         heads: &[ChangeHash],
     ) -> Result<Vec<(Value<'_>, ExId)>, AutomergeError> {
-        let clock = Some(self.clock_at(heads));
-        self.get_all_for(obj.as_ref(), prop.into(), clock)
+        self.get_all_for(obj.as_ref(), prop.into(), self.scope_at(heads))
     }
 
     fn object_type<O: AsRef<ExId>>(&self, obj: O) -> Result<ObjType, AutomergeError> {
@@ -1977,6 +2994,8 @@ pub struct SaveOptions {
     pub deflate: bool,
     /// Whether to save changes which we do not have the dependencies for
     pub retain_orphans: bool,
+    /// Whether to save the changes in a canonical order, see [`Automerge::save_canonical()`]
+    pub canonical: bool,
 }
 
 impl std::default::Default for SaveOptions {
@@ -1984,10 +3003,71 @@ impl std::default::Default for SaveOptions {
         Self {
             deflate: true,
             retain_orphans: true,
+            canonical: false,
         }
     }
 }
 
+/// Batches of at least this many changes have their ops decoded on multiple threads
+#[cfg(feature = "parallel")]
+const PARALLEL_DECODE_THRESHOLD: usize = 32;
+
+/// Check the ops of every change in a batch for which `is_new` is true
+///
+/// This happens before any change in the batch is applied, so that a change with malformed ops
+/// fails the whole batch rather than leaving the changes before it applied.
+///
+/// Changes from different actors are decoded independently of each other, so for large batches
+/// (e.g. the first sync of a big document) this spreads the work of decoding the columns of each
+/// change across all cores and returns the decoded ops. Only the final insertion into the op set
+/// has to be sequential.
+#[cfg(feature = "parallel")]
+fn decode_ops(
+    changes: &mut [Change],
+    is_new: impl Fn(&Change) -> bool + Sync,
+) -> Result<HashMap<ChangeHash, Vec<ChangeOp>>, ReadChangeOpError> {
+    use rayon::prelude::*;
+    if changes.len() < PARALLEL_DECODE_THRESHOLD {
+        check_ops(changes, is_new)?;
+        return Ok(HashMap::new());
+    }
+    changes
+        .par_iter_mut()
+        .filter(|c| is_new(c))
+        .map(|c| {
+            let ops = if c.ops_checked() {
+                c.iter_stored_ops().collect()
+            } else {
+                c.try_decode_ops()?
+            };
+            Ok((c.hash(), ops))
+        })
+        .collect()
+}
+
+/// Check the ops of every change in a batch for which `is_new` is true
+///
+/// This happens before any change in the batch is applied, so that a change with malformed ops
+/// fails the whole batch rather than leaving the changes before it applied.
+#[cfg(not(feature = "parallel"))]
+fn decode_ops(
+    changes: &mut [Change],
+    is_new: impl Fn(&Change) -> bool,
+) -> Result<HashMap<ChangeHash, Vec<ChangeOp>>, ReadChangeOpError> {
+    check_ops(changes, is_new)?;
+    Ok(HashMap::new())
+}
+
+fn check_ops(
+    changes: &mut [Change],
+    is_new: impl Fn(&Change) -> bool,
+) -> Result<(), ReadChangeOpError> {
+    changes
+        .iter_mut()
+        .filter(|c| is_new(c))
+        .try_for_each(Change::check_ops)
+}
+
 #[derive(Debug)]
 pub(crate) struct Isolation {
     actor_index: usize,
@@ -1998,14 +3078,17 @@ pub(crate) struct Isolation {
 pub(crate) fn reconstruct_document<'a>(
     doc: &'a storage::Document<'a>,
     mode: VerificationMode,
+    cancellation: Option<&CancellationToken>,
 ) -> Result<Automerge, AutomergeError> {
     let storage::load::ReconOpSet {
         changes,
         op_set,
         heads,
         max_op,
-    } = storage::load::reconstruct_opset(doc, mode)
-        .map_err(|e| load::Error::InflateDocument(Box::new(e)))?;
+    } = storage::load::reconstruct_opset(doc, mode, cancellation).map_err(|e| match e {
+        storage::load::ReconstructError::Cancelled => AutomergeError::Cancelled,
+        e => load::Error::InflateDocument(Box::new(e)).into(),
+    })?;
 
     let mut hashes_by_index = HashMap::new();
     let mut actor_to_history: HashMap<usize, Vec<usize>> = HashMap::new();
@@ -2021,7 +3104,7 @@ pub(crate) fn reconstruct_document<'a>(
     let history_index = hashes_by_index.into_iter().map(|(k, v)| (v, k)).collect();
     Ok(Automerge {
         queue: vec![],
-        history: changes,
+        history: changes.into(),
         history_index,
         states: actor_to_history,
         change_graph,
@@ -2029,5 +3112,24 @@ pub(crate) fn reconstruct_document<'a>(
         deps: heads.into_iter().collect(),
         actor: Actor::Unused(ActorId::random()),
         max_op,
+        frozen: HashSet::new(),
+        frozen_violations: Vec::new(),
+        quarantine: Vec::new(),
+        quarantined: HashSet::new(),
+        quarantine_frozen_violations: false,
+        authorizer: None,
+        deps_strategy: DepsStrategy::default(),
+        track_conflicts: false,
+        conflict_stats: ConflictStats::default(),
+        conflict_policy: ConflictPolicy::default(),
+        blob_store: None,
+        link_resolver: None,
+        schema: None,
+        schema_policy: None,
+        schema_violations: Vec::new(),
+        compact_history: false,
+        metrics: None,
+        #[cfg(feature = "audit-log")]
+        audit_sink: None,
     })
 }
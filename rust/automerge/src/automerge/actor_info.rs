@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+
+use crate::extra_bytes::Field;
+use crate::storage::parse;
+use crate::{ActorId, Automerge, ChangeHash};
+
+const FIELD_NAME: u8 = 1;
+const FIELD_DEVICE: u8 = 2;
+const FIELD_PUBLIC_KEY: u8 = 3;
+
+/// A description of an actor, see [`Automerge::set_actor_info()`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ActorInfo {
+    /// The name to show for the author of changes made by the actor
+    pub name: Option<String>,
+    /// The device the actor is used on
+    pub device: Option<String>,
+    /// A public key for the actor, in whatever encoding the application uses
+    pub public_key: Option<Vec<u8>>,
+}
+
+impl ActorInfo {
+    pub fn with_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn with_device<S: Into<String>>(mut self, device: S) -> Self {
+        self.device = Some(device.into());
+        self
+    }
+
+    pub fn with_public_key(mut self, public_key: Vec<u8>) -> Self {
+        self.public_key = Some(public_key);
+        self
+    }
+
+    /// Encode each field which is set as its tag, its length and its bytes
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let fields = [
+            (FIELD_NAME, self.name.as_ref().map(|n| n.as_bytes())),
+            (FIELD_DEVICE, self.device.as_ref().map(|d| d.as_bytes())),
+            (FIELD_PUBLIC_KEY, self.public_key.as_deref()),
+        ];
+        for (field, bytes) in fields {
+            if let Some(bytes) = bytes {
+                out.push(field);
+                leb128::write::unsigned(&mut out, bytes.len() as u64).unwrap();
+                out.extend_from_slice(bytes);
+            }
+        }
+        out
+    }
+
+    /// Decode the output of [`Self::encode()`], ignoring fields with unknown tags
+    pub(crate) fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut info = Self::default();
+        let mut input = parse::Input::new(bytes);
+        while !input.is_empty() {
+            let (i, field) = parse::take1::<()>(input).ok()?;
+            let (i, len) = parse::leb128_u64::<parse::leb128::Error>(i).ok()?;
+            let (i, value) = parse::take_n::<()>(usize::try_from(len).ok()?, i).ok()?;
+            match field {
+                FIELD_NAME => info.name = Some(String::from_utf8(value.to_vec()).ok()?),
+                FIELD_DEVICE => info.device = Some(String::from_utf8(value.to_vec()).ok()?),
+                FIELD_PUBLIC_KEY => info.public_key = Some(value.to_vec()),
+                _ => {}
+            }
+            input = i;
+        }
+        Some(info)
+    }
+}
+
+impl Automerge {
+    /// Describe the actor of this document with `info`
+    ///
+    /// The description is recorded as an empty change made by the actor, so it is saved and
+    /// synced with the rest of the document, and any peer can read it with
+    /// [`Self::actor_info()`]. An actor can only describe itself. Describing the actor again
+    /// replaces the previous description. Returns the hash of the change.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{ActorInfo, AutoCommit};
+    /// let mut alice = AutoCommit::new();
+    /// alice.set_actor_info(&ActorInfo::default().with_name("Alice").with_device("laptop"));
+    ///
+    /// let mut bob = AutoCommit::new();
+    /// bob.merge(&mut alice).unwrap();
+    /// let info = bob.actor_info(alice.get_actor()).unwrap();
+    /// assert_eq!(info.name.as_deref(), Some("Alice"));
+    /// ```
+    pub fn set_actor_info(&mut self, info: &ActorInfo) -> ChangeHash {
+        self.empty_commit_with_extra(Field::ActorInfo, info.encode())
+    }
+
+    /// The most recent description of `actor`, see [`Self::set_actor_info()`]
+    pub fn actor_info(&self, actor: &ActorId) -> Option<ActorInfo> {
+        let actor_index = self.ops.osd.actors.lookup(actor)?;
+        self.states
+            .get(&actor_index)?
+            .iter()
+            .rev()
+            .find_map(|i| self.history[*i].actor_info())
+    }
+
+    /// The most recent description of every actor which has described itself
+    pub fn actor_infos(&self) -> BTreeMap<ActorId, ActorInfo> {
+        self.states
+            .iter()
+            .filter_map(|(actor_index, changes)| {
+                let info = changes
+                    .iter()
+                    .rev()
+                    .find_map(|i| self.history[*i].actor_info())?;
+                Some((self.ops.osd.actors.get(*actor_index).clone(), info))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::audit::{AuditRecord, AuditSink, OpCounts};
+use crate::patches::TextRepresentation;
+use crate::path::json_pointer;
+use crate::types::{Key, OpType};
+use crate::{Automerge, ChangeHash, Prop};
+
+#[derive(Clone)]
+pub(crate) struct AuditSinkRef(Arc<dyn AuditSink>);
+
+impl fmt::Debug for AuditSinkRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AuditSink").finish()
+    }
+}
+
+/// Audit logging
+///
+/// These are available with the `audit-log` feature.
+impl Automerge {
+    /// Give `sink` a record of every change applied to this document from now on
+    ///
+    /// This includes changes committed by local transactions and changes applied with
+    /// [`Self::apply_changes()`], [`Self::merge()`], [`Self::load_incremental()`] or the sync
+    /// protocol. Changes which are quarantined are recorded when they are released. The sink is
+    /// called once the change has been applied. See [`crate::audit`] for the records it receives.
+    ///
+    /// Documents created with [`Self::fork()`] do not inherit the sink, as they are a separate
+    /// replica with an actor of their own.
+    pub fn set_audit_sink(&mut self, sink: Arc<dyn AuditSink>) {
+        self.audit_sink = Some(AuditSinkRef(sink));
+    }
+
+    /// Stop recording applied changes, see [`Self::set_audit_sink()`]
+    pub fn clear_audit_sink(&mut self) {
+        self.audit_sink = None;
+    }
+
+    /// Give the audit sink, if there is one, a record of the applied change `hash`
+    pub(crate) fn audit(&mut self, hash: ChangeHash, local: bool) {
+        let Some(AuditSinkRef(sink)) = self.audit_sink.clone() else {
+            return;
+        };
+        let Some(change) = self
+            .history_index
+            .get(&hash)
+            .map(|i| self.history[*i].clone())
+        else {
+            return;
+        };
+        let mut ops = OpCounts::default();
+        let mut paths = BTreeSet::new();
+        for (obj, op, _) in self.import_ops(&change, change.iter_stored_ops()) {
+            match (&op.action, op.insert) {
+                (OpType::MarkBegin(..) | OpType::MarkEnd(_), _) => ops.marks += 1,
+                (_, true) => ops.inserts += 1,
+                (OpType::Make(_) | OpType::Put(_), false) => ops.puts += 1,
+                (OpType::Delete, false) => ops.deletes += 1,
+                (OpType::Increment(_), false) => ops.increments += 1,
+            }
+            let mut path = self
+                .ops
+                .parents(obj, TextRepresentation::default(), None)
+                .path()
+                .into_iter()
+                .map(|(_, prop)| prop)
+                .collect::<Vec<_>>();
+            if let Key::Map(key) = op.key {
+                path.push(Prop::Map(self.ops.osd.props.get(key).clone()));
+            }
+            paths.insert(json_pointer(&path));
+        }
+        let record = AuditRecord {
+            hash,
+            actor: change.actor_id().clone(),
+            seq: change.seq(),
+            timestamp: change.timestamp(),
+            message: change.message().cloned(),
+            local,
+            paths: paths.into_iter().collect(),
+            ops,
+        };
+        sink.record(&record);
+    }
+}
@@ -0,0 +1,99 @@
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::exid::ExId;
+use crate::{ActorId, Automerge, AutomergeError};
+
+/// A value attached to a transaction which identifies who is making the change, see
+/// [`crate::transaction::Transaction::set_context()`]
+pub type TransactionContext = Arc<dyn Any + Send + Sync>;
+
+/// A local write which is being checked by an authorizer, see [`Automerge::set_authorizer()`]
+#[derive(Debug)]
+pub struct WriteRequest<'a> {
+    /// The object being written to
+    pub obj: &'a ExId,
+    /// The actor which will author the change
+    pub actor: &'a ActorId,
+    pub(crate) context: Option<&'a TransactionContext>,
+}
+
+impl<'a> WriteRequest<'a> {
+    /// The context attached to the transaction making this write, if it has one of type `T`
+    pub fn context<T: Any>(&self) -> Option<&'a T> {
+        self.context.and_then(|c| c.downcast_ref())
+    }
+}
+
+type AuthorizerFn = dyn Fn(&WriteRequest<'_>) -> Result<(), String> + Send + Sync;
+
+#[derive(Clone)]
+pub(crate) struct Authorizer(Arc<AuthorizerFn>);
+
+impl fmt::Debug for Authorizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Authorizer").finish()
+    }
+}
+
+impl Automerge {
+    /// Check every local write with `authorizer` before it is made
+    ///
+    /// The authorizer is called with the object being written to and the context attached to
+    /// the transaction with [`crate::transaction::Transaction::set_context()`] or
+    /// [`crate::AutoCommit::set_context()`]. If it returns an error the write fails with
+    /// [`AutomergeError::Unauthorized`] and the document is left unchanged.
+    ///
+    /// Like [`Self::freeze()`] this is a local policy: it is not saved with the document and
+    /// changes received from other peers are not checked.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, AutomergeError, ObjType, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = AutoCommit::new();
+    /// doc.set_authorizer(|write| match write.context::<&str>() {
+    ///     Some(&"admin") => Ok(()),
+    ///     _ => Err("only admins may edit".to_string()),
+    /// });
+    /// assert!(matches!(
+    ///     doc.put(ROOT, "key", "value"),
+    ///     Err(AutomergeError::Unauthorized { .. })
+    /// ));
+    /// doc.set_context(Some("admin"));
+    /// doc.put(ROOT, "key", "value").unwrap();
+    /// ```
+    pub fn set_authorizer<F>(&mut self, authorizer: F)
+    where
+        F: Fn(&WriteRequest<'_>) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.authorizer = Some(Authorizer(Arc::new(authorizer)));
+    }
+
+    /// Stop checking local writes, see [`Self::set_authorizer()`]
+    pub fn clear_authorizer(&mut self) {
+        self.authorizer = None;
+    }
+
+    pub(crate) fn authorize(
+        &self,
+        obj: &ExId,
+        actor: usize,
+        context: Option<&TransactionContext>,
+    ) -> Result<(), AutomergeError> {
+        let Some(Authorizer(authorizer)) = &self.authorizer else {
+            return Ok(());
+        };
+        let write = WriteRequest {
+            obj,
+            actor: &self.ops().osd.actors[actor],
+            context,
+        };
+        authorizer(&write).map_err(|reason| AutomergeError::Unauthorized {
+            obj: obj.clone(),
+            reason,
+        })
+    }
+}
@@ -0,0 +1,52 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::blob::{BlobError, BlobRef, BlobStore};
+use crate::Automerge;
+
+#[derive(Clone)]
+pub(crate) struct BlobStoreRef(Arc<dyn BlobStore>);
+
+impl fmt::Debug for BlobStoreRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("BlobStore").finish()
+    }
+}
+
+/// Blobs
+impl Automerge {
+    /// Keep the data of [`BlobRef`]s in `store`, see [`crate::blob`]
+    ///
+    /// Documents created with [`Self::fork()`] or [`Self::fork_at()`] share the store.
+    pub fn set_blob_store(&mut self, store: Arc<dyn BlobStore>) {
+        self.blob_store = Some(BlobStoreRef(store));
+    }
+
+    pub fn clear_blob_store(&mut self) {
+        self.blob_store = None;
+    }
+
+    /// Put `data` in the blob store and return a reference to it, which can then be put in the
+    /// document
+    pub fn store_blob(&self, data: &[u8]) -> Result<BlobRef, BlobError> {
+        let BlobStoreRef(store) = self.blob_store.as_ref().ok_or(BlobError::NoStore)?;
+        let blob = BlobRef::new(data);
+        store.put(&blob.hash, data)?;
+        Ok(blob)
+    }
+
+    /// Read the data `blob` refers to from the blob store
+    ///
+    /// # Errors
+    ///
+    /// This fails if there is no store, the store does not have the blob or the data the store
+    /// returns does not match the size and hash of `blob`.
+    pub fn read_blob(&self, blob: &BlobRef) -> Result<Vec<u8>, BlobError> {
+        let BlobStoreRef(store) = self.blob_store.as_ref().ok_or(BlobError::NoStore)?;
+        let data = store
+            .get(&blob.hash)?
+            .ok_or(BlobError::Missing(blob.hash))?;
+        blob.verify(&data)?;
+        Ok(data)
+    }
+}
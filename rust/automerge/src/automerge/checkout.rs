@@ -0,0 +1,320 @@
+use std::ops::RangeBounds;
+
+use crate::clock::Clock;
+use crate::exid::ExId;
+use crate::iter::{Keys, ListRange, MapEntriesAll, MapRange, Spans, TextChars, Values, ValuesRef};
+use crate::marks::{Mark, MarkSet};
+use crate::parents::Parents;
+use crate::{
+    hydrate, Automerge, AutomergeError, Change, ChangeHash, Cursor, ObjHandle, ObjType, Prop,
+    ReadDoc, Value, ValueRef,
+};
+
+/// A read only view of a document as it was at some heads, see [`Automerge::checkout()`]
+///
+/// Every method of [`ReadDoc`] which does not take heads reads the document as at the heads of
+/// the checkout, as do those which take optional heads when they are given `None`. The `_at`
+/// methods read the document at the heads they are given, as they do for [`Automerge`].
+#[derive(Debug, Clone)]
+pub struct Checkout<'a> {
+    doc: &'a Automerge,
+    heads: Vec<ChangeHash>,
+    clock: Option<Clock>,
+}
+
+impl Automerge {
+    /// A view of the document as it was at `heads`
+    ///
+    /// The clock for `heads` is computed once, when the checkout is created, rather than for
+    /// every read as the `_at` methods do. Returns [`AutomergeError::MissingHash`] if any of
+    /// `heads` is not a change in this document.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ReadDoc, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = AutoCommit::new();
+    /// doc.put(ROOT, "status", "draft").unwrap();
+    /// let draft = doc.get_heads();
+    /// doc.put(ROOT, "status", "published").unwrap();
+    ///
+    /// let checkout = doc.checkout(&draft).unwrap();
+    /// let (status, _) = checkout.get(ROOT, "status").unwrap().unwrap();
+    /// assert_eq!(status.to_str(), Some("draft"));
+    /// assert_eq!(checkout.keys(ROOT).count(), 1);
+    /// ```
+    pub fn checkout(&self, heads: &[ChangeHash]) -> Result<Checkout<'_>, AutomergeError> {
+        if let Some(missing) = heads.iter().find(|h| !self.history_index.contains_key(h)) {
+            return Err(AutomergeError::MissingHash(*missing));
+        }
+        Ok(Checkout {
+            doc: self,
+            heads: heads.to_vec(),
+            clock: self.scope_at(heads),
+        })
+    }
+}
+
+impl<'a> Checkout<'a> {
+    /// The heads this checkout reads the document at
+    pub fn heads(&self) -> &[ChangeHash] {
+        &self.heads
+    }
+
+    /// The document this is a view of
+    pub fn doc(&self) -> &'a Automerge {
+        self.doc
+    }
+
+    /// See [`Automerge::handle()`]
+    pub fn handle<O: AsRef<ExId>>(&self, obj: O) -> Result<ObjHandle<'a>, AutomergeError> {
+        ObjHandle::new(self.doc, obj.as_ref(), self.clock.clone())
+    }
+
+    /// See [`Automerge::get_ref()`]
+    pub fn get_ref<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Option<(ValueRef<'a>, ExId)>, AutomergeError> {
+        self.doc
+            .get_ref_for(obj.as_ref(), prop.into(), self.clock.clone())
+    }
+
+    /// See [`Automerge::values_ref()`]
+    pub fn values_ref<O: AsRef<ExId>>(&self, obj: O) -> ValuesRef<'a> {
+        self.doc.values_ref_for(obj.as_ref(), self.clock.clone())
+    }
+
+    fn clock_or(&self, heads: Option<&[ChangeHash]>) -> Option<Clock> {
+        match heads {
+            Some(heads) => Some(self.doc.clock_at(heads)),
+            None => self.clock.clone(),
+        }
+    }
+}
+
+impl<'a> ReadDoc for Checkout<'a> {
+    fn parents<O: AsRef<ExId>>(&self, obj: O) -> Result<Parents<'_>, AutomergeError> {
+        self.doc.parents_for(obj.as_ref(), self.clock.clone())
+    }
+
+    fn parents_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<Parents<'_>, AutomergeError> {
+        self.doc.parents_at(obj, heads)
+    }
+
+    fn keys<O: AsRef<ExId>>(&self, obj: O) -> Keys<'_> {
+        self.doc.keys_for(obj.as_ref(), self.clock.clone())
+    }
+
+    fn keys_at<O: AsRef<ExId>>(&self, obj: O, heads: &[ChangeHash]) -> Keys<'_> {
+        self.doc.keys_at(obj, heads)
+    }
+
+    fn map_range<'b, O: AsRef<ExId>, R: RangeBounds<String> + 'b>(
+        &'b self,
+        obj: O,
+        range: R,
+    ) -> MapRange<'b, R> {
+        self.doc
+            .map_range_for(obj.as_ref(), range, self.clock.clone())
+    }
+
+    fn map_range_at<'b, O: AsRef<ExId>, R: RangeBounds<String> + 'b>(
+        &'b self,
+        obj: O,
+        range: R,
+        heads: &[ChangeHash],
+    ) -> MapRange<'b, R> {
+        self.doc.map_range_at(obj, range, heads)
+    }
+
+    fn list_range<O: AsRef<ExId>, R: RangeBounds<usize>>(
+        &self,
+        obj: O,
+        range: R,
+    ) -> ListRange<'_, R> {
+        self.doc
+            .list_range_for(obj.as_ref(), range, self.clock.clone())
+    }
+
+    fn list_range_at<O: AsRef<ExId>, R: RangeBounds<usize>>(
+        &self,
+        obj: O,
+        range: R,
+        heads: &[ChangeHash],
+    ) -> ListRange<'_, R> {
+        self.doc.list_range_at(obj, range, heads)
+    }
+
+    fn values<O: AsRef<ExId>>(&self, obj: O) -> Values<'_> {
+        self.doc.values_for(obj.as_ref(), self.clock.clone())
+    }
+
+    fn values_at<O: AsRef<ExId>>(&self, obj: O, heads: &[ChangeHash]) -> Values<'_> {
+        self.doc.values_at(obj, heads)
+    }
+
+    fn map_entries_all<O: AsRef<ExId>>(&self, obj: O) -> MapEntriesAll<'_> {
+        self.doc
+            .map_entries_all_for(obj.as_ref(), self.clock.clone())
+    }
+
+    fn map_entries_all_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> MapEntriesAll<'_> {
+        self.doc.map_entries_all_at(obj, heads)
+    }
+
+    fn length<O: AsRef<ExId>>(&self, obj: O) -> usize {
+        self.doc.length_for(obj.as_ref(), self.clock.clone())
+    }
+
+    fn length_at<O: AsRef<ExId>>(&self, obj: O, heads: &[ChangeHash]) -> usize {
+        self.doc.length_at(obj, heads)
+    }
+
+    fn object_type<O: AsRef<ExId>>(&self, obj: O) -> Result<ObjType, AutomergeError> {
+        self.doc.object_type(obj)
+    }
+
+    fn marks<O: AsRef<ExId>>(&self, obj: O) -> Result<Vec<Mark<'_>>, AutomergeError> {
+        self.doc.marks_for(obj.as_ref(), self.clock.clone())
+    }
+
+    fn marks_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<Vec<Mark<'_>>, AutomergeError> {
+        self.doc.marks_at(obj, heads)
+    }
+
+    fn get_marks<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        index: usize,
+        heads: Option<&[ChangeHash]>,
+    ) -> Result<MarkSet, AutomergeError> {
+        self.doc.get_marks_for(obj, index, self.clock_or(heads))
+    }
+
+    fn text<O: AsRef<ExId>>(&self, obj: O) -> Result<String, AutomergeError> {
+        self.doc.text_for(obj.as_ref(), self.clock.clone())
+    }
+
+    fn text_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<String, AutomergeError> {
+        self.doc.text_at(obj, heads)
+    }
+
+    fn text_chars<O: AsRef<ExId>>(&self, obj: O) -> Result<TextChars<'_>, AutomergeError> {
+        self.doc.text_chars_for(obj.as_ref(), self.clock.clone())
+    }
+
+    fn text_chars_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<TextChars<'_>, AutomergeError> {
+        self.doc.text_chars_at(obj, heads)
+    }
+
+    fn spans<O: AsRef<ExId>>(&self, obj: O) -> Result<Spans<'_>, AutomergeError> {
+        self.doc.spans_for(obj.as_ref(), self.clock.clone())
+    }
+
+    fn spans_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<Spans<'_>, AutomergeError> {
+        self.doc.spans_at(obj, heads)
+    }
+
+    fn get_cursor<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        position: usize,
+        at: Option<&[ChangeHash]>,
+    ) -> Result<Cursor, AutomergeError> {
+        self.doc
+            .get_cursor_for(obj.as_ref(), position, self.clock_or(at))
+    }
+
+    fn get_cursor_position<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        cursor: &Cursor,
+        at: Option<&[ChangeHash]>,
+    ) -> Result<usize, AutomergeError> {
+        self.doc
+            .get_cursor_position_for(obj.as_ref(), cursor, self.clock_or(at))
+    }
+
+    fn get<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Option<(Value<'_>, ExId)>, AutomergeError> {
+        self.doc
+            .get_for(obj.as_ref(), prop.into(), self.clock.clone())
+    }
+
+    fn get_at<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+        heads: &[ChangeHash],
+    ) -> Result<Option<(Value<'_>, ExId)>, AutomergeError> {
+        self.doc.get_at(obj, prop, heads)
+    }
+
+    fn hydrate<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: Option<&[ChangeHash]>,
+    ) -> Result<hydrate::Value, AutomergeError> {
+        self.doc
+            .hydrate_obj(obj.as_ref(), Some(heads.unwrap_or(&self.heads)))
+    }
+
+    fn get_all<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Vec<(Value<'_>, ExId)>, AutomergeError> {
+        self.doc.get_all_for(obj, prop, self.clock.clone())
+    }
+
+    fn get_all_at<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+        heads: &[ChangeHash],
+    ) -> Result<Vec<(Value<'_>, ExId)>, AutomergeError> {
+        self.doc.get_all_at(obj, prop, heads)
+    }
+
+    fn get_missing_deps(&self, heads: &[ChangeHash]) -> Vec<ChangeHash> {
+        self.doc.get_missing_deps(heads)
+    }
+
+    fn get_change_by_hash(&self, hash: &ChangeHash) -> Option<&Change> {
+        self.doc.get_change_by_hash(hash)
+    }
+
+    fn stats(&self) -> crate::read::Stats {
+        self.doc.stats()
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{ActorId, Automerge};
+
+/// Which actor ID a clone made with [`Automerge::clone_with()`] uses
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum CloneActor {
+    /// Keep the actor ID of the original document
+    #[default]
+    Keep,
+    /// Generate a new random actor ID
+    Random,
+    /// Use the given actor ID
+    Set(ActorId),
+}
+
+/// Options for [`Automerge::clone_with()`] and [`crate::AutoCommit::clone_with()`]
+///
+/// The defaults produce the same document as [`Clone::clone()`].
+#[derive(Debug, Clone)]
+pub struct CloneOptions {
+    pub(crate) actor: CloneActor,
+    pub(crate) keep_patch_log: bool,
+    pub(crate) copy_caches: bool,
+}
+
+impl CloneOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The actor ID the clone will use
+    ///
+    /// The default is [`CloneActor::Keep`]
+    pub fn actor(self, actor: CloneActor) -> Self {
+        Self { actor, ..self }
+    }
+
+    /// Whether the clone keeps the patch log configuration of the original
+    ///
+    /// This only applies to [`crate::AutoCommit`]. If `false` the clone gets an inactive
+    /// [`crate::PatchLog`] with the same text representation, and a diff cursor at the start of
+    /// the document, as for [`crate::AutoCommit::fork()`]. The default is `true`.
+    pub fn keep_patch_log(self, keep_patch_log: bool) -> Self {
+        Self {
+            keep_patch_log,
+            ..self
+        }
+    }
+
+    /// Whether the clone copies caches from the original or rebuilds them when they are next
+    /// needed
+    ///
+    /// The caches are the compressed form of each change and, for [`crate::AutoCommit`], the
+    /// patches cached by [`crate::AutoCommit::diff()`]. Not copying them makes the clone smaller
+    /// but means the work to rebuild them is repeated. The default is `true`.
+    pub fn copy_caches(self, copy_caches: bool) -> Self {
+        Self {
+            copy_caches,
+            ..self
+        }
+    }
+}
+
+impl Default for CloneOptions {
+    fn default() -> Self {
+        Self {
+            actor: CloneActor::Keep,
+            keep_patch_log: true,
+            copy_caches: true,
+        }
+    }
+}
+
+impl Automerge {
+    /// Clone this document, see [`CloneOptions`]
+    pub fn clone_with(&self, options: CloneOptions) -> Self {
+        let mut doc = self.clone();
+        if !options.copy_caches {
+            doc.history = doc
+                .history
+                .iter()
+                .map(|change| change.without_cached_compression())
+                .collect();
+        }
+        match options.actor {
+            CloneActor::Keep => {}
+            CloneActor::Random => {
+                doc.set_actor(ActorId::random());
+            }
+            CloneActor::Set(actor) => {
+                doc.set_actor(actor);
+            }
+        }
+        doc
+    }
+}
@@ -0,0 +1,54 @@
+use crate::Automerge;
+
+/// Keeping the history compressed in memory
+impl Automerge {
+    /// Whether to compact each change as it is added to the history, see
+    /// [`Self::compact_history()`]
+    ///
+    /// This is off by default. Compacting a change compresses it, so committing and applying
+    /// changes is slower with this on. Like [`Self::freeze()`] this is a local policy: it is not
+    /// saved with the document.
+    pub fn set_compact_history(&mut self, compact: bool) -> &mut Self {
+        self.compact_history = compact;
+        self
+    }
+
+    /// Only keep the compressed form of the changes in the history in memory
+    ///
+    /// The ops of every change are kept in memory until this is called, whether or not they
+    /// have already been applied. A compacted change keeps its compressed bytes and its metadata
+    /// (actor, sequence number, dependencies and so on), so the document can still be saved
+    /// without decompressing it. It is decompressed again the first time its ops or its
+    /// uncompressed bytes are read, for example when it is decoded or sent to another peer by
+    /// the sync protocol, so this can be called again afterwards to release the memory. Changes
+    /// which are too small to be worth compressing are not compacted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ReadDoc, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = AutoCommit::new();
+    /// let text = doc.put_object(ROOT, "text", automerge::ObjType::Text).unwrap();
+    /// doc.splice_text(&text, 0, 0, &"all work and no play ".repeat(100)).unwrap();
+    /// doc.commit();
+    ///
+    /// let before = doc.resident_history_bytes();
+    /// doc.compact_history();
+    /// assert!(doc.resident_history_bytes() < before);
+    /// assert_eq!(doc.text(&text).unwrap().len(), 2100);
+    /// ```
+    pub fn compact_history(&mut self) {
+        for change in self.history.iter_mut() {
+            *change = change.compacted();
+        }
+    }
+
+    /// The number of bytes of encoded changes the history holds in memory
+    ///
+    /// This includes the compressed bytes of changes which have been compressed, whether for
+    /// [`Self::compact_history()`] or to be sent to another peer.
+    pub fn resident_history_bytes(&self) -> usize {
+        self.history.iter().map(|c| c.resident_bytes()).sum()
+    }
+}
@@ -0,0 +1,165 @@
+use std::fmt;
+use std::sync::Arc;
+
+use itertools::Itertools;
+
+use crate::clock::Clock;
+use crate::exid::ExId;
+use crate::op_set::Op;
+use crate::types::ObjMeta;
+use crate::{ActorId, Automerge, ObjType, Value};
+
+type ResolverFn = dyn Fn(&[(Value<'_>, ExId)]) -> usize + Send + Sync;
+
+/// How reads choose the value of a property which has conflicting values, see
+/// [`Automerge::set_conflict_policy()`]
+#[derive(Clone, Default)]
+pub enum ConflictPolicy {
+    /// The value written by the op with the greatest ID, which every peer agrees on
+    #[default]
+    LastWriterWins,
+    /// The most recent value written by the given actor, or the default winner if it did not
+    /// write any of the values
+    PreferActor(ActorId),
+    /// The greatest numeric value, or the default winner if none of the values are numbers
+    ///
+    /// Counters and timestamps count as numbers. Values are compared exactly, as by
+    /// [`crate::ScalarValue::to_decimal()`], so big integers and decimals which differ beyond the
+    /// precision of an `f64` are still ordered correctly.
+    MaxNumeric,
+    /// The value at the index returned by the function, which is given every value in the
+    /// order of [`crate::ReadDoc::get_all()`]. An index which is out of bounds chooses the
+    /// default winner.
+    Custom(Arc<ResolverFn>),
+}
+
+impl ConflictPolicy {
+    /// A [`Self::Custom`] policy which chooses with `f`
+    pub fn custom<F: Fn(&[(Value<'_>, ExId)]) -> usize + Send + Sync + 'static>(f: F) -> Self {
+        Self::Custom(Arc::new(f))
+    }
+
+    pub(crate) fn is_default(&self) -> bool {
+        matches!(self, Self::LastWriterWins)
+    }
+
+    /// The index of the winning value in `values`, which is not empty
+    fn choose(&self, values: &[(Value<'_>, ExId)]) -> usize {
+        let default = values.len() - 1;
+        match self {
+            Self::LastWriterWins => default,
+            Self::PreferActor(actor) => values
+                .iter()
+                .rposition(|(_, id)| matches!(id, ExId::Id(_, a, _) if a == actor))
+                .unwrap_or(default),
+            Self::MaxNumeric => values
+                .iter()
+                .enumerate()
+                .filter_map(|(i, (v, _))| Some((i, v.to_decimal()?)))
+                .max_by(|(_, a), (_, b)| a.cmp(b))
+                .map_or(default, |(i, _)| i),
+            Self::Custom(f) => Some(f(values))
+                .filter(|i| *i < values.len())
+                .unwrap_or(default),
+        }
+    }
+}
+
+impl fmt::Debug for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LastWriterWins => f.write_str("LastWriterWins"),
+            Self::PreferActor(actor) => f.debug_tuple("PreferActor").field(actor).finish(),
+            Self::MaxNumeric => f.write_str("MaxNumeric"),
+            Self::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+impl Automerge {
+    /// Choose the values of conflicted properties with `policy` when reading the document
+    ///
+    /// This affects the reads of one property or of the values of one object of a map or list:
+    /// [`crate::ReadDoc::get()`], [`crate::ReadDoc::values()`], [`Self::get_ref()`],
+    /// [`Self::values_ref()`], their `_at` variants and [`crate::ObjHandle::get()`] and
+    /// [`crate::ObjHandle::iter()`]. Every other read always uses
+    /// [`ConflictPolicy::LastWriterWins`]:
+    ///
+    /// * [`crate::ReadDoc::map_range()`] and [`crate::ReadDoc::list_range()`], which walk the op
+    ///   set rather than reading one property at a time
+    /// * [`Self::hydrate()`], [`Self::extract()`] and patches, which describe the document as
+    ///   every peer sees it
+    /// * [`Self::state_hash()`], which has to be the same on peers with the same content
+    /// * reads of text objects, because edits to text count indexes using the default winner of
+    ///   each character and [`crate::ReadDoc::text()`] has to agree with them
+    ///
+    /// Like [`Self::freeze()`] this is a local policy. It is not saved with the document and it
+    /// does not change the document, so peers with different policies may read different
+    /// values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ConflictPolicy, ReadDoc, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = AutoCommit::new();
+    /// let mut other = doc.fork();
+    /// doc.put(ROOT, "score", 10).unwrap();
+    /// other.put(ROOT, "score", 5).unwrap();
+    /// doc.merge(&mut other).unwrap();
+    ///
+    /// doc.set_conflict_policy(ConflictPolicy::MaxNumeric);
+    /// let (score, _) = doc.get(ROOT, "score").unwrap().unwrap();
+    /// assert_eq!(score.to_i64(), Some(10));
+    /// ```
+    pub fn set_conflict_policy(&mut self, policy: ConflictPolicy) -> &mut Self {
+        self.conflict_policy = policy;
+        self
+    }
+
+    /// The policy which reads use to choose among conflicting values, see
+    /// [`Self::set_conflict_policy()`]
+    pub fn conflict_policy(&self) -> &ConflictPolicy {
+        &self.conflict_policy
+    }
+
+    /// Whether the conflict policy chooses the values of `obj`, text always uses the default
+    fn resolves_conflicts_in(&self, obj: &ObjMeta) -> bool {
+        !self.conflict_policy.is_default() && obj.typ != ObjType::Text
+    }
+
+    /// The op with the winning value among `ops`, the visible ops at one property of `obj` in
+    /// the order of their IDs
+    pub(crate) fn resolve_conflict<'a>(
+        &self,
+        obj: &ObjMeta,
+        ops: &[Op<'a>],
+        clock: Option<&Clock>,
+    ) -> Option<Op<'a>> {
+        if ops.len() < 2 || !self.resolves_conflicts_in(obj) {
+            return ops.last().copied();
+        }
+        let values = ops
+            .iter()
+            .map(|op| op.tagged_value(clock))
+            .collect::<Vec<_>>();
+        ops.get(self.conflict_policy.choose(&values)).copied()
+    }
+
+    /// The winning op of each property of `obj`, if the conflict policy applies to it
+    pub(crate) fn resolved_ops(&self, obj: &ObjMeta, clock: Option<&Clock>) -> Option<Vec<Op<'_>>> {
+        if !self.resolves_conflicts_in(obj) {
+            return None;
+        }
+        let ops_by_key = self.ops.iter_ops(&obj.id).chunk_by(|o| o.elemid_or_key());
+        Some(
+            ops_by_key
+                .into_iter()
+                .filter_map(|(_, ops)| {
+                    let visible = ops.filter(|o| o.visible_at(clock)).collect::<Vec<_>>();
+                    self.resolve_conflict(obj, &visible, clock)
+                })
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use crate::exid::ExId;
+use crate::op_set::OpIdx;
+use crate::types::{Key, ObjId};
+use crate::Automerge;
+
+/// The number of conflicts which were created and resolved
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConflictCounts {
+    /// The number of times a property went from having one value to having several
+    pub created: u64,
+    /// The number of times a property went from having several values to having one or none
+    pub resolved: u64,
+}
+
+impl ConflictCounts {
+    fn add(&mut self, other: ConflictCounts) {
+        self.created += other.created;
+        self.resolved += other.resolved;
+    }
+}
+
+/// Counts of the conflicts created and resolved in a document, see
+/// [`Automerge::set_track_conflicts()`]
+///
+/// Conflicts are counted per object and, for maps, per key. Conflicts on list elements are only
+/// counted per object as list indexes change as the list is edited.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConflictStats {
+    objects: HashMap<ExId, ConflictCounts>,
+    keys: HashMap<(ExId, String), ConflictCounts>,
+}
+
+impl ConflictStats {
+    /// The conflicts in every object
+    pub fn total(&self) -> ConflictCounts {
+        let mut total = ConflictCounts::default();
+        for counts in self.objects.values() {
+            total.add(*counts);
+        }
+        total
+    }
+
+    /// The conflicts in `obj`
+    pub fn for_object(&self, obj: &ExId) -> ConflictCounts {
+        self.objects.get(obj).copied().unwrap_or_default()
+    }
+
+    /// The conflicts at `key` in the map `obj`
+    pub fn for_key(&self, obj: &ExId, key: &str) -> ConflictCounts {
+        self.keys
+            .get(&(obj.clone(), key.to_string()))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Every object which has had a conflict, along with its counts
+    pub fn objects(&self) -> impl Iterator<Item = (&ExId, ConflictCounts)> {
+        self.objects.iter().map(|(obj, counts)| (obj, *counts))
+    }
+
+    /// Every map key which has had a conflict, along with its counts
+    pub fn keys(&self) -> impl Iterator<Item = (&ExId, &str, ConflictCounts)> {
+        self.keys
+            .iter()
+            .map(|((obj, key), counts)| (obj, key.as_str(), *counts))
+    }
+
+    /// Add the counts in `other` to these
+    pub(crate) fn merge(&mut self, other: &ConflictStats) {
+        for (obj, counts) in &other.objects {
+            self.objects.entry(obj.clone()).or_default().add(*counts);
+        }
+        for (key, counts) in &other.keys {
+            self.keys.entry(key.clone()).or_default().add(*counts);
+        }
+    }
+}
+
+/// Telemetry about conflicts
+impl Automerge {
+    /// Whether to count the conflicts which are created and resolved as changes are applied,
+    /// see [`Self::conflict_stats()`]
+    ///
+    /// Tracking is off by default. Turning it off discards the counts collected so far.
+    pub fn set_track_conflicts(&mut self, track: bool) -> &mut Self {
+        self.track_conflicts = track;
+        if !track {
+            self.conflict_stats = ConflictStats::default();
+        }
+        self
+    }
+
+    /// The conflicts created and resolved since tracking was turned on with
+    /// [`Self::set_track_conflicts()`] or since the stats were last taken with
+    /// [`Self::take_conflict_stats()`]
+    ///
+    /// Conflicts are created by applying concurrent changes to the same property and resolved by
+    /// overwriting or deleting a conflicted property. Local changes are counted when they are
+    /// made, so a transaction which is rolled back may still have been counted.
+    pub fn conflict_stats(&self) -> &ConflictStats {
+        &self.conflict_stats
+    }
+
+    /// Return the stats collected so far and start counting from zero, for reporting the stats
+    /// at regular intervals
+    pub fn take_conflict_stats(&mut self) -> ConflictStats {
+        std::mem::take(&mut self.conflict_stats)
+    }
+
+    /// The key of the op `idx` and the number of visible values at that key, if conflicts are
+    /// being tracked and `idx` can create or resolve a conflict. Must be called before `idx` is
+    /// inserted at `pos`.
+    pub(crate) fn conflicts_before(
+        &self,
+        obj: &ObjId,
+        pos: usize,
+        idx: OpIdx,
+    ) -> Option<(Key, usize)> {
+        if !self.track_conflicts {
+            return None;
+        }
+        let op = idx.as_op(&self.ops.osd);
+        if op.insert() || op.is_inc() || op.is_mark() {
+            return None;
+        }
+        let key = op.elemid_or_key();
+        Some((key, self.ops.visible_at_key(obj, pos, key)))
+    }
+
+    /// Count the conflict created or resolved at `key` by inserting an op at `pos`, given the
+    /// result of [`Self::conflicts_before()`]
+    pub(crate) fn record_conflicts(
+        &mut self,
+        obj: &ObjId,
+        pos: usize,
+        (key, before): (Key, usize),
+    ) {
+        let after = self.ops.visible_at_key(obj, pos, key);
+        let counts = match (before > 1, after > 1) {
+            (false, true) => ConflictCounts {
+                created: 1,
+                resolved: 0,
+            },
+            (true, false) => ConflictCounts {
+                created: 0,
+                resolved: 1,
+            },
+            _ => return,
+        };
+        let exid = self.ops.id_to_exid(obj.0);
+        if let Key::Map(prop) = key {
+            let name = self.ops.osd.props.get(prop).clone();
+            self.conflict_stats
+                .keys
+                .entry((exid.clone(), name))
+                .or_default()
+                .add(counts);
+        }
+        self.conflict_stats
+            .objects
+            .entry(exid)
+            .or_default()
+            .add(counts);
+    }
+}
@@ -0,0 +1,112 @@
+use itertools::Itertools;
+
+use crate::exid::ExId;
+use crate::patches::TextRepresentation;
+use crate::types::{Key, ObjId, ObjMeta, OpType};
+use crate::{Automerge, AutomergeError, Prop, Value};
+
+/// A property which has more than one value, see [`Automerge::conflicts()`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict<'a> {
+    /// The object the property is in
+    pub obj: ExId,
+    /// The key or index of the property
+    pub prop: Prop,
+    /// Every value of the property, in the order of [`crate::ReadDoc::get_all()`]
+    pub values: Vec<(Value<'a>, ExId)>,
+}
+
+/// Iterator created by [`Automerge::conflicts()`] and [`Automerge::conflicts_in()`]
+#[derive(Debug, Default)]
+pub struct Conflicts<'a> {
+    conflicts: std::vec::IntoIter<Conflict<'a>>,
+}
+
+impl<'a> Iterator for Conflicts<'a> {
+    type Item = Conflict<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.conflicts.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.conflicts.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for Conflicts<'a> {}
+
+impl Automerge {
+    /// Every property in the document which currently has more than one value
+    ///
+    /// Objects are visited depth first, starting from the root. Objects which are one of
+    /// several conflicting values are visited too. Indexes into text objects are in the same
+    /// units as [`crate::ReadDoc::length()`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ObjType, Prop, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = AutoCommit::new();
+    /// let settings = doc.put_object(ROOT, "settings", ObjType::Map).unwrap();
+    /// let mut other = doc.fork();
+    /// doc.put(&settings, "theme", "dark").unwrap();
+    /// other.put(&settings, "theme", "light").unwrap();
+    /// doc.merge(&mut other).unwrap();
+    ///
+    /// let conflicts = doc.conflicts().collect::<Vec<_>>();
+    /// assert_eq!(conflicts.len(), 1);
+    /// assert_eq!(conflicts[0].obj, settings);
+    /// assert_eq!(conflicts[0].prop, Prop::from("theme"));
+    /// assert_eq!(conflicts[0].values.len(), 2);
+    /// ```
+    pub fn conflicts(&self) -> Conflicts<'_> {
+        self.collect_conflicts(ObjMeta::root())
+    }
+
+    /// Like [`Self::conflicts()`] but only for `obj` and the objects inside it
+    pub fn conflicts_in<O: AsRef<ExId>>(&self, obj: O) -> Result<Conflicts<'_>, AutomergeError> {
+        let obj = self.exid_to_obj(obj.as_ref())?;
+        Ok(self.collect_conflicts(obj))
+    }
+
+    fn collect_conflicts(&self, obj: ObjMeta) -> Conflicts<'_> {
+        let mut conflicts = Vec::new();
+        let mut to_visit = vec![obj];
+        while let Some(obj) = to_visit.pop() {
+            let encoding = TextRepresentation::String.encoding(obj.typ);
+            let mut index = 0;
+            let mut children = Vec::new();
+            let ops_by_key = self.ops.iter_ops(&obj.id).chunk_by(|op| op.elemid_or_key());
+            for (key, ops) in ops_by_key.into_iter() {
+                let visible = ops.filter(|op| op.visible_at(None)).collect::<Vec<_>>();
+                let Some(winner) = visible.last() else {
+                    continue;
+                };
+                for op in &visible {
+                    if let OpType::Make(typ) = op.action() {
+                        children.push(ObjMeta::new(ObjId(*op.id()), *typ));
+                    }
+                }
+                let prop = match key {
+                    Key::Map(prop) => Prop::Map(self.ops.osd.props.get(prop).clone()),
+                    Key::Seq(_) => Prop::Seq(index),
+                };
+                index += winner.width(encoding);
+                if visible.len() > 1 {
+                    conflicts.push(Conflict {
+                        obj: self.id_to_exid(obj.id.0),
+                        prop,
+                        values: visible.iter().map(|op| op.tagged_value(None)).collect(),
+                    });
+                }
+            }
+            // visit children in the order they appear in the object
+            to_visit.extend(children.into_iter().rev());
+        }
+        Conflicts {
+            conflicts: conflicts.into_iter(),
+        }
+    }
+}
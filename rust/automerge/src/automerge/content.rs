@@ -0,0 +1,218 @@
+use std::fmt;
+
+use itertools::{EitherOrBoth, Itertools};
+
+use crate::exid::ExId;
+use crate::path::location;
+use crate::{Automerge, ObjType, Prop, ReadDoc, Value, ROOT};
+
+/// A place where the content of two documents differs
+///
+/// This is returned by [`Automerge::content_differences()`]. Each difference has the path of
+/// the value which differs, from the root of the document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentDifference {
+    /// This document has a value at the path and the other document does not
+    OnlyInSelf(Vec<Prop>),
+    /// The other document has a value at the path and this document does not
+    OnlyInOther(Vec<Prop>),
+    /// Both documents have a value at the path but they are different scalars, objects of
+    /// different types, or text objects with different text or marks
+    Changed(Vec<Prop>),
+}
+
+impl ContentDifference {
+    pub fn path(&self) -> &[Prop] {
+        match self {
+            Self::OnlyInSelf(path) | Self::OnlyInOther(path) | Self::Changed(path) => path,
+        }
+    }
+}
+
+impl fmt::Display for ContentDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OnlyInSelf(path) => write!(f, "only this document has {}", location(path)),
+            Self::OnlyInOther(path) => write!(f, "only the other document has {}", location(path)),
+            Self::Changed(path) => write!(f, "the documents differ at {}", location(path)),
+        }
+    }
+}
+
+/// Content comparison
+impl Automerge {
+    /// Whether this document and `other` have the same content
+    ///
+    /// This compares the current state of the documents, not how they got there: documents
+    /// with different histories and actors are equal if every map, list and text object holds
+    /// the same values. Scalar values must have the same type as well as the same value, so
+    /// an `Int` is not equal to a `Uint`. Conflicting values which lost do not matter.
+    ///
+    /// This stops at the first difference, use [`Self::content_differences()`] to find out
+    /// where the documents differ.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc1 = AutoCommit::new();
+    /// doc1.put(ROOT, "count", 1).unwrap();
+    /// let mut doc2 = AutoCommit::new();
+    /// doc2.put(ROOT, "count", 2).unwrap();
+    /// doc2.put(ROOT, "count", 1).unwrap();
+    /// assert!(doc1.equals_content(&doc2));
+    ///
+    /// doc2.put(ROOT, "count", 1_u64).unwrap();
+    /// assert!(!doc1.equals_content(&doc2));
+    /// ```
+    pub fn equals_content<R: ReadDoc>(&self, other: &R) -> bool {
+        let mut comparison = Comparison::new(self, other, true);
+        comparison.obj(&ROOT, &ROOT, ObjType::Map, &mut Vec::new());
+        comparison.differences.is_empty()
+    }
+
+    /// Every place where the content of this document and `other` differs
+    ///
+    /// See [`Self::equals_content()`] for what is compared. A value which only one of the
+    /// documents has, or which is an object in one document and something else in the other,
+    /// is reported once without reporting anything inside it. The differences are ordered by
+    /// path, with map keys in lexicographic order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ContentDifference, ObjType, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc1 = AutoCommit::new();
+    /// let list = doc1.put_object(ROOT, "list", ObjType::List).unwrap();
+    /// doc1.insert(&list, 0, "a").unwrap();
+    /// let mut doc2 = doc1.fork();
+    /// doc2.insert(&list, 1, "b").unwrap();
+    /// doc2.put(ROOT, "title", "list").unwrap();
+    ///
+    /// assert_eq!(
+    ///     doc1.content_differences(&doc2),
+    ///     vec![
+    ///         ContentDifference::OnlyInOther(vec!["list".into(), 1.into()]),
+    ///         ContentDifference::OnlyInOther(vec!["title".into()]),
+    ///     ]
+    /// );
+    /// ```
+    pub fn content_differences<R: ReadDoc>(&self, other: &R) -> Vec<ContentDifference> {
+        let mut comparison = Comparison::new(self, other, false);
+        comparison.obj(&ROOT, &ROOT, ObjType::Map, &mut Vec::new());
+        comparison.differences
+    }
+}
+
+struct Comparison<'a, R> {
+    this: &'a Automerge,
+    other: &'a R,
+    /// Whether to stop at the first difference
+    first_only: bool,
+    differences: Vec<ContentDifference>,
+}
+
+impl<'a, R: ReadDoc> Comparison<'a, R> {
+    fn new(this: &'a Automerge, other: &'a R, first_only: bool) -> Self {
+        Comparison {
+            this,
+            other,
+            first_only,
+            differences: Vec::new(),
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.first_only && !self.differences.is_empty()
+    }
+
+    /// Compare `this_obj` and `other_obj`, which are both of type `typ`
+    fn obj(&mut self, this_obj: &ExId, other_obj: &ExId, typ: ObjType, path: &mut Vec<Prop>) {
+        match typ {
+            ObjType::Map | ObjType::Table => {
+                let this_keys = self.this.keys(this_obj).collect::<Vec<_>>();
+                let other_keys = self.other.keys(other_obj).collect::<Vec<_>>();
+                let keys = this_keys
+                    .into_iter()
+                    .merge_join_by(other_keys, |a, b| a.cmp(b));
+                for key in keys {
+                    if self.done() {
+                        return;
+                    }
+                    let (key, this, other) = match key {
+                        EitherOrBoth::Both(key, _) => (key, true, true),
+                        EitherOrBoth::Left(key) => (key, true, false),
+                        EitherOrBoth::Right(key) => (key, false, true),
+                    };
+                    path.push(Prop::Map(key));
+                    self.prop(this_obj, other_obj, (this, other), path);
+                    path.pop();
+                }
+            }
+            ObjType::List => {
+                let this_len = self.this.length(this_obj);
+                let other_len = self.other.length(other_obj);
+                for index in 0..this_len.max(other_len) {
+                    if self.done() {
+                        return;
+                    }
+                    path.push(Prop::Seq(index));
+                    self.prop(
+                        this_obj,
+                        other_obj,
+                        (index < this_len, index < other_len),
+                        path,
+                    );
+                    path.pop();
+                }
+            }
+            ObjType::Text => {
+                let same = self.this.text(this_obj).ok() == self.other.text(other_obj).ok()
+                    && self.this.marks(this_obj).ok() == self.other.marks(other_obj).ok();
+                if !same {
+                    self.differences
+                        .push(ContentDifference::Changed(path.clone()));
+                }
+            }
+        }
+    }
+
+    /// Compare the values at the last prop of `path` in `this_obj` and `other_obj`, `present`
+    /// says which of the objects have a value there
+    fn prop(
+        &mut self,
+        this_obj: &ExId,
+        other_obj: &ExId,
+        present: (bool, bool),
+        path: &mut Vec<Prop>,
+    ) {
+        let prop = path.last().cloned().unwrap();
+        match present {
+            (true, false) => {
+                self.differences
+                    .push(ContentDifference::OnlyInSelf(path.clone()));
+                return;
+            }
+            (false, true) => {
+                self.differences
+                    .push(ContentDifference::OnlyInOther(path.clone()));
+                return;
+            }
+            _ => {}
+        }
+        let this = self.this.get(this_obj, prop.clone()).ok().flatten();
+        let other = self.other.get(other_obj, prop).ok().flatten();
+        match (this, other) {
+            (Some((Value::Object(a), this_id)), Some((Value::Object(b), other_id))) if a == b => {
+                self.obj(&this_id, &other_id, a, path)
+            }
+            (Some((a, _)), Some((b, _))) if a.is_scalar() && a == b => {}
+            (None, None) => {}
+            _ => self
+                .differences
+                .push(ContentDifference::Changed(path.clone())),
+        }
+    }
+}
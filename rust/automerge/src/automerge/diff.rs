@@ -4,6 +4,7 @@ use std::sync::Arc;
 
 use crate::iter::Keys;
 use crate::iter::ListRange;
+use crate::iter::MapEntriesAll;
 use crate::iter::MapRange;
 use crate::iter::Values;
 use crate::marks::Mark;
@@ -16,6 +17,7 @@ use crate::{
     types::{Clock, ListEncoding, Op, Prop},
     value::Value,
     Automerge, AutomergeError, ChangeHash, Cursor, ObjId as ExId, ObjType, OpType, ReadDoc,
+    ScalarValue,
 };
 
 #[derive(Clone, Debug)]
@@ -269,8 +271,11 @@ fn get_prop<'a>(doc: &'a Automerge, op: Op<'a>) -> Option<&'a str> {
 }
 
 fn get_inc(before: &Winner<'_>, after: &Winner<'_>) -> Option<i64> {
-    if before.op.is_counter() && after.op.is_counter() {
-        let n = after.op.inc_at(after.clock) - before.op.inc_at(before.clock);
+    if let (OpType::Put(ScalarValue::Counter(b)), OpType::Put(ScalarValue::Counter(a))) =
+        (before.op.action(), after.op.action())
+    {
+        let n = (after.op.counter_at(a, Some(after.clock)) - a.start)
+            - (before.op.counter_at(b, Some(before.clock)) - b.start);
         if n != 0 {
             return Some(n);
         }
@@ -381,6 +386,18 @@ impl<'a, 'b> ReadDoc for ReadDocAt<'a, 'b> {
         self.doc.values_at(obj, heads)
     }
 
+    fn map_entries_all<O: AsRef<ExId>>(&self, obj: O) -> MapEntriesAll<'_> {
+        self.doc.map_entries_all_at(obj, self.heads)
+    }
+
+    fn map_entries_all_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> MapEntriesAll<'_> {
+        self.doc.map_entries_all_at(obj, heads)
+    }
+
     fn length<O: AsRef<ExId>>(&self, obj: O) -> usize {
         self.doc.length_at(obj, self.heads)
     }
@@ -405,6 +422,21 @@ impl<'a, 'b> ReadDoc for ReadDocAt<'a, 'b> {
         self.doc.text_at(obj, heads)
     }
 
+    fn text_chars<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+    ) -> Result<crate::iter::TextChars<'_>, AutomergeError> {
+        self.doc.text_chars_at(obj, self.heads)
+    }
+
+    fn text_chars_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<crate::iter::TextChars<'_>, AutomergeError> {
+        self.doc.text_chars_at(obj, heads)
+    }
+
     fn marks<O: AsRef<ExId>>(&self, obj: O) -> Result<Vec<Mark<'_>>, AutomergeError> {
         self.doc.marks_at(obj, self.heads)
     }
@@ -564,6 +596,10 @@ mod tests {
         Insert {
             values: Vec<Value<'static>>,
         },
+        Splice {
+            deleted: usize,
+            inserted: Vec<Value<'static>>,
+        },
         DelMap,
         DelSeq,
         Increment(i64),
@@ -630,6 +666,17 @@ mod tests {
                     },
                     path: ex_path_and(path, index),
                 },
+                PatchAction::Splice {
+                    index,
+                    deleted,
+                    inserted,
+                } => ObservedPatch {
+                    action: ObservedAction::Splice {
+                        deleted,
+                        inserted: inserted.into_iter().map(|(v, _, _)| v).collect(),
+                    },
+                    path: ex_path_and(path, index),
+                },
                 PatchAction::SpliceText { index, value, .. } => ObservedPatch {
                     action: ObservedAction::SpliceText(value.make_string()),
                     path: ex_path_and(path, index),
@@ -0,0 +1,114 @@
+use crate::exid::ExId;
+use crate::iter::Span;
+use crate::marks::ExpandMark;
+use crate::transaction::{BlockOrText, Transactable};
+use crate::value::Counter;
+use crate::{Automerge, AutomergeError, ObjType, ReadDoc, ScalarValue, Value, ROOT};
+
+/// Extracting subtrees
+impl Automerge {
+    /// Create a new document whose root has the content of the map `obj` in this document
+    ///
+    /// This is for splitting a document which has grown too large into several documents which
+    /// are synced separately. The new document has a new actor ID and a single change which
+    /// creates the current content of `obj`: every key, and every nested map, list and text
+    /// object with its marks and blocks. Counters are copied with their current value and their
+    /// bounds.
+    ///
+    /// Only the current content is copied: the new document does not contain any of the history
+    /// of `obj`, nor values which lost a conflict. Its objects have new IDs, so changes made to
+    /// `obj` in this document after the extraction cannot be merged into the new one. As the
+    /// root of a document is always a map only maps can be extracted, to split off a list or
+    /// text object extract the map containing it.
+    ///
+    /// Marks are created with [`ExpandMark::After`], as how a mark expands is not part of the
+    /// marks returned by [`ReadDoc::marks()`].
+    ///
+    /// # Errors
+    ///
+    /// [`AutomergeError::InvalidOp`] if `obj` is a list or text object, and
+    /// [`AutomergeError::InvalidObjId`] if it is not in the document.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ObjType, ReadDoc, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = AutoCommit::new();
+    /// let settings = doc.put_object(ROOT, "settings", ObjType::Map).unwrap();
+    /// doc.put(&settings, "theme", "dark").unwrap();
+    ///
+    /// let extracted = doc.extract(&settings).unwrap();
+    /// assert_eq!(
+    ///     extracted.get(ROOT, "theme").unwrap().unwrap().0.to_str(),
+    ///     Some("dark")
+    /// );
+    /// ```
+    pub fn extract(&self, obj: &ExId) -> Result<Automerge, AutomergeError> {
+        let typ = self.object_type(obj)?;
+        if !matches!(typ, ObjType::Map | ObjType::Table) {
+            return Err(AutomergeError::InvalidOp(typ));
+        }
+        let mut doc = Automerge::new();
+        let mut tx = doc.transaction();
+        copy_obj(self, obj, typ, &mut tx, &ROOT)?;
+        tx.commit();
+        Ok(doc)
+    }
+}
+
+/// Copy the content of `obj` in `from`, which has type `typ`, to `to` in `tx`
+fn copy_obj<T: Transactable>(
+    from: &Automerge,
+    obj: &ExId,
+    typ: ObjType,
+    tx: &mut T,
+    to: &ExId,
+) -> Result<(), AutomergeError> {
+    match typ {
+        ObjType::Map | ObjType::Table => {
+            for item in from.map_range(obj, ..) {
+                match item.value {
+                    Value::Object(typ) => {
+                        let child = tx.put_object(to, item.key, typ)?;
+                        copy_obj(from, &item.id, typ, tx, &child)?;
+                    }
+                    Value::Scalar(value) => tx.put(to, item.key, current(&value))?,
+                }
+            }
+        }
+        ObjType::List => {
+            for item in from.list_range(obj, ..) {
+                match item.value {
+                    Value::Object(typ) => {
+                        let child = tx.insert_object(to, item.index, typ)?;
+                        copy_obj(from, &item.id, typ, tx, &child)?;
+                    }
+                    Value::Scalar(value) => tx.insert(to, item.index, current(&value))?,
+                }
+            }
+        }
+        ObjType::Text => {
+            let spans = from.spans(obj)?.map(|span| match span {
+                Span::Text(text, _) => BlockOrText::Text(text.into()),
+                Span::Block(block) => BlockOrText::Block(block),
+            });
+            tx.update_spans(to, spans)?;
+            for mark in from.marks(obj)? {
+                tx.mark(to, mark, ExpandMark::After)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `value`, with counters replaced by a counter with the same bounds which starts at their current
+/// value
+fn current(value: &ScalarValue) -> ScalarValue {
+    match value {
+        ScalarValue::Counter(c) => {
+            ScalarValue::Counter(Counter::bounded(c.into(), c.min(), c.max()))
+        }
+        other => other.clone(),
+    }
+}
@@ -0,0 +1,154 @@
+use std::ops::Range;
+
+use crate::exid::ExId;
+use crate::{ActorId, Automerge, AutomergeError, Change, ChangeHash, Prop, ReadDoc};
+
+/// A summary of a change, see [`Automerge::history()`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeSummary<'a> {
+    pub hash: ChangeHash,
+    pub actor: &'a ActorId,
+    pub seq: u64,
+    /// The time the change was made, in milliseconds since the unix epoch
+    pub timestamp: i64,
+    pub message: Option<&'a str>,
+    /// The number of operations in the change
+    pub num_ops: usize,
+    pub deps: &'a [ChangeHash],
+}
+
+impl<'a> From<&'a Change> for ChangeSummary<'a> {
+    fn from(change: &'a Change) -> Self {
+        Self {
+            hash: change.hash(),
+            actor: change.actor_id(),
+            seq: change.seq(),
+            timestamp: change.timestamp(),
+            message: change.message().map(String::as_str),
+            num_ops: change.len(),
+            deps: change.deps(),
+        }
+    }
+}
+
+/// Iterator created by [`Automerge::history()`] and [`Automerge::history_at()`]
+///
+/// Iterating backwards yields the most recent changes first.
+#[derive(Debug, Clone)]
+pub struct History<'a> {
+    changes: &'a im::Vector<Change>,
+    indexes: Indexes,
+}
+
+#[derive(Debug, Clone)]
+enum Indexes {
+    All(Range<usize>),
+    Some(std::vec::IntoIter<usize>),
+}
+
+impl<'a> Iterator for History<'a> {
+    type Item = ChangeSummary<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = match &mut self.indexes {
+            Indexes::All(range) => range.next(),
+            Indexes::Some(indexes) => indexes.next(),
+        }?;
+        Some(ChangeSummary::from(&self.changes[index]))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.indexes {
+            Indexes::All(range) => range.size_hint(),
+            Indexes::Some(indexes) => indexes.size_hint(),
+        }
+    }
+}
+
+impl<'a> DoubleEndedIterator for History<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let index = match &mut self.indexes {
+            Indexes::All(range) => range.next_back(),
+            Indexes::Some(indexes) => indexes.next_back(),
+        }?;
+        Some(ChangeSummary::from(&self.changes[index]))
+    }
+}
+
+impl<'a> ExactSizeIterator for History<'a> {}
+
+impl Automerge {
+    /// Summaries of every change in the document in causal order, so every change comes after
+    /// the changes it depends on
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ROOT};
+    /// # use automerge::transaction::{CommitOptions, Transactable};
+    /// let mut doc = AutoCommit::new();
+    /// doc.put(ROOT, "title", "draft").unwrap();
+    /// doc.commit_with(CommitOptions::default().with_message("start"));
+    /// doc.put(ROOT, "title", "final").unwrap();
+    /// doc.commit_with(CommitOptions::default().with_message("finish"));
+    ///
+    /// let log = doc.history().rev().map(|c| c.message.unwrap()).collect::<Vec<_>>();
+    /// assert_eq!(log, vec!["finish", "start"]);
+    /// ```
+    pub fn history(&self) -> History<'_> {
+        History {
+            changes: &self.history,
+            indexes: Indexes::All(0..self.history.len()),
+        }
+    }
+
+    /// Like [`Self::history()`] but only the changes which `heads` depend on, including `heads`
+    /// themselves
+    pub fn history_at(&self, heads: &[ChangeHash]) -> History<'_> {
+        let clock = self.clock_at(heads);
+        let mut indexes = Vec::new();
+        for (actor_index, actor_changes) in &self.states {
+            if let Some(clock_data) = clock.get_for_actor(actor_index) {
+                indexes.extend(&actor_changes[..clock_data.seq as usize]);
+            }
+        }
+        indexes.sort_unstable();
+        History {
+            changes: &self.history,
+            indexes: Indexes::Some(indexes.into_iter()),
+        }
+    }
+
+    /// A summary of the change which wrote the current value of `prop` in `obj`
+    ///
+    /// Returns `None` if there is no value at `prop`. If the value is conflicted this is the
+    /// change which wrote the value [`crate::ReadDoc::get()`] returns.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ROOT};
+    /// # use automerge::transaction::{CommitOptions, Transactable};
+    /// let mut doc = AutoCommit::new();
+    /// doc.put(ROOT, "title", "draft").unwrap();
+    /// doc.commit_with(CommitOptions::default().with_time(1_700_000_000_000));
+    ///
+    /// let actor = doc.get_actor().clone();
+    /// let modified = doc.last_modified(ROOT, "title").unwrap().unwrap();
+    /// assert_eq!(modified.actor, &actor);
+    /// assert_eq!(modified.timestamp, 1_700_000_000_000);
+    /// ```
+    pub fn last_modified<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Option<ChangeSummary<'_>>, AutomergeError> {
+        let Some((_, id)) = self.get_for(obj.as_ref(), prop.into(), None)? else {
+            return Ok(None);
+        };
+        Ok(self
+            .hash_for_opid(&id)
+            .and_then(|hash| self.get_change_by_hash(&hash))
+            .map(ChangeSummary::from))
+    }
+}
@@ -0,0 +1,41 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::link::{DocId, LinkError, LinkResolver};
+use crate::Automerge;
+
+#[derive(Clone)]
+pub(crate) struct LinkResolverRef(Arc<dyn LinkResolver>);
+
+impl fmt::Debug for LinkResolverRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("LinkResolver").finish()
+    }
+}
+
+/// Links
+impl Automerge {
+    /// Find the documents links refer to with `resolver`, see [`crate::link`]
+    ///
+    /// Documents created with [`Self::fork()`] or [`Self::fork_at()`] share the resolver.
+    pub fn set_link_resolver(&mut self, resolver: Arc<dyn LinkResolver>) {
+        self.link_resolver = Some(LinkResolverRef(resolver));
+    }
+
+    pub fn clear_link_resolver(&mut self) {
+        self.link_resolver = None;
+    }
+
+    /// The document `link` refers to
+    ///
+    /// # Errors
+    ///
+    /// This fails if there is no resolver, the resolver does not know the document or the
+    /// resolver fails.
+    pub fn resolve_link(&self, link: &DocId) -> Result<Automerge, LinkError> {
+        let LinkResolverRef(resolver) = self.link_resolver.as_ref().ok_or(LinkError::NoResolver)?;
+        resolver
+            .resolve(link)?
+            .ok_or_else(|| LinkError::Missing(link.clone()))
+    }
+}
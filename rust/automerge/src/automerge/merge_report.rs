@@ -0,0 +1,116 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use crate::exid::ExId;
+use crate::patches::{PatchLog, TextRepresentation};
+use crate::{ActorId, Automerge, AutomergeError, ChangeHash, ConflictCounts, ConflictStats};
+
+/// What [`Automerge::merge_with_report()`] did
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MergeReport {
+    /// The heads of the document after the merge
+    pub heads: Vec<ChangeHash>,
+    /// The changes which were applied, in the order they were applied
+    ///
+    /// Changes which were quarantined rather than applied are not included.
+    pub changes: Vec<ChangeHash>,
+    /// The number of changes applied from each actor
+    pub changes_by_actor: BTreeMap<ActorId, usize>,
+    /// The objects which the applied changes modified
+    pub objects: BTreeSet<ExId>,
+    /// The conflicts which the applied changes created and resolved
+    pub conflicts: ConflictStats,
+}
+
+impl MergeReport {
+    /// Whether the merge applied no changes
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The conflicts created and resolved in every object
+    pub fn total_conflicts(&self) -> ConflictCounts {
+        self.conflicts.total()
+    }
+}
+
+impl Automerge {
+    /// Like [`Self::merge_read()`] but returns a report of what the merge did
+    ///
+    /// Conflicts are counted whether or not [`Self::set_track_conflicts()`] is on. If it is on
+    /// the conflicts of the merge are also added to [`Self::conflict_stats()`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = AutoCommit::new();
+    /// doc.put(ROOT, "theme", "light").unwrap();
+    /// let mut alice = doc.fork();
+    /// doc.put(ROOT, "theme", "dark").unwrap();
+    /// alice.put(ROOT, "theme", "solarized").unwrap();
+    /// alice.put(ROOT, "font", "mono").unwrap();
+    ///
+    /// let report = doc.merge_with_report(&mut alice).unwrap();
+    /// assert_eq!(report.changes.len(), 1);
+    /// assert_eq!(report.changes_by_actor[alice.get_actor()], 1);
+    /// assert_eq!(report.total_conflicts().created, 1);
+    /// assert_eq!(report.conflicts.for_key(&ROOT, "theme").created, 1);
+    /// ```
+    pub fn merge_with_report(&mut self, other: &Self) -> Result<MergeReport, AutomergeError> {
+        self.merge_with_report_and_log_patches(
+            other,
+            &mut PatchLog::inactive(TextRepresentation::default()),
+        )
+    }
+
+    /// Like [`Self::merge_with_report()`] but logs the resulting changes to the current state
+    /// of the document to `patch_log`
+    pub fn merge_with_report_and_log_patches(
+        &mut self,
+        other: &Self,
+        patch_log: &mut PatchLog,
+    ) -> Result<MergeReport, AutomergeError> {
+        let candidates = self
+            .get_changes_added(other)
+            .into_iter()
+            .map(|c| c.hash())
+            .collect::<HashSet<_>>();
+        let was_tracking = std::mem::replace(&mut self.track_conflicts, true);
+        let previous_stats = std::mem::take(&mut self.conflict_stats);
+        let history_len = self.history.len();
+
+        let result = self.merge_and_log_patches(other, patch_log);
+
+        let conflicts = std::mem::replace(&mut self.conflict_stats, previous_stats);
+        self.track_conflicts = was_tracking;
+        if was_tracking {
+            self.conflict_stats.merge(&conflicts);
+        }
+        let heads = result?;
+
+        let mut report = MergeReport {
+            heads,
+            conflicts,
+            ..Default::default()
+        };
+        let applied = self
+            .history
+            .iter()
+            .skip(history_len)
+            .filter(|c| candidates.contains(&c.hash()))
+            .cloned()
+            .collect::<Vec<_>>();
+        for change in applied {
+            report.changes.push(change.hash());
+            *report
+                .changes_by_actor
+                .entry(change.actor_id().clone())
+                .or_default() += 1;
+            for (obj, _, _) in self.import_ops(&change, change.iter_stored_ops()) {
+                report.objects.insert(self.id_to_exid(obj.0));
+            }
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,38 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::metrics::Metrics;
+use crate::Automerge;
+
+#[derive(Clone)]
+pub(crate) struct MetricsRef(Arc<dyn Metrics>);
+
+impl fmt::Debug for MetricsRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Metrics").finish()
+    }
+}
+
+/// Metrics
+impl Automerge {
+    /// Tell `metrics` about the work this document does from now on, see [`crate::metrics`]
+    ///
+    /// Like [`Self::freeze()`] this is a local policy: it is not saved with the document. Unlike
+    /// the audit sink it is kept by documents created with [`Self::fork()`] and
+    /// [`Self::fork_at()`], as the counts describe the process rather than the replica.
+    pub fn set_metrics(&mut self, metrics: Arc<dyn Metrics>) {
+        self.metrics = Some(MetricsRef(metrics));
+    }
+
+    /// Stop reporting metrics, see [`Self::set_metrics()`]
+    pub fn clear_metrics(&mut self) {
+        self.metrics = None;
+    }
+
+    /// Call `f` with the installed metrics, if there are any
+    pub(crate) fn report<F: FnOnce(&dyn Metrics)>(&self, f: F) {
+        if let Some(MetricsRef(metrics)) = &self.metrics {
+            f(metrics.as_ref())
+        }
+    }
+}
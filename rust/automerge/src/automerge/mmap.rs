@@ -0,0 +1,45 @@
+use std::fs::File;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::{Automerge, AutomergeError, LoadOptions};
+
+/// Loading documents from memory mapped files
+///
+/// These are available with the `mmap` feature.
+impl Automerge {
+    /// Load the document saved in the file at `path` by memory mapping it
+    ///
+    /// The file is parsed directly from the mapping rather than first being read into a heap
+    /// allocated buffer, which avoids holding two copies of a large document in memory whilst
+    /// it loads. The loaded document still owns all of its data, it does not borrow from the
+    /// file, so the mapping is released once loading completes. If the file is small enough to
+    /// read into memory, or may be written to whilst it loads, use [`Self::load_from()`] instead.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this process or any other, until this
+    /// function returns. Changing the file whilst it is mapped changes memory which automerge
+    /// assumes is immutable, and truncating it can crash the process when the missing pages are
+    /// read, see [`memmap2::Mmap`].
+    pub unsafe fn load_mmap<P: AsRef<Path>>(path: P) -> Result<Self, AutomergeError> {
+        Self::load_mmap_with_options(path, LoadOptions::default())
+    }
+
+    /// Like [`Self::load_mmap()`] but with options, see [`Self::load_with_options()`]
+    ///
+    /// # Safety
+    ///
+    /// The same as for [`Self::load_mmap()`].
+    pub unsafe fn load_mmap_with_options<P: AsRef<Path>>(
+        path: P,
+        options: LoadOptions<'_>,
+    ) -> Result<Self, AutomergeError> {
+        let file = File::open(path).map_err(AutomergeError::Io)?;
+        // SAFETY: the caller guarantees that the file is not modified whilst it is mapped, and
+        // the mapping does not outlive this call
+        let data = unsafe { Mmap::map(&file) }.map_err(AutomergeError::Io)?;
+        Self::load_with_options(&data, options)
+    }
+}
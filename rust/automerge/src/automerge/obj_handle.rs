@@ -0,0 +1,163 @@
+use std::cell::{OnceCell, RefCell};
+use std::ops::RangeFrom;
+
+use crate::clock::Clock;
+use crate::exid::ExId;
+use crate::iter::{Keys, ListRange, Values};
+use crate::patches::TextRepresentation;
+use crate::types::ObjMeta;
+use crate::{Automerge, AutomergeError, ObjType, Prop, Value};
+
+/// A handle to one object of a document, see [`Automerge::handle()`]
+///
+/// The handle resolves the object once, when it is created, rather than on every read as the
+/// methods of [`crate::ReadDoc`] do. It also caches the length of the object and, for lists, the
+/// position of the last index read, so reading consecutive indexes of a list steps through the
+/// list rather than searching it for each index.
+///
+/// # Example
+///
+/// ```
+/// # use automerge::{AutoCommit, ObjType, ROOT};
+/// # use automerge::transaction::Transactable;
+/// let mut doc = AutoCommit::new();
+/// let list = doc.put_object(ROOT, "numbers", ObjType::List).unwrap();
+/// for i in 0..10 {
+///     doc.insert(&list, i, i as i64).unwrap();
+/// }
+///
+/// let numbers = doc.handle(&list).unwrap();
+/// let mut total = 0;
+/// for i in 0..numbers.len() {
+///     let (value, _) = numbers.get(i).unwrap().unwrap();
+///     total += value.to_i64().unwrap();
+/// }
+/// assert_eq!(total, 45);
+/// ```
+#[derive(Debug)]
+pub struct ObjHandle<'a> {
+    doc: &'a Automerge,
+    id: ExId,
+    obj: ObjMeta,
+    clock: Option<Clock>,
+    len: OnceCell<usize>,
+    /// The index the list range will yield next and the range itself
+    cursor: RefCell<Option<(usize, ListRange<'a, RangeFrom<usize>>)>>,
+}
+
+impl Automerge {
+    /// A handle to the object `obj`, for reading it repeatedly
+    ///
+    /// Returns an error if `obj` is not an object in this document.
+    pub fn handle<O: AsRef<ExId>>(&self, obj: O) -> Result<ObjHandle<'_>, AutomergeError> {
+        ObjHandle::new(self, obj.as_ref(), None)
+    }
+}
+
+impl<'a> ObjHandle<'a> {
+    pub(crate) fn new(
+        doc: &'a Automerge,
+        id: &ExId,
+        clock: Option<Clock>,
+    ) -> Result<Self, AutomergeError> {
+        Ok(Self {
+            doc,
+            id: id.clone(),
+            obj: doc.exid_to_obj(id)?,
+            clock,
+            len: OnceCell::new(),
+            cursor: RefCell::new(None),
+        })
+    }
+
+    /// The ID of the object
+    pub fn id(&self) -> &ExId {
+        &self.id
+    }
+
+    pub fn obj_type(&self) -> ObjType {
+        self.obj.typ
+    }
+
+    /// See [`crate::ReadDoc::length()`]
+    pub fn len(&self) -> usize {
+        *self.len.get_or_init(|| {
+            self.doc.ops().length(
+                &self.obj.id,
+                TextRepresentation::String.encoding(self.obj.typ),
+                self.clock.clone(),
+            )
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// See [`crate::ReadDoc::get()`]
+    pub fn get<P: Into<Prop>>(&self, prop: P) -> Result<Option<(Value<'a>, ExId)>, AutomergeError> {
+        let prop = prop.into();
+        match prop {
+            Prop::Seq(index)
+                if self.obj.typ == ObjType::List && self.doc.conflict_policy().is_default() =>
+            {
+                Ok(self.get_index(index))
+            }
+            prop => Ok(self.doc.get_in(&self.obj, prop, self.clock.as_ref())),
+        }
+    }
+
+    /// A handle to the object at `prop` in this object, or `None` if there is no object there
+    pub fn handle<P: Into<Prop>>(&self, prop: P) -> Result<Option<ObjHandle<'a>>, AutomergeError> {
+        match self.get(prop)? {
+            Some((Value::Object(_), id)) => {
+                Ok(Some(ObjHandle::new(self.doc, &id, self.clock.clone())?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// See [`crate::ReadDoc::values()`]
+    pub fn iter(&self) -> Values<'a> {
+        if let Some(ops) = self.doc.resolved_ops(&self.obj, self.clock.as_ref()) {
+            let values = ops.iter().map(|op| op.tagged_value(self.clock.as_ref()));
+            return Values::resolved(values.collect());
+        }
+        Values::new(
+            self.doc.ops().top_ops(&self.obj.id, self.clock.clone()),
+            self.clock.clone(),
+        )
+    }
+
+    /// See [`crate::ReadDoc::keys()`]
+    pub fn keys(&self) -> Keys<'a> {
+        self.doc.ops().keys(&self.obj.id, self.clock.clone())
+    }
+
+    fn get_index(&self, index: usize) -> Option<(Value<'a>, ExId)> {
+        let mut cursor = self.cursor.borrow_mut();
+        let range = match cursor.as_mut() {
+            Some((next, range)) if *next == index => range,
+            _ => {
+                let range = self.doc.ops().list_range(
+                    &self.obj.id,
+                    index..,
+                    TextRepresentation::Array.encoding(self.obj.typ),
+                    self.clock.clone(),
+                );
+                &mut cursor.insert((index, range)).1
+            }
+        };
+        let item = range.next();
+        match (item, cursor.as_mut()) {
+            (Some(item), Some((next, _))) => {
+                *next = index + 1;
+                Some((item.value, item.id))
+            }
+            _ => {
+                *cursor = None;
+                None
+            }
+        }
+    }
+}
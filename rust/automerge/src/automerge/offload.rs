@@ -0,0 +1,52 @@
+use crate::{Automerge, AutomergeError, SharedAutomerge};
+
+/// Saving and loading on a worker thread
+///
+/// These are available with the `tokio` feature. Encoding and decoding large documents is CPU
+/// heavy, these methods run it on tokio's blocking thread pool so that it doesn't stall the async
+/// runtime. They must be called from within a tokio runtime.
+///
+/// If the runtime shuts down before the work starts these return [`AutomergeError::Cancelled`].
+impl Automerge {
+    /// Like [`Self::save()`] but run on tokio's blocking thread pool
+    ///
+    /// The work happens on another thread so this takes a copy of the document first. The copy
+    /// shares its history and op set with `self` (see [`Self::fork()`]) so this is cheap, but
+    /// any settings which are not shared (the queue of pending changes for instance) are cloned.
+    /// To avoid the copy altogether use [`SharedAutomerge::save_async()`].
+    pub async fn save_async(&self) -> Result<Vec<u8>, AutomergeError> {
+        let doc = self.clone();
+        run_blocking(move || doc.save()).await
+    }
+
+    /// Like [`Self::load()`] but run on tokio's blocking thread pool
+    pub async fn load_async(data: Vec<u8>) -> Result<Self, AutomergeError> {
+        run_blocking(move || Self::load(&data)).await?
+    }
+}
+
+impl SharedAutomerge {
+    /// Save the document on tokio's blocking thread pool
+    ///
+    /// The read lock is held on the worker thread for the duration of the save, so unlike
+    /// [`Automerge::save_async()`] the document is not copied.
+    pub async fn save_async(&self) -> Result<Vec<u8>, AutomergeError> {
+        let doc = self.clone();
+        run_blocking(move || doc.read().save()).await
+    }
+}
+
+async fn run_blocking<F, T>(f: F) -> Result<T, AutomergeError>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => Ok(result),
+        Err(e) => match e.try_into_panic() {
+            Ok(panic) => std::panic::resume_unwind(panic),
+            // The task can only be cancelled if the runtime is shutting down
+            Err(_) => Err(AutomergeError::Cancelled),
+        },
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::HashSet;
+
+use crate::patches::{PatchLog, TextRepresentation};
+use crate::storage::{self, load};
+use crate::{Automerge, AutomergeError, Change, ChangeHash};
+
+/// Holding incoming changes back for moderation
+///
+/// Changes in the quarantine have not been applied to the document, they are held until they
+/// are either accepted with [`Automerge::accept_quarantined()`], at which point they are applied
+/// as if they had just been received, or rejected with [`Automerge::reject_quarantined()`].
+///
+/// Changes which depend on a quarantined change cannot be applied until the quarantined change
+/// is accepted. Until then they wait in the queue of changes which are not causally ready, just
+/// as if their dependency was missing.
+impl Automerge {
+    /// Hold `changes` in the quarantine rather than applying them
+    ///
+    /// Changes which have already been applied or which are already in the quarantine are
+    /// ignored.
+    pub fn quarantine_changes<I: IntoIterator<Item = Change>>(&mut self, changes: I) {
+        for change in changes {
+            self.quarantine_change(change);
+        }
+    }
+
+    pub(super) fn quarantine_change(&mut self, change: Change) {
+        let hash = change.hash();
+        if !self.history_index.contains_key(&hash) && self.quarantined.insert(hash) {
+            tracing::debug!(?hash, "quarantining change");
+            self.quarantine.push(change);
+        }
+    }
+
+    /// Whether the change with hash `hash` is in the quarantine
+    pub fn is_quarantined(&self, hash: &ChangeHash) -> bool {
+        self.quarantined.contains(hash)
+    }
+
+    /// The changes currently held in the quarantine, in the order they were quarantined
+    pub fn quarantined_changes(&self) -> &[Change] {
+        &self.quarantine
+    }
+
+    /// Whether changes received from other peers which modify a frozen object should be
+    /// quarantined rather than applied
+    ///
+    /// See [`Self::freeze()`]. Such changes are reported in [`Self::frozen_violations()`] either
+    /// way.
+    pub fn set_quarantine_frozen_violations(&mut self, quarantine: bool) -> &mut Self {
+        self.quarantine_frozen_violations = quarantine;
+        self
+    }
+
+    /// Remove the changes with the given hashes from the quarantine and apply them to the
+    /// document
+    ///
+    /// Hashes which do not refer to a quarantined change are ignored. Accepted changes are
+    /// applied regardless of [`Self::set_quarantine_frozen_violations()`] and
+    /// [`Self::set_schema_policy()`].
+    pub fn accept_quarantined(&mut self, hashes: &[ChangeHash]) -> Result<(), AutomergeError> {
+        self.accept_quarantined_log_patches(
+            hashes,
+            &mut PatchLog::inactive(TextRepresentation::default()),
+        )
+    }
+
+    /// Like [`Self::accept_quarantined()`] but log the resulting changes to the current state of
+    /// the document to `patch_log`
+    pub fn accept_quarantined_log_patches(
+        &mut self,
+        hashes: &[ChangeHash],
+        patch_log: &mut PatchLog,
+    ) -> Result<(), AutomergeError> {
+        let accepted = self.take_quarantined(hashes);
+        // The caller has explicitly decided that these changes are acceptable so they must not
+        // be sent straight back to the quarantine
+        let quarantine_frozen = std::mem::replace(&mut self.quarantine_frozen_violations, false);
+        let schema_policy = self.schema_policy.take();
+        let result = self.apply_changes_log_patches(accepted, patch_log);
+        self.quarantine_frozen_violations = quarantine_frozen;
+        self.schema_policy = schema_policy;
+        result
+    }
+
+    /// Remove the changes with the given hashes from the quarantine without applying them
+    ///
+    /// Returns the removed changes.
+    pub fn reject_quarantined(&mut self, hashes: &[ChangeHash]) -> Vec<Change> {
+        self.take_quarantined(hashes)
+    }
+
+    fn take_quarantined(&mut self, hashes: &[ChangeHash]) -> Vec<Change> {
+        let hashes = hashes
+            .iter()
+            .filter(|h| self.quarantined.remove(h))
+            .collect::<HashSet<_>>();
+        let (taken, kept) = std::mem::take(&mut self.quarantine)
+            .into_iter()
+            .partition(|c| hashes.contains(&c.hash()));
+        self.quarantine = kept;
+        taken
+    }
+
+    /// Encode the quarantined changes so they can be persisted alongside the document
+    ///
+    /// The result can be restored with [`Self::load_quarantine()`]
+    pub fn save_quarantine(&self) -> Vec<u8> {
+        self.quarantine
+            .iter()
+            .flat_map(|c| c.raw_bytes().iter().copied())
+            .collect()
+    }
+
+    /// Add the changes encoded in `data` (as produced by [`Self::save_quarantine()`]) to the
+    /// quarantine
+    ///
+    /// Returns the number of changes which were loaded.
+    pub fn load_quarantine(&mut self, data: &[u8]) -> Result<usize, AutomergeError> {
+        match load::load_changes(storage::parse::Input::new(data)) {
+            load::LoadedChanges::Complete(mut changes) => {
+                for change in &mut changes {
+                    change
+                        .check_ops()
+                        .map_err(|e| load::Error::InvalidChangeColumns(Box::new(e)))?;
+                }
+                let count = changes.len();
+                self.quarantine_changes(changes);
+                Ok(count)
+            }
+            load::LoadedChanges::Partial { error, .. } => Err(error.into()),
+        }
+    }
+}
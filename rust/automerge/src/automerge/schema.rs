@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::parents::Parent;
+use crate::patches::PatchLog;
+use crate::schema::{Schema, SchemaAction, SchemaViolation};
+use crate::types::{ObjId, OpBuilder, OpIds};
+use crate::{Automerge, AutomergeError, Change, ChangeHash, ReadDoc};
+
+type SchemaPolicyFn = dyn Fn(&Change, &SchemaViolation) -> SchemaAction + Send + Sync;
+
+#[derive(Clone)]
+pub(crate) struct SchemaPolicy(Arc<SchemaPolicyFn>);
+
+impl fmt::Debug for SchemaPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SchemaPolicy").finish()
+    }
+}
+
+/// Schemas
+impl Automerge {
+    /// Check the document against `schema`, see [`crate::schema`]
+    ///
+    /// Local transactions which would leave the document in a state `schema` does not allow
+    /// fail to commit. Changes received from other peers are only checked if a policy has been
+    /// set with [`Self::set_schema_policy()`]. The document is not checked when the schema is
+    /// set or when it is loaded, use [`Schema::validate()`] for that.
+    ///
+    /// Like [`Self::freeze()`] this is a local policy: it is not saved with the document, but it
+    /// is kept when changes are loaded with [`Self::load_incremental()`] or received by the sync
+    /// protocol, even into an empty document.
+    pub fn set_schema<S: Into<Schema>>(&mut self, schema: S) {
+        self.schema = Some(Arc::new(schema.into()));
+    }
+
+    pub fn clear_schema(&mut self) {
+        self.schema = None;
+    }
+
+    /// The schema set with [`Self::set_schema()`]
+    pub fn schema(&self) -> Option<&Schema> {
+        self.schema.as_deref()
+    }
+
+    /// Decide what to do with changes received from other peers which violate the schema
+    ///
+    /// `policy` is called with each such change and the first violation found, and returns
+    /// whether the change is applied, rejected or quarantined. The hashes of all such changes
+    /// are recorded whatever the policy decides, see [`Self::schema_violations()`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ReadDoc, ROOT};
+    /// # use automerge::schema::{ScalarType, Schema, SchemaAction};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = AutoCommit::new();
+    /// doc.set_schema(Schema::map().optional("count", ScalarType::Int));
+    /// doc.set_schema_policy(|_change, _violation| SchemaAction::Quarantine);
+    ///
+    /// let mut other = doc.fork();
+    /// other.clear_schema();
+    /// other.put(ROOT, "count", "many").unwrap();
+    /// doc.merge(&mut other).unwrap();
+    ///
+    /// assert_eq!(doc.get(ROOT, "count").unwrap(), None);
+    /// assert_eq!(doc.quarantined_changes().len(), 1);
+    /// ```
+    pub fn set_schema_policy<F>(&mut self, policy: F)
+    where
+        F: Fn(&Change, &SchemaViolation) -> SchemaAction + Send + Sync + 'static,
+    {
+        self.schema_policy = Some(SchemaPolicy(Arc::new(policy)));
+    }
+
+    /// Stop checking changes received from other peers, see [`Self::set_schema_policy()`]
+    pub fn clear_schema_policy(&mut self) {
+        self.schema_policy = None;
+    }
+
+    /// The hashes of changes received from other peers which violated the schema
+    ///
+    /// See [`Self::set_schema_policy()`]
+    pub fn schema_violations(&self) -> &[ChangeHash] {
+        &self.schema_violations
+    }
+
+    /// Return and clear the list of changes which violated the schema
+    pub fn take_schema_violations(&mut self) -> Vec<ChangeHash> {
+        std::mem::take(&mut self.schema_violations)
+    }
+
+    /// Check the objects in `objs`, and everything beneath them, against the schema
+    pub(crate) fn check_schema<I: IntoIterator<Item = ObjId>>(
+        &self,
+        objs: I,
+    ) -> Result<(), SchemaViolation> {
+        let Some(schema) = &self.schema else {
+            return Ok(());
+        };
+        for obj in objs.into_iter().collect::<HashSet<_>>() {
+            let Ok(meta) = self.get_obj_meta(obj) else {
+                continue;
+            };
+            let exid = self.id_to_exid(obj.0);
+            let Ok(parents) = self.parents(&exid) else {
+                continue;
+            };
+            let mut parents = parents.collect::<Vec<_>>();
+            // an object which has been deleted is checked as part of the object it was in
+            if parents.iter().any(|p| !p.visible) {
+                continue;
+            }
+            parents.reverse();
+            let mut node: &Schema = schema;
+            let mut path = Vec::with_capacity(parents.len());
+            for Parent { typ, prop, .. } in parents {
+                match node.child(typ, &prop) {
+                    Some(child) => node = child,
+                    // the object is somewhere the schema does not allow it, which is a problem
+                    // with one of the objects it is in
+                    None => return schema.validate(self),
+                }
+                path.push(prop);
+            }
+            node.validate_obj(self, &exid, meta.typ, &mut path)?;
+        }
+        Ok(())
+    }
+
+    /// Insert the ops of a change received from another peer and check the result against the
+    /// schema
+    ///
+    /// If the change violates the schema and the policy does not apply it the ops are removed
+    /// again and the action the policy chose is returned.
+    pub(super) fn insert_ops_checked(
+        &mut self,
+        change: &Change,
+        ops: Vec<(ObjId, OpBuilder, OpIds)>,
+        patch_log: &mut PatchLog,
+    ) -> Result<Option<SchemaAction>, AutomergeError> {
+        let Some(SchemaPolicy(policy)) = self.schema_policy.clone() else {
+            for (obj, op, pred) in ops {
+                self.insert_op(&obj, op, &pred, patch_log)?;
+            }
+            return Ok(None);
+        };
+        let objs = ops.iter().map(|(obj, _, _)| *obj).collect::<Vec<_>>();
+        let start = self.ops.osd.start_range();
+        let mut branch = patch_log.branch();
+        for (obj, op, pred) in ops {
+            self.insert_op(&obj, op, &pred, &mut branch)?;
+        }
+        if let Err(violation) = self.check_schema(objs) {
+            tracing::warn!(hash=?change.hash(), %violation, "change violates the schema");
+            self.schema_violations.push(change.hash());
+            let action = policy(change, &violation);
+            if action != SchemaAction::Apply {
+                let range = self.ops.osd.extend_range(start);
+                self.remove_ops(range);
+                return Ok(Some(action));
+            }
+        }
+        patch_log.merge(branch);
+        Ok(None)
+    }
+}
@@ -0,0 +1,209 @@
+use sha2::{Digest, Sha256};
+
+use crate::storage::parse;
+use crate::{Automerge, AutomergeError, ChangeHash};
+
+const MAGIC: &[u8] = b"AMSEAL";
+const VERSION: u8 = 1;
+const SIGNING_DOMAIN: &[u8] = b"automerge-seal-v1";
+
+/// A document which has been checked by [`Automerge::verify_sealed()`]
+///
+/// The document can only be read, through [`Self::doc()`]. To make further changes load the
+/// bytes of the document with [`Automerge::load()`], which gives an ordinary editable document.
+#[derive(Debug)]
+pub struct SealedDocument {
+    doc: Automerge,
+    heads: Vec<ChangeHash>,
+    content_hash: [u8; 32],
+    signature: Option<Vec<u8>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SealError {
+    #[error("not a sealed document")]
+    NotSealed,
+    #[error("unsupported sealed document version {0}")]
+    UnsupportedVersion(u8),
+    #[error("sealed document is truncated")]
+    Truncated,
+    #[error("content hash does not match the sealed document")]
+    ContentHashMismatch,
+    #[error("heads of the document do not match the sealed heads")]
+    HeadsMismatch,
+    #[error("sealed document is not signed")]
+    Unsigned,
+    #[error("signature of the sealed document is invalid")]
+    InvalidSignature,
+    #[error(transparent)]
+    Load(#[from] AutomergeError),
+}
+
+impl Automerge {
+    /// Save the document as a sealed artifact which [`Self::verify_sealed()`] can check
+    ///
+    /// The artifact contains the saved document, its heads and a SHA-256 hash of its content.
+    /// It is meant for publishing finalized documents, whose readers should be able to tell that
+    /// they have exactly the document which was sealed. See [`Self::seal_signed()`] to also sign
+    /// it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, Automerge, ReadDoc, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = AutoCommit::new();
+    /// doc.put(ROOT, "title", "Release 1.0").unwrap();
+    /// let sealed = doc.seal();
+    ///
+    /// let verified = Automerge::verify_sealed(&sealed).unwrap();
+    /// assert_eq!(verified.heads(), doc.get_heads());
+    /// let (title, _) = verified.doc().get(ROOT, "title").unwrap().unwrap();
+    /// assert_eq!(title.to_str(), Some("Release 1.0"));
+    /// ```
+    pub fn seal(&self) -> Vec<u8> {
+        self.seal_with(None::<fn(&[u8]) -> Vec<u8>>)
+    }
+
+    /// Like [`Self::seal()`] but signs the artifact with `sign`
+    ///
+    /// `sign` is given the payload described in [`SealedDocument::signing_payload()`], which
+    /// covers the content hash and the heads, and returns the signature. The signature scheme is
+    /// up to the caller, who checks it with [`SealedDocument::verify_signature()`].
+    pub fn seal_signed<F: FnOnce(&[u8]) -> Vec<u8>>(&self, sign: F) -> Vec<u8> {
+        self.seal_with(Some(sign))
+    }
+
+    fn seal_with<F: FnOnce(&[u8]) -> Vec<u8>>(&self, sign: Option<F>) -> Vec<u8> {
+        let content = self.save();
+        let content_hash: [u8; 32] = Sha256::digest(&content).into();
+        let mut heads = self.get_heads();
+        heads.sort();
+        let signature = sign
+            .map(|sign| sign(&signing_payload(&content_hash, &heads)))
+            .unwrap_or_default();
+
+        let mut out = Vec::with_capacity(content.len() + 64 + heads.len() * 32 + signature.len());
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.extend_from_slice(&content_hash);
+        leb128::write::unsigned(&mut out, heads.len() as u64).unwrap();
+        for head in &heads {
+            out.extend_from_slice(head.as_bytes());
+        }
+        leb128::write::unsigned(&mut out, signature.len() as u64).unwrap();
+        out.extend_from_slice(&signature);
+        out.extend_from_slice(&content);
+        out
+    }
+
+    /// Check and load an artifact created by [`Self::seal()`] or [`Self::seal_signed()`]
+    ///
+    /// This checks that the content hash matches the saved document and that the heads of the
+    /// loaded document are the sealed heads. It does not check the signature, which needs the
+    /// verifier of the caller's signature scheme, see [`SealedDocument::verify_signature()`].
+    pub fn verify_sealed(bytes: &[u8]) -> Result<SealedDocument, SealError> {
+        let rest = bytes.strip_prefix(MAGIC).ok_or(SealError::NotSealed)?;
+        let (&version, rest) = rest.split_first().ok_or(SealError::Truncated)?;
+        if version != VERSION {
+            return Err(SealError::UnsupportedVersion(version));
+        }
+        let (content_hash, rest) = split(rest, 32)?;
+        let content_hash: [u8; 32] = content_hash.try_into().unwrap();
+        let (rest, num_heads) = read_len(rest)?;
+        let (head_bytes, rest) =
+            split(rest, num_heads.checked_mul(32).ok_or(SealError::Truncated)?)?;
+        let heads = head_bytes
+            .chunks_exact(32)
+            .map(|h| ChangeHash::try_from(h).unwrap())
+            .collect::<Vec<_>>();
+        let (rest, signature_len) = read_len(rest)?;
+        let (signature, content) = split(rest, signature_len)?;
+
+        if <[u8; 32]>::from(Sha256::digest(content)) != content_hash {
+            return Err(SealError::ContentHashMismatch);
+        }
+        let doc = Automerge::load(content)?;
+        let mut doc_heads = doc.get_heads();
+        doc_heads.sort();
+        if doc_heads != heads {
+            return Err(SealError::HeadsMismatch);
+        }
+        Ok(SealedDocument {
+            doc,
+            heads,
+            content_hash,
+            signature: (!signature.is_empty()).then(|| signature.to_vec()),
+        })
+    }
+}
+
+impl SealedDocument {
+    /// The sealed document
+    pub fn doc(&self) -> &Automerge {
+        &self.doc
+    }
+
+    /// The heads of the document when it was sealed, sorted
+    pub fn heads(&self) -> &[ChangeHash] {
+        &self.heads
+    }
+
+    /// The SHA-256 hash of the saved document
+    pub fn content_hash(&self) -> &[u8; 32] {
+        &self.content_hash
+    }
+
+    /// The signature given by [`Automerge::seal_signed()`], if there is one
+    pub fn signature(&self) -> Option<&[u8]> {
+        self.signature.as_deref()
+    }
+
+    /// The bytes which the signature is over
+    ///
+    /// These are the string `automerge-seal-v1` followed by the content hash and the sorted
+    /// heads.
+    pub fn signing_payload(&self) -> Vec<u8> {
+        signing_payload(&self.content_hash, &self.heads)
+    }
+
+    /// Check the signature with `verify`, which is given the signing payload and the signature
+    ///
+    /// Returns [`SealError::Unsigned`] if the document was not signed and
+    /// [`SealError::InvalidSignature`] if `verify` returns false.
+    pub fn verify_signature<F: FnOnce(&[u8], &[u8]) -> bool>(
+        &self,
+        verify: F,
+    ) -> Result<(), SealError> {
+        let signature = self.signature.as_deref().ok_or(SealError::Unsigned)?;
+        if verify(&self.signing_payload(), signature) {
+            Ok(())
+        } else {
+            Err(SealError::InvalidSignature)
+        }
+    }
+}
+
+fn signing_payload(content_hash: &[u8; 32], heads: &[ChangeHash]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(SIGNING_DOMAIN.len() + 32 + heads.len() * 32);
+    payload.extend_from_slice(SIGNING_DOMAIN);
+    payload.extend_from_slice(content_hash);
+    for head in heads {
+        payload.extend_from_slice(head.as_bytes());
+    }
+    payload
+}
+
+fn split(bytes: &[u8], len: usize) -> Result<(&[u8], &[u8]), SealError> {
+    if bytes.len() < len {
+        return Err(SealError::Truncated);
+    }
+    Ok(bytes.split_at(len))
+}
+
+fn read_len(bytes: &[u8]) -> Result<(&[u8], usize), SealError> {
+    let (rest, len) = parse::leb128_u64::<parse::leb128::Error>(parse::Input::new(bytes))
+        .map_err(|_| SealError::Truncated)?;
+    let len = usize::try_from(len).map_err(|_| SealError::Truncated)?;
+    Ok((rest.unconsumed_bytes(), len))
+}
@@ -0,0 +1,185 @@
+use std::fmt;
+
+use sha2::{Digest, Sha256};
+
+use crate::columnar::column_range::encode_value;
+use crate::exid::ExId;
+use crate::text_value::TextValue;
+use crate::{Automerge, ObjType, ReadDoc, ScalarValue, Value, ROOT};
+
+/// Identifies the version of the encoding hashed by [`Automerge::state_hash()`]
+const STATE_HASH_VERSION: &[u8] = b"automerge-state-v1";
+
+/// The SHA-256 hash of the content of a document, see [`Automerge::state_hash()`]
+#[derive(Eq, PartialEq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct StateHash(pub [u8; 32]);
+
+impl fmt::Debug for StateHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("StateHash")
+            .field(&hex::encode(self.0))
+            .finish()
+    }
+}
+
+impl fmt::Display for StateHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+impl serde::Serialize for StateHash {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(self.0))
+    }
+}
+
+/// State hashes
+impl Automerge {
+    /// A hash of the current content of the document which does not depend on its history
+    ///
+    /// Documents which [`Self::equals_content()`] says are equal have the same state hash,
+    /// whatever changes and actors produced them, so replicas can exchange state hashes to
+    /// check that they have converged without exchanging the documents.
+    ///
+    /// The hash is the SHA-256 of the version string `automerge-state-v1` followed by the
+    /// encoding of the root map, where
+    ///
+    /// * a map is `M` (or `T` for a table), the number of keys, then each key in lexicographic
+    ///   order of its UTF-8 bytes followed by its value
+    /// * a list is `L`, the number of elements, then each element
+    /// * a text object is `X`, its UTF-8 text, the number of marks, then the start and end of
+    ///   each mark as UTF-8 byte offsets followed by its name and value
+    /// * a scalar is `S` followed by the encoding automerge stores it with: its type and length
+    ///   then its raw bytes. Counters are encoded with their current value, and negative zero
+    ///   and NaN floats with the bits of zero and [`f64::NAN`]
+    ///
+    /// Numbers are unsigned LEB128 and strings are their length followed by their UTF-8 bytes.
+    /// Values which lost a conflict are not included, nor are text blocks.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc1 = AutoCommit::new();
+    /// doc1.put(ROOT, "a", 1).unwrap();
+    /// doc1.put(ROOT, "b", 2).unwrap();
+    /// let mut doc2 = AutoCommit::new();
+    /// doc2.put(ROOT, "b", 2).unwrap();
+    /// doc2.put(ROOT, "a", 1).unwrap();
+    /// assert_eq!(doc1.state_hash(), doc2.state_hash());
+    ///
+    /// doc2.put(ROOT, "a", 3).unwrap();
+    /// assert_ne!(doc1.state_hash(), doc2.state_hash());
+    /// ```
+    pub fn state_hash(&self) -> StateHash {
+        let mut hasher = Sha256::new();
+        hasher.update(STATE_HASH_VERSION);
+        let mut encoder = StateEncoder {
+            doc: self,
+            hasher,
+            buf: Vec::new(),
+        };
+        encoder.obj(&ROOT, ObjType::Map);
+        StateHash(encoder.hasher.finalize().into())
+    }
+}
+
+struct StateEncoder<'a> {
+    doc: &'a Automerge,
+    hasher: Sha256,
+    /// Scratch space for encoding numbers and scalars
+    buf: Vec<u8>,
+}
+
+impl<'a> StateEncoder<'a> {
+    fn obj(&mut self, obj: &ExId, typ: ObjType) {
+        match typ {
+            ObjType::Map | ObjType::Table => {
+                self.hasher
+                    .update(if typ == ObjType::Map { b"M" } else { b"T" });
+                let mut keys = self.doc.keys(obj).collect::<Vec<_>>();
+                keys.sort_unstable_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+                self.uint(keys.len());
+                for key in keys {
+                    self.str(&key);
+                    let value = self.doc.get(obj, key.as_str()).ok().flatten();
+                    self.value(value);
+                }
+            }
+            ObjType::List => {
+                self.hasher.update(b"L");
+                let len = self.doc.length(obj);
+                self.uint(len);
+                for index in 0..len {
+                    let value = self.doc.get(obj, index).ok().flatten();
+                    self.value(value);
+                }
+            }
+            ObjType::Text => {
+                self.hasher.update(b"X");
+                let text = self.doc.text(obj).unwrap_or_default();
+                self.str(&text);
+                let marks = self.doc.marks(obj).unwrap_or_default();
+                self.uint(marks.len());
+                for mark in marks {
+                    self.uint(utf8_offset(&text, mark.start));
+                    self.uint(utf8_offset(&text, mark.end));
+                    self.str(mark.name());
+                    self.scalar(mark.value());
+                }
+            }
+        }
+    }
+
+    fn value(&mut self, value: Option<(Value<'_>, ExId)>) {
+        match value {
+            Some((Value::Object(typ), id)) => self.obj(&id, typ),
+            Some((Value::Scalar(v), _)) => self.scalar(&v),
+            // the key or index was read from the document so there is always a value
+            None => {}
+        }
+    }
+
+    fn scalar(&mut self, value: &ScalarValue) {
+        self.hasher.update(b"S");
+        self.buf.clear();
+        match value {
+            ScalarValue::Counter(c) => {
+                let mut current = c.clone();
+                current.start = current.current;
+                encode_value(&ScalarValue::Counter(current), &mut self.buf);
+            }
+            ScalarValue::F64(f) if f.is_nan() => {
+                encode_value(&ScalarValue::F64(f64::NAN), &mut self.buf)
+            }
+            ScalarValue::F64(f) if *f == 0.0 => encode_value(&ScalarValue::F64(0.0), &mut self.buf),
+            other => encode_value(other, &mut self.buf),
+        }
+        self.hasher.update(&self.buf);
+    }
+
+    fn uint(&mut self, n: usize) {
+        self.buf.clear();
+        leb128::write::unsigned(&mut self.buf, n as u64).unwrap();
+        self.hasher.update(&self.buf);
+    }
+
+    fn str(&mut self, s: &str) {
+        self.uint(s.len());
+        self.hasher.update(s.as_bytes());
+    }
+}
+
+/// Convert `index`, measured in the native text unit, to a byte offset into `text`
+fn utf8_offset(text: &str, index: usize) -> usize {
+    let mut native = 0;
+    for (offset, c) in text.char_indices() {
+        if native >= index {
+            return offset;
+        }
+        native += TextValue::width(&text[offset..offset + c.len_utf8()]);
+    }
+    text.len()
+}
@@ -0,0 +1,317 @@
+use std::collections::VecDeque;
+use std::io::{self, Read};
+
+use crate::patches::{PatchLog, TextRepresentation};
+use crate::storage::{load, parse, ChunkType};
+use crate::{Automerge, AutomergeError, Change};
+
+use super::{current_state, LoadOptions, OnPartialLoad, StringMigration};
+
+/// Decodes the changes in a stream of concatenated chunks, such as the output of
+/// [`Automerge::save_after()`], one chunk at a time
+///
+/// Only one chunk is held in memory at a time, so a large batch of changes can be applied with
+/// [`Automerge::apply_changes_from()`] as it arrives rather than after all of it has been read.
+/// Every op in a change is decoded before the change is returned, and a change with malformed
+/// ops is returned as an error. Iteration stops after the first error.
+#[derive(Debug)]
+pub struct ChangeReader<R> {
+    reader: R,
+    pending: VecDeque<Change>,
+    done: bool,
+}
+
+/// The magic bytes, checksum and chunk type which start every chunk
+const HEADER_LEN: usize = 9;
+
+impl<R: Read> ChangeReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            pending: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    fn next_change(&mut self) -> Result<Option<Change>, AutomergeError> {
+        while self.pending.is_empty() {
+            let Some(chunk) = read_chunk(&mut self.reader)? else {
+                return Ok(None);
+            };
+            match load::load_changes(parse::Input::new(&chunk)) {
+                load::LoadedChanges::Complete(changes) => self.pending.extend(changes),
+                load::LoadedChanges::Partial { error, .. } => {
+                    return Err(AutomergeError::Load(error))
+                }
+            }
+        }
+        Ok(self.pending.pop_front())
+    }
+
+    /// Like [`Iterator::next()`] but without checking the ops in the change
+    ///
+    /// The ops of the returned change may not have been decoded yet, so they must only be used
+    /// by applying the change to a document, which decodes and checks them.
+    pub(crate) fn next_unchecked(&mut self) -> Option<Result<Change, AutomergeError>> {
+        if self.done {
+            return None;
+        }
+        let next = self.next_change().transpose();
+        self.done = !matches!(next, Some(Ok(_)));
+        next
+    }
+}
+
+/// Read the next chunk from `reader`, or `None` if the stream ended cleanly between chunks
+fn read_chunk<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>, AutomergeError> {
+    let mut header = [0; HEADER_LEN];
+    let mut filled = 0;
+    while filled < HEADER_LEN {
+        match reader.read(&mut header[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(AutomergeError::Io(io::ErrorKind::UnexpectedEof.into())),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(AutomergeError::Io(e)),
+        }
+    }
+    let len = leb128::read::unsigned(reader).map_err(|e| match e {
+        leb128::read::Error::IoError(e) => AutomergeError::Io(e),
+        e => AutomergeError::Load(load::Error::Parse(Box::new(e))),
+    })?;
+    let mut chunk = header.to_vec();
+    leb128::write::unsigned(&mut chunk, len).map_err(AutomergeError::Io)?;
+    let start = chunk.len();
+    // read through `take` so a corrupt length doesn't allocate the whole length up front
+    reader
+        .take(len)
+        .read_to_end(&mut chunk)
+        .map_err(AutomergeError::Io)?;
+    if (chunk.len() - start) as u64 != len {
+        return Err(AutomergeError::Io(io::ErrorKind::UnexpectedEof.into()));
+    }
+    Ok(Some(chunk))
+}
+
+impl<R: Read> Iterator for ChangeReader<R> {
+    type Item = Result<Change, AutomergeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.next_unchecked()?.and_then(|mut change| {
+            change.check_ops().map_err(|e| {
+                AutomergeError::Load(load::Error::InvalidChangeColumns(Box::new(e)))
+            })?;
+            Ok(change)
+        });
+        self.done = next.is_err();
+        Some(next)
+    }
+}
+
+/// Applying changes as they arrive
+impl Automerge {
+    /// Decode and apply the changes in `reader` as they are read, returning the number of
+    /// changes read
+    ///
+    /// `reader` contains concatenated chunks, as returned by [`Self::save_after()`] and
+    /// [`Self::save()`], see [`ChangeReader`]. If reading or decoding fails the changes which
+    /// were read before the failure remain applied.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{Automerge, ReadDoc, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut source = Automerge::new();
+    /// source.transact(|tx| tx.put(ROOT, "a", 1)).unwrap();
+    /// let mut stream = source.save();
+    /// let heads = source.get_heads();
+    /// source.transact(|tx| tx.put(ROOT, "b", 2)).unwrap();
+    /// stream.extend(source.save_after(&heads));
+    ///
+    /// let mut doc = Automerge::new();
+    /// assert_eq!(doc.apply_changes_from(stream.as_slice()).unwrap(), 2);
+    /// assert_eq!(doc.get_heads(), source.get_heads());
+    /// ```
+    pub fn apply_changes_from<R: Read>(&mut self, reader: R) -> Result<usize, AutomergeError> {
+        // the ops are decoded when each change is applied, so don't decode them twice
+        let mut changes = ChangeReader::new(reader);
+        self.apply_changes_streamed(
+            std::iter::from_fn(|| changes.next_unchecked()),
+            &mut PatchLog::inactive(TextRepresentation::default()),
+        )
+    }
+
+    /// Like [`Self::apply_changes()`] but apply each change as it is taken from `changes`
+    /// rather than collecting them first
+    pub fn apply_changes_iter<I: IntoIterator<Item = Change>>(
+        &mut self,
+        changes: I,
+    ) -> Result<(), AutomergeError> {
+        self.apply_changes_streamed(
+            changes.into_iter().map(Ok),
+            &mut PatchLog::inactive(TextRepresentation::default()),
+        )?;
+        Ok(())
+    }
+
+    /// Apply the changes in `changes` one at a time, returning the number of changes
+    pub(crate) fn apply_changes_streamed<I>(
+        &mut self,
+        changes: I,
+        patch_log: &mut PatchLog,
+    ) -> Result<usize, AutomergeError>
+    where
+        I: IntoIterator<Item = Result<Change, AutomergeError>>,
+    {
+        let mut count = 0;
+        for change in changes {
+            self.apply_changes_inner(Some(change?), patch_log, None)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+/// Loading documents as they arrive
+impl Automerge {
+    /// Load a document from `reader`, reading one chunk at a time
+    ///
+    /// This accepts the same data as [`Self::load()`]: a document chunk, as written by
+    /// [`Self::save()`] or [`Self::save_to()`], followed by any number of change chunks, as
+    /// written by [`Self::save_after()`]. Rather than reading all of `reader` into memory
+    /// first, each chunk is read, its checksum verified and its contents loaded into the
+    /// document before the next chunk is read, so a document can be loaded from a socket or a
+    /// decompressing reader while holding only the largest chunk in memory.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{Automerge, ReadDoc, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = Automerge::new();
+    /// doc.transact(|tx| tx.put(ROOT, "a", 1)).unwrap();
+    /// let mut file = doc.save();
+    /// let heads = doc.get_heads();
+    /// doc.transact(|tx| tx.put(ROOT, "b", 2)).unwrap();
+    /// file.extend(doc.save_after(&heads));
+    ///
+    /// let loaded = Automerge::load_from(file.as_slice()).unwrap();
+    /// assert_eq!(loaded.get_heads(), doc.get_heads());
+    /// ```
+    pub fn load_from<R: Read>(reader: R) -> Result<Self, AutomergeError> {
+        Self::load_from_with_options(reader, LoadOptions::default())
+    }
+
+    /// Load a document from `reader` with options, see [`Self::load_from()`] and
+    /// [`Self::load_with_options()`]
+    ///
+    /// With [`OnPartialLoad::Ignore`] an error reading or decoding any chunk after the first
+    /// stops loading and the document loaded so far is returned, so a truncated stream loads
+    /// the complete chunks at its start.
+    pub fn load_from_with_options<R: Read>(
+        mut reader: R,
+        options: LoadOptions<'_>,
+    ) -> Result<Self, AutomergeError> {
+        let LoadOptions {
+            on_partial_load,
+            verification_mode,
+            string_migration,
+            patch_log,
+            cancellation,
+            // only load_incremental asks for this, and it doesn't read from a stream
+            reject_malformed_changes: _,
+        } = options;
+        let Some(first) = read_chunk(&mut reader)? else {
+            return Ok(Self::new());
+        };
+        let first_chunk_was_doc =
+            ChunkType::try_from(first[HEADER_LEN - 1]) == Ok(ChunkType::Document);
+        // changes with missing dependencies are checked for once every chunk has been read, as
+        // the dependencies may be in later chunks
+        let mut options = LoadOptions::new()
+            .on_partial_load(OnPartialLoad::Ignore)
+            .verification_mode(verification_mode);
+        if let Some(token) = &cancellation {
+            options = options.cancellation(token.clone());
+        }
+        let mut doc = Self::load_with_options(&first, options)?;
+        drop(first);
+
+        let mut changes = ChangeReader::new(&mut reader);
+        while let Some(change) = changes.next_unchecked() {
+            if let Some(token) = &cancellation {
+                token.check()?;
+            }
+            match change {
+                Ok(change) => doc.apply_changes_inner(
+                    Some(change),
+                    &mut PatchLog::inactive(TextRepresentation::default()),
+                    cancellation.as_ref(),
+                )?,
+                Err(e) if on_partial_load == OnPartialLoad::Error => return Err(e),
+                Err(_) => break,
+            }
+        }
+        if !doc.queue.is_empty() && !first_chunk_was_doc && on_partial_load == OnPartialLoad::Error
+        {
+            return Err(AutomergeError::MissingDeps);
+        }
+
+        if let StringMigration::ConvertToText = string_migration {
+            doc.convert_scalar_strings_to_text()?;
+        }
+        if let Some(patch_log) = patch_log {
+            if patch_log.is_active() {
+                current_state::log_current_state_patches(&doc, patch_log);
+            }
+        }
+        Ok(doc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChangeReader;
+    use crate::storage::{parse, Chunk, ChunkType, Header};
+    use crate::transaction::Transactable;
+    use crate::{Automerge, AutomergeError, ROOT};
+
+    /// A change chunk whose action column is fine but whose value column contains invalid UTF-8
+    fn change_with_malformed_value() -> Vec<u8> {
+        let mut doc = Automerge::new();
+        doc.transact(|tx| tx.put(ROOT, "key", "malformed")).unwrap();
+        let bytes = doc.save_after(&[]);
+        let (_, chunk) = Chunk::parse(parse::Input::new(&bytes)).unwrap();
+        let Chunk::Change(change) = chunk else {
+            panic!("expected a change chunk");
+        };
+        let mut data = change.body_bytes().to_vec();
+        let start = data
+            .windows(9)
+            .position(|w| w == b"malformed")
+            .expect("the value should be in the value column");
+        data[start] = 0xff;
+        let mut chunk = Vec::new();
+        Header::new(ChunkType::Change, &data).write(&mut chunk);
+        chunk.extend(data);
+        chunk
+    }
+
+    #[test]
+    fn changes_with_malformed_ops_are_returned_as_errors() {
+        let bytes = change_with_malformed_value();
+        let mut reader = ChangeReader::new(bytes.as_slice());
+        assert!(matches!(
+            reader.next(),
+            Some(Err(AutomergeError::Load(
+                crate::storage::load::Error::InvalidChangeColumns(_)
+            )))
+        ));
+        assert!(reader.next().is_none());
+
+        let mut doc = Automerge::new();
+        assert!(doc.apply_changes_from(bytes.as_slice()).is_err());
+        assert!(doc.get_heads().is_empty());
+    }
+}
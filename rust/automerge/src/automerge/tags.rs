@@ -0,0 +1,67 @@
+use std::collections::BTreeMap;
+
+use crate::extra_bytes::Field;
+use crate::{Automerge, ChangeHash, ReadDoc};
+
+impl Automerge {
+    /// Tag the current heads of the document with `name`
+    ///
+    /// The tag is recorded as an empty change whose dependencies are the tagged heads, so it is
+    /// saved and synced with the rest of the document. Returns the hash of that change.
+    ///
+    /// Tagging again with the same name moves the tag. If peers tag concurrently with the same
+    /// name the tag with the greatest change hash wins, so every peer agrees on it once they
+    /// have the same changes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ReadDoc, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = AutoCommit::new();
+    /// doc.put(ROOT, "version", "1.0").unwrap();
+    /// doc.tag("v1.0");
+    /// doc.put(ROOT, "version", "1.1-dev").unwrap();
+    ///
+    /// let heads = doc.heads_for_tag("v1.0").unwrap();
+    /// let (version, _) = doc.get_at(ROOT, "version", &heads).unwrap().unwrap();
+    /// assert_eq!(version.to_str(), Some("1.0"));
+    /// ```
+    pub fn tag(&mut self, name: &str) -> ChangeHash {
+        self.empty_commit_with_extra(Field::Tag, name.as_bytes().to_vec())
+    }
+
+    /// The heads tagged with `name`, see [`Self::tag()`]
+    pub fn heads_for_tag(&self, name: &str) -> Option<Vec<ChangeHash>> {
+        self.tag_changes()
+            .remove(name)
+            .and_then(|changes| self.current_tag(changes))
+    }
+
+    /// Every tag in the document and the heads it tags
+    pub fn tags(&self) -> BTreeMap<String, Vec<ChangeHash>> {
+        self.tag_changes()
+            .into_iter()
+            .filter_map(|(name, changes)| Some((name.to_string(), self.current_tag(changes)?)))
+            .collect()
+    }
+
+    /// The hashes of the changes which tag with each name
+    fn tag_changes(&self) -> BTreeMap<&str, Vec<ChangeHash>> {
+        let mut tags = BTreeMap::<_, Vec<_>>::new();
+        for change in &self.history {
+            if let Some(name) = change.tag() {
+                tags.entry(name).or_default().push(change.hash());
+            }
+        }
+        tags
+    }
+
+    /// The heads tagged by the winning change of `changes`, which all tag the same name
+    fn current_tag(&self, mut changes: Vec<ChangeHash>) -> Option<Vec<ChangeHash>> {
+        self.change_graph.remove_redundant(&mut changes);
+        let winner = changes.iter().max()?;
+        self.get_change_by_hash(winner)
+            .map(|change| change.deps().to_vec())
+    }
+}
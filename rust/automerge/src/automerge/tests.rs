@@ -494,6 +494,42 @@ fn keys_iter_seq() {
     );
 }
 
+#[test]
+fn keys_can_be_iterated_from_both_ends() {
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    tx.put(ROOT, "a", 3).unwrap();
+    tx.put(ROOT, "b", 4).unwrap();
+    tx.put(ROOT, "c", 5).unwrap();
+    tx.put(ROOT, "d", 6).unwrap();
+    tx.commit();
+    let heads = doc.get_heads();
+    let mut tx = doc.transaction();
+    tx.put(ROOT, "a", 7).unwrap();
+    tx.delete(ROOT, "c").unwrap();
+    tx.commit();
+
+    assert_eq!(doc.keys(ROOT).len(), 3);
+    assert_eq!(doc.keys_at(ROOT, &heads).len(), 4);
+    assert_eq!(
+        doc.keys(ROOT).rev().collect::<Vec<_>>(),
+        vec!["d", "b", "a"]
+    );
+
+    let mut keys = doc.keys(ROOT);
+    assert_eq!(keys.next(), Some("a".into()));
+    assert_eq!(keys.next_back(), Some("d".into()));
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys.next_back(), Some("b".into()));
+    assert_eq!(keys.len(), 0);
+    assert_eq!(keys.next(), None);
+
+    let mut keys = doc.keys(ROOT);
+    assert_eq!(keys.next_back(), Some("d".into()));
+    assert_eq!(keys.nth(1), Some("b".into()));
+    assert_eq!(keys.next(), None);
+}
+
 #[test]
 fn range_iter_map() {
     let mut doc = Automerge::new();
@@ -1791,3 +1827,193 @@ fn hash_for_opid() {
     assert_eq!(doc.hash_for_opid(&id1), hash1);
     assert_eq!(doc.hash_for_opid(&id2), hash2);
 }
+
+#[test]
+fn frozen_objects_reject_local_changes_and_report_remote_ones() {
+    let mut doc = AutoCommit::new();
+    let published = doc.put_object(ROOT, "published", ObjType::Map).unwrap();
+    let list = doc.put_object(&published, "items", ObjType::List).unwrap();
+    doc.insert(&list, 0, "a").unwrap();
+    doc.put(ROOT, "draft", "hello").unwrap();
+    doc.commit();
+
+    let mut remote = doc.fork();
+
+    doc.freeze(&published).unwrap();
+    assert!(doc.is_frozen(&published));
+    assert!(doc.is_frozen(&list));
+    assert!(!doc.is_frozen(ROOT));
+
+    assert_eq!(
+        doc.put(&published, "title", "x"),
+        Err(AutomergeError::Frozen(published.clone()))
+    );
+    assert!(doc.insert(&list, 1, "b").is_err());
+    assert!(doc.delete(ROOT, "published").is_err());
+    assert!(doc.put(ROOT, "published", 1).is_err());
+    doc.put(ROOT, "draft", "world").unwrap();
+    doc.commit();
+
+    remote.put(ROOT, "draft", "remote").unwrap();
+    remote.commit();
+    doc.merge(&mut remote).unwrap();
+    assert!(doc.frozen_violations().is_empty());
+
+    remote.insert(&list, 1, "b").unwrap();
+    let hash = remote.commit().unwrap();
+    doc.merge(&mut remote).unwrap();
+    assert_eq!(doc.frozen_violations(), &[hash]);
+    assert_eq!(doc.length(&list), 2);
+
+    assert_eq!(doc.take_frozen_violations(), vec![hash]);
+    assert!(doc.unfreeze(&published).unwrap());
+    doc.insert(&list, 2, "c").unwrap();
+}
+
+#[test]
+fn quarantined_changes_are_held_until_accepted() {
+    let mut doc = AutoCommit::new();
+    doc.put(ROOT, "a", 1).unwrap();
+    doc.commit();
+    let mut remote = doc.fork();
+    remote.put(ROOT, "b", 2).unwrap();
+    let first = remote.commit().unwrap();
+    remote.put(ROOT, "c", 3).unwrap();
+    let second = remote.commit().unwrap();
+
+    let first_change = remote.get_change_by_hash(&first).unwrap().clone();
+    let second_change = remote.get_change_by_hash(&second).unwrap().clone();
+    doc.quarantine_changes(vec![first_change.clone()]);
+    assert!(doc.is_quarantined(&first));
+
+    // receiving the quarantined change again doesn't apply it, and its dependents wait for it
+    doc.apply_changes(vec![first_change, second_change])
+        .unwrap();
+    assert_eq!(doc.get(ROOT, "b").unwrap(), None);
+    assert_eq!(doc.get(ROOT, "c").unwrap(), None);
+
+    let saved = doc.save_quarantine();
+    let mut restored = doc.fork();
+    assert_eq!(restored.load_quarantine(&saved).unwrap(), 1);
+    assert_eq!(restored.reject_quarantined(&[first]).len(), 1);
+    assert!(restored.quarantined_changes().is_empty());
+
+    doc.accept_quarantined(&[first]).unwrap();
+    assert!(doc.quarantined_changes().is_empty());
+    assert_eq!(doc.get_heads(), vec![second]);
+}
+
+#[test]
+fn frozen_violations_can_be_quarantined() {
+    let mut doc = AutoCommit::new();
+    let published = doc.put_object(ROOT, "published", ObjType::Map).unwrap();
+    doc.commit();
+    let mut remote = doc.fork();
+    doc.freeze(&published).unwrap();
+    doc.set_quarantine_frozen_violations(true);
+
+    remote.put(&published, "title", "x").unwrap();
+    let hash = remote.commit().unwrap();
+    doc.merge(&mut remote).unwrap();
+    assert_eq!(doc.frozen_violations(), &[hash]);
+    assert!(doc.is_quarantined(&hash));
+    assert_eq!(doc.get(&published, "title").unwrap(), None);
+    // the quarantined change has not added its actor or keys to the document
+    let osd = &doc.document().ops.osd;
+    assert_eq!(osd.actors.lookup(remote.get_actor()), None);
+    assert_eq!(osd.props.lookup(&"title".to_string()), None);
+
+    doc.accept_quarantined(&[hash]).unwrap();
+    assert!(doc.get(&published, "title").unwrap().is_some());
+}
+
+#[test]
+fn verify_detects_broken_bookkeeping() {
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    tx.put(ROOT, "a", 1).unwrap();
+    tx.commit();
+    let mut tx = doc.transaction();
+    tx.put(ROOT, "b", 2).unwrap();
+    let (head, _) = tx.commit();
+    let head = head.unwrap();
+    assert!(doc.verify().is_ok());
+
+    doc.deps.clear();
+    doc.history_index.remove(&head);
+    let problems = doc.verify().problems;
+    assert!(problems.contains(&VerificationProblem::HistoryIndexMismatch { change: head }));
+    assert!(problems.contains(&VerificationProblem::HeadsMismatch {
+        expected: vec![head],
+        found: vec![],
+    }));
+}
+
+/// A change chunk whose value column contains invalid UTF-8, which is only noticed once its ops
+/// are decoded
+fn change_with_malformed_value() -> Vec<u8> {
+    let mut doc = Automerge::new();
+    doc.transact(|tx| tx.put(ROOT, "key", "malformed")).unwrap();
+    let bytes = doc.save_after(&[]);
+    let (_, chunk) = storage::Chunk::parse(storage::parse::Input::new(&bytes)).unwrap();
+    let storage::Chunk::Change(change) = chunk else {
+        panic!("expected a change chunk");
+    };
+    let mut data = change.body_bytes().to_vec();
+    let start = data.windows(9).position(|w| w == b"malformed").unwrap();
+    data[start] = 0xff;
+    let mut chunk = Vec::new();
+    storage::Header::new(storage::ChunkType::Change, &data).write(&mut chunk);
+    chunk.extend(data);
+    chunk
+}
+
+/// Enough changes that they are decoded in parallel with the `parallel` feature
+fn many_changes() -> Automerge {
+    let mut doc = Automerge::new();
+    for i in 0..40 {
+        doc.transact(|tx| tx.put(ROOT, "count", i)).unwrap();
+    }
+    doc
+}
+
+#[test]
+fn loading_a_change_with_malformed_ops_is_a_partial_load() {
+    let doc = many_changes();
+    let mut bytes = doc.save();
+    bytes.extend(doc.save_after(&[]));
+    bytes.extend(change_with_malformed_value());
+
+    let result = Automerge::load_with_options(&bytes, LoadOptions::new());
+    assert!(matches!(
+        result,
+        Err(AutomergeError::Load(load::Error::InvalidChangeColumns(_)))
+    ));
+
+    let loaded = Automerge::load_with_options(
+        &bytes,
+        LoadOptions::new().on_partial_load(OnPartialLoad::Ignore),
+    )
+    .unwrap();
+    assert_eq!(loaded.get_heads(), doc.get_heads());
+    assert_eq!(loaded.get(ROOT, "key").unwrap(), None);
+}
+
+#[test]
+fn load_incremental_applies_nothing_if_a_change_has_malformed_ops() {
+    let source = many_changes();
+    let mut bytes = source.save_after(&[]);
+    bytes.extend(change_with_malformed_value());
+
+    let mut empty = Automerge::new();
+    assert!(empty.load_incremental(&bytes).is_err());
+    assert!(empty.get_heads().is_empty());
+
+    let mut doc = Automerge::new();
+    doc.transact(|tx| tx.put(ROOT, "local", true)).unwrap();
+    let heads = doc.get_heads();
+    assert!(doc.load_incremental(&bytes).is_err());
+    assert_eq!(doc.get_heads(), heads);
+    assert_eq!(doc.get(ROOT, "count").unwrap(), None);
+    assert!(doc.verify().is_ok());
+}
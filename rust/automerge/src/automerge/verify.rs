@@ -0,0 +1,232 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::exid::ExId;
+use crate::patches::TextRepresentation;
+use crate::storage::{change::Verified, Change as StoredChange};
+use crate::{ActorId, Automerge, Change, ChangeHash};
+
+/// The result of [`Automerge::verify()`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VerificationReport {
+    /// The number of changes which were checked
+    pub changes: usize,
+    /// The number of objects which were checked
+    pub objects: usize,
+    /// Every problem which was found, in the order it was found
+    pub problems: Vec<VerificationProblem>,
+}
+
+impl VerificationReport {
+    /// Whether no problems were found
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// A broken invariant found by [`Automerge::verify()`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerificationProblem {
+    /// A change in the history depends on a change which is not in the history
+    MissingDependency {
+        change: ChangeHash,
+        dependency: ChangeHash,
+    },
+    /// A change appears in the history before one of its dependencies
+    DependencyOutOfOrder {
+        change: ChangeHash,
+        dependency: ChangeHash,
+    },
+    /// Re-parsing the bytes of a change gave a different hash, a bad checksum, or failed
+    InvalidHash { change: ChangeHash },
+    /// The index from change hash to position in the history is wrong for this change
+    HistoryIndexMismatch { change: ChangeHash },
+    /// The changes recorded for an actor do not have consecutive sequence numbers starting at 1
+    SequenceMismatch {
+        actor: ActorId,
+        expected: u64,
+        found: Option<u64>,
+    },
+    /// The heads of the document are not the changes which no other change depends on
+    HeadsMismatch {
+        expected: Vec<ChangeHash>,
+        found: Vec<ChangeHash>,
+    },
+    /// The length of an object read from the op tree index differs from the length computed by
+    /// checking the visibility of every op
+    VisibilityMismatch {
+        obj: ExId,
+        indexed: usize,
+        computed: usize,
+    },
+}
+
+impl fmt::Display for VerificationProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingDependency { change, dependency } => {
+                write!(
+                    f,
+                    "change {} depends on missing change {}",
+                    change, dependency
+                )
+            }
+            Self::DependencyOutOfOrder { change, dependency } => write!(
+                f,
+                "change {} appears in the history before its dependency {}",
+                change, dependency
+            ),
+            Self::InvalidHash { change } => write!(f, "change {} has an invalid hash", change),
+            Self::HistoryIndexMismatch { change } => {
+                write!(f, "the history index is wrong for change {}", change)
+            }
+            Self::SequenceMismatch {
+                actor,
+                expected,
+                found,
+            } => match found {
+                Some(found) => write!(
+                    f,
+                    "expected seq {} for actor {} but found {}",
+                    expected, actor, found
+                ),
+                None => write!(
+                    f,
+                    "expected seq {} for actor {} but found nothing",
+                    expected, actor
+                ),
+            },
+            Self::HeadsMismatch { expected, found } => write!(
+                f,
+                "expected heads {:?} but the document has heads {:?}",
+                expected, found
+            ),
+            Self::VisibilityMismatch {
+                obj,
+                indexed,
+                computed,
+            } => write!(
+                f,
+                "object {} has indexed length {} but computed length {}",
+                obj, indexed, computed
+            ),
+        }
+    }
+}
+
+impl Automerge {
+    /// Check the internal invariants of this document
+    ///
+    /// This re-parses every change to check its hash, checks that the history contains the
+    /// dependencies of every change in causal order, that the bookkeeping which maps hashes and
+    /// actors to changes agrees with the history, that the heads are correct, and that the
+    /// indexed length of every object matches the length computed by checking the visibility of
+    /// each op. This is expensive; it is intended for validating stored documents and for
+    /// gathering information when reporting bugs.
+    pub fn verify(&self) -> VerificationReport {
+        let mut report = VerificationReport {
+            changes: self.history.len(),
+            objects: 0,
+            problems: Vec::new(),
+        };
+        self.verify_changes(&mut report.problems);
+        self.verify_actors(&mut report.problems);
+        self.verify_heads(&mut report.problems);
+        report.objects = self.verify_objects(&mut report.problems);
+        report
+    }
+
+    fn verify_changes(&self, problems: &mut Vec<VerificationProblem>) {
+        for (index, change) in self.history.iter().enumerate() {
+            let hash = change.hash();
+            if self.history_index.get(&hash) != Some(&index) {
+                problems.push(VerificationProblem::HistoryIndexMismatch { change: hash });
+            }
+            let valid = Change::try_from(change.raw_bytes()).is_ok_and(|parsed| {
+                let stored: &StoredChange<'_, Verified> = parsed.as_ref();
+                parsed.hash() == hash && stored.checksum_valid()
+            });
+            if !valid {
+                problems.push(VerificationProblem::InvalidHash { change: hash });
+            }
+            for dep in change.deps() {
+                match self.history_index.get(dep) {
+                    None => problems.push(VerificationProblem::MissingDependency {
+                        change: hash,
+                        dependency: *dep,
+                    }),
+                    Some(dep_index) if *dep_index >= index => {
+                        problems.push(VerificationProblem::DependencyOutOfOrder {
+                            change: hash,
+                            dependency: *dep,
+                        })
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        if self.history_index.len() != self.history.len() {
+            for (hash, index) in self.history_index.iter() {
+                if self.history.get(*index).map(|c| c.hash()) != Some(*hash) {
+                    problems.push(VerificationProblem::HistoryIndexMismatch { change: *hash });
+                }
+            }
+        }
+    }
+
+    fn verify_actors(&self, problems: &mut Vec<VerificationProblem>) {
+        for (actor, indices) in self.states.iter() {
+            let actor = self.ops.osd.actors[*actor].clone();
+            for (expected, index) in (1..).zip(indices) {
+                let change = self.history.get(*index);
+                let found = change.map(|c| c.seq());
+                if found != Some(expected) || change.map(|c| c.actor_id()) != Some(&actor) {
+                    problems.push(VerificationProblem::SequenceMismatch {
+                        actor: actor.clone(),
+                        expected,
+                        found,
+                    });
+                    break;
+                }
+            }
+        }
+    }
+
+    fn verify_heads(&self, problems: &mut Vec<VerificationProblem>) {
+        let depended_on = self
+            .history
+            .iter()
+            .flat_map(|c| c.deps().iter().copied())
+            .collect::<HashSet<_>>();
+        let mut expected = self
+            .history
+            .iter()
+            .map(|c| c.hash())
+            .filter(|h| !depended_on.contains(h))
+            .collect::<Vec<_>>();
+        expected.sort_unstable();
+        let found = self.get_heads();
+        if expected != found {
+            problems.push(VerificationProblem::HeadsMismatch { expected, found });
+        }
+    }
+
+    fn verify_objects(&self, problems: &mut Vec<VerificationProblem>) -> usize {
+        let clock = self.clock_at(&self.get_heads());
+        let mut objects = 0;
+        for (obj, _) in self.ops.iter_objs() {
+            objects += 1;
+            let encoding = TextRepresentation::String.encoding(obj.typ);
+            let indexed = self.ops.length(&obj.id, encoding, None);
+            let computed = self.ops.length(&obj.id, encoding, Some(clock.clone()));
+            if indexed != computed {
+                problems.push(VerificationProblem::VisibilityMismatch {
+                    obj: self.id_to_exid(obj.id.0),
+                    indexed,
+                    computed,
+                });
+            }
+        }
+        objects
+    }
+}
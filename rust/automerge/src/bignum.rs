@@ -0,0 +1,376 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{InvalidNumber, NumberOutOfRange};
+
+/// An integer of any size
+///
+/// This is stored as [`crate::ScalarValue::BigInt`], as its decimal digits, so it is never
+/// rounded the way a large [`f64`] is. Big integers convert from every primitive integer type and
+/// to them with `TryFrom` when they fit, and parse from and display as decimal strings.
+///
+/// # Example
+///
+/// ```
+/// # use automerge::BigInt;
+/// let big: BigInt = "123456789012345678901234567890".parse().unwrap();
+/// assert!(big > BigInt::from(u128::MAX / 1_000_000_000_000));
+/// assert_eq!(i64::try_from(&BigInt::from(-5)), Ok(-5));
+/// assert!(i64::try_from(&big).is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BigInt(Box<str>);
+
+/// A decimal number of any size and precision
+///
+/// This is stored as [`crate::ScalarValue::Decimal`], as its decimal digits, so values such as
+/// amounts of money are stored exactly. The scale, the number of digits after the decimal point,
+/// is kept, so `1.50` and `1.5` are different values. Decimals are ordered by the number they
+/// represent and then by scale.
+///
+/// # Example
+///
+/// ```
+/// # use automerge::{BigInt, Decimal};
+/// let price: Decimal = "19.990".parse().unwrap();
+/// assert_eq!(price.scale(), 3);
+/// assert_eq!(price.unscaled(), BigInt::from(19990));
+/// assert_eq!(price, Decimal::new(BigInt::from(19990), 3));
+/// assert!(price < "19.991".parse().unwrap());
+/// assert!(price > "19.99".parse().unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Decimal(Box<str>);
+
+impl BigInt {
+    /// The decimal digits of the integer, preceded by `-` if it is negative
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0.starts_with('-')
+    }
+
+    /// The nearest `f64`, which is infinite if the integer is too large
+    pub fn to_f64(&self) -> f64 {
+        self.0.parse().unwrap_or(f64::NAN)
+    }
+}
+
+impl Decimal {
+    /// The decimal `unscaled` × 10<sup>-`scale`</sup>
+    pub fn new(unscaled: BigInt, scale: u32) -> Self {
+        let (negative, digits) = match unscaled.0.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, &*unscaled.0),
+        };
+        let scale = scale as usize;
+        let padded = format!("{:0>width$}", digits, width = scale + 1);
+        let (int, frac) = padded.split_at(padded.len() - scale);
+        let mut out = String::with_capacity(padded.len() + 2);
+        if negative {
+            out.push('-');
+        }
+        out.push_str(int);
+        if scale > 0 {
+            out.push('.');
+            out.push_str(frac);
+        }
+        Self(out.into())
+    }
+
+    /// The decimal as a string, for example `-12.50`
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The number of digits after the decimal point
+    pub fn scale(&self) -> u32 {
+        self.parts().frac.len() as u32
+    }
+
+    /// The integer which is this decimal × 10<sup>[`Self::scale()`]</sup>
+    pub fn unscaled(&self) -> BigInt {
+        let parts = self.parts();
+        let digits = format!("{}{}", parts.int, parts.frac);
+        let digits = digits.trim_start_matches('0');
+        match (digits.is_empty(), parts.negative) {
+            (true, _) => BigInt("0".into()),
+            (false, true) => BigInt(format!("-{}", digits).into()),
+            (false, false) => BigInt(digits.into()),
+        }
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0.starts_with('-')
+    }
+
+    /// The nearest `f64`
+    pub fn to_f64(&self) -> f64 {
+        self.0.parse().unwrap_or(f64::NAN)
+    }
+
+    fn parts(&self) -> Parts<'_> {
+        Parts::of(&self.0)
+    }
+}
+
+/// The sign and digits of a number in canonical form
+struct Parts<'a> {
+    negative: bool,
+    int: &'a str,
+    frac: &'a str,
+}
+
+impl<'a> Parts<'a> {
+    fn of(canonical: &'a str) -> Self {
+        let (negative, digits) = match canonical.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, canonical),
+        };
+        let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+        Self {
+            negative,
+            int,
+            frac,
+        }
+    }
+
+    /// Parse `s` into the canonical form, which has no leading zeros, no negative zero and a
+    /// fractional part only if `decimal` is true
+    fn canonical(s: &str, decimal: bool) -> Result<String, InvalidNumber> {
+        let invalid = || InvalidNumber(s.to_string());
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s),
+        };
+        let (int, frac) = match digits.split_once('.') {
+            Some(_) if !decimal => return Err(invalid()),
+            Some((_, "")) => return Err(invalid()),
+            Some((int, frac)) => (int, frac),
+            None => (digits, ""),
+        };
+        let all_digits = |d: &str| d.bytes().all(|b| b.is_ascii_digit());
+        if int.is_empty() || !all_digits(int) || !all_digits(frac) {
+            return Err(invalid());
+        }
+        let int = match int.trim_start_matches('0') {
+            "" => "0",
+            trimmed => trimmed,
+        };
+        let is_zero = int == "0" && frac.bytes().all(|b| b == b'0');
+        let mut out = String::with_capacity(s.len());
+        if negative && !is_zero {
+            out.push('-');
+        }
+        out.push_str(int);
+        if !frac.is_empty() {
+            out.push('.');
+            out.push_str(frac);
+        }
+        Ok(out)
+    }
+
+    /// Compare the numbers these parts represent, ignoring trailing zeros after the point
+    fn cmp_value(&self, other: &Parts<'_>) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => self.cmp_magnitude(other),
+            (true, true) => other.cmp_magnitude(self),
+        }
+    }
+
+    fn cmp_magnitude(&self, other: &Parts<'_>) -> Ordering {
+        let int = (self.int.len(), self.int).cmp(&(other.int.len(), other.int));
+        let len = self.frac.len().max(other.frac.len());
+        int.then_with(|| padded(self.frac, len).cmp(padded(other.frac, len)))
+    }
+}
+
+/// The digits of `frac` followed by zeros up to `len` digits
+fn padded(frac: &str, len: usize) -> impl Iterator<Item = u8> + '_ {
+    frac.bytes().chain(std::iter::repeat(b'0')).take(len)
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        Parts::of(&self.0).cmp_value(&Parts::of(&other.0))
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.parts()
+            .cmp_value(&other.parts())
+            .then_with(|| self.scale().cmp(&other.scale()))
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl FromStr for BigInt {
+    type Err = InvalidNumber;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(Parts::canonical(s, false)?.into()))
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = InvalidNumber;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(Parts::canonical(s, true)?.into()))
+    }
+}
+
+impl AsRef<str> for BigInt {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Decimal {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl serde::Serialize for BigInt {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl serde::Serialize for Decimal {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl From<BigInt> for Decimal {
+    fn from(n: BigInt) -> Self {
+        Self(n.0)
+    }
+}
+
+/// Converts the shortest decimal which parses back to `f`, so `0.1` is `0.1` rather than the
+/// exact value of the nearest `f64`
+impl TryFrom<f64> for Decimal {
+    type Error = NumberOutOfRange;
+
+    fn try_from(f: f64) -> Result<Self, Self::Error> {
+        if !f.is_finite() {
+            return Err(NumberOutOfRange);
+        }
+        // `Display` for `f64` never uses an exponent
+        let s = f.to_string();
+        Ok(Self(
+            Parts::canonical(&s, true)
+                .map_err(|_| NumberOutOfRange)?
+                .into(),
+        ))
+    }
+}
+
+macro_rules! primitive_conversions {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for BigInt {
+                fn from(n: $t) -> Self {
+                    Self(n.to_string().into())
+                }
+            }
+
+            impl From<$t> for Decimal {
+                fn from(n: $t) -> Self {
+                    Self(n.to_string().into())
+                }
+            }
+
+            impl TryFrom<&BigInt> for $t {
+                type Error = NumberOutOfRange;
+
+                fn try_from(n: &BigInt) -> Result<Self, Self::Error> {
+                    n.0.parse().map_err(|_| NumberOutOfRange)
+                }
+            }
+        )*
+    };
+}
+
+primitive_conversions!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(s: &str) -> Decimal {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_into_canonical_form() {
+        assert_eq!(decimal("007.50").as_str(), "7.50");
+        assert_eq!(decimal("-0.00").as_str(), "0.00");
+        assert_eq!("-000".parse::<BigInt>().unwrap().as_str(), "0");
+        for invalid in ["", "-", "1.", ".5", "1e5", "+1", "1.2.3", "١"] {
+            assert!(invalid.parse::<Decimal>().is_err(), "{}", invalid);
+        }
+        assert!("1.5".parse::<BigInt>().is_err());
+    }
+
+    #[test]
+    fn orders_numerically_then_by_scale() {
+        let mut values = [
+            "-10.5", "2", "-9.75", "0.00", "10", "1.999", "2.0", "0", "-0.1",
+        ]
+        .map(decimal)
+        .to_vec();
+        values.sort();
+        let sorted = values.iter().map(Decimal::as_str).collect::<Vec<_>>();
+        assert_eq!(
+            sorted,
+            ["-10.5", "-9.75", "-0.1", "0", "0.00", "1.999", "2", "2.0", "10"]
+        );
+    }
+
+    #[test]
+    fn converts_between_scaled_and_unscaled() {
+        assert_eq!(Decimal::new(BigInt::from(-5), 3).as_str(), "-0.005");
+        assert_eq!(decimal("-0.005").unscaled(), BigInt::from(-5));
+        assert_eq!(decimal("12.30").unscaled(), BigInt::from(1230));
+        assert_eq!(Decimal::try_from(0.1).unwrap().as_str(), "0.1");
+        assert_eq!(
+            Decimal::try_from(1e21).unwrap().as_str(),
+            "1000000000000000000000"
+        );
+        assert!(Decimal::try_from(f64::NAN).is_err());
+        assert_eq!(u8::try_from(&BigInt::from(300)), Err(NumberOutOfRange));
+    }
+}
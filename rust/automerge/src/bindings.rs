@@ -0,0 +1,237 @@
+//! Two way bindings between values in a document and application state
+//!
+//! A [`Bindings`] connects paths in a document to cells of application state, each of which is
+//! represented by a closure which reads the cell and a closure which writes it. Changes flow in
+//! both directions:
+//!
+//! * [`Bindings::apply_patches()`] writes the new value of every binding which the patches
+//!   touch into its cell, so that remote changes show up in the application
+//! * [`Bindings::commit()`] reads every cell and writes the values which changed since they were
+//!   last synchronised into the document
+//!
+//! A value written into a cell is remembered, so it is not written back to the document by the
+//! next call to [`Bindings::commit()`].
+//!
+//! Paths are JSON pointers (RFC 6901) such as `/todos/0/title`, read in the same way as by
+//! [`crate::ReadDoc::resolve_pointer()`]. A path may point at a scalar value or at a text
+//! object, whose contents are bound as a string.
+//!
+//! # Example
+//!
+//! ```
+//! # use std::cell::RefCell;
+//! # use std::rc::Rc;
+//! # use automerge::{AutoCommit, ScalarValue, ROOT};
+//! # use automerge::bindings::Bindings;
+//! # use automerge::transaction::Transactable;
+//! let title = Rc::new(RefCell::new(ScalarValue::from("untitled")));
+//! let mut bindings = Bindings::new();
+//! let (get, set) = (title.clone(), title.clone());
+//! bindings
+//!     .bind(
+//!         "/title",
+//!         move || Some(get.borrow().clone()),
+//!         move |value| *set.borrow_mut() = value.unwrap_or(ScalarValue::Null),
+//!     )
+//!     .unwrap();
+//!
+//! // application state is written into the document
+//! let mut doc = AutoCommit::new();
+//! bindings.commit(&mut doc).unwrap();
+//! doc.update_diff_cursor();
+//!
+//! // and changes to the document are written into the application state
+//! let mut remote = doc.fork();
+//! remote.put(ROOT, "title", "my notes").unwrap();
+//! doc.merge(&mut remote).unwrap();
+//! let patches = doc.diff_incremental();
+//! bindings.apply_patches(&doc, &patches);
+//! assert_eq!(*title.borrow(), ScalarValue::from("my notes"));
+//! ```
+use std::fmt;
+
+use crate::exid::ExId;
+use crate::path::{parse_pointer, pointer_index, token_matches};
+use crate::transaction::Transactable;
+use crate::{AutomergeError, ObjType, Patch, PatchAction, Prop, ReadDoc, ScalarValue, Value, ROOT};
+
+type Getter = Box<dyn FnMut() -> Option<ScalarValue>>;
+type Setter = Box<dyn FnMut(Option<ScalarValue>)>;
+
+struct Binding {
+    path: Vec<String>,
+    get: Getter,
+    set: Setter,
+    last: Option<ScalarValue>,
+}
+
+/// A set of bindings between paths in a document and application state, see the
+/// [module documentation](self)
+#[derive(Default)]
+pub struct Bindings {
+    bindings: Vec<Binding>,
+}
+
+impl fmt::Debug for Bindings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.bindings.iter().map(|b| &b.path))
+            .finish()
+    }
+}
+
+impl Bindings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bind the value at `path` to the cell read by `get` and written by `set`
+    ///
+    /// `get` returns `None` when the cell has no value, in which case nothing is written to the
+    /// document. `set` is called with `None` when there is no scalar value or text at `path`.
+    ///
+    /// Returns [`AutomergeError::InvalidPath`] if `path` is not a JSON pointer or is the empty
+    /// pointer, which refers to the root.
+    pub fn bind<G, S>(&mut self, path: &str, get: G, set: S) -> Result<(), AutomergeError>
+    where
+        G: FnMut() -> Option<ScalarValue> + 'static,
+        S: FnMut(Option<ScalarValue>) + 'static,
+    {
+        let path = parse_pointer(path)?;
+        if path.is_empty() {
+            return Err(AutomergeError::InvalidPath(String::new()));
+        }
+        self.bindings.push(Binding {
+            path,
+            get: Box::new(get),
+            set: Box::new(set),
+            last: None,
+        });
+        Ok(())
+    }
+
+    /// Write the value in `doc` of every binding into its cell
+    pub fn pull<R: ReadDoc>(&mut self, doc: &R) {
+        for binding in &mut self.bindings {
+            binding.pull(doc);
+        }
+    }
+
+    /// Write the value in `doc` of every binding which `patches` may have changed into its cell
+    ///
+    /// `patches` must describe the changes which brought `doc` to its current state, for example
+    /// the result of [`crate::AutoCommit::diff_incremental()`].
+    pub fn apply_patches<R: ReadDoc>(&mut self, doc: &R, patches: &[Patch]) {
+        let changed = patches.iter().map(changed_path).collect::<Vec<_>>();
+        for binding in &mut self.bindings {
+            if changed.iter().any(|path| binding.overlaps(path)) {
+                binding.pull(doc);
+            }
+        }
+    }
+
+    /// Write the value of every cell which changed since it was last synchronised into `doc`,
+    /// returning the number of values written
+    ///
+    /// Maps which are missing along the path of a binding are created.
+    pub fn commit<T: Transactable>(&mut self, doc: &mut T) -> Result<usize, AutomergeError> {
+        let mut written = 0;
+        for binding in &mut self.bindings {
+            let Some(value) = (binding.get)() else {
+                continue;
+            };
+            if binding.last.as_ref() == Some(&value) {
+                continue;
+            }
+            binding.write(doc, &value)?;
+            binding.last = Some(value);
+            written += 1;
+        }
+        Ok(written)
+    }
+}
+
+impl Binding {
+    fn pull<R: ReadDoc>(&mut self, doc: &R) {
+        let value = self.read(doc);
+        if value != self.last {
+            self.last = value.clone();
+            (self.set)(value);
+        }
+    }
+
+    fn read<R: ReadDoc>(&self, doc: &R) -> Option<ScalarValue> {
+        let (last, parents) = self.path.split_last()?;
+        let mut obj = ROOT;
+        for key in parents {
+            let prop = prop_in(doc, &obj, key)?;
+            match doc.get(&obj, prop).ok()?? {
+                (Value::Object(_), id) => obj = id,
+                _ => return None,
+            }
+        }
+        match doc.get(&obj, prop_in(doc, &obj, last)?).ok()?? {
+            (Value::Scalar(s), _) => Some(s.into_owned()),
+            (Value::Object(ObjType::Text), id) => doc.text(&id).ok().map(ScalarValue::from),
+            _ => None,
+        }
+    }
+
+    fn write<T: Transactable>(
+        &self,
+        doc: &mut T,
+        value: &ScalarValue,
+    ) -> Result<(), AutomergeError> {
+        let Some((last, parents)) = self.path.split_last() else {
+            return Err(AutomergeError::InvalidOp(ObjType::Map));
+        };
+        let mut obj = ROOT;
+        for key in parents {
+            let prop = prop_in(doc, &obj, key).ok_or(AutomergeError::InvalidOp(ObjType::List))?;
+            obj = doc.get_or_put_object(&obj, prop, ObjType::Map)?;
+        }
+        let prop = prop_in(doc, &obj, last).ok_or(AutomergeError::InvalidOp(ObjType::List))?;
+        match (doc.get(&obj, prop.clone())?, value) {
+            (Some((Value::Object(ObjType::Text), text)), ScalarValue::Str(s)) => {
+                doc.update_text(&text, s)
+            }
+            _ => doc.put(&obj, prop, value.clone()),
+        }
+    }
+
+    /// Whether a change at `path` may change the value of this binding
+    fn overlaps(&self, path: &[Prop]) -> bool {
+        self.path
+            .iter()
+            .zip(path)
+            .all(|(token, prop)| token_matches(token, prop))
+    }
+}
+
+fn prop_in<R: ReadDoc>(doc: &R, obj: &ExId, key: &str) -> Option<Prop> {
+    match doc.object_type(obj).ok()? {
+        ObjType::Map | ObjType::Table => Some(Prop::Map(key.to_string())),
+        ObjType::List | ObjType::Text => pointer_index(key).map(Prop::Seq),
+    }
+}
+
+/// The path of the value changed by `patch`
+///
+/// Inserting into or deleting from a sequence moves every later element so this is the path of
+/// the sequence itself for those patches.
+fn changed_path(patch: &Patch) -> Vec<Prop> {
+    let mut path = patch
+        .path
+        .iter()
+        .map(|(_, prop)| prop.clone())
+        .collect::<Vec<_>>();
+    match &patch.action {
+        PatchAction::Insert { .. }
+        | PatchAction::DeleteSeq { .. }
+        | PatchAction::SpliceText { .. }
+        | PatchAction::Splice { .. }
+        | PatchAction::Mark { .. } => {}
+        action => path.extend(action.prop()),
+    }
+    path
+}
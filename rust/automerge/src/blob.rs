@@ -0,0 +1,177 @@
+//! References to binary data stored outside the document
+//!
+//! Putting a large image or attachment into a document as [`crate::ScalarValue::Bytes`] copies
+//! it into every change, sync message and save. A [`BlobRef`] instead records the SHA-256 hash
+//! and size of the data, and the data itself is kept in a [`BlobStore`] which the document
+//! consults when the blob is read.
+//!
+//! ```
+//! # use std::sync::Arc;
+//! # use automerge::{AutoCommit, ReadDoc, ROOT, transaction::Transactable};
+//! # use automerge::blob::{BlobRef, MemoryBlobStore};
+//! let store = Arc::new(MemoryBlobStore::default());
+//! let mut doc = AutoCommit::new();
+//! doc.set_blob_store(store.clone());
+//!
+//! let blob = doc.store_blob(b"a very large image").unwrap().with_hint("image/png");
+//! doc.put(ROOT, "avatar", blob).unwrap();
+//!
+//! // another replica receives the reference and reads the data from a shared store
+//! let mut other = AutoCommit::load(&doc.save()).unwrap();
+//! other.set_blob_store(store);
+//! let (value, _) = other.get(ROOT, "avatar").unwrap().unwrap();
+//! let blob = value.to_scalar().unwrap().to_blob_ref().unwrap();
+//! assert_eq!(blob.hint.as_deref(), Some("image/png"));
+//! assert_eq!(other.read_blob(blob).unwrap(), b"a very large image");
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::{Mutex, PoisonError};
+
+use sha2::{Digest, Sha256};
+
+/// The SHA-256 hash of the contents of a blob
+#[derive(Eq, PartialEq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct BlobHash(pub [u8; 32]);
+
+impl BlobHash {
+    pub fn of(data: &[u8]) -> Self {
+        Self(Sha256::digest(data).into())
+    }
+}
+
+impl fmt::Debug for BlobHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("BlobHash")
+            .field(&hex::encode(self.0))
+            .finish()
+    }
+}
+
+impl fmt::Display for BlobHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+impl serde::Serialize for BlobHash {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(self.0))
+    }
+}
+
+/// A reference to binary data stored outside the document
+///
+/// This is stored as [`crate::ScalarValue::BlobRef`]. The `hint` is for the application, for
+/// example a media type or file name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize)]
+pub struct BlobRef {
+    pub hash: BlobHash,
+    pub size: u64,
+    pub hint: Option<String>,
+}
+
+impl BlobRef {
+    /// A reference to `data`, which must be put in a [`BlobStore`] separately
+    pub fn new(data: &[u8]) -> Self {
+        Self {
+            hash: BlobHash::of(data),
+            size: data.len() as u64,
+            hint: None,
+        }
+    }
+
+    /// Set the hint, an empty hint is the same as no hint
+    pub fn with_hint<S: Into<String>>(mut self, hint: S) -> Self {
+        self.hint = Some(hint.into()).filter(|h| !h.is_empty());
+        self
+    }
+
+    /// Check that `data` is the data this refers to
+    pub fn verify(&self, data: &[u8]) -> Result<(), BlobError> {
+        if data.len() as u64 != self.size {
+            return Err(BlobError::WrongSize {
+                hash: self.hash,
+                expected: self.size,
+                actual: data.len() as u64,
+            });
+        }
+        if BlobHash::of(data) != self.hash {
+            return Err(BlobError::HashMismatch(self.hash));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for BlobRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({} bytes", self.hash, self.size)?;
+        if let Some(hint) = &self.hint {
+            write!(f, ", {:?}", hint)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Where the data of [`BlobRef`]s is kept, see [`crate::Automerge::set_blob_store()`]
+///
+/// The document checks the hash of the data a store returns, so a store does not need to.
+pub trait BlobStore: Send + Sync {
+    /// The data with `hash`, or `None` if the store does not have it
+    fn get(&self, hash: &BlobHash) -> io::Result<Option<Vec<u8>>>;
+
+    /// Keep `data`, whose hash is `hash`
+    fn put(&self, hash: &BlobHash, data: &[u8]) -> io::Result<()>;
+}
+
+/// A [`BlobStore`] which keeps blobs in memory
+#[derive(Debug, Default)]
+pub struct MemoryBlobStore {
+    blobs: Mutex<HashMap<BlobHash, Vec<u8>>>,
+}
+
+impl MemoryBlobStore {
+    pub fn len(&self) -> usize {
+        self.blobs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl BlobStore for MemoryBlobStore {
+    fn get(&self, hash: &BlobHash) -> io::Result<Option<Vec<u8>>> {
+        let blobs = self.blobs.lock().unwrap_or_else(PoisonError::into_inner);
+        Ok(blobs.get(hash).cloned())
+    }
+
+    fn put(&self, hash: &BlobHash, data: &[u8]) -> io::Result<()> {
+        let mut blobs = self.blobs.lock().unwrap_or_else(PoisonError::into_inner);
+        blobs.entry(*hash).or_insert_with(|| data.to_vec());
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BlobError {
+    #[error("the document has no blob store")]
+    NoStore,
+    #[error("the blob store does not have blob {0}")]
+    Missing(BlobHash),
+    #[error("blob {hash} should have {expected} bytes but has {actual}")]
+    WrongSize {
+        hash: BlobHash,
+        expected: u64,
+        actual: u64,
+    },
+    #[error("the data of blob {0} does not match its hash")]
+    HashMismatch(BlobHash),
+    #[error("error accessing the blob store: {0}")]
+    Store(#[from] io::Error),
+}
@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::AutomergeError;
+
+/// A handle which can be used to abort a long running operation from another thread
+///
+/// Clones of a token share the same state, so a token can be passed to an operation such as
+/// [`crate::Automerge::apply_changes_cancellable()`] or [`crate::LoadOptions::cancellation()`]
+/// whilst a clone is kept to call [`Self::cancel()`] with. Cancelled operations return
+/// [`AutomergeError::Cancelled`].
+///
+/// ```
+/// # use automerge::{Automerge, AutomergeError, CancellationToken};
+/// let token = CancellationToken::new();
+/// token.cancel();
+///
+/// let mut doc = Automerge::new();
+/// let result = doc.apply_changes_cancellable(Vec::new(), &token);
+/// assert!(matches!(result, Err(AutomergeError::Cancelled)));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask any operation using this token to stop as soon as possible
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn check(&self) -> Result<(), AutomergeError> {
+        if self.is_cancelled() {
+            Err(AutomergeError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! Import and export of the current state of a document as CBOR (RFC 8949)
+//!
+//! Only the materialized value of the document is exported, not its history. Values are mapped
+//! as follows:
+//!
+//! | automerge                  | CBOR                                  |
+//! |----------------------------|---------------------------------------|
+//! | map, table                 | map with text keys                    |
+//! | list                       | array                                 |
+//! | text, string               | text string                           |
+//! | bytes                      | byte string                           |
+//! | int, uint, counter         | integer                               |
+//! | f64                        | float                                 |
+//! | timestamp                  | tag 1 (epoch time in seconds)         |
+//! | boolean, null              | simple value                          |
+//!
+//! On import text strings become string scalars rather than text objects, integers become `int`s
+//! if they fit in an `i64` and `uint`s otherwise, and tags other than tag 1 are ignored.
+//!
+//! # Example
+//!
+//! ```
+//! # use automerge::{AutoCommit, ReadDoc, ScalarValue, ROOT};
+//! # use automerge::cbor::{from_cbor, to_cbor};
+//! # use automerge::transaction::Transactable;
+//! let mut doc = AutoCommit::new();
+//! doc.put(ROOT, "reading", 21.5).unwrap();
+//! doc.put(ROOT, "raw", vec![0xde, 0xad]).unwrap();
+//!
+//! let bytes = to_cbor(&doc).unwrap();
+//! let copy = from_cbor(&bytes).unwrap();
+//! assert_eq!(
+//!     copy.get(ROOT, "raw").unwrap().unwrap().0.to_bytes(),
+//!     Some(&[0xde, 0xad][..])
+//! );
+//! ```
+use ciborium::value::{Integer, Value as Cbor};
+
+use crate::exid::ExId;
+use crate::transaction::Transactable;
+use crate::{AutoCommit, AutomergeError, ObjType, ReadDoc, ScalarValue, Value, ROOT};
+
+const DATE_TIME_TAG: u64 = 0;
+const EPOCH_TIME_TAG: u64 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CborError {
+    #[error("invalid CBOR: {0}")]
+    Decode(String),
+    #[error("failed to encode CBOR: {0}")]
+    Encode(String),
+    #[error("the top level CBOR value must be a map")]
+    RootNotAMap,
+    #[error("map keys must be text strings")]
+    NonTextKey,
+    #[error("a segment of a chunked bytes value is missing")]
+    MissingSegment,
+    #[error(transparent)]
+    Automerge(#[from] AutomergeError),
+}
+
+/// Encode the current state of `doc` as CBOR
+pub fn to_cbor<R: ReadDoc>(doc: &R) -> Result<Vec<u8>, CborError> {
+    let value = object_to_cbor(doc, &ROOT, ObjType::Map)?;
+    let mut bytes = Vec::new();
+    ciborium::ser::into_writer(&value, &mut bytes).map_err(|e| CborError::Encode(e.to_string()))?;
+    Ok(bytes)
+}
+
+/// Create a new document whose state is the CBOR map in `bytes`
+pub fn from_cbor(bytes: &[u8]) -> Result<AutoCommit, CborError> {
+    let value: Cbor =
+        ciborium::de::from_reader(bytes).map_err(|e| CborError::Decode(e.to_string()))?;
+    let Cbor::Map(entries) = value else {
+        return Err(CborError::RootNotAMap);
+    };
+    let mut doc = AutoCommit::new();
+    put_entries(&mut doc, &ROOT, entries)?;
+    Ok(doc)
+}
+
+fn object_to_cbor<R: ReadDoc>(doc: &R, obj: &ExId, typ: ObjType) -> Result<Cbor, CborError> {
+    Ok(match typ {
+        ObjType::Map | ObjType::Table => {
+            let mut entries = Vec::new();
+            for item in doc.map_range(obj, ..) {
+                let value = value_to_cbor(doc, item.value, &item.id)?;
+                entries.push((Cbor::Text(item.key.to_string()), value));
+            }
+            Cbor::Map(entries)
+        }
+        ObjType::List => {
+            let mut elems = Vec::new();
+            for item in doc.list_range(obj, ..) {
+                elems.push(value_to_cbor(doc, item.value, &item.id)?);
+            }
+            Cbor::Array(elems)
+        }
+        ObjType::Text => Cbor::Text(doc.text(obj)?),
+    })
+}
+
+fn value_to_cbor<R: ReadDoc>(doc: &R, value: Value<'_>, id: &ExId) -> Result<Cbor, CborError> {
+    Ok(match value {
+        Value::Object(typ) => object_to_cbor(doc, id, typ)?,
+        Value::Scalar(s) => match s.as_ref() {
+            ScalarValue::Bytes(b) => Cbor::Bytes(b.clone()),
+            ScalarValue::Chunked(b) => Cbor::Bytes(b.to_vec().ok_or(CborError::MissingSegment)?),
+            ScalarValue::Str(s) => Cbor::Text(s.to_string()),
+            ScalarValue::Int(i) => Cbor::Integer(Integer::from(*i)),
+            ScalarValue::Uint(u) => Cbor::Integer(Integer::from(*u)),
+            ScalarValue::F64(f) => Cbor::Float(*f),
+            ScalarValue::Counter(c) => Cbor::Integer(Integer::from(i64::from(c))),
+            ScalarValue::Timestamp(ms) => {
+                let secs = if ms % 1000 == 0 {
+                    Cbor::Integer(Integer::from(ms / 1000))
+                } else {
+                    Cbor::Float(*ms as f64 / 1000.0)
+                };
+                Cbor::Tag(EPOCH_TIME_TAG, Box::new(secs))
+            }
+            ScalarValue::BigInt(n) => match (i64::try_from(n), u64::try_from(n)) {
+                (Ok(i), _) => Cbor::Integer(Integer::from(i)),
+                (_, Ok(u)) => Cbor::Integer(Integer::from(u)),
+                // too large for a CBOR integer, so keep every digit as text
+                _ => Cbor::Text(n.to_string()),
+            },
+            ScalarValue::Decimal(n) => Cbor::Text(n.to_string()),
+            // the data is not in the document, so only the hash is exported
+            ScalarValue::BlobRef(b) => Cbor::Text(b.hash.to_string()),
+            ScalarValue::Link(d) => Cbor::Text(d.to_string()),
+            ScalarValue::DateTime(d) => {
+                Cbor::Tag(DATE_TIME_TAG, Box::new(Cbor::Text(d.to_string())))
+            }
+            ScalarValue::Boolean(b) => Cbor::Bool(*b),
+            ScalarValue::Unknown { bytes, .. } => Cbor::Bytes(bytes.clone()),
+            ScalarValue::Null => Cbor::Null,
+        },
+    })
+}
+
+fn put_entries(
+    doc: &mut AutoCommit,
+    obj: &ExId,
+    entries: Vec<(Cbor, Cbor)>,
+) -> Result<(), CborError> {
+    for (key, value) in entries {
+        let Cbor::Text(key) = key else {
+            return Err(CborError::NonTextKey);
+        };
+        match untag(value) {
+            Cbor::Map(entries) => {
+                let child = doc.put_object(obj, key, ObjType::Map)?;
+                put_entries(doc, &child, entries)?;
+            }
+            Cbor::Array(elems) => {
+                let child = doc.put_object(obj, key, ObjType::List)?;
+                insert_elems(doc, &child, elems)?;
+            }
+            scalar => doc.put(obj, key, to_scalar(scalar))?,
+        }
+    }
+    Ok(())
+}
+
+fn insert_elems(doc: &mut AutoCommit, obj: &ExId, elems: Vec<Cbor>) -> Result<(), CborError> {
+    for (index, value) in elems.into_iter().enumerate() {
+        match untag(value) {
+            Cbor::Map(entries) => {
+                let child = doc.insert_object(obj, index, ObjType::Map)?;
+                put_entries(doc, &child, entries)?;
+            }
+            Cbor::Array(elems) => {
+                let child = doc.insert_object(obj, index, ObjType::List)?;
+                insert_elems(doc, &child, elems)?;
+            }
+            scalar => doc.insert(obj, index, to_scalar(scalar))?,
+        }
+    }
+    Ok(())
+}
+
+/// Strip every tag except the epoch time tag, which is converted by [`to_scalar()`]
+fn untag(value: Cbor) -> Cbor {
+    match value {
+        Cbor::Tag(EPOCH_TIME_TAG, inner) => Cbor::Tag(EPOCH_TIME_TAG, inner),
+        Cbor::Tag(_, inner) => untag(*inner),
+        value => value,
+    }
+}
+
+fn to_scalar(value: Cbor) -> ScalarValue {
+    match value {
+        Cbor::Integer(i) => match i64::try_from(i) {
+            Ok(i) => ScalarValue::Int(i),
+            // an integer which does not fit in an i64 fits in a u64 unless it is below i64::MIN
+            Err(_) => u64::try_from(i).map_or(ScalarValue::F64(i128::from(i) as f64), |u| {
+                ScalarValue::Uint(u)
+            }),
+        },
+        Cbor::Bytes(b) => ScalarValue::Bytes(b),
+        Cbor::Float(f) => ScalarValue::F64(f),
+        Cbor::Text(s) => ScalarValue::from(s),
+        Cbor::Bool(b) => ScalarValue::Boolean(b),
+        Cbor::Tag(EPOCH_TIME_TAG, inner) => match *inner {
+            Cbor::Integer(secs) => ScalarValue::Timestamp(
+                (i128::from(secs) * 1000).clamp(i64::MIN.into(), i64::MAX.into()) as i64,
+            ),
+            Cbor::Float(secs) => ScalarValue::Timestamp((secs * 1000.0).round() as i64),
+            other => to_scalar(other),
+        },
+        _ => ScalarValue::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_values_which_json_cannot_represent() {
+        let mut doc = AutoCommit::new();
+        doc.put(ROOT, "bytes", vec![0u8, 255]).unwrap();
+        doc.put(ROOT, "big", u64::MAX).unwrap();
+        doc.put(ROOT, "small", i64::MIN).unwrap();
+        doc.put(ROOT, "when", ScalarValue::Timestamp(1_700_000_000_123))
+            .unwrap();
+        let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+        doc.insert(&list, 0, 0.1).unwrap();
+        let nested = doc.insert_object(&list, 1, ObjType::Map).unwrap();
+        let text = doc.put_object(&nested, "text", ObjType::Text).unwrap();
+        doc.splice_text(&text, 0, 0, "hello").unwrap();
+
+        let copy = from_cbor(&to_cbor(&doc).unwrap()).unwrap();
+        let scalar = |obj: &ExId, prop: &str| match copy.get(obj, prop).unwrap() {
+            Some((Value::Scalar(s), _)) => s.into_owned(),
+            other => panic!("unexpected value {:?}", other),
+        };
+        assert_eq!(scalar(&ROOT, "bytes"), ScalarValue::Bytes(vec![0, 255]));
+        assert_eq!(scalar(&ROOT, "big"), ScalarValue::Uint(u64::MAX));
+        assert_eq!(scalar(&ROOT, "small"), ScalarValue::Int(i64::MIN));
+        assert_eq!(
+            scalar(&ROOT, "when"),
+            ScalarValue::Timestamp(1_700_000_000_123)
+        );
+        let (_, list) = copy.get(ROOT, "list").unwrap().unwrap();
+        assert_eq!(
+            copy.get(&list, 0).unwrap().unwrap().0,
+            Value::from(ScalarValue::F64(0.1))
+        );
+        let (_, nested) = copy.get(&list, 1).unwrap().unwrap();
+        assert_eq!(scalar(&nested, "text"), ScalarValue::from("hello"));
+    }
+
+    #[test]
+    fn rejects_values_other_than_maps_with_text_keys() {
+        let encode = |value: &Cbor| {
+            let mut bytes = Vec::new();
+            ciborium::ser::into_writer(value, &mut bytes).unwrap();
+            bytes
+        };
+        assert!(matches!(
+            from_cbor(&encode(&Cbor::Array(vec![]))),
+            Err(CborError::RootNotAMap)
+        ));
+        assert!(matches!(
+            from_cbor(&encode(&Cbor::Map(vec![(Cbor::Bool(true), Cbor::Null)]))),
+            Err(CborError::NonTextKey)
+        ));
+        assert!(matches!(from_cbor(&[0xff]), Err(CborError::Decode(_))));
+    }
+}
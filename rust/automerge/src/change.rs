@@ -1,28 +1,69 @@
-use std::{borrow::Cow, num::NonZeroU64};
+use std::{
+    borrow::Cow,
+    num::NonZeroU64,
+    sync::{Arc, OnceLock},
+};
 
 use crate::{
     columnar::Key as StoredKey,
+    extra_bytes::{self, Field},
     storage::{
         change::{Unverified, Verified},
         parse, Change as StoredChange, ChangeOp, Chunk, Compressed, ReadChangeOpError,
     },
     types::{ActorId, ChangeHash, ElemId},
+    ActorInfo,
 };
 
-#[derive(Clone, Debug, PartialEq)]
+/// A change to a document
+///
+/// The encoded contents of a change are reference counted so cloning a change, or a document
+/// containing it, does not copy them.
+#[derive(Clone, Debug)]
 pub struct Change {
-    stored: StoredChange<'static, Verified>,
-    compression: CompressionState,
+    /// The uncompressed change, which is empty for a compacted change until it is first needed,
+    /// see [`Self::compacted()`]
+    stored: Arc<OnceLock<StoredChange<'static, Verified>>>,
+    compression: Arc<CompressionState>,
+    /// The metadata of a compacted change, so that it can be read without decompressing the
+    /// change
+    meta: Option<Arc<ChangeMeta>>,
+    hash: ChangeHash,
     len: usize,
+    /// Whether the op columns have been fully decoded and checked, see [`Self::new_lazy()`]
+    ops_checked: bool,
+}
+
+#[derive(Debug)]
+struct ChangeMeta {
+    actor: ActorId,
+    other_actors: Vec<ActorId>,
+    seq: u64,
+    start_op: NonZeroU64,
+    timestamp: i64,
+    message: Option<String>,
+    deps: Vec<ChangeHash>,
+    extra_bytes: Vec<u8>,
+}
+
+impl PartialEq for Change {
+    fn eq(&self, other: &Self) -> bool {
+        // the hash covers every byte of the change, so this doesn't need to decompress compacted
+        // changes to compare them
+        self.hash == other.hash && self.len == other.len
+    }
 }
 
 impl Change {
     pub(crate) fn new(stored: StoredChange<'static, Verified>) -> Self {
         let len = stored.len();
         Self {
-            stored,
+            hash: stored.hash(),
+            stored: Arc::new(OnceLock::from(stored)),
+            meta: None,
             len,
-            compression: CompressionState::NotCompressed,
+            compression: Arc::new(CompressionState::NotCompressed),
+            ops_checked: true,
         }
     }
 
@@ -38,18 +79,153 @@ impl Change {
             CompressionState::NotCompressed
         };
         Ok(Self {
-            stored,
+            hash: stored.hash(),
+            stored: Arc::new(OnceLock::from(stored)),
+            meta: None,
+            len,
+            compression: Arc::new(compression),
+            ops_checked: true,
+        })
+    }
+
+    /// Like [`Self::new_from_unverified()`] but only the action column is decoded up front
+    ///
+    /// Decoding the remaining columns (values, keys, preds etc.) is deferred until the ops are
+    /// first needed, which for changes loaded in bulk is when they are applied to a document.
+    /// Until then [`Self::iter_stored_ops()`] must not be called, use [`Self::try_decode_ops()`] or
+    /// [`Self::check_ops()`] instead.
+    ///
+    /// Lazy changes must not leave the crate: they are either applied to a document, which checks
+    /// their ops, or checked before they are returned. Only change chunks are loaded lazily, the
+    /// ops in a document chunk are all decoded to rebuild the op set.
+    pub(crate) fn new_lazy(
+        stored: StoredChange<'static, Unverified>,
+        compressed: Option<Compressed<'static>>,
+    ) -> Result<Self, ReadChangeOpError> {
+        let stored = stored.count_ops_unchecked()?;
+        let len = stored.len();
+        let compression = if let Some(c) = compressed {
+            CompressionState::Compressed(c)
+        } else {
+            CompressionState::NotCompressed
+        };
+        Ok(Self {
+            hash: stored.hash(),
+            stored: Arc::new(OnceLock::from(stored)),
+            meta: None,
             len,
-            compression,
+            compression: Arc::new(compression),
+            ops_checked: false,
         })
     }
 
+    /// The uncompressed change, decompressing it if this change has been compacted
+    fn stored(&self) -> &StoredChange<'static, Verified> {
+        self.stored.get_or_init(|| {
+            let CompressionState::Compressed(compressed) = &*self.compression else {
+                // only changes with compressed bytes are compacted
+                panic!("compacted change without compressed bytes");
+            };
+            // the compressed bytes were produced from a change which had already been
+            // verified
+            let (_, chunk) = Chunk::parse(parse::Input::new(compressed.as_bytes()))
+                .expect("compacted change is well formed");
+            let Chunk::CompressedChange(stored, _) = chunk else {
+                panic!("compacted change is not a compressed change");
+            };
+            stored
+                .into_owned()
+                .count_ops_unchecked()
+                .expect("compacted change is well formed")
+        })
+    }
+
+    /// A copy of this change which only keeps its compressed bytes and its metadata in memory
+    ///
+    /// The uncompressed change is decompressed again the first time its ops or its uncompressed
+    /// bytes are needed. Changes which are too small to be worth compressing are returned as
+    /// they are.
+    pub(crate) fn compacted(&self) -> Self {
+        let mut change = self.clone();
+        change.bytes();
+        if !matches!(*change.compression, CompressionState::Compressed(_)) {
+            return change;
+        }
+        let meta = change.meta.clone().unwrap_or_else(|| {
+            let stored = change.stored();
+            Arc::new(ChangeMeta {
+                actor: stored.actor().clone(),
+                other_actors: stored.other_actors().to_vec(),
+                seq: stored.seq(),
+                start_op: stored.start_op(),
+                timestamp: stored.timestamp(),
+                message: stored.message().clone(),
+                deps: stored.dependencies().to_vec(),
+                extra_bytes: stored.extra_bytes().to_vec(),
+            })
+        });
+        Self {
+            stored: Arc::new(OnceLock::new()),
+            meta: Some(meta),
+            ..change
+        }
+    }
+
+    /// The number of bytes of encoded change this change holds in memory
+    pub(crate) fn resident_bytes(&self) -> usize {
+        let stored = self.stored.get().map(|s| s.bytes().len()).unwrap_or(0);
+        match &*self.compression {
+            CompressionState::Compressed(c) => stored + c.as_bytes().len(),
+            _ => stored,
+        }
+    }
+
+    /// Whether the ops in this change are known to be well formed
+    pub(crate) fn ops_checked(&self) -> bool {
+        self.ops_checked
+    }
+
+    /// A copy of this change which will compress itself again when next asked for its bytes
+    pub(crate) fn without_cached_compression(&self) -> Self {
+        // the compressed bytes are the only copy of a compacted change
+        self.stored();
+        Self {
+            compression: Arc::new(CompressionState::NotCompressed),
+            meta: None,
+            ..self.clone()
+        }
+    }
+
+    /// Decode all the ops in this change, failing if any of them are malformed
+    pub(crate) fn try_decode_ops(&mut self) -> Result<Vec<ChangeOp>, ReadChangeOpError> {
+        let ops = self
+            .stored()
+            .try_iter_ops()
+            .collect::<Result<Vec<_>, _>>()?;
+        self.ops_checked = true;
+        Ok(ops)
+    }
+
+    /// Check that all the ops in this change are well formed
+    pub(crate) fn check_ops(&mut self) -> Result<(), ReadChangeOpError> {
+        if !self.ops_checked {
+            self.try_decode_ops()?;
+        }
+        Ok(())
+    }
+
     pub fn actor_id(&self) -> &ActorId {
-        self.stored.actor()
+        match &self.meta {
+            Some(meta) => &meta.actor,
+            None => self.stored().actor(),
+        }
     }
 
     pub fn other_actor_ids(&self) -> &[ActorId] {
-        self.stored.other_actors()
+        match &self.meta {
+            Some(meta) => &meta.other_actors,
+            None => self.stored().other_actors(),
+        }
     }
 
     pub fn len(&self) -> usize {
@@ -61,59 +237,177 @@ impl Change {
     }
 
     pub fn max_op(&self) -> u64 {
-        self.stored.start_op().get() + (self.len as u64) - 1
+        self.start_op().get() + (self.len as u64) - 1
     }
 
     pub fn start_op(&self) -> NonZeroU64 {
-        self.stored.start_op()
+        match &self.meta {
+            Some(meta) => meta.start_op,
+            None => self.stored().start_op(),
+        }
     }
 
     pub fn message(&self) -> Option<&String> {
-        self.stored.message().as_ref()
+        match &self.meta {
+            Some(meta) => meta.message.as_ref(),
+            None => self.stored().message().as_ref(),
+        }
     }
 
     pub fn deps(&self) -> &[ChangeHash] {
-        self.stored.dependencies()
+        match &self.meta {
+            Some(meta) => &meta.deps,
+            None => self.stored().dependencies(),
+        }
     }
 
     pub fn hash(&self) -> ChangeHash {
-        self.stored.hash()
+        self.hash
     }
 
     pub fn seq(&self) -> u64 {
-        self.stored.seq()
+        match &self.meta {
+            Some(meta) => meta.seq,
+            None => self.stored().seq(),
+        }
     }
 
     pub fn timestamp(&self) -> i64 {
-        self.stored.timestamp()
+        match &self.meta {
+            Some(meta) => meta.timestamp,
+            None => self.stored().timestamp(),
+        }
     }
 
     pub fn bytes(&mut self) -> Cow<'_, [u8]> {
-        if let CompressionState::NotCompressed = self.compression {
-            if let Some(compressed) = self.stored.compress() {
-                self.compression = CompressionState::Compressed(compressed);
+        if let CompressionState::NotCompressed = *self.compression {
+            if let Some(compressed) = self.stored().compress() {
+                self.compression = Arc::new(CompressionState::Compressed(compressed));
             } else {
-                self.compression = CompressionState::TooSmallToCompress;
+                self.compression = Arc::new(CompressionState::TooSmallToCompress);
             }
         };
-        match &self.compression {
+        match &*self.compression {
             // SAFETY: We just checked this case above
             CompressionState::NotCompressed => unreachable!(),
-            CompressionState::TooSmallToCompress => Cow::Borrowed(self.stored.bytes()),
+            CompressionState::TooSmallToCompress => Cow::Borrowed(self.stored().bytes()),
             CompressionState::Compressed(c) => c.bytes(),
         }
     }
 
     pub fn raw_bytes(&self) -> &[u8] {
-        self.stored.bytes()
+        self.stored().bytes()
     }
 
-    pub(crate) fn iter_ops(&self) -> impl Iterator<Item = ChangeOp> + '_ {
-        self.stored.iter_ops()
+    /// The operations in this change, decoded
+    ///
+    /// The ID of the nth operation is `start_op + n` with the actor ID of the change, see
+    /// [`Self::start_op()`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ExpandedKey, ExpandedOpType, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = AutoCommit::new();
+    /// doc.put(ROOT, "title", "Hello").unwrap();
+    /// let hash = doc.commit().unwrap();
+    ///
+    /// let change = doc.get_change_by_hash(&hash).unwrap();
+    /// let op = change.iter_ops().next().unwrap();
+    /// assert_eq!(op.key, ExpandedKey::Map("title".into()));
+    /// assert_eq!(op.action, ExpandedOpType::Put("Hello".into()));
+    /// assert!(op.pred.is_empty());
+    /// ```
+    pub fn iter_ops(&self) -> impl Iterator<Item = crate::legacy::Op> + '_ {
+        let actors = std::iter::once(self.actor_id())
+            .chain(self.other_actor_ids().iter())
+            .cloned()
+            .collect::<Vec<_>>();
+        let op_id = move |counter: u64, actor: usize| {
+            crate::legacy::OpId::new(counter, actors.get(actor).unwrap())
+        };
+        self.iter_stored_ops().map(move |o| crate::legacy::Op {
+            action: crate::legacy::OpType::from_parts(crate::legacy::OpTypeParts {
+                action: o.action,
+                value: crate::columnar::column_range::decode_extension(o.val),
+                expand: o.expand,
+                mark_name: o.mark_name,
+            }),
+            insert: o.insert,
+            key: match o.key {
+                StoredKey::Elem(e) if e.is_head() => {
+                    crate::legacy::Key::Seq(crate::legacy::ElementId::Head)
+                }
+                StoredKey::Elem(ElemId(o)) => crate::legacy::Key::Seq(
+                    crate::legacy::ElementId::Id(op_id(o.counter(), o.actor())),
+                ),
+                StoredKey::Prop(p) => crate::legacy::Key::Map(p),
+            },
+            obj: if o.obj.is_root() {
+                crate::legacy::ObjectId::Root
+            } else {
+                crate::legacy::ObjectId::Id(op_id(o.obj.opid().counter(), o.obj.opid().actor()))
+            },
+            pred: o
+                .pred
+                .into_iter()
+                .map(|p| op_id(p.counter(), p.actor()))
+                .collect(),
+        })
     }
 
+    pub(crate) fn iter_stored_ops(&self) -> impl Iterator<Item = ChangeOp> + '_ {
+        debug_assert!(
+            self.ops_checked,
+            "ops of a lazily loaded change were not checked"
+        );
+        self.stored().iter_ops()
+    }
+
+    /// The extra bytes of this change as they are encoded
+    ///
+    /// Automerge stores its own metadata, such as [`Self::tag()`], in the extra bytes alongside
+    /// the bytes passed to [`crate::transaction::CommitOptions::with_extra_bytes()`]. When it does
+    /// the extra bytes start with the reserved prefix `automerge:extra:`, use
+    /// [`Self::app_extra_bytes()`] to read just the bytes the application set.
     pub fn extra_bytes(&self) -> &[u8] {
-        self.stored.extra_bytes()
+        match &self.meta {
+            Some(meta) => &meta.extra_bytes,
+            None => self.stored().extra_bytes(),
+        }
+    }
+
+    /// The bytes the application stored with this change, see
+    /// [`crate::transaction::CommitOptions::with_extra_bytes()`]
+    ///
+    /// For changes made by other implementations, whose extra bytes don't start with the prefix
+    /// reserved by automerge, this is the same as [`Self::extra_bytes()`].
+    pub fn app_extra_bytes(&self) -> &[u8] {
+        extra_bytes::read(self.extra_bytes(), Field::Application).unwrap_or_default()
+    }
+
+    /// The actor ID the author of this change used before rotating to the actor ID of this
+    /// change, if the author recorded it
+    ///
+    /// See [`crate::AutoCommit::link_actor_rotations()`]
+    pub fn rotated_from(&self) -> Option<ActorId> {
+        extra_bytes::read(self.extra_bytes(), Field::RotatedFrom).map(ActorId::from)
+    }
+
+    /// The name this change tags its dependencies with, if it is a tag
+    ///
+    /// See [`crate::Automerge::tag()`]
+    pub fn tag(&self) -> Option<&str> {
+        extra_bytes::read(self.extra_bytes(), Field::Tag)
+            .and_then(|name| std::str::from_utf8(name).ok())
+    }
+
+    /// The description of its actor this change records, if it records one
+    ///
+    /// See [`crate::Automerge::set_actor_info()`]
+    pub fn actor_info(&self) -> Option<ActorInfo> {
+        extra_bytes::read(self.extra_bytes(), Field::ActorInfo).and_then(ActorInfo::decode)
     }
 
     // TODO replace all uses of this with TryFrom<&[u8]>
@@ -121,6 +415,10 @@ impl Change {
         Self::try_from(&bytes[..])
     }
 
+    /// Expand this change into its operations
+    ///
+    /// An [`crate::ExpandedChange`] serializes to and from the JSON representation of changes
+    /// used by the JavaScript implementation, and converts back into a [`Change`] with `into()`.
     pub fn decode(&self) -> crate::ExpandedChange {
         crate::ExpandedChange::from(self)
     }
@@ -138,13 +436,18 @@ enum CompressionState {
 
 impl AsRef<StoredChange<'static, Verified>> for Change {
     fn as_ref(&self) -> &StoredChange<'static, Verified> {
-        &self.stored
+        self.stored()
     }
 }
 
 impl From<Change> for StoredChange<'static, Verified> {
     fn from(c: Change) -> Self {
-        c.stored
+        c.stored();
+        match Arc::try_unwrap(c.stored) {
+            // we just decompressed the change if it was compacted
+            Ok(stored) => stored.into_inner().unwrap(),
+            Err(stored) => stored.get().unwrap().clone(),
+        }
     }
 }
 
@@ -282,50 +585,9 @@ mod convert_expanded {
 
 impl From<&Change> for crate::ExpandedChange {
     fn from(c: &Change) -> Self {
-        let actors = std::iter::once(c.actor_id())
-            .chain(c.other_actor_ids().iter())
-            .cloned()
-            .enumerate()
-            .collect::<std::collections::HashMap<_, _>>();
-        let operations = c
-            .iter_ops()
-            .map(|o| crate::legacy::Op {
-                action: crate::legacy::OpType::from_parts(crate::legacy::OpTypeParts {
-                    action: o.action,
-                    value: o.val,
-                    expand: o.expand,
-                    mark_name: o.mark_name,
-                }),
-                insert: o.insert,
-                key: match o.key {
-                    StoredKey::Elem(e) if e.is_head() => {
-                        crate::legacy::Key::Seq(crate::legacy::ElementId::Head)
-                    }
-                    StoredKey::Elem(ElemId(o)) => {
-                        crate::legacy::Key::Seq(crate::legacy::ElementId::Id(
-                            crate::legacy::OpId::new(o.counter(), actors.get(&o.actor()).unwrap()),
-                        ))
-                    }
-                    StoredKey::Prop(p) => crate::legacy::Key::Map(p),
-                },
-                obj: if o.obj.is_root() {
-                    crate::legacy::ObjectId::Root
-                } else {
-                    crate::legacy::ObjectId::Id(crate::legacy::OpId::new(
-                        o.obj.opid().counter(),
-                        actors.get(&o.obj.opid().actor()).unwrap(),
-                    ))
-                },
-                pred: o
-                    .pred
-                    .into_iter()
-                    .map(|p| crate::legacy::OpId::new(p.counter(), actors.get(&p.actor()).unwrap()))
-                    .collect(),
-            })
-            .collect::<Vec<_>>();
         crate::ExpandedChange {
-            operations,
-            actor_id: actors.get(&0).unwrap().clone(),
+            operations: c.iter_ops().collect(),
+            actor_id: c.actor_id().clone(),
             hash: Some(c.hash()),
             time: c.timestamp(),
             deps: c.deps().to_vec(),
@@ -424,3 +686,35 @@ pub(crate) mod gen {
 
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{gen::gen_change, Change};
+    use crate::storage::{parse, Chunk};
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn lazily_loaded_changes_decode_the_same_ops(change in gen_change()) {
+            let (_, chunk) = Chunk::parse(parse::Input::new(change.raw_bytes())).unwrap();
+            let Chunk::Change(stored) = chunk else {
+                panic!("expected a change chunk");
+            };
+            let mut lazy = Change::new_lazy(stored.into_owned(), None).unwrap();
+            assert!(!lazy.ops_checked());
+            assert_eq!(lazy.len(), change.len());
+            let ops = lazy.try_decode_ops().unwrap();
+            assert!(lazy.ops_checked());
+            assert_eq!(ops, change.iter_stored_ops().collect::<Vec<_>>());
+        }
+
+        #[test]
+        fn compacted_changes_are_compared_without_decompressing_them(change in gen_change()) {
+            let compacted = change.compacted();
+            let was_compacted = compacted.stored.get().is_none();
+            assert_eq!(compacted, change.compacted());
+            assert_eq!(compacted, change);
+            assert_eq!(compacted.stored.get().is_none(), was_compacted);
+        }
+    }
+}
@@ -197,6 +197,26 @@ impl ChangeGraph {
         });
     }
 
+    /// Remove every hash from `hashes` which is an ancestor of another hash in `hashes`
+    pub(crate) fn remove_redundant(&self, hashes: &mut Vec<ChangeHash>) {
+        hashes.sort();
+        hashes.dedup();
+        if hashes.len() < 2 {
+            return;
+        }
+        let parents = self
+            .heads_to_nodes(hashes)
+            .into_iter()
+            .flat_map(|idx| self.parents(idx))
+            .collect();
+        let mut ancestors = BTreeSet::new();
+        self.traverse_ancestors(parents, |node, _idx| {
+            ancestors.insert(self.hashes[node.hash_idx.0 as usize]);
+            true
+        });
+        hashes.retain(|hash| !ancestors.contains(hash));
+    }
+
     /// Call `f` for each (node, hash) in the graph, starting from the given heads
     ///
     /// No guarantees are made about the order of traversal but each node will only be visited
@@ -287,6 +307,26 @@ mod tests {
         assert_eq!(changes, expected_changes);
     }
 
+    #[test]
+    fn remove_redundant() {
+        let mut builder = TestGraphBuilder::new();
+        let actor1 = builder.actor();
+        let actor2 = builder.actor();
+        let actor3 = builder.actor();
+        let change1 = builder.change(&actor1, 10, &[]);
+        let change2 = builder.change(&actor2, 20, &[change1]);
+        let change3 = builder.change(&actor3, 30, &[change1]);
+        let change4 = builder.change(&actor1, 10, &[change2]);
+        let graph = builder.build();
+
+        let mut hashes = vec![change4, change1, change3, change2, change3];
+        graph.remove_redundant(&mut hashes);
+
+        let mut expected = vec![change3, change4];
+        expected.sort();
+        assert_eq!(hashes, expected);
+    }
+
     struct TestGraphBuilder {
         actors: Vec<ActorId>,
         changes: Vec<Change>,
@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+
+use crate::op_set::{OpBuilder, OpIdx};
+use crate::types::OpType;
+use crate::ScalarValue;
+
+/// Segments are never split shorter than this, except at the end of the data
+const MIN_SEGMENT: usize = 16 * 1024;
+/// Segments are always split at this length
+const MAX_SEGMENT: usize = 256 * 1024;
+/// A segment ends where the top 16 bits of the rolling hash are zero, which gives segments of
+/// around `MIN_SEGMENT` + 64 KiB
+const BOUNDARY_MASK: u64 = 0xffff << 48;
+
+/// Random values for the gear rolling hash, generated with splitmix64 so they never change
+static GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0; 256];
+    let mut state: u64 = 0x6175_746f_6d65_7267;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// The length of the first segment of `data`
+///
+/// Boundaries are chosen by the content around them, so inserting or removing bytes only changes
+/// the segments near the edit and the segments after it are the same as before.
+fn segment_len(data: &[u8]) -> usize {
+    if data.len() <= MIN_SEGMENT {
+        return data.len();
+    }
+    let end = data.len().min(MAX_SEGMENT);
+    let mut hash: u64 = 0;
+    for (i, b) in data[MIN_SEGMENT..end].iter().enumerate() {
+        hash = (hash << 1).wrapping_add(GEAR[*b as usize]);
+        if hash & BOUNDARY_MASK == 0 {
+            return MIN_SEGMENT + i + 1;
+        }
+    }
+    end
+}
+
+/// The SHA-256 hash of a segment of a [`ChunkedBytes`]
+#[derive(Eq, PartialEq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct SegmentHash(pub [u8; 32]);
+
+impl SegmentHash {
+    pub(crate) fn of(data: &[u8]) -> Self {
+        Self(Sha256::digest(data).into())
+    }
+}
+
+impl fmt::Debug for SegmentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SegmentHash")
+            .field(&hex::encode(self.0))
+            .finish()
+    }
+}
+
+impl fmt::Display for SegmentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Segment {
+    pub(crate) hash: SegmentHash,
+    /// The contents of the segment, which are `None` if no op the document has seen carries them
+    pub(crate) data: Option<Arc<[u8]>>,
+    /// Whether the op this value belongs to carries the contents of the segment, rather than
+    /// referring to an earlier op which does
+    pub(crate) inline: bool,
+}
+
+/// A large binary value which is stored in content addressed segments
+///
+/// A [`ScalarValue::Bytes`] is copied in full into the change which creates it and into every
+/// saved document. A `ChunkedBytes` is split into segments at boundaries chosen by its content and
+/// a change only carries the segments which none of the changes it depends on carries already, so
+/// putting a slightly edited copy of a large value only stores the segments around the edit.
+/// Values which share segments share their memory too.
+///
+/// Segments are not loaded lazily: loading a document reads the contents of every segment it
+/// carries into memory, as with any other value.
+///
+/// # Example
+///
+/// ```
+/// # use automerge::{AutoCommit, ChunkedBytes, ReadDoc, ScalarValue, Value, transaction::Transactable};
+/// let mut doc = AutoCommit::new();
+/// let image = vec![7; 1_000_000];
+/// doc.put(automerge::ROOT, "image", ChunkedBytes::new(&image)).unwrap();
+///
+/// let (value, _) = doc.get(automerge::ROOT, "image").unwrap().unwrap();
+/// let Value::Scalar(scalar) = value else { panic!() };
+/// let ScalarValue::Chunked(chunked) = scalar.as_ref() else { panic!() };
+/// assert_eq!(chunked.len(), 1_000_000);
+/// assert_eq!(chunked.to_vec(), Some(image));
+/// ```
+#[derive(Clone)]
+pub struct ChunkedBytes {
+    len: u64,
+    segments: Arc<[Segment]>,
+}
+
+impl ChunkedBytes {
+    pub fn new(data: &[u8]) -> Self {
+        let mut segments = Vec::new();
+        let mut rest = data;
+        while !rest.is_empty() {
+            let (segment, tail) = rest.split_at(segment_len(rest));
+            segments.push(Segment {
+                hash: SegmentHash::of(segment),
+                data: Some(segment.into()),
+                inline: true,
+            });
+            rest = tail;
+        }
+        Self {
+            len: data.len() as u64,
+            segments: segments.into(),
+        }
+    }
+
+    /// A value of `len` bytes made of `segments`, or `None` if `len` can't be their length
+    ///
+    /// The length of segments which are referred to rather than carried is not known until they
+    /// are resolved, so `len` is checked against the length of the carried segments plus at most
+    /// [`MAX_SEGMENT`] for each of the others.
+    pub(crate) fn from_segments(len: u64, segments: Vec<Segment>) -> Option<Self> {
+        let mut min = 0_u64;
+        let mut max = 0_u64;
+        for segment in &segments {
+            match &segment.data {
+                Some(data) => {
+                    min = min.checked_add(data.len() as u64)?;
+                    max = max.checked_add(data.len() as u64)?;
+                }
+                None => max = max.checked_add(MAX_SEGMENT as u64)?,
+            }
+        }
+        (min..=max).contains(&len).then(|| Self {
+            len,
+            segments: segments.into(),
+        })
+    }
+
+    /// The number of bytes in the value
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The hashes of the segments of the value, in order
+    pub fn hashes(&self) -> impl Iterator<Item = &SegmentHash> + '_ {
+        self.segments.iter().map(|s| &s.hash)
+    }
+
+    /// The contents of each segment of the value, in order
+    ///
+    /// A segment is `None` if the document this value was read from has never seen a change
+    /// carrying it, which only happens if a change which refers to a segment was received
+    /// without the change which carries it.
+    pub fn segments(&self) -> impl Iterator<Item = Option<&[u8]>> + '_ {
+        self.segments.iter().map(|s| s.data.as_deref())
+    }
+
+    /// The contents of the value, or `None` if any segment is missing, see [`Self::segments()`]
+    pub fn to_vec(&self) -> Option<Vec<u8>> {
+        let segments = self.segments().collect::<Option<Vec<_>>>()?;
+        Some(segments.concat())
+    }
+
+    pub(crate) fn raw_segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    fn with_segments<F: FnMut(&Segment) -> Segment>(&self, f: F) -> Self {
+        Self {
+            len: self.len,
+            segments: self.segments.iter().map(f).collect(),
+        }
+    }
+}
+
+impl PartialEq for ChunkedBytes {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.hashes().eq(other.hashes())
+    }
+}
+
+impl fmt::Debug for ChunkedBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChunkedBytes")
+            .field("len", &self.len)
+            .field("segments", &self.hashes().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl fmt::Display for ChunkedBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bytes in {} segments", self.len, self.segments.len())
+    }
+}
+
+impl serde::Serialize for ChunkedBytes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.to_vec() {
+            Some(bytes) => serializer.serialize_bytes(&bytes),
+            None => Err(serde::ser::Error::custom(
+                "missing segment of chunked bytes",
+            )),
+        }
+    }
+}
+
+impl From<&[u8]> for ChunkedBytes {
+    fn from(data: &[u8]) -> Self {
+        Self::new(data)
+    }
+}
+
+impl From<Vec<u8>> for ChunkedBytes {
+    fn from(data: Vec<u8>) -> Self {
+        Self::new(&data)
+    }
+}
+
+/// The contents of every segment carried by an op in the document
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SegmentStore {
+    /// The contents of each segment and the op which carries them
+    carried: HashMap<SegmentHash, (OpIdx, Arc<[u8]>)>,
+    /// Whether an op refers to a segment which was not in the store when the op was added
+    unresolved: bool,
+}
+
+impl SegmentStore {
+    /// Record the segments carried by the op at `idx` and fill in the contents of those it
+    /// refers to
+    pub(crate) fn add(&mut self, idx: OpIdx, op: &mut OpBuilder) {
+        let OpType::Put(ScalarValue::Chunked(value)) = &op.action else {
+            return;
+        };
+        let resolved = value.with_segments(|s| match &s.data {
+            Some(data) => {
+                self.carried
+                    .entry(s.hash)
+                    .or_insert_with(|| (idx, data.clone()));
+                s.clone()
+            }
+            None => Segment {
+                data: self.carried.get(&s.hash).map(|(_, data)| data.clone()),
+                ..s.clone()
+            },
+        });
+        self.unresolved |= resolved.segments().any(|s| s.is_none());
+        op.action = OpType::Put(ScalarValue::Chunked(resolved));
+    }
+
+    /// Record a value put by a local op, which only carries the segments that no op it depends on
+    /// carries. `visible` says whether the op at an index is one the new op depends on.
+    pub(crate) fn add_local<F: Fn(OpIdx) -> bool>(
+        &mut self,
+        idx: OpIdx,
+        op: &mut OpBuilder,
+        visible: F,
+    ) {
+        if let OpType::Put(ScalarValue::Chunked(value)) = &op.action {
+            let shared = value.with_segments(|s| match self.carried.get(&s.hash) {
+                Some((carrier, data)) if visible(*carrier) => Segment {
+                    hash: s.hash,
+                    data: Some(data.clone()),
+                    inline: false,
+                },
+                _ => Segment {
+                    inline: true,
+                    ..s.clone()
+                },
+            });
+            op.action = OpType::Put(ScalarValue::Chunked(shared));
+        }
+        self.add(idx, op)
+    }
+
+    /// Forget the segments carried by ops which have been rolled back
+    pub(crate) fn remove_carried_by<F: Fn(OpIdx) -> bool>(&mut self, removed: F) {
+        self.carried.retain(|_, (carrier, _)| !removed(*carrier));
+    }
+
+    /// Whether any op has referred to a segment before the op carrying it was added, and reset
+    /// the flag
+    pub(crate) fn take_unresolved(&mut self) -> bool {
+        std::mem::take(&mut self.unresolved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pseudo_random(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn segments_are_within_bounds_and_cover_the_data() {
+        let data = pseudo_random(3_000_000, 1);
+        let chunked = ChunkedBytes::new(&data);
+        let lens = chunked
+            .segments()
+            .map(|s| s.unwrap().len())
+            .collect::<Vec<_>>();
+        assert!(lens.len() > 3);
+        assert!(lens[..lens.len() - 1]
+            .iter()
+            .all(|l| (MIN_SEGMENT..=MAX_SEGMENT).contains(l)));
+        assert_eq!(chunked.to_vec(), Some(data));
+        assert_eq!(ChunkedBytes::new(&[]).segments().count(), 0);
+        assert_eq!(ChunkedBytes::new(&[1, 2, 3]).segments().count(), 1);
+    }
+
+    #[test]
+    fn an_insertion_only_changes_nearby_segments() {
+        let data = pseudo_random(2_000_000, 2);
+        let mut edited = data.clone();
+        edited.splice(1_000_000..1_000_000, [1, 2, 3]);
+        let before = ChunkedBytes::new(&data)
+            .hashes()
+            .copied()
+            .collect::<Vec<_>>();
+        let after = ChunkedBytes::new(&edited);
+        let changed = after.hashes().filter(|h| !before.contains(h)).count();
+        assert!(
+            changed <= 2,
+            "{} of {} segments changed",
+            changed,
+            before.len()
+        );
+    }
+}
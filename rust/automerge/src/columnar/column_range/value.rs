@@ -1,6 +1,8 @@
 use std::{borrow::Cow, ops::Range};
 
 use crate::{
+    blob::{BlobHash, BlobRef},
+    chunked_bytes::{Segment, SegmentHash},
     columnar::{
         encoding::{
             leb128::{lebsize, ulebsize},
@@ -9,11 +11,13 @@ use crate::{
         },
         SpliceError,
     },
+    link::DocId,
     storage::parse::{
         leb128::{leb128_i64, leb128_u64},
-        Input, ParseResult,
+        length_prefixed_bytes, take1, take_n, Input, ParseResult,
     },
-    ScalarValue,
+    value::Counter,
+    ChunkedBytes, DateTime, ScalarValue,
 };
 
 use super::{RawRange, RleRange};
@@ -353,6 +357,15 @@ impl ValueEncoder<Vec<u8>> {
     }
 }
 
+/// Encode `value` on its own, as its metadata (which says its type and length) followed by its
+/// raw bytes
+pub(crate) fn encode_single(value: &ScalarValue, out: &mut Vec<u8>) {
+    leb128::write::unsigned(out, u64::from(ValueMeta::from(value))).unwrap();
+    let mut raw = RawEncoder::from(Vec::new());
+    encode_val(&mut raw, value);
+    out.extend(raw.finish().0);
+}
+
 fn encode_val<S: Sink>(out: &mut RawEncoder<S>, val: &ScalarValue) -> usize {
     match val {
         ScalarValue::Uint(i) => out.append(*i),
@@ -361,10 +374,12 @@ fn encode_val<S: Sink>(out: &mut RawEncoder<S>, val: &ScalarValue) -> usize {
         ScalarValue::Boolean(_) => 0,
         ScalarValue::Timestamp(i) => out.append(*i),
         ScalarValue::F64(f) => out.append(*f),
-        ScalarValue::Counter(i) => out.append(i.start),
+        ScalarValue::Counter(i) if !i.is_bounded() => out.append(i.start),
         ScalarValue::Str(s) => out.append(RawBytes::from(s.as_bytes())),
         ScalarValue::Bytes(b) => out.append(RawBytes::from(&b[..])),
         ScalarValue::Unknown { bytes, .. } => out.append(RawBytes::from(&bytes[..])),
+        // the remaining values are extensions
+        other => out.append(RawBytes::from(&encode_extension(other)[..])),
     }
 }
 
@@ -421,12 +436,13 @@ impl From<&ScalarValue> for ValueMeta {
             }),
             ScalarValue::Timestamp(i) => Self((lebsize(*i) << 4) | 9),
             ScalarValue::F64(_) => Self((8 << 4) | 5),
-            ScalarValue::Counter(i) => Self((lebsize(i.start) << 4) | 8),
+            ScalarValue::Counter(i) if !i.is_bounded() => Self((lebsize(i.start) << 4) | 8),
             ScalarValue::Str(s) => Self(((s.as_bytes().len() as u64) << 4) | 6),
             ScalarValue::Bytes(b) => Self(((b.len() as u64) << 4) | 7),
             ScalarValue::Unknown { type_code, bytes } => {
                 Self(((bytes.len() as u64) << 4) | (*type_code as u64))
             }
+            other => Self(((extension_len(other) as u64) << 4) | EXTENSION_TYPE_CODE as u64),
         }
     }
 }
@@ -455,10 +471,11 @@ impl From<&ScalarValue> for ValueType {
             },
             ScalarValue::Timestamp(_) => ValueType::Timestamp,
             ScalarValue::F64(_) => ValueType::Float,
-            ScalarValue::Counter(_) => ValueType::Counter,
+            ScalarValue::Counter(i) if !i.is_bounded() => ValueType::Counter,
             ScalarValue::Str(_) => ValueType::String,
             ScalarValue::Bytes(_) => ValueType::Bytes,
             ScalarValue::Unknown { type_code, .. } => ValueType::Unknown(*type_code),
+            _ => ValueType::Unknown(EXTENSION_TYPE_CODE),
         }
     }
 }
@@ -480,10 +497,293 @@ impl From<ValueType> for u64 {
         }
     }
 }
+const HAS_MIN: u8 = 0b01;
+const HAS_MAX: u8 = 0b10;
+
+/// A bounded counter is encoded as the leb128 start value followed by a byte of flags saying
+/// which bounds are present and then the leb128 bounds themselves
+fn encode_bounded_counter(counter: &Counter) -> Vec<u8> {
+    let mut out = Vec::new();
+    leb128::write::signed(&mut out, counter.start).unwrap();
+    let flags = counter.min().map_or(0, |_| HAS_MIN) | counter.max().map_or(0, |_| HAS_MAX);
+    out.push(flags);
+    for bound in counter.min().iter().chain(counter.max().iter()) {
+        leb128::write::signed(&mut out, *bound).unwrap();
+    }
+    out
+}
+
+fn decode_bounded_counter(bytes: &[u8]) -> Result<ScalarValue, DecodeColumnError> {
+    let invalid = |e: String| DecodeColumnError::invalid_value("value", e);
+    let input = Input::new(bytes);
+    let (input, start) = leb128_i64::<DecodeError>(input).map_err(|e| invalid(e.to_string()))?;
+    let (mut input, flags) = take1::<DecodeError>(input).map_err(|e| invalid(e.to_string()))?;
+    if flags & !(HAS_MIN | HAS_MAX) != 0 {
+        return Err(invalid(format!("unknown bounded counter flags {}", flags)));
+    }
+    let mut bounds = [None, None];
+    for (bound, flag) in bounds.iter_mut().zip([HAS_MIN, HAS_MAX]) {
+        if flags & flag != 0 {
+            let (i, n) = leb128_i64::<DecodeError>(input).map_err(|e| invalid(e.to_string()))?;
+            input = i;
+            *bound = Some(n);
+        }
+    }
+    if !input.is_empty() {
+        return Err(invalid("extra bytes".to_string()));
+    }
+    let [min, max] = bounds;
+    if matches!((min, max), (Some(min), Some(max)) if min > max) {
+        return Err(invalid(
+            "bounded counter minimum is above its maximum".to_string(),
+        ));
+    }
+    Ok(ScalarValue::Counter(Counter::bounded(start, min, max)))
+}
+
+/// A date time is encoded as the leb128 seconds since the epoch, the uleb128 nanoseconds and,
+/// if there is one, the leb128 offset in seconds
+fn encode_datetime(datetime: &DateTime) -> Vec<u8> {
+    let mut out = Vec::new();
+    leb128::write::signed(&mut out, datetime.unix_seconds()).unwrap();
+    leb128::write::unsigned(&mut out, datetime.subsec_nanos().into()).unwrap();
+    if let Some(offset) = datetime.offset() {
+        leb128::write::signed(&mut out, offset.into()).unwrap();
+    }
+    out
+}
+
+fn decode_datetime(bytes: &[u8]) -> Result<ScalarValue, DecodeColumnError> {
+    let invalid = |e: String| DecodeColumnError::invalid_value("value", e);
+    let input = Input::new(bytes);
+    let (input, seconds) = leb128_i64::<DecodeError>(input).map_err(|e| invalid(e.to_string()))?;
+    let (input, nanos) = leb128_u64::<DecodeError>(input).map_err(|e| invalid(e.to_string()))?;
+    let datetime = u32::try_from(nanos)
+        .ok()
+        .and_then(|nanos| DateTime::new(seconds, nanos))
+        .ok_or_else(|| invalid("date time nanoseconds out of range".to_string()))?;
+    if input.is_empty() {
+        return Ok(ScalarValue::DateTime(datetime));
+    }
+    let (input, offset) = leb128_i64::<DecodeError>(input).map_err(|e| invalid(e.to_string()))?;
+    if !input.is_empty() {
+        return Err(invalid("extra bytes".to_string()));
+    }
+    let datetime = i32::try_from(offset)
+        .ok()
+        .and_then(|offset| datetime.with_offset(offset))
+        .ok_or_else(|| invalid("date time offset out of range".to_string()))?;
+    Ok(ScalarValue::DateTime(datetime))
+}
+
+const SEGMENT_REF: u8 = 0;
+const SEGMENT_INLINE: u8 = 1;
+
+/// Chunked bytes are encoded as the uleb128 length of the value and the uleb128 number of
+/// segments followed by each segment, which is either [`SEGMENT_REF`] and the 32 byte hash of a
+/// segment carried by an earlier op or [`SEGMENT_INLINE`] and the uleb128 length prefixed contents
+/// of the segment
+fn encode_chunked(value: &ChunkedBytes) -> Vec<u8> {
+    let mut out = Vec::with_capacity(chunked_len(value));
+    leb128::write::unsigned(&mut out, value.len()).unwrap();
+    leb128::write::unsigned(&mut out, value.raw_segments().len() as u64).unwrap();
+    for segment in value.raw_segments() {
+        match &segment.data {
+            Some(data) if segment.inline => {
+                out.push(SEGMENT_INLINE);
+                leb128::write::unsigned(&mut out, data.len() as u64).unwrap();
+                out.extend_from_slice(data);
+            }
+            _ => {
+                out.push(SEGMENT_REF);
+                out.extend_from_slice(&segment.hash.0);
+            }
+        }
+    }
+    out
+}
+
+/// The length of [`encode_chunked()`], which is needed before encoding the value
+fn chunked_len(value: &ChunkedBytes) -> usize {
+    let segments = value.raw_segments();
+    let contents = segments
+        .iter()
+        .map(|segment| match &segment.data {
+            Some(data) if segment.inline => 1 + ulebsize(data.len() as u64) as usize + data.len(),
+            _ => 1 + segment.hash.0.len(),
+        })
+        .sum::<usize>();
+    ulebsize(value.len()) as usize + ulebsize(segments.len() as u64) as usize + contents
+}
+
+fn decode_chunked(bytes: &[u8]) -> Result<ScalarValue, DecodeColumnError> {
+    let invalid = |e: String| DecodeColumnError::invalid_value("value", e);
+    let input = Input::new(bytes);
+    let (input, len) = leb128_u64::<DecodeError>(input).map_err(|e| invalid(e.to_string()))?;
+    let (mut input, count) =
+        leb128_u64::<DecodeError>(input).map_err(|e| invalid(e.to_string()))?;
+    let mut segments = Vec::new();
+    for _ in 0..count {
+        let (i, kind) = take1::<DecodeError>(input).map_err(|e| invalid(e.to_string()))?;
+        let (i, segment) = match kind {
+            SEGMENT_REF => {
+                let (i, hash) = take_n::<DecodeError>(32, i).map_err(|e| invalid(e.to_string()))?;
+                let segment = Segment {
+                    // take_n() returns exactly the 32 bytes of the hash
+                    hash: SegmentHash(hash.try_into().unwrap()),
+                    data: None,
+                    inline: false,
+                };
+                (i, segment)
+            }
+            SEGMENT_INLINE => {
+                let (i, data) =
+                    length_prefixed_bytes::<DecodeError>(i).map_err(|e| invalid(e.to_string()))?;
+                let segment = Segment {
+                    hash: SegmentHash::of(data),
+                    data: Some(data.into()),
+                    inline: true,
+                };
+                (i, segment)
+            }
+            other => return Err(invalid(format!("unknown segment kind {}", other))),
+        };
+        segments.push(segment);
+        input = i;
+    }
+    if !input.is_empty() {
+        return Err(invalid("extra bytes".to_string()));
+    }
+    ChunkedBytes::from_segments(len, segments)
+        .map(ScalarValue::Chunked)
+        .ok_or_else(|| invalid("length does not match the segments".to_string()))
+}
+
+/// A blob reference is encoded as the 32 byte hash, the uleb128 size and then, if there is a
+/// hint, the UTF-8 hint
+fn encode_blob_ref(blob: &BlobRef) -> Vec<u8> {
+    let mut out = blob.hash.0.to_vec();
+    leb128::write::unsigned(&mut out, blob.size).unwrap();
+    if let Some(hint) = &blob.hint {
+        out.extend_from_slice(hint.as_bytes());
+    }
+    out
+}
+
+fn decode_blob_ref(bytes: &[u8]) -> Result<ScalarValue, DecodeColumnError> {
+    let invalid = |e: String| DecodeColumnError::invalid_value("value", e);
+    let input = Input::new(bytes);
+    let (input, hash) = take_n::<DecodeError>(32, input).map_err(|e| invalid(e.to_string()))?;
+    let (input, size) = leb128_u64::<DecodeError>(input).map_err(|e| invalid(e.to_string()))?;
+    let hint = match input.unconsumed_bytes() {
+        [] => None,
+        hint => Some(
+            std::str::from_utf8(hint)
+                .map_err(|e| invalid(e.to_string()))?
+                .to_string(),
+        ),
+    };
+    Ok(ScalarValue::BlobRef(Box::new(BlobRef {
+        // take_n() returns exactly the 32 bytes of the hash
+        hash: BlobHash(hash.try_into().unwrap()),
+        size,
+        hint,
+    })))
+}
+
+/// The type code of values which this implementation stores as extensions
+///
+/// The value format has 4 bits of type code and codes 10 to 15 are not assigned by the format
+/// spec, so other implementations may use them. The column decoder returns values with these
+/// codes as [`ScalarValue::Unknown`] and writes them back unchanged. Scalars which the spec has
+/// no code for, such as [`DateTime`] or bounded counters, are written with this code followed by
+/// one of the `EXTENSION_*` subtype bytes below and are only read back as typed values by
+/// [`decode_extension()`], when ops are imported. The format of each subtype is documented on
+/// [`ScalarValue`], as other implementations rely on it.
+pub(crate) const EXTENSION_TYPE_CODE: u8 = 15;
+
+const EXTENSION_BLOB_REF: u8 = 0;
+const EXTENSION_LINK: u8 = 1;
+const EXTENSION_BOUNDED_COUNTER: u8 = 2;
+const EXTENSION_DATETIME: u8 = 3;
+const EXTENSION_BIGINT: u8 = 4;
+const EXTENSION_DECIMAL: u8 = 5;
+const EXTENSION_CHUNKED: u8 = 6;
+
+/// The subtype byte and contents of a value which is stored as an extension
+fn encode_extension(value: &ScalarValue) -> Vec<u8> {
+    let (subtype, contents) = match value {
+        ScalarValue::BlobRef(b) => (EXTENSION_BLOB_REF, encode_blob_ref(b)),
+        ScalarValue::Link(d) => (EXTENSION_LINK, d.as_bytes().to_vec()),
+        ScalarValue::Counter(c) => (EXTENSION_BOUNDED_COUNTER, encode_bounded_counter(c)),
+        ScalarValue::DateTime(d) => (EXTENSION_DATETIME, encode_datetime(d)),
+        ScalarValue::BigInt(n) => (EXTENSION_BIGINT, n.as_str().as_bytes().to_vec()),
+        ScalarValue::Decimal(n) => (EXTENSION_DECIMAL, n.as_str().as_bytes().to_vec()),
+        ScalarValue::Chunked(b) => (EXTENSION_CHUNKED, encode_chunked(b)),
+        other => unreachable!("{:?} has a type code of its own", other),
+    };
+    let mut out = Vec::with_capacity(contents.len() + 1);
+    out.push(subtype);
+    out.extend(contents);
+    out
+}
+
+/// The length of [`encode_extension()`], which is needed before encoding the value
+fn extension_len(value: &ScalarValue) -> usize {
+    match value {
+        ScalarValue::Chunked(b) => 1 + chunked_len(b),
+        other => encode_extension(other).len(),
+    }
+}
+
+/// The typed value of an extension value read from storage
+///
+/// Values which are not extensions, have a subtype this version does not know or were not
+/// encoded exactly as this version encodes them are returned unchanged, so that writing the
+/// value back produces the same bytes.
+pub(crate) fn decode_extension(value: ScalarValue) -> ScalarValue {
+    let bytes = match &value {
+        ScalarValue::Unknown {
+            type_code: EXTENSION_TYPE_CODE,
+            bytes,
+        } => bytes,
+        _ => return value,
+    };
+    let decoded = match bytes.split_first() {
+        Some((&EXTENSION_BLOB_REF, rest)) => decode_blob_ref(rest),
+        Some((&EXTENSION_LINK, rest)) => Ok(ScalarValue::Link(DocId::from(rest))),
+        Some((&EXTENSION_BOUNDED_COUNTER, rest)) => decode_bounded_counter(rest),
+        Some((&EXTENSION_DATETIME, rest)) => decode_datetime(rest),
+        Some((&EXTENSION_BIGINT, rest)) => decode_number(rest).map(ScalarValue::BigInt),
+        Some((&EXTENSION_DECIMAL, rest)) => decode_number(rest).map(ScalarValue::Decimal),
+        Some((&EXTENSION_CHUNKED, rest)) => decode_chunked(rest),
+        _ => return value,
+    };
+    match decoded {
+        Ok(typed) if encode_extension(&typed) == *bytes => typed,
+        _ => value,
+    }
+}
+
+/// Big integers and decimals are encoded as their ASCII decimal digits, in the canonical form
+/// of [`crate::BigInt::as_str()`] and [`crate::Decimal::as_str()`]
+fn decode_number<N>(bytes: &[u8]) -> Result<N, DecodeColumnError>
+where
+    N: std::str::FromStr + AsRef<str>,
+{
+    let invalid = || DecodeColumnError::invalid_value("value", "invalid number");
+    let s = std::str::from_utf8(bytes).map_err(|_| invalid())?;
+    let n = s.parse::<N>().map_err(|_| invalid())?;
+    if n.as_ref() != s {
+        return Err(invalid());
+    }
+    Ok(n)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::columnar::encoding::properties::{scalar_value, splice_scenario};
+    use crate::columnar::encoding::properties::{extension_value, scalar_value, splice_scenario};
     use proptest::prelude::*;
     use std::borrow::Cow;
 
@@ -528,6 +828,55 @@ mod tests {
             assert_eq!(result_values, expected);
         }
 
+        #[test]
+        fn extension_values_are_stored_as_unknown_values(values in proptest::collection::vec(extension_value(), 0..50)) {
+            let (out, range) = encode_values(&values[..]);
+            let stored = range.iter(&out).collect::<Result<Vec<_>, _>>().unwrap();
+            assert!(stored.iter().all(|v| matches!(v, ScalarValue::Unknown { type_code: EXTENSION_TYPE_CODE, .. })));
+            let decoded = stored.iter().cloned().map(decode_extension).collect::<Vec<_>>();
+            assert_eq!(values, decoded);
+            let (rewritten, _) = encode_values(&decoded[..]);
+            assert_eq!(out, rewritten);
+        }
+
+        #[test]
+        fn documents_with_extension_values_round_trip_through_readers_which_do_not_know_them(
+            values in proptest::collection::vec(extension_value(), 1..10)
+        ) {
+            use crate::transaction::{CommitOptions, Transactable};
+            use crate::{ActorId, Automerge, ReadDoc, ROOT};
+
+            // another implementation reads the values as unknown values and writes them back as
+            // they were read
+            let unknown = values
+                .iter()
+                .map(|v| ScalarValue::Unknown {
+                    type_code: EXTENSION_TYPE_CODE,
+                    bytes: encode_extension(v),
+                })
+                .collect::<Vec<_>>();
+            let write = |values: &[ScalarValue]| {
+                let mut doc = Automerge::new().with_actor(ActorId::from([1; 16]));
+                let mut tx = doc.transaction();
+                for (i, value) in values.iter().enumerate() {
+                    tx.put(ROOT, i.to_string(), value.clone()).unwrap();
+                }
+                tx.commit_with(CommitOptions::default().with_time(0));
+                doc
+            };
+            let typed = write(&values);
+            let mut other = write(&unknown);
+            assert_eq!(typed.save(), other.save());
+
+            // the other implementation makes a change of its own
+            other.transact(|tx| tx.put(ROOT, "other", true)).unwrap();
+            let loaded = Automerge::load(&other.save()).unwrap();
+            for (i, value) in values.iter().enumerate() {
+                let (loaded, _) = loaded.get(ROOT, i.to_string()).unwrap().unwrap();
+                assert_eq!(loaded.to_scalar(), Some(value));
+            }
+        }
+
         #[test]
         fn encode_row_wise_and_columnwise_equal(values in proptest::collection::vec(scalar_value(), 0..50)) {
             let (colwise, col_range) = encode_values(&values[..]);
@@ -537,6 +886,52 @@ mod tests {
         }
     }
 
+    #[test]
+    fn extensions_which_are_not_canonical_stay_unknown() {
+        let unknown = |bytes: Vec<u8>| ScalarValue::Unknown {
+            type_code: EXTENSION_TYPE_CODE,
+            bytes,
+        };
+        // a subtype this version doesn't know
+        assert_eq!(
+            decode_extension(unknown(vec![200, 1])),
+            unknown(vec![200, 1])
+        );
+        // a big integer with a leading zero
+        assert_eq!(
+            decode_extension(unknown(vec![EXTENSION_BIGINT, b'0', b'1'])),
+            unknown(vec![EXTENSION_BIGINT, b'0', b'1'])
+        );
+        // a padded leb128 bounded counter start
+        let padded = vec![EXTENSION_BOUNDED_COUNTER, 0x81, 0x00, HAS_MIN, 0];
+        assert_eq!(decode_extension(unknown(padded.clone())), unknown(padded));
+        assert_eq!(
+            decode_extension(unknown(vec![EXTENSION_BOUNDED_COUNTER, 1, HAS_MIN, 0])),
+            ScalarValue::bounded_counter(1, Some(0), None)
+        );
+        // chunked bytes whose length is more than their segments can hold
+        let mut too_long = vec![EXTENSION_CHUNKED];
+        leb128::write::unsigned(&mut too_long, 1 << 60).unwrap();
+        too_long.extend([1, SEGMENT_INLINE, 3, b'a', b'b', b'c']);
+        assert_eq!(
+            decode_extension(unknown(too_long.clone())),
+            unknown(too_long)
+        );
+        let mut short = vec![EXTENSION_CHUNKED, 3, 1, SEGMENT_INLINE, 3, b'a', b'b', b'c'];
+        assert!(matches!(
+            decode_extension(unknown(short.clone())),
+            ScalarValue::Chunked(c) if c.to_vec() == Some(b"abc".to_vec())
+        ));
+        short[1] = 2;
+        assert_eq!(decode_extension(unknown(short.clone())), unknown(short));
+        // other type codes are never extensions
+        let other = ScalarValue::Unknown {
+            type_code: 12,
+            bytes: vec![EXTENSION_LINK],
+        };
+        assert_eq!(decode_extension(other.clone()), other);
+    }
+
     #[test]
     fn test_value_uleb() {
         let vals = [ScalarValue::Uint(127), ScalarValue::Uint(183)];
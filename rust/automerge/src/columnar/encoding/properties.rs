@@ -6,8 +6,11 @@ use proptest::prelude::*;
 use smol_str::SmolStr;
 
 use crate::{
+    blob::{BlobHash, BlobRef},
     columnar::Key,
+    link::DocId,
     types::{ElemId, OpId, ScalarValue},
+    BigInt, ChunkedBytes, DateTime, Decimal,
 };
 
 #[derive(Clone, Debug)]
@@ -169,10 +172,76 @@ pub(crate) fn scalar_value() -> impl Strategy<Value = ScalarValue> + Clone {
         any::<Vec<u8>>().prop_map(ScalarValue::Bytes),
         encodable_int().prop_map(|i| ScalarValue::Counter(i.into())),
         encodable_int().prop_map(ScalarValue::Timestamp),
-        (10..15_u8, any::<Vec<u8>>()).prop_map(|(c, b)| ScalarValue::Unknown { type_code: c, bytes: b }),
+        (10..=15_u8, any::<Vec<u8>>()).prop_map(|(c, b)| ScalarValue::Unknown { type_code: c, bytes: b }),
     }
 }
 
+/// Values which are stored with the extension type code, see
+/// [`crate::columnar::column_range::decode_extension()`]
+pub(crate) fn extension_value() -> impl Strategy<Value = ScalarValue> + Clone {
+    prop_oneof! {
+        bounded_counter(),
+        datetime(),
+        big_int().prop_map(ScalarValue::BigInt),
+        (big_int(), 0..40_u32).prop_map(|(n, scale)| ScalarValue::Decimal(Decimal::new(n, scale))),
+        any::<Vec<u8>>().prop_map(|b| ScalarValue::Chunked(ChunkedBytes::new(&b))),
+        blob_ref(),
+        any::<Vec<u8>>().prop_map(|b| ScalarValue::Link(DocId::from(b))),
+    }
+}
+
+fn blob_ref() -> impl Strategy<Value = ScalarValue> + Clone {
+    (
+        any::<[u8; 32]>(),
+        any::<u64>(),
+        // an empty hint is encoded as no hint
+        proptest::option::of(".+"),
+    )
+        .prop_map(|(hash, size, hint)| {
+            ScalarValue::BlobRef(Box::new(BlobRef {
+                hash: BlobHash(hash),
+                size,
+                hint,
+            }))
+        })
+}
+
+fn bounded_counter() -> impl Strategy<Value = ScalarValue> + Clone {
+    (
+        encodable_int(),
+        proptest::option::of(encodable_int()),
+        proptest::option::of(encodable_int()),
+    )
+        .prop_filter("needs a bound", |(_, min, max)| {
+            min.is_some() || max.is_some()
+        })
+        .prop_map(|(start, a, b)| match (a, b) {
+            (Some(a), Some(b)) => {
+                ScalarValue::bounded_counter(start, Some(a.min(b)), Some(a.max(b)))
+            }
+            (min, max) => ScalarValue::bounded_counter(start, min, max),
+        })
+}
+
+fn datetime() -> impl Strategy<Value = ScalarValue> + Clone {
+    (
+        encodable_int(),
+        0..1_000_000_000_u32,
+        proptest::option::of(-86_399..86_400_i32),
+    )
+        .prop_map(|(seconds, nanos, offset)| {
+            let datetime = DateTime::new(seconds, nanos).unwrap();
+            ScalarValue::DateTime(match offset {
+                Some(offset) => datetime.with_offset(offset).unwrap(),
+                None => datetime,
+            })
+        })
+}
+
+fn big_int() -> impl Strategy<Value = BigInt> + Clone {
+    "-?[1-9][0-9]{0,60}|0".prop_map(|s| s.parse().unwrap())
+}
+
 fn smol_str() -> impl Strategy<Value = SmolStr> + Clone {
     any::<String>().prop_map(SmolStr::from)
 }
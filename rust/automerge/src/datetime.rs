@@ -0,0 +1,354 @@
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::DateTimeOutOfRange;
+
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+const SECS_PER_DAY: i64 = 86_400;
+
+/// A point in time with nanosecond precision and an optional offset from UTC
+///
+/// This is stored as [`crate::ScalarValue::DateTime`]. Unlike [`crate::ScalarValue::Timestamp`],
+/// which is a number of milliseconds since the unix epoch, a `DateTime` keeps sub-millisecond
+/// precision and the offset of the time zone it was recorded in.
+///
+/// Date times are ordered by the instant they represent, the offset only breaks ties between
+/// equal instants, and can be moved with [`Self::checked_add()`] and [`Self::checked_sub()`].
+/// With the `chrono` or `time` features they convert to and from `chrono::DateTime` and
+/// `time::OffsetDateTime`.
+///
+/// # Example
+///
+/// ```
+/// # use automerge::{AutoCommit, DateTime, ReadDoc, ROOT};
+/// # use automerge::transaction::Transactable;
+/// # use std::time::Duration;
+/// let mut doc = AutoCommit::new();
+/// let start = DateTime::new(1_700_000_000, 123_456_789)
+///     .unwrap()
+///     .with_offset(3600)
+///     .unwrap();
+/// doc.put(ROOT, "start", start).unwrap();
+///
+/// let (value, _) = doc.get(ROOT, "start").unwrap().unwrap();
+/// let read = value.to_datetime().unwrap();
+/// assert_eq!(read.to_string(), "2023-11-14T23:13:20.123456789+01:00");
+/// assert!(read < read.checked_add(Duration::from_nanos(1)).unwrap());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DateTime {
+    // the field order gives the ordering described above
+    seconds: i64,
+    nanos: u32,
+    offset: Option<i32>,
+}
+
+impl DateTime {
+    /// A date time `seconds` and `nanos` after the unix epoch, with no offset
+    ///
+    /// Returns `None` if `nanos` is not less than one second.
+    pub fn new(seconds: i64, nanos: u32) -> Option<Self> {
+        (nanos < NANOS_PER_SEC).then_some(Self {
+            seconds,
+            nanos,
+            offset: None,
+        })
+    }
+
+    /// A date time `millis` milliseconds after the unix epoch, like a
+    /// [`crate::ScalarValue::Timestamp`]
+    pub fn from_unix_millis(millis: i64) -> Self {
+        Self {
+            seconds: millis.div_euclid(1000),
+            nanos: millis.rem_euclid(1000) as u32 * 1_000_000,
+            offset: None,
+        }
+    }
+
+    /// A date time `nanos` nanoseconds after the unix epoch
+    ///
+    /// Returns `None` if the number of seconds does not fit in an `i64`.
+    pub fn from_unix_nanos(nanos: i128) -> Option<Self> {
+        let seconds = i64::try_from(nanos.div_euclid(NANOS_PER_SEC as i128)).ok()?;
+        Self::new(seconds, nanos.rem_euclid(NANOS_PER_SEC as i128) as u32)
+    }
+
+    /// The same instant recorded with an offset from UTC of `offset` seconds east
+    ///
+    /// Returns `None` if the offset is a day or more.
+    pub fn with_offset(self, offset: i32) -> Option<Self> {
+        (i64::from(offset).abs() < SECS_PER_DAY).then_some(Self {
+            offset: Some(offset),
+            ..self
+        })
+    }
+
+    /// The same instant with no offset
+    pub fn without_offset(self) -> Self {
+        Self {
+            offset: None,
+            ..self
+        }
+    }
+
+    /// The whole number of seconds since the unix epoch
+    pub fn unix_seconds(&self) -> i64 {
+        self.seconds
+    }
+
+    /// The nanoseconds after [`Self::unix_seconds()`]
+    pub fn subsec_nanos(&self) -> u32 {
+        self.nanos
+    }
+
+    /// Milliseconds since the unix epoch, rounded down
+    pub fn unix_millis(&self) -> i64 {
+        self.seconds
+            .saturating_mul(1000)
+            .saturating_add(i64::from(self.nanos / 1_000_000))
+    }
+
+    /// Nanoseconds since the unix epoch
+    pub fn unix_nanos(&self) -> i128 {
+        i128::from(self.seconds) * NANOS_PER_SEC as i128 + i128::from(self.nanos)
+    }
+
+    /// The offset from UTC in seconds east, if there is one
+    pub fn offset(&self) -> Option<i32> {
+        self.offset
+    }
+
+    /// The date time `duration` later, keeping the offset
+    pub fn checked_add(self, duration: Duration) -> Option<Self> {
+        let nanos = self
+            .unix_nanos()
+            .checked_add(i128::try_from(duration.as_nanos()).ok()?)?;
+        Some(Self {
+            offset: self.offset,
+            ..Self::from_unix_nanos(nanos)?
+        })
+    }
+
+    /// The date time `duration` earlier, keeping the offset
+    pub fn checked_sub(self, duration: Duration) -> Option<Self> {
+        let nanos = self
+            .unix_nanos()
+            .checked_sub(i128::try_from(duration.as_nanos()).ok()?)?;
+        Some(Self {
+            offset: self.offset,
+            ..Self::from_unix_nanos(nanos)?
+        })
+    }
+}
+
+impl From<SystemTime> for DateTime {
+    fn from(time: SystemTime) -> Self {
+        let nanos = match time.duration_since(UNIX_EPOCH) {
+            Ok(after) => after.as_nanos() as i128,
+            Err(before) => -(before.duration().as_nanos() as i128),
+        };
+        Self::from_unix_nanos(nanos).expect("system time out of range")
+    }
+}
+
+impl TryFrom<DateTime> for SystemTime {
+    type Error = DateTimeOutOfRange;
+
+    fn try_from(time: DateTime) -> Result<Self, Self::Error> {
+        let since_epoch = Duration::new(time.seconds.unsigned_abs(), 0);
+        let whole = if time.seconds >= 0 {
+            UNIX_EPOCH.checked_add(since_epoch)
+        } else {
+            UNIX_EPOCH.checked_sub(since_epoch)
+        };
+        whole
+            .and_then(|t| t.checked_add(Duration::from_nanos(u64::from(time.nanos))))
+            .ok_or(DateTimeOutOfRange)
+    }
+}
+
+/// Formats as RFC 3339, in the offset of the date time or UTC if there is none
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let offset = i64::from(self.offset.unwrap_or(0));
+        let local = self.seconds.saturating_add(offset);
+        let (year, month, day) = civil_from_days(local.div_euclid(SECS_PER_DAY));
+        let secs = local.rem_euclid(SECS_PER_DAY);
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            year,
+            month,
+            day,
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        )?;
+        if self.nanos % 1_000_000 == 0 && self.nanos != 0 {
+            write!(f, ".{:03}", self.nanos / 1_000_000)?;
+        } else if self.nanos % 1000 == 0 && self.nanos != 0 {
+            write!(f, ".{:06}", self.nanos / 1000)?;
+        } else if self.nanos != 0 {
+            write!(f, ".{:09}", self.nanos)?;
+        }
+        match self.offset {
+            None | Some(0) => write!(f, "Z"),
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                let offset = offset.unsigned_abs();
+                write!(f, "{}{:02}:{:02}", sign, offset / 3600, offset / 60 % 60)
+            }
+        }
+    }
+}
+
+impl serde::Serialize for DateTime {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// The proleptic gregorian (year, month, day) `days` after the unix epoch
+///
+/// This is the `civil_from_days` algorithm from
+/// <http://howardhinnant.github.io/date_algorithms.html>
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> From<chrono::DateTime<Tz>> for DateTime {
+    fn from(time: chrono::DateTime<Tz>) -> Self {
+        use chrono::Offset;
+        Self {
+            seconds: time.timestamp(),
+            // chrono represents leap seconds as more than a second of nanoseconds
+            nanos: time.timestamp_subsec_nanos().min(NANOS_PER_SEC - 1),
+            offset: Some(time.offset().fix().local_minus_utc()),
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<DateTime> for chrono::DateTime<chrono::FixedOffset> {
+    type Error = DateTimeOutOfRange;
+
+    /// Converts with the offset of `time`, or UTC if it has none
+    fn try_from(time: DateTime) -> Result<Self, Self::Error> {
+        let offset =
+            chrono::FixedOffset::east_opt(time.offset.unwrap_or(0)).ok_or(DateTimeOutOfRange)?;
+        let utc =
+            chrono::DateTime::from_timestamp(time.seconds, time.nanos).ok_or(DateTimeOutOfRange)?;
+        Ok(utc.with_timezone(&offset))
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<DateTime> for chrono::DateTime<chrono::Utc> {
+    type Error = DateTimeOutOfRange;
+
+    fn try_from(time: DateTime) -> Result<Self, Self::Error> {
+        chrono::DateTime::from_timestamp(time.seconds, time.nanos).ok_or(DateTimeOutOfRange)
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::OffsetDateTime> for DateTime {
+    fn from(time: time::OffsetDateTime) -> Self {
+        Self {
+            seconds: time.unix_timestamp(),
+            nanos: time.nanosecond(),
+            offset: Some(time.offset().whole_seconds()),
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<DateTime> for time::OffsetDateTime {
+    type Error = DateTimeOutOfRange;
+
+    /// Converts with the offset of `time`, or UTC if it has none
+    fn try_from(time: DateTime) -> Result<Self, Self::Error> {
+        let offset = time::UtcOffset::from_whole_seconds(time.offset.unwrap_or(0))
+            .map_err(|_| DateTimeOutOfRange)?;
+        let utc = time::OffsetDateTime::from_unix_timestamp_nanos(time.unix_nanos())
+            .map_err(|_| DateTimeOutOfRange)?;
+        utc.checked_to_offset(offset).ok_or(DateTimeOutOfRange)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_dates_before_and_after_the_epoch() {
+        assert_eq!(
+            DateTime::new(0, 0).unwrap().to_string(),
+            "1970-01-01T00:00:00Z"
+        );
+        assert_eq!(
+            DateTime::from_unix_millis(-1).to_string(),
+            "1969-12-31T23:59:59.999Z"
+        );
+        assert_eq!(
+            DateTime::new(951_782_400, 1000)
+                .unwrap()
+                .with_offset(-(5 * 3600 + 30 * 60))
+                .unwrap()
+                .to_string(),
+            "2000-02-28T18:30:00.000001-05:30"
+        );
+    }
+
+    #[test]
+    fn orders_by_instant_then_offset() {
+        let utc = DateTime::new(100, 5).unwrap();
+        let later = utc.checked_add(Duration::from_nanos(1)).unwrap();
+        let shifted = utc.with_offset(3600).unwrap();
+        assert!(utc < later);
+        assert!(utc < shifted);
+        assert!(shifted < later);
+        assert_eq!(later.checked_sub(Duration::from_nanos(1)), Some(utc));
+    }
+
+    #[test]
+    fn converts_to_and_from_system_time() {
+        let time = UNIX_EPOCH - Duration::new(10, 250);
+        let datetime = DateTime::from(time);
+        assert_eq!(datetime.unix_nanos(), -10_000_000_250);
+        assert_eq!(SystemTime::try_from(datetime), Ok(time));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn converts_to_and_from_chrono() {
+        let offset = chrono::FixedOffset::east_opt(-7200).unwrap();
+        let time = chrono::DateTime::from_timestamp(1_700_000_000, 42)
+            .unwrap()
+            .with_timezone(&offset);
+        let datetime = DateTime::from(time);
+        assert_eq!(datetime.offset(), Some(-7200));
+        assert_eq!(chrono::DateTime::try_from(datetime), Ok(time));
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn converts_to_and_from_time() {
+        let time = time::OffsetDateTime::from_unix_timestamp_nanos(1_700_000_000_000_000_042)
+            .unwrap()
+            .to_offset(time::UtcOffset::from_whole_seconds(19_800).unwrap());
+        let datetime = DateTime::from(time);
+        assert_eq!(datetime.offset(), Some(19_800));
+        assert_eq!(time::OffsetDateTime::try_from(datetime), Ok(time));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::exid::ExId;
 use crate::storage::load::Error as LoadError;
 use crate::types::{ActorId, ScalarValue};
 use crate::value::DataType;
@@ -30,6 +31,10 @@ pub enum AutomergeError {
     InvalidObjIdFormat(String),
     #[error("invalid op for object of type `{0}`")]
     InvalidOp(ObjType),
+    #[error("invalid path `{0}`")]
+    InvalidPath(String),
+    #[error("the savepoint is not in the current transaction or has been rolled back")]
+    InvalidSavepoint,
     #[error("seq {0} is out of bounds")]
     InvalidSeq(u64),
     #[error("cursor {0} is invalid")]
@@ -55,8 +60,18 @@ pub enum AutomergeError {
     NonChangeCompressed,
     #[error("id was not an object id")]
     NotAnObject,
+    #[error("object {0} is frozen")]
+    Frozen(ExId),
+    #[error("write to object {obj} was not authorized: {reason}")]
+    Unauthorized { obj: ExId, reason: String },
+    #[error(transparent)]
+    SchemaViolation(#[from] crate::schema::SchemaViolation),
     #[error(transparent)]
     HydrateError(#[from] HydrateError),
+    #[error("failed to read document: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("the operation was cancelled")]
+    Cancelled,
 }
 
 impl PartialEq for AutomergeError {
@@ -101,6 +116,22 @@ pub struct InvalidElementId(pub String);
 #[error("Invalid OpID: {0}")]
 pub struct InvalidOpId(pub String);
 
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("this build of automerge does not support {0}")]
+pub struct UnsupportedFeature(pub String);
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("date time is out of the supported range")]
+pub struct DateTimeOutOfRange;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid number `{0}`")]
+pub struct InvalidNumber(pub String);
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("number is out of range")]
+pub struct NumberOutOfRange;
+
 #[derive(Error, Debug)]
 pub enum InvalidOpType {
     #[error("unrecognized action index {0}")]
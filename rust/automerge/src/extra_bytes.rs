@@ -0,0 +1,142 @@
+//! The extra bytes of a change
+//!
+//! Every change has a field of extra bytes. Applications set it with
+//! [`crate::transaction::CommitOptions::with_extra_bytes()`], and automerge uses it to record
+//! rotated actor IDs, tags and actor descriptions. So that these can be stored in the same change,
+//! whenever the field holds anything other than application bytes it starts with
+//! [`RESERVED_PREFIX`] followed by a list of fields. Each field is a one byte field code, the
+//! length of the value as a uLEB128 and then the value.
+//!
+//! Extra bytes which don't start with [`RESERVED_PREFIX`], such as those written by other
+//! implementations, are all application bytes. Application bytes which do start with it are
+//! always stored as a field, so that they are read back unchanged.
+
+use std::collections::BTreeMap;
+
+/// The bytes which start the extra bytes of a change when they are a list of fields
+pub(crate) const RESERVED_PREFIX: &[u8] = b"automerge:extra:";
+
+/// The fields which can be stored in the extra bytes of a change
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Field {
+    /// The bytes set with [`crate::transaction::CommitOptions::with_extra_bytes()`]
+    Application,
+    /// The actor ID the author of the change rotated from, see [`crate::Change::rotated_from()`]
+    RotatedFrom,
+    /// The name the change tags its dependencies with, see [`crate::Change::tag()`]
+    Tag,
+    /// An encoded [`crate::ActorInfo`], see [`crate::Change::actor_info()`]
+    ActorInfo,
+}
+
+impl Field {
+    fn code(self) -> u8 {
+        match self {
+            Self::Application => 0,
+            Self::RotatedFrom => 1,
+            Self::Tag => 2,
+            Self::ActorInfo => 3,
+        }
+    }
+}
+
+/// The extra bytes of a change which is being built
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ExtraBytes {
+    fields: BTreeMap<Field, Vec<u8>>,
+}
+
+impl ExtraBytes {
+    pub(crate) fn set(&mut self, field: Field, value: Vec<u8>) {
+        self.fields.insert(field, value);
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        match self.fields.iter().next() {
+            None => return Vec::new(),
+            Some((Field::Application, value))
+                if self.fields.len() == 1 && !value.starts_with(RESERVED_PREFIX) =>
+            {
+                return value.clone();
+            }
+            Some(_) => {}
+        }
+        let mut out = RESERVED_PREFIX.to_vec();
+        for (field, value) in &self.fields {
+            out.push(field.code());
+            leb128::write::unsigned(&mut out, value.len() as u64).unwrap();
+            out.extend_from_slice(value);
+        }
+        out
+    }
+}
+
+/// Read `field` from the extra bytes of a change
+pub(crate) fn read(bytes: &[u8], field: Field) -> Option<&[u8]> {
+    match fields(bytes) {
+        Some(fields) => fields
+            .into_iter()
+            .find(|(code, _)| *code == field.code())
+            .map(|(_, value)| value),
+        None if field == Field::Application && !bytes.is_empty() => Some(bytes),
+        None => None,
+    }
+}
+
+/// The fields in `bytes`, or `None` if `bytes` are not a well formed list of fields
+fn fields(bytes: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let mut input = bytes.strip_prefix(RESERVED_PREFIX)?;
+    let mut fields = Vec::new();
+    while let Some((&code, mut rest)) = input.split_first() {
+        let len = leb128::read::unsigned(&mut rest).ok()?;
+        let len = usize::try_from(len).ok().filter(|len| *len <= rest.len())?;
+        let (value, rest) = rest.split_at(len);
+        fields.push((code, value));
+        input = rest;
+    }
+    Some(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read, ExtraBytes, Field, RESERVED_PREFIX};
+
+    #[test]
+    fn application_bytes_on_their_own_are_stored_as_they_are() {
+        let mut extra = ExtraBytes::default();
+        extra.set(Field::Application, b"review:42".to_vec());
+        assert_eq!(extra.encode(), b"review:42");
+        assert_eq!(
+            read(b"review:42", Field::Application),
+            Some(&b"review:42"[..])
+        );
+        assert_eq!(read(b"review:42", Field::Tag), None);
+        assert_eq!(read(b"", Field::Application), None);
+    }
+
+    #[test]
+    fn fields_are_stored_together() {
+        let mut extra = ExtraBytes::default();
+        extra.set(Field::Tag, b"v1.0".to_vec());
+        extra.set(Field::Application, b"review:42".to_vec());
+        let bytes = extra.encode();
+        assert!(bytes.starts_with(RESERVED_PREFIX));
+        assert_eq!(read(&bytes, Field::Tag), Some(&b"v1.0"[..]));
+        assert_eq!(read(&bytes, Field::Application), Some(&b"review:42"[..]));
+        assert_eq!(read(&bytes, Field::ActorInfo), None);
+    }
+
+    #[test]
+    fn application_bytes_which_start_with_the_prefix_round_trip() {
+        let app = [RESERVED_PREFIX, &[0, 100]].concat();
+        let mut extra = ExtraBytes::default();
+        extra.set(Field::Application, app.clone());
+        let bytes = extra.encode();
+        assert_ne!(bytes, app);
+        assert_eq!(read(&bytes, Field::Application), Some(app.as_slice()));
+
+        // a malformed list of fields is read as application bytes
+        assert_eq!(read(&app, Field::Application), Some(app.as_slice()));
+        assert_eq!(read(&app, Field::Tag), None);
+    }
+}
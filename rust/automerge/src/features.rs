@@ -0,0 +1,114 @@
+use crate::error::UnsupportedFeature;
+
+/// The unit in which this build of automerge natively indexes text
+///
+/// See [`crate::TextUnit::Native`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NativeTextEncoding {
+    /// Unicode code points, the default
+    CodePoints,
+    /// UTF-16 code units, when targeting wasm
+    Utf16,
+    /// UTF-8 bytes, with the `utf8-indexing` feature
+    Utf8,
+}
+
+impl NativeTextEncoding {
+    fn name(self) -> &'static str {
+        match self {
+            Self::CodePoints => "text-code-points",
+            Self::Utf16 => "text-utf16",
+            Self::Utf8 => "text-utf8",
+        }
+    }
+}
+
+/// The optional capabilities of this build of automerge
+///
+/// This is returned by [`features()`]. Each capability also has a stable name, listed by
+/// [`Features::names()`], which applications can exchange with peers to find out what they have
+/// in common.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Features {
+    /// The compression algorithms which can be used to read and write documents and changes
+    pub compression: &'static [&'static str],
+    /// The unit text is indexed in
+    pub text_encoding: NativeTextEncoding,
+    /// Whether text can be indexed by grapheme clusters, see [`crate::TextUnit::Grapheme`]
+    pub grapheme_indexing: bool,
+    /// Whether text can be indexed by UTF-16 code units, see [`crate::TextUnit::Utf16`]
+    pub utf16_indexing: bool,
+    /// Whether rich text marks are supported
+    pub marks: bool,
+    /// Whether move operations are supported
+    pub moves: bool,
+    /// Whether documents can be saved and loaded in parallel (the `parallel` feature)
+    pub parallel: bool,
+    /// Whether documents can be loaded from memory mapped files (the `mmap` feature)
+    pub mmap: bool,
+    /// Whether saving and loading can be offloaded to a tokio runtime (the `tokio` feature)
+    pub tokio: bool,
+}
+
+/// Report the optional capabilities of this build of automerge
+///
+/// # Example
+///
+/// ```
+/// let features = automerge::features();
+/// assert!(features.names().contains(&"deflate"));
+/// assert!(features.require("moves").is_err());
+/// ```
+pub fn features() -> Features {
+    Features {
+        compression: &["deflate"],
+        text_encoding: if cfg!(target_family = "wasm") {
+            NativeTextEncoding::Utf16
+        } else if cfg!(feature = "utf8-indexing") {
+            NativeTextEncoding::Utf8
+        } else {
+            NativeTextEncoding::CodePoints
+        },
+        grapheme_indexing: true,
+        utf16_indexing: true,
+        marks: true,
+        moves: false,
+        parallel: cfg!(feature = "parallel"),
+        mmap: cfg!(feature = "mmap"),
+        tokio: cfg!(feature = "tokio"),
+    }
+}
+
+impl Features {
+    /// The names of the supported capabilities
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names = self.compression.to_vec();
+        names.push(self.text_encoding.name());
+        let flags = [
+            ("graphemes", self.grapheme_indexing),
+            ("utf16", self.utf16_indexing),
+            ("marks", self.marks),
+            ("moves", self.moves),
+            ("parallel", self.parallel),
+            ("mmap", self.mmap),
+            ("tokio", self.tokio),
+        ];
+        names.extend(flags.iter().filter(|(_, on)| *on).map(|(name, _)| *name));
+        names
+    }
+
+    /// Whether the capability called `name` is supported
+    pub fn supports(&self, name: &str) -> bool {
+        self.names().contains(&name)
+    }
+
+    /// Return an error if the capability called `name` is not supported
+    pub fn require(&self, name: &str) -> Result<(), UnsupportedFeature> {
+        if self.supports(name) {
+            Ok(())
+        } else {
+            Err(UnsupportedFeature(name.to_string()))
+        }
+    }
+}
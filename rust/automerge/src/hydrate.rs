@@ -77,6 +77,33 @@ impl Value {
     }
 }
 
+impl serde::Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::{SerializeMap, SerializeSeq};
+        match self {
+            Value::Scalar(s) => s.serialize(serializer),
+            Value::Map(map) => {
+                let mut map_ser = serializer.serialize_map(None)?;
+                for (key, value) in map.iter() {
+                    map_ser.serialize_entry(key, &value.value)?;
+                }
+                map_ser.end()
+            }
+            Value::List(list) => {
+                let mut seq_ser = serializer.serialize_seq(Some(list.len()))?;
+                for value in list.iter() {
+                    seq_ser.serialize_element(&value.value)?;
+                }
+                seq_ser.end()
+            }
+            Value::Text(text) => serializer.serialize_str(&String::from(text)),
+        }
+    }
+}
+
 impl From<value::Value<'_>> for Value {
     fn from(value: value::Value<'_>) -> Self {
         match value {
@@ -223,12 +250,22 @@ impl From<&Value> for wasm_bindgen::JsValue {
         match value {
             Value::Scalar(s) => match s {
                 ScalarValue::Bytes(v) => Uint8Array::from(v.as_slice()).into(),
+                // a segment is only missing from a document with a change which refers to a
+                // segment no other change carries
+                ScalarValue::Chunked(v) => {
+                    Uint8Array::from(v.to_vec().unwrap_or_default().as_slice()).into()
+                }
                 ScalarValue::Str(v) => v.to_string().into(),
                 ScalarValue::Int(v) => (*v as f64).into(),
                 ScalarValue::Uint(v) => (*v as f64).into(),
                 ScalarValue::F64(v) => (*v).into(),
                 ScalarValue::Counter(v) => (f64::from(v)).into(),
                 ScalarValue::Timestamp(v) => Date::new(&(*v as f64).into()).into(),
+                ScalarValue::DateTime(d) => Date::new(&(d.unix_millis() as f64).into()).into(),
+                ScalarValue::BigInt(n) => n.to_string().into(),
+                ScalarValue::Decimal(n) => n.to_string().into(),
+                ScalarValue::BlobRef(b) => b.hash.to_string().into(),
+                ScalarValue::Link(d) => d.to_string().into(),
                 ScalarValue::Boolean(v) => (*v).into(),
                 ScalarValue::Null => JsValue::null(),
                 ScalarValue::Unknown {
@@ -62,6 +62,23 @@ impl List {
                 }
                 Ok(())
             }
+            PatchAction::Splice {
+                index,
+                deleted,
+                inserted,
+            } => {
+                if index + deleted > self.0.len() {
+                    return Err(HydrateError::InvalidIndex(index + deleted));
+                }
+                for _ in 0..deleted {
+                    self.0.remove(index);
+                }
+                for (n, (value, _, conflict)) in inserted.into_iter().enumerate() {
+                    self.0
+                        .insert(index + n, ListValue::new(value.into(), conflict));
+                }
+                Ok(())
+            }
             PatchAction::Increment {
                 prop: Prop::Seq(index),
                 value,
@@ -0,0 +1,43 @@
+//! Helpers for the spans created when the `tracing` feature is enabled
+//!
+//! The spans themselves are declared with `#[cfg_attr(feature = "tracing", instrument(..))]`
+//! on the functions they cover. Without the feature there is no span and these helpers do
+//! nothing.
+
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+
+/// Records how long the current span took in its `duration_us` field when dropped
+pub(crate) struct Timer {
+    #[cfg(feature = "tracing")]
+    start: Instant,
+}
+
+impl Timer {
+    pub(crate) fn start() -> Self {
+        Timer {
+            #[cfg(feature = "tracing")]
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("duration_us", self.start.elapsed().as_micros() as u64);
+    }
+}
+
+/// Record fields which are only known once the work is done on the current span
+macro_rules! record {
+    ($($field:ident = $value:expr),+ $(,)?) => {
+        #[cfg(feature = "tracing")]
+        {
+            let span = tracing::Span::current();
+            $(span.record(stringify!($field), $value);)+
+        }
+    };
+}
+
+pub(crate) use record;
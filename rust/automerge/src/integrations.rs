@@ -0,0 +1,3 @@
+//! Glue between automerge and other crates, each behind a feature of the same name
+#[cfg(feature = "ropey")]
+pub mod ropey;
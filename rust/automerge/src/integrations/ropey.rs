@@ -0,0 +1,175 @@
+//! Keeping a [`Rope`] in sync with a text object
+//!
+//! Editors built on [`ropey`](::ropey) index text by `char`, whereas automerge indexes text in
+//! its native [`crate::TextUnit`], which is only `char`s in some builds. [`RopeText`] does the
+//! translation in both directions:
+//!
+//! * [`RopeText::splice()`] applies an edit made in the editor to both the rope and the document
+//! * [`RopeText::apply_patches()`] applies the changes described by patches, for example remote
+//!   changes merged into the document, to the rope
+//! * [`RopeText::commit()`] writes the whole rope into the document, diffing it against the
+//!   current text, for editors which do not report individual edits
+//!
+//! # Example
+//!
+//! ```
+//! # use automerge::{AutoCommit, ObjType, ReadDoc, ROOT};
+//! # use automerge::integrations::ropey::RopeText;
+//! # use automerge::transaction::Transactable;
+//! let mut doc = AutoCommit::new();
+//! let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+//! doc.splice_text(&text, 0, 0, "héllo").unwrap();
+//! doc.update_diff_cursor();
+//!
+//! let binding = RopeText::new(text.clone());
+//! let mut rope = binding.load(&doc).unwrap();
+//! binding.splice(&mut doc, &mut rope, 5..5, " world").unwrap();
+//! // the rope already contains the edit, so don't apply patches for it
+//! doc.update_diff_cursor();
+//!
+//! let mut remote = doc.fork();
+//! remote.splice_text(&text, 0, 1, "H").unwrap();
+//! doc.merge(&mut remote).unwrap();
+//! let patches = doc.diff_incremental();
+//! binding.apply_patches(&mut rope, &patches);
+//! assert_eq!(rope.to_string(), "Héllo world");
+//! assert_eq!(doc.text(&text).unwrap(), "Héllo world");
+//! ```
+use std::ops::Range;
+
+use ::ropey::Rope;
+
+use crate::exid::ExId;
+use crate::transaction::Transactable;
+use crate::{AutomergeError, NativeTextEncoding, Patch, PatchAction, ReadDoc};
+
+/// A text object which is edited through a [`Rope`], see the [module documentation](self)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RopeText {
+    text: ExId,
+}
+
+impl RopeText {
+    /// Bind the text object `text`
+    pub fn new(text: ExId) -> Self {
+        Self { text }
+    }
+
+    /// The text object this binding edits
+    pub fn text(&self) -> &ExId {
+        &self.text
+    }
+
+    /// A rope containing the current text of the text object
+    pub fn load<R: ReadDoc>(&self, doc: &R) -> Result<Rope, AutomergeError> {
+        Ok(Rope::from_str(&doc.text(&self.text)?))
+    }
+
+    /// Replace the `char`s in `range` of `rope` with `insert`, making the same edit to the text
+    /// object
+    ///
+    /// `rope` must contain the current text of the text object.
+    pub fn splice<T: Transactable>(
+        &self,
+        doc: &mut T,
+        rope: &mut Rope,
+        range: Range<usize>,
+        insert: &str,
+    ) -> Result<(), AutomergeError> {
+        if range.start > range.end || range.end > rope.len_chars() {
+            return Err(AutomergeError::InvalidIndex(range.end));
+        }
+        let start = to_native(rope, range.start);
+        let del = to_native(rope, range.end) - start;
+        doc.splice_text(&self.text, start, del as isize, insert)?;
+        rope.remove(range.clone());
+        rope.insert(range.start, insert);
+        Ok(())
+    }
+
+    /// Write the contents of `rope` into the text object, changing only the parts which differ
+    pub fn commit<T: Transactable>(&self, doc: &mut T, rope: &Rope) -> Result<(), AutomergeError> {
+        doc.update_text(&self.text, rope.to_string())
+    }
+
+    /// Apply the patches to the text object in `patches` to `rope`, returning the number of
+    /// patches applied
+    ///
+    /// `rope` must contain the text of the text object before the patches, so the patches must
+    /// not include edits made with [`Self::splice()`]. The patches must use
+    /// [`crate::patches::TextRepresentation::String`], which is the default.
+    pub fn apply_patches(&self, rope: &mut Rope, patches: &[Patch]) -> usize {
+        let mut applied = 0;
+        for patch in patches.iter().filter(|p| p.obj == self.text) {
+            match &patch.action {
+                PatchAction::SpliceText { index, value, .. } => {
+                    rope.insert(to_char(rope, *index), &value.make_string());
+                }
+                PatchAction::DeleteSeq { index, length } => {
+                    let start = to_char(rope, *index);
+                    let end = to_char(rope, index + length);
+                    rope.remove(start..end);
+                }
+                _ => continue,
+            }
+            applied += 1;
+        }
+        applied
+    }
+}
+
+/// Convert a `char` index into `rope` to an index in automerge's native text unit
+fn to_native(rope: &Rope, char_idx: usize) -> usize {
+    match crate::features().text_encoding {
+        NativeTextEncoding::CodePoints => char_idx,
+        NativeTextEncoding::Utf16 => rope.char_to_utf16_cu(char_idx),
+        NativeTextEncoding::Utf8 => rope.char_to_byte(char_idx),
+    }
+}
+
+/// Convert an index in automerge's native text unit into `rope` to a `char` index
+fn to_char(rope: &Rope, native_idx: usize) -> usize {
+    match crate::features().text_encoding {
+        NativeTextEncoding::CodePoints => native_idx,
+        NativeTextEncoding::Utf16 => rope.utf16_cu_to_char(native_idx),
+        NativeTextEncoding::Utf8 => rope.byte_to_char(native_idx),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AutoCommit, ObjType, ROOT};
+
+    #[test]
+    fn rope_and_text_stay_in_sync_through_concurrent_edits() {
+        let mut doc = AutoCommit::new();
+        let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+        doc.splice_text(&text, 0, 0, "a🐻b").unwrap();
+        doc.update_diff_cursor();
+        let binding = RopeText::new(text.clone());
+        let mut rope = binding.load(&doc).unwrap();
+        let mut remote = doc.fork();
+
+        binding.splice(&mut doc, &mut rope, 1..2, "🦀🦀").unwrap();
+        binding.splice(&mut doc, &mut rope, 4..4, "!").unwrap();
+        assert!(binding.splice(&mut doc, &mut rope, 2..9, "").is_err());
+        doc.update_diff_cursor();
+        assert_eq!(rope.to_string(), doc.text(&text).unwrap());
+
+        remote.splice_text(&text, 0, 1, "🐝").unwrap();
+        remote
+            .splice_text(&text, remote.length(&text), 0, "c")
+            .unwrap();
+        doc.merge(&mut remote).unwrap();
+        let patches = doc.diff_incremental();
+        assert!(binding.apply_patches(&mut rope, &patches) > 0);
+        assert_eq!(rope.to_string(), doc.text(&text).unwrap());
+        assert_eq!(rope.to_string(), "🐝🦀🦀b!c");
+
+        rope.insert(0, "x");
+        rope.remove(3..4);
+        binding.commit(&mut doc, &rope).unwrap();
+        assert_eq!(doc.text(&text).unwrap(), "x🐝🦀b!c");
+    }
+}
@@ -1,15 +1,19 @@
 mod keys;
 mod list_range;
+mod map_entries_all;
 mod map_range;
 mod spans;
+mod text_chars;
 mod top_ops;
 mod values;
 
 pub use keys::Keys;
 pub use list_range::{ListRange, ListRangeItem};
+pub use map_entries_all::{MapEntriesAll, MapEntry};
 pub use map_range::{MapRange, MapRangeItem};
 pub use spans::{Span, Spans};
-pub use values::Values;
+pub use text_chars::TextChars;
+pub use values::{Values, ValuesRef};
 
 pub(crate) use spans::{SpanInternal, SpansInternal};
 pub(crate) use top_ops::{TopOp, TopOps};
@@ -1,13 +1,124 @@
 use std::fmt;
+use std::sync::OnceLock;
 
-use crate::op_set::OpSet;
-
-use super::TopOps;
+use crate::op_set::{Op, OpIter, OpSet};
+use crate::types::{Clock, Key, ListEncoding, ObjId};
 
 /// Iterator created by the [`crate::ReadDoc::keys()`] and [`crate::ReadDoc::keys_at()`] methods
+///
+/// This can be iterated from either end. The number of keys is looked up the first time it is
+/// asked for, which is as cheap as [`crate::ReadDoc::length()`], and tracked from then on.
 #[derive(Default)]
 pub struct Keys<'a> {
-    pub(crate) iter: Option<(TopOps<'a>, &'a OpSet)>,
+    iter: Option<KeysInner<'a>>,
+}
+
+struct KeysInner<'a> {
+    iter: OpIter<'a>,
+    op_set: &'a OpSet,
+    obj: ObjId,
+    clock: Option<Clock>,
+    /// An op read from the front of `iter` which belongs to the next key from the front
+    front: Option<Op<'a>>,
+    /// An op read from the back of `iter` which belongs to the next key from the back
+    back: Option<Op<'a>>,
+    /// The number of keys which have been returned
+    returned: usize,
+    /// The total number of keys, if it has been looked up
+    total: OnceLock<usize>,
+}
+
+impl<'a> Keys<'a> {
+    pub(crate) fn new(
+        iter: OpIter<'a>,
+        op_set: &'a OpSet,
+        obj: ObjId,
+        clock: Option<Clock>,
+    ) -> Self {
+        Self {
+            iter: Some(KeysInner {
+                iter,
+                op_set,
+                obj,
+                clock,
+                front: None,
+                back: None,
+                returned: 0,
+                total: OnceLock::new(),
+            }),
+        }
+    }
+}
+
+impl<'a> KeysInner<'a> {
+    fn next_op(&mut self) -> Option<Op<'a>> {
+        self.front
+            .take()
+            .or_else(|| self.iter.next())
+            .or_else(|| self.back.take())
+    }
+
+    fn next_op_back(&mut self) -> Option<Op<'a>> {
+        self.back
+            .take()
+            .or_else(|| self.iter.next_back())
+            .or_else(|| self.front.take())
+    }
+
+    /// The next key from the front which has a visible value
+    fn next_key(&mut self) -> Option<Key> {
+        loop {
+            let op = self.next_op()?;
+            let key = op.elemid_or_key();
+            let mut visible = op.visible_at(self.clock.as_ref());
+            while let Some(next) = self.next_op() {
+                if next.elemid_or_key() == key {
+                    visible |= next.visible_at(self.clock.as_ref());
+                } else {
+                    self.front = Some(next);
+                    break;
+                }
+            }
+            if visible {
+                return Some(key);
+            }
+        }
+    }
+
+    /// The next key from the back which has a visible value
+    fn next_key_back(&mut self) -> Option<Key> {
+        loop {
+            let op = self.next_op_back()?;
+            let key = op.elemid_or_key();
+            let mut visible = op.visible_at(self.clock.as_ref());
+            while let Some(next) = self.next_op_back() {
+                if next.elemid_or_key() == key {
+                    visible |= next.visible_at(self.clock.as_ref());
+                } else {
+                    self.back = Some(next);
+                    break;
+                }
+            }
+            if visible {
+                return Some(key);
+            }
+        }
+    }
+
+    /// Each key is one element of the length of the object
+    fn remaining(&self) -> usize {
+        let total = self.total.get_or_init(|| {
+            self.op_set
+                .length(&self.obj, ListEncoding::List, self.clock.clone())
+        });
+        total.saturating_sub(self.returned)
+    }
+
+    fn returned(&mut self, key: Option<Key>) -> Option<String> {
+        let key = key?;
+        self.returned += 1;
+        Some(self.op_set.to_string(key))
+    }
 }
 
 impl<'a> fmt::Debug for Keys<'a> {
@@ -20,8 +131,27 @@ impl<'a> Iterator for Keys<'a> {
     type Item = String;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.iter
-            .as_mut()
-            .and_then(|(i, op_set)| i.next().map(|top| op_set.to_string(top.op.elemid_or_key())))
+        let inner = self.iter.as_mut()?;
+        let key = inner.next_key();
+        inner.returned(key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        (len, Some(len))
+    }
+}
+
+impl<'a> DoubleEndedIterator for Keys<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let inner = self.iter.as_mut()?;
+        let key = inner.next_key_back();
+        inner.returned(key)
+    }
+}
+
+impl<'a> ExactSizeIterator for Keys<'a> {
+    fn len(&self) -> usize {
+        self.iter.as_ref().map(|i| i.remaining()).unwrap_or(0)
     }
 }
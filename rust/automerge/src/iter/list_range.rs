@@ -1,5 +1,5 @@
 use std::fmt;
-use std::ops::RangeBounds;
+use std::ops::{Bound, RangeBounds};
 use std::sync::Arc;
 
 use crate::exid::ExId;
@@ -68,6 +68,9 @@ impl<'a, R: RangeBounds<usize>> Iterator for ListRange<'a, R> {
             } in inner.iter.by_ref()
             {
                 let index = inner.state;
+                if past_end(&inner.range, index) {
+                    break;
+                }
                 inner.state += op.width(inner.encoding);
                 let value = op.value_at(inner.clock.as_ref());
                 let id = op.exid();
@@ -86,6 +89,15 @@ impl<'a, R: RangeBounds<usize>> Iterator for ListRange<'a, R> {
     }
 }
 
+/// Whether every index from `index` onwards lies after the end of `range`
+fn past_end<R: RangeBounds<usize>>(range: &R, index: usize) -> bool {
+    match range.end_bound() {
+        Bound::Included(end) => index > *end,
+        Bound::Excluded(end) => index >= *end,
+        Bound::Unbounded => false,
+    }
+}
+
 #[derive(Debug)]
 pub struct ListRangeItem<'a> {
     pub index: usize,
@@ -95,6 +107,12 @@ pub struct ListRangeItem<'a> {
     pub(crate) marks: Option<Arc<MarkSet>>,
 }
 
+impl<'a> From<ListRangeItem<'a>> for (usize, Value<'a>, ExId) {
+    fn from(item: ListRangeItem<'a>) -> Self {
+        (item.index, item.value, item.id)
+    }
+}
+
 impl<'a> ListRangeItem<'a> {
     pub fn marks(&self) -> Option<&MarkSet> {
         self.marks.as_deref()
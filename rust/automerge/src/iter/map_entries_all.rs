@@ -0,0 +1,93 @@
+use std::fmt;
+use std::iter::Peekable;
+
+use crate::exid::ExId;
+use crate::op_set::{OpIter, OpSetData};
+use crate::types::{Clock, Key};
+use crate::value::Value;
+
+/// Iterator created by the [`crate::ReadDoc::map_entries_all()`] and
+/// [`crate::ReadDoc::map_entries_all_at()`] methods
+///
+/// Unlike [`crate::iter::MapRange`], which only yields the winning value for each key, this
+/// iterator yields every visible value for each key, ordered in the same way as
+/// [`crate::ReadDoc::get_all()`]. The last value for each key is the one which [`crate::ReadDoc::get()`]
+/// would return.
+#[derive(Default)]
+pub struct MapEntriesAll<'a> {
+    iter: Option<MapEntriesAllInner<'a>>,
+}
+
+struct MapEntriesAllInner<'a> {
+    iter: Peekable<OpIter<'a>>,
+    osd: &'a OpSetData,
+    clock: Option<Clock>,
+}
+
+impl<'a> MapEntriesAll<'a> {
+    pub(crate) fn new(iter: OpIter<'a>, osd: &'a OpSetData, clock: Option<Clock>) -> Self {
+        Self {
+            iter: Some(MapEntriesAllInner {
+                iter: iter.peekable(),
+                osd,
+                clock,
+            }),
+        }
+    }
+}
+
+impl<'a> fmt::Debug for MapEntriesAll<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MapEntriesAll").finish()
+    }
+}
+
+impl<'a> Iterator for MapEntriesAll<'a> {
+    type Item = MapEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let inner = self.iter.as_mut()?;
+        while let Some(op) = inner.iter.next() {
+            let Key::Map(n) = op.key() else {
+                continue;
+            };
+            let mut values = Vec::new();
+            if op.visible_at(inner.clock.as_ref()) {
+                values.push(op.tagged_value(inner.clock.as_ref()));
+            }
+            while let Some(next) = inner.iter.next_if(|next| next.key() == op.key()) {
+                if next.visible_at(inner.clock.as_ref()) {
+                    values.push(next.tagged_value(inner.clock.as_ref()));
+                }
+            }
+            if values.is_empty() {
+                continue;
+            }
+            if let Some(key) = inner.osd.props.safe_get(*n) {
+                return Some(MapEntry {
+                    key: key.as_str(),
+                    values,
+                });
+            }
+        }
+        None
+    }
+}
+
+/// A key in a map along with all of the values which are currently in conflict at that key
+///
+/// See [`MapEntriesAll`]
+#[derive(Debug, PartialEq)]
+pub struct MapEntry<'a> {
+    pub key: &'a str,
+    /// The values at this key, tagged with the ID of the operation which created them. If there
+    /// are no conflicts this will have exactly one element.
+    pub values: Vec<(Value<'a>, ExId)>,
+}
+
+impl<'a> MapEntry<'a> {
+    /// Whether there is more than one value at this key
+    pub fn is_conflict(&self) -> bool {
+        self.values.len() > 1
+    }
+}
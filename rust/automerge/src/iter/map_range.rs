@@ -89,3 +89,9 @@ impl<'a> MapRangeItem<'a> {
         }
     }
 }
+
+impl<'a> From<MapRangeItem<'a>> for (&'a str, Value<'a>, ExId) {
+    fn from(item: MapRangeItem<'a>) -> Self {
+        (item.key, item.value, item.id)
+    }
+}
@@ -0,0 +1,56 @@
+use std::fmt;
+use std::str::Chars;
+
+use crate::exid::ExId;
+use crate::types::{ActorId, ElemId, Key};
+
+use super::TopOps;
+
+/// Iterator created by the [`crate::ReadDoc::text_chars()`] and
+/// [`crate::ReadDoc::text_chars_at()`] methods
+///
+/// Each item is a character of the text along with the ID of the sequence element it belongs to
+/// and the actor which inserted that element. The element ID is stable across concurrent edits so
+/// it can be used to anchor cursors, attribution and decorations.
+#[derive(Default)]
+pub struct TextChars<'a> {
+    iter: TopOps<'a>,
+    current: Option<(Chars<'a>, ExId, &'a ActorId)>,
+}
+
+impl<'a> TextChars<'a> {
+    pub(crate) fn new(iter: TopOps<'a>) -> Self {
+        Self {
+            iter,
+            current: None,
+        }
+    }
+}
+
+impl<'a> fmt::Debug for TextChars<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TextChars").finish()
+    }
+}
+
+impl<'a> Iterator for TextChars<'a> {
+    type Item = (char, ExId, &'a ActorId);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((chars, id, actor)) = self.current.as_mut() {
+                if let Some(c) = chars.next() {
+                    return Some((c, id.clone(), *actor));
+                }
+            }
+            let op = self.iter.next()?.op;
+            let Key::Seq(ElemId(elem)) = op.elemid_or_key() else {
+                continue;
+            };
+            let osd = op.osd();
+            let actor = &osd.actors.cache[elem.actor()];
+            let id = ExId::Id(elem.counter(), actor.clone(), elem.actor());
+            self.current = Some((op.as_str().chars(), id, actor));
+        }
+    }
+}
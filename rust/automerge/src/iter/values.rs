@@ -2,7 +2,7 @@ use std::fmt;
 
 use crate::exid::ExId;
 use crate::types::Clock;
-use crate::value::Value;
+use crate::value::{Value, ValueRef};
 
 use super::TopOps;
 
@@ -10,12 +10,22 @@ use super::TopOps;
 #[derive(Default)]
 pub struct Values<'a> {
     iter: Option<(TopOps<'a>, Option<Clock>)>,
+    /// Values which have already been chosen by a conflict policy
+    resolved: std::vec::IntoIter<(Value<'a>, ExId)>,
 }
 
 impl<'a> Values<'a> {
     pub(crate) fn new(iter: TopOps<'a>, clock: Option<Clock>) -> Self {
         Self {
             iter: Some((iter, clock)),
+            resolved: Default::default(),
+        }
+    }
+
+    pub(crate) fn resolved(values: Vec<(Value<'a>, ExId)>) -> Self {
+        Self {
+            iter: None,
+            resolved: values.into_iter(),
         }
     }
 }
@@ -30,8 +40,53 @@ impl<'a> Iterator for Values<'a> {
     type Item = (Value<'a>, ExId);
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.iter
-            .as_mut()
-            .and_then(|(i, clock)| i.next().map(|top| top.op.tagged_value(clock.as_ref())))
+        match self.iter.as_mut() {
+            Some((i, clock)) => i.next().map(|top| top.op.tagged_value(clock.as_ref())),
+            None => self.resolved.next(),
+        }
+    }
+}
+
+/// Iterator created by [`crate::Automerge::values_ref()`], which yields values borrowing their
+/// data from the document
+#[derive(Default)]
+pub struct ValuesRef<'a> {
+    iter: Option<(TopOps<'a>, Option<Clock>)>,
+    /// Values which have already been chosen by a conflict policy
+    resolved: std::vec::IntoIter<(ValueRef<'a>, ExId)>,
+}
+
+impl<'a> ValuesRef<'a> {
+    pub(crate) fn new(iter: TopOps<'a>, clock: Option<Clock>) -> Self {
+        Self {
+            iter: Some((iter, clock)),
+            resolved: Default::default(),
+        }
+    }
+
+    pub(crate) fn resolved(values: Vec<(ValueRef<'a>, ExId)>) -> Self {
+        Self {
+            iter: None,
+            resolved: values.into_iter(),
+        }
+    }
+}
+
+impl<'a> fmt::Debug for ValuesRef<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ValuesRef").finish()
+    }
+}
+
+impl<'a> Iterator for ValuesRef<'a> {
+    type Item = (ValueRef<'a>, ExId);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.iter.as_mut() {
+            Some((i, clock)) => i
+                .next()
+                .map(|top| (top.op.value_ref_at(clock.as_ref()), top.op.exid())),
+            None => self.resolved.next(),
+        }
     }
 }
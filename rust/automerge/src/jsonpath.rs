@@ -0,0 +1,665 @@
+//! JSONPath queries evaluated directly against a document
+//!
+//! A [`JsonPath`] selects values from a document without first converting the document to
+//! JSON. Only the objects which the query visits are read, so selecting a few values from a
+//! large document is cheap. Queries can be evaluated against the current state of a document
+//! with [`JsonPath::query()`] or against its state at some heads with [`JsonPath::query_at()`].
+//!
+//! The supported syntax is
+//!
+//! | Expression        | Selects                                                          |
+//! |-------------------|------------------------------------------------------------------|
+//! | `$`               | the root of the document                                         |
+//! | `.key`, `['key']` | the value at `key` in a map                                      |
+//! | `[2]`, `[-1]`     | the element at an index of a list, negative indexes count from the end |
+//! | `[1:3]`           | the elements in a range of indexes of a list                     |
+//! | `.*`, `[*]`       | every value in a map or list                                     |
+//! | `..key`, `..*`    | the value at `key` in, or every value of, every nested object    |
+//! | `[?(filter)]`     | every value in a map or list for which the filter is true        |
+//!
+//! Filters compare values relative to the value being filtered, written `@.key` or `@[0]`,
+//! with literal strings, numbers, `true`, `false` and `null` using `==`, `!=`, `<`, `<=`, `>`
+//! and `>=`. A relative path on its own tests whether the value exists. Filters can be
+//! combined with `&&`, `||`, `!` and parentheses. Text objects are compared as strings.
+//!
+//! # Example
+//!
+//! ```
+//! # use automerge::{AutoCommit, ObjType, Value, ROOT};
+//! # use automerge::jsonpath::JsonPath;
+//! # use automerge::transaction::Transactable;
+//! let mut doc = AutoCommit::new();
+//! let todos = doc.put_object(ROOT, "todos", ObjType::List).unwrap();
+//! for (i, (title, done)) in [("shop", true), ("cook", false)].into_iter().enumerate() {
+//!     let todo = doc.insert_object(&todos, i, ObjType::Map).unwrap();
+//!     doc.put(&todo, "title", title).unwrap();
+//!     doc.put(&todo, "done", done).unwrap();
+//! }
+//!
+//! let query: JsonPath = "$.todos[?(@.done==false)].title".parse().unwrap();
+//! let matches = query.query(&doc).unwrap();
+//! assert_eq!(matches.len(), 1);
+//! assert_eq!(matches[0].value, Value::from("cook"));
+//! ```
+use std::str::FromStr;
+
+use crate::exid::ExId;
+use crate::{AutomergeError, ChangeHash, ObjType, Prop, ReadDoc, ScalarValue, Value, ROOT};
+
+/// A parsed JSONPath expression, see the [module documentation](self)
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    segments: Vec<Segment>,
+}
+
+/// A value selected by a [`JsonPath`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryMatch<'a> {
+    /// The path from the root of the document to the value
+    pub path: Vec<Prop>,
+    /// The value
+    pub value: Value<'a>,
+    /// The ID of the value, as returned by [`ReadDoc::get()`]
+    pub id: ExId,
+}
+
+/// The error returned when parsing an invalid [`JsonPath`]
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("invalid JSONPath at offset {offset}: {reason}")]
+pub struct ParseError {
+    /// The offset in bytes into the expression at which the error was found
+    pub offset: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Child(Selector),
+    Descendant(Selector),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    Key(String),
+    Index(i64),
+    Slice(Option<i64>, Option<i64>),
+    Wildcard,
+    Filter(Filter),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Or(Box<Filter>, Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Exists(Vec<Prop>),
+    Compare(Operand, CompareOp, Operand),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Relative(Vec<Prop>),
+    Literal(Literal),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Str(String),
+    Num(f64),
+    Bool(bool),
+    Null,
+}
+
+impl JsonPath {
+    /// Parse a JSONPath expression
+    pub fn parse(expr: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser { expr, pos: 0 };
+        parser.expect("$")?;
+        let mut segments = Vec::new();
+        while !parser.at_end() {
+            segments.push(parser.segment()?);
+        }
+        Ok(Self { segments })
+    }
+
+    /// Evaluate this query against the current state of `doc`
+    pub fn query<'a, R: ReadDoc>(&self, doc: &'a R) -> Result<Vec<QueryMatch<'a>>, AutomergeError> {
+        self.evaluate(&Reader { doc, heads: None })
+    }
+
+    /// Evaluate this query against the state of `doc` at `heads`
+    pub fn query_at<'a, R: ReadDoc>(
+        &self,
+        doc: &'a R,
+        heads: &[ChangeHash],
+    ) -> Result<Vec<QueryMatch<'a>>, AutomergeError> {
+        self.evaluate(&Reader {
+            doc,
+            heads: Some(heads),
+        })
+    }
+
+    /// The props this query selects if it only selects keys and non-negative indexes, which is
+    /// how [`ReadDoc::get_path()`] reads paths
+    pub(crate) fn props(&self) -> Option<Vec<Prop>> {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Child(Selector::Key(key)) => Some(Prop::Map(key.clone())),
+                Segment::Child(Selector::Index(index)) => {
+                    usize::try_from(*index).ok().map(Prop::Seq)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn evaluate<'a, R: ReadDoc>(
+        &self,
+        reader: &Reader<'a, '_, R>,
+    ) -> Result<Vec<QueryMatch<'a>>, AutomergeError> {
+        let mut nodes = vec![QueryMatch {
+            path: Vec::new(),
+            value: Value::Object(ObjType::Map),
+            id: ROOT,
+        }];
+        for segment in &self.segments {
+            let mut next = Vec::new();
+            for node in nodes {
+                match segment {
+                    Segment::Child(selector) => reader.select(&node, selector, &mut next)?,
+                    Segment::Descendant(selector) => {
+                        for node in reader.descendants(node) {
+                            reader.select(&node, selector, &mut next)?;
+                        }
+                    }
+                }
+            }
+            nodes = next;
+        }
+        Ok(nodes)
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+struct Parser<'a> {
+    expr: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.expr[self.pos..]
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos == self.expr.len()
+    }
+
+    fn error<T>(&self, reason: &str) -> Result<T, ParseError> {
+        Err(ParseError {
+            offset: self.pos,
+            reason: reason.to_string(),
+        })
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.pos = self.expr.len() - trimmed.len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), ParseError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            self.error(&format!("expected `{}`", token))
+        }
+    }
+
+    fn segment(&mut self) -> Result<Segment, ParseError> {
+        if self.eat("..") {
+            let selector = if self.rest().starts_with('[') {
+                self.bracket()?
+            } else {
+                self.dot_selector()?
+            };
+            Ok(Segment::Descendant(selector))
+        } else if self.eat(".") {
+            Ok(Segment::Child(self.dot_selector()?))
+        } else if self.rest().starts_with('[') {
+            Ok(Segment::Child(self.bracket()?))
+        } else {
+            self.error("expected `.`, `..` or `[`")
+        }
+    }
+
+    fn dot_selector(&mut self) -> Result<Selector, ParseError> {
+        if self.eat("*") {
+            Ok(Selector::Wildcard)
+        } else {
+            Ok(Selector::Key(self.name()?))
+        }
+    }
+
+    fn name(&mut self) -> Result<String, ParseError> {
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-' || c == '$'))
+            .unwrap_or(self.rest().len());
+        if len == 0 {
+            return self.error("expected a key");
+        }
+        let name = self.rest()[..len].to_string();
+        self.pos += len;
+        Ok(name)
+    }
+
+    fn bracket(&mut self) -> Result<Selector, ParseError> {
+        self.expect("[")?;
+        self.skip_whitespace();
+        let selector = if self.eat("*") {
+            Selector::Wildcard
+        } else if self.eat("?") {
+            self.skip_whitespace();
+            self.expect("(")?;
+            let filter = self.or()?;
+            self.skip_whitespace();
+            self.expect(")")?;
+            Selector::Filter(filter)
+        } else if self.rest().starts_with(['\'', '"']) {
+            Selector::Key(self.string()?)
+        } else {
+            let start = self.optional_int()?;
+            self.skip_whitespace();
+            if self.eat(":") {
+                self.skip_whitespace();
+                Selector::Slice(start, self.optional_int()?)
+            } else if let Some(index) = start {
+                Selector::Index(index)
+            } else {
+                return self.error("expected a key, index, slice, `*` or filter");
+            }
+        };
+        self.skip_whitespace();
+        self.expect("]")?;
+        Ok(selector)
+    }
+
+    fn optional_int(&mut self) -> Result<Option<i64>, ParseError> {
+        let rest = self.rest();
+        let digits = rest.strip_prefix('-').unwrap_or(rest);
+        let len = digits
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(digits.len());
+        if len == 0 {
+            return Ok(None);
+        }
+        let len = len + (rest.len() - digits.len());
+        match rest[..len].parse() {
+            Ok(n) => {
+                self.pos += len;
+                Ok(Some(n))
+            }
+            Err(_) => self.error("invalid index"),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        let quote = self.rest().chars().next();
+        let Some(quote @ ('\'' | '"')) = quote else {
+            return self.error("expected a string");
+        };
+        self.pos += 1;
+        let mut out = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, c)) => out.push(c),
+                    None => break,
+                },
+                c if c == quote => {
+                    self.pos += i + 1;
+                    return Ok(out);
+                }
+                c => out.push(c),
+            }
+        }
+        self.pos = self.expr.len();
+        self.error("unterminated string")
+    }
+
+    fn or(&mut self) -> Result<Filter, ParseError> {
+        let mut filter = self.and()?;
+        loop {
+            self.skip_whitespace();
+            if !self.eat("||") {
+                return Ok(filter);
+            }
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+    }
+
+    fn and(&mut self) -> Result<Filter, ParseError> {
+        let mut filter = self.unary()?;
+        loop {
+            self.skip_whitespace();
+            if !self.eat("&&") {
+                return Ok(filter);
+            }
+            filter = Filter::And(Box::new(filter), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Filter, ParseError> {
+        self.skip_whitespace();
+        if self.rest().starts_with("!=") {
+            return self.error("expected a filter");
+        }
+        if self.eat("!") {
+            return Ok(Filter::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let filter = self.or()?;
+            self.skip_whitespace();
+            self.expect(")")?;
+            return Ok(filter);
+        }
+        let left = self.operand()?;
+        self.skip_whitespace();
+        let op = [
+            ("==", CompareOp::Eq),
+            ("!=", CompareOp::Ne),
+            ("<=", CompareOp::Le),
+            (">=", CompareOp::Ge),
+            ("<", CompareOp::Lt),
+            (">", CompareOp::Gt),
+        ]
+        .into_iter()
+        .find(|(token, _)| self.eat(token));
+        match (op, left) {
+            (Some((_, op)), left) => {
+                self.skip_whitespace();
+                Ok(Filter::Compare(left, op, self.operand()?))
+            }
+            (None, Operand::Relative(path)) => Ok(Filter::Exists(path)),
+            (None, Operand::Literal(_)) => self.error("expected a comparison"),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, ParseError> {
+        if self.eat("@") {
+            let mut path = Vec::new();
+            loop {
+                if self.eat(".") {
+                    path.push(Prop::Map(self.name()?));
+                } else if self.eat("[") {
+                    self.skip_whitespace();
+                    if self.rest().starts_with(['\'', '"']) {
+                        path.push(Prop::Map(self.string()?));
+                    } else {
+                        match self.optional_int()? {
+                            Some(i) if i >= 0 => path.push(Prop::Seq(i as usize)),
+                            _ => return self.error("expected a key or an index"),
+                        }
+                    }
+                    self.skip_whitespace();
+                    self.expect("]")?;
+                } else {
+                    return Ok(Operand::Relative(path));
+                }
+            }
+        }
+        if self.rest().starts_with(['\'', '"']) {
+            return Ok(Operand::Literal(Literal::Str(self.string()?)));
+        }
+        for (token, literal) in [
+            ("true", Literal::Bool(true)),
+            ("false", Literal::Bool(false)),
+            ("null", Literal::Null),
+        ] {
+            if self.eat(token) {
+                return Ok(Operand::Literal(literal));
+            }
+        }
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')))
+            .unwrap_or(self.rest().len());
+        match self.rest()[..len].parse() {
+            Ok(n) if len > 0 => {
+                self.pos += len;
+                Ok(Operand::Literal(Literal::Num(n)))
+            }
+            _ => self.error("expected `@` or a literal"),
+        }
+    }
+}
+
+/// Reads a document either at its current state or at some heads
+struct Reader<'a, 'h, R> {
+    doc: &'a R,
+    heads: Option<&'h [ChangeHash]>,
+}
+
+impl<'a, 'h, R: ReadDoc> Reader<'a, 'h, R> {
+    fn get(&self, obj: &ExId, prop: Prop) -> Result<Option<(Value<'a>, ExId)>, AutomergeError> {
+        match self.heads {
+            Some(heads) => self.doc.get_at(obj, prop, heads),
+            None => self.doc.get(obj, prop),
+        }
+    }
+
+    fn length(&self, obj: &ExId) -> usize {
+        match self.heads {
+            Some(heads) => self.doc.length_at(obj, heads),
+            None => self.doc.length(obj),
+        }
+    }
+
+    fn text(&self, obj: &ExId) -> Result<String, AutomergeError> {
+        match self.heads {
+            Some(heads) => self.doc.text_at(obj, heads),
+            None => self.doc.text(obj),
+        }
+    }
+
+    fn children(&self, node: &QueryMatch<'a>) -> Vec<QueryMatch<'a>> {
+        let child = |prop: Prop, value, id| {
+            let mut path = node.path.clone();
+            path.push(prop);
+            QueryMatch { path, value, id }
+        };
+        match (&node.value, self.heads) {
+            (Value::Object(ObjType::Map | ObjType::Table), None) => self
+                .doc
+                .map_range(&node.id, ..)
+                .map(|item| child(item.key.into(), item.value, item.id))
+                .collect(),
+            (Value::Object(ObjType::Map | ObjType::Table), Some(heads)) => self
+                .doc
+                .map_range_at(&node.id, .., heads)
+                .map(|item| child(item.key.into(), item.value, item.id))
+                .collect(),
+            (Value::Object(ObjType::List), None) => self
+                .doc
+                .list_range(&node.id, ..)
+                .map(|item| child(item.index.into(), item.value, item.id))
+                .collect(),
+            (Value::Object(ObjType::List), Some(heads)) => self
+                .doc
+                .list_range_at(&node.id, .., heads)
+                .map(|item| child(item.index.into(), item.value, item.id))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn child(
+        &self,
+        node: &QueryMatch<'a>,
+        prop: Prop,
+    ) -> Result<Option<QueryMatch<'a>>, AutomergeError> {
+        Ok(self.get(&node.id, prop.clone())?.map(|(value, id)| {
+            let mut path = node.path.clone();
+            path.push(prop);
+            QueryMatch { path, value, id }
+        }))
+    }
+
+    /// `node` followed by every value nested inside it, depth first
+    fn descendants(&self, node: QueryMatch<'a>) -> Vec<QueryMatch<'a>> {
+        let mut out = Vec::new();
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            let mut children = self.children(&node);
+            children.reverse();
+            stack.extend(children);
+            out.push(node);
+        }
+        out
+    }
+
+    fn select(
+        &self,
+        node: &QueryMatch<'a>,
+        selector: &Selector,
+        out: &mut Vec<QueryMatch<'a>>,
+    ) -> Result<(), AutomergeError> {
+        match (selector, &node.value) {
+            (Selector::Key(key), Value::Object(ObjType::Map | ObjType::Table)) => {
+                out.extend(self.child(node, key.as_str().into())?);
+            }
+            (Selector::Index(index), Value::Object(ObjType::List)) => {
+                let len = self.length(&node.id) as i64;
+                let index = if *index < 0 { len + index } else { *index };
+                if (0..len).contains(&index) {
+                    out.extend(self.child(node, Prop::Seq(index as usize))?);
+                }
+            }
+            (Selector::Slice(start, end), Value::Object(ObjType::List)) => {
+                let len = self.length(&node.id) as i64;
+                let clamp = |i: i64| if i < 0 { (len + i).max(0) } else { i.min(len) };
+                let start = start.map(clamp).unwrap_or(0);
+                let end = end.map(clamp).unwrap_or(len);
+                for index in start..end {
+                    out.extend(self.child(node, Prop::Seq(index as usize))?);
+                }
+            }
+            (Selector::Wildcard, _) => out.extend(self.children(node)),
+            (Selector::Filter(filter), _) => {
+                for child in self.children(node) {
+                    if self.matches(&child, filter)? {
+                        out.push(child);
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn matches(&self, node: &QueryMatch<'a>, filter: &Filter) -> Result<bool, AutomergeError> {
+        Ok(match filter {
+            Filter::Or(a, b) => self.matches(node, a)? || self.matches(node, b)?,
+            Filter::And(a, b) => self.matches(node, a)? && self.matches(node, b)?,
+            Filter::Not(a) => !self.matches(node, a)?,
+            Filter::Exists(path) => self.relative(node, path)?.is_some(),
+            Filter::Compare(a, op, b) => {
+                let (Some(a), Some(b)) = (self.literal(node, a)?, self.literal(node, b)?) else {
+                    return Ok(*op == CompareOp::Ne);
+                };
+                compare(&a, *op, &b)
+            }
+        })
+    }
+
+    fn relative(
+        &self,
+        node: &QueryMatch<'a>,
+        path: &[Prop],
+    ) -> Result<Option<QueryMatch<'a>>, AutomergeError> {
+        let mut node = node.clone();
+        for prop in path {
+            if !node.value.is_object() {
+                return Ok(None);
+            }
+            match self.child(&node, prop.clone())? {
+                Some(child) => node = child,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(node))
+    }
+
+    /// The value of `operand` as a literal, or `None` if it does not exist or is an object
+    /// other than text
+    fn literal(
+        &self,
+        node: &QueryMatch<'a>,
+        operand: &Operand,
+    ) -> Result<Option<Literal>, AutomergeError> {
+        let path = match operand {
+            Operand::Literal(literal) => return Ok(Some(literal.clone())),
+            Operand::Relative(path) => path,
+        };
+        let Some(found) = self.relative(node, path)? else {
+            return Ok(None);
+        };
+        Ok(match &found.value {
+            Value::Object(ObjType::Text) => Some(Literal::Str(self.text(&found.id)?)),
+            Value::Object(_) => None,
+            Value::Scalar(s) => match s.as_ref() {
+                ScalarValue::Str(s) => Some(Literal::Str(s.to_string())),
+                ScalarValue::Boolean(b) => Some(Literal::Bool(*b)),
+                ScalarValue::Null => Some(Literal::Null),
+                s => s.to_f64().map(Literal::Num),
+            },
+        })
+    }
+}
+
+fn compare(a: &Literal, op: CompareOp, b: &Literal) -> bool {
+    use std::cmp::Ordering;
+    let ordering = match (a, b) {
+        (Literal::Str(a), Literal::Str(b)) => Some(a.cmp(b)),
+        (Literal::Num(a), Literal::Num(b)) => a.partial_cmp(b),
+        (Literal::Bool(a), Literal::Bool(b)) if a == b => Some(Ordering::Equal),
+        (Literal::Null, Literal::Null) => Some(Ordering::Equal),
+        _ => None,
+    };
+    match op {
+        CompareOp::Eq => ordering == Some(Ordering::Equal),
+        CompareOp::Ne => ordering != Some(Ordering::Equal),
+        CompareOp::Lt => ordering == Some(Ordering::Less),
+        CompareOp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        CompareOp::Gt => ordering == Some(Ordering::Greater),
+        CompareOp::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+    }
+}
@@ -214,6 +214,31 @@ impl<'de> Deserialize<'de> for Op {
                         Some(ScalarValue::F64(n)) => Ok(OpType::Increment(n as i64)),
                         Some(ScalarValue::Counter(n)) => Ok(OpType::Increment(n.into())),
                         Some(ScalarValue::Timestamp(n)) => Ok(OpType::Increment(n)),
+                        Some(ScalarValue::BigInt(n)) => {
+                            i64::try_from(&n).map(OpType::Increment).map_err(|_| {
+                                Error::invalid_value(Unexpected::Str(n.as_str()), &"an i64")
+                            })
+                        }
+                        Some(ScalarValue::Decimal(n)) => Err(Error::invalid_value(
+                            Unexpected::Str(n.as_str()),
+                            &"an integer",
+                        )),
+                        Some(ScalarValue::DateTime(d)) => Err(Error::invalid_value(
+                            Unexpected::Other(&d.to_string()),
+                            &"a number",
+                        )),
+                        Some(ScalarValue::Chunked(_)) => Err(Error::invalid_value(
+                            Unexpected::Other("chunked bytes"),
+                            &"a number",
+                        )),
+                        Some(ScalarValue::BlobRef(_)) => Err(Error::invalid_value(
+                            Unexpected::Other("a blob reference"),
+                            &"a number",
+                        )),
+                        Some(ScalarValue::Link(_)) => Err(Error::invalid_value(
+                            Unexpected::Other("a link"),
+                            &"a number",
+                        )),
                         Some(ScalarValue::Bytes(s)) => {
                             Err(Error::invalid_value(Unexpected::Bytes(&s), &"a number"))
                         }
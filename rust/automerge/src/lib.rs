@@ -256,57 +256,102 @@ macro_rules! __log {
      }
  }
 
+pub mod annotations;
+#[cfg(feature = "audit-log")]
+pub mod audit;
 mod autocommit;
 mod automerge;
 mod autoserde;
+mod bignum;
+pub mod bindings;
+pub mod blob;
+mod cancel;
+#[cfg(feature = "cbor")]
+pub mod cbor;
 mod change;
 mod change_graph;
+mod chunked_bytes;
 mod clock;
 mod columnar;
 mod convert;
 mod cursor;
+mod datetime;
 pub mod error;
 mod exid;
+mod extra_bytes;
+mod features;
 pub mod hydrate;
 mod indexed_cache;
+mod instrument;
+pub mod integrations;
 pub mod iter;
+pub mod jsonpath;
 mod legacy;
+pub mod link;
 pub mod marks;
+pub mod metrics;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
 mod op_set;
 pub mod op_tree;
 mod parents;
 pub mod patches;
+mod path;
 mod query;
 mod read;
+pub mod repo;
+pub mod schema;
 mod sequence_tree;
+mod shared;
 mod storage;
+#[cfg(feature = "proptest")]
+pub mod strategies;
 pub mod sync;
+pub mod table;
 mod text_diff;
+mod text_unit;
 mod text_value;
 pub mod transaction;
+pub mod typed;
 mod types;
 mod value;
 #[cfg(feature = "optree-visualisation")]
 mod visualisation;
 
-pub use crate::automerge::{Automerge, LoadOptions, OnPartialLoad, SaveOptions, StringMigration};
+pub use crate::automerge::{
+    ActorInfo, Automerge, ChangeReader, ChangeSummary, Checkout, CloneActor, CloneOptions,
+    Conflict, ConflictCounts, ConflictPolicy, ConflictStats, Conflicts, ContentDifference,
+    DepsStrategy, History, LoadOptions, MergeReport, ObjHandle, OnPartialLoad, SaveOptions,
+    SealError, SealedDocument, StateHash, StringMigration, TransactionContext, VerificationProblem,
+    VerificationReport, WriteRequest,
+};
 pub use autocommit::AutoCommit;
 pub use autoserde::AutoSerde;
+pub use bignum::{BigInt, Decimal};
+pub use cancel::CancellationToken;
 pub use change::{Change, LoadError as LoadChangeError};
+pub use chunked_bytes::{ChunkedBytes, SegmentHash};
 pub use cursor::Cursor;
+pub use datetime::DateTime;
 pub use error::AutomergeError;
 pub use error::InvalidActorId;
 pub use error::InvalidChangeHashSlice;
 pub use exid::{ExId as ObjId, ObjIdFromBytesError};
-pub use legacy::Change as ExpandedChange;
+pub use features::{features, Features, NativeTextEncoding};
+pub use legacy::{
+    Change as ExpandedChange, ElementId as ExpandedElementId, Key as ExpandedKey,
+    ObjectId as ExpandedObjectId, Op as ExpandedOp, OpId as ExpandedOpId, OpType as ExpandedOpType,
+};
 pub use parents::{Parent, Parents};
 pub use patches::{Patch, PatchAction, PatchLog};
 pub use read::ReadDoc;
 pub use sequence_tree::SequenceTree;
+pub use shared::SharedAutomerge;
 pub use storage::VerificationMode;
+pub use text_unit::TextUnit;
 pub use transaction::BlockOrText;
 pub use types::{ActorId, ChangeHash, ObjType, OpType, ParseChangeHashError, Prop};
-pub use value::{ScalarValue, Value};
+pub use value::{Counter, ScalarValue, Value, ValueRef};
 
 /// The object ID for the root map of a document
 pub const ROOT: ObjId = ObjId::Root;
@@ -0,0 +1,146 @@
+//! Links from one document to another
+//!
+//! Applications made of several documents need to refer from one document to another. A
+//! [`DocId`] put in a document is stored as [`crate::ScalarValue::Link`], so it is synced and
+//! saved like any other value but, unlike an ID encoded in a string, can always be told apart
+//! from text. The document does not know where other documents are kept: a [`LinkResolver`] set
+//! with [`crate::Automerge::set_link_resolver()`] finds the document a link refers to.
+//!
+//! ```
+//! # use std::sync::Arc;
+//! # use automerge::{AutoCommit, ReadDoc, ROOT, transaction::Transactable};
+//! # use automerge::link::{DocId, MemoryLinkResolver};
+//! let resolver = Arc::new(MemoryLinkResolver::default());
+//! let mut comments = AutoCommit::new();
+//! comments.put(ROOT, "count", 0).unwrap();
+//! let comments_id = DocId::random();
+//! resolver.insert(comments_id.clone(), comments.document().clone());
+//!
+//! let mut post = AutoCommit::new();
+//! post.set_link_resolver(resolver);
+//! post.put(ROOT, "comments", comments_id).unwrap();
+//!
+//! let (value, _) = post.get(ROOT, "comments").unwrap().unwrap();
+//! let link = value.to_scalar().unwrap().to_link().unwrap();
+//! let linked = post.resolve_link(link).unwrap();
+//! assert_eq!(linked.get_heads(), comments.get_heads());
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use std::sync::{Mutex, PoisonError};
+
+use crate::Automerge;
+
+/// The ID of a document, which other documents can link to
+///
+/// An ID is any sequence of bytes the application chooses, usually a random ID from
+/// [`Self::random()`]. It is displayed and parsed as hex.
+#[derive(Eq, PartialEq, Hash, Clone, PartialOrd, Ord)]
+pub struct DocId(Vec<u8>);
+
+impl DocId {
+    /// A random 16 byte ID
+    pub fn random() -> Self {
+        Self(uuid::Uuid::new_v4().as_bytes().to_vec())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<&[u8]> for DocId {
+    fn from(bytes: &[u8]) -> Self {
+        Self(bytes.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for DocId {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl FromStr for DocId {
+    type Err = hex::FromHexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        hex::decode(s).map(Self)
+    }
+}
+
+impl fmt::Debug for DocId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("DocId").field(&hex::encode(&self.0)).finish()
+    }
+}
+
+impl fmt::Display for DocId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(&self.0))
+    }
+}
+
+impl serde::Serialize for DocId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(&self.0))
+    }
+}
+
+/// Finds the documents links refer to, see [`crate::Automerge::set_link_resolver()`]
+pub trait LinkResolver: Send + Sync {
+    /// The document with ID `doc`, or `None` if the resolver does not know it
+    fn resolve(&self, doc: &DocId) -> io::Result<Option<Automerge>>;
+}
+
+/// A [`LinkResolver`] which keeps documents in memory
+///
+/// Resolving a link returns a copy of the document as it was inserted.
+#[derive(Debug, Default)]
+pub struct MemoryLinkResolver {
+    docs: Mutex<HashMap<DocId, Automerge>>,
+}
+
+impl MemoryLinkResolver {
+    /// Resolve links to `id` to `doc`, replacing any document which already has that ID
+    pub fn insert(&self, id: DocId, doc: Automerge) {
+        let mut docs = self.docs.lock().unwrap_or_else(PoisonError::into_inner);
+        docs.insert(id, doc);
+    }
+
+    pub fn remove(&self, id: &DocId) -> Option<Automerge> {
+        let mut docs = self.docs.lock().unwrap_or_else(PoisonError::into_inner);
+        docs.remove(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.docs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl LinkResolver for MemoryLinkResolver {
+    fn resolve(&self, doc: &DocId) -> io::Result<Option<Automerge>> {
+        let docs = self.docs.lock().unwrap_or_else(PoisonError::into_inner);
+        Ok(docs.get(doc).cloned())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LinkError {
+    #[error("the document has no link resolver")]
+    NoResolver,
+    #[error("the link resolver does not have document {0}")]
+    Missing(DocId),
+    #[error("error resolving a link: {0}")]
+    Resolver(#[from] io::Error),
+}
@@ -0,0 +1,110 @@
+//! Counters describing the work a document does, for exporting to a metrics system
+//!
+//! A [`Metrics`] implementation set with [`crate::Automerge::set_metrics()`] is told about ops
+//! being applied, changes being received from other peers, sync messages being generated and
+//! documents being saved as it happens, so that a service can keep Prometheus (or similar)
+//! counters without wrapping every call it makes to the document.
+//!
+//! Every method has an empty default implementation, so an implementation only needs to provide
+//! the ones it is interested in. [`Counters`] is an implementation which keeps running totals.
+//!
+//! # Example
+//!
+//! ```
+//! # use automerge::{AutoCommit, ROOT};
+//! # use automerge::metrics::Counters;
+//! # use automerge::transaction::Transactable;
+//! # use std::sync::Arc;
+//! let counters = Arc::new(Counters::default());
+//! let mut doc = AutoCommit::new();
+//! doc.set_metrics(counters.clone());
+//! doc.put(ROOT, "key", "value").unwrap();
+//! let saved = doc.save();
+//!
+//! assert_eq!(counters.ops_applied(), 1);
+//! assert_eq!(counters.bytes_saved(), saved.len() as u64);
+//! ```
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Receives counts of the work done by a document, see [`crate::metrics`]
+///
+/// The methods are called while the document is being modified, so they should be cheap and
+/// must not block.
+pub trait Metrics: Send + Sync {
+    /// `ops` ops were applied to the document by a local commit or a change from another peer
+    fn ops_applied(&self, ops: usize) {
+        let _ = ops;
+    }
+
+    /// `changes` changes from other peers were applied to the document
+    ///
+    /// Changes which are already in the document are not counted, and changes which are waiting
+    /// for their dependencies are counted once they are applied.
+    fn changes_received(&self, changes: usize) {
+        let _ = changes;
+    }
+
+    /// A sync message containing `bytes` bytes of changes was generated
+    fn sync_message_generated(&self, bytes: usize) {
+        let _ = bytes;
+    }
+
+    /// The document, or some of its changes, was saved to `bytes` bytes
+    fn bytes_saved(&self, bytes: usize) {
+        let _ = bytes;
+    }
+}
+
+/// A [`Metrics`] implementation which keeps a running total of each count
+#[derive(Debug, Default)]
+pub struct Counters {
+    ops_applied: AtomicU64,
+    changes_received: AtomicU64,
+    sync_messages_generated: AtomicU64,
+    sync_bytes_generated: AtomicU64,
+    bytes_saved: AtomicU64,
+}
+
+impl Counters {
+    pub fn ops_applied(&self) -> u64 {
+        self.ops_applied.load(Ordering::Relaxed)
+    }
+
+    pub fn changes_received(&self) -> u64 {
+        self.changes_received.load(Ordering::Relaxed)
+    }
+
+    pub fn sync_messages_generated(&self) -> u64 {
+        self.sync_messages_generated.load(Ordering::Relaxed)
+    }
+
+    /// The total size of the changes in the generated sync messages
+    pub fn sync_bytes_generated(&self) -> u64 {
+        self.sync_bytes_generated.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_saved(&self) -> u64 {
+        self.bytes_saved.load(Ordering::Relaxed)
+    }
+}
+
+impl Metrics for Counters {
+    fn ops_applied(&self, ops: usize) {
+        self.ops_applied.fetch_add(ops as u64, Ordering::Relaxed);
+    }
+
+    fn changes_received(&self, changes: usize) {
+        self.changes_received
+            .fetch_add(changes as u64, Ordering::Relaxed);
+    }
+
+    fn sync_message_generated(&self, bytes: usize) {
+        self.sync_messages_generated.fetch_add(1, Ordering::Relaxed);
+        self.sync_bytes_generated
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    fn bytes_saved(&self, bytes: usize) {
+        self.bytes_saved.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
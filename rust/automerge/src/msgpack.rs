@@ -0,0 +1,198 @@
+//! Export of the current state of a document as MessagePack
+//!
+//! Only the materialized value of the document is exported, not its history. Maps and tables
+//! become MessagePack maps, lists become arrays and text objects and strings become strings.
+//! Scalar types which MessagePack has no native type for are written as extension types so
+//! that they survive the trip:
+//!
+//! * bytes are written as `bin` values
+//! * counters are written as extension type [`COUNTER_EXT`] holding the current value of the
+//!   counter as a big endian `i64`
+//! * timestamps are written as the standard timestamp extension type (-1)
+//! * values from future versions of automerge are written as extension type [`UNKNOWN_EXT`]
+//!   holding the type code followed by the raw bytes of the value
+//!
+//! # Example
+//!
+//! ```
+//! # use automerge::{AutoCommit, ROOT};
+//! # use automerge::msgpack::to_msgpack;
+//! # use automerge::transaction::Transactable;
+//! let mut doc = AutoCommit::new();
+//! doc.put(ROOT, "raw", vec![0xde, 0xad]).unwrap();
+//!
+//! let bytes = to_msgpack(&doc).unwrap();
+//! assert_eq!(bytes, [0x81, 0xa3, b'r', b'a', b'w', 0xc4, 0x02, 0xde, 0xad]);
+//! ```
+use rmp::encode;
+
+use crate::exid::ExId;
+use crate::{ObjType, ReadDoc, ScalarValue, Value, ROOT};
+
+/// The extension type counters are written as
+pub const COUNTER_EXT: i8 = 1;
+/// The extension type values from future versions of automerge are written as
+pub const UNKNOWN_EXT: i8 = 2;
+/// The standard MessagePack timestamp extension type
+const TIMESTAMP_EXT: i8 = -1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MsgpackError {
+    #[error("a value of length {0} is too long to encode")]
+    TooLong(usize),
+    #[error("a segment of a chunked bytes value is missing")]
+    MissingSegment,
+    #[error(transparent)]
+    Automerge(#[from] crate::AutomergeError),
+}
+
+/// Encode the current state of `doc` as MessagePack
+pub fn to_msgpack<R: ReadDoc>(doc: &R) -> Result<Vec<u8>, MsgpackError> {
+    let mut out = Vec::new();
+    write_object(doc, &mut out, &ROOT, ObjType::Map)?;
+    Ok(out)
+}
+
+// Writing to a `Vec` can only fail if a length does not fit in a u32, which `len()` checks, so
+// the results of the `encode` functions are ignored
+
+fn write_object<R: ReadDoc>(
+    doc: &R,
+    out: &mut Vec<u8>,
+    obj: &ExId,
+    typ: ObjType,
+) -> Result<(), MsgpackError> {
+    match typ {
+        ObjType::Map | ObjType::Table => {
+            let _ = encode::write_map_len(out, len(doc.length(obj))?);
+            for item in doc.map_range(obj, ..) {
+                write_str(out, item.key)?;
+                write_value(doc, out, item.value, &item.id)?;
+            }
+        }
+        ObjType::List => {
+            let _ = encode::write_array_len(out, len(doc.length(obj))?);
+            for item in doc.list_range(obj, ..) {
+                write_value(doc, out, item.value, &item.id)?;
+            }
+        }
+        ObjType::Text => write_str(out, &doc.text(obj)?)?,
+    }
+    Ok(())
+}
+
+fn write_value<R: ReadDoc>(
+    doc: &R,
+    out: &mut Vec<u8>,
+    value: Value<'_>,
+    id: &ExId,
+) -> Result<(), MsgpackError> {
+    let scalar = match value {
+        Value::Object(typ) => return write_object(doc, out, id, typ),
+        Value::Scalar(s) => s,
+    };
+    match scalar.as_ref() {
+        ScalarValue::Bytes(b) => {
+            let _ = encode::write_bin_len(out, len(b.len())?);
+            out.extend_from_slice(b);
+        }
+        ScalarValue::Chunked(b) => {
+            let _ = encode::write_bin_len(out, len(b.len() as usize)?);
+            for segment in b.segments() {
+                out.extend_from_slice(segment.ok_or(MsgpackError::MissingSegment)?);
+            }
+        }
+        ScalarValue::Str(s) => write_str(out, s)?,
+        ScalarValue::Int(n) => {
+            let _ = encode::write_sint(out, *n);
+        }
+        ScalarValue::Uint(n) => {
+            let _ = encode::write_uint(out, *n);
+        }
+        ScalarValue::F64(n) => {
+            let _ = encode::write_f64(out, *n);
+        }
+        ScalarValue::Counter(c) => {
+            let _ = encode::write_ext_meta(out, 8, COUNTER_EXT);
+            out.extend_from_slice(&i64::from(c).to_be_bytes());
+        }
+        ScalarValue::Timestamp(ms) => {
+            // the 96 bit timestamp format: nanoseconds as a u32 followed by seconds as an i64
+            let secs = ms.div_euclid(1000);
+            let nanos = (ms.rem_euclid(1000) * 1_000_000) as u32;
+            let _ = encode::write_ext_meta(out, 12, TIMESTAMP_EXT);
+            out.extend_from_slice(&nanos.to_be_bytes());
+            out.extend_from_slice(&secs.to_be_bytes());
+        }
+        ScalarValue::BigInt(n) => match (i64::try_from(n), u64::try_from(n)) {
+            (Ok(i), _) => {
+                let _ = encode::write_sint(out, i);
+            }
+            (_, Ok(u)) => {
+                let _ = encode::write_uint(out, u);
+            }
+            // too large for a msgpack integer, so keep every digit as a string
+            _ => write_str(out, n.as_str())?,
+        },
+        ScalarValue::Decimal(n) => write_str(out, n.as_str())?,
+        // the data is not in the document, so only the hash is exported
+        ScalarValue::BlobRef(b) => write_str(out, &b.hash.to_string())?,
+        ScalarValue::Link(d) => write_str(out, &d.to_string())?,
+        ScalarValue::DateTime(d) => {
+            // msgpack timestamps have no offset, so only the instant is kept
+            let _ = encode::write_ext_meta(out, 12, TIMESTAMP_EXT);
+            out.extend_from_slice(&d.subsec_nanos().to_be_bytes());
+            out.extend_from_slice(&d.unix_seconds().to_be_bytes());
+        }
+        ScalarValue::Boolean(b) => {
+            let _ = encode::write_bool(out, *b);
+        }
+        ScalarValue::Unknown { type_code, bytes } => {
+            let _ = encode::write_ext_meta(out, len(bytes.len() + 1)?, UNKNOWN_EXT);
+            out.push(*type_code);
+            out.extend_from_slice(bytes);
+        }
+        ScalarValue::Null => {
+            let _ = encode::write_nil(out);
+        }
+    }
+    Ok(())
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) -> Result<(), MsgpackError> {
+    let _ = encode::write_str_len(out, len(s.len())?);
+    out.extend_from_slice(s.as_bytes());
+    Ok(())
+}
+
+fn len(len: usize) -> Result<u32, MsgpackError> {
+    u32::try_from(len).map_err(|_| MsgpackError::TooLong(len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::Transactable;
+    use crate::AutoCommit;
+
+    #[test]
+    fn scalar_types_are_written_as_extension_types() {
+        let mut doc = AutoCommit::new();
+        let list = doc.put_object(ROOT, "l", ObjType::List).unwrap();
+        doc.insert(&list, 0, ScalarValue::counter(3)).unwrap();
+        doc.insert(&list, 1, ScalarValue::Timestamp(-1)).unwrap();
+        doc.insert(&list, 2, ScalarValue::Uint(200)).unwrap();
+        doc.insert(&list, 3, -5).unwrap();
+        let text = doc.insert_object(&list, 4, ObjType::Text).unwrap();
+        doc.splice_text(&text, 0, 0, "hi").unwrap();
+        doc.increment(&list, 0, 2).unwrap();
+
+        let mut expected = vec![0x81, 0xa1, b'l', 0x95];
+        expected.extend([0xd7, COUNTER_EXT as u8, 0, 0, 0, 0, 0, 0, 0, 5]);
+        expected.extend([0xc7, 12, TIMESTAMP_EXT as u8]);
+        expected.extend(999_000_000u32.to_be_bytes());
+        expected.extend((-1i64).to_be_bytes());
+        expected.extend([0xcc, 200, 0xfb, 0xa2, b'h', b'i']);
+        assert_eq!(to_msgpack(&doc).unwrap(), expected);
+    }
+}
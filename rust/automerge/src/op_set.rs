@@ -1,3 +1,4 @@
+use crate::chunked_bytes::SegmentStore;
 use crate::clock::Clock;
 use crate::error::AutomergeError;
 use crate::exid::ExId;
@@ -16,7 +17,7 @@ use crate::text_value::TextValue;
 use crate::types::{
     self, ActorId, Export, Exportable, Key, ListEncoding, ObjId, ObjMeta, OpId, OpIds, OpType, Prop,
 };
-use crate::ObjType;
+use crate::{ObjType, ScalarValue};
 use fxhash::FxBuildHasher;
 use std::borrow::Borrow;
 use std::cmp::Ordering;
@@ -24,13 +25,16 @@ use std::collections::HashMap;
 use std::ops::RangeBounds;
 use std::sync::Arc;
 
+mod cow;
 mod op;
 
+use cow::{ChunkedVec, Shared};
+
 pub(crate) use op::{Op, OpBuilder, OpDepIdx, OpDepRaw, OpIdx, OpRaw};
 
 pub(crate) type OpSet = OpSetInternal;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub(crate) struct OpIdxRange {
     start: u32,
     end: u32,
@@ -40,12 +44,24 @@ impl OpIdxRange {
     pub(crate) fn len(&self) -> usize {
         (self.end - self.start) as usize
     }
+
+    pub(crate) fn contains(&self, idx: OpIdx) -> bool {
+        (self.start as usize..self.end as usize).contains(&idx.get())
+    }
+
+    /// The ops in this range which are not in `earlier`, a range with the same start
+    pub(crate) fn since(&self, earlier: OpIdxRange) -> OpIdxRange {
+        OpIdxRange {
+            start: earlier.end,
+            end: self.end,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct OpSetInternal {
     /// The map of objects to their type and ops.
-    trees: HashMap<ObjId, OpTree, FxBuildHasher>,
+    trees: HashMap<ObjId, Shared<OpTree>, FxBuildHasher>,
     /// The number of operations in the opset.
     length: usize,
     /// Metadata about the operations in this opset.
@@ -55,7 +71,7 @@ pub(crate) struct OpSetInternal {
 impl OpSetInternal {
     pub(crate) fn from_actors(actors: Vec<ActorId>) -> Self {
         let mut trees: HashMap<_, _, _> = Default::default();
-        trees.insert(ObjId::root(), OpTree::new(ObjType::Map));
+        trees.insert(ObjId::root(), OpTree::new(ObjType::Map).into());
         OpSetInternal {
             trees,
             length: 0,
@@ -65,15 +81,16 @@ impl OpSetInternal {
 
     pub(crate) fn new() -> Self {
         let mut trees: HashMap<_, _, _> = Default::default();
-        trees.insert(ObjId::root(), OpTree::new(ObjType::Map));
+        trees.insert(ObjId::root(), OpTree::new(ObjType::Map).into());
         OpSetInternal {
             trees,
             length: 0,
             osd: OpSetData {
                 actors: IndexedCache::new(),
                 props: IndexedCache::new(),
-                ops: Vec::new(),
-                op_deps: Vec::new(),
+                ops: ChunkedVec::new(),
+                op_deps: ChunkedVec::new(),
+                segments: SegmentStore::default(),
             },
         }
     }
@@ -91,7 +108,11 @@ impl OpSetInternal {
     }
 
     pub(crate) fn iter(&self) -> Iter<'_> {
-        let mut objs: Vec<_> = self.trees.iter().map(|t| (t.0, t.1.objtype, t.1)).collect();
+        let mut objs: Vec<_> = self
+            .trees
+            .iter()
+            .map(|t| (t.0, t.1.objtype, &**t.1))
+            .collect();
         objs.sort_by(|a, b| self.osd.lamport_cmp((a.0).0, (b.0).0));
         Iter {
             opset: self,
@@ -105,13 +126,29 @@ impl OpSetInternal {
         self.trees.get(obj).map(|t| t.iter())
     }
 
+    /// Structured diagnostics about the trees of `objects`, or of every object
+    pub(crate) fn optree_stats(&self, objects: Option<Vec<ObjId>>) -> op_tree::OpTreeStats {
+        let mut trees: Vec<_> = self
+            .trees
+            .iter()
+            .filter(|(obj, _)| objects.as_ref().map_or(true, |os| os.contains(obj)))
+            .collect();
+        trees.sort_by(|a, b| self.osd.lamport_cmp((a.0).0, (b.0).0));
+        op_tree::OpTreeStats::new(
+            trees
+                .into_iter()
+                .map(|(obj, tree)| op_tree::ObjTreeStats::new(self.id_to_exid(obj.0), tree))
+                .collect(),
+        )
+    }
+
     /// Iterate over objects in the opset in causal order
     pub(crate) fn iter_objs(&self) -> impl Iterator<Item = (ObjMeta, OpTreeIter<'_>)> + '_ {
         // TODO
         let mut objs: Vec<_> = self
             .trees
             .iter()
-            .map(|t| (ObjMeta::new(*t.0, t.1.objtype), t.1))
+            .map(|t| (ObjMeta::new(*t.0, t.1.objtype), &**t.1))
             .collect();
         objs.sort_by(|a, b| self.osd.lamport_cmp((a.0).id, (b.0).id));
         IterObjs {
@@ -195,13 +232,18 @@ impl OpSetInternal {
         encoding: ListEncoding,
         clock: Option<&Clock>,
     ) -> OpsFound<'a> {
-        self.trees
-            .get(obj)
-            .and_then(|tree| {
-                tree.internal
-                    .seek_ops_by_prop(&self.osd, prop, encoding, clock)
-            })
-            .unwrap_or_default()
+        let Some(tree) = self.trees.get(obj) else {
+            return OpsFound::default();
+        };
+        match (prop, clock) {
+            (Prop::Seq(index), Some(clock)) if tree.objtype.is_sequence() => tree
+                .historical_index(&self.osd, clock, encoding)
+                .seek(&tree.internal, &self.osd, index),
+            (prop, clock) => tree
+                .internal
+                .seek_ops_by_prop(&self.osd, prop, encoding, clock)
+                .unwrap_or_default(),
+        }
     }
 
     pub(crate) fn op_iter<'a>(&'a self, obj: &ObjId) -> Option<OpIter<'a>> {
@@ -260,15 +302,40 @@ impl OpSetInternal {
         }
     }
 
+    /// The number of visible ops in `obj` with the key `key`
+    ///
+    /// The ops with the same key are contiguous, `pos` must be the position of one of them or
+    /// the position one of them would be inserted at.
+    pub(crate) fn visible_at_key(&self, obj: &ObjId, pos: usize, key: Key) -> usize {
+        let Some(tree) = self.trees.get(obj) else {
+            return 0;
+        };
+        let op_at = |i: usize| {
+            tree.internal
+                .get(i)
+                .map(|idx| idx.as_op(&self.osd))
+                .filter(|op| op.elemid_or_key() == key)
+        };
+        let before = (0..pos).rev().map(op_at).take_while(Option::is_some);
+        let after = (pos..).map(op_at).take_while(Option::is_some);
+        before
+            .chain(after)
+            .flatten()
+            .filter(|op| op.visible())
+            .count()
+    }
+
     /// Add `op` as a successor to each op at `op_indices` in `obj`
     pub(crate) fn add_succ(&mut self, obj: &ObjId, op_indices: &[usize], op: OpIdx) {
         if let Some(tree) = self.trees.get_mut(obj) {
             tree.last_insert = None;
+            tree.historical.clear();
             for i in op_indices {
                 if let Some(idx) = tree.internal.get(*i) {
                     let old_vis = idx.as_op(&self.osd).visible();
                     self.osd.add_inc(idx, op);
                     self.osd.add_dep(idx, op);
+                    self.osd.clamp_counter(idx);
                     let new_vis = idx.as_op(&self.osd).visible();
                     tree.internal.update(
                         *i,
@@ -287,10 +354,12 @@ impl OpSetInternal {
     pub(crate) fn remove_succ(&mut self, obj: &ObjId, index: usize, op: OpIdx) {
         if let Some(tree) = self.trees.get_mut(obj) {
             tree.last_insert = None;
+            tree.historical.clear();
             if let Some(idx) = tree.internal.get(index) {
                 let old_vis = idx.as_op(&self.osd).visible();
                 self.osd.remove_inc(idx, op);
                 self.osd.remove_dep(idx, op);
+                self.osd.clamp_counter(idx);
                 let new_vis = idx.as_op(&self.osd).visible();
                 tree.internal.update(
                     index,
@@ -310,6 +379,7 @@ impl OpSetInternal {
         let tree = self.trees.get_mut(obj).unwrap();
         self.length -= 1;
         tree.last_insert = None;
+        tree.historical.clear();
         let idx = tree.internal.remove(index, &self.osd);
         let op = idx.as_op(&self.osd);
         if let OpType::Make(_) = op.action() {
@@ -341,17 +411,46 @@ impl OpSetInternal {
         }
     }
 
+    /// The position after the last op of the sequence `obj` and the key to insert after to
+    /// append to it
+    ///
+    /// The last op in a sequence always belongs to its last element, visible or not, so this
+    /// finds the end of the sequence without searching it.
+    pub(crate) fn end_of_seq(&self, obj: &ObjId) -> (usize, Key) {
+        let Some(tree) = self.trees.get(obj) else {
+            return (0, Key::Seq(types::HEAD));
+        };
+        let pos = tree.len();
+        let key = pos
+            .checked_sub(1)
+            .and_then(|last| tree.internal.get(last))
+            .map(|idx| idx.as_op(&self.osd).elemid_or_key())
+            .unwrap_or(Key::Seq(types::HEAD));
+        (pos, key)
+    }
+
     pub(crate) fn load(&mut self, obj: ObjId, op: OpBuilder) -> OpIdx {
         self.osd.push(obj, op)
     }
 
+    /// Add an op created by a local transaction whose ops are `range` and which sees the ops
+    /// covered by `scope`
     pub(crate) fn load_with_range(
         &mut self,
         obj: ObjId,
-        op: OpBuilder,
+        mut op: OpBuilder,
         range: &mut OpIdxRange,
+        scope: Option<&Clock>,
     ) -> OpIdx {
-        let idx = self.osd.push(obj, op);
+        let osd = &mut self.osd;
+        let ops = &osd.ops;
+        let visible = |idx: OpIdx| match scope {
+            Some(clock) => range.contains(idx) || clock.covers(&ops[idx.get()].op.id),
+            None => true,
+        };
+        osd.segments
+            .add_local(OpIdx::new(ops.len()), &mut op, visible);
+        let idx = osd.push_unchecked(obj, op);
         range.end += 1;
         assert!(idx.get() >= range.start as usize && idx.get() < range.end as usize);
         idx
@@ -376,12 +475,15 @@ impl OpSetInternal {
                     objtype: *typ,
                     last_insert: None,
                     parent: Some(idx),
-                },
+                    historical: Default::default(),
+                }
+                .into(),
             );
         }
 
         if let Some(tree) = self.trees.get_mut(obj) {
             tree.last_insert = None;
+            tree.historical.clear();
             tree.internal.insert(index, idx, &self.osd);
             self.length += 1;
         } else {
@@ -399,13 +501,17 @@ impl OpSetInternal {
                     objtype: *typ,
                     last_insert: None,
                     parent: Some(idx),
-                },
+                    historical: Default::default(),
+                }
+                .into(),
             );
         }
 
         if let Some(tree) = self.trees.get_mut(obj) {
             tree.last_insert = None;
-            tree.internal.insert(tree.len(), idx, &self.osd);
+            tree.historical.clear();
+            let index = tree.len();
+            tree.internal.insert(index, idx, &self.osd);
             self.length += 1;
             Ok(())
         } else {
@@ -430,16 +536,13 @@ impl OpSetInternal {
     ///            visualised
     #[cfg(feature = "optree-visualisation")]
     pub(crate) fn visualise(&self, objects: Option<Vec<ObjId>>) -> String {
-        use std::borrow::Cow;
         let mut out = Vec::new();
-        let trees = if let Some(objects) = objects {
-            let mut filtered = self.trees.clone();
-            filtered.retain(|k, _| objects.contains(k));
-            Cow::Owned(filtered)
-        } else {
-            Cow::Borrowed(&self.trees)
-        };
-        let graph = super::visualisation::GraphVisualisation::construct(&trees, &self.osd);
+        let trees = self
+            .trees
+            .iter()
+            .filter(|(k, _)| objects.as_ref().map_or(true, |o| o.contains(k)))
+            .map(|(k, tree)| (k, &**tree));
+        let graph = super::visualisation::GraphVisualisation::construct(trees, &self.osd);
         dot::render(&graph, &mut out).unwrap();
         String::from_utf8_lossy(&out[..]).to_string()
     }
@@ -454,6 +557,10 @@ impl OpSetInternal {
             match (&clock, tree.index(encoding)) {
                 // no clock and a clean index? - use it
                 (None, Some(index)) => index.visible_len(encoding),
+                // a sequence at some other heads - use the index for those heads
+                (Some(clock), _) if tree.objtype.is_sequence() => {
+                    tree.historical_index(&self.osd, clock, encoding).len()
+                }
                 // do it the hard way - walk each op
                 _ => self
                     .top_ops(obj, clock)
@@ -471,9 +578,9 @@ impl OpSetInternal {
     }
 
     pub(crate) fn keys<'a>(&'a self, obj: &ObjId, clock: Option<Clock>) -> Keys<'a> {
-        Keys {
-            iter: Some((self.top_ops(obj, clock), self)),
-        }
+        self.op_iter(obj)
+            .map(|iter| Keys::new(iter, self, *obj, clock))
+            .unwrap_or_default()
     }
 
     pub(crate) fn list_range<R: RangeBounds<usize>>(
@@ -574,8 +681,10 @@ impl<'a> ExactSizeIterator for Iter<'a> {
 pub(crate) struct OpSetData {
     pub(crate) actors: IndexedCache<ActorId>,
     pub(crate) props: IndexedCache<String>,
-    ops: Vec<OpRaw>,
-    op_deps: Vec<OpDepRaw>,
+    ops: ChunkedVec<OpRaw>,
+    op_deps: ChunkedVec<OpDepRaw>,
+    /// The contents of the segments of every [`crate::ChunkedBytes`] in the document
+    pub(crate) segments: SegmentStore,
 }
 
 impl Default for OpSetData {
@@ -583,8 +692,9 @@ impl Default for OpSetData {
         Self {
             actors: IndexedCache::new(),
             props: IndexedCache::new(),
-            ops: Vec::new(),
-            op_deps: Vec::new(),
+            ops: ChunkedVec::new(),
+            op_deps: ChunkedVec::new(),
+            segments: SegmentStore::default(),
         }
     }
 }
@@ -605,6 +715,16 @@ impl<'a> Iterator for OpIter<'a> {
     fn nth(&mut self, n: usize) -> Option<Self::Item> {
         self.iter.nth(n).map(|idx| idx.as_op(self.osd))
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for OpIter<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(|idx| idx.as_op(self.osd))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -668,6 +788,22 @@ impl OpSetData {
         }
     }
 
+    /// The range of `start`, which was created with [`Self::start_range()`], and every op added
+    /// since
+    pub(crate) fn extend_range(&self, start: OpIdxRange) -> OpIdxRange {
+        OpIdxRange {
+            start: start.start,
+            end: self.ops.len() as u32,
+        }
+    }
+
+    /// Forget the ops in `range`, which must be the last ops added and must already have been
+    /// removed from the op trees with [`crate::Automerge::remove_ops()`]
+    pub(crate) fn truncate(&mut self, range: OpIdxRange) {
+        debug_assert_eq!(range.end as usize, self.ops.len());
+        self.ops.truncate(range.start as usize);
+    }
+
     pub(crate) fn get_ops(&self, range: OpIdxRange) -> ChangeOpIter<'_> {
         ChangeOpIter::new(self, range)
     }
@@ -693,6 +829,26 @@ impl OpSetData {
         }
 
         self.add_dep(pred, succ);
+        if inc.is_some() {
+            self.clamp_counter(pred);
+        }
+    }
+
+    /// Recalculate the value of `counter` if it is a bounded counter
+    ///
+    /// The value of a bounded counter depends on the order its increments are applied in, so
+    /// it cannot be updated as increments are added and removed like other counters.
+    pub(crate) fn clamp_counter(&mut self, counter: OpIdx) {
+        let op = counter.as_op(self);
+        let OpType::Put(ScalarValue::Counter(c)) = op.action() else {
+            return;
+        };
+        if c.is_bounded() {
+            let value = op.counter_at(c, None);
+            if let OpType::Put(ScalarValue::Counter(c)) = &mut self.ops[counter.get()].op.action {
+                c.current = value;
+            }
+        }
     }
 
     pub(crate) fn remove_dep(&mut self, pred: OpIdx, succ: OpIdx) {
@@ -798,7 +954,28 @@ impl OpSetData {
         self.op_deps.push(op_dep);
     }
 
-    pub(crate) fn push(&mut self, obj: ObjId, op: OpBuilder) -> OpIdx {
+    pub(crate) fn push(&mut self, obj: ObjId, mut op: OpBuilder) -> OpIdx {
+        self.segments.add(OpIdx::new(self.ops.len()), &mut op);
+        self.push_unchecked(obj, op)
+    }
+
+    /// Fill in the contents of segments which ops refer to before the op carrying them was
+    /// pushed, which happens when loading a document as its ops are not in causal order
+    pub(crate) fn resolve_segments(&mut self) {
+        if !self.segments.take_unresolved() {
+            return;
+        }
+        for (index, raw) in self.ops.iter_mut().enumerate() {
+            if let OpType::Put(ScalarValue::Chunked(value)) = &raw.op.action {
+                if value.segments().any(|s| s.is_none()) {
+                    self.segments.add(OpIdx::new(index), &mut raw.op);
+                }
+            }
+        }
+    }
+
+    /// Push an op whose segments have already been added to [`Self::segments`]
+    fn push_unchecked(&mut self, obj: ObjId, op: OpBuilder) -> OpIdx {
         let index = self.ops.len();
         //log!("push idx={:?} op={:?}", index, op);
         let width = TextValue::width(op.to_str()) as u32; // TODO faster
@@ -818,8 +995,9 @@ impl OpSetData {
         Self {
             props: IndexedCache::new(),
             actors: actors.into_iter().collect(),
-            ops: Vec::new(),
-            op_deps: Vec::new(),
+            ops: ChunkedVec::new(),
+            op_deps: ChunkedVec::new(),
+            segments: SegmentStore::default(),
         }
     }
 
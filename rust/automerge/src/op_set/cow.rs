@@ -0,0 +1,136 @@
+//! Copy on write containers which let forks of a document share their op set
+//!
+//! Cloning either container is cheap: the contents are reference counted and only copied when
+//! one of the clones is mutated, so a fork only pays for the parts of the op set it changes.
+
+use std::ops::{Deref, DerefMut, Index, IndexMut};
+use std::sync::Arc;
+
+/// A value which is shared between clones until one of them takes a mutable reference to it
+#[derive(Debug, Clone)]
+pub(crate) struct Shared<T>(Arc<T>);
+
+impl<T> From<T> for Shared<T> {
+    fn from(value: T) -> Self {
+        Self(Arc::new(value))
+    }
+}
+
+impl<T> Deref for Shared<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Clone> DerefMut for Shared<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        Arc::make_mut(&mut self.0)
+    }
+}
+
+/// The number of elements in each chunk of a [`ChunkedVec`]
+const CHUNK_SIZE: usize = 1024;
+
+/// A vector stored as fixed size chunks which are shared between clones
+///
+/// Mutating an element copies only the chunk containing it (if that chunk is shared), so two
+/// clones which each push and update a few elements share everything else.
+#[derive(Debug, Clone)]
+pub(crate) struct ChunkedVec<T> {
+    chunks: Vec<Arc<Vec<T>>>,
+    len: usize,
+}
+
+impl<T> Default for ChunkedVec<T> {
+    fn default() -> Self {
+        Self {
+            chunks: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<T: Clone> ChunkedVec<T> {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn push(&mut self, value: T) {
+        if self.len % CHUNK_SIZE == 0 {
+            self.chunks.push(Arc::new(Vec::with_capacity(CHUNK_SIZE)));
+        }
+        // there is always a last chunk with room here
+        if let Some(last) = self.chunks.last_mut() {
+            Arc::make_mut(last).push(value);
+        }
+        self.len += 1;
+    }
+
+    pub(crate) fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        self.chunks.truncate(len.div_ceil(CHUNK_SIZE));
+        if len % CHUNK_SIZE != 0 {
+            if let Some(last) = self.chunks.last_mut() {
+                Arc::make_mut(last).truncate(len % CHUNK_SIZE);
+            }
+        }
+        self.len = len;
+    }
+
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.chunks
+            .iter_mut()
+            .flat_map(|chunk| Arc::make_mut(chunk).iter_mut())
+    }
+}
+
+impl<T> Index<usize> for ChunkedVec<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        assert!(index < self.len, "index {} out of bounds", index);
+        &self.chunks[index / CHUNK_SIZE][index % CHUNK_SIZE]
+    }
+}
+
+impl<T: Clone> IndexMut<usize> for ChunkedVec<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        assert!(index < self.len, "index {} out of bounds", index);
+        &mut Arc::make_mut(&mut self.chunks[index / CHUNK_SIZE])[index % CHUNK_SIZE]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_of_a_chunked_vec_share_unchanged_chunks() {
+        let mut a = ChunkedVec::new();
+        for i in 0..(CHUNK_SIZE * 3 + 5) {
+            a.push(i);
+        }
+        let mut b = a.clone();
+        b[1] = 42;
+        b.push(7);
+        assert_eq!(a[1], 1);
+        assert_eq!(b[1], 42);
+        assert_eq!(a.len() + 1, b.len());
+        assert!(!Arc::ptr_eq(&a.chunks[0], &b.chunks[0]));
+        assert!(Arc::ptr_eq(&a.chunks[1], &b.chunks[1]));
+        assert!(Arc::ptr_eq(&a.chunks[2], &b.chunks[2]));
+
+        b.truncate(CHUNK_SIZE + 1);
+        assert_eq!(b.len(), CHUNK_SIZE + 1);
+        assert_eq!(b.iter_mut().count(), CHUNK_SIZE + 1);
+        assert_eq!(a[CHUNK_SIZE * 3 + 4], CHUNK_SIZE * 3 + 4);
+    }
+}
@@ -2,7 +2,7 @@ use crate::clock::Clock;
 use crate::exid::ExId;
 use crate::op_set::OpSetData;
 use crate::types::{self, ActorId, ElemId, Key, ListEncoding, ObjId, OpId, OpType, Prop};
-use crate::value::{ScalarValue, Value};
+use crate::value::{Counter, ScalarValue, Value, ValueRef};
 use std::borrow::Cow;
 use std::cmp::Ordering;
 
@@ -171,6 +171,10 @@ impl<'a> Op<'a> {
         if let Some(clock) = clock {
             if self.is_inc() || self.is_mark() {
                 false
+            } else if self.is_counter() {
+                // incrementing a counter doesn't hide it, like in `visible()`
+                clock.covers(&self.op().id)
+                    && self.succ().all(|op| op.is_inc() || !clock.covers(op.id()))
             } else {
                 clock.covers(&self.op().id) && !self.succ().any(|i| clock.covers(i.id()))
             }
@@ -245,27 +249,67 @@ impl<'a> Op<'a> {
         self.op().value()
     }
 
-    pub(crate) fn inc_at(&self, clock: &Clock) -> i64 {
-        self.succ()
-            .filter_map(|o| {
-                if clock.covers(o.id()) {
-                    o.op().get_increment_value()
-                } else {
-                    None
-                }
-            })
-            .sum()
+    /// The increments of this op which `clock` covers, or all of them if there is no clock, in
+    /// lamport order
+    fn increments(&self, clock: Option<&Clock>) -> Vec<(OpId, i64)> {
+        let mut incs = self
+            .succ()
+            .filter(|o| clock.map_or(true, |c| c.covers(o.id())))
+            .filter_map(|o| Some((*o.id(), o.get_increment_value()?)))
+            .collect::<Vec<_>>();
+        incs.sort_by(|(a, _), (b, _)| self.osd.lamport_cmp(*a, *b));
+        incs
+    }
+
+    /// The value of the counter this op puts after its increments, limited to those `clock`
+    /// covers if there is a clock
+    pub(crate) fn counter_at(&self, counter: &Counter, clock: Option<&Clock>) -> i64 {
+        counter.apply(self.increments(clock).into_iter().map(|(_, n)| n))
+    }
+
+    /// How much applying the increment `inc` changes the value of the counter this op puts
+    ///
+    /// This is the value of `inc` unless the counter is bounded, in which case it is the change
+    /// after clamping. `inc` may or may not already be a successor of this op.
+    pub(crate) fn increment_delta(&self, inc: Op<'_>) -> i64 {
+        let value = inc.get_increment_value().unwrap_or(0);
+        let counter = match &self.op().action {
+            OpType::Put(ScalarValue::Counter(c)) if c.is_bounded() => c,
+            _ => return value,
+        };
+        let mut incs = self.increments(None);
+        incs.retain(|(id, _)| id != inc.id());
+        let before = counter.apply(incs.iter().map(|(_, n)| *n));
+        let pos = incs
+            .binary_search_by(|(id, _)| self.osd.lamport_cmp(*id, *inc.id()))
+            .unwrap_or_else(|p| p);
+        incs.insert(pos, (*inc.id(), value));
+        counter.apply(incs.iter().map(|(_, n)| *n)) - before
     }
 
     pub(crate) fn value_at(&self, clock: Option<&Clock>) -> Value<'a> {
         if let Some(clock) = clock {
             if let OpType::Put(ScalarValue::Counter(c)) = &self.op().action {
-                return Value::counter(c.start + self.inc_at(clock));
+                let mut counter = c.clone();
+                counter.current = self.counter_at(c, Some(clock));
+                return Value::Scalar(Cow::Owned(ScalarValue::Counter(counter)));
             }
         }
         self.value()
     }
 
+    /// Like [`Self::value_at()`] but borrowing string and bytes data from the op
+    pub(crate) fn value_ref_at(&self, clock: Option<&Clock>) -> ValueRef<'a> {
+        match (&self.op().action, clock) {
+            (OpType::Make(obj_type), _) => ValueRef::Object(*obj_type),
+            (OpType::Put(ScalarValue::Counter(c)), Some(clock)) => {
+                ValueRef::Counter(self.counter_at(c, Some(clock)))
+            }
+            (OpType::Put(scalar), _) => ValueRef::from(scalar),
+            _ => panic!("cant convert op into a value - {:?}", self.op()),
+        }
+    }
+
     pub(crate) fn tagged_value(&self, clock: Option<&Clock>) -> (Value<'a>, ExId) {
         (self.value_at(clock), self.exid())
     }
@@ -15,13 +15,18 @@ use std::cmp::Ordering;
 use std::sync::Arc;
 use std::{fmt::Debug, mem};
 
+mod historical;
 mod iter;
 mod node;
+mod stats;
+
+use historical::{HistoricalIndex, HistoricalIndexes};
 
 pub(crate) use iter::{OpTreeIter, OpTreeOpIter};
 #[allow(unused)]
 pub(crate) use node::OpTreeNode;
 pub use node::B;
+pub use stats::{ObjTreeStats, OpTreeStats};
 
 #[derive(Debug, Clone)]
 pub(crate) struct OpTree {
@@ -34,6 +39,9 @@ pub(crate) struct OpTree {
     /// short circuit the query if the follow op is another
     /// insert or delete at the same spot
     pub(crate) last_insert: Option<LastInsert>,
+    /// Indexes of this sequence as at historical clocks, which have to be cleared whenever the
+    /// tree changes
+    pub(crate) historical: HistoricalIndexes,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -52,9 +60,20 @@ impl OpTree {
             objtype,
             parent: None,
             last_insert: None,
+            historical: HistoricalIndexes::default(),
         }
     }
 
+    /// The index of this sequence as at `clock`, see [`HistoricalIndex`]
+    pub(crate) fn historical_index(
+        &self,
+        osd: &OpSetData,
+        clock: &Clock,
+        encoding: ListEncoding,
+    ) -> Arc<HistoricalIndex> {
+        self.historical.get(&self.internal, osd, clock, encoding)
+    }
+
     pub(crate) fn iter(&self) -> OpTreeIter<'_> {
         self.internal.iter()
     }
@@ -163,10 +182,11 @@ impl<'a> FoundOpWithPatchLog<'a> {
                 }
                 _ => { /* do nothing */ }
             }
-        } else if let Some(value) = op.get_increment_value() {
+        } else if op.get_increment_value().is_some() {
             if self.after.is_none() {
                 if let Some(counter) = self.overwritten {
                     if pred.overwrites(counter.id()) {
+                        let value = counter.increment_delta(op);
                         patch_log.increment(obj.id, &key, value, *op.id());
                     }
                 }
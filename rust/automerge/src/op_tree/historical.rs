@@ -0,0 +1,148 @@
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use super::{OpSetData, OpTreeInternal, OpsFound};
+use crate::clock::Clock;
+use crate::types::ListEncoding;
+
+/// How many historical indexes a tree keeps at once
+const MAX_INDEXES: usize = 4;
+
+/// The visible elements of a sequence as at a clock
+///
+/// The cached indexes in the nodes of an op tree only describe the current state, so reads at
+/// other heads would have to walk every op. This records the position and width of each element
+/// which is visible at the clock once, so that `length` and `nth` at those heads are a binary
+/// search. It is only valid until the tree changes, see [`HistoricalIndexes::clear()`].
+#[derive(Debug)]
+pub(crate) struct HistoricalIndex {
+    clock: Clock,
+    encoding: ListEncoding,
+    /// The positions of the first op of each visible element and of the op after its last op,
+    /// and the total width of the elements up to and including it
+    elems: Vec<(usize, usize, usize)>,
+}
+
+impl HistoricalIndex {
+    fn new(tree: &OpTreeInternal, osd: &OpSetData, clock: &Clock, encoding: ListEncoding) -> Self {
+        let mut elems = Vec::new();
+        let mut total = 0;
+        let mut push = |start: usize, end: usize, width: Option<usize>| {
+            if let Some(width) = width {
+                total += width;
+                elems.push((start, end, total));
+            }
+        };
+        // the key and first position of the current element, and the width of its last visible
+        // op
+        let mut current = None;
+        for (pos, idx) in tree.iter().enumerate() {
+            let op = idx.as_op(osd);
+            let key = op.elemid_or_key();
+            if !matches!(current, Some((k, _, _)) if k == key) {
+                if let Some((_, start, width)) = current.take() {
+                    push(start, pos, width);
+                }
+                current = Some((key, pos, None));
+            }
+            if let Some((_, _, width)) = current.as_mut().filter(|_| op.visible_at(Some(clock))) {
+                *width = Some(op.width(encoding));
+            }
+        }
+        if let Some((_, start, width)) = current {
+            push(start, tree.len(), width);
+        }
+        Self {
+            clock: clock.clone(),
+            encoding,
+            elems,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.elems.last().map_or(0, |(_, _, total)| *total)
+    }
+
+    /// The ops of the element at `index`, as the `Nth` query would find them
+    pub(crate) fn seek<'a>(
+        &self,
+        tree: &OpTreeInternal,
+        osd: &'a OpSetData,
+        index: usize,
+    ) -> OpsFound<'a> {
+        let i = self.elems.partition_point(|(_, _, total)| *total <= index);
+        let Some(&(start, end, _)) = self.elems.get(i) else {
+            return OpsFound {
+                end_pos: tree.len(),
+                ..Default::default()
+            };
+        };
+        let mut found = OpsFound {
+            end_pos: end,
+            ..Default::default()
+        };
+        for pos in start..end {
+            let Some(op) = tree.get(pos).map(|idx| idx.as_op(osd)) else {
+                break;
+            };
+            if op.visible_at(Some(&self.clock)) {
+                found.ops.push(op);
+                found.ops_pos.push(pos);
+            }
+        }
+        found
+    }
+}
+
+/// The historical indexes of one op tree, which are built when they are first needed
+#[derive(Default)]
+pub(crate) struct HistoricalIndexes(Mutex<Vec<Arc<HistoricalIndex>>>);
+
+impl HistoricalIndexes {
+    /// The index of `tree` as at `clock`, building it if there isn't one
+    pub(crate) fn get(
+        &self,
+        tree: &OpTreeInternal,
+        osd: &OpSetData,
+        clock: &Clock,
+        encoding: ListEncoding,
+    ) -> Arc<HistoricalIndex> {
+        let mut indexes = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(i) = indexes
+            .iter()
+            .position(|index| index.encoding == encoding && index.clock == *clock)
+        {
+            // keep the most recently used index at the back
+            let index = indexes.remove(i);
+            indexes.push(index.clone());
+            return index;
+        }
+        if indexes.len() == MAX_INDEXES {
+            indexes.remove(0);
+        }
+        let index = Arc::new(HistoricalIndex::new(tree, osd, clock, encoding));
+        indexes.push(index.clone());
+        index
+    }
+
+    /// Forget every index, as the tree they were built from has changed
+    pub(crate) fn clear(&mut self) {
+        self.0
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
+
+impl Clone for HistoricalIndexes {
+    fn clone(&self) -> Self {
+        let indexes = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        Self(Mutex::new(indexes.clone()))
+    }
+}
+
+impl fmt::Debug for HistoricalIndexes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HistoricalIndexes").finish()
+    }
+}
@@ -4,14 +4,22 @@ use crate::op_set::OpIdx;
 
 use super::{Op, OpTreeInternal, OpTreeNode};
 
+/// Iterates over the ops in an op tree in order
+///
+/// Iterating forwards walks the tree in place. Iterating backwards looks up each element from the
+/// root, which is `O(log n)` per element.
 #[derive(Clone)]
-pub(crate) struct OpTreeIter<'a>(Inner<'a>);
+pub(crate) struct OpTreeIter<'a> {
+    inner: Inner<'a>,
+    /// One past the index of the next element to return from the back
+    back: usize,
+}
 
 impl<'a> OpTreeIter<'a> {
     pub(crate) fn new(tree: &'a OpTreeInternal) -> OpTreeIter<'a> {
-        Self(
-            tree.root_node
-                .as_ref()
+        let root = tree.root_node.as_ref();
+        Self {
+            inner: root
                 .map(|root| Inner::NonEmpty {
                     // This is a guess at the average depth of an OpTree
                     ancestors: Vec::with_capacity(6),
@@ -23,7 +31,18 @@ impl<'a> OpTreeIter<'a> {
                     root_node: root,
                 })
                 .unwrap_or(Inner::Empty),
-        )
+            back: root.map(|r| r.len()).unwrap_or(0),
+        }
+    }
+
+    /// The index of the next element to return from the front
+    fn front(&self) -> usize {
+        match &self.inner {
+            Inner::Empty => 0,
+            Inner::NonEmpty {
+                cumulative_index, ..
+            } => *cumulative_index,
+        }
     }
 }
 
@@ -31,14 +50,44 @@ impl<'a> Iterator for OpTreeIter<'a> {
     type Item = OpIdx;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.0.next()
+        if self.front() >= self.back {
+            return None;
+        }
+        self.inner.next()
     }
 
     fn nth(&mut self, n: usize) -> Option<Self::Item> {
-        self.0.nth(n)
+        // `Inner::nth` takes the index from the start of the tree
+        let index = self.front().saturating_add(n);
+        if index >= self.back {
+            self.inner = Inner::Empty;
+            self.back = 0;
+            return None;
+        }
+        self.inner.nth(index)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back.saturating_sub(self.front());
+        (len, Some(len))
+    }
+}
+
+impl<'a> DoubleEndedIterator for OpTreeIter<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.back <= self.front() {
+            return None;
+        }
+        self.back -= 1;
+        match &self.inner {
+            Inner::Empty => None,
+            Inner::NonEmpty { root_node, .. } => root_node.get(self.back),
+        }
     }
 }
 
+impl<'a> ExactSizeIterator for OpTreeIter<'a> {}
+
 #[derive(Clone)]
 enum Inner<'a> {
     Empty,
@@ -197,6 +246,7 @@ impl<'a> Iterator for Inner<'a> {
                     // we're in a leaf node and we kept track of the cumulative index as we went,
                     let index_in_this_node = n.saturating_sub(*cumulative_index);
                     current.index = index_in_this_node + 1;
+                    *cumulative_index = n + 1;
                     Some(current.node.elements[index_in_this_node])
                 }
             }
@@ -470,5 +520,39 @@ mod tests {
             let expected_tail = model_iter.cloned().collect::<Vec<_>>();
             assert_eq!(DebugOps(tail.as_slice()), DebugOps(expected_tail.as_slice()));
         }
+
+        #[test]
+        fn optree_iter_double_ended(model in nth_model()) {
+            let optree = make_optree(&model.actions, &model.osd);
+            let mut iter = super::OpTreeIter::new(&optree);
+            let mut model_iter = model.model.iter().cloned();
+            for _ in 0..model.n {
+                assert_eq!(model_iter.next(), iter.next());
+            }
+            assert_eq!(model_iter.len(), iter.len());
+
+            let reversed = iter.rev().collect::<Vec<_>>();
+            let expected_reversed = model_iter.rev().collect::<Vec<_>>();
+            assert_eq!(DebugOps(reversed.as_slice()), DebugOps(expected_reversed.as_slice()));
+        }
+
+        #[test]
+        fn optree_iter_mixed(model in nth_model(), steps in proptest::collection::vec(0..4_usize, 0..20)) {
+            let optree = make_optree(&model.actions, &model.osd);
+            let mut iter = super::OpTreeIter::new(&optree);
+            let mut model_iter = model.model.iter().cloned();
+            for step in steps {
+                match step {
+                    0 => assert_eq!(model_iter.next(), iter.next()),
+                    1 => assert_eq!(model_iter.next_back(), iter.next_back()),
+                    2 => assert_eq!(model_iter.nth(model.n / 2), iter.nth(model.n / 2)),
+                    _ => assert_eq!(model_iter.nth(model.n), iter.nth(model.n)),
+                }
+                assert_eq!(model_iter.len(), iter.len());
+            }
+            let rest = iter.collect::<Vec<_>>();
+            let expected_rest = model_iter.collect::<Vec<_>>();
+            assert_eq!(DebugOps(rest.as_slice()), DebugOps(expected_rest.as_slice()));
+        }
     }
 }
@@ -0,0 +1,101 @@
+use serde::Serialize;
+
+use super::{OpTree, OpTreeNode, B};
+use crate::exid::ExId;
+use crate::ObjType;
+
+/// The number of elements a node of an op tree can hold
+const CAPACITY: usize = 2 * B - 1;
+
+/// Structured diagnostics about the op trees of a document
+///
+/// This is returned by [`crate::Automerge::optree_stats()`]. It carries the same information
+/// as the Graphviz output of `visualise_optree`, summarised so that it can be serialized and
+/// asserted on.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpTreeStats {
+    /// The number of ops in all of the trees
+    pub ops: usize,
+    /// The number of nodes in all of the trees
+    pub nodes: usize,
+    /// The depth of the deepest tree
+    pub max_depth: usize,
+    /// One entry per object, in the order the objects were created
+    pub objects: Vec<ObjTreeStats>,
+}
+
+/// Diagnostics about the op tree of a single object
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjTreeStats {
+    pub obj: ExId,
+    // `ObjType` serializes as an untagged unit variant, which would be `null`
+    #[serde(serialize_with = "serialize_obj_type")]
+    pub obj_type: ObjType,
+    /// The number of ops in the object, including ops which are no longer visible
+    pub ops: usize,
+    /// The number of levels of nodes, zero for an object with no ops
+    pub depth: usize,
+    pub nodes: usize,
+    /// The number of nodes without children
+    pub leaves: usize,
+    /// The fewest ops held directly by any node
+    pub min_node_ops: usize,
+    /// The most ops held directly by any node
+    pub max_node_ops: usize,
+    /// The mean fraction of its capacity each node holds, between 0 and 1
+    pub occupancy: f64,
+}
+
+impl OpTreeStats {
+    pub(crate) fn new(objects: Vec<ObjTreeStats>) -> Self {
+        OpTreeStats {
+            ops: objects.iter().map(|o| o.ops).sum(),
+            nodes: objects.iter().map(|o| o.nodes).sum(),
+            max_depth: objects.iter().map(|o| o.depth).max().unwrap_or(0),
+            objects,
+        }
+    }
+}
+
+impl ObjTreeStats {
+    pub(crate) fn new(obj: ExId, tree: &OpTree) -> Self {
+        let mut stats = ObjTreeStats {
+            obj,
+            obj_type: tree.objtype,
+            ops: tree.internal.len(),
+            depth: 0,
+            nodes: 0,
+            leaves: 0,
+            min_node_ops: 0,
+            max_node_ops: 0,
+            occupancy: 0.0,
+        };
+        if let Some(root) = &tree.internal.root_node {
+            stats.min_node_ops = usize::MAX;
+            let mut filled = 0;
+            stats.visit(root, 1, &mut filled);
+            stats.occupancy = filled as f64 / (stats.nodes * CAPACITY) as f64;
+        }
+        stats
+    }
+
+    fn visit(&mut self, node: &OpTreeNode, depth: usize, filled: &mut usize) {
+        self.nodes += 1;
+        self.depth = self.depth.max(depth);
+        self.min_node_ops = self.min_node_ops.min(node.elements.len());
+        self.max_node_ops = self.max_node_ops.max(node.elements.len());
+        *filled += node.elements.len();
+        if node.children.is_empty() {
+            self.leaves += 1;
+        }
+        for child in &node.children {
+            self.visit(child, depth + 1, filled);
+        }
+    }
+}
+
+fn serialize_obj_type<S: serde::Serializer>(typ: &ObjType, s: S) -> Result<S::Ok, S::Error> {
+    s.collect_str(typ)
+}
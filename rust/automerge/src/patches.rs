@@ -1,9 +1,23 @@
+mod coalescing;
+mod filter;
+mod json_patch;
+mod observer;
 mod patch;
 mod patch_builder;
 mod patch_log;
+mod patch_target;
+mod recorder;
+mod text_units;
+pub use coalescing::CoalescingObserver;
+pub use filter::{ActionKind, PatchFilter};
+pub use json_patch::{to_json_patch, JsonPatchOp};
+pub use observer::{FilteredObserver, PatchObserver};
 pub use patch::{Patch, PatchAction};
 pub(crate) use patch_builder::PatchBuilder;
 pub use patch_log::PatchLog;
+pub use patch_target::{apply_patches, ApplyPatchError, PatchTarget};
+pub use recorder::{RecordedPatches, Recorder, ReplayError, Replayer};
+pub use text_units::TextUnitTranslator;
 
 use crate::{types::ListEncoding, ObjType};
 
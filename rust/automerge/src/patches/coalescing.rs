@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+use crate::{ObjId, Prop};
+
+use super::{Patch, PatchAction, PatchObserver};
+
+/// A property of an object which patches can change. Every index of a sequence is the same
+/// property, as inserting or deleting at one index moves the others.
+type Slot = (ObjId, Option<String>);
+
+/// A [`PatchObserver`] which merges patches that say the same thing in fewer patches
+///
+/// Applying changes made by someone typing produces a patch for every character they typed.
+/// Consumers which only want to know the end result, such as a UI which is redrawn once per
+/// frame, can collect patches with a `CoalescingObserver` instead of a `Vec<Patch>`. The
+/// patches it returns make the same changes to a copy of the document as the patches it
+/// observed, but:
+///
+/// * a put to a key or an index replaces the previous put to it
+/// * deleting elements or characters which a previous patch inserted removes them from that patch,
+///   and removes the patch if nothing is left
+/// * inserting elements or text within or next to those inserted by a previous patch adds them to
+///   that patch, as long as text has the same marks
+/// * deleting next to a previous delete extends it
+///
+/// Patches are only merged if no patch in between them changed the same object, or the object
+/// or property they are in. The patches are returned in the order of the first patch each of
+/// them was merged into.
+///
+/// # Example
+///
+/// ```
+/// # use automerge::{Automerge, ObjType, ROOT};
+/// # use automerge::patches::{CoalescingObserver, PatchAction};
+/// # use automerge::transaction::Transactable;
+/// let mut doc = Automerge::new();
+/// let text = doc.transact(|tx| tx.put_object(ROOT, "text", ObjType::Text)).unwrap().result;
+///
+/// let mut other = doc.fork();
+/// for (index, ch) in "hello".chars().enumerate() {
+///     other
+///         .transact(|tx| tx.splice_text(&text, index, 0, &ch.to_string()))
+///         .unwrap();
+/// }
+///
+/// let mut observer = CoalescingObserver::new();
+/// doc.merge_with(&other, &mut observer).unwrap();
+/// let patches = observer.take_patches();
+/// assert_eq!(patches.len(), 1);
+/// assert!(matches!(
+///     &patches[0].action,
+///     PatchAction::SpliceText { index: 0, value, .. } if value.make_string() == "hello"
+/// ));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CoalescingObserver {
+    /// The patches so far, with `None` in place of patches which were merged away
+    patches: Vec<Option<Patch>>,
+    /// The index in `patches` of the last patch which touched each slot
+    last_touched: HashMap<Slot, usize>,
+}
+
+/// What happened when merging a patch into an earlier one
+enum Merge {
+    /// The earlier patch now includes the later one
+    Merged,
+    /// The later patch undid the earlier one, which should be removed
+    Cancelled,
+    /// The later patch replaces the earlier one
+    Replaced,
+    /// The patches can't be merged
+    Unmerged,
+}
+
+impl CoalescingObserver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Coalesce `patches`, for example the patches from [`super::PatchLog`]
+    pub fn coalesce(patches: &[Patch]) -> Vec<Patch> {
+        let mut observer = Self::new();
+        observer.observe(patches);
+        observer.take_patches()
+    }
+
+    /// The patches observed so far, coalesced
+    pub fn patches(&self) -> impl Iterator<Item = &Patch> {
+        self.patches.iter().flatten()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patches().next().is_none()
+    }
+
+    /// Take the patches observed so far, coalesced
+    ///
+    /// Patches observed after this are not merged into the ones returned.
+    pub fn take_patches(&mut self) -> Vec<Patch> {
+        self.last_touched.clear();
+        std::mem::take(&mut self.patches)
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    fn push(&mut self, patch: &Patch) {
+        let slot = action_slot(patch);
+        if let Some(&previous) = self.last_touched.get(&slot) {
+            // the path to the object must not have changed since the earlier patch
+            let same_path = patch.path.iter().all(|(parent, prop)| {
+                self.last_touched.get(&prop_slot(parent, prop)) == Some(&previous)
+            });
+            let merge = match self.patches[previous].as_mut() {
+                Some(earlier) if same_path => merge(&mut earlier.action, &patch.action),
+                _ => Merge::Unmerged,
+            };
+            match merge {
+                Merge::Merged => return,
+                Merge::Cancelled => {
+                    self.patches[previous] = None;
+                    return;
+                }
+                Merge::Replaced => self.patches[previous] = None,
+                Merge::Unmerged => {}
+            }
+        }
+
+        let index = self.patches.len();
+        for (parent, prop) in &patch.path {
+            self.last_touched.insert(prop_slot(parent, prop), index);
+        }
+        self.last_touched.insert(slot, index);
+        self.patches.push(Some(patch.clone()));
+    }
+}
+
+impl PatchObserver for CoalescingObserver {
+    fn observe(&mut self, patches: &[Patch]) {
+        for patch in patches {
+            self.push(patch);
+        }
+    }
+}
+
+fn prop_slot(obj: &ObjId, prop: &Prop) -> Slot {
+    match prop {
+        Prop::Map(key) => (obj.clone(), Some(key.clone())),
+        Prop::Seq(_) => (obj.clone(), None),
+    }
+}
+
+fn action_slot(patch: &Patch) -> Slot {
+    match patch.action.prop() {
+        Some(prop) => prop_slot(&patch.obj, &prop),
+        None => (patch.obj.clone(), None),
+    }
+}
+
+fn merge(earlier: &mut PatchAction, later: &PatchAction) -> Merge {
+    match (earlier, later) {
+        (PatchAction::PutMap { key: k0, .. }, PatchAction::PutMap { key, .. }) if k0 == key => {
+            Merge::Replaced
+        }
+        (PatchAction::PutSeq { index: i0, .. }, PatchAction::PutSeq { index, .. })
+            if i0 == index =>
+        {
+            Merge::Replaced
+        }
+        (
+            PatchAction::Insert { index: i0, values },
+            PatchAction::Insert {
+                index,
+                values: inserted,
+            },
+        ) if *i0 <= *index && *index <= *i0 + values.len() => {
+            for (offset, value) in inserted.iter().enumerate() {
+                values.insert(index - *i0 + offset, value.clone());
+            }
+            Merge::Merged
+        }
+        (PatchAction::Insert { index: i0, values }, PatchAction::DeleteSeq { index, length })
+            if *i0 <= *index && index + length <= *i0 + values.len() =>
+        {
+            for _ in 0..*length {
+                values.remove(index - *i0);
+            }
+            if values.len() == 0 {
+                Merge::Cancelled
+            } else {
+                Merge::Merged
+            }
+        }
+        (
+            PatchAction::SpliceText {
+                index: i0,
+                value: text,
+                marks: m0,
+            },
+            PatchAction::SpliceText {
+                index,
+                value,
+                marks,
+            },
+        ) if m0 == marks && *i0 <= *index && *index <= *i0 + text.len() => {
+            text.splice_text_value(index - *i0, value);
+            Merge::Merged
+        }
+        (
+            PatchAction::SpliceText {
+                index: i0,
+                value: text,
+                ..
+            },
+            PatchAction::DeleteSeq { index, length },
+        ) if *i0 <= *index && index + length <= *i0 + text.len() => {
+            for _ in 0..*length {
+                text.remove(index - *i0);
+            }
+            if text.len() == 0 {
+                Merge::Cancelled
+            } else {
+                Merge::Merged
+            }
+        }
+        (
+            PatchAction::DeleteSeq {
+                index: i0,
+                length: l0,
+            },
+            PatchAction::DeleteSeq { index, length },
+        ) => {
+            if index == i0 {
+                // deleting forwards
+                *l0 += length;
+                Merge::Merged
+            } else if index + length == *i0 {
+                // deleting backwards
+                *i0 = *index;
+                *l0 += length;
+                Merge::Merged
+            } else {
+                Merge::Unmerged
+            }
+        }
+        _ => Merge::Unmerged,
+    }
+}
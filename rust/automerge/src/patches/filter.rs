@@ -0,0 +1,198 @@
+use std::collections::HashSet;
+
+use crate::path::{parse_pointer, token_matches};
+use crate::{AutomergeError, ObjId, Prop};
+
+use super::{Patch, PatchAction};
+
+/// The kinds of [`PatchAction`] a [`PatchFilter`] can select
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ActionKind {
+    /// [`PatchAction::PutMap`] and [`PatchAction::PutSeq`]
+    Put,
+    /// [`PatchAction::Insert`]
+    Insert,
+    /// [`PatchAction::SpliceText`]
+    SpliceText,
+    /// [`PatchAction::Increment`]
+    Increment,
+    /// [`PatchAction::Conflict`]
+    Conflict,
+    /// [`PatchAction::DeleteMap`] and [`PatchAction::DeleteSeq`]
+    Delete,
+    /// [`PatchAction::Mark`]
+    Mark,
+    /// [`PatchAction::Splice`]
+    Splice,
+}
+
+impl PatchAction {
+    /// The kind of this action
+    pub fn kind(&self) -> ActionKind {
+        match self {
+            Self::PutMap { .. } | Self::PutSeq { .. } => ActionKind::Put,
+            Self::Insert { .. } => ActionKind::Insert,
+            Self::SpliceText { .. } => ActionKind::SpliceText,
+            Self::Increment { .. } => ActionKind::Increment,
+            Self::Conflict { .. } => ActionKind::Conflict,
+            Self::DeleteMap { .. } | Self::DeleteSeq { .. } => ActionKind::Delete,
+            Self::Mark { .. } => ActionKind::Mark,
+            Self::Splice { .. } => ActionKind::Splice,
+        }
+    }
+
+    /// The property of the patched object this action changes, if it changes a single one
+    ///
+    /// For actions which change a range of a sequence this is the index of the start of the
+    /// range. [`PatchAction::Mark`] has no property.
+    pub fn prop(&self) -> Option<Prop> {
+        match self {
+            Self::PutMap { key, .. } | Self::DeleteMap { key } => Some(Prop::Map(key.clone())),
+            Self::PutSeq { index, .. }
+            | Self::Insert { index, .. }
+            | Self::SpliceText { index, .. }
+            | Self::DeleteSeq { index, .. }
+            | Self::Splice { index, .. } => Some(Prop::Seq(*index)),
+            Self::Increment { prop, .. } | Self::Conflict { prop } => Some(prop.clone()),
+            Self::Mark { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Exact(String),
+    One,
+    Any,
+}
+
+/// Selects patches by the path they change and the kind of their action
+///
+/// A path pattern is a JSON pointer (RFC 6901) whose reference tokens are matched against map
+/// keys and list indexes as by [`crate::ReadDoc::resolve_pointer()`]. `*` matches
+/// exactly one segment and `**` matches any number of segments, including none. A patch is
+/// matched against the path of the property it changes, which is the [`Patch::path`] of the
+/// patched object followed by [`PatchAction::prop()`]. A [`PatchAction::Mark`] is matched
+/// against the path of the text object itself.
+///
+/// # Example
+///
+/// ```
+/// # use automerge::{AutoCommit, ObjType, ROOT};
+/// # use automerge::transaction::Transactable;
+/// # use automerge::patches::{ActionKind::*, PatchFilter};
+/// let mut doc = AutoCommit::new();
+/// let todos = doc.put_object(ROOT, "todos", ObjType::Map).unwrap();
+/// doc.put(&todos, "shopping", false).unwrap();
+/// doc.put(ROOT, "title", "my list").unwrap();
+///
+/// let filter = PatchFilter::path("/todos/*").unwrap().actions([Put, Delete]);
+/// let patches = filter.apply(doc.diff_incremental());
+/// assert_eq!(patches.len(), 1);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PatchFilter {
+    paths: Vec<Vec<Segment>>,
+    objects: Vec<ObjId>,
+    actions: Option<HashSet<ActionKind>>,
+}
+
+impl PatchFilter {
+    /// A filter which matches every patch
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A filter which matches patches to properties whose path matches `pattern`
+    ///
+    /// Returns [`AutomergeError::InvalidPath`] if `pattern` is not a JSON pointer.
+    pub fn path(pattern: &str) -> Result<Self, AutomergeError> {
+        Self::new().or_path(pattern)
+    }
+
+    /// Also match patches to properties whose path matches `pattern`, see [`Self::path()`]
+    pub fn or_path(mut self, pattern: &str) -> Result<Self, AutomergeError> {
+        let segments = parse_pointer(pattern)?
+            .into_iter()
+            .map(|token| match token.as_str() {
+                "*" => Segment::One,
+                "**" => Segment::Any,
+                _ => Segment::Exact(token),
+            })
+            .collect();
+        self.paths.push(segments);
+        Ok(self)
+    }
+
+    /// A filter which matches patches to `obj` and to the objects inside it, however deeply
+    /// nested
+    ///
+    /// Unlike a path, this keeps matching the same object as it moves around a list. Patches to
+    /// the property of the parent which holds `obj`, such as the patch which creates it, are not
+    /// matched.
+    pub fn object<O: AsRef<ObjId>>(obj: O) -> Self {
+        Self::new().or_object(obj)
+    }
+
+    /// Also match patches to `obj` and to the objects inside it
+    pub fn or_object<O: AsRef<ObjId>>(mut self, obj: O) -> Self {
+        self.objects.push(obj.as_ref().clone());
+        self
+    }
+
+    /// Only match patches whose action is one of `actions`
+    pub fn actions<I: IntoIterator<Item = ActionKind>>(self, actions: I) -> Self {
+        Self {
+            actions: Some(actions.into_iter().collect()),
+            ..self
+        }
+    }
+
+    /// Whether `patch` is selected by this filter
+    pub fn matches(&self, patch: &Patch) -> bool {
+        if let Some(actions) = &self.actions {
+            if !actions.contains(&patch.action.kind()) {
+                return false;
+            }
+        }
+        if self.paths.is_empty() && self.objects.is_empty() {
+            return true;
+        }
+        let in_object = self
+            .objects
+            .iter()
+            .any(|obj| patch.obj == *obj || patch.path.iter().any(|(parent, _)| parent == obj));
+        if in_object {
+            return true;
+        }
+        let props = patch
+            .path
+            .iter()
+            .map(|(_, prop)| prop.clone())
+            .chain(patch.action.prop())
+            .collect::<Vec<_>>();
+        self.paths
+            .iter()
+            .any(|pattern| matches_path(pattern, &props))
+    }
+
+    /// The patches in `patches` which are selected by this filter
+    pub fn apply(&self, mut patches: Vec<Patch>) -> Vec<Patch> {
+        patches.retain(|patch| self.matches(patch));
+        patches
+    }
+}
+
+fn matches_path(pattern: &[Segment], props: &[Prop]) -> bool {
+    match (pattern.split_first(), props.split_first()) {
+        (None, None) => true,
+        (Some((Segment::Any, rest)), _) => {
+            matches_path(rest, props) || (!props.is_empty() && matches_path(pattern, &props[1..]))
+        }
+        (Some((Segment::One, rest)), Some((_, props))) => matches_path(rest, props),
+        (Some((Segment::Exact(token), rest)), Some((prop, props))) => {
+            token_matches(token, prop) && matches_path(rest, props)
+        }
+        _ => false,
+    }
+}
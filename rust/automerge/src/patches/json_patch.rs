@@ -0,0 +1,165 @@
+use serde::Serialize;
+
+use crate::hydrate;
+use crate::{ObjId, ObjType, Prop, ReadDoc, Value};
+
+use super::{Patch, PatchAction};
+
+/// An operation of a JSON Patch (RFC 6902)
+///
+/// This serializes to the JSON representation of the operation, e.g.
+/// `{"op":"add","path":"/todos/0","value":"shop"}`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum JsonPatchOp {
+    Add { path: String, value: hydrate::Value },
+    Remove { path: String },
+    Replace { path: String, value: hydrate::Value },
+}
+
+impl JsonPatchOp {
+    /// The JSON pointer this operation targets
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Add { path, .. } | Self::Remove { path } | Self::Replace { path, .. } => path,
+        }
+    }
+}
+
+/// Convert `patches` into the JSON Patch operations which make the same changes to the JSON
+/// representation of the document
+///
+/// `doc` must be in the state the patches bring it to, for example the document
+/// [`crate::AutoCommit::diff_incremental()`] was called on. A new object is added empty and filled
+/// in by the operations which follow it. Conflicts and marks have no JSON representation and are
+/// skipped.
+///
+/// Text objects are represented as JSON strings and counters as numbers, neither of which JSON
+/// Patch can edit in place. Their edits are not replayed one by one: instead the operations end
+/// with a single `replace` of every text object and counter which changed, carrying its value in
+/// `doc` at its path in `doc` (those which are no longer in the document are skipped). Applying
+/// all of the operations gives the JSON representation of `doc`, but for text and counters the
+/// output is a snapshot of the final state rather than a history of the intermediate ones.
+///
+/// # Example
+///
+/// ```
+/// # use automerge::{AutoCommit, ObjType, ROOT};
+/// # use automerge::transaction::Transactable;
+/// # use automerge::patches::{to_json_patch, JsonPatchOp};
+/// let mut doc = AutoCommit::new();
+/// let todos = doc.put_object(ROOT, "todos", ObjType::List).unwrap();
+/// doc.insert(&todos, 0, "shop").unwrap();
+///
+/// let patches = doc.diff_incremental();
+/// let ops = to_json_patch(&doc, &patches);
+/// assert_eq!(ops[0].path(), "/todos");
+/// assert_eq!(
+///     ops[1],
+///     JsonPatchOp::Add { path: "/todos/0".to_string(), value: "shop".into() }
+/// );
+/// ```
+pub fn to_json_patch<R: ReadDoc>(doc: &R, patches: &[Patch]) -> Vec<JsonPatchOp> {
+    let mut ops = Vec::new();
+    // the text objects (with no prop) and counters which changed, in the order they first changed
+    let mut snapshots: Vec<(&ObjId, Option<&Prop>)> = Vec::new();
+    for patch in patches {
+        let mut path = String::new();
+        for (_, prop) in &patch.path {
+            push_token(&mut path, prop);
+        }
+        if doc.object_type(&patch.obj) == Ok(ObjType::Text) {
+            if !snapshots.contains(&(&patch.obj, None)) {
+                snapshots.push((&patch.obj, None));
+            }
+            continue;
+        }
+        match &patch.action {
+            PatchAction::PutMap { key, value, .. } => ops.push(JsonPatchOp::Add {
+                path: with_token(&path, &Prop::Map(key.clone())),
+                value: value.0.clone().into(),
+            }),
+            PatchAction::PutSeq { index, value, .. } => ops.push(JsonPatchOp::Replace {
+                path: with_token(&path, &Prop::Seq(*index)),
+                value: value.0.clone().into(),
+            }),
+            PatchAction::Insert { index, values } => {
+                for (offset, (value, _, _)) in values.iter().enumerate() {
+                    ops.push(JsonPatchOp::Add {
+                        path: with_token(&path, &Prop::Seq(index + offset)),
+                        value: value.clone().into(),
+                    });
+                }
+            }
+            PatchAction::Increment { prop, .. } => {
+                if !snapshots.contains(&(&patch.obj, Some(prop))) {
+                    snapshots.push((&patch.obj, Some(prop)));
+                }
+            }
+            PatchAction::DeleteMap { key } => ops.push(JsonPatchOp::Remove {
+                path: with_token(&path, &Prop::Map(key.clone())),
+            }),
+            PatchAction::DeleteSeq { index, length } => {
+                let path = with_token(&path, &Prop::Seq(*index));
+                ops.extend((0..*length).map(|_| JsonPatchOp::Remove { path: path.clone() }));
+            }
+            PatchAction::Splice {
+                index,
+                deleted,
+                inserted,
+            } => {
+                let at = with_token(&path, &Prop::Seq(*index));
+                ops.extend((0..*deleted).map(|_| JsonPatchOp::Remove { path: at.clone() }));
+                for (offset, (value, _, _)) in inserted.iter().enumerate() {
+                    ops.push(JsonPatchOp::Add {
+                        path: with_token(&path, &Prop::Seq(index + offset)),
+                        value: value.clone().into(),
+                    });
+                }
+            }
+            PatchAction::SpliceText { .. }
+            | PatchAction::Conflict { .. }
+            | PatchAction::Mark { .. } => {}
+        }
+    }
+    ops.extend(
+        snapshots
+            .into_iter()
+            .filter_map(|(obj, prop)| snapshot(doc, obj, prop)),
+    );
+    ops
+}
+
+/// A `replace` of the text object `obj`, or of the counter at `prop` in `obj`, with its value in
+/// `doc`, or `None` if it is no longer in `doc`
+fn snapshot<R: ReadDoc>(doc: &R, obj: &ObjId, prop: Option<&Prop>) -> Option<JsonPatchOp> {
+    let mut path = String::new();
+    for (_, parent_prop) in doc.parents(obj).ok()?.visible_path()? {
+        push_token(&mut path, &parent_prop);
+    }
+    let value = match prop {
+        None => hydrate::Value::Text(doc.text(obj).ok()?.into()),
+        Some(prop) => match doc.get(obj, prop.clone()).ok()?? {
+            (Value::Scalar(counter), _) if counter.is_counter() => {
+                push_token(&mut path, prop);
+                hydrate::Value::Scalar(counter.into_owned())
+            }
+            _ => return None,
+        },
+    };
+    Some(JsonPatchOp::Replace { path, value })
+}
+
+fn with_token(path: &str, prop: &Prop) -> String {
+    let mut path = path.to_string();
+    push_token(&mut path, prop);
+    path
+}
+
+pub(super) fn push_token(path: &mut String, prop: &Prop) {
+    path.push('/');
+    match prop {
+        Prop::Map(key) => path.push_str(&key.replace('~', "~0").replace('/', "~1")),
+        Prop::Seq(index) => path.push_str(&index.to_string()),
+    }
+}
@@ -0,0 +1,153 @@
+use super::{Patch, PatchFilter};
+
+/// Receives the patches produced by changes as they are applied to a document
+///
+/// See [`crate::Automerge::merge_with()`] and [`crate::Automerge::apply_changes_with()`]. A
+/// `Vec<Patch>` collects every patch it observes. A tuple of observers passes the patches to
+/// each of them in turn, so that one document can feed several consumers, for example a UI patch
+/// queue, a metrics collector and a search index:
+///
+/// ```
+/// # use automerge::{Automerge, ROOT};
+/// # use automerge::patches::{CoalescingObserver, Patch, PatchObserver};
+/// # use automerge::transaction::Transactable;
+/// struct Count(usize);
+/// impl PatchObserver for Count {
+///     fn observe(&mut self, patches: &[Patch]) {
+///         self.0 += patches.len();
+///     }
+/// }
+///
+/// let mut doc = Automerge::new();
+/// let mut other = doc.fork();
+/// other.transact(|tx| tx.put(ROOT, "key", "value")).unwrap();
+///
+/// let mut ui = CoalescingObserver::new();
+/// let mut count = Count(0);
+/// let mut all = Vec::<Patch>::new();
+/// doc.merge_with(&other, &mut (&mut ui, &mut count, &mut all)).unwrap();
+/// assert_eq!(ui.take_patches(), all);
+/// assert_eq!(count.0, 1);
+/// ```
+pub trait PatchObserver {
+    /// Called with the patches produced by applying one change, and any changes which were
+    /// waiting for that change before they could be applied
+    fn observe(&mut self, patches: &[Patch]);
+}
+
+impl PatchObserver for Vec<Patch> {
+    fn observe(&mut self, patches: &[Patch]) {
+        self.extend_from_slice(patches);
+    }
+}
+
+impl<O: PatchObserver + ?Sized> PatchObserver for &mut O {
+    fn observe(&mut self, patches: &[Patch]) {
+        (**self).observe(patches);
+    }
+}
+
+impl<O: PatchObserver + ?Sized> PatchObserver for Box<O> {
+    fn observe(&mut self, patches: &[Patch]) {
+        (**self).observe(patches);
+    }
+}
+
+/// Observers chosen at runtime, each of which observes every patch
+impl<O: PatchObserver> PatchObserver for [O] {
+    fn observe(&mut self, patches: &[Patch]) {
+        for observer in self {
+            observer.observe(patches);
+        }
+    }
+}
+
+macro_rules! tuple_observer {
+    ($($name:ident),+) => {
+        impl<$($name: PatchObserver),+> PatchObserver for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn observe(&mut self, patches: &[Patch]) {
+                let ($($name,)+) = self;
+                $($name.observe(patches);)+
+            }
+        }
+    };
+}
+
+tuple_observer!(A, B);
+tuple_observer!(A, B, C);
+tuple_observer!(A, B, C, D);
+tuple_observer!(A, B, C, D, E);
+tuple_observer!(A, B, C, D, E, F);
+
+/// A [`PatchObserver`] which passes on the patches which match a [`PatchFilter`]
+///
+/// Use this to observe one part of a large document, for example one object and everything
+/// inside it with [`PatchFilter::object()`]. `observer` is not called for changes which produce
+/// no matching patches.
+///
+/// # Example
+///
+/// ```
+/// # use automerge::{Automerge, ObjType, ROOT};
+/// # use automerge::patches::{FilteredObserver, Patch, PatchFilter};
+/// # use automerge::transaction::Transactable;
+/// let mut doc = Automerge::new();
+/// let todos = doc
+///     .transact(|tx| tx.put_object(ROOT, "todos", ObjType::List))
+///     .unwrap()
+///     .result;
+///
+/// let mut other = doc.fork();
+/// other
+///     .transact(|tx| {
+///         let todo = tx.insert_object(&todos, 0, ObjType::Map)?;
+///         tx.put(&todo, "title", "shop")?;
+///         tx.put(ROOT, "title", "my list")
+///     })
+///     .unwrap();
+///
+/// let mut observer = FilteredObserver::new(PatchFilter::object(&todos), Vec::<Patch>::new());
+/// doc.merge_with(&other, &mut observer).unwrap();
+/// assert_eq!(observer.into_observer().len(), 2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct FilteredObserver<O> {
+    filter: PatchFilter,
+    observer: O,
+}
+
+impl<O: PatchObserver> FilteredObserver<O> {
+    pub fn new(filter: PatchFilter, observer: O) -> Self {
+        Self { filter, observer }
+    }
+
+    pub fn filter(&self) -> &PatchFilter {
+        &self.filter
+    }
+
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    pub fn observer_mut(&mut self) -> &mut O {
+        &mut self.observer
+    }
+
+    pub fn into_observer(self) -> O {
+        self.observer
+    }
+}
+
+impl<O: PatchObserver> PatchObserver for FilteredObserver<O> {
+    fn observe(&mut self, patches: &[Patch]) {
+        let matching = patches
+            .iter()
+            .filter(|patch| self.filter.matches(patch))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !matching.is_empty() {
+            self.observer.observe(&matching);
+        }
+    }
+}
@@ -78,6 +78,21 @@ pub enum PatchAction {
     DeleteMap { key: String },
     /// One or more indices were removed from a sequence
     DeleteSeq { index: usize, length: usize },
+    /// Elements of a list were deleted and others inserted in their place
+    ///
+    /// This is only produced by a [`PatchLog`](super::PatchLog) with
+    /// [`PatchLog::with_splices()`](super::PatchLog::with_splices) set, in which case it replaces
+    /// [`Self::Insert`] and [`Self::DeleteSeq`] for lists, grouping consecutive edits into one
+    /// patch. The `deleted` elements starting at `index` are removed first, then `inserted` is
+    /// inserted at `index`.
+    Splice {
+        index: usize,
+        deleted: usize,
+        /// The values that were inserted, in the order they appear. As with [`Self::Insert`] the
+        /// object ID is only meaningful for `Value::Obj` values, and the flag is whether the
+        /// value is the winner of a conflict
+        inserted: Vec<(Value<'static>, ObjId, bool)>,
+    },
     /// Some marks within a text object were added or removed
     Mark { marks: Vec<Mark<'static>> },
 }
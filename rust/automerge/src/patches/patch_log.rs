@@ -4,8 +4,8 @@ use crate::hydrate::Value;
 use crate::iter::{ListRangeItem, MapRangeItem};
 use crate::marks::{MarkAccumulator, MarkSet};
 use crate::read::ReadDocInternal;
-use crate::types::{ObjId, ObjType, OpId, Prop};
-use crate::{Automerge, ChangeHash, Patch, ReadDoc};
+use crate::types::{ListEncoding, ObjId, ObjType, OpId, Prop};
+use crate::{Automerge, ChangeHash, Patch, PatchAction, ReadDoc};
 use std::collections::BTreeSet;
 use std::collections::HashSet;
 use std::sync::Arc;
@@ -46,6 +46,7 @@ pub struct PatchLog {
     expose: HashSet<OpId>,
     active: bool,
     text_rep: TextRepresentation,
+    splices: bool,
     pub(crate) heads: Option<Vec<ChangeHash>>,
 }
 
@@ -122,6 +123,7 @@ impl PatchLog {
             events: vec![],
             heads: None,
             text_rep,
+            splices: false,
         }
     }
 
@@ -143,6 +145,25 @@ impl PatchLog {
         Self::new(true, text_rep)
     }
 
+    /// Whether edits to lists are reported as [`PatchAction::Splice`]
+    ///
+    /// When this is set consecutive insertions into and deletions from a list are grouped into
+    /// a single [`PatchAction::Splice`] rather than being reported as [`PatchAction::Insert`]
+    /// and [`PatchAction::DeleteSeq`] patches. Text objects are not affected, unless text is
+    /// represented as an array. The default is `false`.
+    pub fn with_splices(mut self, splices: bool) -> Self {
+        self.splices = splices;
+        self
+    }
+
+    pub(crate) fn splices(&self) -> bool {
+        self.splices
+    }
+
+    pub(crate) fn set_splices(&mut self, splices: bool) {
+        self.splices = splices;
+    }
+
     pub(crate) fn set_active(&mut self, setting: bool) {
         self.active = setting
     }
@@ -322,11 +343,16 @@ impl PatchLog {
     pub(crate) fn make_patches(&mut self, doc: &Automerge) -> Vec<Patch> {
         self.events.sort_by(|a, b| doc.ops().osd.lamport_cmp(a, b));
         let expose = ExposeQueue(self.expose.iter().map(|id| doc.id_to_exid(*id)).collect());
-        if let Some(heads) = self.heads.as_ref() {
+        let patches = if let Some(heads) = self.heads.as_ref() {
             let read_doc = ReadDocAt { doc, heads };
             Self::make_patches_inner(&self.events, expose, doc, &read_doc, self.text_rep)
         } else {
             Self::make_patches_inner(&self.events, expose, doc, doc, self.text_rep)
+        };
+        if self.splices {
+            group_splices(patches, doc, self.text_rep)
+        } else {
+            patches
         }
     }
 
@@ -422,12 +448,26 @@ impl PatchLog {
         self.expose.clear();
     }
 
+    /// The number of events in the log, see [`Self::rollback_to()`]
+    pub(crate) fn checkpoint(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Forget the events logged since `checkpoint` was returned by [`Self::checkpoint()`], and
+    /// the objects created by ops from `first` onwards
+    pub(crate) fn rollback_to(&mut self, checkpoint: usize, first: OpId) {
+        self.events.truncate(checkpoint);
+        self.expose
+            .retain(|id| id.actor() != first.actor() || id.counter() < first.counter());
+    }
+
     pub(crate) fn branch(&mut self) -> Self {
         Self {
             active: self.active,
             expose: HashSet::new(),
             events: Default::default(),
             text_rep: self.text_rep,
+            splices: self.splices,
             heads: None,
         }
     }
@@ -548,3 +588,55 @@ impl ExposeQueue {
         Some(())
     }
 }
+
+/// Replace the insertions into and deletions from lists in `patches` with splices, grouping
+/// consecutive edits to the same list
+fn group_splices(patches: Vec<Patch>, doc: &Automerge, text_rep: TextRepresentation) -> Vec<Patch> {
+    let is_list = |obj: &ExId| {
+        doc.object_type(obj)
+            .map(|typ| text_rep.encoding(typ) == ListEncoding::List)
+            .unwrap_or(false)
+    };
+    let mut grouped: Vec<Patch> = Vec::with_capacity(patches.len());
+    for patch in patches {
+        let (index, deleted, inserted) = match patch.action {
+            PatchAction::Insert { index, values } if is_list(&patch.obj) => {
+                (index, 0, values.iter().cloned().collect())
+            }
+            PatchAction::DeleteSeq { index, length } if is_list(&patch.obj) => {
+                (index, length, Vec::new())
+            }
+            action => {
+                grouped.push(Patch { action, ..patch });
+                continue;
+            }
+        };
+        if let Some(Patch {
+            obj,
+            action:
+                PatchAction::Splice {
+                    index: last_index,
+                    deleted: last_deleted,
+                    inserted: last_inserted,
+                },
+            ..
+        }) = grouped.last_mut()
+        {
+            // an edit just after the elements the last splice inserted extends that splice
+            if *obj == patch.obj && *last_index + last_inserted.len() == index {
+                *last_deleted += deleted;
+                last_inserted.extend(inserted);
+                continue;
+            }
+        }
+        grouped.push(Patch {
+            action: PatchAction::Splice {
+                index,
+                deleted,
+                inserted,
+            },
+            ..patch
+        });
+    }
+    grouped
+}
@@ -0,0 +1,236 @@
+use crate::{Prop, Value};
+
+#[cfg(feature = "json")]
+use crate::{ObjType, TextUnit};
+
+use super::json_patch::push_token;
+use super::{ActionKind, Patch, PatchAction};
+
+/// A copy of the JSON representation of a document which [`apply_patches()`] can keep up to date
+///
+/// Maps and tables are JSON objects, lists are arrays, text objects are strings and scalars are
+/// whatever the target uses for them, as with [`crate::AutoSerde`]. Each method changes the value
+/// it is called on and returns `false` if that value is not the kind of value the method
+/// expects, for example [`Self::put_key()`] on an array, or an index is out of range.
+///
+/// With the `json` feature this is implemented for `serde_json::Value`.
+pub trait PatchTarget: Sized {
+    /// A new value for `value`, where objects are empty and text is an empty string
+    fn new_value(value: &Value<'_>) -> Self;
+
+    /// The value of `prop` in this object or array
+    fn child_mut(&mut self, prop: &Prop) -> Option<&mut Self>;
+
+    /// Set `key` in this object to `value`
+    fn put_key(&mut self, key: &str, value: Self) -> bool;
+
+    /// Remove `key` from this object
+    fn delete_key(&mut self, key: &str) -> bool;
+
+    /// Replace the element at `index` in this array with `value`
+    fn put_index(&mut self, index: usize, value: Self) -> bool;
+
+    /// Insert `value` at `index` in this array
+    fn insert(&mut self, index: usize, value: Self) -> bool;
+
+    /// Remove `length` elements of this array, or characters of this string, starting at `index`
+    ///
+    /// Indexes into strings are in [`crate::TextUnit::Native`] units.
+    fn delete_seq(&mut self, index: usize, length: usize) -> bool;
+
+    /// Insert `text` at `index` in this string
+    fn splice_text(&mut self, index: usize, text: &str) -> bool;
+
+    /// Add `by` to this counter
+    fn increment(&mut self, by: i64) -> bool;
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ApplyPatchError {
+    #[error("there is no value at {0:?}")]
+    MissingPath(String),
+    #[error("can't apply a {kind:?} patch to the value at {path:?}")]
+    Mismatch { path: String, kind: ActionKind },
+}
+
+/// Change `target` in the same way `patches` changed the document they came from
+///
+/// `target` must be in the state of the document the patches start from, which for the first
+/// patches of a new document is an empty object. The patches must represent text as strings, as
+/// they do with the default [`super::TextRepresentation`]. Conflicts and marks have no JSON
+/// representation and are skipped.
+///
+/// If a patch can't be applied this returns an error, leaving the patches before it applied. The
+/// target no longer matches the document and should be rebuilt from scratch.
+pub fn apply_patches<T: PatchTarget>(
+    target: &mut T,
+    patches: &[Patch],
+) -> Result<(), ApplyPatchError> {
+    for patch in patches {
+        apply_patch(target, patch)?;
+    }
+    Ok(())
+}
+
+fn apply_patch<T: PatchTarget>(root: &mut T, patch: &Patch) -> Result<(), ApplyPatchError> {
+    let mut path = String::new();
+    let mut target = root;
+    for (_, prop) in &patch.path {
+        push_token(&mut path, prop);
+        target = target
+            .child_mut(prop)
+            .ok_or_else(|| ApplyPatchError::MissingPath(path.clone()))?;
+    }
+    let applied = match &patch.action {
+        PatchAction::PutMap { key, value, .. } => target.put_key(key, T::new_value(&value.0)),
+        PatchAction::PutSeq { index, value, .. } => {
+            target.put_index(*index, T::new_value(&value.0))
+        }
+        PatchAction::Insert { index, values } => values
+            .iter()
+            .enumerate()
+            .all(|(offset, (value, _, _))| target.insert(index + offset, T::new_value(value))),
+        PatchAction::SpliceText { index, value, .. } => {
+            target.splice_text(*index, &value.make_string())
+        }
+        PatchAction::Increment { prop, value } => target
+            .child_mut(prop)
+            .map(|counter| counter.increment(*value))
+            .unwrap_or(false),
+        PatchAction::DeleteMap { key } => target.delete_key(key),
+        PatchAction::DeleteSeq { index, length } => target.delete_seq(*index, *length),
+        PatchAction::Splice {
+            index,
+            deleted,
+            inserted,
+        } => {
+            target.delete_seq(*index, *deleted)
+                && inserted.iter().enumerate().all(|(offset, (value, _, _))| {
+                    target.insert(index + offset, T::new_value(value))
+                })
+        }
+        PatchAction::Conflict { .. } | PatchAction::Mark { .. } => true,
+    };
+    if applied {
+        Ok(())
+    } else {
+        Err(ApplyPatchError::Mismatch {
+            path,
+            kind: patch.action.kind(),
+        })
+    }
+}
+
+/// A JSON mirror of a document
+///
+/// Scalars are converted as they are serialized by [`crate::AutoSerde`], so the mirror stays
+/// equal to `serde_json::to_value(AutoSerde::from(&doc))`.
+///
+/// ```
+/// # use automerge::{AutoCommit, AutoSerde, ObjType, ROOT};
+/// # use automerge::patches::apply_patches;
+/// # use automerge::transaction::Transactable;
+/// let mut doc = AutoCommit::new();
+/// let mut mirror = serde_json::json!({});
+///
+/// let todos = doc.put_object(ROOT, "todos", ObjType::List).unwrap();
+/// doc.insert(&todos, 0, "shop").unwrap();
+/// apply_patches(&mut mirror, &doc.diff_incremental()).unwrap();
+/// assert_eq!(mirror, serde_json::json!({"todos": ["shop"]}));
+///
+/// doc.put(&todos, 0, "cook").unwrap();
+/// apply_patches(&mut mirror, &doc.diff_incremental()).unwrap();
+/// assert_eq!(mirror, serde_json::to_value(AutoSerde::from(&doc)).unwrap());
+/// ```
+#[cfg(feature = "json")]
+impl PatchTarget for serde_json::Value {
+    fn new_value(value: &Value<'_>) -> Self {
+        match value {
+            Value::Object(ObjType::Map | ObjType::Table) => Self::Object(Default::default()),
+            Value::Object(ObjType::List) => Self::Array(Vec::new()),
+            Value::Object(ObjType::Text) => Self::String(String::new()),
+            Value::Scalar(scalar) => serde_json::to_value(scalar.as_ref()).unwrap_or(Self::Null),
+        }
+    }
+
+    fn child_mut(&mut self, prop: &Prop) -> Option<&mut Self> {
+        match (self, prop) {
+            (Self::Object(map), Prop::Map(key)) => map.get_mut(key),
+            (Self::Array(array), Prop::Seq(index)) => array.get_mut(*index),
+            _ => None,
+        }
+    }
+
+    fn put_key(&mut self, key: &str, value: Self) -> bool {
+        match self {
+            Self::Object(map) => {
+                map.insert(key.to_string(), value);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn delete_key(&mut self, key: &str) -> bool {
+        match self {
+            Self::Object(map) => map.remove(key).is_some(),
+            _ => false,
+        }
+    }
+
+    fn put_index(&mut self, index: usize, value: Self) -> bool {
+        match self.as_array_mut().and_then(|array| array.get_mut(index)) {
+            Some(element) => {
+                *element = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn insert(&mut self, index: usize, value: Self) -> bool {
+        match self {
+            Self::Array(array) if index <= array.len() => {
+                array.insert(index, value);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn delete_seq(&mut self, index: usize, length: usize) -> bool {
+        match self {
+            Self::Array(array) if index + length <= array.len() => {
+                array.drain(index..index + length);
+                true
+            }
+            Self::String(s) if index + length <= TextUnit::Native.len(s) => {
+                let start = TextUnit::byte_offset(s, index);
+                let end = TextUnit::byte_offset(s, index + length);
+                s.replace_range(start..end, "");
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn splice_text(&mut self, index: usize, text: &str) -> bool {
+        match self {
+            Self::String(s) if index <= TextUnit::Native.len(s) => {
+                s.insert_str(TextUnit::byte_offset(s, index), text);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn increment(&mut self, by: i64) -> bool {
+        match self.as_i64() {
+            Some(current) => {
+                *self = Self::from(current + by);
+                true
+            }
+            None => false,
+        }
+    }
+}
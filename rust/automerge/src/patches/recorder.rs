@@ -0,0 +1,799 @@
+use std::borrow::Cow;
+use std::io::{self, Read, Write};
+
+use sha2::{Digest, Sha256};
+
+use crate::blob::{BlobHash, BlobRef};
+use crate::chunked_bytes::Segment;
+use crate::link::DocId;
+use crate::marks::{Mark, MarkSet};
+use crate::sequence_tree::SequenceTree;
+use crate::text_value::TextValue;
+use crate::value::Counter;
+use crate::{
+    ChangeHash, ChunkedBytes, DateTime, ObjId, ObjType, Prop, ScalarValue, SegmentHash, Value,
+};
+
+use super::{Patch, PatchAction};
+
+const CHECKSUM_LEN: usize = 4;
+
+/// Persists a stream of patches to an append only log
+///
+/// Each call to [`Self::record()`] appends one record containing the heads of the document after
+/// the patches were generated along with the patches themselves, so the grouping of patches into
+/// changes is preserved. The log can be read back with a [`Replayer`] to rebuild whatever state
+/// was derived from the patches, for example after a crash.
+///
+/// Records are written with a single `write_all` followed by a `flush`. If the process dies part
+/// way through writing a record the [`Replayer`] will treat the incomplete record as the end of
+/// the log and report how much of the log was valid via [`Replayer::valid_len()`], so the log
+/// can be truncated to that length before recording resumes.
+///
+/// ```
+/// # use automerge::{AutoCommit, ROOT, transaction::Transactable};
+/// # use automerge::patches::{Recorder, Replayer};
+/// let mut doc = AutoCommit::new();
+/// doc.put(ROOT, "key", "value").unwrap();
+/// let patches = doc.diff_incremental();
+///
+/// let mut recorder = Recorder::new(Vec::new());
+/// recorder.record(&doc.get_heads(), &patches).unwrap();
+/// let log = recorder.into_inner();
+///
+/// let mut replayed = Vec::new();
+/// Replayer::new(log.as_slice())
+///     .replay(|_heads, patches| replayed.extend(patches))
+///     .unwrap();
+/// assert_eq!(replayed, patches);
+/// ```
+#[derive(Debug)]
+pub struct Recorder<W> {
+    out: W,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Create a recorder which appends records to `out`
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    /// Append a record of `patches`, which were generated when the document reached `heads`
+    pub fn record(&mut self, heads: &[ChangeHash], patches: &[Patch]) -> io::Result<()> {
+        let mut payload = Encoder::default();
+        payload.uint(heads.len() as u64);
+        for head in heads {
+            payload.raw(head.as_ref());
+        }
+        payload.uint(patches.len() as u64);
+        for patch in patches {
+            payload.patch(patch);
+        }
+        let payload = payload.0;
+
+        let mut record = Vec::with_capacity(payload.len() + CHECKSUM_LEN + 10);
+        leb128::write::unsigned(&mut record, payload.len() as u64)?;
+        record.extend_from_slice(&payload);
+        record.extend_from_slice(&checksum(&payload));
+        self.out.write_all(&record)?;
+        self.out.flush()
+    }
+
+    /// The underlying writer
+    pub fn get_ref(&self) -> &W {
+        &self.out
+    }
+
+    /// Consume the recorder, returning the underlying writer
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// A group of patches read back from a log written by a [`Recorder`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedPatches {
+    /// The heads of the document after the patches were generated
+    pub heads: Vec<ChangeHash>,
+    pub patches: Vec<Patch>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReplayError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("record at offset {offset} has an invalid checksum")]
+    InvalidChecksum { offset: u64 },
+    #[error("record at offset {offset} is invalid: {reason}")]
+    InvalidRecord { offset: u64, reason: &'static str },
+}
+
+/// Reads the records written by a [`Recorder`]
+///
+/// This is an iterator over the [`RecordedPatches`] in the log, in the order they were recorded.
+/// An incomplete record at the end of the log is assumed to be the result of a crash whilst
+/// writing and ends the iteration without an error, see [`Self::truncated()`].
+#[derive(Debug)]
+pub struct Replayer<R> {
+    input: R,
+    offset: u64,
+    truncated: bool,
+    done: bool,
+}
+
+impl<R: Read> Replayer<R> {
+    pub fn new(input: R) -> Self {
+        Self {
+            input,
+            offset: 0,
+            truncated: false,
+            done: false,
+        }
+    }
+
+    /// Read every remaining record, passing each group of patches to `f` along with the heads
+    /// they were recorded at
+    ///
+    /// Returns the length of the valid portion of the log, see [`Self::valid_len()`]
+    pub fn replay<F>(mut self, mut f: F) -> Result<u64, ReplayError>
+    where
+        F: FnMut(&[ChangeHash], Vec<Patch>),
+    {
+        for record in &mut self {
+            let RecordedPatches { heads, patches } = record?;
+            f(&heads, patches);
+        }
+        Ok(self.offset)
+    }
+
+    /// The number of bytes of the log which contain complete records read so far
+    pub fn valid_len(&self) -> u64 {
+        self.offset
+    }
+
+    /// Whether the log ended with an incomplete record
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn read_record(&mut self) -> Result<Option<RecordedPatches>, ReplayError> {
+        let mut len: u64 = 0;
+        let mut len_bytes = 0;
+        loop {
+            let mut byte = [0; 1];
+            if self.input.read(&mut byte)? == 0 {
+                self.truncated = len_bytes > 0;
+                return Ok(None);
+            }
+            if len_bytes == 9 && byte[0] > 1 {
+                return Err(ReplayError::InvalidRecord {
+                    offset: self.offset,
+                    reason: "invalid length",
+                });
+            }
+            len |= u64::from(byte[0] & 0x7f) << (7 * len_bytes);
+            len_bytes += 1;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+        }
+
+        let mut body = Vec::new();
+        let expected = len + CHECKSUM_LEN as u64;
+        (&mut self.input).take(expected).read_to_end(&mut body)?;
+        if (body.len() as u64) < expected {
+            self.truncated = true;
+            return Ok(None);
+        }
+        let (payload, sum) = body.split_at(len as usize);
+        if sum != checksum(payload) {
+            return Err(ReplayError::InvalidChecksum {
+                offset: self.offset,
+            });
+        }
+
+        let mut decoder = Decoder(payload);
+        let record = decoder
+            .record()
+            .and_then(|r| {
+                if decoder.0.is_empty() {
+                    Ok(r)
+                } else {
+                    Err("trailing data")
+                }
+            })
+            .map_err(|reason| ReplayError::InvalidRecord {
+                offset: self.offset,
+                reason,
+            })?;
+        self.offset += len_bytes + expected;
+        Ok(Some(record))
+    }
+}
+
+impl<R: Read> Iterator for Replayer<R> {
+    type Item = Result<RecordedPatches, ReplayError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.read_record().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
+
+fn checksum(payload: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let digest = Sha256::digest(payload);
+    let mut sum = [0; CHECKSUM_LEN];
+    sum.copy_from_slice(&digest[..CHECKSUM_LEN]);
+    sum
+}
+
+const ACTION_PUT_MAP: u8 = 0;
+const ACTION_PUT_SEQ: u8 = 1;
+const ACTION_INSERT: u8 = 2;
+const ACTION_SPLICE_TEXT: u8 = 3;
+const ACTION_INCREMENT: u8 = 4;
+const ACTION_CONFLICT: u8 = 5;
+const ACTION_DELETE_MAP: u8 = 6;
+const ACTION_DELETE_SEQ: u8 = 7;
+const ACTION_MARK: u8 = 8;
+const ACTION_SPLICE: u8 = 9;
+
+const SCALAR_NULL: u8 = 0;
+const SCALAR_FALSE: u8 = 1;
+const SCALAR_TRUE: u8 = 2;
+const SCALAR_UINT: u8 = 3;
+const SCALAR_INT: u8 = 4;
+const SCALAR_F64: u8 = 5;
+const SCALAR_STR: u8 = 6;
+const SCALAR_BYTES: u8 = 7;
+const SCALAR_COUNTER: u8 = 8;
+const SCALAR_TIMESTAMP: u8 = 9;
+const SCALAR_UNKNOWN: u8 = 10;
+const SCALAR_DATETIME: u8 = 11;
+const SCALAR_BIGINT: u8 = 12;
+const SCALAR_DECIMAL: u8 = 13;
+const SCALAR_CHUNKED: u8 = 14;
+const SCALAR_BLOB_REF: u8 = 15;
+const SCALAR_LINK: u8 = 16;
+
+#[derive(Default)]
+struct Encoder(Vec<u8>);
+
+impl Encoder {
+    fn byte(&mut self, b: u8) {
+        self.0.push(b);
+    }
+
+    fn raw(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn uint(&mut self, n: u64) {
+        leb128::write::unsigned(&mut self.0, n).unwrap();
+    }
+
+    fn int(&mut self, n: i64) {
+        leb128::write::signed(&mut self.0, n).unwrap();
+    }
+
+    fn bool(&mut self, b: bool) {
+        self.byte(b as u8);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.uint(bytes.len() as u64);
+        self.raw(bytes);
+    }
+
+    fn str(&mut self, s: &str) {
+        self.bytes(s.as_bytes());
+    }
+
+    fn obj(&mut self, obj: &ObjId) {
+        self.bytes(&obj.to_bytes());
+    }
+
+    fn prop(&mut self, prop: &Prop) {
+        match prop {
+            Prop::Map(key) => {
+                self.byte(0);
+                self.str(key);
+            }
+            Prop::Seq(index) => {
+                self.byte(1);
+                self.uint(*index as u64);
+            }
+        }
+    }
+
+    fn obj_type(&mut self, typ: ObjType) {
+        self.byte(match typ {
+            ObjType::Map => 0,
+            ObjType::Table => 1,
+            ObjType::List => 2,
+            ObjType::Text => 3,
+        });
+    }
+
+    fn scalar(&mut self, value: &ScalarValue) {
+        match value {
+            ScalarValue::Null => self.byte(SCALAR_NULL),
+            ScalarValue::Boolean(false) => self.byte(SCALAR_FALSE),
+            ScalarValue::Boolean(true) => self.byte(SCALAR_TRUE),
+            ScalarValue::Uint(n) => {
+                self.byte(SCALAR_UINT);
+                self.uint(*n);
+            }
+            ScalarValue::Int(n) => {
+                self.byte(SCALAR_INT);
+                self.int(*n);
+            }
+            ScalarValue::F64(f) => {
+                self.byte(SCALAR_F64);
+                self.raw(&f.to_le_bytes());
+            }
+            ScalarValue::Str(s) => {
+                self.byte(SCALAR_STR);
+                self.str(s);
+            }
+            ScalarValue::Bytes(b) => {
+                self.byte(SCALAR_BYTES);
+                self.bytes(b);
+            }
+            ScalarValue::Counter(c) => {
+                self.byte(SCALAR_COUNTER);
+                self.int(c.into());
+            }
+            ScalarValue::Timestamp(n) => {
+                self.byte(SCALAR_TIMESTAMP);
+                self.int(*n);
+            }
+            ScalarValue::DateTime(d) => {
+                self.byte(SCALAR_DATETIME);
+                self.int(d.unix_seconds());
+                self.uint(d.subsec_nanos().into());
+                match d.offset() {
+                    Some(offset) => {
+                        self.byte(1);
+                        self.int(offset.into());
+                    }
+                    None => self.byte(0),
+                }
+            }
+            ScalarValue::BigInt(n) => {
+                self.byte(SCALAR_BIGINT);
+                self.str(n.as_str());
+            }
+            ScalarValue::Decimal(n) => {
+                self.byte(SCALAR_DECIMAL);
+                self.str(n.as_str());
+            }
+            ScalarValue::Chunked(b) => {
+                self.byte(SCALAR_CHUNKED);
+                self.uint(b.len());
+                self.uint(b.raw_segments().len() as u64);
+                for segment in b.raw_segments() {
+                    match &segment.data {
+                        Some(data) => {
+                            self.byte(1);
+                            self.bytes(data);
+                        }
+                        None => {
+                            self.byte(0);
+                            self.raw(&segment.hash.0);
+                        }
+                    }
+                }
+            }
+            ScalarValue::BlobRef(b) => {
+                self.byte(SCALAR_BLOB_REF);
+                self.raw(&b.hash.0);
+                self.uint(b.size);
+                match &b.hint {
+                    Some(hint) => {
+                        self.byte(1);
+                        self.str(hint);
+                    }
+                    None => self.byte(0),
+                }
+            }
+            ScalarValue::Link(d) => {
+                self.byte(SCALAR_LINK);
+                self.bytes(d.as_bytes());
+            }
+            ScalarValue::Unknown { type_code, bytes } => {
+                self.byte(SCALAR_UNKNOWN);
+                self.byte(*type_code);
+                self.bytes(bytes);
+            }
+        }
+    }
+
+    fn value(&mut self, value: &Value<'_>) {
+        match value {
+            Value::Object(typ) => {
+                self.byte(0);
+                self.obj_type(*typ);
+            }
+            Value::Scalar(s) => {
+                self.byte(1);
+                self.scalar(s);
+            }
+        }
+    }
+
+    fn mark_set(&mut self, marks: &MarkSet) {
+        self.uint(marks.len() as u64);
+        for (name, value) in marks.iter() {
+            self.str(name);
+            self.scalar(value);
+        }
+    }
+
+    fn patch(&mut self, patch: &Patch) {
+        self.obj(&patch.obj);
+        self.uint(patch.path.len() as u64);
+        for (obj, prop) in &patch.path {
+            self.obj(obj);
+            self.prop(prop);
+        }
+        match &patch.action {
+            PatchAction::PutMap {
+                key,
+                value,
+                conflict,
+            } => {
+                self.byte(ACTION_PUT_MAP);
+                self.str(key);
+                self.value(&value.0);
+                self.obj(&value.1);
+                self.bool(*conflict);
+            }
+            PatchAction::PutSeq {
+                index,
+                value,
+                conflict,
+            } => {
+                self.byte(ACTION_PUT_SEQ);
+                self.uint(*index as u64);
+                self.value(&value.0);
+                self.obj(&value.1);
+                self.bool(*conflict);
+            }
+            PatchAction::Insert { index, values } => {
+                self.byte(ACTION_INSERT);
+                self.uint(*index as u64);
+                self.uint(values.len() as u64);
+                for (value, id, conflict) in values.iter() {
+                    self.value(value);
+                    self.obj(id);
+                    self.bool(*conflict);
+                }
+            }
+            PatchAction::SpliceText {
+                index,
+                value,
+                marks,
+            } => {
+                self.byte(ACTION_SPLICE_TEXT);
+                self.uint(*index as u64);
+                self.str(&value.make_string());
+                match marks {
+                    Some(marks) => {
+                        self.bool(true);
+                        self.mark_set(marks);
+                    }
+                    None => self.bool(false),
+                }
+            }
+            PatchAction::Increment { prop, value } => {
+                self.byte(ACTION_INCREMENT);
+                self.prop(prop);
+                self.int(*value);
+            }
+            PatchAction::Conflict { prop } => {
+                self.byte(ACTION_CONFLICT);
+                self.prop(prop);
+            }
+            PatchAction::DeleteMap { key } => {
+                self.byte(ACTION_DELETE_MAP);
+                self.str(key);
+            }
+            PatchAction::DeleteSeq { index, length } => {
+                self.byte(ACTION_DELETE_SEQ);
+                self.uint(*index as u64);
+                self.uint(*length as u64);
+            }
+            PatchAction::Splice {
+                index,
+                deleted,
+                inserted,
+            } => {
+                self.byte(ACTION_SPLICE);
+                self.uint(*index as u64);
+                self.uint(*deleted as u64);
+                self.uint(inserted.len() as u64);
+                for (value, id, conflict) in inserted {
+                    self.value(value);
+                    self.obj(id);
+                    self.bool(*conflict);
+                }
+            }
+            PatchAction::Mark { marks } => {
+                self.byte(ACTION_MARK);
+                self.uint(marks.len() as u64);
+                for mark in marks {
+                    self.uint(mark.start as u64);
+                    self.uint(mark.end as u64);
+                    self.str(mark.name());
+                    self.scalar(mark.value());
+                }
+            }
+        }
+    }
+}
+
+struct Decoder<'a>(&'a [u8]);
+
+type DecodeResult<T> = Result<T, &'static str>;
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, n: usize) -> DecodeResult<&'a [u8]> {
+        if self.0.len() < n {
+            return Err("unexpected end of record");
+        }
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> DecodeResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn uint(&mut self) -> DecodeResult<u64> {
+        leb128::read::unsigned(&mut self.0).map_err(|_| "invalid uLEB")
+    }
+
+    fn usize(&mut self) -> DecodeResult<usize> {
+        usize::try_from(self.uint()?).map_err(|_| "integer out of range")
+    }
+
+    fn int(&mut self) -> DecodeResult<i64> {
+        leb128::read::signed(&mut self.0).map_err(|_| "invalid LEB")
+    }
+
+    fn bool(&mut self) -> DecodeResult<bool> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err("invalid boolean"),
+        }
+    }
+
+    fn bytes(&mut self) -> DecodeResult<&'a [u8]> {
+        let len = self.usize()?;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> DecodeResult<String> {
+        std::str::from_utf8(self.bytes()?)
+            .map(String::from)
+            .map_err(|_| "invalid UTF-8")
+    }
+
+    fn obj(&mut self) -> DecodeResult<ObjId> {
+        ObjId::try_from(self.bytes()?).map_err(|_| "invalid object ID")
+    }
+
+    fn prop(&mut self) -> DecodeResult<Prop> {
+        match self.byte()? {
+            0 => Ok(Prop::Map(self.string()?)),
+            1 => Ok(Prop::Seq(self.usize()?)),
+            _ => Err("invalid property"),
+        }
+    }
+
+    fn obj_type(&mut self) -> DecodeResult<ObjType> {
+        match self.byte()? {
+            0 => Ok(ObjType::Map),
+            1 => Ok(ObjType::Table),
+            2 => Ok(ObjType::List),
+            3 => Ok(ObjType::Text),
+            _ => Err("invalid object type"),
+        }
+    }
+
+    fn scalar(&mut self) -> DecodeResult<ScalarValue> {
+        Ok(match self.byte()? {
+            SCALAR_NULL => ScalarValue::Null,
+            SCALAR_FALSE => ScalarValue::Boolean(false),
+            SCALAR_TRUE => ScalarValue::Boolean(true),
+            SCALAR_UINT => ScalarValue::Uint(self.uint()?),
+            SCALAR_INT => ScalarValue::Int(self.int()?),
+            SCALAR_F64 => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(self.take(8)?);
+                ScalarValue::F64(f64::from_le_bytes(bytes))
+            }
+            SCALAR_STR => ScalarValue::Str(self.string()?.into()),
+            SCALAR_BYTES => ScalarValue::Bytes(self.bytes()?.to_vec()),
+            SCALAR_COUNTER => ScalarValue::Counter(Counter::from(self.int()?)),
+            SCALAR_TIMESTAMP => ScalarValue::Timestamp(self.int()?),
+            SCALAR_DATETIME => {
+                let seconds = self.int()?;
+                let nanos = u32::try_from(self.uint()?).map_err(|_| "invalid date time")?;
+                let datetime = DateTime::new(seconds, nanos).ok_or("invalid date time")?;
+                let datetime = match self.byte()? {
+                    0 => datetime,
+                    1 => i32::try_from(self.int()?)
+                        .ok()
+                        .and_then(|offset| datetime.with_offset(offset))
+                        .ok_or("invalid date time offset")?,
+                    _ => return Err("invalid date time"),
+                };
+                ScalarValue::DateTime(datetime)
+            }
+            SCALAR_BIGINT => {
+                ScalarValue::BigInt(self.string()?.parse().map_err(|_| "invalid big integer")?)
+            }
+            SCALAR_DECIMAL => {
+                ScalarValue::Decimal(self.string()?.parse().map_err(|_| "invalid decimal")?)
+            }
+            SCALAR_CHUNKED => {
+                let len = self.uint()?;
+                let count = self.uint()?;
+                let mut segments = Vec::new();
+                for _ in 0..count {
+                    segments.push(match self.byte()? {
+                        0 => Segment {
+                            // take() returns exactly the 32 bytes of the hash
+                            hash: SegmentHash(self.take(32)?.try_into().unwrap()),
+                            data: None,
+                            inline: true,
+                        },
+                        1 => {
+                            let data = self.bytes()?;
+                            Segment {
+                                hash: SegmentHash::of(data),
+                                data: Some(data.into()),
+                                inline: true,
+                            }
+                        }
+                        _ => return Err("invalid chunked bytes"),
+                    });
+                }
+                ScalarValue::Chunked(
+                    ChunkedBytes::from_segments(len, segments).ok_or("invalid chunked bytes")?,
+                )
+            }
+            SCALAR_BLOB_REF => ScalarValue::BlobRef(Box::new(BlobRef {
+                // take() returns exactly the 32 bytes of the hash
+                hash: BlobHash(self.take(32)?.try_into().unwrap()),
+                size: self.uint()?,
+                hint: match self.byte()? {
+                    0 => None,
+                    1 => Some(self.string()?),
+                    _ => return Err("invalid blob reference"),
+                },
+            })),
+            SCALAR_LINK => ScalarValue::Link(DocId::from(self.bytes()?)),
+            SCALAR_UNKNOWN => ScalarValue::Unknown {
+                type_code: self.byte()?,
+                bytes: self.bytes()?.to_vec(),
+            },
+            _ => return Err("invalid scalar value"),
+        })
+    }
+
+    fn value(&mut self) -> DecodeResult<Value<'static>> {
+        match self.byte()? {
+            0 => Ok(Value::Object(self.obj_type()?)),
+            1 => Ok(Value::Scalar(Cow::Owned(self.scalar()?))),
+            _ => Err("invalid value"),
+        }
+    }
+
+    fn mark_set(&mut self) -> DecodeResult<MarkSet> {
+        let len = self.usize()?;
+        (0..len)
+            .map(|_| Ok((self.string()?, self.scalar()?)))
+            .collect()
+    }
+
+    fn record(&mut self) -> DecodeResult<RecordedPatches> {
+        let num_heads = self.usize()?;
+        let heads = (0..num_heads)
+            .map(|_| ChangeHash::try_from(self.take(32)?).map_err(|_| "invalid change hash"))
+            .collect::<DecodeResult<_>>()?;
+        let num_patches = self.usize()?;
+        let patches = (0..num_patches)
+            .map(|_| self.patch())
+            .collect::<DecodeResult<_>>()?;
+        Ok(RecordedPatches { heads, patches })
+    }
+
+    fn patch(&mut self) -> DecodeResult<Patch> {
+        let obj = self.obj()?;
+        let path_len = self.usize()?;
+        let path = (0..path_len)
+            .map(|_| Ok((self.obj()?, self.prop()?)))
+            .collect::<DecodeResult<_>>()?;
+        let action = match self.byte()? {
+            ACTION_PUT_MAP => PatchAction::PutMap {
+                key: self.string()?,
+                value: (self.value()?, self.obj()?),
+                conflict: self.bool()?,
+            },
+            ACTION_PUT_SEQ => PatchAction::PutSeq {
+                index: self.usize()?,
+                value: (self.value()?, self.obj()?),
+                conflict: self.bool()?,
+            },
+            ACTION_INSERT => {
+                let index = self.usize()?;
+                let len = self.usize()?;
+                let mut values = SequenceTree::new();
+                for _ in 0..len {
+                    values.push((self.value()?, self.obj()?, self.bool()?));
+                }
+                PatchAction::Insert { index, values }
+            }
+            ACTION_SPLICE_TEXT => PatchAction::SpliceText {
+                index: self.usize()?,
+                value: TextValue::from(self.string()?),
+                marks: if self.bool()? {
+                    Some(self.mark_set()?)
+                } else {
+                    None
+                },
+            },
+            ACTION_INCREMENT => PatchAction::Increment {
+                prop: self.prop()?,
+                value: self.int()?,
+            },
+            ACTION_CONFLICT => PatchAction::Conflict { prop: self.prop()? },
+            ACTION_DELETE_MAP => PatchAction::DeleteMap {
+                key: self.string()?,
+            },
+            ACTION_DELETE_SEQ => PatchAction::DeleteSeq {
+                index: self.usize()?,
+                length: self.usize()?,
+            },
+            ACTION_SPLICE => {
+                let index = self.usize()?;
+                let deleted = self.usize()?;
+                let len = self.usize()?;
+                let inserted = (0..len)
+                    .map(|_| Ok((self.value()?, self.obj()?, self.bool()?)))
+                    .collect::<DecodeResult<_>>()?;
+                PatchAction::Splice {
+                    index,
+                    deleted,
+                    inserted,
+                }
+            }
+            ACTION_MARK => {
+                let len = self.usize()?;
+                let marks = (0..len)
+                    .map(|_| {
+                        let start = self.usize()?;
+                        let end = self.usize()?;
+                        let name = self.string()?;
+                        let value = self.scalar()?;
+                        Ok(Mark::new(name, value, start, end))
+                    })
+                    .collect::<DecodeResult<_>>()?;
+                PatchAction::Mark { marks }
+            }
+            _ => return Err("invalid patch action"),
+        };
+        Ok(Patch { obj, path, action })
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use crate::marks::MarkSet;
+use crate::text_value::TextValue;
+use crate::{ChangeHash, ObjId, ObjType, Prop, ReadDoc, TextUnit};
+
+use super::{Patch, PatchAction};
+
+/// Translates the indexes in patches to text objects from native units into another [`TextUnit`]
+///
+/// Patches index text in the [`TextUnit::Native`] unit. To translate them the translator keeps
+/// a copy of each text object it sees, starting from the state at the heads the patches were
+/// generated from and applying each patch in turn. This means the patches must be translated in
+/// order and none of them may be skipped.
+///
+/// Splices and deletions in text are reported as a deletion of the units which changed followed
+/// by a splice of their new contents. With [`TextUnit::Grapheme`] this means that inserting a
+/// combining character is reported as replacing the grapheme cluster it joins.
+///
+/// # Example
+///
+/// ```
+/// # use automerge::{AutoCommit, ObjType, PatchAction, ReadDoc, TextUnit, ROOT};
+/// # use automerge::patches::TextUnitTranslator;
+/// # use automerge::transaction::Transactable;
+/// let mut doc = AutoCommit::new();
+/// let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+/// doc.splice_text(&text, 0, 0, "🐻‍❄️!").unwrap();
+/// let before = doc.get_heads();
+/// // insert before the "!"
+/// doc.splice_text(&text, doc.length(&text) - 1, 0, "?").unwrap();
+/// let after = doc.get_heads();
+///
+/// let patches = doc.diff(&before, &after);
+/// let patches = TextUnitTranslator::new(&doc, &before, TextUnit::Grapheme).translate(patches);
+/// assert!(matches!(patches[0].action, PatchAction::SpliceText { index: 1, .. }));
+/// ```
+#[derive(Debug)]
+pub struct TextUnitTranslator<'a, R> {
+    doc: &'a R,
+    before: Vec<ChangeHash>,
+    unit: TextUnit,
+    texts: HashMap<ObjId, String>,
+}
+
+impl<'a, R: ReadDoc> TextUnitTranslator<'a, R> {
+    /// Create a translator for patches which were generated starting from the state of `doc` at
+    /// `before`
+    pub fn new(doc: &'a R, before: &[ChangeHash], unit: TextUnit) -> Self {
+        Self {
+            doc,
+            before: before.to_vec(),
+            unit,
+            texts: HashMap::new(),
+        }
+    }
+
+    /// Translate `patches`, which must follow any patches previously translated
+    pub fn translate(&mut self, patches: Vec<Patch>) -> Vec<Patch> {
+        if self.unit == TextUnit::Native {
+            return patches;
+        }
+        let mut translated = Vec::with_capacity(patches.len());
+        for patch in patches {
+            self.translate_patch(patch, &mut translated);
+        }
+        translated
+    }
+
+    fn is_text(&self, obj: &ObjId) -> bool {
+        self.doc.object_type(obj) == Ok(ObjType::Text)
+    }
+
+    fn text(&mut self, obj: &ObjId) -> &mut String {
+        let (doc, before) = (self.doc, &self.before);
+        self.texts
+            .entry(obj.clone())
+            .or_insert_with(|| doc.text_at(obj, before).unwrap_or_default())
+    }
+
+    fn translate_patch(&mut self, patch: Patch, out: &mut Vec<Patch>) {
+        let unit = self.unit;
+        let Patch { obj, path, action } = patch;
+        let action = match action {
+            PatchAction::SpliceText {
+                index,
+                value,
+                marks,
+            } => {
+                let text = self.text(&obj);
+                let old = text.clone();
+                text.insert_str(TextUnit::byte_offset(text, index), &value.make_string());
+                return self.replace(obj, path, &old, marks, out);
+            }
+            PatchAction::DeleteSeq { index, length } if self.is_text(&obj) => {
+                let text = self.text(&obj);
+                let old = text.clone();
+                let start = TextUnit::byte_offset(text, index);
+                let end = TextUnit::byte_offset(text, index + length);
+                text.replace_range(start..end, "");
+                return self.replace(obj, path, &old, None, out);
+            }
+            PatchAction::Insert { index, values } if self.is_text(&obj) => {
+                let text = self.text(&obj);
+                let translated = unit.index_from_native(text, index);
+                let at = TextUnit::byte_offset(text, index);
+                text.insert_str(at, &"\u{fffc}".repeat(values.len()));
+                PatchAction::Insert {
+                    index: translated,
+                    values,
+                }
+            }
+            PatchAction::PutSeq {
+                index,
+                value,
+                conflict,
+            } if self.is_text(&obj) => PatchAction::PutSeq {
+                index: unit.index_from_native(self.text(&obj), index),
+                value,
+                conflict,
+            },
+            PatchAction::Mark { mut marks } => {
+                let text = self.text(&obj);
+                for mark in marks.iter_mut() {
+                    mark.start = unit.index_from_native(text, mark.start);
+                    mark.end = unit.index_from_native_ceil(text, mark.end);
+                }
+                PatchAction::Mark { marks }
+            }
+            PatchAction::Increment {
+                prop: Prop::Seq(index),
+                value,
+            } if self.is_text(&obj) => PatchAction::Increment {
+                prop: Prop::Seq(unit.index_from_native(self.text(&obj), index)),
+                value,
+            },
+            PatchAction::Conflict {
+                prop: Prop::Seq(index),
+            } if self.is_text(&obj) => PatchAction::Conflict {
+                prop: Prop::Seq(unit.index_from_native(self.text(&obj), index)),
+            },
+            action => action,
+        };
+        out.push(Patch { obj, path, action });
+    }
+
+    /// Emit the patches which turn `old` into the current copy of the text of `obj`,
+    /// measured in units
+    fn replace(
+        &mut self,
+        obj: ObjId,
+        path: Vec<(ObjId, Prop)>,
+        old: &str,
+        marks: Option<MarkSet>,
+        out: &mut Vec<Patch>,
+    ) {
+        let unit = self.unit;
+        let new = self.text(&obj);
+        let old_segments = unit.segments(old).collect::<Vec<_>>();
+        let new_segments = unit.segments(new).collect::<Vec<_>>();
+        let prefix = old_segments
+            .iter()
+            .zip(new_segments.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = old_segments[prefix..]
+            .iter()
+            .rev()
+            .zip(new_segments[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let deleted = &old_segments[prefix..old_segments.len() - suffix];
+        let inserted = &new_segments[prefix..new_segments.len() - suffix];
+        let index = old_segments[..prefix]
+            .iter()
+            .map(|s| unit.len(s))
+            .sum::<usize>();
+        if !deleted.is_empty() {
+            out.push(Patch {
+                obj: obj.clone(),
+                path: path.clone(),
+                action: PatchAction::DeleteSeq {
+                    index,
+                    length: deleted.iter().map(|s| unit.len(s)).sum(),
+                },
+            });
+        }
+        if !inserted.is_empty() {
+            out.push(Patch {
+                obj,
+                path,
+                action: PatchAction::SpliceText {
+                    index,
+                    value: TextValue::new(&inserted.concat()),
+                    marks,
+                },
+            });
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! The two path syntaxes used by the API and the helpers which resolve them
+//!
+//! Dotted paths such as `config.users[3].name` are JSONPath expressions without the leading `$`
+//! and are parsed by [`JsonPath`], so they follow its rules for keys, quoting and indexes. JSON
+//! pointers such as `/config/users/3/name` are split by [`parse_pointer()`] and their tokens are
+//! read as indexes by [`pointer_index()`], which is used everywhere a pointer meets a sequence.
+use crate::exid::ExId;
+use crate::jsonpath::JsonPath;
+use crate::transaction::Transactable;
+use crate::{AutomergeError, ObjType, Prop, ReadDoc, Value, ROOT};
+
+/// Parse a path such as `config.users[3].name` into the props it is made of, returning the props
+/// of the parents and the last prop
+///
+/// The path is a JSONPath expression without the leading `$` which only selects keys and
+/// non-negative indexes. A key which isn't a plain name can be written as a quoted string in
+/// brackets, e.g. `files["a.txt"]`.
+pub(crate) fn parse(path: &str) -> Result<(Vec<Prop>, Prop), AutomergeError> {
+    let expr = if path.starts_with('[') {
+        format!("${}", path)
+    } else {
+        format!("$.{}", path)
+    };
+    let mut props = JsonPath::parse(&expr)
+        .ok()
+        .and_then(|query| query.props())
+        .unwrap_or_default();
+    match props.pop() {
+        Some(last) => Ok((props, last)),
+        None => Err(AutomergeError::InvalidPath(path.to_string())),
+    }
+}
+
+/// Find the object at `props`, returning `None` if part of the path does not exist
+pub(crate) fn resolve<R: ReadDoc + ?Sized>(
+    doc: &R,
+    props: &[Prop],
+) -> Result<Option<ExId>, AutomergeError> {
+    let mut obj = ROOT;
+    for prop in props {
+        match doc.get(&obj, prop.clone())? {
+            Some((Value::Object(_), id)) => obj = id,
+            Some((Value::Scalar(_), _)) => return Err(AutomergeError::NotAnObject),
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(obj))
+}
+
+/// Find the object at `parents`, which contains `last`, creating any missing objects along the
+/// way
+///
+/// A missing object is created as a list if the prop which follows it is an index and as a map
+/// otherwise. An index one past the end of a list appends a new object to the list.
+pub(crate) fn resolve_parent_or_create<T: Transactable + ?Sized>(
+    doc: &mut T,
+    parents: &[Prop],
+    last: &Prop,
+) -> Result<ExId, AutomergeError> {
+    let mut obj = ROOT;
+    let next_props = parents.iter().skip(1).chain(std::iter::once(last));
+    for (prop, next) in parents.iter().zip(next_props) {
+        let typ = match next {
+            Prop::Seq(_) => ObjType::List,
+            Prop::Map(_) => ObjType::Map,
+        };
+        obj = match doc.get(&obj, prop.clone())? {
+            Some((Value::Object(_), id)) => id,
+            Some((Value::Scalar(_), _)) => return Err(AutomergeError::NotAnObject),
+            None => match prop {
+                Prop::Seq(index) if *index == doc.length(&obj) => {
+                    doc.insert_object(&obj, *index, typ)?
+                }
+                prop => doc.put_object(&obj, prop.clone(), typ)?,
+            },
+        };
+    }
+    Ok(obj)
+}
+
+/// The index to insert at if `prop` is one past the end of the sequence `obj`
+pub(crate) fn append_index<R: ReadDoc + ?Sized>(
+    doc: &R,
+    obj: &ExId,
+    prop: &Prop,
+) -> Result<Option<usize>, AutomergeError> {
+    match prop {
+        Prop::Seq(index) if doc.object_type(obj)?.is_sequence() && *index == doc.length(obj) => {
+            Ok(Some(*index))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Format `path` as a JSON pointer (RFC 6901)
+pub(crate) fn json_pointer(path: &[Prop]) -> String {
+    path.iter()
+        .map(|prop| match prop {
+            Prop::Map(key) => format!("/{}", key.replace('~', "~0").replace('/', "~1")),
+            Prop::Seq(index) => format!("/{}", index),
+        })
+        .collect()
+}
+
+/// Describe `path` in an error message, as a JSON pointer or as "the root"
+pub(crate) fn location(path: &[Prop]) -> String {
+    if path.is_empty() {
+        "the root".to_string()
+    } else {
+        json_pointer(path)
+    }
+}
+
+/// Split a JSON pointer (RFC 6901) such as `/todos/0/title` into its unescaped reference tokens
+///
+/// The empty pointer refers to the root and has no tokens.
+pub(crate) fn parse_pointer(pointer: &str) -> Result<Vec<String>, AutomergeError> {
+    let invalid = || AutomergeError::InvalidPath(pointer.to_string());
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(tokens) = pointer.strip_prefix('/') else {
+        return Err(invalid());
+    };
+    tokens
+        .split('/')
+        .map(|token| {
+            let mut unescaped = String::with_capacity(token.len());
+            let mut chars = token.chars();
+            while let Some(c) = chars.next() {
+                if c != '~' {
+                    unescaped.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    Some('0') => unescaped.push('~'),
+                    Some('1') => unescaped.push('/'),
+                    _ => return Err(invalid()),
+                }
+            }
+            Ok(unescaped)
+        })
+        .collect()
+}
+
+/// The index a JSON pointer reference token refers to in a sequence
+///
+/// An index is a run of ASCII digits without leading zeros, so `01`, `+1` and `-` are not
+/// indexes.
+pub(crate) fn pointer_index(token: &str) -> Option<usize> {
+    let canonical = token == "0" || (!token.starts_with('0') && !token.is_empty());
+    if canonical && token.bytes().all(|b| b.is_ascii_digit()) {
+        token.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Whether the JSON pointer reference token `token` refers to `prop`
+pub(crate) fn token_matches(token: &str, prop: &Prop) -> bool {
+    match prop {
+        Prop::Map(key) => key == token,
+        Prop::Seq(index) => pointer_index(token) == Some(*index),
+    }
+}
+
+/// The prop a JSON pointer reference token refers to in `obj`
+///
+/// In a sequence the token must be an index, see [`pointer_index()`], or `-` which refers to the
+/// position one past the last element.
+pub(crate) fn pointer_prop<R: ReadDoc + ?Sized>(
+    doc: &R,
+    obj: &ExId,
+    token: &str,
+) -> Result<Prop, AutomergeError> {
+    match doc.object_type(obj)? {
+        ObjType::Map | ObjType::Table => Ok(Prop::Map(token.to_string())),
+        ObjType::List | ObjType::Text => match token {
+            "-" => Ok(Prop::Seq(doc.length(obj))),
+            t => pointer_index(t)
+                .map(Prop::Seq)
+                .ok_or_else(|| AutomergeError::InvalidPath(token.to_string())),
+        },
+    }
+}
+
+/// Find the object and prop a JSON pointer refers to, returning `None` if the object does not
+/// exist
+pub(crate) fn resolve_pointer<R: ReadDoc + ?Sized>(
+    doc: &R,
+    pointer: &str,
+) -> Result<Option<(ExId, Prop)>, AutomergeError> {
+    let tokens = parse_pointer(pointer)?;
+    let Some((last, parents)) = tokens.split_last() else {
+        return Err(AutomergeError::InvalidPath(pointer.to_string()));
+    };
+    let mut obj = ROOT;
+    for token in parents {
+        let prop = pointer_prop(doc, &obj, token)?;
+        match doc.get(&obj, prop)? {
+            Some((Value::Object(_), id)) => obj = id,
+            Some((Value::Scalar(_), _)) => return Err(AutomergeError::NotAnObject),
+            None => return Ok(None),
+        }
+    }
+    let prop = pointer_prop(doc, &obj, last)?;
+    Ok(Some((obj, prop)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, parse_pointer, pointer_index};
+    use crate::Prop;
+
+    #[test]
+    fn parses_json_pointers() {
+        assert_eq!(parse_pointer("").unwrap(), Vec::<String>::new());
+        assert_eq!(parse_pointer("/").unwrap(), vec![""]);
+        assert_eq!(
+            parse_pointer("/a~1b/m~0n/0").unwrap(),
+            vec!["a/b", "m~n", "0"]
+        );
+        for invalid in ["a", "/a~", "/a~2"] {
+            assert!(
+                parse_pointer(invalid).is_err(),
+                "{:?} should not parse",
+                invalid
+            );
+        }
+        assert_eq!(pointer_index("0"), Some(0));
+        assert_eq!(pointer_index("10"), Some(10));
+        for invalid in ["", "-", "01", "+1", "-1", "1a"] {
+            assert_eq!(
+                pointer_index(invalid),
+                None,
+                "{:?} is not an index",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn parses_keys_indexes_and_quoted_keys() {
+        assert_eq!(
+            parse("config.users[3].name").unwrap(),
+            (
+                vec![Prop::from("config"), Prop::from("users"), Prop::from(3)],
+                Prop::from("name")
+            )
+        );
+        assert_eq!(
+            parse("files[\"a.txt\"][0]['b\\'c']").unwrap(),
+            (
+                vec![Prop::from("files"), Prop::from("a.txt"), Prop::from(0)],
+                Prop::from("b'c")
+            )
+        );
+        assert_eq!(parse("[\"a b\"]").unwrap(), (vec![], Prop::from("a b")));
+        for invalid in [
+            "", ".a", "a.", "a..b", "a[x]", "a[1", "a[1]b", "a.[1]", "a[-1]", "a[*]", "a[0:1]",
+            "a b",
+        ] {
+            assert!(parse(invalid).is_err(), "{:?} should not parse", invalid);
+        }
+    }
+}
@@ -2,11 +2,11 @@ use crate::{
     error::AutomergeError,
     exid::ExId,
     hydrate,
-    iter::Spans,
-    iter::{Keys, ListRange, MapRange, Values},
+    iter::{Keys, ListRange, MapEntriesAll, MapRange, Values},
+    iter::{Spans, TextChars},
     marks::{Mark, MarkSet},
     parents::Parents,
-    Change, ChangeHash, Cursor, ObjType, Prop, Value,
+    Change, ChangeHash, Cursor, ObjType, Prop, TextUnit, Value,
 };
 
 use std::{collections::HashMap, ops::RangeBounds};
@@ -57,8 +57,9 @@ pub trait ReadDoc {
     ///
     /// If the object correspoding to `obj` is a list then this will return an empty iterator
     ///
-    /// The returned iterator yields `(key, value, exid)` tuples, where the
-    /// third element is the ID of the operation which created the value.
+    /// The returned iterator yields [`MapRangeItem`](crate::iter::MapRangeItem)s holding the key,
+    /// the value and the ID of the operation which created the value. Each item converts into a
+    /// `(key, value, exid)` tuple.
     fn map_range<'a, O: AsRef<ExId>, R: RangeBounds<String> + 'a>(
         &'a self,
         obj: O,
@@ -70,8 +71,9 @@ pub trait ReadDoc {
     ///
     /// If the object correspoding to `obj` is a list then this will return an empty iterator
     ///
-    /// The returned iterator yields `(key, value, exid)` tuples, where the
-    /// third element is the ID of the operation which created the value.
+    /// The returned iterator yields [`MapRangeItem`](crate::iter::MapRangeItem)s holding the key,
+    /// the value and the ID of the operation which created the value. Each item converts into a
+    /// `(key, value, exid)` tuple.
     ///
     /// See [`Self::map_range()`]
     fn map_range_at<'a, O: AsRef<ExId>, R: RangeBounds<String> + 'a>(
@@ -83,8 +85,10 @@ pub trait ReadDoc {
 
     /// Iterate over the indexes and values of the list or text `obj` in the given range.
     ///
-    /// The reuturned iterator yields `(index, value, exid)` tuples, where the third
-    /// element is the ID of the operation which created the value.
+    /// The returned iterator yields [`ListRangeItem`](crate::iter::ListRangeItem)s holding the
+    /// index, the value and the ID of the operation which created the value. Each item converts
+    /// into an `(index, value, exid)` tuple. Iteration stops as soon as the end of `range` is
+    /// reached.
     fn list_range<O: AsRef<ExId>, R: RangeBounds<usize>>(
         &self,
         obj: O,
@@ -93,8 +97,10 @@ pub trait ReadDoc {
 
     /// Iterate over the indexes and values of the list or text `obj` in the given range as at `heads`
     ///
-    /// The returned iterator yields `(index, value, exid)` tuples, where the third
-    /// element is the ID of the operation which created the value.
+    /// The returned iterator yields [`ListRangeItem`](crate::iter::ListRangeItem)s holding the
+    /// index, the value and the ID of the operation which created the value. Each item converts
+    /// into an `(index, value, exid)` tuple. Iteration stops as soon as the end of `range` is
+    /// reached.
     ///
     /// See [`Self::list_range()`]
     fn list_range_at<O: AsRef<ExId>, R: RangeBounds<usize>>(
@@ -118,6 +124,23 @@ pub trait ReadDoc {
     /// See [`Self::values()`]
     fn values_at<O: AsRef<ExId>>(&self, obj: O, heads: &[ChangeHash]) -> Values<'_>;
 
+    /// Iterate over every key in the map `obj` along with all of the conflicting values at that
+    /// key.
+    ///
+    /// Where [`Self::map_range()`] yields only the winning value for each key, this yields every
+    /// concurrently written value, each tagged with the ID of the operation which created it.
+    /// This is useful for tools which need to export a document faithfully and resolve conflicts
+    /// elsewhere.
+    ///
+    /// If the object correspoding to `obj` is a list then this will return an empty iterator
+    fn map_entries_all<O: AsRef<ExId>>(&self, obj: O) -> MapEntriesAll<'_>;
+
+    /// Iterate over every key and all of the conflicting values in the map `obj` as at `heads`
+    ///
+    /// See [`Self::map_entries_all()`]
+    fn map_entries_all_at<O: AsRef<ExId>>(&self, obj: O, heads: &[ChangeHash])
+        -> MapEntriesAll<'_>;
+
     /// Get the length of the given object.
     ///
     /// If the given object is not in this document this method will return `0`
@@ -130,6 +153,39 @@ pub trait ReadDoc {
     /// See [`Self::length()`]
     fn length_at<O: AsRef<ExId>>(&self, obj: O, heads: &[ChangeHash]) -> usize;
 
+    /// Get the length of the given object, measuring text objects in `unit`
+    ///
+    /// Objects which are not text are measured as by [`Self::length()`]. Any unit other than
+    /// [`TextUnit::Native`] requires reading the whole text.
+    fn length_in<O: AsRef<ExId>>(&self, obj: O, unit: TextUnit) -> usize {
+        match self.object_type(obj.as_ref()) {
+            Ok(ObjType::Text) if unit != TextUnit::Native => {
+                self.text(obj).map(|text| unit.len(&text)).unwrap_or(0)
+            }
+            _ => self.length(obj),
+        }
+    }
+
+    /// Get the length of the given object as at `heads`, measuring text objects in `unit`
+    ///
+    /// See [`Self::length_in()`]
+    fn length_in_at<O: AsRef<ExId>>(&self, obj: O, unit: TextUnit, heads: &[ChangeHash]) -> usize {
+        match self.object_type(obj.as_ref()) {
+            Ok(ObjType::Text) if unit != TextUnit::Native => self
+                .text_at(obj, heads)
+                .map(|text| unit.len(&text))
+                .unwrap_or(0),
+            _ => self.length_at(obj, heads),
+        }
+    }
+
+    /// Get the length of the given object, measuring text objects in UTF-16 code units
+    ///
+    /// See [`Self::length_in()`] and [`TextUnit::Utf16`]
+    fn length_utf16<O: AsRef<ExId>>(&self, obj: O) -> usize {
+        self.length_in(obj, TextUnit::Utf16)
+    }
+
     /// Get the type of this object, if it is an object.
     fn object_type<O: AsRef<ExId>>(&self, obj: O) -> Result<ObjType, AutomergeError>;
 
@@ -161,6 +217,22 @@ pub trait ReadDoc {
         heads: &[ChangeHash],
     ) -> Result<String, AutomergeError>;
 
+    /// Iterate over the characters of the text object `obj`
+    ///
+    /// Each character is yielded along with the ID of the sequence element it belongs to and the
+    /// actor which inserted it. Element IDs do not change as the text is edited, which makes this
+    /// the building block for attribution, cursors and decorations that would otherwise need a
+    /// query per character.
+    fn text_chars<O: AsRef<ExId>>(&self, obj: O) -> Result<TextChars<'_>, AutomergeError>;
+
+    /// Iterate over the characters of the text object `obj` as at `heads`, see
+    /// [`Self::text_chars()`]
+    fn text_chars_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<TextChars<'_>, AutomergeError>;
+
     /// Return the sequence of text and block markers in the text object `obj`
     fn spans<O: AsRef<ExId>>(&self, obj: O) -> Result<Spans<'_>, AutomergeError>;
 
@@ -226,6 +298,69 @@ pub trait ReadDoc {
         heads: &[ChangeHash],
     ) -> Result<Option<(Value<'_>, ExId)>, AutomergeError>;
 
+    /// Get the value at `path`, starting from the root of the document
+    ///
+    /// A path is a list of map keys separated by `.`, with list indexes written in brackets, for
+    /// example `config.users[3].name`. This is a [`crate::jsonpath::JsonPath`] without the
+    /// leading `$` which only selects keys and indexes, so keys which are not made of letters,
+    /// digits, `_` and `-` are written as a quoted string in brackets, e.g. `files["a.txt"]`.
+    /// Returns `None` if any part of the path does not exist, [`AutomergeError::InvalidPath`] if
+    /// the path can't be parsed and [`AutomergeError::NotAnObject`] if the path goes through a
+    /// scalar value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ReadDoc, Value};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = AutoCommit::new();
+    /// doc.put_path("config.users[0].name", "alice").unwrap();
+    /// let (name, _) = doc.get_path("config.users[0].name").unwrap().unwrap();
+    /// assert_eq!(name, Value::from("alice"));
+    /// assert!(doc.get_path("config.groups[0]").unwrap().is_none());
+    /// ```
+    fn get_path(&self, path: &str) -> Result<Option<(Value<'_>, ExId)>, AutomergeError> {
+        let (parents, prop) = crate::path::parse(path)?;
+        match crate::path::resolve(self, &parents)? {
+            Some(obj) => self.get(&obj, prop),
+            None => Ok(None),
+        }
+    }
+
+    /// Find the object and property a JSON pointer (RFC 6901) such as `/todos/0/title` refers
+    /// to
+    ///
+    /// Returns `None` if the object containing the property does not exist, but the property
+    /// itself need not exist. In a list the last reference token must be an index, or `-` which
+    /// refers to the position after the last element.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ObjType, Prop, ReadDoc, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = AutoCommit::new();
+    /// let todos = doc.put_object(ROOT, "todos", ObjType::List).unwrap();
+    /// doc.insert(&todos, 0, "shop").unwrap();
+    /// assert_eq!(doc.resolve_pointer("/todos/-").unwrap(), Some((todos, Prop::Seq(1))));
+    /// ```
+    fn resolve_pointer(&self, pointer: &str) -> Result<Option<(ExId, Prop)>, AutomergeError> {
+        crate::path::resolve_pointer(self, pointer)
+    }
+
+    /// Get the value a JSON pointer refers to, see [`Self::resolve_pointer()`]
+    ///
+    /// The empty pointer refers to the root of the document.
+    fn get_pointer(&self, pointer: &str) -> Result<Option<(Value<'_>, ExId)>, AutomergeError> {
+        if pointer.is_empty() {
+            return Ok(Some((Value::Object(ObjType::Map), ExId::Root)));
+        }
+        match self.resolve_pointer(pointer)? {
+            Some((obj, prop)) => self.get(&obj, prop),
+            None => Ok(None),
+        }
+    }
+
     fn hydrate<O: AsRef<ExId>>(
         &self,
         obj: O,
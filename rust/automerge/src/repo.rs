@@ -0,0 +1,325 @@
+//! Many documents, their storage and their sync with other peers
+//!
+//! A [`Repo`] owns a collection of documents keyed by [`DocId`]. Each document is reached
+//! through a [`DocHandle`], which writes changes to the repo's [`Storage`] as they are made and
+//! tells subscribers about them. The repo keeps a [`sync::MultiPeer`] for every document, so an
+//! application only has to move the messages from
+//! [`Repo::generate_sync_messages()`] to the other peer and hand the replies to
+//! [`Repo::receive_sync_message()`]. How messages travel between peers is up to the application.
+//!
+//! ```
+//! # use std::sync::Arc;
+//! # use automerge::{ReadDoc, ROOT, transaction::Transactable};
+//! # use automerge::repo::{MemoryStorage, Repo};
+//! let alice = Repo::new();
+//! let bob = Repo::with_storage(Arc::new(MemoryStorage::default()));
+//!
+//! let doc = alice.create();
+//! doc.transact(|tx| tx.put(ROOT, "title", "Notes")).unwrap().unwrap();
+//!
+//! // sync until neither peer has anything more to say
+//! loop {
+//!     let to_bob = alice.generate_sync_messages(&"bob".into());
+//!     let to_alice = bob.generate_sync_messages(&"alice".into());
+//!     if to_bob.is_empty() && to_alice.is_empty() {
+//!         break;
+//!     }
+//!     for (id, message) in to_bob {
+//!         bob.receive_sync_message(&"alice".into(), &id, message).unwrap();
+//!     }
+//!     for (id, message) in to_alice {
+//!         alice.receive_sync_message(&"bob".into(), &id, message).unwrap();
+//!     }
+//! }
+//!
+//! let synced = bob.find(doc.id()).unwrap().unwrap();
+//! let synced = synced.read();
+//! let (title, _) = synced.get(ROOT, "title").unwrap().unwrap();
+//! assert_eq!(title.to_str(), Some("Notes"));
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::link::DocId;
+use crate::sync;
+use crate::{Automerge, AutomergeError};
+
+#[cfg(feature = "gossip")]
+pub mod gossip;
+mod handle;
+mod storage;
+#[cfg(feature = "websocket")]
+pub mod websocket;
+
+pub use handle::{DocEvent, DocHandle, ExclusiveHandle, Origin, ReadOnlyHandle};
+pub use storage::{FileStorage, MemoryStorage, Storage, StorageLock};
+
+/// The ID of a peer a [`Repo`] syncs with
+///
+/// The repo only uses the ID to tell peers apart, so it can be anything the application uses to
+/// identify a connection.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PeerId(String);
+
+impl PeerId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for PeerId {
+    fn from(id: &str) -> Self {
+        Self(id.to_string())
+    }
+}
+
+impl From<String> for PeerId {
+    fn from(id: String) -> Self {
+        Self(id)
+    }
+}
+
+impl fmt::Display for PeerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RepoError {
+    #[error("error accessing storage: {0}")]
+    Storage(#[from] io::Error),
+    #[error(transparent)]
+    Automerge(#[from] AutomergeError),
+    /// The document has an [`ExclusiveHandle`], in this repo or, if its [`Storage`] supports
+    /// locking, in another process
+    #[error("document {0} is locked by another writer")]
+    Locked(DocId),
+}
+
+/// A collection of documents, see the [module documentation](self)
+#[derive(Default)]
+pub struct Repo {
+    docs: Mutex<HashMap<DocId, DocHandle>>,
+    storage: Option<Arc<dyn Storage>>,
+    syncs: Mutex<HashMap<DocId, sync::MultiPeer<PeerId>>>,
+}
+
+impl Repo {
+    /// A repo which keeps its documents in memory only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A repo which writes its documents to `storage` and loads documents it does not have in
+    /// memory from it
+    pub fn with_storage(storage: Arc<dyn Storage>) -> Self {
+        Self {
+            storage: Some(storage),
+            ..Self::default()
+        }
+    }
+
+    /// Create a new empty document with a random ID
+    pub fn create(&self) -> DocHandle {
+        let handle = DocHandle::new(
+            DocId::random(),
+            Automerge::new(),
+            self.storage.clone(),
+            Vec::new(),
+        );
+        self.docs().insert(handle.id().clone(), handle.clone());
+        handle
+    }
+
+    /// Add `doc` to the repo with ID `id`
+    ///
+    /// If the repo already has a document with this ID `doc` is merged into it.
+    pub fn insert(&self, id: DocId, mut doc: Automerge) -> Result<DocHandle, RepoError> {
+        let handle = self.find_or_create(&id)?;
+        handle.update(|existing| existing.merge(&mut doc))??;
+        Ok(handle)
+    }
+
+    /// The document with ID `id`, loading it from storage if it is not in memory
+    pub fn find(&self, id: &DocId) -> Result<Option<DocHandle>, RepoError> {
+        if let Some(handle) = self.docs().get(id) {
+            return Ok(Some(handle.clone()));
+        }
+        let Some(storage) = &self.storage else {
+            return Ok(None);
+        };
+        let Some(data) = storage.load(id)? else {
+            return Ok(None);
+        };
+        let doc = Automerge::load(&data)?;
+        let saved = doc.get_heads();
+        let handle = DocHandle::new(id.clone(), doc, Some(storage.clone()), saved);
+        // another thread may have loaded the document whilst we were reading it
+        Ok(Some(
+            self.docs().entry(id.clone()).or_insert(handle).clone(),
+        ))
+    }
+
+    /// A handle to the document with ID `id` which can only read it, see [`Self::find()`]
+    ///
+    /// Code which is given a [`ReadOnlyHandle`] can't change the document, as the handle has
+    /// no methods which would.
+    pub fn open_readonly(&self, id: &DocId) -> Result<Option<ReadOnlyHandle>, RepoError> {
+        Ok(self.find(id)?.map(|handle| handle.read_only()))
+    }
+
+    /// The only handle which can change the document with ID `id` until it is dropped, see
+    /// [`Self::find()`]
+    ///
+    /// Whilst it exists, changing the document through a [`DocHandle`] fails with
+    /// [`RepoError::Locked`]. The document is also locked in the repo's [`Storage`], see
+    /// [`Storage::lock()`], so with a storage such as [`FileStorage`] writers in other processes
+    /// are shut out too. Changes received from peers are still applied.
+    ///
+    /// # Errors
+    ///
+    /// [`RepoError::Locked`] if there is already an exclusive handle to the document, here or,
+    /// through the storage, elsewhere.
+    pub fn open_exclusive(&self, id: &DocId) -> Result<Option<ExclusiveHandle>, RepoError> {
+        match self.find(id)? {
+            Some(handle) => handle.exclusive().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// The document with ID `id`, or a new empty document with that ID if there is none
+    pub(super) fn find_or_create(&self, id: &DocId) -> Result<DocHandle, RepoError> {
+        if let Some(handle) = self.find(id)? {
+            return Ok(handle);
+        }
+        let handle = DocHandle::new(
+            id.clone(),
+            Automerge::new(),
+            self.storage.clone(),
+            Vec::new(),
+        );
+        Ok(self.docs().entry(id.clone()).or_insert(handle).clone())
+    }
+
+    /// Remove the document with ID `id` from the repo and its storage
+    ///
+    /// Existing handles to the document keep working but are no longer part of the repo, and
+    /// changes made with them are not stored.
+    pub fn remove(&self, id: &DocId) -> Result<Option<DocHandle>, RepoError> {
+        let removed = self.docs().remove(id);
+        if let Some(storage) = &self.storage {
+            storage.remove(id)?;
+        }
+        self.syncs().remove(id);
+        Ok(removed)
+    }
+
+    /// The IDs of every document in memory or in storage
+    pub fn ids(&self) -> Result<Vec<DocId>, RepoError> {
+        let mut ids = self.docs().keys().cloned().collect::<Vec<_>>();
+        if let Some(storage) = &self.storage {
+            ids.extend(storage.ids()?);
+        }
+        ids.sort();
+        ids.dedup();
+        Ok(ids)
+    }
+
+    /// Generate the next sync message for `peer` for every document in memory which has one
+    ///
+    /// Documents which are only in storage are not offered to peers until they are loaded with
+    /// [`Self::find()`], or a peer sends a message about them.
+    pub fn generate_sync_messages(&self, peer: &PeerId) -> Vec<(DocId, sync::Message)> {
+        let mut handles = self.docs().values().cloned().collect::<Vec<_>>();
+        handles.sort_by(|a, b| a.id().cmp(b.id()));
+        let mut syncs = self.syncs();
+        handles
+            .into_iter()
+            .filter_map(|handle| {
+                let sync = syncs.entry(handle.id().clone()).or_default();
+                let message = sync.generate_sync_message(&handle.read(), peer)?;
+                Some((handle.id().clone(), message))
+            })
+            .collect()
+    }
+
+    /// Generate the next sync message for `peer` about the document with ID `id`
+    pub fn generate_sync_message(
+        &self,
+        peer: &PeerId,
+        id: &DocId,
+    ) -> Result<Option<sync::Message>, RepoError> {
+        let Some(handle) = self.find(id)? else {
+            return Ok(None);
+        };
+        let mut syncs = self.syncs();
+        let sync = syncs.entry(id.clone()).or_default();
+        let message = sync.generate_sync_message(&handle.read(), peer);
+        Ok(message)
+    }
+
+    /// Receive a sync message from `peer` about the document with ID `id`
+    ///
+    /// If the repo does not have the document an empty one is created, so that peers can share
+    /// documents with each other just by syncing them. Any changes are written to storage and
+    /// sent to the subscribers of the document with [`Origin::Peer`].
+    pub fn receive_sync_message(
+        &self,
+        peer: &PeerId,
+        id: &DocId,
+        message: sync::Message,
+    ) -> Result<DocHandle, RepoError> {
+        let handle = self.find_or_create(id)?;
+        let mut syncs = self.syncs();
+        let sync = syncs.entry(id.clone()).or_default();
+        handle.changing(Origin::Peer(peer.clone()), |doc| {
+            Ok(sync.receive_sync_message(doc, peer, message)?)
+        })?;
+        Ok(handle)
+    }
+
+    /// Forget the sync state of `peer`
+    ///
+    /// Call this when the connection to a peer is closed, so that syncing starts afresh when it
+    /// reconnects.
+    pub fn disconnect(&self, peer: &PeerId) {
+        for sync in self.syncs().values_mut() {
+            sync.remove_peer(peer);
+        }
+    }
+
+    /// The peers the repo has sync state for
+    pub fn peer_ids(&self) -> Vec<PeerId> {
+        let mut peers = self
+            .syncs()
+            .values()
+            .flat_map(|sync| sync.peers().cloned())
+            .collect::<Vec<_>>();
+        peers.sort();
+        peers.dedup();
+        peers
+    }
+
+    fn docs(&self) -> MutexGuard<'_, HashMap<DocId, DocHandle>> {
+        self.docs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn syncs(&self) -> MutexGuard<'_, HashMap<DocId, sync::MultiPeer<PeerId>>> {
+        self.syncs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl fmt::Debug for Repo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut ids = self.docs().keys().cloned().collect::<Vec<_>>();
+        ids.sort();
+        f.debug_struct("Repo")
+            .field("docs", &ids)
+            .field("peers", &self.peer_ids())
+            .finish()
+    }
+}
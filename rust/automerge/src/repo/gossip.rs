@@ -0,0 +1,173 @@
+//! Broadcasting the changes to the documents in a [`Repo`] over libp2p gossipsub
+//!
+//! Syncing with each peer separately is wasteful in a peer to peer network where every peer is
+//! interested in the same documents. [`Gossip`] instead publishes new changes to a gossipsub
+//! topic, which the network delivers to every peer subscribed to it, and applies the changes
+//! other peers publish.
+//!
+//! Gossipsub delivers messages at most once and in no particular order. Changes which arrive
+//! before the changes they depend on are held by the document until their dependencies arrive,
+//! and changes which arrive twice are ignored. A peer which joins late or misses messages never
+//! receives the changes it missed from gossip though, so when changes are waiting for
+//! dependencies [`Gossip::receive()`] returns [`GossipEvent::MissingDeps`] and the application
+//! should catch up by running the full sync protocol with that peer, using
+//! [`Repo::generate_sync_message()`] and [`Repo::receive_sync_message()`] over a request-response
+//! protocol or any other connection.
+//!
+//! Each gossip message is the ID of the document, as a LEB128 length followed by the bytes of
+//! the ID, followed by the changes as they are saved by [`crate::Automerge::save_after()`].
+//!
+//! This module needs the `gossip` feature, which requires Rust 1.75.
+
+use std::collections::HashMap;
+
+use libp2p::gossipsub::{self, IdentTopic, MessageId, PublishError, SubscriptionError};
+use sha2::{Digest, Sha256};
+
+use crate::link::DocId;
+use crate::{ChangeHash, ReadDoc};
+
+use super::{DocHandle, Origin, PeerId, Repo, RepoError};
+
+/// The version of `libp2p` the gossipsub types come from
+pub use libp2p;
+
+#[derive(Debug, thiserror::Error)]
+pub enum GossipError {
+    #[error("failed to publish changes: {0}")]
+    Publish(#[from] PublishError),
+    #[error("invalid gossip message")]
+    InvalidMessage,
+    #[error(transparent)]
+    Repo(#[from] RepoError),
+}
+
+/// What [`Gossip::receive()`] did with a message
+#[derive(Debug)]
+pub enum GossipEvent {
+    /// New changes were applied to the document
+    Applied(DocHandle),
+    /// The document already had every change in the message
+    Duplicate(DocHandle),
+    /// Some changes in the document are waiting for `missing` changes which have not arrived.
+    /// Any changes which could be applied were applied. Sync the document with `peer` to catch
+    /// up.
+    MissingDeps {
+        doc: DocHandle,
+        peer: PeerId,
+        missing: Vec<ChangeHash>,
+    },
+}
+
+/// Publishes and receives changes on one gossipsub topic, see the
+/// [module documentation](self)
+#[derive(Debug)]
+pub struct Gossip {
+    topic: IdentTopic,
+    /// The heads of each document as of the last changes published for it
+    published: HashMap<DocId, Vec<ChangeHash>>,
+}
+
+impl Gossip {
+    pub fn new(topic: impl Into<String>) -> Self {
+        Self {
+            topic: IdentTopic::new(topic),
+            published: HashMap::new(),
+        }
+    }
+
+    pub fn topic(&self) -> &IdentTopic {
+        &self.topic
+    }
+
+    /// Subscribe `behaviour` to the topic
+    pub fn subscribe(
+        &self,
+        behaviour: &mut gossipsub::Behaviour,
+    ) -> Result<bool, SubscriptionError> {
+        behaviour.subscribe(&self.topic)
+    }
+
+    /// The message containing the changes to `doc` since the last message published for it, or
+    /// `None` if there are no new changes
+    pub fn changes(&self, doc: &DocHandle) -> Option<Vec<u8>> {
+        let published = self
+            .published
+            .get(doc.id())
+            .map(Vec::as_slice)
+            .unwrap_or(&[]);
+        let changes = doc.read().save_after(published);
+        if changes.is_empty() {
+            return None;
+        }
+        let mut message = Vec::with_capacity(changes.len() + doc.id().as_bytes().len() + 2);
+        leb128::write::unsigned(&mut message, doc.id().as_bytes().len() as u64).unwrap();
+        message.extend_from_slice(doc.id().as_bytes());
+        message.extend_from_slice(&changes);
+        Some(message)
+    }
+
+    /// Publish the changes to `doc` since the last time it was published
+    ///
+    /// Returns `None` if there are no new changes. Call this after changing a document, for
+    /// example on every [`super::DocEvent`] with [`Origin::Local`].
+    pub fn publish(
+        &mut self,
+        behaviour: &mut gossipsub::Behaviour,
+        doc: &DocHandle,
+    ) -> Result<Option<MessageId>, GossipError> {
+        let heads = doc.get_heads();
+        let Some(message) = self.changes(doc) else {
+            return Ok(None);
+        };
+        let id = behaviour.publish(self.topic.clone(), message)?;
+        self.published.insert(doc.id().clone(), heads);
+        Ok(Some(id))
+    }
+
+    /// Apply the changes in a message received from the topic to `repo`
+    ///
+    /// `propagation_source` is the peer which forwarded the message, from
+    /// [`gossipsub::Event::Message`]. Unlike the peer which published the message it is always
+    /// connected, so it is the peer to catch up with. If `repo` does not have the document an
+    /// empty one is created. Any changes are written to storage and sent to the subscribers of
+    /// the document with [`Origin::Peer`].
+    pub fn receive(
+        &self,
+        repo: &Repo,
+        propagation_source: &libp2p::PeerId,
+        message: &gossipsub::Message,
+    ) -> Result<GossipEvent, GossipError> {
+        let mut data = message.data.as_slice();
+        let len = leb128::read::unsigned(&mut data).map_err(|_| GossipError::InvalidMessage)?;
+        let len = usize::try_from(len).map_err(|_| GossipError::InvalidMessage)?;
+        if data.len() < len {
+            return Err(GossipError::InvalidMessage);
+        }
+        let (id, changes) = data.split_at(len);
+        let peer = PeerId::from(propagation_source.to_string());
+
+        let doc = repo.find_or_create(&DocId::from(id))?;
+        let before = doc.get_heads();
+        let missing = doc.changing(Origin::Peer(peer.clone()), |doc| {
+            doc.load_incremental(changes)?;
+            Ok(doc.get_missing_deps(&[]))
+        })?;
+        if !missing.is_empty() {
+            Ok(GossipEvent::MissingDeps { doc, peer, missing })
+        } else if doc.get_heads() != before {
+            Ok(GossipEvent::Applied(doc))
+        } else {
+            Ok(GossipEvent::Duplicate(doc))
+        }
+    }
+}
+
+/// A message ID derived from the content of the message
+///
+/// Gossipsub identifies messages by their source and sequence number by default, so the same
+/// changes published by two peers are delivered twice. Pass this to
+/// [`gossipsub::ConfigBuilder::message_id_fn()`] to identify messages by their content instead.
+pub fn message_id(message: &gossipsub::Message) -> MessageId {
+    MessageId::from(Sha256::digest(&message.data).to_vec())
+}
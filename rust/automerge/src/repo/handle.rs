@@ -0,0 +1,362 @@
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex, PoisonError, RwLock, RwLockReadGuard};
+
+use crate::link::DocId;
+use crate::transaction::{self, Transaction};
+use crate::{Automerge, ChangeHash};
+
+use super::{PeerId, RepoError, Storage, StorageLock};
+
+/// Where the changes in a [`DocEvent`] came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// Changes made through a [`DocHandle`]
+    Local,
+    /// Changes received from a peer, see [`super::Repo::receive_sync_message()`]
+    Peer(PeerId),
+}
+
+/// Sent to the subscribers of a document when it changes, see [`DocHandle::subscribe()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocEvent {
+    pub doc: DocId,
+    /// The heads of the document after the change
+    pub heads: Vec<ChangeHash>,
+    pub origin: Origin,
+}
+
+/// A handle to a document in a [`super::Repo`]
+///
+/// Cloning a handle produces another handle to the same document. Changes made with
+/// [`Self::update()`] or [`Self::transact()`], like changes received from peers, are written to
+/// the repo's storage and sent to the subscribers of the document. Whilst an
+/// [`ExclusiveHandle`] to the document exists, changing it through a `DocHandle` fails with
+/// [`RepoError::Locked`].
+#[derive(Clone)]
+pub struct DocHandle {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    id: DocId,
+    doc: RwLock<Automerge>,
+    storage: Option<Arc<dyn Storage>>,
+    /// The heads of the changes which have been written to storage
+    saved: Mutex<Vec<ChangeHash>>,
+    subscribers: Mutex<Vec<mpsc::Sender<DocEvent>>>,
+    /// Whether an [`ExclusiveHandle`] to the document exists
+    exclusive: AtomicBool,
+}
+
+impl DocHandle {
+    /// A handle to `doc`, whose changes up to `saved` are already in `storage`
+    pub(super) fn new(
+        id: DocId,
+        doc: Automerge,
+        storage: Option<Arc<dyn Storage>>,
+        saved: Vec<ChangeHash>,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                id,
+                doc: RwLock::new(doc),
+                storage,
+                saved: Mutex::new(saved),
+                subscribers: Mutex::new(Vec::new()),
+                exclusive: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    pub fn id(&self) -> &DocId {
+        &self.inner.id
+    }
+
+    /// Acquire shared read access to the document, blocking whilst it is being changed
+    pub fn read(&self) -> RwLockReadGuard<'_, Automerge> {
+        self.inner
+            .doc
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The current heads of the document
+    pub fn get_heads(&self) -> Vec<ChangeHash> {
+        self.read().get_heads()
+    }
+
+    /// Change the document with `f`
+    ///
+    /// If `f` changes the document the new changes are written to storage and the subscribers
+    /// are sent a [`DocEvent`] with [`Origin::Local`].
+    ///
+    /// # Errors
+    ///
+    /// This fails if writing to storage fails. The changes are still in the document, and are
+    /// written to storage with the next change.
+    pub fn update<F, O>(&self, f: F) -> Result<O, RepoError>
+    where
+        F: FnOnce(&mut Automerge) -> O,
+    {
+        self.check_not_exclusive()?;
+        self.changing(Origin::Local, |doc| Ok(f(doc)))
+    }
+
+    /// Run a transaction on the document, see [`Self::update()`] and [`Automerge::transact()`]
+    pub fn transact<F, O, E>(&self, f: F) -> Result<transaction::Result<O, E>, RepoError>
+    where
+        F: FnOnce(&mut Transaction<'_>) -> Result<O, E>,
+    {
+        self.update(|doc| doc.transact(f))
+    }
+
+    /// Receive a [`DocEvent`] on the returned channel whenever the document changes
+    ///
+    /// Dropping the receiver unsubscribes.
+    pub fn subscribe(&self) -> mpsc::Receiver<DocEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.inner
+            .subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(tx);
+        rx
+    }
+
+    /// Replace the data in storage for this document with a compact save of the whole document
+    ///
+    /// Each change is appended to storage as it is made, so the stored data grows with every
+    /// change. Compacting it from time to time makes it smaller and faster to load.
+    pub fn compact(&self) -> Result<(), RepoError> {
+        self.check_not_exclusive()?;
+        self.compact_unchecked()
+    }
+
+    /// A handle which can only read the document
+    pub fn read_only(&self) -> ReadOnlyHandle {
+        ReadOnlyHandle {
+            handle: self.clone(),
+        }
+    }
+
+    /// Become the only handle which can change the document, see
+    /// [`super::Repo::open_exclusive()`]
+    pub(super) fn exclusive(&self) -> Result<ExclusiveHandle, RepoError> {
+        let id = &self.inner.id;
+        if self.inner.exclusive.swap(true, Ordering::AcqRel) {
+            return Err(RepoError::Locked(id.clone()));
+        }
+        let lock = match &self.inner.storage {
+            Some(storage) => storage.lock(id),
+            None => Ok(StorageLock::new(())),
+        };
+        let lock = match lock {
+            Ok(lock) => lock,
+            Err(e) => {
+                self.inner.exclusive.store(false, Ordering::Release);
+                return Err(match e.kind() {
+                    io::ErrorKind::WouldBlock => RepoError::Locked(id.clone()),
+                    _ => RepoError::Storage(e),
+                });
+            }
+        };
+        Ok(ExclusiveHandle {
+            handle: self.clone(),
+            _lock: lock,
+        })
+    }
+
+    fn check_not_exclusive(&self) -> Result<(), RepoError> {
+        if self.inner.exclusive.load(Ordering::Acquire) {
+            Err(RepoError::Locked(self.inner.id.clone()))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn compact_unchecked(&self) -> Result<(), RepoError> {
+        let doc = self
+            .inner
+            .doc
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(storage) = &self.inner.storage {
+            storage.replace(&self.inner.id, &doc.save())?;
+            *self.saved() = doc.get_heads();
+        }
+        Ok(())
+    }
+
+    /// Run `f` on the document whilst holding the write lock, then store and announce any
+    /// changes it made
+    pub(super) fn changing<F, O>(&self, origin: Origin, f: F) -> Result<O, RepoError>
+    where
+        F: FnOnce(&mut Automerge) -> Result<O, RepoError>,
+    {
+        let mut doc = self
+            .inner
+            .doc
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let before = doc.get_heads();
+        let result = f(&mut doc);
+        let heads = doc.get_heads();
+        if heads != before {
+            self.inner
+                .subscribers
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .retain(|subscriber| {
+                    subscriber
+                        .send(DocEvent {
+                            doc: self.inner.id.clone(),
+                            heads: heads.clone(),
+                            origin: origin.clone(),
+                        })
+                        .is_ok()
+                });
+        }
+        self.store(&doc)?;
+        result
+    }
+
+    /// Append the changes which are not in storage yet to storage
+    fn store(&self, doc: &Automerge) -> Result<(), RepoError> {
+        if let Some(storage) = &self.inner.storage {
+            let mut saved = self.saved();
+            let bytes = doc.save_after(&saved);
+            if !bytes.is_empty() {
+                storage.append(&self.inner.id, &bytes)?;
+                *saved = doc.get_heads();
+            }
+        }
+        Ok(())
+    }
+
+    fn saved(&self) -> std::sync::MutexGuard<'_, Vec<ChangeHash>> {
+        self.inner
+            .saved
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl fmt::Debug for DocHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DocHandle")
+            .field("id", &self.inner.id)
+            .finish()
+    }
+}
+
+/// A handle to a document in a [`super::Repo`] which can only read it, see
+/// [`super::Repo::open_readonly()`]
+///
+/// The document can still change, for example when changes are received from peers, and
+/// subscribers are told about those changes as they are by a [`DocHandle`].
+#[derive(Clone)]
+pub struct ReadOnlyHandle {
+    handle: DocHandle,
+}
+
+impl ReadOnlyHandle {
+    pub fn id(&self) -> &DocId {
+        self.handle.id()
+    }
+
+    /// See [`DocHandle::read()`]
+    pub fn read(&self) -> RwLockReadGuard<'_, Automerge> {
+        self.handle.read()
+    }
+
+    /// See [`DocHandle::get_heads()`]
+    pub fn get_heads(&self) -> Vec<ChangeHash> {
+        self.handle.get_heads()
+    }
+
+    /// See [`DocHandle::subscribe()`]
+    pub fn subscribe(&self) -> mpsc::Receiver<DocEvent> {
+        self.handle.subscribe()
+    }
+}
+
+impl fmt::Debug for ReadOnlyHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadOnlyHandle")
+            .field("id", self.id())
+            .finish()
+    }
+}
+
+/// The only handle which can change a document in a [`super::Repo`], see
+/// [`super::Repo::open_exclusive()`]
+///
+/// Dropping the handle lets other handles change the document again and releases the lock on
+/// the document in storage.
+pub struct ExclusiveHandle {
+    handle: DocHandle,
+    _lock: StorageLock,
+}
+
+impl ExclusiveHandle {
+    pub fn id(&self) -> &DocId {
+        self.handle.id()
+    }
+
+    /// See [`DocHandle::read()`]
+    pub fn read(&self) -> RwLockReadGuard<'_, Automerge> {
+        self.handle.read()
+    }
+
+    /// See [`DocHandle::get_heads()`]
+    pub fn get_heads(&self) -> Vec<ChangeHash> {
+        self.handle.get_heads()
+    }
+
+    /// See [`DocHandle::update()`]
+    pub fn update<F, O>(&self, f: F) -> Result<O, RepoError>
+    where
+        F: FnOnce(&mut Automerge) -> O,
+    {
+        self.handle.changing(Origin::Local, |doc| Ok(f(doc)))
+    }
+
+    /// See [`DocHandle::transact()`]
+    pub fn transact<F, O, E>(&self, f: F) -> Result<transaction::Result<O, E>, RepoError>
+    where
+        F: FnOnce(&mut Transaction<'_>) -> Result<O, E>,
+    {
+        self.update(|doc| doc.transact(f))
+    }
+
+    /// See [`DocHandle::subscribe()`]
+    pub fn subscribe(&self) -> mpsc::Receiver<DocEvent> {
+        self.handle.subscribe()
+    }
+
+    /// See [`DocHandle::compact()`]
+    pub fn compact(&self) -> Result<(), RepoError> {
+        self.handle.compact_unchecked()
+    }
+
+    /// A handle which can only read the document
+    pub fn read_only(&self) -> ReadOnlyHandle {
+        self.handle.read_only()
+    }
+}
+
+impl Drop for ExclusiveHandle {
+    fn drop(&mut self) {
+        self.handle.inner.exclusive.store(false, Ordering::Release);
+    }
+}
+
+impl fmt::Debug for ExclusiveHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExclusiveHandle")
+            .field("id", self.id())
+            .finish()
+    }
+}
@@ -0,0 +1,377 @@
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, SystemTime};
+
+use crate::link::DocId;
+
+/// Where a [`super::Repo`] keeps its documents
+///
+/// The data stored for a document is whatever it was last replaced with followed by everything
+/// appended to it since, which is always something [`crate::Automerge::load()`] can load: a
+/// [`super::Repo`] appends the changes made since the last write with
+/// [`crate::Automerge::save_after()`], and replaces the data with a compact save from
+/// [`super::DocHandle::compact()`].
+pub trait Storage: Send + Sync {
+    /// The data stored for `doc`, or `None` if there is none
+    fn load(&self, doc: &DocId) -> io::Result<Option<Vec<u8>>>;
+
+    /// Add `data` to the end of the data stored for `doc`
+    fn append(&self, doc: &DocId, data: &[u8]) -> io::Result<()>;
+
+    /// Replace the data stored for `doc` with `data`
+    fn replace(&self, doc: &DocId, data: &[u8]) -> io::Result<()>;
+
+    /// Remove the data stored for `doc`, if there is any
+    fn remove(&self, doc: &DocId) -> io::Result<()>;
+
+    /// The IDs of every document with data in the store
+    fn ids(&self) -> io::Result<Vec<DocId>>;
+
+    /// Become the only writer of `doc` until the returned lock is dropped
+    ///
+    /// Whilst the lock is held, writes to `doc` through other instances of the storage, which
+    /// may be in other processes, fail. This fails with [`io::ErrorKind::WouldBlock`] if another
+    /// writer holds the lock. The default implementation locks nothing, which is enough for
+    /// storage which is only used by one [`super::Repo`].
+    fn lock(&self, doc: &DocId) -> io::Result<StorageLock> {
+        let _ = doc;
+        Ok(StorageLock::new(()))
+    }
+}
+
+/// A lock on the data stored for a document, which is released when it is dropped, see
+/// [`Storage::lock()`]
+pub struct StorageLock {
+    _guard: Box<dyn Any + Send + Sync>,
+}
+
+impl StorageLock {
+    /// A lock which is released by dropping `guard`
+    pub fn new<G: Any + Send + Sync>(guard: G) -> Self {
+        Self {
+            _guard: Box::new(guard),
+        }
+    }
+}
+
+impl fmt::Debug for StorageLock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StorageLock").finish()
+    }
+}
+
+fn locked(doc: &DocId) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::WouldBlock,
+        format!("document {} is locked by another writer", doc),
+    )
+}
+
+/// A [`Storage`] which keeps documents in memory
+///
+/// Its locks only stop other holders of a lock, as everything which shares the storage is in
+/// one process.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    docs: Mutex<HashMap<DocId, Vec<u8>>>,
+    locks: Arc<Mutex<HashSet<DocId>>>,
+}
+
+impl MemoryStorage {
+    fn docs(&self) -> MutexGuard<'_, HashMap<DocId, Vec<u8>>> {
+        self.docs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+struct MemoryLock {
+    doc: DocId,
+    locks: Arc<Mutex<HashSet<DocId>>>,
+}
+
+impl Drop for MemoryLock {
+    fn drop(&mut self) {
+        self.locks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.doc);
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn load(&self, doc: &DocId) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.docs().get(doc).cloned())
+    }
+
+    fn append(&self, doc: &DocId, data: &[u8]) -> io::Result<()> {
+        self.docs()
+            .entry(doc.clone())
+            .or_default()
+            .extend_from_slice(data);
+        Ok(())
+    }
+
+    fn replace(&self, doc: &DocId, data: &[u8]) -> io::Result<()> {
+        self.docs().insert(doc.clone(), data.to_vec());
+        Ok(())
+    }
+
+    fn remove(&self, doc: &DocId) -> io::Result<()> {
+        self.docs().remove(doc);
+        Ok(())
+    }
+
+    fn ids(&self) -> io::Result<Vec<DocId>> {
+        let mut ids = self.docs().keys().cloned().collect::<Vec<_>>();
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn lock(&self, doc: &DocId) -> io::Result<StorageLock> {
+        let mut locks = self.locks.lock().unwrap_or_else(PoisonError::into_inner);
+        if !locks.insert(doc.clone()) {
+            return Err(locked(doc));
+        }
+        Ok(StorageLock::new(MemoryLock {
+            doc: doc.clone(),
+            locks: self.locks.clone(),
+        }))
+    }
+}
+
+/// A [`Storage`] which keeps each document in a file in a directory
+///
+/// The data for a document is in `<id>.automerge`, where `<id>` is the hex form of its
+/// [`DocId`]. Appends go to the end of the file and replacements are written to a temporary
+/// file which is renamed over it, so a crash never leaves a partly replaced document.
+///
+/// # Locking
+///
+/// Writes are guarded by an advisory lock file, `<id>.lock`, which is created for the duration
+/// of each write, or for as long as a [`StorageLock`] from [`Storage::lock()`] is held. A write
+/// which finds another writer's lock fails with [`io::ErrorKind::WouldBlock`] instead of
+/// interleaving its data with the other writer's, so two processes which open the same
+/// directory cannot corrupt a document. The lock files work the same way on every platform,
+/// but only protect against writers which use them.
+///
+/// A lock whose file has not been touched for [`Self::with_stale_lock_timeout()`], 30 seconds
+/// by default, is taken to belong to a writer which crashed and is removed. A held
+/// [`StorageLock`] is refreshed by every write through it, so a writer which holds one for
+/// longer than the timeout without writing can lose it; its next write then fails rather than
+/// overwriting the new writer's data.
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    dir: PathBuf,
+    stale_after: Duration,
+    /// The tokens of the locks held through [`Storage::lock()`] by this storage
+    held: Arc<Mutex<HashMap<DocId, String>>>,
+}
+
+impl FileStorage {
+    /// Store documents in `dir`, creating it if it doesn't exist
+    pub fn open<P: Into<PathBuf>>(dir: P) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            stale_after: Duration::from_secs(30),
+            held: Arc::default(),
+        })
+    }
+
+    /// Treat locks which have not been touched for `timeout` as stale, see the
+    /// [type documentation](Self#locking)
+    pub fn with_stale_lock_timeout(self, timeout: Duration) -> Self {
+        Self {
+            stale_after: timeout,
+            ..self
+        }
+    }
+
+    fn data_path(&self, doc: &DocId) -> PathBuf {
+        self.dir.join(format!("{}.automerge", doc))
+    }
+
+    fn lock_path(&self, doc: &DocId) -> PathBuf {
+        self.dir.join(format!("{}.lock", doc))
+    }
+
+    fn held(&self) -> MutexGuard<'_, HashMap<DocId, String>> {
+        self.held.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Create the lock file for `doc`, removing it first if it is stale
+    fn acquire(&self, doc: &DocId) -> io::Result<LockFile> {
+        let path = self.lock_path(doc);
+        let token = uuid::Uuid::new_v4().to_string();
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    file.write_all(token.as_bytes())?;
+                    return Ok(LockFile { path, token });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if !self.is_stale(&path)? {
+                        return Err(locked(doc));
+                    }
+                    match fs::remove_file(&path) {
+                        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                        _ => {}
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(locked(doc))
+    }
+
+    fn is_stale(&self, lock: &Path) -> io::Result<bool> {
+        match fs::metadata(lock) {
+            Ok(metadata) => Ok(metadata
+                .modified()?
+                .elapsed()
+                .is_ok_and(|age| age > self.stale_after)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(true),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Run `write` whilst holding the lock for `doc`
+    fn write_locked<F>(&self, doc: &DocId, write: F) -> io::Result<()>
+    where
+        F: FnOnce() -> io::Result<()>,
+    {
+        let token = self.held().get(doc).cloned();
+        match token {
+            Some(token) => {
+                let path = self.lock_path(doc);
+                if !owns(&path, &token) {
+                    return Err(locked(doc));
+                }
+                File::options()
+                    .write(true)
+                    .open(&path)?
+                    .set_modified(SystemTime::now())?;
+                write()
+            }
+            None => {
+                let _lock = self.acquire(doc)?;
+                write()
+            }
+        }
+    }
+}
+
+/// A lock file created by [`FileStorage`], which is removed when this is dropped unless another
+/// writer has taken it over
+struct LockFile {
+    path: PathBuf,
+    token: String,
+}
+
+/// Whether the lock file at `path` was created with `token`
+fn owns(path: &Path, token: &str) -> bool {
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut contents))
+        .is_ok()
+        && contents == token
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        if owns(&self.path, &self.token) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+struct HeldLock {
+    doc: DocId,
+    held: Arc<Mutex<HashMap<DocId, String>>>,
+    _file: LockFile,
+}
+
+impl Drop for HeldLock {
+    fn drop(&mut self) {
+        self.held
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.doc);
+    }
+}
+
+impl Storage for FileStorage {
+    fn load(&self, doc: &DocId) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.data_path(doc)) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn append(&self, doc: &DocId, data: &[u8]) -> io::Result<()> {
+        self.write_locked(doc, || {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.data_path(doc))?;
+            file.write_all(data)?;
+            file.sync_data()
+        })
+    }
+
+    fn replace(&self, doc: &DocId, data: &[u8]) -> io::Result<()> {
+        self.write_locked(doc, || {
+            let path = self.data_path(doc);
+            let temporary = path.with_extension("automerge.tmp");
+            let mut file = File::create(&temporary)?;
+            file.write_all(data)?;
+            file.sync_data()?;
+            fs::rename(&temporary, &path)
+        })
+    }
+
+    fn remove(&self, doc: &DocId) -> io::Result<()> {
+        self.write_locked(doc, || match fs::remove_file(self.data_path(doc)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        })
+    }
+
+    fn ids(&self) -> io::Result<Vec<DocId>> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("automerge") {
+                continue;
+            }
+            if let Some(id) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse().ok())
+            {
+                ids.push(id);
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn lock(&self, doc: &DocId) -> io::Result<StorageLock> {
+        if self.held().contains_key(doc) {
+            return Err(locked(doc));
+        }
+        let file = self.acquire(doc)?;
+        self.held().insert(doc.clone(), file.token.clone());
+        Ok(StorageLock::new(HeldLock {
+            doc: doc.clone(),
+            held: self.held.clone(),
+            _file: file,
+        }))
+    }
+}
@@ -0,0 +1,648 @@
+//! Syncing a [`Repo`] with peers over WebSockets
+//!
+//! A [`Connection`] sends the sync messages for the documents in a repo over a WebSocket and
+//! applies the ones it receives. Messages are framed the way the JavaScript `automerge-repo`
+//! network adapters frame them, so a [`Connection`] can talk to the sync servers and clients of
+//! that ecosystem. Each WebSocket binary message is one CBOR map with a `type` field:
+//!
+//! | `type`            | fields                                                                  |
+//! |-------------------|-------------------------------------------------------------------------|
+//! | `join`            | `senderId`, `peerMetadata`, `supportedProtocolVersions`                 |
+//! | `peer`            | `senderId`, `targetId`, `peerMetadata`, `selectedProtocolVersion`       |
+//! | `sync`, `request` | `senderId`, `targetId`, `documentId`, `data` (a [`sync::Message`])      |
+//! | `doc-unavailable` | `senderId`, `targetId`, `documentId`                                    |
+//! | `ephemeral`       | `senderId`, `targetId`, `documentId`, `sessionId`, `count`, `data`      |
+//! | `error`           | `message`                                                               |
+//!
+//! The client opens the connection with `join` and the server answers with `peer`, after which
+//! both sides send `sync` messages. A peer which is looking for a document it does not have
+//! sends `request` instead of `sync`, and is answered with `doc-unavailable` if the other side
+//! does not have it either. Document IDs are the bytes of a [`DocId`] in base58check. Messages
+//! with any other `type` are ignored.
+//!
+//! ```no_run
+//! # use std::net::TcpListener;
+//! # use std::sync::Arc;
+//! # use automerge::repo::Repo;
+//! # use automerge::repo::websocket::{tungstenite, Connection, Received};
+//! let repo = Arc::new(Repo::new());
+//! let listener = TcpListener::bind("127.0.0.1:3030").unwrap();
+//! for stream in listener.incoming() {
+//!     let socket = tungstenite::accept(stream.unwrap()).unwrap();
+//!     let mut connection = Connection::accept(repo.clone(), "server".into(), socket).unwrap();
+//!     while !matches!(connection.receive().unwrap(), Received::Closed) {}
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use ciborium::value::Value as Cbor;
+use tungstenite::{Message as WsMessage, WebSocket};
+
+use crate::link::DocId;
+use crate::sync::{self, ReadMessageError};
+
+use super::{DocHandle, PeerId, Repo, RepoError};
+
+/// The version of `tungstenite` the [`Connection`] sockets come from
+pub use tungstenite;
+
+/// The version of the protocol a [`Connection`] speaks
+pub const PROTOCOL_VERSION: &str = "1";
+
+#[derive(Debug, thiserror::Error)]
+pub enum WebSocketError {
+    #[error("WebSocket error: {0}")]
+    WebSocket(Box<tungstenite::Error>),
+    #[error("invalid message: {0}")]
+    InvalidFrame(String),
+    #[error("expected a {expected} message but received a {received} message")]
+    UnexpectedFrame {
+        expected: &'static str,
+        received: &'static str,
+    },
+    #[error("the peer does not support protocol version {PROTOCOL_VERSION}")]
+    UnsupportedVersion,
+    #[error("the connection was closed")]
+    Closed,
+    #[error("the peer reported an error: {0}")]
+    Remote(String),
+    #[error("invalid sync message: {0}")]
+    Sync(#[from] ReadMessageError),
+    #[error(transparent)]
+    Repo(#[from] RepoError),
+}
+
+impl From<tungstenite::Error> for WebSocketError {
+    fn from(e: tungstenite::Error) -> Self {
+        Self::WebSocket(Box::new(e))
+    }
+}
+
+/// One message of the protocol, see the [module documentation](self)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frame {
+    Join {
+        sender_id: PeerId,
+        supported_protocol_versions: Vec<String>,
+    },
+    Peer {
+        sender_id: PeerId,
+        target_id: PeerId,
+        selected_protocol_version: String,
+    },
+    Sync {
+        sender_id: PeerId,
+        target_id: PeerId,
+        document_id: DocId,
+        data: Vec<u8>,
+    },
+    Request {
+        sender_id: PeerId,
+        target_id: PeerId,
+        document_id: DocId,
+        data: Vec<u8>,
+    },
+    DocUnavailable {
+        sender_id: PeerId,
+        target_id: PeerId,
+        document_id: DocId,
+    },
+    Ephemeral {
+        sender_id: PeerId,
+        target_id: PeerId,
+        document_id: DocId,
+        session_id: String,
+        count: u64,
+        data: Vec<u8>,
+    },
+    Error {
+        message: String,
+    },
+    /// A message with a `type` this version does not know
+    Unknown(String),
+}
+
+impl Frame {
+    /// The `type` field of the message
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Join { .. } => "join",
+            Self::Peer { .. } => "peer",
+            Self::Sync { .. } => "sync",
+            Self::Request { .. } => "request",
+            Self::DocUnavailable { .. } => "doc-unavailable",
+            Self::Ephemeral { .. } => "ephemeral",
+            Self::Error { .. } => "error",
+            Self::Unknown(_) => "unknown",
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut fields = vec![("type", Cbor::Text(self.type_name().to_string()))];
+        match self {
+            Self::Join {
+                sender_id,
+                supported_protocol_versions,
+            } => {
+                fields.push(("senderId", peer(sender_id)));
+                fields.push(("peerMetadata", Cbor::Map(Vec::new())));
+                fields.push((
+                    "supportedProtocolVersions",
+                    Cbor::Array(
+                        supported_protocol_versions
+                            .iter()
+                            .map(|v| Cbor::Text(v.clone()))
+                            .collect(),
+                    ),
+                ));
+            }
+            Self::Peer {
+                sender_id,
+                target_id,
+                selected_protocol_version,
+            } => {
+                fields.push(("senderId", peer(sender_id)));
+                fields.push(("targetId", peer(target_id)));
+                fields.push(("peerMetadata", Cbor::Map(Vec::new())));
+                fields.push((
+                    "selectedProtocolVersion",
+                    Cbor::Text(selected_protocol_version.clone()),
+                ));
+            }
+            Self::Sync {
+                sender_id,
+                target_id,
+                document_id,
+                data,
+            }
+            | Self::Request {
+                sender_id,
+                target_id,
+                document_id,
+                data,
+            } => {
+                fields.push(("senderId", peer(sender_id)));
+                fields.push(("targetId", peer(target_id)));
+                fields.push(("documentId", Cbor::Text(encode_document_id(document_id))));
+                fields.push(("data", Cbor::Bytes(data.clone())));
+            }
+            Self::DocUnavailable {
+                sender_id,
+                target_id,
+                document_id,
+            } => {
+                fields.push(("senderId", peer(sender_id)));
+                fields.push(("targetId", peer(target_id)));
+                fields.push(("documentId", Cbor::Text(encode_document_id(document_id))));
+            }
+            Self::Ephemeral {
+                sender_id,
+                target_id,
+                document_id,
+                session_id,
+                count,
+                data,
+            } => {
+                fields.push(("senderId", peer(sender_id)));
+                fields.push(("targetId", peer(target_id)));
+                fields.push(("documentId", Cbor::Text(encode_document_id(document_id))));
+                fields.push(("sessionId", Cbor::Text(session_id.clone())));
+                fields.push(("count", Cbor::Integer((*count).into())));
+                fields.push(("data", Cbor::Bytes(data.clone())));
+            }
+            Self::Error { message } => {
+                fields.push(("message", Cbor::Text(message.clone())));
+            }
+            Self::Unknown(ty) => {
+                fields[0].1 = Cbor::Text(ty.clone());
+            }
+        }
+        let map = Cbor::Map(
+            fields
+                .into_iter()
+                .map(|(k, v)| (Cbor::Text(k.to_string()), v))
+                .collect(),
+        );
+        let mut bytes = Vec::new();
+        // writing to a Vec can't fail
+        ciborium::ser::into_writer(&map, &mut bytes).unwrap();
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, WebSocketError> {
+        let value: Cbor = ciborium::de::from_reader(bytes)
+            .map_err(|e| WebSocketError::InvalidFrame(e.to_string()))?;
+        let Cbor::Map(entries) = value else {
+            return Err(WebSocketError::InvalidFrame("not a map".to_string()));
+        };
+        let fields = Fields(
+            entries
+                .into_iter()
+                .filter_map(|(k, v)| match k {
+                    Cbor::Text(k) => Some((k, v)),
+                    _ => None,
+                })
+                .collect(),
+        );
+        let frame = match fields.text("type")?.as_str() {
+            "join" => Self::Join {
+                sender_id: fields.text("senderId")?.into(),
+                supported_protocol_versions: fields.texts("supportedProtocolVersions")?,
+            },
+            "peer" => Self::Peer {
+                sender_id: fields.text("senderId")?.into(),
+                target_id: fields.text("targetId")?.into(),
+                selected_protocol_version: fields.text("selectedProtocolVersion")?,
+            },
+            "sync" => Self::Sync {
+                sender_id: fields.text("senderId")?.into(),
+                target_id: fields.text("targetId")?.into(),
+                document_id: fields.document_id()?,
+                data: fields.bytes("data")?,
+            },
+            "request" => Self::Request {
+                sender_id: fields.text("senderId")?.into(),
+                target_id: fields.text("targetId")?.into(),
+                document_id: fields.document_id()?,
+                data: fields.bytes("data")?,
+            },
+            "doc-unavailable" => Self::DocUnavailable {
+                sender_id: fields.text("senderId")?.into(),
+                target_id: fields.text("targetId")?.into(),
+                document_id: fields.document_id()?,
+            },
+            "ephemeral" => Self::Ephemeral {
+                sender_id: fields.text("senderId")?.into(),
+                target_id: fields.text("targetId")?.into(),
+                document_id: fields.document_id()?,
+                session_id: fields.text("sessionId")?,
+                count: fields.uint("count")?,
+                data: fields.bytes("data")?,
+            },
+            "error" => Self::Error {
+                message: fields.text("message")?,
+            },
+            other => Self::Unknown(other.to_string()),
+        };
+        Ok(frame)
+    }
+}
+
+fn peer(id: &PeerId) -> Cbor {
+    Cbor::Text(id.as_str().to_string())
+}
+
+struct Fields(BTreeMap<String, Cbor>);
+
+impl Fields {
+    fn get(&self, key: &str) -> Result<&Cbor, WebSocketError> {
+        self.0
+            .get(key)
+            .ok_or_else(|| WebSocketError::InvalidFrame(format!("missing field {}", key)))
+    }
+
+    fn invalid(key: &str) -> WebSocketError {
+        WebSocketError::InvalidFrame(format!("invalid field {}", key))
+    }
+
+    fn text(&self, key: &str) -> Result<String, WebSocketError> {
+        match self.get(key)? {
+            Cbor::Text(t) => Ok(t.clone()),
+            _ => Err(Self::invalid(key)),
+        }
+    }
+
+    fn texts(&self, key: &str) -> Result<Vec<String>, WebSocketError> {
+        match self.get(key)? {
+            Cbor::Array(items) => items
+                .iter()
+                .map(|item| match item {
+                    Cbor::Text(t) => Ok(t.clone()),
+                    _ => Err(Self::invalid(key)),
+                })
+                .collect(),
+            _ => Err(Self::invalid(key)),
+        }
+    }
+
+    fn bytes(&self, key: &str) -> Result<Vec<u8>, WebSocketError> {
+        match self.get(key)? {
+            Cbor::Bytes(b) => Ok(b.clone()),
+            _ => Err(Self::invalid(key)),
+        }
+    }
+
+    fn uint(&self, key: &str) -> Result<u64, WebSocketError> {
+        match self.get(key)? {
+            Cbor::Integer(i) => u64::try_from(*i).map_err(|_| Self::invalid(key)),
+            _ => Err(Self::invalid(key)),
+        }
+    }
+
+    fn document_id(&self) -> Result<DocId, WebSocketError> {
+        decode_document_id(&self.text("documentId")?).ok_or_else(|| Self::invalid("documentId"))
+    }
+}
+
+/// The base58check encoding of `id` used for document IDs on the wire
+pub fn encode_document_id(id: &DocId) -> String {
+    bs58::encode(id.as_bytes()).with_check().into_string()
+}
+
+/// Parse a base58check document ID, returning `None` if it is not valid
+pub fn decode_document_id(id: &str) -> Option<DocId> {
+    bs58::decode(id)
+        .with_check(None)
+        .into_vec()
+        .ok()
+        .map(DocId::from)
+}
+
+/// What [`Connection::receive()`] received
+#[derive(Debug)]
+pub enum Received {
+    /// A sync message was applied to this document
+    Sync(DocHandle),
+    /// The peer does not have a document we requested
+    Unavailable(DocId),
+    /// An ephemeral message about `document`, which is not stored anywhere
+    Ephemeral { document: DocId, data: Vec<u8> },
+    /// The peer closed the connection
+    Closed,
+    /// A message which needs no handling, such as one of a `type` this version does not know
+    Ignored,
+}
+
+/// A WebSocket connection between a [`Repo`] and one peer
+///
+/// The connection keeps the peer up to date with every document in the repo. Messages are sent
+/// when [`Self::flush()`] is called and after each message handled by [`Self::receive()`], so an
+/// application which changes documents whilst waiting for messages from the peer should call
+/// [`Self::flush()`] after making changes. The sync state for the peer is dropped with the
+/// connection.
+pub struct Connection<S> {
+    repo: Arc<Repo>,
+    socket: WebSocket<S>,
+    peer_id: PeerId,
+    remote: PeerId,
+    session_id: String,
+    count: u64,
+}
+
+impl<S: Read + Write> Connection<S> {
+    /// Open a connection as the client, sending `join` and waiting for the server's `peer`
+    pub fn connect(
+        repo: Arc<Repo>,
+        peer_id: PeerId,
+        mut socket: WebSocket<S>,
+    ) -> Result<Self, WebSocketError> {
+        send(
+            &mut socket,
+            &Frame::Join {
+                sender_id: peer_id.clone(),
+                supported_protocol_versions: vec![PROTOCOL_VERSION.to_string()],
+            },
+        )?;
+        match read(&mut socket)? {
+            Some(Frame::Peer {
+                sender_id,
+                selected_protocol_version,
+                ..
+            }) => {
+                if selected_protocol_version != PROTOCOL_VERSION {
+                    return Err(WebSocketError::UnsupportedVersion);
+                }
+                let mut connection = Self::new(repo, peer_id, sender_id, socket);
+                connection.flush()?;
+                Ok(connection)
+            }
+            Some(Frame::Error { message }) => Err(WebSocketError::Remote(message)),
+            Some(other) => Err(WebSocketError::UnexpectedFrame {
+                expected: "peer",
+                received: other.type_name(),
+            }),
+            None => Err(WebSocketError::Closed),
+        }
+    }
+
+    /// Open a connection as the server, waiting for the client's `join` and answering with `peer`
+    pub fn accept(
+        repo: Arc<Repo>,
+        peer_id: PeerId,
+        mut socket: WebSocket<S>,
+    ) -> Result<Self, WebSocketError> {
+        match read(&mut socket)? {
+            Some(Frame::Join {
+                sender_id,
+                supported_protocol_versions,
+            }) => {
+                if !supported_protocol_versions
+                    .iter()
+                    .any(|v| v == PROTOCOL_VERSION)
+                {
+                    send(
+                        &mut socket,
+                        &Frame::Error {
+                            message: format!(
+                                "unsupported protocol version, expected {}",
+                                PROTOCOL_VERSION
+                            ),
+                        },
+                    )?;
+                    return Err(WebSocketError::UnsupportedVersion);
+                }
+                send(
+                    &mut socket,
+                    &Frame::Peer {
+                        sender_id: peer_id.clone(),
+                        target_id: sender_id.clone(),
+                        selected_protocol_version: PROTOCOL_VERSION.to_string(),
+                    },
+                )?;
+                let mut connection = Self::new(repo, peer_id, sender_id, socket);
+                connection.flush()?;
+                Ok(connection)
+            }
+            Some(other) => Err(WebSocketError::UnexpectedFrame {
+                expected: "join",
+                received: other.type_name(),
+            }),
+            None => Err(WebSocketError::Closed),
+        }
+    }
+
+    fn new(repo: Arc<Repo>, peer_id: PeerId, remote: PeerId, socket: WebSocket<S>) -> Self {
+        Self {
+            repo,
+            socket,
+            peer_id,
+            remote,
+            session_id: DocId::random().to_string(),
+            count: 0,
+        }
+    }
+
+    /// The ID the peer gave in its `join` or `peer` message
+    pub fn remote(&self) -> &PeerId {
+        &self.remote
+    }
+
+    /// Send the pending sync messages for every document in the repo
+    pub fn flush(&mut self) -> Result<(), WebSocketError> {
+        for (document_id, message) in self.repo.generate_sync_messages(&self.remote) {
+            let empty = self
+                .repo
+                .find(&document_id)?
+                .map(|handle| handle.get_heads().is_empty())
+                .unwrap_or(true);
+            let (sender_id, target_id, data) =
+                (self.peer_id.clone(), self.remote.clone(), message.encode());
+            let frame = if empty {
+                Frame::Request {
+                    sender_id,
+                    target_id,
+                    document_id,
+                    data,
+                }
+            } else {
+                Frame::Sync {
+                    sender_id,
+                    target_id,
+                    document_id,
+                    data,
+                }
+            };
+            self.socket.write(WsMessage::Binary(frame.encode()))?;
+        }
+        self.socket.flush()?;
+        Ok(())
+    }
+
+    /// Send an ephemeral message about `document`
+    pub fn send_ephemeral(
+        &mut self,
+        document: &DocId,
+        data: Vec<u8>,
+    ) -> Result<(), WebSocketError> {
+        self.count += 1;
+        send(
+            &mut self.socket,
+            &Frame::Ephemeral {
+                sender_id: self.peer_id.clone(),
+                target_id: self.remote.clone(),
+                document_id: document.clone(),
+                session_id: self.session_id.clone(),
+                count: self.count,
+                data,
+            },
+        )
+    }
+
+    /// Block until a message arrives from the peer, handle it and send any replies
+    pub fn receive(&mut self) -> Result<Received, WebSocketError> {
+        let Some(frame) = read(&mut self.socket)? else {
+            self.repo.disconnect(&self.remote);
+            return Ok(Received::Closed);
+        };
+        let received = match frame {
+            Frame::Sync {
+                document_id, data, ..
+            } => {
+                let message = sync::Message::decode(&data)?;
+                let handle = self
+                    .repo
+                    .receive_sync_message(&self.remote, &document_id, message)?;
+                Received::Sync(handle)
+            }
+            Frame::Request {
+                document_id, data, ..
+            } => {
+                if self.repo.find(&document_id)?.is_none() {
+                    send(
+                        &mut self.socket,
+                        &Frame::DocUnavailable {
+                            sender_id: self.peer_id.clone(),
+                            target_id: self.remote.clone(),
+                            document_id,
+                        },
+                    )?;
+                    return Ok(Received::Ignored);
+                }
+                let message = sync::Message::decode(&data)?;
+                let handle = self
+                    .repo
+                    .receive_sync_message(&self.remote, &document_id, message)?;
+                Received::Sync(handle)
+            }
+            Frame::DocUnavailable { document_id, .. } => Received::Unavailable(document_id),
+            Frame::Ephemeral {
+                document_id, data, ..
+            } => Received::Ephemeral {
+                document: document_id,
+                data,
+            },
+            Frame::Error { message } => return Err(WebSocketError::Remote(message)),
+            Frame::Join { .. } | Frame::Peer { .. } | Frame::Unknown(_) => Received::Ignored,
+        };
+        self.flush()?;
+        Ok(received)
+    }
+
+    /// Close the connection
+    pub fn close(mut self) -> Result<(), WebSocketError> {
+        self.socket.close(None)?;
+        // wait for the peer to acknowledge the close
+        loop {
+            match self.socket.read() {
+                Ok(_) => continue,
+                Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl<S> fmt::Debug for Connection<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Connection")
+            .field("peer_id", &self.peer_id)
+            .field("remote", &self.remote)
+            .finish()
+    }
+}
+
+impl<S> Drop for Connection<S> {
+    fn drop(&mut self) {
+        self.repo.disconnect(&self.remote);
+    }
+}
+
+fn send<S: Read + Write>(socket: &mut WebSocket<S>, frame: &Frame) -> Result<(), WebSocketError> {
+    socket.send(WsMessage::Binary(frame.encode()))?;
+    Ok(())
+}
+
+/// Read the next frame, or `None` if the connection was closed
+fn read<S: Read + Write>(socket: &mut WebSocket<S>) -> Result<Option<Frame>, WebSocketError> {
+    loop {
+        match socket.read() {
+            Ok(WsMessage::Binary(bytes)) => return Frame::decode(&bytes).map(Some),
+            Ok(WsMessage::Close(_)) => {
+                // flushing sends the reply to the close frame
+                match socket.flush() {
+                    Ok(()) | Err(tungstenite::Error::ConnectionClosed) => return Ok(None),
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            Ok(_) => continue,
+            Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                return Ok(None)
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,453 @@
+//! Structural validation of documents
+//!
+//! A [`Schema`] describes the shape a document is expected to have: which keys a map must or may
+//! have, what type of value each key holds, what the elements of a list are and so on. A schema
+//! can be checked against any document with [`Schema::validate()`], or attached to a document
+//! with [`crate::Automerge::set_schema()`], after which
+//!
+//! * a local transaction which would leave the document in a state the schema does not allow
+//!   fails to commit, see [`crate::transaction::Transaction::try_commit()`], and
+//! * changes received from other peers which do the same are passed to the policy set with
+//!   [`crate::Automerge::set_schema_policy()`], which decides whether they are applied, rejected
+//!   or quarantined.
+//!
+//! Only the objects a change modifies are checked, together with everything beneath them, so
+//! checking a small change to a large document is cheap.
+//!
+//! # Example
+//!
+//! ```
+//! # use automerge::{AutoCommit, AutomergeError, ObjType, ReadDoc, ROOT};
+//! # use automerge::schema::{ScalarType, Schema};
+//! # use automerge::transaction::Transactable;
+//! let todo = Schema::map()
+//!     .required("title", Schema::text())
+//!     .optional("done", ScalarType::Boolean);
+//! let schema = Schema::map().required("todos", Schema::list(todo));
+//!
+//! let mut doc = AutoCommit::new();
+//! doc.set_schema(schema);
+//! let todos = doc.put_object(ROOT, "todos", ObjType::List).unwrap();
+//! doc.commit();
+//!
+//! let todo = doc.insert_object(&todos, 0, ObjType::Map).unwrap();
+//! doc.put(&todo, "done", "yes").unwrap();
+//! let Err(AutomergeError::SchemaViolation(violation)) = doc.try_commit() else { panic!() };
+//! assert_eq!(violation.to_string(), "missing required key `title` at /todos/0");
+//! assert_eq!(doc.length(&todos), 0);
+//! ```
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::exid::ExId;
+use crate::iter::{ListRangeItem, MapRangeItem};
+use crate::path::location;
+use crate::{ObjType, Prop, ReadDoc, ScalarValue, Value, ROOT};
+
+/// The shape of a value in a document
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    kind: Kind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    Any,
+    Scalar(ScalarType),
+    Text,
+    List(Box<Schema>),
+    Map(MapSchema),
+    OneOf(Vec<Schema>),
+}
+
+impl Schema {
+    /// Any value at all
+    pub fn any() -> Self {
+        Self { kind: Kind::Any }
+    }
+
+    /// A scalar value of type `typ`
+    pub fn scalar(typ: ScalarType) -> Self {
+        Self {
+            kind: Kind::Scalar(typ),
+        }
+    }
+
+    /// A text object
+    pub fn text() -> Self {
+        Self { kind: Kind::Text }
+    }
+
+    /// A list whose elements are all `items`
+    pub fn list<S: Into<Schema>>(items: S) -> Self {
+        Self {
+            kind: Kind::List(Box::new(items.into())),
+        }
+    }
+
+    /// A map, whose keys are added with [`MapSchema::required()`] and [`MapSchema::optional()`]
+    pub fn map() -> MapSchema {
+        MapSchema::default()
+    }
+
+    /// A value which matches at least one of `schemas`
+    pub fn one_of<I: IntoIterator<Item = Schema>>(schemas: I) -> Self {
+        Self {
+            kind: Kind::OneOf(schemas.into_iter().collect()),
+        }
+    }
+
+    /// Check that the whole of `doc` matches this schema
+    pub fn validate<R: ReadDoc + ?Sized>(&self, doc: &R) -> Result<(), SchemaViolation> {
+        self.validate_obj(doc, &ROOT, ObjType::Map, &mut Vec::new())
+    }
+
+    /// Check that the object `obj`, of type `obj_type` and at `path`, matches this schema
+    pub(crate) fn validate_obj<R: ReadDoc + ?Sized>(
+        &self,
+        doc: &R,
+        obj: &ExId,
+        obj_type: ObjType,
+        path: &mut Vec<Prop>,
+    ) -> Result<(), SchemaViolation> {
+        self.validate_value(doc, &Value::Object(obj_type), obj, path)
+    }
+
+    fn validate_value<R: ReadDoc + ?Sized>(
+        &self,
+        doc: &R,
+        value: &Value<'_>,
+        id: &ExId,
+        path: &mut Vec<Prop>,
+    ) -> Result<(), SchemaViolation> {
+        match (&self.kind, value) {
+            (Kind::Any, _) => Ok(()),
+            (Kind::OneOf(schemas), _) => {
+                // report the problem with the first alternative of the right type, which is
+                // more useful than saying that nothing matched
+                let mut first_error = None;
+                for schema in schemas {
+                    match schema.validate_value(doc, value, id, path) {
+                        Ok(()) => return Ok(()),
+                        Err(e) if schema.accepts_type_of(value) => {
+                            first_error.get_or_insert(e);
+                        }
+                        Err(_) => {}
+                    }
+                }
+                Err(first_error.unwrap_or_else(|| {
+                    SchemaViolation::new(
+                        path,
+                        format!("{} matches none of the alternatives", describe(value)),
+                    )
+                }))
+            }
+            (Kind::Scalar(typ), Value::Scalar(s)) if typ.matches(s) => Ok(()),
+            (Kind::Text, Value::Object(ObjType::Text)) => Ok(()),
+            (Kind::List(items), Value::Object(ObjType::List)) => {
+                for ListRangeItem {
+                    index, value, id, ..
+                } in doc.list_range(id, ..)
+                {
+                    path.push(Prop::Seq(index));
+                    items.validate_value(doc, &value, &id, path)?;
+                    path.pop();
+                }
+                Ok(())
+            }
+            (Kind::Map(map), Value::Object(ObjType::Map)) => map.validate(doc, id, path),
+            _ => Err(SchemaViolation::new(
+                path,
+                format!("expected {} but found {}", self.expected(), describe(value)),
+            )),
+        }
+    }
+
+    /// Whether a value of the same type as `value` could match this schema
+    fn accepts_type_of(&self, value: &Value<'_>) -> bool {
+        match (&self.kind, value) {
+            (Kind::Any, _) => true,
+            (Kind::OneOf(schemas), _) => schemas.iter().any(|s| s.accepts_type_of(value)),
+            (Kind::Scalar(typ), Value::Scalar(s)) => typ.matches(s),
+            (Kind::Text, Value::Object(ObjType::Text)) => true,
+            (Kind::List(_), Value::Object(ObjType::List)) => true,
+            (Kind::Map(_), Value::Object(ObjType::Map)) => true,
+            _ => false,
+        }
+    }
+
+    /// The schema of the value at `prop` in an object of type `obj_type` which matches this
+    /// schema, or `None` if this schema does not allow such a value
+    pub(crate) fn child(&self, obj_type: ObjType, prop: &Prop) -> Option<&Schema> {
+        match (&self.kind, prop) {
+            (Kind::Any, _) => Some(self),
+            (Kind::OneOf(schemas), _) => schemas
+                .iter()
+                .filter(|s| s.accepts_type_of(&Value::Object(obj_type)))
+                .find_map(|s| s.child(obj_type, prop)),
+            (Kind::List(items), Prop::Seq(_)) if obj_type == ObjType::List => Some(items),
+            (Kind::Map(map), Prop::Map(key)) if obj_type == ObjType::Map => map.get(key),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn expected(&self) -> String {
+        match &self.kind {
+            Kind::Any => "any value".to_string(),
+            Kind::Scalar(typ) => format!("{} {}", article(typ.name()), typ.name()),
+            Kind::Text => "a text object".to_string(),
+            Kind::List(_) => "a list".to_string(),
+            Kind::Map(_) => "a map".to_string(),
+            Kind::OneOf(schemas) => schemas
+                .iter()
+                .map(Schema::expected)
+                .collect::<Vec<_>>()
+                .join(" or "),
+        }
+    }
+}
+
+impl From<ScalarType> for Schema {
+    fn from(typ: ScalarType) -> Self {
+        Self::scalar(typ)
+    }
+}
+
+impl From<MapSchema> for Schema {
+    fn from(map: MapSchema) -> Self {
+        Self {
+            kind: Kind::Map(map),
+        }
+    }
+}
+
+/// The schema of a map, created with [`Schema::map()`]
+///
+/// By default a map may have keys other than those given to [`Self::required()`] and
+/// [`Self::optional()`], with any value, so that documents written by a newer version of an
+/// application which has added keys still match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapSchema {
+    keys: BTreeMap<String, (Schema, bool)>,
+    /// The schema of keys which are not in `keys`, or `None` if there may be no other keys
+    other_keys: Option<Box<Schema>>,
+}
+
+impl Default for MapSchema {
+    fn default() -> Self {
+        Self {
+            keys: BTreeMap::new(),
+            other_keys: Some(Box::new(Schema::any())),
+        }
+    }
+}
+
+impl MapSchema {
+    /// The map must have `key`, with a value matching `schema`
+    pub fn required<K: Into<String>, S: Into<Schema>>(mut self, key: K, schema: S) -> Self {
+        self.keys.insert(key.into(), (schema.into(), true));
+        self
+    }
+
+    /// If the map has `key` its value must match `schema`
+    pub fn optional<K: Into<String>, S: Into<Schema>>(mut self, key: K, schema: S) -> Self {
+        self.keys.insert(key.into(), (schema.into(), false));
+        self
+    }
+
+    /// The values of keys which were not given to [`Self::required()`] or [`Self::optional()`]
+    /// must match `schema`
+    pub fn other_keys<S: Into<Schema>>(mut self, schema: S) -> Self {
+        self.other_keys = Some(Box::new(schema.into()));
+        self
+    }
+
+    /// The map may only have the keys given to [`Self::required()`] and [`Self::optional()`]
+    pub fn no_other_keys(mut self) -> Self {
+        self.other_keys = None;
+        self
+    }
+
+    fn get(&self, key: &str) -> Option<&Schema> {
+        match self.keys.get(key) {
+            Some((schema, _)) => Some(schema),
+            None => self.other_keys.as_deref(),
+        }
+    }
+
+    fn validate<R: ReadDoc + ?Sized>(
+        &self,
+        doc: &R,
+        obj: &ExId,
+        path: &mut Vec<Prop>,
+    ) -> Result<(), SchemaViolation> {
+        for (key, (_, required)) in &self.keys {
+            if *required && matches!(doc.get(obj, key.as_str()), Ok(None)) {
+                return Err(SchemaViolation::new(
+                    path,
+                    format!("missing required key `{}`", key),
+                ));
+            }
+        }
+        for MapRangeItem { key, value, id, .. } in doc.map_range(obj, ..) {
+            let Some(schema) = self.get(key) else {
+                return Err(SchemaViolation::new(
+                    path,
+                    format!("unexpected key `{}`", key),
+                ));
+            };
+            path.push(Prop::Map(key.to_string()));
+            schema.validate_value(doc, &value, &id, path)?;
+            path.pop();
+        }
+        Ok(())
+    }
+}
+
+/// The type of a scalar value, see [`Schema::scalar()`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScalarType {
+    Null,
+    Boolean,
+    Int,
+    Uint,
+    F64,
+    /// An [`ScalarType::Int`], [`ScalarType::Uint`] or [`ScalarType::F64`]
+    Number,
+    Str,
+    /// A [`ScalarValue::Bytes`] or a [`ScalarValue::Chunked`]
+    Bytes,
+    Counter,
+    Timestamp,
+    DateTime,
+    BigInt,
+    Decimal,
+    BlobRef,
+    /// A [`ScalarValue::Link`] to another document
+    Link,
+}
+
+impl ScalarType {
+    /// Whether `value` is of this type
+    pub fn matches(&self, value: &ScalarValue) -> bool {
+        matches!(
+            (self, value),
+            (Self::Null, ScalarValue::Null)
+                | (Self::Boolean, ScalarValue::Boolean(_))
+                | (Self::Int, ScalarValue::Int(_))
+                | (Self::Uint, ScalarValue::Uint(_))
+                | (Self::F64, ScalarValue::F64(_))
+                | (
+                    Self::Number,
+                    ScalarValue::Int(_) | ScalarValue::Uint(_) | ScalarValue::F64(_)
+                )
+                | (Self::Str, ScalarValue::Str(_))
+                | (Self::Bytes, ScalarValue::Bytes(_) | ScalarValue::Chunked(_))
+                | (Self::Counter, ScalarValue::Counter(_))
+                | (Self::Timestamp, ScalarValue::Timestamp(_))
+                | (Self::DateTime, ScalarValue::DateTime(_))
+                | (Self::BigInt, ScalarValue::BigInt(_))
+                | (Self::Decimal, ScalarValue::Decimal(_))
+                | (Self::BlobRef, ScalarValue::BlobRef(_))
+                | (Self::Link, ScalarValue::Link(_))
+        )
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Boolean => "boolean",
+            Self::Int => "int",
+            Self::Uint => "uint",
+            Self::F64 => "f64",
+            Self::Number => "number",
+            Self::Str => "str",
+            Self::Bytes => "bytes",
+            Self::Counter => "counter",
+            Self::Timestamp => "timestamp",
+            Self::DateTime => "date time",
+            Self::BigInt => "big int",
+            Self::Decimal => "decimal",
+            Self::BlobRef => "blob reference",
+            Self::Link => "link",
+        }
+    }
+
+    fn of(value: &ScalarValue) -> Option<Self> {
+        match value {
+            ScalarValue::Null => Some(Self::Null),
+            ScalarValue::Boolean(_) => Some(Self::Boolean),
+            ScalarValue::Int(_) => Some(Self::Int),
+            ScalarValue::Uint(_) => Some(Self::Uint),
+            ScalarValue::F64(_) => Some(Self::F64),
+            ScalarValue::Str(_) => Some(Self::Str),
+            ScalarValue::Bytes(_) | ScalarValue::Chunked(_) => Some(Self::Bytes),
+            ScalarValue::Counter(_) => Some(Self::Counter),
+            ScalarValue::Timestamp(_) => Some(Self::Timestamp),
+            ScalarValue::DateTime(_) => Some(Self::DateTime),
+            ScalarValue::BigInt(_) => Some(Self::BigInt),
+            ScalarValue::Decimal(_) => Some(Self::Decimal),
+            ScalarValue::BlobRef(_) => Some(Self::BlobRef),
+            ScalarValue::Link(_) => Some(Self::Link),
+            ScalarValue::Unknown { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for ScalarType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+fn article(name: &str) -> &'static str {
+    match name.chars().next() {
+        Some('a' | 'e' | 'i' | 'o' | 'u') => "an",
+        _ => "a",
+    }
+}
+
+pub(crate) fn describe(value: &Value<'_>) -> String {
+    let name = match value {
+        Value::Object(ObjType::Map) => "map",
+        Value::Object(ObjType::Table) => "table",
+        Value::Object(ObjType::List) => "list",
+        Value::Object(ObjType::Text) => "text object",
+        Value::Scalar(s) => match ScalarType::of(s) {
+            Some(typ) => typ.name(),
+            None => "unknown value",
+        },
+    };
+    format!("{} {}", article(name), name)
+}
+
+/// A place where a document does not match a [`Schema`]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{reason} at {}", location(path))]
+pub struct SchemaViolation {
+    /// The path of the value which does not match
+    pub path: Vec<Prop>,
+    pub reason: String,
+}
+
+impl SchemaViolation {
+    fn new(path: &[Prop], reason: String) -> Self {
+        Self {
+            path: path.to_vec(),
+            reason,
+        }
+    }
+}
+
+/// What to do with a change received from another peer which violates the schema, see
+/// [`crate::Automerge::set_schema_policy()`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaAction {
+    /// Apply the change anyway
+    Apply,
+    /// Drop the change. Changes which depend on it will wait for it, as if it had not been
+    /// received.
+    Reject,
+    /// Hold the change in the quarantine, see [`crate::Automerge::quarantine_changes()`]
+    Quarantine,
+}
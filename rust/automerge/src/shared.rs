@@ -0,0 +1,96 @@
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::transaction::{self, Transaction};
+use crate::{Automerge, AutomergeError, Change, ChangeHash};
+
+/// A handle to an [`Automerge`] document which can be shared between threads
+///
+/// Cloning a [`SharedAutomerge`] produces another handle to the same document. Any number of
+/// threads can read from the document at the same time using [`Self::read()`], which returns a
+/// guard implementing [`crate::ReadDoc`] (via `Deref`). Writes take an exclusive lock for the
+/// duration of the write, either via [`Self::write()`] or [`Self::transact()`].
+///
+/// ```
+/// # use automerge::{ReadDoc, SharedAutomerge, ROOT, transaction::Transactable};
+/// let doc = SharedAutomerge::default();
+/// doc.transact(|tx| tx.put(ROOT, "key", "value")).unwrap();
+///
+/// let reader = doc.clone();
+/// std::thread::spawn(move || {
+///     let doc = reader.read();
+///     assert!(doc.get(ROOT, "key").unwrap().is_some());
+/// })
+/// .join()
+/// .unwrap();
+/// ```
+///
+/// If a thread panics whilst holding the write lock the document is left in whatever state the
+/// panicking thread left it in and later callers will still be able to access it; the lock is
+/// never reported as poisoned.
+#[derive(Debug, Clone, Default)]
+pub struct SharedAutomerge {
+    doc: Arc<RwLock<Automerge>>,
+}
+
+impl SharedAutomerge {
+    /// Wrap `doc` in a shared handle
+    pub fn new(doc: Automerge) -> Self {
+        Self {
+            doc: Arc::new(RwLock::new(doc)),
+        }
+    }
+
+    /// Acquire shared read access to the document, blocking whilst a writer holds the lock
+    pub fn read(&self) -> RwLockReadGuard<'_, Automerge> {
+        self.doc.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Acquire exclusive write access to the document, blocking until all readers have released
+    /// their locks
+    pub fn write(&self) -> RwLockWriteGuard<'_, Automerge> {
+        self.doc.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Run a transaction on the document whilst holding the write lock, see
+    /// [`Automerge::transact()`]
+    pub fn transact<F, O, E>(&self, f: F) -> transaction::Result<O, E>
+    where
+        F: FnOnce(&mut Transaction<'_>) -> Result<O, E>,
+    {
+        self.write().transact(f)
+    }
+
+    /// Apply changes to the document whilst holding the write lock, see
+    /// [`Automerge::apply_changes()`]
+    pub fn apply_changes(
+        &self,
+        changes: impl IntoIterator<Item = Change>,
+    ) -> Result<(), AutomergeError> {
+        self.write().apply_changes(changes)
+    }
+
+    /// The current heads of the document
+    pub fn get_heads(&self) -> Vec<ChangeHash> {
+        self.read().get_heads()
+    }
+
+    /// Take a copy of the current state of the document which can be read without holding a lock
+    pub fn snapshot(&self) -> Automerge {
+        self.read().clone()
+    }
+
+    /// Return the underlying document if this is the only handle to it
+    ///
+    /// If there are other handles then `self` is returned
+    pub fn try_unwrap(self) -> Result<Automerge, Self> {
+        Arc::try_unwrap(self.doc)
+            .map(|lock| lock.into_inner().unwrap_or_else(PoisonError::into_inner))
+            .map_err(|doc| Self { doc })
+    }
+}
+
+impl From<Automerge> for SharedAutomerge {
+    fn from(doc: Automerge) -> Self {
+        Self::new(doc)
+    }
+}
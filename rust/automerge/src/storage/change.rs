@@ -201,6 +201,35 @@ impl<'a> Change<'a, Unverified> {
             _phantom: PhantomData,
         })
     }
+
+    /// Count the ops in this change without decoding them
+    ///
+    /// Unlike [`Self::verify_ops`] this only decodes the action column, the remaining columns are
+    /// left undecoded until they are needed. This means that the ops in the returned change may
+    /// be malformed, callers must check them using [`Change::try_iter_ops`] before calling
+    /// [`Change::iter_ops`].
+    pub(crate) fn count_ops_unchecked(self) -> Result<Change<'a, Verified>, ReadChangeOpError> {
+        let num_ops = self.ops_meta.count(self.ops_data())?;
+        if u32::try_from(u64::from(self.start_op)).is_err() {
+            return Err(ReadChangeOpError::CounterTooLarge);
+        }
+        Ok(Change {
+            bytes: self.bytes,
+            header: self.header,
+            dependencies: self.dependencies,
+            actor: self.actor,
+            other_actors: self.other_actors,
+            seq: self.seq,
+            start_op: self.start_op,
+            timestamp: self.timestamp,
+            message: self.message,
+            ops_meta: self.ops_meta,
+            ops_data: self.ops_data,
+            extra_bytes: self.extra_bytes,
+            num_ops,
+            _phantom: PhantomData,
+        })
+    }
 }
 
 impl<'a> Change<'a, Verified> {
@@ -217,6 +246,15 @@ impl<'a> Change<'a, Verified> {
         // using either `verify_ops` or `Builder::build`, so we know the ops columns are valid.
         self.ops_meta.iter(self.ops_data()).map(|o| o.unwrap())
     }
+
+    /// Iterate over the ops in this change, returning an error for any malformed op
+    ///
+    /// This is only necessary for changes created with [`Change::count_ops_unchecked`]
+    pub(crate) fn try_iter_ops(
+        &'a self,
+    ) -> impl Iterator<Item = Result<ChangeOp, ReadChangeOpError>> + Clone + 'a {
+        self.ops_meta.iter(self.ops_data())
+    }
 }
 
 impl<'a, O: OpReadState> Change<'a, O> {
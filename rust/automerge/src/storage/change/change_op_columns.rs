@@ -142,6 +142,17 @@ impl ChangeOpsColumns {
         }
     }
 
+    /// Count the ops in `data` by decoding only the action column
+    pub(crate) fn count(&self, data: &[u8]) -> Result<usize, ReadChangeOpError> {
+        let mut action = self.action.decoder(data);
+        let mut count = 0;
+        while !action.done() {
+            action.next_in_col("action")?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
     #[tracing::instrument(skip(ops, out))]
     pub(crate) fn encode<'a, 'b, 'c, I, C, Op>(ops: I, out: &'b mut Vec<u8>) -> ChangeOpsColumns
     where
@@ -38,6 +38,11 @@ impl<'a> Compressed<'a> {
         self.bytes.clone()
     }
 
+    /// The compressed chunk, without copying it like [`Self::bytes()`] does
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
     pub(crate) fn checksum(&self) -> CheckSum {
         self.checksum
     }
@@ -1,7 +1,7 @@
 use std::{
     borrow::Cow,
     convert::{TryFrom, TryInto},
-    io::Read,
+    io::{self, Read},
     ops::Range,
 };
 
@@ -179,15 +179,22 @@ pub(crate) struct Header {
 
 impl Header {
     pub(crate) fn new(chunk_type: ChunkType, data: &[u8]) -> Self {
-        let hash = hash(chunk_type, data);
+        Self::from_parts(chunk_type, &[data])
+    }
+
+    /// Returns the header of a chunk whose data is the concatenation of `parts`, without
+    /// concatenating them
+    pub(crate) fn from_parts(chunk_type: ChunkType, parts: &[&[u8]]) -> Self {
+        let data_len = parts.iter().map(|p| p.len()).sum::<usize>();
+        let hash = hash_parts(chunk_type, data_len, parts);
         Self {
             hash,
             checksum: hash.checksum().into(),
-            data_len: data.len(),
+            data_len,
             header_size: MAGIC_BYTES.len()
                 + 4 // checksum
                 + 1 // chunk type
-                + (ulebsize(data.len() as u64) as usize),
+                + (ulebsize(data_len as u64) as usize),
             chunk_type,
         }
     }
@@ -220,6 +227,14 @@ impl Header {
         leb128::write::unsigned(out, self.data_len as u64).unwrap();
     }
 
+    pub(crate) fn write_to<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(&MAGIC_BYTES)?;
+        out.write_all(&self.checksum.bytes())?;
+        out.write_all(&[u8::from(self.chunk_type)])?;
+        leb128::write::unsigned(out, self.data_len as u64)?;
+        Ok(())
+    }
+
     pub(crate) fn parse<E>(input: parse::Input<'_>) -> parse::ParseResult<'_, Header, E>
     where
         E: From<error::Header>,
@@ -284,10 +299,17 @@ impl Header {
 }
 
 fn hash(typ: ChunkType, data: &[u8]) -> ChangeHash {
-    let mut out = vec![u8::from(typ)];
-    leb128::write::unsigned(&mut out, data.len() as u64).unwrap();
-    out.extend(data);
-    let hash_result = Sha256::digest(out);
-    let array: [u8; 32] = hash_result.into();
+    hash_parts(typ, data.len(), &[data])
+}
+
+fn hash_parts(typ: ChunkType, data_len: usize, parts: &[&[u8]]) -> ChangeHash {
+    let mut prefix = vec![u8::from(typ)];
+    leb128::write::unsigned(&mut prefix, data_len as u64).unwrap();
+    let mut hasher = Sha256::new();
+    hasher.update(prefix);
+    for part in parts {
+        hasher.update(part);
+    }
+    let array: [u8; 32] = hasher.finalize().into();
     ChangeHash(array)
 }
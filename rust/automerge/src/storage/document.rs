@@ -1,4 +1,4 @@
-use std::{borrow::Cow, ops::Range};
+use std::{borrow::Cow, io, ops::Range};
 
 use super::{parse, shift_range, ChunkType, Columns, Header, RawColumns};
 
@@ -308,6 +308,84 @@ impl<'a> Document<'a> {
         }
     }
 
+    /// Encode a document chunk as [`Self::new()`] does and write it to `out`
+    ///
+    /// The encoded columns are held in memory, as the chunk header contains the length and
+    /// checksum of the data, but they are written to `out` directly rather than being copied
+    /// into one buffer for the whole chunk (and another for the compressed chunk). The bytes
+    /// written are the same as `Self::new(..).into_bytes()`.
+    ///
+    /// # Returns
+    /// The number of bytes written
+    pub(crate) fn write<'b, W, I, C, IC, D, O>(
+        out: &mut W,
+        mut actors: Vec<ActorId>,
+        heads_with_indices: Vec<(ChangeHash, usize)>,
+        ops: I,
+        changes: IC,
+        compress: CompressConfig,
+    ) -> io::Result<usize>
+    where
+        W: io::Write,
+        I: Iterator<Item = D> + Clone + ExactSizeIterator,
+        O: convert::OpId<usize>,
+        D: AsDocOp<'b, OpId = O>,
+        C: AsChangeMeta<'b>,
+        IC: Iterator<Item = C> + Clone,
+    {
+        let mut ops_out = Vec::new();
+        let ops_meta = DocOpColumns::encode(ops, &mut ops_out);
+
+        let mut change_out = Vec::new();
+        let change_meta = DocChangeColumns::encode(changes, &mut change_out);
+        actors.sort_unstable();
+
+        let mut prefix = Vec::new();
+        leb128::write::unsigned(&mut prefix, actors.len() as u64).unwrap();
+        for actor in &actors {
+            leb128::write::unsigned(&mut prefix, actor.to_bytes().len() as u64).unwrap();
+            prefix.extend(actor.to_bytes());
+        }
+        leb128::write::unsigned(&mut prefix, heads_with_indices.len() as u64).unwrap();
+        for (head, _) in &heads_with_indices {
+            prefix.extend(head.as_bytes());
+        }
+
+        if let CompressConfig::Threshold(threshold) = compress {
+            let mut compressed = Vec::new();
+            change_meta
+                .raw_columns()
+                .compress(&change_out, &mut compressed, threshold)
+                .write(&mut prefix);
+            change_out = compressed;
+
+            let mut compressed = Vec::new();
+            ops_meta
+                .raw_columns()
+                .compress(&ops_out, &mut compressed, threshold)
+                .write(&mut prefix);
+            ops_out = compressed;
+        } else {
+            change_meta.raw_columns().write(&mut prefix);
+            ops_meta.raw_columns().write(&mut prefix);
+        }
+
+        let mut suffix = Vec::new();
+        for (_, index) in &heads_with_indices {
+            leb128::write::unsigned(&mut suffix, *index as u64).unwrap();
+        }
+
+        let parts = [&prefix[..], &change_out, &ops_out, &suffix];
+        let header = Header::from_parts(ChunkType::Document, &parts);
+        header.write_to(out)?;
+        let mut written = header.len();
+        for part in parts {
+            out.write_all(part)?;
+            written += part.len();
+        }
+        Ok(written)
+    }
+
     pub(crate) fn iter_ops(
         &'a self,
     ) -> impl Iterator<Item = Result<DocOp, ReadDocOpError>> + Clone + 'a {
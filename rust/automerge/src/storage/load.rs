@@ -8,7 +8,7 @@ use crate::{
 pub(crate) mod change_collector;
 mod reconstruct_document;
 pub use reconstruct_document::VerificationMode;
-pub(crate) use reconstruct_document::{reconstruct_opset, ReconOpSet};
+pub(crate) use reconstruct_document::{reconstruct_opset, Error as ReconstructError, ReconOpSet};
 
 #[derive(Debug, thiserror::Error)]
 #[allow(unreachable_pub)]
@@ -80,29 +80,22 @@ fn load_next_change<'a>(
     match chunk {
         storage::Chunk::Document(d) => {
             tracing::trace!("loading document chunk");
-            let new_changes = reconstruct_opset(&d, VerificationMode::DontCheck)
+            let new_changes = reconstruct_opset(&d, VerificationMode::DontCheck, None)
                 .map_err(|e| Error::InflateDocument(Box::new(e)))?
                 .changes;
             changes.extend(new_changes);
         }
         storage::Chunk::Change(change) => {
             tracing::trace!("loading change chunk");
-            let change = Change::new_from_unverified(change.into_owned(), None)
+            let change = Change::new_lazy(change.into_owned(), None)
                 .map_err(|e| Error::InvalidChangeColumns(Box::new(e)))?;
-            #[cfg(debug_assertions)]
-            {
-                let loaded_ops = change.iter_ops().collect::<Vec<_>>();
-                tracing::trace!(actor=?change.actor_id(), num_ops=change.len(), ops=?loaded_ops, "loaded change");
-            }
-            #[cfg(not(debug_assertions))]
             tracing::trace!(actor=?change.actor_id(), num_ops=change.len(), "loaded change");
             changes.push(change);
         }
         storage::Chunk::CompressedChange(change, compressed) => {
             tracing::trace!("loading compressed change chunk");
-            let change =
-                Change::new_from_unverified(change.into_owned(), Some(compressed.into_owned()))
-                    .map_err(|e| Error::InvalidChangeColumns(Box::new(e)))?;
+            let change = Change::new_lazy(change.into_owned(), Some(compressed.into_owned()))
+                .map_err(|e| Error::InvalidChangeColumns(Box::new(e)))?;
             changes.push(change);
         }
     };
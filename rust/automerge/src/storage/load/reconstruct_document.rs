@@ -3,6 +3,7 @@ use std::collections::{BTreeSet, HashMap};
 
 use crate::storage::document::ReadDocOpError;
 use crate::{
+    cancel::CancellationToken,
     change::Change,
     columnar::Key as DocOpKey,
     op_set::{OpIdx, OpSet, OpSetData},
@@ -26,6 +27,8 @@ pub(crate) enum Error {
     SuccOutOfOrder,
     #[error(transparent)]
     InvalidOp(#[from] crate::error::InvalidOpType),
+    #[error("cancelled")]
+    Cancelled,
 }
 
 pub(crate) struct MismatchedHeads {
@@ -107,13 +110,18 @@ impl<'a> ReconstructionState<'a> {
     }
 }
 
+/// How many ops to load between checks for cancellation
+const CANCELLATION_CHECK_INTERVAL: usize = 4096;
+
 pub(crate) fn reconstruct_opset<'a>(
     doc: &'a Document<'a>,
     mode: VerificationMode,
+    cancellation: Option<&CancellationToken>,
 ) -> Result<ReconOpSet, Error> {
     let mut state = ReconstructionState::new(doc)?;
     let mut iter_ops = doc.iter_ops();
     let mut next = next_op(&mut iter_ops, &mut state.op_set)?;
+    let mut loaded = 0;
     while let Some(NextDocOp {
         op,
         succ,
@@ -147,12 +155,20 @@ pub(crate) fn reconstruct_opset<'a>(
         state.last_key = Some(key);
         state.last_obj = Some(obj);
 
+        loaded += 1;
+        if loaded % CANCELLATION_CHECK_INTERVAL == 0
+            && cancellation.map(|c| c.is_cancelled()).unwrap_or(false)
+        {
+            return Err(Error::Cancelled);
+        }
+
         next = next_op(&mut iter_ops, &mut state.op_set)?;
 
         flush_ops(&obj, next.as_ref(), &mut state)?;
     }
 
     state.op_set.add_indexes();
+    state.op_set.osd.resolve_segments();
 
     let op_set = state.op_set;
     let change_collector = state.change_collector;
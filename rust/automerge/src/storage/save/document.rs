@@ -1,4 +1,9 @@
-use std::{borrow::Cow, collections::HashMap, iter::Iterator};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    io,
+    iter::Iterator,
+};
 
 use fxhash::FxBuildHasher;
 use itertools::Itertools;
@@ -31,16 +36,7 @@ where
     I: Iterator<Item = &'a Change> + Clone + 'a,
     O: Iterator<Item = (&'a ObjId, Op<'a>)> + Clone + ExactSizeIterator,
 {
-    let mut actor_lookup = HashMap::with_capacity(actors.len());
-    let mut actor_ids = changes
-        .clone()
-        .map(|c| c.actor_id().clone())
-        .unique()
-        .collect::<Vec<_>>();
-    actor_ids.sort();
-    for (index, actor_id) in actor_ids.iter().enumerate() {
-        actor_lookup.insert(actors.lookup(actor_id).unwrap(), index);
-    }
+    let (actor_ids, actor_lookup) = document_actors(changes.clone(), actors);
 
     let doc_ops = ops
         .clone()
@@ -64,6 +60,116 @@ where
     doc.into_bytes()
 }
 
+/// Like [`save_document()`] but writes the document to `out`, see [`Document::write()`]
+///
+/// # Returns
+/// The number of bytes written
+///
+/// # Panics
+///
+/// As [`save_document()`]
+#[tracing::instrument(skip(out, changes, ops, actors, props, config))]
+pub(crate) fn save_document_to<'a, W, I, O>(
+    out: &mut W,
+    changes: I,
+    ops: O,
+    actors: &'a IndexedCache<ActorId>,
+    props: &IndexedCache<String>,
+    heads: &[ChangeHash],
+    config: Option<CompressConfig>,
+) -> io::Result<usize>
+where
+    W: io::Write,
+    I: Iterator<Item = &'a Change> + Clone + 'a,
+    O: Iterator<Item = (&'a ObjId, Op<'a>)> + Clone + ExactSizeIterator,
+{
+    let (actor_ids, actor_lookup) = document_actors(changes.clone(), actors);
+
+    let doc_ops = ops
+        .clone()
+        .map(|(_obj, op)| op_as_docop(&actor_lookup, props, op));
+
+    let hash_graph = HashGraph::new(changes.clone());
+    let changes = changes.map(|c| ChangeWithGraph {
+        actors,
+        actor_lookup: &actor_lookup,
+        change: c,
+        graph: &hash_graph,
+    });
+
+    Document::write(
+        out,
+        actor_ids,
+        hash_graph.heads_with_indices(heads.to_vec()),
+        doc_ops,
+        changes,
+        config.unwrap_or(CompressConfig::Threshold(DEFLATE_MIN_SIZE)),
+    )
+}
+
+/// The sorted actors of `changes` and a map from their index in `actors` to their index in the
+/// sorted list
+fn document_actors<'a, I>(
+    changes: I,
+    actors: &IndexedCache<ActorId>,
+) -> (Vec<ActorId>, HashMap<usize, usize>)
+where
+    I: Iterator<Item = &'a Change>,
+{
+    let mut actor_lookup = HashMap::with_capacity(actors.len());
+    let mut actor_ids = changes
+        .map(|c| c.actor_id().clone())
+        .unique()
+        .collect::<Vec<_>>();
+    actor_ids.sort();
+    for (index, actor_id) in actor_ids.iter().enumerate() {
+        actor_lookup.insert(actors.lookup(actor_id).unwrap(), index);
+    }
+    (actor_ids, actor_lookup)
+}
+
+/// Sort `changes` topologically, choosing the change with the lowest hash whenever there is a
+/// choice, so that the same changes are always saved in the same order
+///
+/// # Panics
+///
+/// * If any of the changes reference a dependency which is not in `changes`
+pub(crate) fn canonical_order(changes: Vec<&Change>) -> Vec<&Change> {
+    let mut waiting_on = HashMap::with_capacity(changes.len());
+    let mut dependents: HashMap<ChangeHash, Vec<ChangeHash>> = HashMap::new();
+    let mut ready = BTreeMap::new();
+    for change in &changes {
+        waiting_on.insert(change.hash(), change.deps().len());
+        for dep in change.deps() {
+            dependents.entry(*dep).or_default().push(change.hash());
+        }
+        if change.deps().is_empty() {
+            ready.insert(change.hash(), *change);
+        }
+    }
+    let by_hash = changes
+        .iter()
+        .map(|c| (c.hash(), *c))
+        .collect::<HashMap<_, _>>();
+    let mut sorted = Vec::with_capacity(changes.len());
+    while let Some((hash, change)) = ready.pop_first() {
+        sorted.push(change);
+        for dependent in dependents.remove(&hash).into_iter().flatten() {
+            let waiting = waiting_on.get_mut(&dependent).unwrap();
+            *waiting -= 1;
+            if *waiting == 0 {
+                ready.insert(dependent, by_hash[&dependent]);
+            }
+        }
+    }
+    assert_eq!(
+        sorted.len(),
+        changes.len(),
+        "dependency missing from changes"
+    );
+    sorted
+}
+
 struct HashGraph {
     index_by_hash: HashMap<ChangeHash, usize, FxBuildHasher>,
 }
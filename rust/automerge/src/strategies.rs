@@ -0,0 +1,555 @@
+//! [`proptest`] strategies for generating random documents, histories and sync schedules
+//!
+//! These let crates which build on automerge property test their own invariants against
+//! realistic documents without writing generators of their own. This module is available with
+//! the `proptest` feature.
+//!
+//! The building block is [`Edit`], a random change to a document. An edit does not name the
+//! object it changes, instead it carries a [`proptest::sample::Index`] which picks one of the
+//! objects the document has when the edit is applied, so edits can be generated without knowing
+//! the document they will be applied to and shrink well.
+//!
+//! * [`document()`] generates a document made by a single actor
+//! * [`history()`] generates a [`History`] of edits made concurrently by several peers and the
+//!   merges between them
+//! * [`sync_schedule()`] generates a [`SyncSchedule`] of edits and sync messages which are sent
+//!   and delivered between several peers in an arbitrary interleaving
+//!
+//! Documents are given the actor IDs from [`actor()`], so the same value always produces the
+//! same document.
+//!
+//! # Example
+//!
+//! ```
+//! # use automerge::strategies::history;
+//! # use automerge::ReadDoc;
+//! use proptest::prelude::*;
+//!
+//! proptest! {
+//!     fn merging_everything_converges(history in history(2..4, 0..20)) {
+//!         let mut peers = history.replay().unwrap();
+//!         let (first, rest) = peers.split_first_mut().unwrap();
+//!         for peer in rest.iter_mut() {
+//!             first.merge(peer).unwrap();
+//!         }
+//!         for peer in rest.iter_mut() {
+//!             peer.merge(first).unwrap();
+//!             prop_assert_eq!(peer.get_heads(), first.get_heads());
+//!         }
+//!     }
+//! }
+//! # merging_everything_converges();
+//! ```
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+
+use proptest::prelude::*;
+use proptest::sample::Index;
+
+use crate::sync::{self, SyncDoc};
+use crate::transaction::Transactable;
+use crate::{
+    ActorId, AutoCommit, AutomergeError, ObjId, ObjType, ReadDoc, ScalarValue, Value, ROOT,
+};
+
+/// The keys edits use in maps, kept small so that concurrent edits conflict
+const KEYS: &[&str] = &["a", "b", "c", "d", "e"];
+
+/// The actor ID of the `peer`th document made by these strategies
+pub fn actor(peer: usize) -> ActorId {
+    let mut bytes = *b"proptest-peer-00";
+    bytes[14..].copy_from_slice(&(peer as u16).to_be_bytes());
+    ActorId::from(bytes)
+}
+
+/// A value to put in or insert into an object
+#[derive(Debug, Clone, PartialEq)]
+pub enum NewValue {
+    Scalar(ScalarValue),
+    Object(ObjType),
+}
+
+/// A random change to a document, see the [module documentation](self)
+#[derive(Debug, Clone)]
+pub enum Edit {
+    /// Put `value` at `key` of a map, or over the element at `index` of a list
+    Put {
+        obj: Index,
+        key: &'static str,
+        index: Index,
+        value: NewValue,
+    },
+    /// Insert `value` at `index` of a list
+    Insert {
+        obj: Index,
+        index: Index,
+        value: NewValue,
+    },
+    /// Delete one of the keys of a map or one of the elements of a list
+    Delete { obj: Index, index: Index },
+    /// Increment a counter at `key` of a map, or at `index` of a list
+    ///
+    /// This does nothing if there is no counter there.
+    Increment {
+        obj: Index,
+        key: &'static str,
+        index: Index,
+        by: i64,
+    },
+    /// Delete `delete` characters at `index` of a text object and insert `text` in their place
+    SpliceText {
+        obj: Index,
+        index: Index,
+        delete: usize,
+        text: String,
+    },
+}
+
+impl Edit {
+    /// Apply this edit to `doc`
+    ///
+    /// If `doc` has no object the edit can be applied to, for instance a text splice to a
+    /// document without text, then this does nothing.
+    pub fn apply(&self, doc: &mut AutoCommit) -> Result<(), AutomergeError> {
+        let objs = objects(doc);
+        let pick = |obj: &Index, types: &[ObjType]| {
+            let candidates = objs
+                .iter()
+                .filter(|(_, typ)| types.contains(typ))
+                .collect::<Vec<_>>();
+            (!candidates.is_empty()).then(|| candidates[obj.index(candidates.len())].clone())
+        };
+        match self {
+            Edit::Put {
+                obj,
+                key,
+                index,
+                value,
+            } => match pick(obj, &[ObjType::Map, ObjType::Table, ObjType::List]) {
+                Some((obj, ObjType::List)) => {
+                    let len = doc.length(&obj);
+                    if len > 0 {
+                        put(doc, &obj, index.index(len), value)?;
+                    }
+                }
+                Some((obj, _)) => put(doc, &obj, *key, value)?,
+                None => {}
+            },
+            Edit::Insert { obj, index, value } => {
+                if let Some((obj, _)) = pick(obj, &[ObjType::List]) {
+                    let index = index.index(doc.length(&obj) + 1);
+                    match value {
+                        NewValue::Scalar(v) => doc.insert(&obj, index, v.clone())?,
+                        NewValue::Object(typ) => {
+                            doc.insert_object(&obj, index, *typ)?;
+                        }
+                    }
+                }
+            }
+            Edit::Delete { obj, index } => {
+                match pick(obj, &[ObjType::Map, ObjType::Table, ObjType::List]) {
+                    Some((obj, ObjType::List)) => {
+                        let len = doc.length(&obj);
+                        if len > 0 {
+                            doc.delete(&obj, index.index(len))?;
+                        }
+                    }
+                    Some((obj, _)) => {
+                        let keys = doc.keys(&obj).collect::<Vec<_>>();
+                        if !keys.is_empty() {
+                            doc.delete(&obj, keys[index.index(keys.len())].as_str())?;
+                        }
+                    }
+                    None => {}
+                }
+            }
+            Edit::Increment {
+                obj,
+                key,
+                index,
+                by,
+            } => match pick(obj, &[ObjType::Map, ObjType::Table, ObjType::List]) {
+                Some((obj, ObjType::List)) => {
+                    let len = doc.length(&obj);
+                    if len > 0 {
+                        increment(doc, &obj, index.index(len), *by)?;
+                    }
+                }
+                Some((obj, _)) => increment(doc, &obj, *key, *by)?,
+                None => {}
+            },
+            Edit::SpliceText {
+                obj,
+                index,
+                delete,
+                text,
+            } => {
+                if let Some((obj, _)) = pick(obj, &[ObjType::Text]) {
+                    let len = doc.length(&obj);
+                    let index = index.index(len + 1);
+                    let delete = (*delete).min(len - index) as isize;
+                    doc.splice_text(&obj, index, delete, text)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn put<P: Into<crate::Prop>>(
+    doc: &mut AutoCommit,
+    obj: &ObjId,
+    prop: P,
+    value: &NewValue,
+) -> Result<(), AutomergeError> {
+    match value {
+        NewValue::Scalar(v) => doc.put(obj, prop, v.clone()),
+        NewValue::Object(typ) => doc.put_object(obj, prop, *typ).map(|_| ()),
+    }
+}
+
+/// Increment the counter at `prop` of `obj`, if there is one
+fn increment<P: Into<crate::Prop> + Clone>(
+    doc: &mut AutoCommit,
+    obj: &ObjId,
+    prop: P,
+    by: i64,
+) -> Result<(), AutomergeError> {
+    let value = doc.get(obj, prop.clone())?;
+    if matches!(value, Some((Value::Scalar(v), _)) if matches!(v.as_ref(), ScalarValue::Counter(_)))
+    {
+        doc.increment(obj, prop, by)?;
+    }
+    Ok(())
+}
+
+/// The visible objects of `doc`, parents before their children
+fn objects(doc: &AutoCommit) -> Vec<(ObjId, ObjType)> {
+    let mut objs = vec![(ROOT, ObjType::Map)];
+    let mut next = 0;
+    while let Some((obj, typ)) = objs.get(next).cloned() {
+        next += 1;
+        let children = match typ {
+            ObjType::Map | ObjType::Table => doc
+                .keys(&obj)
+                .filter_map(|key| doc.get(&obj, key).ok().flatten())
+                .collect::<Vec<_>>(),
+            ObjType::List => (0..doc.length(&obj))
+                .filter_map(|i| doc.get(&obj, i).ok().flatten())
+                .collect(),
+            ObjType::Text => Vec::new(),
+        };
+        for (value, id) in children {
+            if let Value::Object(typ) = value {
+                objs.push((id, typ));
+            }
+        }
+    }
+    objs
+}
+
+/// Generate a scalar value of any type
+///
+/// Floats are always finite, so that documents containing them compare equal to themselves.
+pub fn scalar_value() -> impl Strategy<Value = ScalarValue> {
+    prop_oneof![
+        Just(ScalarValue::Null),
+        any::<bool>().prop_map(ScalarValue::Boolean),
+        any::<i64>().prop_map(ScalarValue::Int),
+        any::<u64>().prop_map(ScalarValue::Uint),
+        (-1e9f64..1e9).prop_map(ScalarValue::F64),
+        text().prop_map(|s| ScalarValue::Str(s.into())),
+        proptest::collection::vec(any::<u8>(), 0..8).prop_map(ScalarValue::Bytes),
+        any::<i32>().prop_map(|c| ScalarValue::counter(c.into())),
+        any::<i32>().prop_map(|t| ScalarValue::Timestamp(t.into())),
+    ]
+}
+
+fn new_value() -> impl Strategy<Value = NewValue> {
+    prop_oneof![
+        4 => scalar_value().prop_map(NewValue::Scalar),
+        1 => prop_oneof![
+            Just(ObjType::Map),
+            Just(ObjType::List),
+            Just(ObjType::Text),
+        ]
+        .prop_map(NewValue::Object),
+    ]
+}
+
+fn text() -> impl Strategy<Value = String> {
+    let chars = prop_oneof![
+        8 => proptest::char::range('a', 'z'),
+        1 => Just('é'),
+        1 => Just('🙂'),
+    ];
+    proptest::collection::vec(chars, 0..6).prop_map(|chars| chars.into_iter().collect())
+}
+
+/// Generate a single random edit, see [`Edit`]
+pub fn edit() -> impl Strategy<Value = Edit> {
+    let key = proptest::sample::select(KEYS);
+    prop_oneof![
+        3 => (any::<Index>(), key.clone(), any::<Index>(), new_value()).prop_map(
+            |(obj, key, index, value)| Edit::Put {
+                obj,
+                key,
+                index,
+                value
+            }
+        ),
+        3 => (any::<Index>(), any::<Index>(), new_value())
+            .prop_map(|(obj, index, value)| Edit::Insert { obj, index, value }),
+        1 => (any::<Index>(), any::<Index>()).prop_map(|(obj, index)| Edit::Delete { obj, index }),
+        1 => (any::<Index>(), key, any::<Index>(), -10i64..10).prop_map(
+            |(obj, key, index, by)| Edit::Increment {
+                obj,
+                key,
+                index,
+                by
+            }
+        ),
+        3 => (any::<Index>(), any::<Index>(), 0usize..4, text()).prop_map(
+            |(obj, index, delete, text)| Edit::SpliceText {
+                obj,
+                index,
+                delete,
+                text
+            }
+        ),
+    ]
+}
+
+/// Generate a document by applying between `edits.start` and `edits.end` random edits,
+/// committing after some of them
+pub fn document(edits: Range<usize>) -> impl Strategy<Value = AutoCommit> {
+    proptest::collection::vec((edit(), any::<bool>()), edits).prop_map(|edits| {
+        let mut doc = AutoCommit::new().with_actor(actor(0));
+        for (edit, commit) in edits {
+            // the generated edits are always valid for the document they are applied to
+            edit.apply(&mut doc).unwrap();
+            if commit {
+                doc.commit();
+            }
+        }
+        doc.commit();
+        doc
+    })
+}
+
+/// One step of a [`History`]
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// Apply `edits` to `peer` and commit them as a single change
+    Edit { peer: usize, edits: Vec<Edit> },
+    /// Merge all of the changes `from` has into `into`
+    Merge { from: usize, into: usize },
+}
+
+/// Edits made concurrently by several peers and the merges between them
+#[derive(Debug, Clone)]
+pub struct History {
+    pub peers: usize,
+    pub steps: Vec<Step>,
+}
+
+impl History {
+    /// Run the history, returning the document of each peer at the end of it
+    ///
+    /// The `n`th peer has the actor ID [`actor(n)`](actor()).
+    pub fn replay(&self) -> Result<Vec<AutoCommit>, AutomergeError> {
+        let mut docs = peers(self.peers);
+        for step in &self.steps {
+            match step {
+                Step::Edit { peer, edits } => {
+                    for edit in edits {
+                        edit.apply(&mut docs[*peer])?;
+                    }
+                    docs[*peer].commit();
+                }
+                Step::Merge { from, into } => {
+                    let mut from = docs[*from].clone();
+                    docs[*into].merge(&mut from)?;
+                }
+            }
+        }
+        Ok(docs)
+    }
+}
+
+fn peers(count: usize) -> Vec<AutoCommit> {
+    (0..count)
+        .map(|peer| AutoCommit::new().with_actor(actor(peer)))
+        .collect()
+}
+
+/// Two different peers of `peers`
+fn peer_pair(peers: usize) -> impl Strategy<Value = (usize, usize)> {
+    (0..peers, 1..peers).prop_map(move |(from, offset)| (from, (from + offset) % peers))
+}
+
+/// Generate a [`History`] between `peers.start` and `peers.end` peers (at least two) with
+/// between `steps.start` and `steps.end` steps
+pub fn history(peers: Range<usize>, steps: Range<usize>) -> impl Strategy<Value = History> {
+    let peers = peers.start.max(2)..peers.end.max(3);
+    peers.prop_flat_map(move |peers| {
+        let step = prop_oneof![
+            2 => (0..peers, proptest::collection::vec(edit(), 1..4))
+                .prop_map(|(peer, edits)| Step::Edit { peer, edits }),
+            1 => peer_pair(peers).prop_map(|(from, into)| Step::Merge { from, into }),
+        ];
+        proptest::collection::vec(step, steps.clone())
+            .prop_map(move |steps| History { peers, steps })
+    })
+}
+
+/// One step of a [`SyncSchedule`]
+#[derive(Debug, Clone)]
+pub enum SyncStep {
+    /// Apply `edits` to `peer` and commit them as a single change
+    Edit { peer: usize, edits: Vec<Edit> },
+    /// Generate a sync message from `from` to `to`, if there is one to send
+    Send { from: usize, to: usize },
+    /// Deliver the oldest message sent from `from` to `to` which has not been delivered yet
+    Deliver { from: usize, to: usize },
+}
+
+/// Edits and sync messages between several peers, in an arbitrary interleaving
+///
+/// Messages between each pair of peers are delivered in the order they were sent, as the sync
+/// protocol expects, but messages between different pairs and edits are interleaved freely.
+#[derive(Debug, Clone)]
+pub struct SyncSchedule {
+    pub peers: usize,
+    pub steps: Vec<SyncStep>,
+}
+
+impl SyncSchedule {
+    /// Run the schedule, returning the network at the end of it
+    ///
+    /// Messages which have been sent but not delivered are still in flight in the returned
+    /// network, use [`Network::settle()`] to finish syncing.
+    pub fn run(&self) -> Result<Network, AutomergeError> {
+        let mut network = Network::new(self.peers);
+        for step in &self.steps {
+            network.step(step)?;
+        }
+        Ok(network)
+    }
+}
+
+/// Generate a [`SyncSchedule`] between `peers.start` and `peers.end` peers (at least two) with
+/// between `steps.start` and `steps.end` steps
+pub fn sync_schedule(
+    peers: Range<usize>,
+    steps: Range<usize>,
+) -> impl Strategy<Value = SyncSchedule> {
+    let peers = peers.start.max(2)..peers.end.max(3);
+    peers.prop_flat_map(move |peers| {
+        let step = prop_oneof![
+            1 => (0..peers, proptest::collection::vec(edit(), 1..4))
+                .prop_map(|(peer, edits)| SyncStep::Edit { peer, edits }),
+            1 => peer_pair(peers).prop_map(|(from, to)| SyncStep::Send { from, to }),
+            1 => peer_pair(peers).prop_map(|(from, to)| SyncStep::Deliver { from, to }),
+        ];
+        proptest::collection::vec(step, steps.clone())
+            .prop_map(move |steps| SyncSchedule { peers, steps })
+    })
+}
+
+/// Several peers which sync with each other, see [`SyncSchedule`]
+#[derive(Debug)]
+pub struct Network {
+    pub docs: Vec<AutoCommit>,
+    /// The sync state each peer has for each other peer, by `(peer, other)`
+    states: HashMap<(usize, usize), sync::State>,
+    /// Messages which have been sent but not delivered, by `(from, to)`
+    in_flight: HashMap<(usize, usize), VecDeque<sync::Message>>,
+}
+
+impl Network {
+    /// A network of `peers` empty documents, the `n`th with the actor ID
+    /// [`actor(n)`](actor())
+    pub fn new(peers: usize) -> Self {
+        Network {
+            docs: self::peers(peers),
+            states: HashMap::new(),
+            in_flight: HashMap::new(),
+        }
+    }
+
+    pub fn step(&mut self, step: &SyncStep) -> Result<(), AutomergeError> {
+        match step {
+            SyncStep::Edit { peer, edits } => {
+                for edit in edits {
+                    edit.apply(&mut self.docs[*peer])?;
+                }
+                self.docs[*peer].commit();
+            }
+            SyncStep::Send { from, to } => {
+                self.send(*from, *to);
+            }
+            SyncStep::Deliver { from, to } => {
+                self.deliver(*from, *to)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The number of messages which have been sent but not delivered
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.values().map(|q| q.len()).sum()
+    }
+
+    /// Deliver every message in flight and then sync every pair of peers until none of them
+    /// have anything to send
+    ///
+    /// Once this returns every peer has every change.
+    pub fn settle(&mut self) -> Result<(), AutomergeError> {
+        let pairs = (0..self.docs.len())
+            .flat_map(|a| (0..self.docs.len()).map(move |b| (a, b)))
+            .filter(|(a, b)| a != b)
+            .collect::<Vec<_>>();
+        loop {
+            let mut quiet = true;
+            for &(from, to) in &pairs {
+                while self.deliver(from, to)? {
+                    quiet = false;
+                }
+                if self.send(from, to) {
+                    quiet = false;
+                }
+            }
+            if quiet {
+                return Ok(());
+            }
+        }
+    }
+
+    fn send(&mut self, from: usize, to: usize) -> bool {
+        let state = self.states.entry((from, to)).or_default();
+        match self.docs[from].sync().generate_sync_message(state) {
+            Some(message) => {
+                self.in_flight
+                    .entry((from, to))
+                    .or_default()
+                    .push_back(message);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn deliver(&mut self, from: usize, to: usize) -> Result<bool, AutomergeError> {
+        let Some(message) = self
+            .in_flight
+            .get_mut(&(from, to))
+            .and_then(|q| q.pop_front())
+        else {
+            return Ok(false);
+        };
+        let state = self.states.entry((to, from)).or_default();
+        self.docs[to].sync().receive_sync_message(state, message)?;
+        Ok(true)
+    }
+}
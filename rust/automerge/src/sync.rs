@@ -69,9 +69,10 @@
 
 use itertools::Itertools;
 use serde::ser::SerializeMap;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 
 use crate::{
+    instrument::{record, Timer},
     patches::{PatchLog, TextRepresentation},
     storage::{parse, ReadChangeOpError},
     Automerge, AutomergeError, Change, ChangeHash, ReadDoc,
@@ -79,13 +80,18 @@ use crate::{
 
 mod bloom;
 mod message_builder;
+mod multi_peer;
+mod presence;
 mod state;
 use message_builder::MessageBuilder;
+use multi_peer::{ChangesSince, SharedWork};
 
 #[cfg(test)]
 mod v1_compat_test;
 
 pub use bloom::{BloomFilter, DecodeError as DecodeBloomError};
+pub use multi_peer::MultiPeer;
+pub use presence::{Presence, PresenceEvent, PresencePeer};
 pub use state::DecodeError as DecodeStateError;
 pub use state::{Have, State};
 
@@ -103,6 +109,8 @@ pub trait SyncDoc {
     /// * `message` - The [`Message`] to receive
     /// * `patch_log` - A [`PatchLog`] which will be updated with any changes that are made to the
     ///                 current state of the document due to the received sync message
+    ///
+    /// Messages are generated with the [`SyncOptions`] of `sync_state`, see [`State::options`].
     fn generate_sync_message(&self, sync_state: &mut State) -> Option<Message>;
 
     /// Apply a received sync message to this document and `sync_state`
@@ -133,6 +141,25 @@ pub trait SyncDoc {
     ) -> Result<(), AutomergeError>;
 }
 
+/// Options for generating the messages for one peer, see [`State::options`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SyncOptions {
+    /// The largest message to generate, in bytes
+    ///
+    /// If the changes the peer needs don't fit in one message they are spread over several, each
+    /// of which is generated once the peer has replied to the previous one. The changes are sent
+    /// in causal order, so the peer can apply each message as it arrives. A change which is
+    /// larger than this on its own is sent in a message of its own, as changes can't be split.
+    pub max_message_size: Option<usize>,
+}
+
+/// The capabilities we advertise in the first message of a session
+const SUPPORTED_CAPABILITIES: [Capability; 3] = [
+    Capability::MessageV1,
+    Capability::MessageV2,
+    Capability::Ephemeral,
+];
+
 const MESSAGE_TYPE_SYNC: u8 = 0x42; // first byte of a sync message, for identification
 const MESSAGE_TYPE_SYNC_V2: u8 = 0x43; // first byte of a sync message, for identification
 
@@ -165,9 +192,63 @@ impl MessageVersion {
 
 impl SyncDoc for Automerge {
     fn generate_sync_message(&self, sync_state: &mut State) -> Option<Message> {
+        let options = sync_state.options.clone();
+        self.generate_sync_message_shared(sync_state, &options, &mut SharedWork::default())
+    }
+
+    fn receive_sync_message(
+        &mut self,
+        sync_state: &mut State,
+        message: Message,
+    ) -> Result<(), AutomergeError> {
+        let mut patch_log = PatchLog::inactive(TextRepresentation::default());
+        self.receive_sync_message_inner(sync_state, message, &mut patch_log)
+    }
+
+    fn receive_sync_message_log_patches(
+        &mut self,
+        sync_state: &mut State,
+        message: Message,
+        patch_log: &mut PatchLog,
+    ) -> Result<(), AutomergeError> {
+        self.receive_sync_message_inner(sync_state, message, patch_log)
+    }
+}
+
+impl Automerge {
+    /// Generate a sync message, reusing and adding to the work in `shared`
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip_all,
+            fields(
+                changes = tracing::field::Empty,
+                bytes = tracing::field::Empty,
+                duration_us = tracing::field::Empty,
+            )
+        )
+    )]
+    pub(crate) fn generate_sync_message_shared(
+        &self,
+        sync_state: &mut State,
+        options: &SyncOptions,
+        shared: &mut SharedWork,
+    ) -> Option<Message> {
+        let _timer = Timer::start();
         let our_heads = self.get_heads();
+        shared.check_heads(self, &our_heads);
 
-        let our_need = self.get_missing_deps(sync_state.their_heads.as_ref().unwrap_or(&vec![]));
+        // a state saved before a crash which lost changes may refer to changes we no longer have
+        if !sync_state
+            .shared_heads
+            .iter()
+            .all(|hash| self.get_change_by_hash(hash).is_some())
+        {
+            sync_state.shared_heads.clear();
+        }
+
+        let our_need =
+            shared.missing_deps(self, sync_state.their_heads.as_ref().unwrap_or(&vec![]));
 
         let their_heads_set = if let Some(ref heads) = sync_state.their_heads {
             heads.iter().collect::<HashSet<_>>()
@@ -175,11 +256,19 @@ impl SyncDoc for Automerge {
             HashSet::new()
         };
         let our_have = if our_need.iter().all(|hash| their_heads_set.contains(hash)) {
-            vec![self.make_bloom_filter(sync_state.shared_heads.clone())]
+            vec![shared.bloom(self, sync_state.shared_heads.clone())]
         } else {
             Vec::new()
         };
 
+        // Only send the supported capabilities in the first message, the other end will store them
+        // in it's sync state and use them for subsequent messages
+        let supported_capabilities = if sync_state.have_responded {
+            None
+        } else {
+            Some(SUPPORTED_CAPABILITIES.to_vec())
+        };
+
         if let Some(ref their_have) = sync_state.their_have {
             if let Some(first_have) = their_have.first().as_ref() {
                 if !first_have
@@ -192,17 +281,42 @@ impl SyncDoc for Automerge {
                         need: Vec::new(),
                         have: vec![Have::default()],
                         changes: ChunkList::empty(),
-                        supported_capabilities: Some(vec![
-                            Capability::MessageV1,
-                            Capability::MessageV2,
-                        ]),
+                        supported_capabilities,
+                        ephemeral: Vec::new(),
                         version: MessageVersion::V1,
                     };
+                    sync_state.have_responded = true;
                     return Some(reset_msg);
                 }
             }
         }
 
+        // ephemeral data for peers which can't receive it is dropped, and for peers we have not
+        // heard from yet is held until we know
+        if sync_state.supports_ephemeral() == Some(false) {
+            sync_state.ephemeral_to_send.clear();
+        }
+        let send_ephemeral = !sync_state.ephemeral_to_send.is_empty()
+            && sync_state.supports_ephemeral() == Some(true);
+
+        // the number of bytes left for changes once everything else is in the message
+        let changes_budget = options.max_message_size.map(|max| {
+            let without_changes = Message {
+                heads: our_heads.clone(),
+                need: our_need.clone(),
+                have: our_have.clone(),
+                changes: ChunkList::empty(),
+                supported_capabilities: supported_capabilities.clone(),
+                ephemeral: if send_ephemeral {
+                    sync_state.ephemeral_to_send.clone()
+                } else {
+                    Vec::new()
+                },
+                version: MessageVersion::V1,
+            };
+            max.saturating_sub(without_changes.encode().len())
+        });
+
         let (message_builder, sent_hashes) = if let (Some(their_have), Some(their_need)) = (
             sync_state.their_have.as_ref(),
             sync_state.their_need.as_ref(),
@@ -213,24 +327,27 @@ impl SyncDoc for Automerge {
                 .map(|h| h.is_empty())
                 .unwrap_or(false)
                 && !sync_state.have_responded
-                && sync_state.supports_v2_messages();
+                && sync_state.supports_v2_messages()
+                && changes_budget
+                    .map(|budget| chunk_len(shared.saved(self).len()) <= budget)
+                    .unwrap_or(true);
 
             if send_doc {
-                let hashes = self
-                    .get_changes(&[])
-                    .iter()
-                    .map(|c| c.hash())
-                    .collect::<Vec<_>>();
-                (MessageBuilder::new_v2(self.save()), hashes)
+                let hashes = shared.changes_since(self, &[]).hashes.clone();
+                (MessageBuilder::new_v2(shared.saved(self)), hashes)
             } else {
                 let all_changes = self
-                    .get_changes_to_send(their_have, their_need)
+                    .get_changes_to_send(their_have, their_need, shared)
                     .expect("Should have only used hashes that are in the document");
                 // deduplicate the changes to send with those we have already sent and clone it now
                 let changes = all_changes
                     .into_iter()
                     .filter(|change| !sync_state.sent_hashes.contains(&change.hash()));
-                let hashes = changes.clone().map(|c| c.hash()).collect::<Vec<_>>();
+                let changes = match changes_budget {
+                    Some(budget) => first_page(changes, budget),
+                    None => changes.collect(),
+                };
+                let hashes = changes.iter().map(|c| c.hash()).collect::<Vec<_>>();
                 if sync_state.supports_v2_messages() {
                     let encoded = changes
                         .into_iter()
@@ -238,7 +355,7 @@ impl SyncDoc for Automerge {
                         .collect::<Vec<_>>();
                     (MessageBuilder::new_v2(encoded), hashes)
                 } else {
-                    (MessageBuilder::new_v1(changes), hashes)
+                    (MessageBuilder::new_v1(changes.into_iter()), hashes)
                 }
             }
         } else if sync_state.supports_v2_messages() {
@@ -255,7 +372,7 @@ impl SyncDoc for Automerge {
             false
         };
 
-        if heads_unchanged && sync_state.have_responded {
+        if heads_unchanged && sync_state.have_responded && !send_ephemeral {
             if heads_equal && !message_builder.has_changes_to_send() {
                 return None;
             }
@@ -264,16 +381,14 @@ impl SyncDoc for Automerge {
             }
         }
 
-        // Only send the supported capabilities in the first message, the other end will store them
-        // in it's sync state and use them for subsequent messages
-        let supported_capabilities = if sync_state.have_responded {
-            None
+        sync_state.have_responded = true;
+        let ephemeral = if send_ephemeral {
+            std::mem::take(&mut sync_state.ephemeral_to_send)
         } else {
-            Some(vec![Capability::MessageV1, Capability::MessageV2])
+            Vec::new()
         };
-
-        sync_state.have_responded = true;
         sync_state.last_sent_heads.clone_from(&our_heads);
+        record!(changes = sent_hashes.len());
         sync_state.sent_hashes.extend(sent_hashes);
 
         let sync_message = message_builder
@@ -281,32 +396,16 @@ impl SyncDoc for Automerge {
             .have(our_have)
             .need(our_need)
             .supported_capabilities(supported_capabilities)
+            .ephemeral(ephemeral)
             .build();
+        let bytes = sync_message.changes.iter().map(|c| c.len()).sum::<usize>();
+        record!(bytes = bytes);
+        self.report(|m| m.sync_message_generated(bytes));
 
         sync_state.in_flight = true;
         Some(sync_message)
     }
 
-    fn receive_sync_message(
-        &mut self,
-        sync_state: &mut State,
-        message: Message,
-    ) -> Result<(), AutomergeError> {
-        let mut patch_log = PatchLog::inactive(TextRepresentation::default());
-        self.receive_sync_message_inner(sync_state, message, &mut patch_log)
-    }
-
-    fn receive_sync_message_log_patches(
-        &mut self,
-        sync_state: &mut State,
-        message: Message,
-        patch_log: &mut PatchLog,
-    ) -> Result<(), AutomergeError> {
-        self.receive_sync_message_inner(sync_state, message, patch_log)
-    }
-}
-
-impl Automerge {
     fn make_bloom_filter(&self, last_sync: Vec<ChangeHash>) -> Have {
         let new_changes = self.get_changes(&last_sync);
         let hashes = new_changes.iter().map(|change| change.hash());
@@ -320,6 +419,7 @@ impl Automerge {
         &self,
         have: &[Have],
         need: &[ChangeHash],
+        shared: &mut SharedWork,
     ) -> Result<Vec<&Change>, AutomergeError> {
         if have.is_empty() {
             Ok(need
@@ -337,24 +437,12 @@ impl Automerge {
             }
             let last_sync_hashes = last_sync_hashes.into_iter().copied().collect::<Vec<_>>();
 
-            let changes = self.get_changes(&last_sync_hashes);
+            let ChangesSince { hashes, dependents } = shared.changes_since(self, &last_sync_hashes);
 
-            let mut change_hashes = HashSet::with_capacity(changes.len());
-            let mut dependents: HashMap<ChangeHash, Vec<ChangeHash>> = HashMap::new();
             let mut hashes_to_send = HashSet::new();
-
-            for change in &changes {
-                change_hashes.insert(change.hash());
-
-                for dep in change.deps() {
-                    dependents.entry(*dep).or_default().push(change.hash());
-                }
-
-                if bloom_filters
-                    .iter()
-                    .all(|bloom| !bloom.contains_hash(&change.hash()))
-                {
-                    hashes_to_send.insert(change.hash());
+            for hash in hashes {
+                if bloom_filters.iter().all(|bloom| !bloom.contains_hash(hash)) {
+                    hashes_to_send.insert(*hash);
                 }
             }
 
@@ -378,21 +466,37 @@ impl Automerge {
                 }
             }
 
-            for change in changes {
-                if hashes_to_send.contains(&change.hash()) {
-                    changes_to_send.push(change);
+            for hash in hashes {
+                if hashes_to_send.contains(hash) {
+                    if let Some(change) = self.get_change_by_hash(hash) {
+                        changes_to_send.push(change);
+                    }
                 }
             }
             Ok(changes_to_send)
         }
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip_all,
+            fields(
+                heads = message.heads.len(),
+                chunks = message.changes.len(),
+                bytes = message.changes.iter().map(|c| c.len()).sum::<usize>(),
+                duration_us = tracing::field::Empty,
+            ),
+            err
+        )
+    )]
     pub(crate) fn receive_sync_message_inner(
         &mut self,
         sync_state: &mut State,
         message: Message,
         patch_log: &mut PatchLog,
     ) -> Result<(), AutomergeError> {
+        let _timer = Timer::start();
         sync_state.in_flight = false;
         let before_heads = self.get_heads();
 
@@ -402,12 +506,22 @@ impl Automerge {
             need: message_need,
             have: message_have,
             supported_capabilities,
+            ephemeral,
             ..
         } = message;
 
+        // Capabilities are only sent in the first message of a session, so if the peer already
+        // sent us its capabilities it has started a new session and may have lost anything we
+        // sent it. Every peer supports at least one capability, so an empty list is only a
+        // placeholder in front of ephemeral data.
+        let supported_capabilities = supported_capabilities.filter(|caps| !caps.is_empty());
+        if supported_capabilities.is_some() && sync_state.their_capabilities.is_some() {
+            sync_state.restart_session();
+        }
         if let Some(caps) = supported_capabilities {
             sync_state.their_capabilities = Some(caps);
         }
+        sync_state.receive_ephemeral(ephemeral);
 
         let changes_is_empty = message_changes.is_empty();
         if !changes_is_empty {
@@ -458,6 +572,36 @@ impl Automerge {
     }
 }
 
+/// The number of bytes `n` takes up when LEB128 encoded
+fn leb128_len(n: usize) -> usize {
+    ((usize::BITS - n.leading_zeros()).max(1) as usize).div_ceil(7)
+}
+
+/// The number of bytes a chunk of `len` bytes takes up in an encoded message
+fn chunk_len(len: usize) -> usize {
+    leb128_len(len) + len
+}
+
+/// The longest run of `changes` from the start which fits in `budget` bytes, or just the first
+/// change if even that doesn't fit
+///
+/// This assumes each change is a chunk of its own. In a v2 message the changes are sent as one
+/// chunk, which is never larger.
+fn first_page<'a, I: Iterator<Item = &'a Change>>(changes: I, budget: usize) -> Vec<&'a Change> {
+    let mut used = 0;
+    let mut page = Vec::new();
+    for change in changes {
+        used += chunk_len(change.raw_bytes().len());
+        // the budget already includes the one byte count of an empty list of changes
+        let count_len = leb128_len(page.len() + 1) - 1;
+        if !page.is_empty() && used + count_len > budget {
+            break;
+        }
+        page.push(change);
+    }
+    page
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ReadMessageError {
     #[error("expected {expected_one_of:?} but found {found}")]
@@ -519,6 +663,17 @@ impl From<parse::ParseError<ReadMessageError>> for ReadMessageError {
 /// the advertised capabilities on the sync state. This allows new implementations to discover if
 /// the remote peer supports the V2 message format (the `Capability::MessageV2` capability) and if
 /// so send a V2 message.
+///
+/// Ephemeral data is appended after the capabilities in the same way, as a length prefixed list
+/// of length prefixed byte arrays, and only sent to peers which advertise the
+/// `Capability::Ephemeral` capability. A message with ephemeral data but no capabilities is encoded
+/// with an empty list of capabilities, which receivers take to mean that the sender sent no
+/// capabilities.
+///
+/// The ephemeral capability and section are an extension of this implementation, they are not
+/// part of the sync protocol which other automerge implementations speak. Those implementations
+/// see `Capability::Ephemeral` as an unknown capability and never advertise it, so they are never
+/// sent the ephemeral section.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Message {
     /// The heads of the sender.
@@ -536,6 +691,11 @@ pub struct Message {
     pub changes: ChunkList,
     /// The capabilities the sender supports
     pub supported_capabilities: Option<Vec<Capability>>,
+    /// Data which is not part of the document, see [`State::send_ephemeral()`]
+    ///
+    /// This is only encoded if it is not empty, so it can be left empty for peers which don't
+    /// advertise [`Capability::Ephemeral`].
+    pub ephemeral: Vec<Vec<u8>>,
     /// What version to encode this message as
     pub version: MessageVersion,
 }
@@ -628,6 +788,14 @@ impl Message {
         } else {
             (i, None)
         };
+        let (i, ephemeral) = if !i.is_empty() {
+            parse::length_prefixed(|i| {
+                let (i, data) = parse::length_prefixed_bytes(i)?;
+                Ok((i, data.to_vec()))
+            })(i)?
+        } else {
+            (i, Vec::new())
+        };
         Ok((
             i,
             Message {
@@ -636,6 +804,7 @@ impl Message {
                 have,
                 changes,
                 supported_capabilities,
+                ephemeral,
                 version: message_version,
             },
         ))
@@ -657,21 +826,35 @@ impl Message {
             buf.extend::<&[u8]>(change.as_ref())
         });
 
-        if let Some(supported_capabilities) = self.supported_capabilities {
+        if self.supported_capabilities.is_some() || !self.ephemeral.is_empty() {
+            let supported_capabilities = self.supported_capabilities.unwrap_or_default();
             encode_many(&mut buf, supported_capabilities.iter(), |buf, cap| {
                 cap.encode(buf);
             });
         }
 
+        if !self.ephemeral.is_empty() {
+            encode_many(&mut buf, self.ephemeral.iter(), |buf, data| {
+                leb128::write::unsigned(buf, data.len() as u64).unwrap();
+                buf.extend_from_slice(data)
+            });
+        }
+
         buf
     }
 }
 
 #[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum Capability {
     #[default]
     MessageV1,
     MessageV2,
+    /// Messages may carry ephemeral data, see [`State::send_ephemeral()`]
+    ///
+    /// This is encoded as `0x03`. It is an extension of this implementation rather than part of
+    /// the sync protocol, see the notes on [`Message`].
+    Ephemeral,
     Unknown(u8),
 }
 
@@ -680,6 +863,7 @@ impl Capability {
         match self {
             Capability::MessageV1 => out.push(0x01),
             Capability::MessageV2 => out.push(0x02),
+            Capability::Ephemeral => out.push(0x03),
             Capability::Unknown(v) => out.push(*v),
         }
     }
@@ -689,6 +873,7 @@ impl Capability {
         match v {
             0x01 => Ok((i, Self::MessageV1)),
             0x02 => Ok((i, Self::MessageV2)),
+            0x03 => Ok((i, Self::Ephemeral)),
             _ => Ok((i, Self::Unknown(v))),
         }
     }
@@ -791,6 +976,7 @@ mod tests {
                 have,
                 changes: changes.into_iter().map(|c| c.raw_bytes().to_vec()).collect::<Vec<Vec<u8>>>().into(),
                 supported_capabilities,
+                ephemeral: Vec::new(),
                 version: MessageVersion::V1,
             }
         }
@@ -815,6 +1001,7 @@ mod tests {
                 have,
                 changes: ChunkList::from(raw),
                 supported_capabilities,
+                ephemeral: Vec::new(),
                 version: MessageVersion::V2,
             }
         }
@@ -832,6 +1019,7 @@ mod tests {
             have: vec![],
             changes: ChunkList::empty(),
             supported_capabilities: None,
+            ephemeral: Vec::new(),
             version: MessageVersion::V2,
         };
         let encoded = msg.encode();
@@ -1232,6 +1420,434 @@ mod tests {
         }
     }
 
+    #[test]
+    fn multi_peer_generates_the_same_messages_as_separate_states() {
+        let mut hub = crate::Automerge::new();
+        let mut multi = MultiPeer::new();
+        let mut peers = (0..3)
+            .map(|i| {
+                let mut doc = crate::AutoCommit::new();
+                doc.put(crate::ROOT, "peer", i).unwrap();
+                (doc, State::new())
+            })
+            .collect::<Vec<_>>();
+        peers.push((crate::AutoCommit::new(), State::new()));
+        let mut tx = hub.transaction();
+        tx.put(crate::ROOT, "hub", true).unwrap();
+        tx.commit();
+
+        for _ in 0..10 {
+            let mut sent = false;
+            for (i, (doc, state)) in peers.iter_mut().enumerate() {
+                let mut separate = multi.state(&i).cloned().unwrap_or_default();
+                let expected = hub.generate_sync_message(&mut separate);
+                let message = multi.generate_sync_message(&hub, &i);
+                assert_eq!(message, expected);
+                assert_eq!(multi.state(&i), Some(&separate));
+                if let Some(message) = message {
+                    sent = true;
+                    doc.sync().receive_sync_message(state, message).unwrap();
+                }
+                if let Some(reply) = doc.sync().generate_sync_message(state) {
+                    sent = true;
+                    multi.receive_sync_message(&mut hub, &i, reply).unwrap();
+                }
+            }
+            if !sent {
+                break;
+            }
+        }
+        for (doc, _) in &mut peers {
+            assert_eq!(doc.get_heads(), hub.get_heads());
+        }
+        assert_eq!(multi.peers().count(), 4);
+        assert!(multi.remove_peer(&0).is_some());
+        assert!(multi.state(&0).is_none());
+    }
+
+    #[test]
+    fn peer_which_restarts_and_loses_changes_is_sent_them_again() {
+        let mut doc1 = crate::AutoCommit::new();
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        doc1.put(crate::ROOT, "a", 1).unwrap();
+        sync(&mut doc1, &mut doc2, &mut s1, &mut s2);
+        let saved = doc2.save();
+
+        // doc2 receives a change but crashes before saving it
+        doc1.put(crate::ROOT, "b", 2).unwrap();
+        let msg = doc1.sync().generate_sync_message(&mut s1).unwrap();
+        doc2.sync().receive_sync_message(&mut s2, msg).unwrap();
+        let mut doc2 = crate::AutoCommit::load(&saved).unwrap();
+        let mut s2 = State::new();
+
+        sync(&mut doc1, &mut doc2, &mut s1, &mut s2);
+        assert_eq!(doc1.get_heads(), doc2.get_heads());
+        assert_eq!(s1.peer_resets, 1);
+        assert_eq!(s2.peer_resets, 0);
+        // doc1 advertised its capabilities again for doc2's new session
+        assert!(s2.supports_v2_messages());
+        assert_eq!(s2.supports_ephemeral(), Some(true));
+    }
+
+    #[test]
+    fn a_restarted_peer_which_asks_for_a_reset_is_only_counted_once() {
+        let mut doc1 = crate::AutoCommit::new();
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        doc1.put(crate::ROOT, "a", 1).unwrap();
+        sync(&mut doc1, &mut doc2, &mut s1, &mut s2);
+        let saved = doc2.save();
+        doc1.put(crate::ROOT, "b", 2).unwrap();
+        sync(&mut doc1, &mut doc2, &mut s1, &mut s2);
+
+        // doc2 restarts without the last change and hears from doc1 before it says anything, so
+        // its first message asks doc1 to start over
+        let mut doc2 = crate::AutoCommit::load(&saved).unwrap();
+        let mut s2 = State::new();
+        doc1.put(crate::ROOT, "c", 3).unwrap();
+        let msg = doc1.sync().generate_sync_message(&mut s1).unwrap();
+        doc2.sync().receive_sync_message(&mut s2, msg).unwrap();
+        let reset = doc2.sync().generate_sync_message(&mut s2).unwrap();
+        assert_eq!(reset.have, vec![Have::default()]);
+        assert!(reset.supported_capabilities.is_some());
+        doc1.sync().receive_sync_message(&mut s1, reset).unwrap();
+
+        sync(&mut doc1, &mut doc2, &mut s1, &mut s2);
+        assert_eq!(doc1.get_heads(), doc2.get_heads());
+        assert_eq!(s1.peer_resets, 1);
+        assert_eq!(s2.peer_resets, 0);
+    }
+
+    #[test]
+    fn persisted_state_which_is_ahead_of_the_document_is_discarded() {
+        let mut doc1 = crate::AutoCommit::new();
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        doc1.put(crate::ROOT, "a", 1).unwrap();
+        sync(&mut doc1, &mut doc2, &mut s1, &mut s2);
+        let saved = doc2.save();
+
+        // doc2 persists its sync state but not the change it just received
+        doc1.put(crate::ROOT, "b", 2).unwrap();
+        sync(&mut doc1, &mut doc2, &mut s1, &mut s2);
+        let persisted = s2.encode();
+        let mut doc2 = crate::AutoCommit::load(&saved).unwrap();
+        let mut s2 = State::decode(&persisted).unwrap();
+        let mut s1 = State::decode(&s1.encode()).unwrap();
+
+        sync(&mut doc1, &mut doc2, &mut s1, &mut s2);
+        assert_eq!(doc1.get_heads(), doc2.get_heads());
+    }
+
+    #[test]
+    fn sync_state_encoding_records_its_version() {
+        let state = State {
+            shared_heads: vec![ChangeHash([1; 32])],
+            ..State::new()
+        };
+        let encoded = state.encode();
+        assert_eq!(
+            State::decode(&encoded).unwrap().shared_heads,
+            state.shared_heads
+        );
+        // the type byte and the heads, which older versions read, then the version
+        assert_eq!(encoded.len(), 1 + 1 + 32 + 1);
+
+        let unversioned = &encoded[..encoded.len() - 1];
+        assert_eq!(
+            State::decode(unversioned).unwrap().shared_heads,
+            state.shared_heads
+        );
+
+        let mut future = encoded.clone();
+        *future.last_mut().unwrap() += 1;
+        assert!(matches!(
+            State::decode(&future),
+            Err(DecodeStateError::Parse(_))
+        ));
+
+        let mut wrong_type = encoded.clone();
+        wrong_type[0] += 1;
+        assert!(matches!(
+            State::decode(&wrong_type),
+            Err(DecodeStateError::WrongType { found, .. }) if found == encoded[0] + 1
+        ));
+    }
+
+    #[test]
+    fn sync_states_are_compared_without_options_or_ephemeral_data() {
+        let state = State {
+            peer_resets: 2,
+            ..State::new()
+        };
+        let mut used = state.clone();
+        used.in_flight = true;
+        used.options.max_message_size = Some(1024);
+        used.send_ephemeral(b"cursor".to_vec());
+        assert_ne!(used, state);
+        used.reset();
+        assert_eq!(used, state);
+        assert_eq!(used.peer_resets, 2);
+        assert_eq!(used.options.max_message_size, Some(1024));
+        assert_eq!(used.ephemeral_to_send, vec![b"cursor".to_vec()]);
+    }
+
+    #[test]
+    fn max_message_size_spreads_changes_over_several_messages() {
+        let mut doc1 = crate::AutoCommit::new();
+        for i in 0..100 {
+            doc1.put(crate::ROOT, format!("key{}", i), "x".repeat(100))
+                .unwrap();
+            doc1.commit();
+        }
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        let options = SyncOptions {
+            max_message_size: Some(1000),
+        };
+        s1.options = options.clone();
+        s2.options = options;
+
+        let mut messages_with_changes = 0;
+        for _ in 0..100 {
+            let one_to_two = doc1.sync().generate_sync_message(&mut s1);
+            let two_to_one = doc2.sync().generate_sync_message(&mut s2);
+            if one_to_two.is_none() && two_to_one.is_none() {
+                break;
+            }
+            if let Some(msg) = one_to_two {
+                if !msg.changes.is_empty() {
+                    messages_with_changes += 1;
+                }
+                assert!(msg.clone().encode().len() <= 1000);
+                doc2.sync().receive_sync_message(&mut s2, msg).unwrap();
+                // each page can be applied as it arrives
+                assert!(doc2.document().get_missing_deps(&[]).is_empty());
+            }
+            if let Some(msg) = two_to_one {
+                assert!(msg.clone().encode().len() <= 1000);
+                doc1.sync().receive_sync_message(&mut s1, msg).unwrap();
+            }
+        }
+        assert_eq!(doc1.get_heads(), doc2.get_heads());
+        assert!(messages_with_changes > 1);
+    }
+
+    #[test]
+    fn change_larger_than_max_message_size_is_sent_alone() {
+        let mut doc1 = crate::AutoCommit::new();
+        doc1.put(crate::ROOT, "small", 1).unwrap();
+        doc1.commit();
+        doc1.put(crate::ROOT, "large", "x".repeat(1000)).unwrap();
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        let options = SyncOptions {
+            max_message_size: Some(200),
+        };
+        s1.options = options;
+
+        for _ in 0..10 {
+            let one_to_two = doc1.sync().generate_sync_message(&mut s1);
+            let two_to_one = doc2.sync().generate_sync_message(&mut s2);
+            if one_to_two.is_none() && two_to_one.is_none() {
+                break;
+            }
+            if let Some(msg) = one_to_two {
+                assert!(msg.changes.len() <= 1);
+                doc2.sync().receive_sync_message(&mut s2, msg).unwrap();
+            }
+            if let Some(msg) = two_to_one {
+                doc1.sync().receive_sync_message(&mut s1, msg).unwrap();
+            }
+        }
+        assert_eq!(doc1.get_heads(), doc2.get_heads());
+    }
+
+    #[test]
+    fn ephemeral_data_is_sent_to_synced_peers() {
+        let mut doc1 = crate::AutoCommit::new();
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        doc1.put(crate::ROOT, "a", 1).unwrap();
+        sync(&mut doc1, &mut doc2, &mut s1, &mut s2);
+        assert_eq!(s1.supports_ephemeral(), Some(true));
+
+        s1.send_ephemeral(b"cursor".to_vec());
+        let msg = doc1.sync().generate_sync_message(&mut s1).unwrap();
+        assert_eq!(msg.ephemeral, vec![b"cursor".to_vec()]);
+        let msg = Message::decode(&msg.encode()).unwrap();
+        doc2.sync().receive_sync_message(&mut s2, msg).unwrap();
+        assert_eq!(s2.take_ephemeral(), vec![b"cursor".to_vec()]);
+        assert!(s2.take_ephemeral().is_empty());
+
+        // nothing else needs to be said
+        sync(&mut doc1, &mut doc2, &mut s1, &mut s2);
+        assert!(s1.ephemeral_to_send.is_empty());
+        assert!(s2.take_ephemeral().is_empty());
+    }
+
+    #[test]
+    fn ephemeral_data_does_not_restart_the_session() {
+        let mut doc1 = crate::AutoCommit::new();
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        doc1.put(crate::ROOT, "a", 1).unwrap();
+
+        // messages with ephemeral data but no capabilities only look different on the wire
+        let over_the_wire = |msg: Message| Message::decode(&msg.encode()).unwrap();
+        for round in 0..5_u8 {
+            s1.send_ephemeral(vec![round]);
+            s2.send_ephemeral(vec![round, round]);
+            doc1.put(crate::ROOT, "round", round as i64).unwrap();
+            for _ in 0..10 {
+                let one_to_two = doc1.sync().generate_sync_message(&mut s1);
+                let two_to_one = doc2.sync().generate_sync_message(&mut s2);
+                if one_to_two.is_none() && two_to_one.is_none() {
+                    break;
+                }
+                if let Some(msg) = one_to_two {
+                    let msg = over_the_wire(msg);
+                    doc2.sync().receive_sync_message(&mut s2, msg).unwrap();
+                }
+                if let Some(msg) = two_to_one {
+                    let msg = over_the_wire(msg);
+                    doc1.sync().receive_sync_message(&mut s1, msg).unwrap();
+                }
+            }
+            assert_eq!(s2.take_ephemeral(), vec![vec![round]]);
+            assert_eq!(s1.take_ephemeral(), vec![vec![round, round]]);
+        }
+        for state in [&s1, &s2] {
+            assert_eq!(state.peer_resets, 0);
+            assert!(state.supports_v2_messages());
+            assert_eq!(state.supports_ephemeral(), Some(true));
+        }
+        assert_eq!(doc1.get_heads(), doc2.get_heads());
+    }
+
+    #[test]
+    fn ephemeral_data_which_is_not_taken_is_limited() {
+        let mut doc1 = crate::AutoCommit::new();
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        sync(&mut doc1, &mut doc2, &mut s1, &mut s2);
+
+        for round in 0..3_u16 {
+            for item in 0..200_u16 {
+                s1.send_ephemeral((round * 200 + item).to_be_bytes().to_vec());
+            }
+            let msg = doc1.sync().generate_sync_message(&mut s1).unwrap();
+            doc2.sync().receive_sync_message(&mut s2, msg).unwrap();
+        }
+        let received = s2.take_ephemeral();
+        assert_eq!(received.len(), 256);
+        assert_eq!(received.last(), Some(&599_u16.to_be_bytes().to_vec()));
+
+        s1.send_ephemeral(vec![0; 600 * 1024]);
+        s1.send_ephemeral(vec![1; 600 * 1024]);
+        let msg = doc1.sync().generate_sync_message(&mut s1).unwrap();
+        doc2.sync().receive_sync_message(&mut s2, msg).unwrap();
+        assert_eq!(s2.take_ephemeral(), vec![vec![1; 600 * 1024]]);
+    }
+
+    #[test]
+    fn ephemeral_data_is_dropped_for_peers_which_do_not_support_it() {
+        let mut doc1 = crate::AutoCommit::new();
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        s2.send_ephemeral(b"cursor".to_vec());
+        assert_eq!(s2.supports_ephemeral(), None);
+
+        let mut msg = doc1.sync().generate_sync_message(&mut s1).unwrap();
+        msg.supported_capabilities = Some(vec![Capability::MessageV1, Capability::MessageV2]);
+        doc2.sync().receive_sync_message(&mut s2, msg).unwrap();
+        assert_eq!(s2.supports_ephemeral(), Some(false));
+        let msg = doc2.sync().generate_sync_message(&mut s2).unwrap();
+        assert!(msg.ephemeral.is_empty());
+        assert!(s2.ephemeral_to_send.is_empty());
+    }
+
+    #[test]
+    fn encode_decode_ephemeral_without_capabilities() {
+        let msg = Message {
+            heads: vec![],
+            need: vec![],
+            have: vec![],
+            changes: ChunkList::empty(),
+            supported_capabilities: None,
+            ephemeral: vec![b"one".to_vec(), Vec::new()],
+            version: MessageVersion::V2,
+        };
+        let decoded = Message::decode(&msg.clone().encode()).unwrap();
+        assert_eq!(decoded.supported_capabilities, Some(vec![]));
+        assert_eq!(decoded.ephemeral, msg.ephemeral);
+    }
+
+    #[test]
+    fn presence_tracks_peers_until_they_leave_or_time_out() {
+        let start = std::time::Instant::now();
+        let timeout = std::time::Duration::from_secs(10);
+        let mut alice = Presence::<&str>::new(timeout);
+        let mut carol = Presence::<&str>::new(timeout);
+        let mut bob = Presence::new(timeout);
+        let events = bob.subscribe();
+
+        assert_eq!(alice.poll(start), None);
+        alice.set_local(b"one".to_vec());
+        let data = alice.poll(start).unwrap();
+        assert_eq!(alice.poll(start), None);
+        assert!(bob.receive(&"alice", &data, start));
+        assert!(!bob.receive(&"alice", b"something else", start));
+
+        // alice sends her state again before she times out
+        let later = start + timeout / 2;
+        let heartbeat = alice.poll(later).unwrap();
+        assert!(bob.receive(&"alice", &heartbeat, later));
+        assert_eq!(bob.poll(start + timeout), None);
+        assert_eq!(bob.get(&"alice"), Some(&b"one"[..]));
+
+        alice.set_local(b"two".to_vec());
+        bob.receive(&"alice", &alice.poll(later).unwrap(), later);
+        alice.clear_local();
+        bob.receive(&"alice", &alice.poll(later).unwrap(), later);
+        assert_eq!(bob.get(&"alice"), None);
+
+        carol.set_local(b"three".to_vec());
+        bob.receive(&"carol", &carol.poll(later).unwrap(), later);
+        bob.poll(later + timeout);
+        assert_eq!(bob.peers().count(), 0);
+
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![
+                PresenceEvent::Joined {
+                    peer: "alice",
+                    state: b"one".to_vec()
+                },
+                PresenceEvent::Updated {
+                    peer: "alice",
+                    state: b"two".to_vec()
+                },
+                PresenceEvent::Left { peer: "alice" },
+                PresenceEvent::Joined {
+                    peer: "carol",
+                    state: b"three".to_vec()
+                },
+                PresenceEvent::Left { peer: "carol" },
+            ]
+        );
+    }
+
     fn sync(
         a: &mut crate::AutoCommit,
         b: &mut crate::AutoCommit,
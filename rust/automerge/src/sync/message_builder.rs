@@ -8,6 +8,7 @@ pub(super) struct MessageBuilder {
     have: Vec<Have>,
     changes: Vec<Vec<u8>>,
     supported_capabilities: Option<Vec<Capability>>,
+    ephemeral: Vec<Vec<u8>>,
     version: MessageVersion,
 }
 
@@ -19,6 +20,7 @@ impl MessageBuilder {
             have: Vec::new(),
             changes: changes.map(|c| c.raw_bytes().to_vec()).collect(),
             supported_capabilities: None,
+            ephemeral: Vec::new(),
             version: MessageVersion::V1,
         }
     }
@@ -34,6 +36,7 @@ impl MessageBuilder {
             },
             have: Vec::new(),
             supported_capabilities: None,
+            ephemeral: Vec::new(),
             version: MessageVersion::V2,
         }
     }
@@ -61,6 +64,11 @@ impl MessageBuilder {
         self
     }
 
+    pub(super) fn ephemeral(mut self, ephemeral: Vec<Vec<u8>>) -> Self {
+        self.ephemeral = ephemeral;
+        self
+    }
+
     pub(super) fn build(self) -> Message {
         Message {
             heads: self.heads,
@@ -68,6 +76,7 @@ impl MessageBuilder {
             have: self.have,
             changes: super::ChunkList::from(self.changes),
             supported_capabilities: self.supported_capabilities,
+            ephemeral: self.ephemeral,
             version: self.version,
         }
     }
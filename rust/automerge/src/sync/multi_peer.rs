@@ -0,0 +1,230 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use super::{Have, Message, State, SyncOptions};
+use crate::{Automerge, AutomergeError, ChangeHash, ReadDoc};
+
+/// Syncs one document with many peers, sharing work between them
+///
+/// Generating a sync message for a peer walks the change graph to build a bloom filter of the
+/// changes since the heads the peer shares with us, to find the changes the peer is missing and
+/// to find the changes we are missing. Peers which are in the same position ask for the same
+/// walks: every peer which is up to date shares the same heads with us and, when a change is
+/// made, every one of them needs the same new changes. A `MultiPeer` keeps a [`State`] for each
+/// peer and remembers the results of these walks until the document changes, so that they are
+/// done once however many peers need them.
+///
+/// ```
+/// # use automerge::{AutoCommit, ROOT, sync::{MultiPeer, SyncDoc}, transaction::Transactable};
+/// let mut doc = AutoCommit::new();
+/// doc.put(ROOT, "key", "value").unwrap();
+///
+/// let mut peers = MultiPeer::new();
+/// let mut replicas = vec![AutoCommit::new(), AutoCommit::new()];
+/// let mut states = vec![automerge::sync::State::new(), automerge::sync::State::new()];
+/// loop {
+///     let messages = peers.generate_sync_messages(doc.document(), 0..replicas.len());
+///     let mut replies = Vec::new();
+///     for (peer, message) in messages.iter().cloned() {
+///         replicas[peer].sync().receive_sync_message(&mut states[peer], message).unwrap();
+///         if let Some(reply) = replicas[peer].sync().generate_sync_message(&mut states[peer]) {
+///             replies.push((peer, reply));
+///         }
+///     }
+///     for (peer, reply) in replies.iter().cloned() {
+///         doc.sync().receive_sync_message(peers.state_mut(&peer), reply).unwrap();
+///     }
+///     if messages.is_empty() && replies.is_empty() {
+///         break;
+///     }
+/// }
+/// let heads = doc.get_heads();
+/// assert!(replicas.iter_mut().all(|r| r.get_heads() == heads));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MultiPeer<P> {
+    peers: HashMap<P, State>,
+    options: SyncOptions,
+    shared: SharedWork,
+}
+
+impl<P> Default for MultiPeer<P> {
+    fn default() -> Self {
+        Self {
+            peers: HashMap::new(),
+            options: SyncOptions::default(),
+            shared: SharedWork::default(),
+        }
+    }
+}
+
+impl<P: Eq + Hash + Clone> MultiPeer<P> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Generate every message with `options`, instead of the options of the state of each peer
+    pub fn with_options(options: SyncOptions) -> Self {
+        Self {
+            options,
+            ..Self::default()
+        }
+    }
+
+    /// Start syncing with `peer` from `state`, for example a state saved with
+    /// [`State::encode()`] in an earlier session
+    pub fn insert_peer(&mut self, peer: P, state: State) -> Option<State> {
+        self.peers.insert(peer, state)
+    }
+
+    /// Stop syncing with `peer`, returning its state
+    pub fn remove_peer(&mut self, peer: &P) -> Option<State> {
+        self.peers.remove(peer)
+    }
+
+    pub fn peers(&self) -> impl Iterator<Item = &P> {
+        self.peers.keys()
+    }
+
+    pub fn state(&self, peer: &P) -> Option<&State> {
+        self.peers.get(peer)
+    }
+
+    /// The state for `peer`, starting a new one if there is none
+    ///
+    /// Use this to receive messages into documents which are not an [`Automerge`], such as an
+    /// [`crate::AutoCommit`] with [`crate::AutoCommit::sync()`].
+    pub fn state_mut(&mut self, peer: &P) -> &mut State {
+        self.peers.entry(peer.clone()).or_default()
+    }
+
+    /// Generate the next sync message for `peer`, see
+    /// [`super::SyncDoc::generate_sync_message()`]
+    ///
+    /// A new state is started for peers which have none. `doc` should be the same document every
+    /// time.
+    pub fn generate_sync_message(&mut self, doc: &Automerge, peer: &P) -> Option<Message> {
+        let state = self.peers.entry(peer.clone()).or_default();
+        doc.generate_sync_message_shared(state, &self.options, &mut self.shared)
+    }
+
+    /// Generate the next sync message for each of `peers` which has one
+    pub fn generate_sync_messages<I>(&mut self, doc: &Automerge, peers: I) -> Vec<(P, Message)>
+    where
+        I: IntoIterator<Item = P>,
+    {
+        peers
+            .into_iter()
+            .filter_map(|peer| {
+                let message = self.generate_sync_message(doc, &peer)?;
+                Some((peer, message))
+            })
+            .collect()
+    }
+
+    /// Receive a sync message from `peer`, see [`super::SyncDoc::receive_sync_message()`]
+    pub fn receive_sync_message(
+        &mut self,
+        doc: &mut Automerge,
+        peer: &P,
+        message: Message,
+    ) -> Result<(), AutomergeError> {
+        let state = self.peers.entry(peer.clone()).or_default();
+        super::SyncDoc::receive_sync_message(doc, state, message)
+    }
+}
+
+/// The parts of generating a sync message which only depend on the document
+///
+/// Everything in here is valid for the document at `heads` with `queued` changes waiting for
+/// their dependencies, and is thrown away when the document changes.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SharedWork {
+    heads: Vec<ChangeHash>,
+    queued: usize,
+    blooms: HashMap<Vec<ChangeHash>, Have>,
+    changes_since: HashMap<Vec<ChangeHash>, ChangesSince>,
+    missing_deps: HashMap<Vec<ChangeHash>, Vec<ChangeHash>>,
+    saved: Option<Vec<u8>>,
+}
+
+/// The changes which are not ancestors of a set of heads
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ChangesSince {
+    /// The hashes of the changes in the order they were applied
+    pub(crate) hashes: Vec<ChangeHash>,
+    /// The changes which depend on each change
+    pub(crate) dependents: HashMap<ChangeHash, Vec<ChangeHash>>,
+}
+
+impl SharedWork {
+    /// Forget everything if the document has changed since it was computed
+    pub(crate) fn check_heads(&mut self, doc: &Automerge, heads: &[ChangeHash]) {
+        let queued = doc.queued_changes();
+        if self.heads != heads || self.queued != queued {
+            *self = Self {
+                heads: heads.to_vec(),
+                queued,
+                ..Self::default()
+            };
+        }
+    }
+
+    pub(crate) fn bloom(&mut self, doc: &Automerge, last_sync: Vec<ChangeHash>) -> Have {
+        let key = sorted(&last_sync);
+        if let Some(have) = self.blooms.get(&key) {
+            return Have {
+                last_sync,
+                bloom: have.bloom.clone(),
+            };
+        }
+        let have = doc.make_bloom_filter(last_sync);
+        self.blooms.insert(key, have.clone());
+        have
+    }
+
+    pub(crate) fn changes_since(&mut self, doc: &Automerge, heads: &[ChangeHash]) -> &ChangesSince {
+        self.changes_since
+            .entry(sorted(heads))
+            .or_insert_with_key(|heads| {
+                let mut since = ChangesSince::default();
+                for change in doc.get_changes(heads) {
+                    since.hashes.push(change.hash());
+                    for dep in change.deps() {
+                        since
+                            .dependents
+                            .entry(*dep)
+                            .or_default()
+                            .push(change.hash());
+                    }
+                }
+                since
+            })
+    }
+
+    pub(crate) fn missing_deps(
+        &mut self,
+        doc: &Automerge,
+        their_heads: &[ChangeHash],
+    ) -> Vec<ChangeHash> {
+        self.missing_deps
+            .entry(sorted(their_heads))
+            .or_insert_with_key(|heads| doc.get_missing_deps(heads))
+            .clone()
+    }
+
+    pub(crate) fn saved(&mut self, doc: &Automerge) -> Vec<u8> {
+        self.saved.get_or_insert_with(|| doc.save()).clone()
+    }
+}
+
+fn sorted(hashes: &[ChangeHash]) -> Vec<ChangeHash> {
+    let mut hashes = hashes
+        .iter()
+        .copied()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    hashes.sort();
+    hashes
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use super::State;
+
+/// The first byte of an ephemeral message which [`Presence`] sent, for identification
+const MESSAGE_TYPE_PRESENCE: u8 = 0x50;
+const PRESENCE_ONLINE: u8 = 0x01;
+const PRESENCE_OFFLINE: u8 = 0x00;
+
+/// Sent to the subscribers of a [`Presence`] when the state of a peer changes, see
+/// [`Presence::subscribe()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PresenceEvent<P> {
+    /// We heard from a peer which we had no state for
+    Joined { peer: P, state: Vec<u8> },
+    /// A peer changed its state
+    Updated { peer: P, state: Vec<u8> },
+    /// A peer went offline, timed out or was removed
+    Left { peer: P },
+}
+
+/// What we know about the presence of a peer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresencePeer {
+    /// The state the peer last sent
+    pub state: Vec<u8>,
+    /// When we last heard from the peer
+    pub last_seen: Instant,
+}
+
+/// Transient state of each peer, such as the position of their cursor, their name or whether
+/// they are online
+///
+/// Collaborative applications usually show who else is looking at a document and where they
+/// are. This state changes often and is meaningless once a peer has gone, so it doesn't belong in
+/// the document. Instead each peer sends its state to the peers it syncs with as ephemeral data,
+/// see [`State::send_ephemeral()`]. The state is whatever bytes the application chooses, for
+/// example a [`crate::Cursor`] encoded with [`crate::Cursor::to_bytes()`].
+///
+/// Peers which have not been heard from for the timeout passed to [`Self::new()`] are taken to
+/// have left. To stop that happening to us [`Self::poll()`] returns our state again every half a
+/// timeout, as well as whenever it changes.
+///
+/// ```
+/// # use std::time::{Duration, Instant};
+/// # use automerge::{AutoCommit, sync::{Presence, PresenceEvent, State, SyncDoc}};
+/// let mut alice = (AutoCommit::new(), State::new(), Presence::<&str>::new(Duration::from_secs(30)));
+/// let mut bob = (AutoCommit::new(), State::new(), Presence::<&str>::new(Duration::from_secs(30)));
+/// let events = bob.2.subscribe();
+///
+/// let now = Instant::now();
+/// alice.2.set_local(b"alice, line 3".to_vec());
+/// loop {
+///     if let Some(presence) = alice.2.poll(now) {
+///         alice.1.send_ephemeral(presence);
+///     }
+///     let to_bob = alice.0.sync().generate_sync_message(&mut alice.1);
+///     let to_alice = bob.0.sync().generate_sync_message(&mut bob.1);
+///     if to_bob.is_none() && to_alice.is_none() {
+///         break;
+///     }
+///     if let Some(message) = to_bob {
+///         bob.0.sync().receive_sync_message(&mut bob.1, message).unwrap();
+///         let other = bob.2.receive_from(&"alice", &mut bob.1, now);
+///         assert!(other.is_empty());
+///     }
+///     if let Some(message) = to_alice {
+///         alice.0.sync().receive_sync_message(&mut alice.1, message).unwrap();
+///     }
+/// }
+///
+/// assert_eq!(bob.2.get(&"alice"), Some(&b"alice, line 3"[..]));
+/// assert_eq!(
+///     events.try_recv(),
+///     Ok(PresenceEvent::Joined { peer: "alice", state: b"alice, line 3".to_vec() })
+/// );
+/// ```
+#[derive(Debug)]
+pub struct Presence<P> {
+    timeout: Duration,
+    local: Option<Vec<u8>>,
+    /// Whether the local state has changed since [`Self::poll()`] last returned it
+    changed: bool,
+    last_sent: Option<Instant>,
+    peers: HashMap<P, PresencePeer>,
+    subscribers: Vec<mpsc::Sender<PresenceEvent<P>>>,
+}
+
+impl<P: Eq + Hash + Clone> Presence<P> {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            local: None,
+            changed: false,
+            last_sent: None,
+            peers: HashMap::new(),
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Our state, which is sent to peers by [`Self::poll()`]
+    pub fn local(&self) -> Option<&[u8]> {
+        self.local.as_deref()
+    }
+
+    pub fn set_local(&mut self, state: Vec<u8>) {
+        self.local = Some(state);
+        self.changed = true;
+    }
+
+    /// Tell peers we have gone offline, rather than letting them time us out
+    pub fn clear_local(&mut self) {
+        if self.local.take().is_some() {
+            self.changed = true;
+        }
+    }
+
+    /// The state of `peer`, if it is online
+    pub fn get(&self, peer: &P) -> Option<&[u8]> {
+        self.peers.get(peer).map(|p| p.state.as_slice())
+    }
+
+    /// The peers which are online
+    pub fn peers(&self) -> impl Iterator<Item = (&P, &PresencePeer)> {
+        self.peers.iter()
+    }
+
+    /// Receive a [`PresenceEvent`] on the returned channel whenever the state of a peer changes
+    ///
+    /// Dropping the receiver unsubscribes.
+    pub fn subscribe(&mut self) -> mpsc::Receiver<PresenceEvent<P>> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    /// The ephemeral data to send to every peer, if there is any
+    ///
+    /// This also removes peers we have not heard from for longer than the timeout. Call it
+    /// regularly, at least a few times per timeout, and pass what it returns to
+    /// [`State::send_ephemeral()`] for each peer.
+    pub fn poll(&mut self, now: Instant) -> Option<Vec<u8>> {
+        let timeout = self.timeout;
+        let expired = self
+            .peers
+            .iter()
+            .filter(|(_, p)| now.saturating_duration_since(p.last_seen) >= timeout)
+            .map(|(peer, _)| peer.clone())
+            .collect::<Vec<_>>();
+        for peer in expired {
+            self.remove_peer(&peer);
+        }
+
+        let due = self
+            .last_sent
+            .map(|sent| now.saturating_duration_since(sent) >= self.timeout / 2)
+            .unwrap_or(true);
+        if self.changed || (due && self.local.is_some()) {
+            self.changed = false;
+            self.last_sent = Some(now);
+            Some(self.encode_local())
+        } else {
+            None
+        }
+    }
+
+    /// The ephemeral data to send to a peer which has just connected, so that it doesn't have to
+    /// wait for the next [`Self::poll()`] to see us
+    pub fn encode_local(&self) -> Vec<u8> {
+        match &self.local {
+            Some(state) => {
+                let mut data = Vec::with_capacity(state.len() + 2);
+                data.push(MESSAGE_TYPE_PRESENCE);
+                data.push(PRESENCE_ONLINE);
+                data.extend_from_slice(state);
+                data
+            }
+            None => vec![MESSAGE_TYPE_PRESENCE, PRESENCE_OFFLINE],
+        }
+    }
+
+    /// Receive ephemeral data from `peer`
+    ///
+    /// Returns `false` if `data` was not sent by a [`Presence`], in which case it is ignored.
+    pub fn receive(&mut self, peer: &P, data: &[u8], now: Instant) -> bool {
+        let state = match data {
+            [MESSAGE_TYPE_PRESENCE, PRESENCE_ONLINE, state @ ..] => state,
+            [MESSAGE_TYPE_PRESENCE, PRESENCE_OFFLINE] => {
+                self.remove_peer(peer);
+                return true;
+            }
+            _ => return false,
+        };
+        let event = match self.peers.get_mut(peer) {
+            Some(existing) => {
+                existing.last_seen = now;
+                if existing.state == state {
+                    return true;
+                }
+                existing.state = state.to_vec();
+                PresenceEvent::Updated {
+                    peer: peer.clone(),
+                    state: state.to_vec(),
+                }
+            }
+            None => {
+                self.peers.insert(
+                    peer.clone(),
+                    PresencePeer {
+                        state: state.to_vec(),
+                        last_seen: now,
+                    },
+                );
+                PresenceEvent::Joined {
+                    peer: peer.clone(),
+                    state: state.to_vec(),
+                }
+            }
+        };
+        self.notify(event);
+        true
+    }
+
+    /// Receive the ephemeral data `sync_state` has received from `peer`, returning any which was
+    /// not sent by a [`Presence`]
+    pub fn receive_from(&mut self, peer: &P, sync_state: &mut State, now: Instant) -> Vec<Vec<u8>> {
+        sync_state
+            .take_ephemeral()
+            .into_iter()
+            .filter(|data| !self.receive(peer, data, now))
+            .collect()
+    }
+
+    /// Forget `peer`, for example because the connection to it closed
+    pub fn remove_peer(&mut self, peer: &P) -> Option<PresencePeer> {
+        let removed = self.peers.remove(peer)?;
+        self.notify(PresenceEvent::Left { peer: peer.clone() });
+        Some(removed)
+    }
+
+    fn notify(&mut self, event: PresenceEvent<P>) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
@@ -1,12 +1,25 @@
 use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
 
 #[cfg(doc)]
 use super::SyncDoc;
-use super::{encode_hashes, BloomFilter, Capability};
+use super::{encode_hashes, BloomFilter, Capability, SyncOptions};
 use crate::storage::parse;
 use crate::ChangeHash;
 
-const SYNC_STATE_TYPE: u8 = 0x43; // first byte of an encoded sync state, for identification
+/// The first byte of an encoded sync state, for identification
+const SYNC_STATE_TYPE: u8 = 0x43;
+/// The version of the encoding, which follows the shared heads
+///
+/// Encodings from before the version was recorded end after the shared heads and are version 0.
+/// Versions which only append fields can still be read by older versions, which stop after the
+/// fields they know about, so a newer version is only rejected when [`State::decode()`] is given
+/// one.
+const SYNC_STATE_VERSION: u8 = 1;
+/// The most ephemeral data items [`State`] holds until [`State::take_ephemeral()`] is called
+const MAX_EPHEMERAL_RECEIVED: usize = 256;
+/// The most bytes of ephemeral data [`State`] holds until [`State::take_ephemeral()`] is called
+const MAX_EPHEMERAL_RECEIVED_BYTES: usize = 1 << 20;
 
 #[derive(Debug, thiserror::Error)]
 pub enum DecodeError {
@@ -29,7 +42,18 @@ impl From<parse::leb128::Error> for DecodeError {
 /// This should be persisted using [`Self::encode()`] when you know you will be interacting with the
 /// same peer in multiple sessions. [`Self::encode()`] only encodes state which should be reused
 /// across connections.
-#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+///
+/// A session lasts from the first message a state generates or receives until the state is
+/// dropped or [`Self::reset()`]. If the peer starts a new session whilst this one is going on,
+/// for example because it restarted and lost its in-memory state, the next message it sends is
+/// detected and everything this state assumed about the previous session is discarded, see
+/// [`Self::peer_resets`]. Persist the state only once the document it belongs to has been saved:
+/// should the document lose changes in a crash anyway, heads in the decoded state which the
+/// document does not have are discarded and the document is synced from scratch.
+///
+/// Two states are equal if they describe the same session with the peer, the options and the
+/// ephemeral data waiting to be sent or taken are not compared.
+#[derive(Debug, Clone, Default)]
 pub struct State {
     /// The hashes which we know both peers have
     pub shared_heads: Vec<ChangeHash>,
@@ -60,6 +84,53 @@ pub struct State {
 
     /// The capabilities the other side has said they have
     pub their_capabilities: Option<Vec<Capability>>,
+
+    /// The number of times the other end has started a new session after this one started,
+    /// which happens when it restarts or resets its state for us
+    pub peer_resets: usize,
+
+    /// The options messages for this peer are generated with
+    ///
+    /// Options are not persisted by [`Self::encode()`], as they depend on the connection to the
+    /// peer.
+    pub options: SyncOptions,
+
+    /// Ephemeral data waiting to be sent, see [`Self::send_ephemeral()`]
+    pub ephemeral_to_send: Vec<Vec<u8>>,
+    /// Ephemeral data received from the other end, see [`Self::take_ephemeral()`]
+    pub ephemeral_received: Vec<Vec<u8>>,
+}
+
+impl PartialEq for State {
+    fn eq(&self, other: &Self) -> bool {
+        self.shared_heads == other.shared_heads
+            && self.last_sent_heads == other.last_sent_heads
+            && self.their_heads == other.their_heads
+            && self.their_need == other.their_need
+            && self.their_have == other.their_have
+            && self.sent_hashes == other.sent_hashes
+            && self.in_flight == other.in_flight
+            && self.have_responded == other.have_responded
+            && self.their_capabilities == other.their_capabilities
+            && self.peer_resets == other.peer_resets
+    }
+}
+
+impl Eq for State {}
+
+impl Hash for State {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.shared_heads.hash(state);
+        self.last_sent_heads.hash(state);
+        self.their_heads.hash(state);
+        self.their_need.hash(state);
+        self.their_have.hash(state);
+        self.sent_hashes.hash(state);
+        self.in_flight.hash(state);
+        self.have_responded.hash(state);
+        self.their_capabilities.hash(state);
+        self.peer_resets.hash(state);
+    }
 }
 
 /// A summary of the changes that the sender of the message already has.
@@ -82,9 +153,91 @@ impl State {
     pub fn encode(&self) -> Vec<u8> {
         let mut buf = vec![SYNC_STATE_TYPE];
         encode_hashes(&mut buf, &self.shared_heads);
+        buf.push(SYNC_STATE_VERSION);
         buf
     }
 
+    /// Forget everything about the peer, so that the next exchange with it starts from scratch
+    ///
+    /// Use this when the state may no longer describe the peer, for example when either side has
+    /// been restored from a backup. Syncing from scratch costs a larger exchange of bloom filters
+    /// and changes but is always safe. The options and ephemeral data which has not been sent or
+    /// taken yet are kept.
+    pub fn reset(&mut self) {
+        *self = Self {
+            peer_resets: self.peer_resets,
+            options: std::mem::take(&mut self.options),
+            ephemeral_to_send: std::mem::take(&mut self.ephemeral_to_send),
+            ephemeral_received: std::mem::take(&mut self.ephemeral_received),
+            ..Self::new()
+        };
+    }
+
+    /// Send `data` to the other end with the next sync message
+    ///
+    /// Ephemeral data is for things like cursor positions, which the other end should know about
+    /// whilst connected but which are not part of the document. It is not persisted and is not
+    /// sent again if it is lost. Only peers which support the [`Capability::Ephemeral`]
+    /// capability receive it, so the data is held until we know whether the peer does and dropped
+    /// if it does not, see [`Self::supports_ephemeral()`].
+    pub fn send_ephemeral(&mut self, data: Vec<u8>) {
+        self.ephemeral_to_send.push(data);
+    }
+
+    /// The ephemeral data received from the other end since the last call
+    ///
+    /// At most 256 items or 1MiB of data are held, so that a peer can't make us buffer without
+    /// limit when this is not called. Beyond that the oldest data is dropped, as it is the most
+    /// likely to be out of date.
+    pub fn take_ephemeral(&mut self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut self.ephemeral_received)
+    }
+
+    pub(crate) fn receive_ephemeral(&mut self, data: Vec<Vec<u8>>) {
+        self.ephemeral_received.extend(data);
+        let mut bytes = 0;
+        let keep = self
+            .ephemeral_received
+            .iter()
+            .rev()
+            .take(MAX_EPHEMERAL_RECEIVED)
+            .take_while(|data| {
+                bytes += data.len();
+                bytes <= MAX_EPHEMERAL_RECEIVED_BYTES
+            })
+            .count();
+        let dropped = self.ephemeral_received.len() - keep;
+        self.ephemeral_received.drain(..dropped);
+    }
+
+    /// Whether the other end can receive ephemeral data, or `None` if we have not heard from it
+    /// yet
+    pub fn supports_ephemeral(&self) -> Option<bool> {
+        match &self.their_capabilities {
+            Some(caps) => Some(caps.contains(&Capability::Ephemeral)),
+            // peers which advertise capabilities do so in their first message
+            None if self.their_heads.is_some() => Some(false),
+            None => None,
+        }
+    }
+
+    /// Discard the parts of the state which only hold for the session the peer has abandoned
+    ///
+    /// The peer has forgotten our capabilities along with everything else, so we respond as if
+    /// for the first time and send them again. It doesn't take that to mean that we restarted
+    /// too, as it has not heard our capabilities in its new session.
+    pub(crate) fn restart_session(&mut self) {
+        *self = Self {
+            shared_heads: std::mem::take(&mut self.shared_heads),
+            their_capabilities: self.their_capabilities.take(),
+            peer_resets: self.peer_resets + 1,
+            options: std::mem::take(&mut self.options),
+            ephemeral_to_send: std::mem::take(&mut self.ephemeral_to_send),
+            ephemeral_received: std::mem::take(&mut self.ephemeral_received),
+            ..Self::new()
+        };
+    }
+
     pub fn decode(input: &[u8]) -> Result<Self, DecodeError> {
         let input = parse::Input::new(input);
         match Self::parse(input) {
@@ -104,6 +257,17 @@ impl State {
         }
 
         let (i, shared_heads) = parse::length_prefixed(parse::change_hash)(i)?;
+        let (i, version) = if i.is_empty() {
+            (i, 0)
+        } else {
+            parse::take1(i)?
+        };
+        if version > SYNC_STATE_VERSION {
+            return Err(parse::ParseError::Error(DecodeError::Parse(format!(
+                "unsupported sync state version {}",
+                version
+            ))));
+        }
         Ok((
             i,
             Self {
@@ -116,6 +280,10 @@ impl State {
                 in_flight: false,
                 have_responded: false,
                 their_capabilities: None,
+                peer_resets: 0,
+                options: SyncOptions::default(),
+                ephemeral_to_send: Vec::new(),
+                ephemeral_received: Vec::new(),
             },
         ))
     }
@@ -0,0 +1,153 @@
+//! Reading tables of rows with typed columns
+//!
+//! A table is an object of type [`ObjType::Table`] whose values are rows. Each row is a map
+//! from column names to values, stored under a row ID which is generated when the row is added
+//! with [`crate::transaction::Transactable::add_row()`]. Row IDs are random, so rows added
+//! concurrently on different peers never conflict, and a row can be referred to by its ID
+//! however the table is edited.
+//!
+//! # Example
+//!
+//! ```
+//! # use automerge::{AutoCommit, ObjType, ROOT};
+//! # use automerge::table::Table;
+//! # use automerge::transaction::Transactable;
+//! let mut doc = AutoCommit::new();
+//! let people = doc.put_object(ROOT, "people", ObjType::Table).unwrap();
+//! let (alice, row) = doc.add_row(&people).unwrap();
+//! doc.put(&row, "name", "Alice").unwrap();
+//! doc.put(&row, "age", 36).unwrap();
+//!
+//! let table = Table::new(&doc, &people).unwrap();
+//! assert_eq!(table.len(), 1);
+//! let ages = table.column::<i64>("age").collect::<Vec<_>>();
+//! assert_eq!(ages, vec![(alice, Some(36))]);
+//! ```
+use crate::exid::ExId;
+use crate::iter::Keys;
+use crate::{AutomergeError, ObjType, ReadDoc, Value};
+
+/// A read only view of a table in a document
+#[derive(Debug)]
+pub struct Table<'a, R> {
+    doc: &'a R,
+    obj: ExId,
+}
+
+impl<'a, R: ReadDoc> Table<'a, R> {
+    /// A view of the table `obj` in `doc`
+    ///
+    /// Returns an error if `obj` does not exist or is not a table.
+    pub fn new<O: AsRef<ExId>>(doc: &'a R, obj: O) -> Result<Self, AutomergeError> {
+        let obj = obj.as_ref();
+        match doc.object_type(obj)? {
+            ObjType::Table => Ok(Self {
+                doc,
+                obj: obj.clone(),
+            }),
+            other => Err(AutomergeError::InvalidOp(other)),
+        }
+    }
+
+    /// The ID of the table object
+    pub fn id(&self) -> &ExId {
+        &self.obj
+    }
+
+    /// The number of rows in the table
+    pub fn len(&self) -> usize {
+        self.doc.length(&self.obj)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The IDs of every row, in order
+    pub fn row_ids(&self) -> Keys<'a> {
+        self.doc.keys(&self.obj)
+    }
+
+    /// The object ID of the row `row_id`, if there is a row with that ID
+    pub fn row(&self, row_id: &str) -> Option<ExId> {
+        match self.doc.get(&self.obj, row_id) {
+            Ok(Some((Value::Object(ObjType::Map), id))) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// The value in `column` of the row `row_id`
+    ///
+    /// Returns `None` if there is no such row, the row has no value in the column or the value
+    /// is not of type `T`.
+    pub fn get<T: ColumnType>(&self, row_id: &str, column: &str) -> Option<T> {
+        let row = self.row(row_id)?;
+        let (value, _) = self.doc.get(&row, column).ok()??;
+        T::from_value(&value)
+    }
+
+    /// The value in `column` of every row, as `T`, along with the ID of the row
+    ///
+    /// Rows which have no value in the column, or a value which is not of type `T`, are
+    /// included with `None`.
+    pub fn column<T: ColumnType + 'a>(
+        &self,
+        column: &'a str,
+    ) -> impl Iterator<Item = (String, Option<T>)> + 'a {
+        let doc = self.doc;
+        let obj = self.obj.clone();
+        self.row_ids().map(move |row_id| {
+            let value = match doc.get(&obj, row_id.as_str()) {
+                Ok(Some((Value::Object(ObjType::Map), row))) => doc
+                    .get(&row, column)
+                    .ok()
+                    .flatten()
+                    .and_then(|(value, _)| T::from_value(&value)),
+                _ => None,
+            };
+            (row_id, value)
+        })
+    }
+}
+
+/// A type which the values of a column can be read as, see [`Table::column()`]
+pub trait ColumnType: Sized {
+    /// The value as this type, or `None` if it is a value of a different type
+    fn from_value(value: &Value<'_>) -> Option<Self>;
+}
+
+impl ColumnType for String {
+    fn from_value(value: &Value<'_>) -> Option<Self> {
+        value.to_str().map(String::from)
+    }
+}
+
+impl ColumnType for i64 {
+    fn from_value(value: &Value<'_>) -> Option<Self> {
+        value.to_i64()
+    }
+}
+
+impl ColumnType for u64 {
+    fn from_value(value: &Value<'_>) -> Option<Self> {
+        value.to_u64()
+    }
+}
+
+impl ColumnType for f64 {
+    fn from_value(value: &Value<'_>) -> Option<Self> {
+        value.to_f64()
+    }
+}
+
+impl ColumnType for bool {
+    fn from_value(value: &Value<'_>) -> Option<Self> {
+        value.to_bool()
+    }
+}
+
+impl ColumnType for Value<'static> {
+    fn from_value(value: &Value<'_>) -> Option<Self> {
+        Some(value.to_owned())
+    }
+}
@@ -0,0 +1,114 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::text_value::TextValue;
+
+/// The unit in which indexes into and lengths of text objects are measured
+///
+/// Text objects are indexed in a unit which is fixed when automerge is built: unicode code points
+/// by default, UTF-16 code units when targeting wasm, or UTF-8 bytes with the `utf8-indexing`
+/// feature. This is the [`TextUnit::Native`] unit, which is what [`crate::ReadDoc::length()`],
+/// [`crate::transaction::Transactable::splice_text()`] and patches use. Methods such as
+/// [`crate::ReadDoc::length_in()`] and [`crate::transaction::Transactable::splice_text_in()`]
+/// accept indexes in other units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextUnit {
+    /// The unit this build of automerge indexes text in
+    #[default]
+    Native,
+    /// Extended grapheme clusters
+    ///
+    /// Text which is spliced in using this unit is inserted one grapheme cluster per sequence
+    /// element, so concurrent edits cannot split an emoji or a combining sequence.
+    Grapheme,
+    /// UTF-16 code units, as used by JavaScript strings
+    ///
+    /// This is the unit automerge-js indexes text in, so it can be used to interoperate with
+    /// JavaScript frontends. An index which falls between the two halves of a surrogate pair is
+    /// invalid.
+    Utf16,
+}
+
+impl TextUnit {
+    /// The length of `text` measured in this unit
+    pub fn len(self, text: &str) -> usize {
+        match self {
+            Self::Native => TextValue::width(text),
+            Self::Grapheme => text.graphemes(true).count(),
+            Self::Utf16 => text.encode_utf16().count(),
+        }
+    }
+
+    /// Split `text` into the pieces which are stored as one sequence element when splicing in
+    /// this unit
+    pub(crate) fn segments(self, text: &str) -> Box<dyn Iterator<Item = &str> + '_> {
+        match self {
+            Self::Native | Self::Utf16 => Box::new(
+                text.char_indices()
+                    .map(move |(i, c)| &text[i..i + c.len_utf8()]),
+            ),
+            Self::Grapheme => Box::new(text.graphemes(true)),
+        }
+    }
+
+    /// Convert `index`, measured in this unit, to a native index into `text`
+    ///
+    /// Returns `None` if `index` is past the end of `text` or falls inside a segment.
+    pub(crate) fn index_to_native(self, text: &str, index: usize) -> Option<usize> {
+        if self == Self::Native {
+            return (index <= TextValue::width(text)).then_some(index);
+        }
+        let mut native = 0;
+        let mut units = 0;
+        for segment in self.segments(text) {
+            if units >= index {
+                break;
+            }
+            units += self.len(segment);
+            native += TextValue::width(segment);
+        }
+        (units == index).then_some(native)
+    }
+
+    /// Convert a native index into `text` to an index measured in this unit
+    ///
+    /// A native index which falls inside a unit is rounded down to the start of that unit.
+    pub(crate) fn index_from_native(self, text: &str, index: usize) -> usize {
+        self.convert_index_from_native(text, index, false)
+    }
+
+    /// Like [`Self::index_from_native()`] but rounding up to the end of the unit
+    pub(crate) fn index_from_native_ceil(self, text: &str, index: usize) -> usize {
+        self.convert_index_from_native(text, index, true)
+    }
+
+    fn convert_index_from_native(self, text: &str, index: usize, round_up: bool) -> usize {
+        if self == Self::Native {
+            return index;
+        }
+        let mut native = 0;
+        let mut converted = 0;
+        for segment in self.segments(text) {
+            if native >= index {
+                break;
+            }
+            native += TextValue::width(segment);
+            if native > index && !round_up {
+                break;
+            }
+            converted += self.len(segment);
+        }
+        converted
+    }
+
+    /// The byte offset in `text` of the native index `index`
+    pub(crate) fn byte_offset(text: &str, index: usize) -> usize {
+        let mut native = 0;
+        for (offset, c) in text.char_indices() {
+            if native >= index {
+                return offset;
+            }
+            native += TextValue::width(&text[offset..offset + c.len_utf8()]);
+        }
+        text.len()
+    }
+}
@@ -1,14 +1,18 @@
 mod commit;
 mod inner;
 mod manual_transaction;
+mod pending;
 mod result;
+mod savepoint;
 mod transactable;
 
 pub use self::commit::CommitOptions;
 pub use self::transactable::{BlockOrText, Transactable};
 pub(crate) use inner::{TransactionArgs, TransactionInner};
 pub use manual_transaction::Transaction;
+pub use pending::PendingChange;
 pub use result::Failure;
 pub use result::Success;
+pub use savepoint::Savepoint;
 
 pub type Result<O, E> = std::result::Result<Success<O>, Failure<E>>;
@@ -5,6 +5,8 @@ pub struct CommitOptions {
     pub message: Option<String>,
     /// The unix timestamp (in seconds) of the commit (purely advisory, not used in conflict resolution)
     pub time: Option<i64>,
+    /// Application defined bytes stored with the change, see [`crate::Change::app_extra_bytes()`]
+    pub extra_bytes: Option<Vec<u8>>,
 }
 
 impl CommitOptions {
@@ -31,4 +33,24 @@ impl CommitOptions {
         self.time = Some(time);
         self
     }
+
+    /// Attach application defined bytes to the commit, such as a review ID or device details.
+    ///
+    /// These are stored with the change and synced with it, and can be read with
+    /// [`crate::Change::app_extra_bytes()`].
+    ///
+    /// Automerge also uses the extra bytes of a change to store its own metadata, such as
+    /// [`crate::Change::rotated_from()`]. In a change which has both, or whose application bytes
+    /// start with the prefix `automerge:extra:` which automerge reserves for this, the extra bytes
+    /// are encoded as a list of fields. Otherwise they are stored as they are.
+    pub fn with_extra_bytes(mut self, extra_bytes: Vec<u8>) -> Self {
+        self.extra_bytes = Some(extra_bytes);
+        self
+    }
+
+    /// Attach application defined bytes to the commit.
+    pub fn set_extra_bytes(&mut self, extra_bytes: Vec<u8>) -> &mut Self {
+        self.extra_bytes = Some(extra_bytes);
+        self
+    }
 }
@@ -2,16 +2,21 @@ use std::collections::HashSet;
 use std::num::NonZeroU64;
 use std::sync::Arc;
 
+use crate::automerge::TransactionContext;
 use crate::exid::ExId;
+use crate::extra_bytes::{ExtraBytes, Field};
+use crate::instrument::{record, Timer};
 use crate::iter::{ListRangeItem, MapRangeItem};
 use crate::marks::{ExpandMark, Mark, MarkSet};
 use crate::op_set::{ChangeOpIter, OpIdx, OpIdxRange};
 use crate::patches::{PatchLog, TextRepresentation};
-use crate::query::{self, OpIdSearch};
+use crate::query;
 use crate::storage::Change as StoredChange;
 use crate::types::{Clock, Key, ListEncoding, ObjMeta, OpId};
 use crate::{op_tree::OpSetData, types::OpBuilder, Automerge, Change, ChangeHash, Prop};
-use crate::{AutomergeError, ObjType, OpType, ReadDoc, ScalarValue};
+use crate::{AutomergeError, ObjType, OpType, ReadDoc, ScalarValue, TextUnit};
+
+use super::{CommitOptions, PendingChange, Savepoint};
 
 #[derive(Debug, Clone)]
 pub(crate) struct TransactionInner {
@@ -23,6 +28,9 @@ pub(crate) struct TransactionInner {
     deps: Vec<ChangeHash>,
     scope: Option<Clock>,
     idx_range: OpIdxRange,
+    context: Option<TransactionContext>,
+    extra_bytes: ExtraBytes,
+    savepoints: Vec<Savepoint>,
 }
 
 /// Arguments required to create a new transaction
@@ -62,6 +70,9 @@ impl TransactionInner {
             idx_range,
             deps,
             scope,
+            context: None,
+            extra_bytes: ExtraBytes::default(),
+            savepoints: Vec::new(),
         }
     }
 
@@ -69,10 +80,22 @@ impl TransactionInner {
     pub(crate) fn empty(
         doc: &mut Automerge,
         args: TransactionArgs,
-        message: Option<String>,
-        time: Option<i64>,
+        options: CommitOptions,
     ) -> ChangeHash {
-        Self::new(args).commit_impl(doc, message, time)
+        let mut tx = Self::new(args);
+        if let Some(extra_bytes) = options.extra_bytes {
+            tx.set_extra_bytes(extra_bytes);
+        }
+        tx.commit_impl(doc, options.message, options.time)
+    }
+
+    pub(crate) fn set_extra_bytes(&mut self, extra_bytes: Vec<u8>) {
+        self.set_extra_field(Field::Application, extra_bytes);
+    }
+
+    /// Store `value` in `field` of the extra bytes of the change
+    pub(crate) fn set_extra_field(&mut self, field: Field, value: Vec<u8>) {
+        self.extra_bytes.set(field, value);
     }
 
     pub(crate) fn pending_ops(&self) -> usize {
@@ -90,18 +113,58 @@ impl TransactionInner {
         message: Option<String>,
         time: Option<i64>,
     ) -> Option<ChangeHash> {
+        match self.try_commit(doc, message, time) {
+            Ok(hash) => hash,
+            Err(e) => {
+                tracing::warn!(error=%e, "rolled back transaction");
+                None
+            }
+        }
+    }
+
+    /// Like [`Self::commit()`] but if the transaction leaves the document in a state its schema
+    /// does not allow roll the transaction back and return the violation
+    pub(crate) fn try_commit(
+        self,
+        doc: &mut Automerge,
+        message: Option<String>,
+        time: Option<i64>,
+    ) -> Result<Option<ChangeHash>, AutomergeError> {
         if self.pending_ops() == 0 {
-            return None;
+            return Ok(None);
         }
-        Some(self.commit_impl(doc, message, time))
+        let objs = self
+            .operations(doc.osd())
+            .map(|op| *op.obj())
+            .collect::<Vec<_>>();
+        if let Err(violation) = doc.check_schema(objs) {
+            self.rollback(doc);
+            return Err(violation.into());
+        }
+        Ok(Some(self.commit_impl(doc, message, time)))
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                ops = self.pending_ops(),
+                seq = self.seq,
+                hash = tracing::field::Empty,
+                bytes = tracing::field::Empty,
+                duration_us = tracing::field::Empty,
+            )
+        )
+    )]
     pub(crate) fn commit_impl(
         mut self,
         doc: &mut Automerge,
         message: Option<String>,
         time: Option<i64>,
     ) -> ChangeHash {
+        let _timer = Timer::start();
         if message.is_some() {
             self.message = message;
         }
@@ -113,14 +176,20 @@ impl TransactionInner {
         let num_ops = self.pending_ops();
         let change = self.export(doc.osd());
         let hash = change.hash();
+        record!(
+            hash = tracing::field::display(hash),
+            bytes = change.raw_bytes().len(),
+        );
         #[cfg(not(debug_assertions))]
         tracing::trace!(commit=?hash, deps=?change.deps(), "committing transaction");
         #[cfg(debug_assertions)]
         {
-            let ops = change.iter_ops().collect::<Vec<_>>();
+            let ops = change.iter_stored_ops().collect::<Vec<_>>();
             tracing::trace!(commit=?hash, ?ops, deps=?change.deps(), "committing transaction");
         }
         doc.update_history(change, num_ops);
+        #[cfg(feature = "audit-log")]
+        doc.audit(hash, true);
         //debug_assert_eq!(doc.get_heads(), vec![hash]);
         hash
     }
@@ -142,6 +211,7 @@ impl TransactionInner {
             .with_message(self.message.clone())
             .with_dependencies(deps)
             .with_timestamp(self.time)
+            .with_extra_bytes(self.extra_bytes.encode())
             .build(self.operations(osd).map(op_as_actor_id))
         {
             Ok(s) => s,
@@ -164,42 +234,42 @@ impl TransactionInner {
     /// operations.
     pub(crate) fn rollback(self, doc: &mut Automerge) -> usize {
         let num = self.pending_ops();
-        // remove in reverse order so sets are removed before makes etc...
-        let encoding = ListEncoding::List; // encoding doesnt matter here - we dont care what the index is
-        let ops: Vec<_> = self
-            .operations(doc.osd())
-            .rev()
-            .map(|op| {
-                (
-                    op.idx(),
-                    *op.obj(),
-                    *op.id(),
-                    op.pred().map(|op| *op.id()).collect::<Vec<_>>(),
-                )
-            })
-            .collect();
-        for (idx, obj, opid, pred) in ops.into_iter() {
-            for pred_id in &pred {
-                if let Some(p) = doc
-                    .ops()
-                    .search(&obj, OpIdSearch::opid(*pred_id, encoding, None))
-                    .found()
-                {
-                    doc.ops_mut().remove_succ(&obj, p, idx);
-                }
-            }
-            if let Some(pos) = doc
-                .ops()
-                .search(&obj, OpIdSearch::opid(opid, encoding, None))
-                .found()
-            {
-                doc.ops_mut().remove(&obj, pos);
-            }
-        }
-
+        doc.remove_ops(self.idx_range);
         num
     }
 
+    /// Mark the current point in the transaction so it can be rolled back to with
+    /// [`Self::rollback_to()`]
+    pub(crate) fn savepoint(&mut self, patch_log: &PatchLog) -> Savepoint {
+        let savepoint = Savepoint {
+            range: self.idx_range,
+            patches: patch_log.checkpoint(),
+            depth: self.savepoints.len(),
+        };
+        self.savepoints.push(savepoint);
+        savepoint
+    }
+
+    /// Undo the operations added since `savepoint` was created, returning the number of
+    /// cancelled operations.
+    pub(crate) fn rollback_to(
+        &mut self,
+        doc: &mut Automerge,
+        patch_log: &mut PatchLog,
+        savepoint: Savepoint,
+    ) -> Result<usize, AutomergeError> {
+        if self.savepoints.get(savepoint.depth) != Some(&savepoint) {
+            return Err(AutomergeError::InvalidSavepoint);
+        }
+        self.savepoints.truncate(savepoint.depth + 1);
+        let range = self.idx_range.since(savepoint.range);
+        doc.remove_ops(range);
+        doc.ops_mut().osd.truncate(range);
+        self.idx_range = savepoint.range;
+        patch_log.rollback_to(savepoint.patches, self.next_id());
+        Ok(range.len())
+    }
+
     /// Set the value of property `P` to value `V` in object `obj`.
     ///
     /// # Returns
@@ -221,11 +291,11 @@ impl TransactionInner {
         prop: P,
         value: V,
     ) -> Result<(), AutomergeError> {
-        let obj = doc.exid_to_obj(ex_obj)?;
+        let obj = self.writable_obj(doc, ex_obj)?;
         let value = value.into();
         let prop = prop.into();
         match (&prop, obj.typ) {
-            (Prop::Map(_), ObjType::Map) => Ok(()),
+            (Prop::Map(_), ObjType::Map | ObjType::Table) => Ok(()),
             (Prop::Seq(_), ObjType::List) => Ok(()),
             (Prop::Seq(_), ObjType::Text) => Ok(()),
             _ => Err(AutomergeError::InvalidOp(obj.typ)),
@@ -255,10 +325,10 @@ impl TransactionInner {
         prop: P,
         value: ObjType,
     ) -> Result<ExId, AutomergeError> {
-        let obj = doc.exid_to_obj(ex_obj)?;
+        let obj = self.writable_obj(doc, ex_obj)?;
         let prop = prop.into();
         match (&prop, obj.typ) {
-            (Prop::Map(_), ObjType::Map) => Ok(()),
+            (Prop::Map(_), ObjType::Map | ObjType::Table) => Ok(()),
             (Prop::Seq(_), ObjType::List) => Ok(()),
             _ => Err(AutomergeError::InvalidOp(obj.typ)),
         }?;
@@ -300,12 +370,18 @@ impl TransactionInner {
         obj: &ObjMeta,
         succ_pos: &[usize],
     ) {
+        let conflicts = doc.conflicts_before(&obj.id, pos, idx);
+
         doc.ops_mut().add_succ(&obj.id, succ_pos, idx);
 
         if !is_delete {
             doc.ops_mut().insert(pos, &obj.id, idx);
         }
 
+        if let Some(conflicts) = conflicts {
+            doc.record_conflicts(&obj.id, pos, conflicts);
+        }
+
         self.finalize_op(doc, patch_log, obj, prop, idx, None);
     }
 
@@ -317,7 +393,7 @@ impl TransactionInner {
         index: usize,
         value: V,
     ) -> Result<(), AutomergeError> {
-        let obj = doc.exid_to_obj(ex_obj)?;
+        let obj = self.writable_obj(doc, ex_obj)?;
         if !matches!(obj.typ, ObjType::List | ObjType::Text) {
             return Err(AutomergeError::InvalidOp(obj.typ));
         }
@@ -335,7 +411,7 @@ impl TransactionInner {
         index: usize,
         value: ObjType,
     ) -> Result<ExId, AutomergeError> {
-        let obj = doc.exid_to_obj(ex_obj)?;
+        let obj = self.writable_obj(doc, ex_obj)?;
         if !matches!(obj.typ, ObjType::List | ObjType::Text) {
             return Err(AutomergeError::InvalidOp(obj.typ));
         }
@@ -343,6 +419,91 @@ impl TransactionInner {
         Ok(idx.as_op(doc.osd()).exid())
     }
 
+    pub(crate) fn push<V: Into<ScalarValue>>(
+        &mut self,
+        doc: &mut Automerge,
+        patch_log: &mut PatchLog,
+        ex_obj: &ExId,
+        value: V,
+    ) -> Result<(), AutomergeError> {
+        let obj = self.writable_obj(doc, ex_obj)?;
+        if !matches!(obj.typ, ObjType::List | ObjType::Text) {
+            return Err(AutomergeError::InvalidOp(obj.typ));
+        }
+        let value = value.into();
+        tracing::trace!(obj=?obj, value=?value, "pushing value");
+        self.do_append(doc, patch_log, &obj, value.into())?;
+        Ok(())
+    }
+
+    pub(crate) fn push_object(
+        &mut self,
+        doc: &mut Automerge,
+        patch_log: &mut PatchLog,
+        ex_obj: &ExId,
+        value: ObjType,
+    ) -> Result<ExId, AutomergeError> {
+        let obj = self.writable_obj(doc, ex_obj)?;
+        if !matches!(obj.typ, ObjType::List | ObjType::Text) {
+            return Err(AutomergeError::InvalidOp(obj.typ));
+        }
+        let idx = self.do_append(doc, patch_log, &obj, value.into())?;
+        Ok(idx.as_op(doc.osd()).exid())
+    }
+
+    pub(crate) fn extend<I: IntoIterator<Item = ScalarValue>>(
+        &mut self,
+        doc: &mut Automerge,
+        patch_log: &mut PatchLog,
+        ex_obj: &ExId,
+        values: I,
+    ) -> Result<(), AutomergeError> {
+        let obj = self.writable_obj(doc, ex_obj)?;
+        if !matches!(obj.typ, ObjType::List | ObjType::Text) {
+            return Err(AutomergeError::InvalidOp(obj.typ));
+        }
+        for value in values {
+            self.do_append(doc, patch_log, &obj, value.into())?;
+        }
+        Ok(())
+    }
+
+    /// Insert a new element at the end of a sequence
+    ///
+    /// For lists this skips the search for the insert position. Text may end with mark
+    /// anchors, which decide whether the new element is marked, and an isolated transaction
+    /// cannot see every element, so those use the regular insert query.
+    fn do_append(
+        &mut self,
+        doc: &mut Automerge,
+        patch_log: &mut PatchLog,
+        obj: &ObjMeta,
+        action: OpType,
+    ) -> Result<OpIdx, AutomergeError> {
+        let encoding = patch_log.text_rep().encoding(obj.typ);
+        let index = doc.ops().length(&obj.id, encoding, self.scope.clone());
+        if obj.typ != ObjType::List || self.scope.is_some() {
+            return self.do_insert(doc, patch_log, obj, index, action);
+        }
+
+        let (pos, key) = doc.ops().end_of_seq(&obj.id);
+        let op = OpBuilder {
+            id: self.next_id(),
+            action,
+            key,
+            insert: true,
+        };
+
+        let idx =
+            doc.ops_mut()
+                .load_with_range(obj.id, op, &mut self.idx_range, self.scope.as_ref());
+        doc.ops_mut().insert(pos, &obj.id, idx);
+
+        self.finalize_op(doc, patch_log, obj, Prop::Seq(index), idx, None);
+
+        Ok(idx)
+    }
+
     fn do_insert(
         &mut self,
         doc: &mut Automerge,
@@ -372,9 +533,12 @@ impl TransactionInner {
             insert: true,
         };
 
-        let idx = doc
-            .ops_mut()
-            .load_with_range(obj.id, op.clone(), &mut self.idx_range);
+        let idx = doc.ops_mut().load_with_range(
+            obj.id,
+            op.clone(),
+            &mut self.idx_range,
+            self.scope.as_ref(),
+        );
         doc.ops_mut().insert(pos, &obj.id, idx);
 
         self.finalize_op(doc, patch_log, obj, Prop::Seq(index), idx, marks);
@@ -423,6 +587,8 @@ impl TransactionInner {
             return Ok(None);
         }
 
+        doc.check_not_frozen(query.ops.iter().map(|op| op.id()))?;
+
         // increment operations are only valid against counter values.
         // if there are multiple values (from conflicts) then we just need one of them to be a counter.
         if matches!(action, OpType::Increment(_)) && query.ops.iter().all(|op| !op.is_counter()) {
@@ -439,9 +605,9 @@ impl TransactionInner {
         let ops_pos = query.ops_pos;
 
         let is_delete = op.is_delete();
-        let idx = doc
-            .ops_mut()
-            .load_with_range(obj.id, op, &mut self.idx_range);
+        let idx =
+            doc.ops_mut()
+                .load_with_range(obj.id, op, &mut self.idx_range, self.scope.as_ref());
 
         self.insert_local_op(doc, patch_log, prop, idx, is_delete, pos, obj, &ops_pos);
 
@@ -470,6 +636,8 @@ impl TransactionInner {
             return Ok(None);
         }
 
+        doc.check_not_frozen(query.ops.iter().map(|op| op.id()))?;
+
         // increment operations are only valid against counter values.
         // if there are multiple values (from conflicts) then we just need one of them to be a counter.
         if matches!(action, OpType::Increment(_)) && query.ops.iter().all(|op| !op.is_counter()) {
@@ -485,9 +653,9 @@ impl TransactionInner {
         let pos = query.pos();
         let ops_pos = query.ops_pos;
         let is_delete = op.is_delete();
-        let idx = doc
-            .ops_mut()
-            .load_with_range(obj.id, op, &mut self.idx_range);
+        let idx =
+            doc.ops_mut()
+                .load_with_range(obj.id, op, &mut self.idx_range, self.scope.as_ref());
 
         self.insert_local_op(
             doc,
@@ -511,7 +679,7 @@ impl TransactionInner {
         prop: P,
         value: i64,
     ) -> Result<(), AutomergeError> {
-        let obj = doc.exid_to_obj(obj)?;
+        let obj = self.writable_obj(doc, obj)?;
         self.local_op(doc, patch_log, &obj, prop.into(), OpType::Increment(value))?;
         Ok(())
     }
@@ -523,7 +691,7 @@ impl TransactionInner {
         ex_obj: &ExId,
         prop: P,
     ) -> Result<(), AutomergeError> {
-        let obj = doc.exid_to_obj(ex_obj)?;
+        let obj = self.writable_obj(doc, ex_obj)?;
         let prop = prop.into();
         if obj.typ == ObjType::Text {
             let index = prop.as_index().ok_or(AutomergeError::InvalidOp(obj.typ))?;
@@ -555,7 +723,7 @@ impl TransactionInner {
         del: isize,
         vals: impl IntoIterator<Item = ScalarValue>,
     ) -> Result<(), AutomergeError> {
-        let obj = doc.exid_to_obj(ex_obj)?;
+        let obj = self.writable_obj(doc, ex_obj)?;
         if !matches!(obj.typ, ObjType::List | ObjType::Text) {
             return Err(AutomergeError::InvalidOp(obj.typ));
         }
@@ -584,7 +752,7 @@ impl TransactionInner {
         del: isize,
         text: &str,
     ) -> Result<(), AutomergeError> {
-        let obj = doc.exid_to_obj(ex_obj)?;
+        let obj = self.writable_obj(doc, ex_obj)?;
         if obj.typ != ObjType::Text {
             return Err(AutomergeError::InvalidOp(obj.typ));
         }
@@ -602,6 +770,56 @@ impl TransactionInner {
         )
     }
 
+    /// Splice string into a text object, measuring `index` and `del` in `unit`
+    ///
+    /// The inserted text is split into one element per `unit`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn splice_text_in(
+        &mut self,
+        doc: &mut Automerge,
+        patch_log: &mut PatchLog,
+        ex_obj: &ExId,
+        index: usize,
+        del: isize,
+        text: &str,
+        unit: TextUnit,
+    ) -> Result<(), AutomergeError> {
+        if unit == TextUnit::Native {
+            return self.splice_text(doc, patch_log, ex_obj, index, del, text);
+        }
+        let obj = self.writable_obj(doc, ex_obj)?;
+        if obj.typ != ObjType::Text {
+            return Err(AutomergeError::InvalidOp(obj.typ));
+        }
+        let (start, end) = if del < 0 {
+            let start = index
+                .checked_add_signed(del)
+                .ok_or(AutomergeError::InvalidIndex(index))?;
+            (start, index)
+        } else {
+            (index, index.saturating_add(del as usize))
+        };
+        let current = doc.ops().text(&obj.id, self.scope.clone());
+        let native_start = unit
+            .index_to_native(&current, start)
+            .ok_or(AutomergeError::InvalidIndex(start))?;
+        let native_end = unit
+            .index_to_native(&current, end)
+            .unwrap_or_else(|| TextUnit::Native.len(&current));
+        let values = unit.segments(text).map(ScalarValue::from).collect();
+        self.inner_splice(
+            doc,
+            patch_log,
+            SpliceArgs {
+                obj,
+                index: native_start,
+                del: (native_end - native_start) as isize,
+                values,
+                splice_type: SpliceType::Text(text),
+            },
+        )
+    }
+
     fn inner_splice(
         &mut self,
         doc: &mut Automerge,
@@ -648,12 +866,13 @@ impl TransactionInner {
                 break;
             };
 
+            doc.check_not_frozen(query.ops.iter().map(|op| op.id()))?;
             let query_key = query.key()?;
             let ops_pos = query.ops_pos;
             let op = self.next_delete(query_key);
-            let idx = doc
-                .ops_mut()
-                .load_with_range(obj.id, op, &mut self.idx_range);
+            let idx =
+                doc.ops_mut()
+                    .load_with_range(obj.id, op, &mut self.idx_range, self.scope.as_ref());
 
             doc.ops_mut().add_succ(&obj.id, &ops_pos, idx);
 
@@ -682,9 +901,12 @@ impl TransactionInner {
 
                 key = op.id.into();
 
-                let idx = doc
-                    .ops_mut()
-                    .load_with_range(obj.id, op, &mut self.idx_range);
+                let idx = doc.ops_mut().load_with_range(
+                    obj.id,
+                    op,
+                    &mut self.idx_range,
+                    self.scope.as_ref(),
+                );
                 doc.ops_mut().insert(pos, &obj.id, idx);
 
                 width = idx.as_op(doc.osd()).width(encoding);
@@ -736,7 +958,7 @@ impl TransactionInner {
             // "b" and end at the anchor point after "a". This is nonsensical so we ignore it.
             return Ok(());
         }
-        let obj = doc.exid_to_obj(ex_obj)?;
+        let obj = self.writable_obj(doc, ex_obj)?;
         let action = OpType::MarkBegin(expand.before(), mark.data.clone().into_owned());
 
         self.do_insert(doc, patch_log, &obj, mark.start, action)?;
@@ -775,7 +997,7 @@ impl TransactionInner {
         ex_obj: &ExId,
         index: usize,
     ) -> Result<ExId, AutomergeError> {
-        let obj = doc.exid_to_obj(ex_obj)?;
+        let obj = self.writable_obj(doc, ex_obj)?;
         if obj.typ != ObjType::Text {
             return Err(AutomergeError::InvalidOp(obj.typ));
         }
@@ -801,9 +1023,9 @@ impl TransactionInner {
             insert: true,
         };
 
-        let op_idx = doc
-            .ops_mut()
-            .load_with_range(obj.id, op, &mut self.idx_range);
+        let op_idx =
+            doc.ops_mut()
+                .load_with_range(obj.id, op, &mut self.idx_range, self.scope.as_ref());
         doc.ops_mut().insert(pos, &obj.id, op_idx);
         let op = op_idx.as_op(doc.osd());
 
@@ -825,7 +1047,7 @@ impl TransactionInner {
         text: &ExId,
         index: usize,
     ) -> Result<(), AutomergeError> {
-        let text_obj = doc.exid_to_obj(text)?;
+        let text_obj = self.writable_obj(doc, text)?;
 
         if text_obj.typ != ObjType::Text {
             return Err(AutomergeError::InvalidOp(text_obj.typ));
@@ -881,9 +1103,12 @@ impl TransactionInner {
             }
         }
 
-        let op_idx = doc
-            .ops_mut()
-            .load_with_range(text_obj.id, op, &mut self.idx_range);
+        let op_idx = doc.ops_mut().load_with_range(
+            text_obj.id,
+            op,
+            &mut self.idx_range,
+            self.scope.as_ref(),
+        );
 
         doc.ops_mut().add_succ(&text_obj.id, &succ_pos, op_idx);
 
@@ -941,6 +1166,10 @@ impl TransactionInner {
                     Prop::Map(key) => patch_log.delete_map(obj.id, &key),
                 }
             } else if let Some(value) = op.get_increment_value() {
+                let value = op
+                    .pred()
+                    .find(|p| p.is_counter())
+                    .map_or(value, |counter| counter.increment_delta(op));
                 patch_log.increment(obj.id, &prop, value, *op.id());
             } else {
                 patch_log.put(obj.id, &prop, op.value().into(), *op.id(), false, false);
@@ -955,7 +1184,7 @@ impl TransactionInner {
         obj: &ExId,
         new_value: &crate::hydrate::Value,
     ) -> Result<(), crate::error::UpdateObjectError> {
-        let obj_meta = doc.exid_to_obj(obj)?;
+        let obj_meta = self.writable_obj(doc, obj)?;
         match (obj_meta.typ, new_value) {
             (ObjType::Map, crate::hydrate::Value::Map(map)) => {
                 Ok(self.update_map(doc, patch_log, obj, map)?)
@@ -984,7 +1213,7 @@ impl TransactionInner {
         new_value: &crate::hydrate::Map,
     ) -> Result<(), AutomergeError> {
         let mut delenda = HashSet::new();
-        let obj = doc.exid_to_obj(map)?;
+        let obj = self.writable_obj(doc, map)?;
         let current_vals = doc
             .ops()
             .map_range(&obj.id, .., self.scope.clone())
@@ -1129,6 +1358,18 @@ impl TransactionInner {
         }
     }
 
+    pub(crate) fn set_context(&mut self, context: Option<TransactionContext>) {
+        self.context = context;
+    }
+
+    /// Look up `ex_obj` for writing, checking that it is not frozen and that the write is
+    /// authorized
+    fn writable_obj(&self, doc: &Automerge, ex_obj: &ExId) -> Result<ObjMeta, AutomergeError> {
+        let obj = doc.exid_to_writable_obj(ex_obj)?;
+        doc.authorize(ex_obj, self.actor, self.context.as_ref())?;
+        Ok(obj)
+    }
+
     pub(crate) fn get_scope(&self) -> &Option<Clock> {
         &self.scope
     }
@@ -1136,6 +1377,15 @@ impl TransactionInner {
     pub(crate) fn get_deps(&self) -> Vec<ChangeHash> {
         self.deps.clone()
     }
+
+    pub(crate) fn pending_change(&self, osd: &OpSetData) -> PendingChange {
+        PendingChange {
+            actor: osd.actors[self.actor].clone(),
+            seq: self.seq,
+            start_op: self.start_op,
+            deps: self.deps.clone(),
+        }
+    }
 }
 
 enum SpliceType<'a> {
@@ -1,15 +1,19 @@
+use std::any::Any;
 use std::ops::RangeBounds;
+use std::sync::Arc;
 
 use crate::exid::ExId;
-use crate::iter::Spans;
-use crate::iter::{Keys, ListRange, MapRange, Values};
+use crate::iter::{Keys, ListRange, MapEntriesAll, MapRange, Values};
+use crate::iter::{Spans, TextChars};
 use crate::marks::{ExpandMark, Mark, MarkSet};
 use crate::patches::PatchLog;
 use crate::types::Clock;
-use crate::{hydrate, AutomergeError};
+use crate::{hydrate, AutomergeError, TextUnit};
 use crate::{Automerge, ChangeHash, Cursor, ObjType, Parents, Prop, ReadDoc, ScalarValue, Value};
 
-use super::{CommitOptions, Transactable, TransactionArgs, TransactionInner};
+use super::{
+    CommitOptions, PendingChange, Savepoint, Transactable, TransactionArgs, TransactionInner,
+};
 
 /// A transaction on a document.
 /// Transactions group operations into a single change so that no other operations can happen
@@ -50,6 +54,24 @@ impl<'a> Transaction<'a> {
     pub fn hash_for_opid(&self, opid: &ExId) -> Option<ChangeHash> {
         self.doc.hash_for_opid(opid)
     }
+
+    /// Attach `context` to this transaction
+    ///
+    /// The context is passed to the authorizer set with [`Automerge::set_authorizer()`] for
+    /// every write made by this transaction, so it can be used to attribute and authorize the
+    /// writes of a particular user.
+    pub fn set_context<C: Any + Send + Sync>(&mut self, context: C) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.set_context(Some(Arc::new(context)));
+        }
+    }
+
+    /// The actor, sequence number, start op and dependencies of the change this transaction will
+    /// create when it is committed
+    pub fn pending_change(&self) -> PendingChange {
+        // inner is only `None` once the transaction has been committed or rolled back
+        self.inner.as_ref().unwrap().pending_change(self.doc.osd())
+    }
 }
 
 impl<'a> Transaction<'a> {
@@ -58,7 +80,7 @@ impl<'a> Transaction<'a> {
         args: TransactionArgs,
         opts: CommitOptions,
     ) -> ChangeHash {
-        TransactionInner::empty(doc, args, opts.message, opts.time)
+        TransactionInner::empty(doc, args, opts)
     }
 }
 
@@ -70,6 +92,10 @@ impl<'a> Transaction<'a> {
 
     /// Commit the operations performed in this transaction, returning the hashes corresponding to
     /// the new heads.
+    ///
+    /// If the document has a schema (see [`Automerge::set_schema()`]) which the transaction
+    /// violates the transaction is rolled back and no hash is returned, use [`Self::try_commit()`]
+    /// to find out why.
     pub fn commit(mut self) -> (Option<ChangeHash>, PatchLog) {
         let tx = self.inner.take().unwrap();
         let hash = tx.commit(self.doc, None, None);
@@ -94,18 +120,81 @@ impl<'a> Transaction<'a> {
     /// tx.commit_with(CommitOptions::default().with_message("Create todos list").with_time(now));
     /// ```
     pub fn commit_with(mut self, options: CommitOptions) -> (Option<ChangeHash>, PatchLog) {
-        let tx = self.inner.take().unwrap();
+        let mut tx = self.inner.take().unwrap();
+        if let Some(extra_bytes) = options.extra_bytes {
+            tx.set_extra_bytes(extra_bytes);
+        }
         let hash = tx.commit(self.doc, options.message, options.time);
         // TODO - remove this clone
         (hash, self.patch_log.clone())
     }
 
+    /// Like [`Self::commit()`] but fail with [`AutomergeError::SchemaViolation`] if the
+    /// transaction leaves the document in a state its schema does not allow
+    ///
+    /// The transaction is rolled back if it fails.
+    pub fn try_commit(self) -> Result<(Option<ChangeHash>, PatchLog), AutomergeError> {
+        self.try_commit_with(CommitOptions::default())
+    }
+
+    /// Like [`Self::commit_with()`] but fail if the transaction violates the schema, see
+    /// [`Self::try_commit()`]
+    pub fn try_commit_with(
+        mut self,
+        options: CommitOptions,
+    ) -> Result<(Option<ChangeHash>, PatchLog), AutomergeError> {
+        let mut tx = self.inner.take().unwrap();
+        if let Some(extra_bytes) = options.extra_bytes {
+            tx.set_extra_bytes(extra_bytes);
+        }
+        let hash = tx.try_commit(self.doc, options.message, options.time)?;
+        // TODO - remove this clone
+        Ok((hash, self.patch_log.clone()))
+    }
+
     /// Undo the operations added in this transaction, returning the number of cancelled
     /// operations.
     pub fn rollback(mut self) -> usize {
         self.inner.take().unwrap().rollback(self.doc)
     }
 
+    /// Mark the current point in the transaction so the operations added after it can be undone
+    /// with [`Self::rollback_to()`] without rolling back the whole transaction
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{Automerge, ReadDoc, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = Automerge::new();
+    /// let mut tx = doc.transaction();
+    /// tx.put(ROOT, "title", "Draft").unwrap();
+    /// let savepoint = tx.savepoint();
+    /// tx.put(ROOT, "title", "Preview").unwrap();
+    /// assert_eq!(tx.rollback_to(savepoint).unwrap(), 1);
+    /// tx.commit();
+    ///
+    /// let (title, _) = doc.get(ROOT, "title").unwrap().unwrap();
+    /// assert_eq!(title.to_str(), Some("Draft"));
+    /// ```
+    pub fn savepoint(&mut self) -> Savepoint {
+        let tx = self.inner.as_mut().unwrap();
+        tx.savepoint(&self.patch_log)
+    }
+
+    /// Undo the operations added since `savepoint` was created, returning the number of
+    /// cancelled operations.
+    ///
+    /// `savepoint` stays valid, but any savepoint created after it does not.
+    ///
+    /// # Errors
+    ///
+    /// [`AutomergeError::InvalidSavepoint`] if `savepoint` was not created by this transaction,
+    /// or was created after a savepoint which has since been rolled back to.
+    pub fn rollback_to(&mut self, savepoint: Savepoint) -> Result<usize, AutomergeError> {
+        self.do_tx(|tx, doc, patch_log| tx.rollback_to(doc, patch_log, savepoint))
+    }
+
     fn do_tx<F, O>(&mut self, f: F) -> O
     where
         F: FnOnce(&mut TransactionInner, &mut Automerge, &mut PatchLog) -> O,
@@ -179,6 +268,20 @@ impl<'a> ReadDoc for Transaction<'a> {
             .values_for(obj.as_ref(), self.get_scope(Some(heads)))
     }
 
+    fn map_entries_all<O: AsRef<ExId>>(&self, obj: O) -> MapEntriesAll<'_> {
+        self.doc
+            .map_entries_all_for(obj.as_ref(), self.get_scope(None))
+    }
+
+    fn map_entries_all_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> MapEntriesAll<'_> {
+        self.doc
+            .map_entries_all_for(obj.as_ref(), self.get_scope(Some(heads)))
+    }
+
     fn length<O: AsRef<ExId>>(&self, obj: O) -> usize {
         self.doc.length_for(obj.as_ref(), self.get_scope(None))
     }
@@ -204,6 +307,19 @@ impl<'a> ReadDoc for Transaction<'a> {
         self.doc.text_for(obj.as_ref(), self.get_scope(Some(heads)))
     }
 
+    fn text_chars<O: AsRef<ExId>>(&self, obj: O) -> Result<TextChars<'_>, AutomergeError> {
+        self.doc.text_chars_for(obj.as_ref(), self.get_scope(None))
+    }
+
+    fn text_chars_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<TextChars<'_>, AutomergeError> {
+        self.doc
+            .text_chars_for(obj.as_ref(), self.get_scope(Some(heads)))
+    }
+
     fn spans<O: AsRef<ExId>>(&self, obj: O) -> Result<Spans<'_>, AutomergeError> {
         self.doc.spans_for(obj.as_ref(), self.get_scope(None))
     }
@@ -382,6 +498,30 @@ impl<'a> Transactable for Transaction<'a> {
         self.do_tx(|tx, doc, hist| tx.insert_object(doc, hist, obj.as_ref(), index, value))
     }
 
+    fn push<O: AsRef<ExId>, V: Into<ScalarValue>>(
+        &mut self,
+        obj: O,
+        value: V,
+    ) -> Result<(), AutomergeError> {
+        self.do_tx(|tx, doc, hist| tx.push(doc, hist, obj.as_ref(), value))
+    }
+
+    fn push_object<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        value: ObjType,
+    ) -> Result<ExId, AutomergeError> {
+        self.do_tx(|tx, doc, hist| tx.push_object(doc, hist, obj.as_ref(), value))
+    }
+
+    fn extend<O: AsRef<ExId>, V: IntoIterator<Item = ScalarValue>>(
+        &mut self,
+        obj: O,
+        values: V,
+    ) -> Result<(), AutomergeError> {
+        self.do_tx(|tx, doc, hist| tx.extend(doc, hist, obj.as_ref(), values))
+    }
+
     fn increment<O: AsRef<ExId>, P: Into<Prop>>(
         &mut self,
         obj: O,
@@ -423,6 +563,17 @@ impl<'a> Transactable for Transaction<'a> {
         Ok(())
     }
 
+    fn splice_text_in<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: isize,
+        text: &str,
+        unit: TextUnit,
+    ) -> Result<(), AutomergeError> {
+        self.do_tx(|tx, doc, hist| tx.splice_text_in(doc, hist, obj.as_ref(), pos, del, text, unit))
+    }
+
     fn mark<O: AsRef<ExId>>(
         &mut self,
         obj: O,
@@ -0,0 +1,20 @@
+use std::num::NonZeroU64;
+
+use crate::{ActorId, ChangeHash};
+
+/// Metadata of the change a transaction will create when it is committed
+///
+/// This is known as soon as the transaction starts, so it can be used to label a change before
+/// the commit completes. Obtained from [`super::Transaction::pending_change()`] or
+/// [`crate::AutoCommit::pending_change()`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingChange {
+    /// The actor which will author the change
+    pub actor: ActorId,
+    /// The sequence number of the change
+    pub seq: u64,
+    /// The counter of the first op in the change
+    pub start_op: NonZeroU64,
+    /// The dependencies of the change
+    pub deps: Vec<ChangeHash>,
+}
@@ -0,0 +1,16 @@
+use crate::op_set::OpIdxRange;
+
+/// A point in a transaction which the transaction can be rolled back to
+///
+/// Created with [`super::Transaction::savepoint()`] or [`crate::AutoCommit::savepoint()`]. A
+/// savepoint can only be used with the transaction it was created in, and rolling back to a
+/// savepoint discards every savepoint created after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Savepoint {
+    /// The ops in the transaction when the savepoint was created
+    pub(crate) range: OpIdxRange,
+    /// The number of events in the patch log of the transaction
+    pub(crate) patches: usize,
+    /// The number of savepoints created before this one
+    pub(crate) depth: usize,
+}
@@ -2,7 +2,7 @@ use std::borrow::Cow;
 
 use crate::exid::ExId;
 use crate::marks::{ExpandMark, Mark};
-use crate::{AutomergeError, ChangeHash, ObjType, Prop, ReadDoc, ScalarValue};
+use crate::{AutomergeError, ChangeHash, ObjType, Prop, ReadDoc, ScalarValue, TextUnit, Value};
 
 /// A way of mutating a document within a single change.
 pub trait Transactable: ReadDoc {
@@ -43,6 +43,138 @@ pub trait Transactable: ReadDoc {
         object: ObjType,
     ) -> Result<ExId, AutomergeError>;
 
+    /// Set the value at `path`, creating any objects which are missing along the way.
+    ///
+    /// See [`crate::ReadDoc::get_path()`] for the syntax of paths. A missing object is created as
+    /// a list if it is followed by an index in the path and as a map otherwise. An index one
+    /// past the end of a list inserts a new element at the end of the list.
+    fn put_path<V: Into<ScalarValue>>(
+        &mut self,
+        path: &str,
+        value: V,
+    ) -> Result<(), AutomergeError> {
+        let (parents, prop) = crate::path::parse(path)?;
+        let obj = crate::path::resolve_parent_or_create(self, &parents, &prop)?;
+        match crate::path::append_index(self, &obj, &prop)? {
+            Some(index) => self.insert(&obj, index, value),
+            None => self.put(&obj, prop, value),
+        }
+    }
+
+    /// Set the value at `path` to a new object, creating any objects which are missing along
+    /// the way, see [`Self::put_path()`].
+    fn put_object_path(&mut self, path: &str, object: ObjType) -> Result<ExId, AutomergeError> {
+        let (parents, prop) = crate::path::parse(path)?;
+        let obj = crate::path::resolve_parent_or_create(self, &parents, &prop)?;
+        match crate::path::append_index(self, &obj, &prop)? {
+            Some(index) => self.insert_object(&obj, index, object),
+            None => self.put_object(&obj, prop, object),
+        }
+    }
+
+    /// Set the value a JSON pointer refers to, see [`crate::ReadDoc::resolve_pointer()`].
+    ///
+    /// A pointer to the position after the last element of a list appends to the list. Unlike
+    /// [`Self::put_path()`] missing objects are not created, instead
+    /// [`AutomergeError::InvalidPath`] is returned.
+    fn put_pointer<V: Into<ScalarValue>>(
+        &mut self,
+        pointer: &str,
+        value: V,
+    ) -> Result<(), AutomergeError> {
+        let (obj, prop) = self
+            .resolve_pointer(pointer)?
+            .ok_or_else(|| AutomergeError::InvalidPath(pointer.to_string()))?;
+        match crate::path::append_index(self, &obj, &prop)? {
+            Some(index) => self.insert(&obj, index, value),
+            None => self.put(&obj, prop, value),
+        }
+    }
+
+    /// Set the value a JSON pointer refers to to a new object, see [`Self::put_pointer()`].
+    fn put_object_pointer(
+        &mut self,
+        pointer: &str,
+        object: ObjType,
+    ) -> Result<ExId, AutomergeError> {
+        let (obj, prop) = self
+            .resolve_pointer(pointer)?
+            .ok_or_else(|| AutomergeError::InvalidPath(pointer.to_string()))?;
+        match crate::path::append_index(self, &obj, &prop)? {
+            Some(index) => self.insert_object(&obj, index, object),
+            None => self.put_object(&obj, prop, object),
+        }
+    }
+
+    /// Delete the value a JSON pointer refers to, see [`crate::ReadDoc::resolve_pointer()`].
+    fn delete_pointer(&mut self, pointer: &str) -> Result<(), AutomergeError> {
+        let (obj, prop) = self
+            .resolve_pointer(pointer)?
+            .ok_or_else(|| AutomergeError::InvalidPath(pointer.to_string()))?;
+        self.delete(&obj, prop)
+    }
+
+    /// Get the object of type `object` at property `P` in object `obj`, creating it if there
+    /// isn't one.
+    ///
+    /// If the property has conflicting values the latest object of type `object` among them is
+    /// returned, which is the same object on every peer which has seen the same changes. This
+    /// means that code which ensures a nested object exists before writing to it converges on
+    /// one object after a merge, as long as the peers which created objects concurrently have
+    /// both seen each other's changes. A new object is only created if none of the values is an
+    /// object of the requested type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ObjType, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = AutoCommit::new();
+    /// let settings = doc.get_or_put_object(ROOT, "settings", ObjType::Map).unwrap();
+    /// doc.put(&settings, "theme", "dark").unwrap();
+    /// assert_eq!(
+    ///     doc.get_or_put_object(ROOT, "settings", ObjType::Map).unwrap(),
+    ///     settings
+    /// );
+    /// ```
+    fn get_or_put_object<O: AsRef<ExId>, P: Into<Prop>>(
+        &mut self,
+        obj: O,
+        prop: P,
+        object: ObjType,
+    ) -> Result<ExId, AutomergeError> {
+        let prop = prop.into();
+        let existing = self
+            .get_all(obj.as_ref(), prop.clone())?
+            .into_iter()
+            .filter(|(value, _)| *value == Value::Object(object))
+            .last();
+        match existing {
+            Some((_, id)) => Ok(id),
+            None => self.put_object(obj, prop, object),
+        }
+    }
+
+    /// Add a new row to the table `table`, returning the ID of the row and of the row object
+    ///
+    /// Each row is a map stored in the table under a new random row ID, so rows added
+    /// concurrently by different actors never conflict and a row keeps its ID however the
+    /// table changes. See [`crate::table::Table`] for reading tables.
+    ///
+    /// # Errors
+    ///
+    /// This will return an error if `table` does not exist or is not a table.
+    fn add_row<O: AsRef<ExId>>(&mut self, table: O) -> Result<(String, ExId), AutomergeError> {
+        let table = table.as_ref();
+        match self.object_type(table)? {
+            ObjType::Table => {}
+            other => return Err(AutomergeError::InvalidOp(other)),
+        }
+        let row_id = uuid::Uuid::new_v4().to_string();
+        let row = self.put_object(table, row_id.as_str(), ObjType::Map)?;
+        Ok((row_id, row))
+    }
+
     /// Insert a value into a list at the given index.
     fn insert<O: AsRef<ExId>, V: Into<ScalarValue>>(
         &mut self,
@@ -59,6 +191,55 @@ pub trait Transactable: ReadDoc {
         object: ObjType,
     ) -> Result<ExId, AutomergeError>;
 
+    /// Append a value to the end of a list.
+    ///
+    /// This is the same as inserting at [`crate::ReadDoc::length()`] but does not need to look
+    /// up the length first, and for lists it skips the search for the insert position, which
+    /// makes it the cheapest way to grow log-style lists.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ObjType, ReadDoc, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = AutoCommit::new();
+    /// let log = doc.put_object(ROOT, "log", ObjType::List).unwrap();
+    /// doc.push(&log, "started").unwrap();
+    /// doc.extend(&log, ["working".into(), "done".into()]).unwrap();
+    /// assert_eq!(doc.length(&log), 3);
+    /// ```
+    ///
+    /// The default implementation inserts at the length, implementors should override it to skip
+    /// the search.
+    fn push<O: AsRef<ExId>, V: Into<ScalarValue>>(
+        &mut self,
+        obj: O,
+        value: V,
+    ) -> Result<(), AutomergeError> {
+        let index = self.length_in(obj.as_ref(), TextUnit::Native);
+        self.insert(obj, index, value)
+    }
+
+    /// Append an object to the end of a list, see [`Self::push()`].
+    fn push_object<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        object: ObjType,
+    ) -> Result<ExId, AutomergeError> {
+        let index = self.length_in(obj.as_ref(), TextUnit::Native);
+        self.insert_object(obj, index, object)
+    }
+
+    /// Append each of `values` to the end of a list, see [`Self::push()`].
+    fn extend<O: AsRef<ExId>, V: IntoIterator<Item = ScalarValue>>(
+        &mut self,
+        obj: O,
+        values: V,
+    ) -> Result<(), AutomergeError> {
+        let index = self.length_in(obj.as_ref(), TextUnit::Native);
+        self.splice(obj, index, 0, values)
+    }
+
     /// Increment the counter at the prop in the object by `value`.
     fn increment<O: AsRef<ExId>, P: Into<Prop>>(
         &mut self,
@@ -86,6 +267,22 @@ pub trait Transactable: ReadDoc {
     ) -> Result<(), AutomergeError>;
 
     /// Like [`Self::splice`] but for text.
+    ///
+    /// The string is split into the document's character representation internally, so there is
+    /// no need to convert it into [`ScalarValue`]s first. `pos` and `del` are measured in the
+    /// native [`TextUnit`], see [`Self::splice_text_in()`] for other units.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ObjType, ReadDoc, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc = AutoCommit::new();
+    /// let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    /// doc.splice_text(&text, 0, 0, "hello world").unwrap();
+    /// doc.splice_text(&text, 6, 5, "there").unwrap();
+    /// assert_eq!(doc.text(&text).unwrap(), "hello there");
+    /// ```
     fn splice_text<O: AsRef<ExId>>(
         &mut self,
         obj: O,
@@ -94,6 +291,67 @@ pub trait Transactable: ReadDoc {
         text: &str,
     ) -> Result<(), AutomergeError>;
 
+    /// Like [`Self::splice_text`] but with `pos` and `del` measured in `unit`
+    ///
+    /// The inserted text is stored one `unit` per sequence element, so for example splicing with
+    /// [`TextUnit::Grapheme`] ensures that concurrent edits cannot split a grapheme cluster. Any
+    /// unit other than [`TextUnit::Native`] requires reading the whole text to translate the
+    /// indexes.
+    ///
+    /// The default implementation inserts the units with [`Self::splice()`], implementors should
+    /// override it so that the edit is reported as a text splice.
+    fn splice_text_in<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: isize,
+        text: &str,
+        unit: TextUnit,
+    ) -> Result<(), AutomergeError> {
+        if unit == TextUnit::Native {
+            return self.splice_text(obj, pos, del, text);
+        }
+        match self.object_type(obj.as_ref())? {
+            ObjType::Text => {}
+            typ => return Err(AutomergeError::InvalidOp(typ)),
+        }
+        let (start, end) = if del < 0 {
+            let start = pos
+                .checked_add_signed(del)
+                .ok_or(AutomergeError::InvalidIndex(pos))?;
+            (start, pos)
+        } else {
+            (pos, pos.saturating_add(del as usize))
+        };
+        let current = self.text(obj.as_ref())?;
+        let native_start = unit
+            .index_to_native(&current, start)
+            .ok_or(AutomergeError::InvalidIndex(start))?;
+        let native_end = unit
+            .index_to_native(&current, end)
+            .unwrap_or_else(|| TextUnit::Native.len(&current));
+        let values = unit.segments(text).map(ScalarValue::from);
+        self.splice(
+            obj,
+            native_start,
+            (native_end - native_start) as isize,
+            values,
+        )
+    }
+
+    /// Like [`Self::splice_text`] but with `pos` and `del` measured in UTF-16 code units
+    ///
+    /// See [`Self::splice_text_in()`] and [`TextUnit::Utf16`]
+    fn splice_text_utf16<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: isize,
+        text: &str,
+    ) -> Result<(), AutomergeError> {
+        self.splice_text_in(obj, pos, del, text, TextUnit::Utf16)
+    }
+
     /// Mark a sequence
     fn mark<O: AsRef<ExId>>(
         &mut self,
@@ -147,10 +405,11 @@ pub trait Transactable: ReadDoc {
 
     /// Update the value of a string
     ///
-    /// This will calculate a diff between the current value and the new value and
-    /// then convert that diff into calls to {@link splice}. This will produce results
-    /// which don't merge as well as directly capturing the user input actions, but
-    /// sometimes it's not possible to capture user input and this is the best you
+    /// This will calculate a diff (using the Myers algorithm over grapheme clusters) between the
+    /// current value and the new value and then convert that diff into calls to
+    /// [`Self::splice_text()`], so only the parts of the text which changed are touched. This
+    /// will produce results which don't merge as well as directly capturing the user input
+    /// actions, but sometimes it's not possible to capture user input and this is the best you
     /// can do.
     fn update_text<S: AsRef<str>>(&mut self, obj: &ExId, new_text: S)
         -> Result<(), AutomergeError>;
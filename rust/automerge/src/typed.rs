@@ -0,0 +1,579 @@
+//! Reading documents through a description of their shape
+//!
+//! Reading a nested value out of a document usually means matching on a [`Value`] at every
+//! step of the path and deciding what to do when it is not what was expected. A [`TypedDoc`]
+//! instead reads the document through a [`Shape`] which describes it, so each step returns a
+//! view of the expected type, or a [`TypedError`] which says where the document differs.
+//!
+//! Maps with a fixed set of keys are described with the [`crate::typed_record!`] macro, which
+//! creates a type with a function for each key. These functions return a [`Field`], and a
+//! [`RecordView`] can only be read with the fields of its own record, so a misspelled key or a
+//! key of a different record is a compile error rather than a missing value.
+//!
+//! | Shape              | View                    |
+//! |--------------------|-------------------------|
+//! | a record           | [`RecordView`]          |
+//! | [`Map<T>`]         | [`MapView`]             |
+//! | [`List<T>`]        | [`ListView`]            |
+//! | [`Text`]           | [`TextView`]            |
+//! | [`Option<T>`]      | `Option` of the view of `T`, `None` if a key is missing |
+//! | [`String`], [`i64`], [`u64`], [`f64`], [`bool`], `Vec<u8>` | the value |
+//!
+//! The [`crate::schema::Schema`] which matches a shape is returned by [`Shape::schema()`], so
+//! the same description can be used to check changes, see [`crate::Automerge::set_schema()`].
+//!
+//! # Example
+//!
+//! ```
+//! # use automerge::{AutoCommit, ObjType, ROOT};
+//! # use automerge::transaction::Transactable;
+//! use automerge::typed::{List, Text, TypedDoc};
+//!
+//! automerge::typed_record! {
+//!     pub struct Todo {
+//!         title: Text,
+//!         done: bool,
+//!         due: Option<i64>,
+//!     }
+//! }
+//!
+//! automerge::typed_record! {
+//!     pub struct Todos {
+//!         todos: List<Todo>,
+//!     }
+//! }
+//!
+//! let mut doc = AutoCommit::new();
+//! let todos = doc.put_object(ROOT, "todos", ObjType::List).unwrap();
+//! let todo = doc.insert_object(&todos, 0, ObjType::Map).unwrap();
+//! let title = doc.put_object(&todo, "title", ObjType::Text).unwrap();
+//! doc.splice_text(&title, 0, 0, "water the plants").unwrap();
+//! doc.put(&todo, "done", "no").unwrap();
+//!
+//! let doc = TypedDoc::<_, Todos>::new(doc);
+//! let first = doc.root().unwrap().get(Todos::todos()).unwrap().get(0).unwrap();
+//! assert_eq!(first.get(Todo::title()).unwrap().text(), "water the plants");
+//! assert_eq!(first.get(Todo::due()).unwrap(), None);
+//! assert_eq!(
+//!     first.get(Todo::done()).unwrap_err().to_string(),
+//!     "expected a boolean but found a str at /todos/0/done"
+//! );
+//! ```
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::exid::ExId;
+use crate::iter::Keys;
+use crate::link::DocId;
+use crate::path::location;
+use crate::schema::{describe, MapSchema, ScalarType, Schema};
+use crate::{ObjType, Prop, ReadDoc, ScalarValue, Value, ROOT};
+
+/// A description of the shape of a value in a document
+pub trait Shape {
+    /// What a value of this shape is read as
+    type View<'a, R: ReadDoc + 'a>;
+
+    /// Whether a key of a record with this shape must be present
+    const REQUIRED: bool = true;
+
+    /// The schema which values of this shape match
+    fn schema() -> Schema;
+
+    /// Read a value of this shape, where `found` is the value and its ID, or `None` if there is
+    /// no value, and `path` is where it is in the document
+    fn read<'a, R: ReadDoc + 'a>(
+        doc: &'a R,
+        found: Option<(Value<'a>, ExId)>,
+        path: &[Prop],
+    ) -> Result<Self::View<'a, R>, TypedError>;
+}
+
+/// A value in a document which does not have the expected shape
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TypedError {
+    #[error("missing value at {}", location(path))]
+    Missing { path: Vec<Prop> },
+    #[error("expected {expected} but found {found} at {}", location(path))]
+    WrongType {
+        path: Vec<Prop>,
+        expected: String,
+        found: String,
+    },
+}
+
+impl TypedError {
+    /// Where in the document the problem is
+    pub fn path(&self) -> &[Prop] {
+        match self {
+            Self::Missing { path } | Self::WrongType { path, .. } => path,
+        }
+    }
+
+    fn wrong_type<S: Shape>(path: &[Prop], found: &Value<'_>) -> Self {
+        Self::WrongType {
+            path: path.to_vec(),
+            expected: S::schema().expected(),
+            found: describe(found),
+        }
+    }
+}
+
+/// Check that `found` is an object of type `obj_type` and return its ID
+fn expect_object<S: Shape>(
+    found: Option<(Value<'_>, ExId)>,
+    obj_type: ObjType,
+    path: &[Prop],
+) -> Result<ExId, TypedError> {
+    match found {
+        Some((Value::Object(t), id)) if t == obj_type => Ok(id),
+        Some((value, _)) => Err(TypedError::wrong_type::<S>(path, &value)),
+        None => Err(TypedError::Missing {
+            path: path.to_vec(),
+        }),
+    }
+}
+
+fn child_path(path: &[Prop], prop: Prop) -> Vec<Prop> {
+    let mut path = path.to_vec();
+    path.push(prop);
+    path
+}
+
+/// A document read through the shape `S` of its root, see [`crate::typed`]
+#[derive(Debug, Clone)]
+pub struct TypedDoc<D, S> {
+    doc: D,
+    _shape: PhantomData<fn() -> S>,
+}
+
+impl<D: ReadDoc, S: Shape> TypedDoc<D, S> {
+    pub fn new(doc: D) -> Self {
+        Self {
+            doc,
+            _shape: PhantomData,
+        }
+    }
+
+    /// A view of the root of the document
+    pub fn root(&self) -> Result<S::View<'_, D>, TypedError> {
+        S::read(&self.doc, Some((Value::Object(ObjType::Map), ROOT)), &[])
+    }
+
+    /// Check that the whole document has the shape `S`
+    pub fn validate(&self) -> Result<(), crate::schema::SchemaViolation> {
+        S::schema().validate(&self.doc)
+    }
+
+    pub fn doc(&self) -> &D {
+        &self.doc
+    }
+
+    /// The document, for making changes
+    pub fn doc_mut(&mut self) -> &mut D {
+        &mut self.doc
+    }
+
+    pub fn into_inner(self) -> D {
+        self.doc
+    }
+}
+
+/// A key of the record `R` whose value has the shape `S`, created by the functions
+/// [`crate::typed_record!`] generates
+pub struct Field<R, S> {
+    key: &'static str,
+    _shape: PhantomData<fn() -> (R, S)>,
+}
+
+impl<R, S: Shape> Field<R, S> {
+    #[doc(hidden)]
+    pub fn new(key: &'static str) -> Self {
+        Self {
+            key,
+            _shape: PhantomData,
+        }
+    }
+
+    pub fn key(&self) -> &'static str {
+        self.key
+    }
+
+    #[doc(hidden)]
+    pub fn add_to(self, schema: MapSchema) -> MapSchema {
+        if S::REQUIRED {
+            schema.required(self.key, S::schema())
+        } else {
+            schema.optional(self.key, S::schema())
+        }
+    }
+}
+
+impl<R, S> Clone for Field<R, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R, S> Copy for Field<R, S> {}
+
+impl<R, S> fmt::Debug for Field<R, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Field").field(&self.key).finish()
+    }
+}
+
+/// A view of a map described with [`crate::typed_record!`]
+pub struct RecordView<'a, D, R> {
+    doc: &'a D,
+    obj: ExId,
+    path: Vec<Prop>,
+    _record: PhantomData<fn() -> R>,
+}
+
+impl<'a, D: ReadDoc, R: Shape> RecordView<'a, D, R> {
+    #[doc(hidden)]
+    pub fn read(
+        doc: &'a D,
+        found: Option<(Value<'a>, ExId)>,
+        path: &[Prop],
+    ) -> Result<Self, TypedError> {
+        Ok(Self {
+            doc,
+            obj: expect_object::<R>(found, ObjType::Map, path)?,
+            path: path.to_vec(),
+            _record: PhantomData,
+        })
+    }
+
+    /// The ID of the map
+    pub fn id(&self) -> &ExId {
+        &self.obj
+    }
+
+    /// The value of `field`
+    pub fn get<S: Shape>(&self, field: Field<R, S>) -> Result<S::View<'a, D>, TypedError> {
+        let path = child_path(&self.path, Prop::Map(field.key.to_string()));
+        S::read(
+            self.doc,
+            self.doc.get(&self.obj, field.key).ok().flatten(),
+            &path,
+        )
+    }
+}
+
+impl<'a, D, R> fmt::Debug for RecordView<'a, D, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordView")
+            .field("obj", &self.obj)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+/// A map whose values all have the shape `S`
+#[derive(Debug)]
+pub struct Map<S>(PhantomData<fn() -> S>);
+
+impl<S: Shape> Shape for Map<S> {
+    type View<'a, R: ReadDoc + 'a> = MapView<'a, R, S>;
+
+    fn schema() -> Schema {
+        Schema::map().other_keys(S::schema()).into()
+    }
+
+    fn read<'a, R: ReadDoc + 'a>(
+        doc: &'a R,
+        found: Option<(Value<'a>, ExId)>,
+        path: &[Prop],
+    ) -> Result<Self::View<'a, R>, TypedError> {
+        Ok(MapView {
+            doc,
+            obj: expect_object::<Self>(found, ObjType::Map, path)?,
+            path: path.to_vec(),
+            _shape: PhantomData,
+        })
+    }
+}
+
+/// A view of a [`Map`]
+pub struct MapView<'a, R, S> {
+    doc: &'a R,
+    obj: ExId,
+    path: Vec<Prop>,
+    _shape: PhantomData<fn() -> S>,
+}
+
+impl<'a, R: ReadDoc, S: Shape> MapView<'a, R, S> {
+    /// The ID of the map
+    pub fn id(&self) -> &ExId {
+        &self.obj
+    }
+
+    pub fn len(&self) -> usize {
+        self.doc.length(&self.obj)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn keys(&self) -> Keys<'a> {
+        self.doc.keys(&self.obj)
+    }
+
+    /// The value at `key`, or `None` if the map has no value at `key`
+    pub fn get(&self, key: &str) -> Option<Result<S::View<'a, R>, TypedError>> {
+        let found = self.doc.get(&self.obj, key).ok().flatten()?;
+        let path = child_path(&self.path, Prop::Map(key.to_string()));
+        Some(S::read(self.doc, Some(found), &path))
+    }
+}
+
+impl<'a, R, S> fmt::Debug for MapView<'a, R, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MapView")
+            .field("obj", &self.obj)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+/// A list whose elements all have the shape `S`
+#[derive(Debug)]
+pub struct List<S>(PhantomData<fn() -> S>);
+
+impl<S: Shape> Shape for List<S> {
+    type View<'a, R: ReadDoc + 'a> = ListView<'a, R, S>;
+
+    fn schema() -> Schema {
+        Schema::list(S::schema())
+    }
+
+    fn read<'a, R: ReadDoc + 'a>(
+        doc: &'a R,
+        found: Option<(Value<'a>, ExId)>,
+        path: &[Prop],
+    ) -> Result<Self::View<'a, R>, TypedError> {
+        Ok(ListView {
+            doc,
+            obj: expect_object::<Self>(found, ObjType::List, path)?,
+            path: path.to_vec(),
+            _shape: PhantomData,
+        })
+    }
+}
+
+/// A view of a [`List`]
+pub struct ListView<'a, R, S> {
+    doc: &'a R,
+    obj: ExId,
+    path: Vec<Prop>,
+    _shape: PhantomData<fn() -> S>,
+}
+
+impl<'a, R: ReadDoc, S: Shape> ListView<'a, R, S> {
+    /// The ID of the list
+    pub fn id(&self) -> &ExId {
+        &self.obj
+    }
+
+    pub fn len(&self) -> usize {
+        self.doc.length(&self.obj)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The element at `index`, which is [`TypedError::Missing`] if `index` is out of bounds
+    pub fn get(&self, index: usize) -> Result<S::View<'a, R>, TypedError> {
+        let path = child_path(&self.path, Prop::Seq(index));
+        S::read(
+            self.doc,
+            self.doc.get(&self.obj, index).ok().flatten(),
+            &path,
+        )
+    }
+
+    /// Every element of the list, in order
+    pub fn iter(&self) -> impl Iterator<Item = Result<S::View<'a, R>, TypedError>> + '_ {
+        (0..self.len()).map(|index| self.get(index))
+    }
+}
+
+impl<'a, R, S> fmt::Debug for ListView<'a, R, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ListView")
+            .field("obj", &self.obj)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+/// A text object
+#[derive(Debug)]
+pub struct Text;
+
+impl Shape for Text {
+    type View<'a, R: ReadDoc + 'a> = TextView<'a, R>;
+
+    fn schema() -> Schema {
+        Schema::text()
+    }
+
+    fn read<'a, R: ReadDoc + 'a>(
+        doc: &'a R,
+        found: Option<(Value<'a>, ExId)>,
+        path: &[Prop],
+    ) -> Result<Self::View<'a, R>, TypedError> {
+        Ok(TextView {
+            doc,
+            obj: expect_object::<Self>(found, ObjType::Text, path)?,
+        })
+    }
+}
+
+/// A view of a [`Text`]
+pub struct TextView<'a, R> {
+    doc: &'a R,
+    obj: ExId,
+}
+
+impl<'a, R: ReadDoc> TextView<'a, R> {
+    /// The ID of the text object
+    pub fn id(&self) -> &ExId {
+        &self.obj
+    }
+
+    /// The contents of the text object
+    pub fn text(&self) -> String {
+        self.doc.text(&self.obj).unwrap_or_default()
+    }
+}
+
+impl<'a, R> fmt::Debug for TextView<'a, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TextView").field("obj", &self.obj).finish()
+    }
+}
+
+impl<S: Shape> Shape for Option<S> {
+    type View<'a, R: ReadDoc + 'a> = Option<S::View<'a, R>>;
+
+    const REQUIRED: bool = false;
+
+    fn schema() -> Schema {
+        S::schema()
+    }
+
+    fn read<'a, R: ReadDoc + 'a>(
+        doc: &'a R,
+        found: Option<(Value<'a>, ExId)>,
+        path: &[Prop],
+    ) -> Result<Self::View<'a, R>, TypedError> {
+        match found {
+            Some(found) => S::read(doc, Some(found), path).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+macro_rules! scalar_shape {
+    ($t:ty, $typ:ident, $value:pat => $read:expr) => {
+        impl Shape for $t {
+            type View<'a, R: ReadDoc + 'a> = $t;
+
+            fn schema() -> Schema {
+                Schema::scalar(ScalarType::$typ)
+            }
+
+            fn read<'a, R: ReadDoc + 'a>(
+                _doc: &'a R,
+                found: Option<(Value<'a>, ExId)>,
+                path: &[Prop],
+            ) -> Result<Self::View<'a, R>, TypedError> {
+                match found {
+                    Some((Value::Scalar(s), _)) => match s.as_ref() {
+                        $value => Ok($read),
+                        _ => Err(TypedError::wrong_type::<Self>(path, &Value::Scalar(s))),
+                    },
+                    Some((value, _)) => Err(TypedError::wrong_type::<Self>(path, &value)),
+                    None => Err(TypedError::Missing {
+                        path: path.to_vec(),
+                    }),
+                }
+            }
+        }
+    };
+}
+
+scalar_shape!(String, Str, ScalarValue::Str(s) => s.to_string());
+scalar_shape!(i64, Int, ScalarValue::Int(n) => *n);
+scalar_shape!(u64, Uint, ScalarValue::Uint(n) => *n);
+scalar_shape!(f64, F64, ScalarValue::F64(n) => *n);
+scalar_shape!(bool, Boolean, ScalarValue::Boolean(b) => *b);
+scalar_shape!(Vec<u8>, Bytes, ScalarValue::Bytes(b) => b.clone());
+scalar_shape!(DocId, Link, ScalarValue::Link(d) => d.clone());
+
+/// Describe a map with a fixed set of keys, see [`crate::typed`]
+///
+/// This creates a type with a function for each key which returns the [`Field`] used to read
+/// that key from a [`RecordView`]. The type implements [`Shape`], so it can be used in other
+/// shapes.
+///
+/// ```
+/// # use automerge::typed::{List, Shape, Text};
+/// automerge::typed_record! {
+///     /// A contact in an address book
+///     pub struct Contact {
+///         name: Text,
+///         emails: List<String>,
+///         age: Option<u64>,
+///     }
+/// }
+///
+/// assert_eq!(Contact::emails().key(), "emails");
+/// let schema = Contact::schema();
+/// ```
+#[macro_export]
+macro_rules! typed_record {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_meta:meta])* $field:ident : $shape:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        $vis struct $name;
+
+        #[allow(dead_code)]
+        impl $name {
+            $(
+                $(#[$field_meta])*
+                $vis fn $field() -> $crate::typed::Field<$name, $shape> {
+                    $crate::typed::Field::new(stringify!($field))
+                }
+            )*
+        }
+
+        impl $crate::typed::Shape for $name {
+            type View<'a, R: $crate::ReadDoc + 'a> = $crate::typed::RecordView<'a, R, $name>;
+
+            fn schema() -> $crate::schema::Schema {
+                let schema = $crate::schema::Schema::map();
+                $(let schema = $name::$field().add_to(schema);)*
+                schema.into()
+            }
+
+            fn read<'a, R: $crate::ReadDoc + 'a>(
+                doc: &'a R,
+                found: Option<($crate::Value<'a>, $crate::ObjId)>,
+                path: &[$crate::Prop],
+            ) -> Result<Self::View<'a, R>, $crate::typed::TypedError> {
+                $crate::typed::RecordView::read(doc, found, path)
+            }
+        }
+    };
+}
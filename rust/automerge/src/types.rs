@@ -160,7 +160,9 @@ impl fmt::Display for ActorId {
 pub enum ObjType {
     /// A map
     Map,
-    /// Retained for backwards compatibility, tables are identical to maps
+    /// A map of rows keyed by row ID, see [`crate::table`]
+    ///
+    /// Apart from how rows are added, tables behave like maps.
     Table,
     /// A sequence of arbitrary values
     List,
@@ -252,6 +254,7 @@ impl OpType {
         mark_name: Option<smol_str::SmolStr>,
         expand: bool,
     ) -> OpType {
+        let value = crate::columnar::column_range::decode_extension(value);
         match action {
             0 => Self::Make(ObjType::Map),
             1 => Self::Put(value),
@@ -1,4 +1,9 @@
+use crate::bignum::{BigInt, Decimal};
+use crate::blob::BlobRef;
+use crate::chunked_bytes::ChunkedBytes;
+use crate::datetime::DateTime;
 use crate::error;
+use crate::link::DocId;
 use crate::types::ObjType;
 use serde::{Deserialize, Serialize, Serializer};
 use smol_str::SmolStr;
@@ -125,6 +130,14 @@ impl<'a> Value<'a> {
         }
     }
 
+    pub fn is_datetime(&self) -> bool {
+        if let Self::Scalar(s) = self {
+            s.is_datetime()
+        } else {
+            false
+        }
+    }
+
     pub fn is_boolean(&self) -> bool {
         if let Self::Scalar(s) = self {
             s.is_boolean()
@@ -212,6 +225,14 @@ impl<'a> Value<'a> {
         }
     }
 
+    /// If this value is a date time or timestamp, return it as a [`DateTime`]
+    pub fn to_datetime(&self) -> Option<DateTime> {
+        match self {
+            Value::Scalar(s) => s.to_datetime(),
+            _ => None,
+        }
+    }
+
     /// If this value can be coerced to an i64, return the i64 value
     pub fn to_i64(&self) -> Option<i64> {
         match self {
@@ -234,6 +255,15 @@ impl<'a> Value<'a> {
         }
     }
 
+    /// If this value is a number, return it exactly as a [`Decimal`], see
+    /// [`ScalarValue::to_decimal()`]
+    pub fn to_decimal(&self) -> Option<Decimal> {
+        match self {
+            Value::Scalar(s) => s.to_decimal(),
+            _ => None,
+        }
+    }
+
     pub fn to_bool(&self) -> Option<bool> {
         match self {
             Value::Scalar(s) => s.to_bool(),
@@ -317,6 +347,42 @@ impl<'a> From<u64> for Value<'a> {
     }
 }
 
+impl<'a> From<BigInt> for Value<'a> {
+    fn from(n: BigInt) -> Self {
+        Value::Scalar(Cow::Owned(ScalarValue::BigInt(n)))
+    }
+}
+
+impl<'a> From<Decimal> for Value<'a> {
+    fn from(n: Decimal) -> Self {
+        Value::Scalar(Cow::Owned(ScalarValue::Decimal(n)))
+    }
+}
+
+impl<'a> From<BlobRef> for Value<'a> {
+    fn from(b: BlobRef) -> Self {
+        Value::Scalar(Cow::Owned(ScalarValue::BlobRef(Box::new(b))))
+    }
+}
+
+impl<'a> From<DocId> for Value<'a> {
+    fn from(d: DocId) -> Self {
+        Value::Scalar(Cow::Owned(ScalarValue::Link(d)))
+    }
+}
+
+impl<'a> From<ChunkedBytes> for Value<'a> {
+    fn from(b: ChunkedBytes) -> Self {
+        Value::Scalar(Cow::Owned(ScalarValue::Chunked(b)))
+    }
+}
+
+impl<'a> From<DateTime> for Value<'a> {
+    fn from(d: DateTime) -> Self {
+        Value::Scalar(Cow::Owned(ScalarValue::DateTime(d)))
+    }
+}
+
 impl<'a> From<bool> for Value<'a> {
     fn from(v: bool) -> Self {
         Value::Scalar(Cow::Owned(ScalarValue::Boolean(v)))
@@ -347,6 +413,130 @@ impl<'a> From<&'a ScalarValue> for Value<'a> {
     }
 }
 
+/// A value in an automerge document which borrows its string and bytes data from the document
+///
+/// Returned by [`crate::Automerge::get_ref()`] and [`crate::Automerge::values_ref()`], which
+/// avoid cloning the data of every value they return.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueRef<'a> {
+    /// A composite object of type [`ObjType`]
+    Object(ObjType),
+    Str(&'a str),
+    Bytes(&'a [u8]),
+    Int(i64),
+    Uint(u64),
+    F64(f64),
+    /// The current value of a counter
+    Counter(i64),
+    Timestamp(i64),
+    DateTime(DateTime),
+    BigInt(&'a BigInt),
+    Decimal(&'a Decimal),
+    Chunked(&'a ChunkedBytes),
+    BlobRef(&'a BlobRef),
+    Link(&'a DocId),
+    Boolean(bool),
+    /// A value from a future version of automerge
+    Unknown {
+        type_code: u8,
+        bytes: &'a [u8],
+    },
+    Null,
+}
+
+impl<'a> ValueRef<'a> {
+    pub fn is_object(&self) -> bool {
+        matches!(self, ValueRef::Object(_))
+    }
+
+    pub fn is_scalar(&self) -> bool {
+        !self.is_object()
+    }
+
+    pub fn to_str(&self) -> Option<&'a str> {
+        match self {
+            ValueRef::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            ValueRef::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn to_objtype(&self) -> Option<ObjType> {
+        match self {
+            ValueRef::Object(o) => Some(*o),
+            _ => None,
+        }
+    }
+
+    /// Copy this value out of the document
+    pub fn to_owned(&self) -> Value<'static> {
+        let scalar = match *self {
+            ValueRef::Object(o) => return Value::Object(o),
+            ValueRef::Str(s) => ScalarValue::Str(s.into()),
+            ValueRef::Bytes(b) => ScalarValue::Bytes(b.to_vec()),
+            ValueRef::Int(n) => ScalarValue::Int(n),
+            ValueRef::Uint(n) => ScalarValue::Uint(n),
+            ValueRef::F64(n) => ScalarValue::F64(n),
+            ValueRef::Counter(n) => ScalarValue::counter(n),
+            ValueRef::Timestamp(n) => ScalarValue::Timestamp(n),
+            ValueRef::DateTime(d) => ScalarValue::DateTime(d),
+            ValueRef::BigInt(n) => ScalarValue::BigInt(n.clone()),
+            ValueRef::Decimal(n) => ScalarValue::Decimal(n.clone()),
+            ValueRef::Chunked(b) => ScalarValue::Chunked(b.clone()),
+            ValueRef::BlobRef(b) => ScalarValue::BlobRef(Box::new(b.clone())),
+            ValueRef::Link(d) => ScalarValue::Link(d.clone()),
+            ValueRef::Boolean(b) => ScalarValue::Boolean(b),
+            ValueRef::Unknown { type_code, bytes } => ScalarValue::Unknown {
+                type_code,
+                bytes: bytes.to_vec(),
+            },
+            ValueRef::Null => ScalarValue::Null,
+        };
+        Value::Scalar(Cow::Owned(scalar))
+    }
+}
+
+impl<'a> From<&'a ScalarValue> for ValueRef<'a> {
+    fn from(v: &'a ScalarValue) -> Self {
+        match v {
+            ScalarValue::Bytes(b) => ValueRef::Bytes(b),
+            ScalarValue::Str(s) => ValueRef::Str(s),
+            ScalarValue::Int(n) => ValueRef::Int(*n),
+            ScalarValue::Uint(n) => ValueRef::Uint(*n),
+            ScalarValue::F64(n) => ValueRef::F64(*n),
+            ScalarValue::Counter(c) => ValueRef::Counter(c.current),
+            ScalarValue::Timestamp(n) => ValueRef::Timestamp(*n),
+            ScalarValue::DateTime(d) => ValueRef::DateTime(*d),
+            ScalarValue::BigInt(n) => ValueRef::BigInt(n),
+            ScalarValue::Decimal(n) => ValueRef::Decimal(n),
+            ScalarValue::Chunked(b) => ValueRef::Chunked(b),
+            ScalarValue::BlobRef(b) => ValueRef::BlobRef(b),
+            ScalarValue::Link(d) => ValueRef::Link(d),
+            ScalarValue::Boolean(b) => ValueRef::Boolean(*b),
+            ScalarValue::Unknown { type_code, bytes } => ValueRef::Unknown {
+                type_code: *type_code,
+                bytes,
+            },
+            ScalarValue::Null => ValueRef::Null,
+        }
+    }
+}
+
+impl<'a> PartialEq<Value<'_>> for ValueRef<'a> {
+    fn eq(&self, other: &Value<'_>) -> bool {
+        match other {
+            Value::Object(o) => *self == ValueRef::Object(*o),
+            Value::Scalar(s) => *self == ValueRef::from(s.as_ref()),
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug, Clone, Copy)]
 pub(crate) enum DataType {
     #[serde(rename = "counter")]
@@ -365,16 +555,92 @@ pub(crate) enum DataType {
     Undefined,
 }
 
+/// The value of a counter, see [`ScalarValue::Counter`]
+///
+/// A counter may be bounded, see [`ScalarValue::bounded_counter()`]. Increments which would take
+/// a bounded counter past one of its bounds are clamped to that bound.
 #[derive(Debug, Clone)]
 pub struct Counter {
     pub(crate) start: i64,
     pub(crate) current: i64,
+    // boxed so that bounds don't make every `ScalarValue` larger
+    bounds: Option<Box<Bounds>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Bounds {
+    min: Option<i64>,
+    max: Option<i64>,
 }
 
 impl Counter {
+    /// A counter starting at `start` which is never less than `min` or greater than `max`
+    ///
+    /// `start` is clamped to the bounds.
+    ///
+    /// # Panics
+    ///
+    /// If `min` is greater than `max`
+    pub fn bounded(start: i64, min: Option<i64>, max: Option<i64>) -> Self {
+        if let (Some(min), Some(max)) = (min, max) {
+            assert!(
+                min <= max,
+                "counter minimum {} is above maximum {}",
+                min,
+                max
+            );
+        }
+        let mut counter = Counter {
+            start,
+            current: start,
+            bounds: (min.is_some() || max.is_some()).then(|| Box::new(Bounds { min, max })),
+        };
+        counter.start = counter.clamp(start);
+        counter.current = counter.start;
+        counter
+    }
+
+    /// The current value of the counter
+    pub fn value(&self) -> i64 {
+        self.current
+    }
+
+    /// The lower bound of the counter, if it has one
+    pub fn min(&self) -> Option<i64> {
+        self.bounds.as_ref().and_then(|b| b.min)
+    }
+
+    /// The upper bound of the counter, if it has one
+    pub fn max(&self) -> Option<i64> {
+        self.bounds.as_ref().and_then(|b| b.max)
+    }
+
+    pub fn is_bounded(&self) -> bool {
+        self.bounds.is_some()
+    }
+
     pub(crate) fn increment(&mut self, inc: i64) {
         self.current += inc;
     }
+
+    fn clamp(&self, n: i64) -> i64 {
+        let n = self.min().map_or(n, |min| n.max(min));
+        self.max().map_or(n, |max| n.min(max))
+    }
+
+    /// The value of the counter after applying `increments` to its start value
+    ///
+    /// For a bounded counter each increment is clamped in turn, so the result depends on the
+    /// order of `increments`. Callers pass them in lamport order, which every replica agrees on.
+    pub(crate) fn apply<I: IntoIterator<Item = i64>>(&self, increments: I) -> i64 {
+        if self.is_bounded() {
+            increments
+                .into_iter()
+                .fold(self.start, |n, inc| self.clamp(n.saturating_add(inc)))
+        } else {
+            self.start + increments.into_iter().sum::<i64>()
+        }
+    }
 }
 
 impl Serialize for Counter {
@@ -397,6 +663,7 @@ impl From<i64> for Counter {
         Counter {
             start: n,
             current: n,
+            bounds: None,
         }
     }
 }
@@ -406,6 +673,7 @@ impl From<&i64> for Counter {
         Counter {
             start: *n,
             current: *n,
+            bounds: None,
         }
     }
 }
@@ -435,6 +703,28 @@ impl From<&Counter> for f64 {
 }
 
 /// A value which is not a composite value
+///
+/// ## Storage of values the format has no type code for
+///
+/// The binary format has type codes for [`Self::Null`], [`Self::Boolean`], [`Self::Uint`],
+/// [`Self::Int`], [`Self::F64`], [`Self::Str`], [`Self::Bytes`], [`Self::Timestamp`] and counters
+/// without bounds. Implementations read values with any other type code as [`Self::Unknown`] and
+/// write them back unchanged. The other variants are stored with type code 15, followed by a
+/// subtype byte and the contents, which is the contract for implementations which want to read
+/// them:
+///
+/// | Subtype | Variant | Contents |
+/// |---------|---------|----------|
+/// | 0 | [`Self::BlobRef`] | the 32 byte hash, the size as a uLEB128 and the UTF-8 hint, if any |
+/// | 1 | [`Self::Link`] | the bytes of the [`DocId`] |
+/// | 2 | [`Self::Counter`] with bounds | the start as a LEB128, a byte with bit 0 set if there is a minimum and bit 1 set if there is a maximum, then the minimum and maximum which are set as LEB128s |
+/// | 3 | [`Self::DateTime`] | the seconds since the unix epoch as a LEB128, the nanoseconds as a uLEB128 and the offset from UTC in seconds as a LEB128, if any |
+/// | 4 | [`Self::BigInt`] | the ASCII text of [`BigInt::as_str()`] |
+/// | 5 | [`Self::Decimal`] | the ASCII text of [`Decimal::as_str()`] |
+/// | 6 | [`Self::Chunked`] | the length as a uLEB128, the number of segments as a uLEB128, then for each segment `0` and its 32 byte hash, or `1`, its length as a uLEB128 and its bytes |
+///
+/// LEB128s are the shortest encoding of their value. A type 15 value with another subtype, or
+/// whose contents are not exactly as above, is read as [`Self::Unknown`].
 #[derive(Serialize, PartialEq, Debug, Clone)]
 #[serde(untagged)]
 pub enum ScalarValue {
@@ -444,7 +734,20 @@ pub enum ScalarValue {
     Uint(u64),
     F64(f64),
     Counter(Counter),
+    /// Milliseconds since the unix epoch
     Timestamp(i64),
+    /// A date time with nanosecond precision and an optional offset from UTC
+    DateTime(DateTime),
+    /// An integer of any size
+    BigInt(BigInt),
+    /// A decimal number of any size and precision
+    Decimal(Decimal),
+    /// A large binary value stored in content addressed segments
+    Chunked(ChunkedBytes),
+    /// A reference to binary data stored outside the document, see [`crate::blob`]
+    BlobRef(Box<BlobRef>),
+    /// A link to another document, see [`crate::link`]
+    Link(DocId),
     Boolean(bool),
     /// A value from a future version of automerge
     Unknown {
@@ -456,7 +759,7 @@ pub enum ScalarValue {
 
 impl PartialEq for Counter {
     fn eq(&self, other: &Self) -> bool {
-        self.current == other.current
+        self.current == other.current && self.bounds == other.bounds
     }
 }
 
@@ -576,6 +879,38 @@ impl ScalarValue {
         matches!(self, Self::Timestamp(_))
     }
 
+    pub fn is_datetime(&self) -> bool {
+        matches!(self, Self::DateTime(_))
+    }
+
+    pub fn to_datetime(&self) -> Option<DateTime> {
+        match self {
+            ScalarValue::DateTime(d) => Some(*d),
+            ScalarValue::Timestamp(n) => Some(DateTime::from_unix_millis(*n)),
+            _ => None,
+        }
+    }
+
+    pub fn is_bigint(&self) -> bool {
+        matches!(self, Self::BigInt(_))
+    }
+
+    pub fn is_decimal(&self) -> bool {
+        matches!(self, Self::Decimal(_))
+    }
+
+    pub fn is_chunked(&self) -> bool {
+        matches!(self, Self::Chunked(_))
+    }
+
+    pub fn is_blob_ref(&self) -> bool {
+        matches!(self, Self::BlobRef(_))
+    }
+
+    pub fn is_link(&self) -> bool {
+        matches!(self, Self::Link(_))
+    }
+
     pub fn is_boolean(&self) -> bool {
         matches!(self, Self::Boolean(_))
     }
@@ -598,6 +933,27 @@ impl ScalarValue {
         }
     }
 
+    pub fn to_chunked(&self) -> Option<&ChunkedBytes> {
+        match self {
+            ScalarValue::Chunked(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn to_blob_ref(&self) -> Option<&BlobRef> {
+        match self {
+            ScalarValue::BlobRef(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn to_link(&self) -> Option<&DocId> {
+        match self {
+            ScalarValue::Link(d) => Some(d),
+            _ => None,
+        }
+    }
+
     pub fn into_string(self) -> Result<String, Self> {
         match self {
             ScalarValue::Str(s) => Ok(s.to_string()),
@@ -620,6 +976,7 @@ impl ScalarValue {
             ScalarValue::F64(n) => Some(*n as i64),
             ScalarValue::Counter(n) => Some(n.into()),
             ScalarValue::Timestamp(n) => Some(*n),
+            ScalarValue::BigInt(n) => i64::try_from(n).ok(),
             _ => None,
         }
     }
@@ -631,6 +988,7 @@ impl ScalarValue {
             ScalarValue::F64(n) => Some(*n as u64),
             ScalarValue::Counter(n) => Some(n.into()),
             ScalarValue::Timestamp(n) => Some(*n as u64),
+            ScalarValue::BigInt(n) => u64::try_from(n).ok(),
             _ => None,
         }
     }
@@ -642,6 +1000,25 @@ impl ScalarValue {
             ScalarValue::F64(n) => Some(*n),
             ScalarValue::Counter(n) => Some(n.into()),
             ScalarValue::Timestamp(n) => Some(*n as f64),
+            ScalarValue::BigInt(n) => Some(n.to_f64()),
+            ScalarValue::Decimal(n) => Some(n.to_f64()),
+            _ => None,
+        }
+    }
+
+    /// If this value is a number return it exactly as a [`Decimal`]
+    ///
+    /// Floats are converted to the shortest decimal which parses back to the same float, and
+    /// infinite and NaN floats are not numbers. Counters are their current value.
+    pub fn to_decimal(&self) -> Option<Decimal> {
+        match self {
+            ScalarValue::Int(n) => Some(Decimal::from(*n)),
+            ScalarValue::Uint(n) => Some(Decimal::from(*n)),
+            ScalarValue::F64(n) => Decimal::try_from(*n).ok(),
+            ScalarValue::Counter(n) => Some(Decimal::from(n.current)),
+            ScalarValue::Timestamp(n) => Some(Decimal::from(*n)),
+            ScalarValue::BigInt(n) => Some(Decimal::from(n.clone())),
+            ScalarValue::Decimal(n) => Some(n.clone()),
             _ => None,
         }
     }
@@ -656,6 +1033,70 @@ impl ScalarValue {
     pub fn counter(n: i64) -> ScalarValue {
         ScalarValue::Counter(n.into())
     }
+
+    /// A counter which concurrent increments cannot take below `min` or above `max`
+    ///
+    /// When increments are merged they are applied in a deterministic order and each is clamped
+    /// to the bounds, so every replica sees the same value. This is useful for things like stock
+    /// levels where concurrent decrements must not take the value below zero.
+    ///
+    /// # Panics
+    ///
+    /// If `min` is greater than `max`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use automerge::{AutoCommit, ReadDoc, ScalarValue, Value, ROOT};
+    /// # use automerge::transaction::Transactable;
+    /// let mut doc1 = AutoCommit::new();
+    /// doc1.put(ROOT, "seats", ScalarValue::bounded_counter(1, Some(0), None)).unwrap();
+    /// let mut doc2 = doc1.fork();
+    /// doc1.increment(ROOT, "seats", -1).unwrap();
+    /// doc2.increment(ROOT, "seats", -1).unwrap();
+    /// doc1.merge(&mut doc2).unwrap();
+    /// let (seats, _) = doc1.get(ROOT, "seats").unwrap().unwrap();
+    /// assert_eq!(seats.to_i64(), Some(0));
+    /// ```
+    pub fn bounded_counter(n: i64, min: Option<i64>, max: Option<i64>) -> ScalarValue {
+        ScalarValue::Counter(Counter::bounded(n, min, max))
+    }
+}
+
+impl From<BigInt> for ScalarValue {
+    fn from(n: BigInt) -> Self {
+        ScalarValue::BigInt(n)
+    }
+}
+
+impl From<Decimal> for ScalarValue {
+    fn from(n: Decimal) -> Self {
+        ScalarValue::Decimal(n)
+    }
+}
+
+impl From<BlobRef> for ScalarValue {
+    fn from(b: BlobRef) -> Self {
+        ScalarValue::BlobRef(Box::new(b))
+    }
+}
+
+impl From<DocId> for ScalarValue {
+    fn from(d: DocId) -> Self {
+        ScalarValue::Link(d)
+    }
+}
+
+impl From<ChunkedBytes> for ScalarValue {
+    fn from(b: ChunkedBytes) -> Self {
+        ScalarValue::Chunked(b)
+    }
+}
+
+impl From<DateTime> for ScalarValue {
+    fn from(d: DateTime) -> Self {
+        ScalarValue::DateTime(d)
+    }
 }
 
 impl From<&str> for ScalarValue {
@@ -740,6 +1181,12 @@ impl fmt::Display for ScalarValue {
             ScalarValue::F64(n) => write!(f, "{:.324}", n),
             ScalarValue::Counter(c) => write!(f, "Counter: {}", c),
             ScalarValue::Timestamp(i) => write!(f, "Timestamp: {}", i),
+            ScalarValue::DateTime(d) => write!(f, "DateTime: {}", d),
+            ScalarValue::BigInt(n) => write!(f, "BigInt: {}", n),
+            ScalarValue::Decimal(n) => write!(f, "Decimal: {}", n),
+            ScalarValue::Chunked(b) => write!(f, "Chunked: {}", b),
+            ScalarValue::BlobRef(b) => write!(f, "BlobRef: {}", b),
+            ScalarValue::Link(d) => write!(f, "Link: {}", d),
             ScalarValue::Boolean(b) => write!(f, "{}", b),
             ScalarValue::Null => write!(f, "null"),
             ScalarValue::Unknown { type_code, .. } => write!(f, "unknown type {}", type_code),
@@ -3,8 +3,9 @@ use automerge::op_tree::B;
 use automerge::patches::TextRepresentation;
 use automerge::transaction::{CommitOptions, Transactable};
 use automerge::{
-    sync::SyncDoc, ActorId, AutoCommit, Automerge, AutomergeError, Change, ExpandedChange, ObjId,
-    ObjType, Patch, PatchAction, PatchLog, Prop, ReadDoc, ScalarValue, SequenceTree, Value, ROOT,
+    sync::SyncDoc, ActorId, ActorInfo, AutoCommit, AutoSerde, Automerge, AutomergeError, Change,
+    ContentDifference, DepsStrategy, ExpandedChange, ObjId, ObjType, Patch, PatchAction, PatchLog,
+    Prop, ReadDoc, ScalarValue, SequenceTree, Value, ValueRef, ROOT,
 };
 use std::fs;
 
@@ -194,6 +195,37 @@ fn concurrent_updates_of_same_field() {
     );
 }
 
+#[test]
+fn map_entries_all_yields_every_conflicting_value() {
+    let mut doc1 = new_doc();
+    let mut doc2 = new_doc();
+    doc1.put(&automerge::ROOT, "field", "one").unwrap();
+    doc1.put(&automerge::ROOT, "other", 1).unwrap();
+    doc2.put(&automerge::ROOT, "field", "two").unwrap();
+    let heads_before_merge = doc1.get_heads();
+
+    doc1.merge(&mut doc2).unwrap();
+
+    let entries = doc1.map_entries_all(automerge::ROOT).collect::<Vec<_>>();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].key, "field");
+    assert!(entries[0].is_conflict());
+    assert_eq!(
+        entries[0].values,
+        doc1.get_all(automerge::ROOT, "field").unwrap()
+    );
+    assert_eq!(entries[1].key, "other");
+    assert!(!entries[1].is_conflict());
+    assert_eq!(entries[1].values[0].0, Value::int(1));
+
+    let entries = doc1
+        .map_entries_all_at(automerge::ROOT, &heads_before_merge)
+        .collect::<Vec<_>>();
+    assert_eq!(entries.len(), 2);
+    assert!(!entries[0].is_conflict());
+    assert_eq!(entries[0].values[0].0, Value::str("one"));
+}
+
 #[test]
 fn concurrent_updates_of_same_list_element() {
     let mut doc1 = new_doc();
@@ -2276,3 +2308,4266 @@ fn stats_smoke_test() {
     assert_eq!(stats.num_changes, 2);
     assert_eq!(stats.num_ops, 2);
 }
+
+#[test]
+fn shared_document_can_be_read_from_many_threads() {
+    let doc = automerge::SharedAutomerge::default();
+    doc.transact(|tx| tx.put(ROOT, "counter", ScalarValue::counter(0)))
+        .unwrap();
+
+    let readers = (0..4)
+        .map(|_| {
+            let doc = doc.clone();
+            std::thread::spawn(move || {
+                for _ in 0..10 {
+                    let doc = doc.read();
+                    let (value, _) = doc.get(ROOT, "counter").unwrap().unwrap();
+                    assert!(matches!(value, Value::Scalar(_)));
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for _ in 0..10 {
+        doc.transact(|tx| tx.increment(ROOT, "counter", 1)).unwrap();
+    }
+    for reader in readers {
+        reader.join().unwrap();
+    }
+
+    let doc = doc.try_unwrap().unwrap();
+    assert_eq!(
+        doc.get(ROOT, "counter").unwrap().unwrap().0,
+        Value::counter(10)
+    );
+}
+
+#[test]
+fn applying_a_large_batch_of_changes_from_many_actors() {
+    let mut source = AutoCommit::new();
+    let list = source.put_object(ROOT, "list", ObjType::List).unwrap();
+    source.commit();
+    let mut forks = (0..4).map(|_| source.fork()).collect::<Vec<_>>();
+    for (i, fork) in forks.iter_mut().enumerate() {
+        for j in 0..20 {
+            fork.insert(&list, 0, (i * 100 + j) as i64).unwrap();
+            fork.put(ROOT, format!("key{}", i), j as i64).unwrap();
+            fork.commit();
+        }
+    }
+    for fork in forks.iter_mut() {
+        source.merge(fork).unwrap();
+    }
+
+    let mut changes = source
+        .get_changes(&[])
+        .into_iter()
+        .cloned()
+        .collect::<Vec<_>>();
+    changes.reverse();
+    let mut doc = Automerge::new();
+    doc.apply_changes(changes).unwrap();
+
+    assert_eq!(doc.get_heads(), source.get_heads());
+    assert_eq!(doc.length(&list), 80);
+    assert_eq!(doc.hydrate(None), source.hydrate(ROOT, None).unwrap());
+}
+
+#[test]
+fn recorded_patches_can_be_replayed() {
+    use automerge::patches::{Recorder, Replayer};
+
+    let mut doc = AutoCommit::new();
+    let mut recorder = Recorder::new(Vec::new());
+    let mut recorded = Vec::new();
+
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc.put(ROOT, "counter", ScalarValue::counter(1)).unwrap();
+    doc.splice_text(&text, 0, 0, "hello world").unwrap();
+    doc.insert(&list, 0, 1.5).unwrap();
+    doc.insert(&list, 1, vec![1_u8, 2, 3]).unwrap();
+    doc.commit();
+    recorded.push((doc.get_heads(), doc.diff_incremental()));
+
+    doc.mark(
+        &text,
+        Mark::new("bold".to_string(), true, 0, 5),
+        ExpandMark::After,
+    )
+    .unwrap();
+    doc.increment(ROOT, "counter", -3).unwrap();
+    doc.delete(&list, 0).unwrap();
+    doc.delete(ROOT, "counter").unwrap();
+    doc.commit();
+    recorded.push((doc.get_heads(), doc.diff_incremental()));
+
+    for (heads, patches) in &recorded {
+        recorder.record(heads, patches).unwrap();
+    }
+    let mut log = recorder.into_inner();
+
+    let replayed = Replayer::new(log.as_slice())
+        .map(|r| r.map(|r| (r.heads, r.patches)))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(replayed, recorded);
+
+    // A partially written record at the end of the log is ignored
+    let valid_len = log.len() as u64;
+    log.extend_from_slice(&[0x80, 0x01, 0x00]);
+    let mut replayer = Replayer::new(log.as_slice());
+    assert_eq!(replayer.by_ref().filter_map(Result::ok).count(), 2);
+    assert!(replayer.truncated());
+    assert_eq!(replayer.valid_len(), valid_len);
+}
+
+#[test]
+fn text_chars_yields_element_ids_and_inserting_actors() {
+    let mut doc1 = new_doc();
+    let text = doc1.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc1.splice_text(&text, 0, 0, "ab").unwrap();
+    doc1.commit();
+    let heads = doc1.get_heads();
+
+    let mut doc2 = doc1.fork();
+    doc2.splice_text(&text, 1, 0, "é").unwrap();
+    doc1.merge(&mut doc2).unwrap();
+
+    let chars = doc1.text_chars(&text).unwrap().collect::<Vec<_>>();
+    assert_eq!(
+        chars.iter().map(|(c, _, _)| *c).collect::<String>(),
+        doc1.text(&text).unwrap()
+    );
+    let actors = chars
+        .iter()
+        .map(|(_, _, actor)| (*actor).clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        actors,
+        vec![
+            doc1.get_actor().clone(),
+            doc2.get_actor().clone(),
+            doc1.get_actor().clone()
+        ]
+    );
+    let mut index = 0;
+    for (c, id, _) in &chars {
+        let cursor = doc1.get_cursor(&text, index, None).unwrap();
+        assert_eq!(cursor.to_string(), format!("{}", id));
+        index += automerge::TextUnit::Native.len(&c.to_string());
+    }
+
+    let before = doc1
+        .text_chars_at(&text, &heads)
+        .unwrap()
+        .map(|(c, id, _)| (c, id))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        before,
+        vec![
+            (chars[0].0, chars[0].1.clone()),
+            (chars[2].0, chars[2].1.clone())
+        ]
+    );
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn load_mmap_loads_a_saved_document() {
+    let mut doc = new_doc();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello").unwrap();
+    let path = std::env::temp_dir().join(format!("automerge-mmap-{}.automerge", doc.get_actor()));
+    fs::write(&path, doc.save()).unwrap();
+
+    // SAFETY: the file is only written by this test, before it is loaded
+    let loaded = unsafe { Automerge::load_mmap(&path) };
+    fs::remove_file(&path).unwrap();
+    let loaded = loaded.unwrap();
+    assert_eq!(loaded.get_heads(), doc.get_heads());
+    assert_eq!(loaded.text(&text).unwrap(), "hello");
+
+    assert!(matches!(
+        unsafe { Automerge::load_mmap(&path) },
+        Err(AutomergeError::Io(_))
+    ));
+}
+
+#[test]
+fn reads_at_the_current_heads_match_the_current_state() {
+    let mut doc = new_doc();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello world").unwrap();
+    doc.put(ROOT, "counter", ScalarValue::counter(1)).unwrap();
+    doc.increment(ROOT, "counter", 5).unwrap();
+    doc.commit();
+    let heads = doc.get_heads();
+
+    assert_eq!(doc.length_at(&text, &heads), doc.length(&text));
+    assert_eq!(
+        doc.get_at(&text, 4, &heads).unwrap(),
+        doc.get(&text, 4).unwrap()
+    );
+    assert_eq!(
+        doc.get_all_at(ROOT, "counter", &heads).unwrap(),
+        doc.get_all(ROOT, "counter").unwrap()
+    );
+
+    // ops in an open transaction are not part of the heads
+    doc.splice_text(&text, 11, 0, "!").unwrap();
+    doc.increment(ROOT, "counter", 1).unwrap();
+    assert_eq!(doc.length_at(&text, &heads), 11);
+    assert_eq!(doc.length(&text), 12);
+    assert_eq!(
+        doc.get_at(ROOT, "counter", &heads).unwrap().unwrap().0,
+        Value::counter(6)
+    );
+    assert_eq!(
+        doc.get(ROOT, "counter").unwrap().unwrap().0,
+        Value::counter(7)
+    );
+
+    let doc = doc.document();
+    assert_eq!(doc.length_at(&text, &heads), 11);
+}
+
+#[test]
+fn reads_at_historical_heads_match_the_state_at_those_heads() {
+    use automerge::TextUnit;
+
+    let mut doc = new_doc();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    let mut all_heads = vec![doc.get_heads()];
+    let mut other = doc.fork();
+    for i in 0..20_usize {
+        doc.splice_text(&text, (i * 7) % (i + 1), 0, "ab\u{1f600}")
+            .unwrap();
+        doc.insert(&list, i / 2, i as i64).unwrap();
+        if i % 3 == 0 {
+            doc.splice_text(&text, i / 2, 1, "").unwrap();
+            doc.delete(&list, 0).unwrap();
+        }
+        other.splice_text(&text, 0, 0, "\u{e9}").unwrap();
+        other.commit();
+        if i % 5 == 0 {
+            doc.merge(&mut other).unwrap();
+        }
+        doc.commit();
+        all_heads.push(doc.get_heads());
+
+        // every read at the earlier heads agrees with the text and values at those heads, both
+        // before and after the document changes again
+        for heads in &all_heads {
+            let expected = doc.text_at(&text, heads).unwrap();
+            let len = TextUnit::Native.len(&expected);
+            assert_eq!(doc.length_at(&text, heads), len);
+            let mut index = 0;
+            for ch in expected.chars() {
+                let ch = ch.to_string();
+                let (value, _) = doc.get_at(&text, index, heads).unwrap().unwrap();
+                assert_eq!(value.to_str(), Some(ch.as_str()));
+                index += TextUnit::Native.len(&ch);
+            }
+            assert_eq!(doc.get_at(&text, len, heads).unwrap(), None);
+
+            let values = doc
+                .list_range_at(&list, .., heads)
+                .map(|item| item.value.to_i64())
+                .collect::<Vec<_>>();
+            assert_eq!(doc.length_at(&list, heads), values.len());
+            for (index, value) in values.iter().enumerate() {
+                let (found, _) = doc.get_at(&list, index, heads).unwrap().unwrap();
+                assert_eq!(found.to_i64(), *value);
+            }
+        }
+    }
+}
+
+#[test]
+fn incremented_counters_are_visible_at_historical_heads() {
+    let mut doc = AutoCommit::new();
+    doc.put(ROOT, "counter", ScalarValue::counter(1)).unwrap();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc.insert(&list, 0, ScalarValue::counter(10)).unwrap();
+    doc.commit();
+    doc.increment(ROOT, "counter", 5).unwrap();
+    doc.increment(&list, 0, 1).unwrap();
+    doc.commit();
+    let incremented = doc.get_heads();
+    doc.increment(ROOT, "counter", 1).unwrap();
+    doc.put(&list, 0, "replaced").unwrap();
+    doc.commit();
+    let doc = doc.document();
+    assert_ne!(incremented, doc.get_heads());
+
+    assert_eq!(
+        doc.get_at(ROOT, "counter", &incremented)
+            .unwrap()
+            .unwrap()
+            .0,
+        Value::counter(6)
+    );
+    assert_eq!(
+        doc.get_all_at(ROOT, "counter", &incremented).unwrap().len(),
+        1
+    );
+    assert_eq!(
+        doc.get_at(&list, 0, &incremented).unwrap().unwrap().0,
+        Value::counter(11)
+    );
+    assert_eq!(doc.length_at(&list, &incremented), 1);
+    assert_eq!(
+        doc.get(&list, 0).unwrap().unwrap().0,
+        Value::str("replaced")
+    );
+}
+
+#[test]
+fn forks_share_the_history_of_the_original_document() {
+    let mut doc = new_doc();
+    doc.put(ROOT, "key", "value").unwrap();
+    doc.commit();
+    let mut fork = doc.fork();
+    fork.put(ROOT, "other", "value").unwrap();
+    fork.commit();
+
+    let original = doc.get_changes(&[]);
+    let forked = fork.get_changes(&[]);
+    assert_eq!(original.len(), 1);
+    assert_eq!(forked.len(), 2);
+    assert_eq!(
+        original[0].raw_bytes().as_ptr(),
+        forked[0].raw_bytes().as_ptr()
+    );
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn save_and_load_on_a_worker_thread() {
+    use automerge::SharedAutomerge;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    tx.put(ROOT, "key", "value").unwrap();
+    tx.commit();
+
+    let saved = runtime.block_on(doc.save_async()).unwrap();
+    assert_eq!(saved, doc.save());
+    let loaded = runtime.block_on(Automerge::load_async(saved)).unwrap();
+    assert_eq!(loaded.get_heads(), doc.get_heads());
+
+    let shared = SharedAutomerge::new(loaded);
+    assert_eq!(runtime.block_on(shared.save_async()).unwrap(), doc.save());
+
+    let err = runtime.block_on(Automerge::load_async(vec![1, 2, 3]));
+    assert!(err.is_err());
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn saving_on_a_runtime_which_has_shut_down_is_cancelled() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let handle = runtime.handle().clone();
+    runtime.shutdown_background();
+
+    let doc = Automerge::new();
+    let driver = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let result = driver.block_on(async {
+        let _guard = handle.enter();
+        doc.save_async().await
+    });
+    assert!(matches!(result, Err(AutomergeError::Cancelled)));
+}
+
+#[test]
+fn cancelled_operations_return_an_error() {
+    use automerge::{CancellationToken, LoadOptions};
+
+    let mut doc = new_doc();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    for i in 0..5000 {
+        doc.insert(&list, i, i as i64).unwrap();
+    }
+    doc.commit();
+    doc.put(ROOT, "key", "value").unwrap();
+    doc.commit();
+    let saved = doc.save();
+
+    let cancelled = CancellationToken::new();
+    cancelled.clone().cancel();
+    assert!(cancelled.is_cancelled());
+    assert!(matches!(
+        Automerge::load_with_options(&saved, LoadOptions::new().cancellation(cancelled.clone())),
+        Err(AutomergeError::Cancelled)
+    ));
+
+    let changes = doc
+        .get_changes(&[])
+        .into_iter()
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut other = Automerge::new();
+    assert!(matches!(
+        other.apply_changes_cancellable(changes.clone(), &cancelled),
+        Err(AutomergeError::Cancelled)
+    ));
+    assert!(other.get_heads().is_empty());
+
+    let token = CancellationToken::new();
+    other.apply_changes_cancellable(changes, &token).unwrap();
+    assert_eq!(other.get_heads(), doc.get_heads());
+    let loaded =
+        Automerge::load_with_options(&saved, LoadOptions::new().cancellation(token)).unwrap();
+    assert_eq!(loaded.get_heads(), doc.get_heads());
+}
+
+#[test]
+fn range_items_convert_into_key_value_id_triples() {
+    let mut doc = new_doc();
+    let map = doc.put_object(ROOT, "map", ObjType::Map).unwrap();
+    for key in ["a", "b", "c", "d"] {
+        doc.put(&map, key, key).unwrap();
+    }
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    for i in 0..10 {
+        doc.insert(&list, i, i as i64).unwrap();
+    }
+
+    let keys = doc
+        .map_range(&map, "b".to_string().."d".to_string())
+        .map(<(&str, Value<'_>, ObjId)>::from)
+        .map(|(key, value, id)| {
+            assert_eq!(doc.get(&map, key).unwrap(), Some((value, id)));
+            key.to_string()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(keys, vec!["b", "c"]);
+
+    let items = doc
+        .list_range(&list, 3..=5)
+        .map(<(usize, Value<'_>, ObjId)>::from)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        items.iter().map(|(i, _, _)| *i).collect::<Vec<_>>(),
+        vec![3, 4, 5]
+    );
+    for (index, value, id) in items {
+        assert_eq!(doc.get(&list, index).unwrap(), Some((value, id)));
+    }
+}
+
+#[test]
+fn verify_reports_no_problems_for_a_healthy_document() {
+    let mut doc = new_doc();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    for i in 0..20 {
+        doc.insert(&list, i, i as i64).unwrap();
+    }
+    doc.splice_text(&text, 0, 0, "hello world").unwrap();
+    doc.commit();
+    let mut other = doc.fork();
+    other.delete(&list, 3).unwrap();
+    other.splice_text(&text, 0, 5, "goodbye").unwrap();
+    other.commit();
+    doc.put(ROOT, "counter", ScalarValue::counter(1)).unwrap();
+    doc.commit();
+    doc.merge(&mut other).unwrap();
+
+    let loaded = Automerge::load(&doc.save()).unwrap();
+    let report = loaded.verify();
+    assert!(report.is_ok(), "{:?}", report.problems);
+    assert_eq!(report.changes, 3);
+    assert_eq!(report.objects, 3);
+}
+
+#[test]
+fn historical_iterators_match_a_fork_at_the_same_heads() {
+    let mut doc = new_doc();
+    let map = doc.put_object(ROOT, "map", ObjType::Map).unwrap();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    for (i, key) in ["a", "b", "c", "d"].into_iter().enumerate() {
+        doc.put(&map, key, i as i64).unwrap();
+        doc.insert(&list, i, key).unwrap();
+    }
+    let heads = doc.get_heads();
+    doc.delete(&map, "b").unwrap();
+    doc.put(&map, "c", "changed").unwrap();
+    doc.put(&map, "e", 5).unwrap();
+    doc.delete(&list, 0).unwrap();
+    doc.insert(&list, 2, "x").unwrap();
+    doc.commit();
+
+    let old = doc.fork_at(&heads).unwrap();
+    for obj in [&map, &list] {
+        assert_eq!(
+            doc.values_at(obj, &heads).collect::<Vec<_>>(),
+            old.values(obj).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            doc.keys_at(obj, &heads).collect::<Vec<_>>(),
+            old.keys(obj).collect::<Vec<_>>()
+        );
+    }
+    assert_eq!(
+        doc.map_range_at(&map, "b".to_string().., &heads)
+            .collect::<Vec<_>>(),
+        old.map_range(&map, "b".to_string()..).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        doc.map_entries_all_at(&map, &heads).count(),
+        old.map_entries_all(&map).count()
+    );
+    let at = doc
+        .list_range_at(&list, 1..3, &heads)
+        .map(<(usize, Value<'_>, ObjId)>::from)
+        .collect::<Vec<_>>();
+    let forked = old
+        .list_range(&list, 1..3)
+        .map(<(usize, Value<'_>, ObjId)>::from)
+        .collect::<Vec<_>>();
+    assert_eq!(at, forked);
+}
+
+#[test]
+fn text_can_be_indexed_by_grapheme_clusters() {
+    use automerge::patches::TextUnitTranslator;
+    use automerge::TextUnit;
+
+    let mut doc = new_doc();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    let polar_bear = "🐻‍❄️";
+    doc.splice_text_in(
+        &text,
+        0,
+        0,
+        &format!("a{}e\u{301}", polar_bear),
+        TextUnit::Grapheme,
+    )
+    .unwrap();
+    assert_eq!(doc.length_in(&text, TextUnit::Grapheme), 3);
+    assert_eq!(doc.length_in(&text, TextUnit::Native), doc.length(&text));
+    assert_eq!(
+        doc.length(&text),
+        TextUnit::Native.len(&format!("a{}e\u{301}", polar_bear))
+    );
+    let before = doc.get_heads();
+
+    doc.splice_text_in(&text, 2, 1, "!", TextUnit::Grapheme)
+        .unwrap();
+    doc.splice_text_in(&text, 1, 1, "", TextUnit::Grapheme)
+        .unwrap();
+    assert_eq!(doc.text(&text).unwrap(), "a!");
+    assert_eq!(doc.length_in_at(&text, TextUnit::Grapheme, &before), 3);
+    assert!(matches!(
+        doc.splice_text_in(&text, 3, 0, "x", TextUnit::Grapheme),
+        Err(AutomergeError::InvalidIndex(3))
+    ));
+
+    let after = doc.get_heads();
+    let patches = doc.diff(&before, &after);
+    let patches = TextUnitTranslator::new(&doc, &before, TextUnit::Grapheme).translate(patches);
+    let actions = patches.into_iter().map(|p| p.action).collect::<Vec<_>>();
+    assert!(matches!(
+        actions.as_slice(),
+        [
+            PatchAction::DeleteSeq {
+                index: 1,
+                length: 1
+            },
+            PatchAction::SpliceText { index: 1, .. },
+            PatchAction::DeleteSeq {
+                index: 2,
+                length: 1
+            },
+        ]
+    ));
+}
+#[test]
+fn a_document_can_measure_all_of_its_text_in_grapheme_clusters() {
+    use automerge::TextUnit;
+
+    let mut doc = new_doc().with_text_unit(TextUnit::Grapheme);
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "a🐻‍❄️b").unwrap();
+    assert_eq!(doc.length(&text), 3);
+    let before = doc.get_heads();
+
+    doc.splice_text(&text, 2, 0, "e\u{301}").unwrap();
+    assert_eq!(doc.text(&text).unwrap(), "a🐻‍❄️e\u{301}b");
+    assert_eq!(doc.length(&text), 4);
+    assert_eq!(doc.length_at(&text, &before), 3);
+    assert_eq!(
+        doc.length_in(&text, TextUnit::Native),
+        TextUnit::Native.len("a🐻‍❄️e\u{301}b")
+    );
+
+    let after = doc.get_heads();
+    let patches = doc.diff(&before, &after);
+    assert!(matches!(
+        patches[0].action,
+        PatchAction::SpliceText { index: 2, .. }
+    ));
+
+    let mut fork = doc.fork();
+    assert_eq!(fork.text_unit(), TextUnit::Grapheme);
+    fork.set_text_unit(TextUnit::Native);
+    assert_eq!(fork.length(&text), TextUnit::Native.len("a🐻‍❄️e\u{301}b"));
+}
+
+#[test]
+fn features_report_the_capabilities_of_this_build() {
+    let features = automerge::features();
+    assert_eq!(features.compression, &["deflate"]);
+    if cfg!(feature = "utf8-indexing") {
+        assert_eq!(features.text_encoding, automerge::NativeTextEncoding::Utf8);
+    } else {
+        assert_eq!(
+            features.text_encoding,
+            automerge::NativeTextEncoding::CodePoints
+        );
+        assert!(features.supports("text-code-points"));
+    }
+    assert!(features.supports("marks"));
+    assert_eq!(features.supports("mmap"), cfg!(feature = "mmap"));
+    assert_eq!(
+        features.require("moves").unwrap_err().to_string(),
+        "this build of automerge does not support moves"
+    );
+}
+
+#[test]
+fn clone_with_controls_the_actor_and_patch_log() {
+    use automerge::{CloneActor, CloneOptions};
+
+    let mut doc = AutoCommit::new();
+    doc.reset_diff_cursor();
+    doc.put(ROOT, "key", "value").unwrap();
+
+    let mut clone = doc.clone_with(CloneOptions::new());
+    assert_eq!(clone.get_actor(), doc.get_actor());
+    assert_eq!(clone.diff_incremental(), doc.diff_incremental());
+
+    let actor = ActorId::random();
+    let mut clone = doc.clone_with(
+        CloneOptions::new()
+            .actor(CloneActor::Set(actor.clone()))
+            .keep_patch_log(false)
+            .copy_caches(false),
+    );
+    assert_eq!(clone.get_actor(), &actor);
+    assert_eq!(clone.get_heads(), doc.get_heads());
+    clone.put(ROOT, "other", 1).unwrap();
+    assert_eq!(clone.diff_incremental().len(), 2);
+
+    let random = doc
+        .document()
+        .clone_with(CloneOptions::new().actor(CloneActor::Random));
+    assert_ne!(random.get_actor(), doc.get_actor());
+    assert_eq!(random.save(), doc.save());
+}
+
+#[test]
+fn text_can_be_indexed_by_utf16_code_units() {
+    use automerge::patches::TextUnitTranslator;
+    use automerge::TextUnit;
+
+    let mut doc = new_doc();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "a😀b").unwrap();
+    assert_eq!(doc.length(&text), TextUnit::Native.len("a😀b"));
+    assert_eq!(doc.length_utf16(&text), 4);
+    let before = doc.get_heads();
+
+    doc.splice_text_utf16(&text, 3, 1, "c").unwrap();
+    assert_eq!(doc.text(&text).unwrap(), "a😀c");
+    assert!(matches!(
+        doc.splice_text_utf16(&text, 2, 0, "x"),
+        Err(AutomergeError::InvalidIndex(2))
+    ));
+
+    let after = doc.get_heads();
+    let patches = doc.diff(&before, &after);
+    let patches = TextUnitTranslator::new(&doc, &before, TextUnit::Utf16).translate(patches);
+    let actions = patches.into_iter().map(|p| p.action).collect::<Vec<_>>();
+    assert!(matches!(
+        actions.as_slice(),
+        [
+            PatchAction::SpliceText { index: 3, .. },
+            PatchAction::DeleteSeq {
+                index: 4,
+                length: 1
+            },
+        ]
+    ));
+}
+
+#[test]
+fn pending_change_describes_the_change_being_built() {
+    let mut doc = new_doc();
+    assert_eq!(doc.pending_change(), None);
+    doc.put(ROOT, "a", 1).unwrap();
+    let first = doc.commit().unwrap();
+
+    doc.put(ROOT, "b", 2).unwrap();
+    let pending = doc.pending_change().unwrap();
+    assert_eq!(pending.seq, 2);
+    assert_eq!(pending.deps, vec![first]);
+    let hash = doc.commit().unwrap();
+    let change = doc.get_change_by_hash(&hash).unwrap();
+    assert_eq!(&pending.actor, change.actor_id());
+    assert_eq!(pending.seq, change.seq());
+    assert_eq!(pending.start_op, change.start_op());
+
+    let mut doc = Automerge::new();
+    let tx = doc.transaction();
+    let pending = tx.pending_change();
+    let (hash, _) = tx.commit();
+    assert!(hash.is_none());
+    assert_eq!(pending.seq, 1);
+    assert_eq!(pending.start_op.get(), 1);
+}
+
+#[test]
+fn transaction_context_is_passed_to_the_authorizer() {
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, PartialEq)]
+    struct User(&'static str);
+
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    let notes = tx.put_object(ROOT, "notes", ObjType::List).unwrap();
+    tx.commit();
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = seen.clone();
+    let admin_only = notes.clone();
+    doc.set_authorizer(move |write| {
+        let user = write.context::<User>().map(|u| u.0);
+        log.lock().unwrap().push((write.obj.clone(), user));
+        if write.obj == &admin_only && user != Some("admin") {
+            Err(format!("{:?} may not edit notes", user))
+        } else {
+            Ok(())
+        }
+    });
+
+    let mut tx = doc.transaction();
+    tx.set_context(User("guest"));
+    tx.put(ROOT, "title", "hello").unwrap();
+    assert!(matches!(
+        tx.insert(&notes, 0, "note"),
+        Err(AutomergeError::Unauthorized { obj, .. }) if obj == notes
+    ));
+    tx.commit();
+
+    let mut tx = doc.transaction();
+    tx.set_context(User("admin"));
+    tx.insert(&notes, 0, "note").unwrap();
+    tx.commit();
+
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![
+            (ObjId::Root, Some("guest")),
+            (notes.clone(), Some("guest")),
+            (notes.clone(), Some("admin")),
+        ]
+    );
+    assert_eq!(doc.length(&notes), 1);
+    assert_eq!(
+        doc.get(ROOT, "title").unwrap().unwrap().0,
+        Value::str("hello")
+    );
+
+    doc.clear_authorizer();
+    let mut tx = doc.transaction();
+    tx.insert(&notes, 0, "anonymous").unwrap();
+    tx.commit();
+    assert_eq!(doc.length(&notes), 2);
+}
+
+#[test]
+fn push_and_extend_append_to_the_end_of_a_list() {
+    let mut doc = new_doc();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc.push(&list, 1).unwrap();
+    doc.extend(&list, [2.into(), 3.into()]).unwrap();
+    let map = doc.push_object(&list, ObjType::Map).unwrap();
+    doc.put(&map, "key", "value").unwrap();
+
+    // deleted elements at the end of the list do not change where pushed elements go
+    doc.delete(&list, 3).unwrap();
+    doc.delete(&list, 2).unwrap();
+    doc.push(&list, 4).unwrap();
+
+    let values = doc
+        .list_range(&list, ..)
+        .map(|item| item.value.into_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(values, vec![Value::int(1), Value::int(2), Value::int(4)]);
+
+    // concurrent pushes both end up at the end of the list
+    let mut other = doc.fork();
+    doc.push(&list, 5).unwrap();
+    other.push(&list, 6).unwrap();
+    doc.merge(&mut other).unwrap();
+    other.merge(&mut doc).unwrap();
+    let values = doc
+        .list_range(&list, ..)
+        .map(|item| item.value.into_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(values[..3], [Value::int(1), Value::int(2), Value::int(4)]);
+    assert!(values[3..].contains(&Value::int(5)) && values[3..].contains(&Value::int(6)));
+    assert_eq!(doc.get_heads(), other.get_heads());
+    assert_eq!(
+        other
+            .list_range(&list, ..)
+            .map(|item| item.value.into_owned())
+            .collect::<Vec<_>>(),
+        values
+    );
+
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "ab").unwrap();
+    doc.push(&text, "c").unwrap();
+    assert_eq!(doc.text(&text).unwrap(), "abc");
+
+    let mut loaded = Automerge::load(&doc.save()).unwrap();
+    assert_eq!(loaded.length(&list), 5);
+    let mut tx = loaded.transaction();
+    tx.push(&list, 7).unwrap();
+    tx.commit();
+    assert_eq!(loaded.get(&list, 5).unwrap().unwrap().0, Value::int(7));
+
+    assert!(matches!(
+        doc.push(ROOT, 1),
+        Err(AutomergeError::InvalidOp(ObjType::Map))
+    ));
+}
+
+#[test]
+fn patch_filters_select_patches_by_path_and_action() {
+    use automerge::patches::{ActionKind, PatchFilter};
+
+    let mut doc = new_doc();
+    let todos = doc.put_object(ROOT, "todos", ObjType::List).unwrap();
+    let todo = doc.insert_object(&todos, 0, ObjType::Map).unwrap();
+    doc.put(&todo, "title", "shopping").unwrap();
+    doc.put(&todo, "done", false).unwrap();
+    doc.put(ROOT, "owner", "alice").unwrap();
+    let patches = doc.diff_incremental();
+
+    let props = |filter: PatchFilter| {
+        filter
+            .apply(patches.clone())
+            .into_iter()
+            .map(|patch| patch.action.prop())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(props(PatchFilter::new()).len(), patches.len());
+    assert_eq!(
+        props(PatchFilter::path("/todos/*").unwrap()),
+        vec![Some(0.into())]
+    );
+    assert_eq!(
+        props(PatchFilter::path("/todos/*/*").unwrap()),
+        vec![Some("done".into()), Some("title".into())]
+    );
+    assert_eq!(
+        props(PatchFilter::path("/todos/**").unwrap()).len(),
+        props(PatchFilter::path("/todos").unwrap()).len() + 3
+    );
+    assert_eq!(
+        props(
+            PatchFilter::path("/todos/0/title")
+                .unwrap()
+                .or_path("/owner")
+                .unwrap()
+        ),
+        vec![Some("owner".into()), Some("title".into())]
+    );
+    assert!(props(
+        PatchFilter::path("/todos/*")
+            .unwrap()
+            .actions([ActionKind::Put])
+    )
+    .is_empty());
+    // patterns are JSON pointers, and indexes are read as they are by `resolve_pointer`
+    assert!(props(PatchFilter::path("/todos/00").unwrap()).is_empty());
+    assert!(PatchFilter::path("todos").is_err());
+    assert!(PatchFilter::path("/todos/~2").is_err());
+    assert_eq!(
+        props(PatchFilter::new().actions([ActionKind::Insert])),
+        vec![Some(0.into())]
+    );
+}
+
+#[test]
+fn get_or_put_object_converges_on_one_object() {
+    let mut doc1 = new_doc();
+    let mut doc2 = new_doc();
+    let settings1 = doc1
+        .get_or_put_object(ROOT, "settings", ObjType::Map)
+        .unwrap();
+    doc1.put(&settings1, "theme", "dark").unwrap();
+    let settings2 = doc2
+        .get_or_put_object(ROOT, "settings", ObjType::Map)
+        .unwrap();
+    doc2.put(&settings2, "font", "mono").unwrap();
+    assert_eq!(
+        doc1.get_or_put_object(ROOT, "settings", ObjType::Map)
+            .unwrap(),
+        settings1
+    );
+
+    doc1.merge(&mut doc2).unwrap();
+    doc2.merge(&mut doc1).unwrap();
+    let chosen = doc1
+        .get_or_put_object(ROOT, "settings", ObjType::Map)
+        .unwrap();
+    assert_eq!(
+        doc2.get_or_put_object(ROOT, "settings", ObjType::Map)
+            .unwrap(),
+        chosen
+    );
+    assert_eq!(doc1.get(ROOT, "settings").unwrap().unwrap().1, chosen);
+    // neither lookup created another object
+    assert_eq!(doc1.get_all(ROOT, "settings").unwrap().len(), 2);
+
+    // a value of another type is replaced
+    doc1.put(ROOT, "count", 1).unwrap();
+    let list = doc1
+        .get_or_put_object(ROOT, "count", ObjType::List)
+        .unwrap();
+    assert_eq!(doc1.object_type(&list).unwrap(), ObjType::List);
+    assert_eq!(
+        doc1.get_or_put_object(ROOT, "count", ObjType::List)
+            .unwrap(),
+        list
+    );
+}
+
+#[test]
+fn bindings_propagate_changes_in_both_directions() {
+    use automerge::bindings::Bindings;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn cell(bindings: &mut Bindings, path: &str) -> Rc<RefCell<Option<ScalarValue>>> {
+        let cell = Rc::new(RefCell::new(None));
+        let (get, set) = (cell.clone(), cell.clone());
+        bindings
+            .bind(
+                path,
+                move || get.borrow().clone(),
+                move |value| *set.borrow_mut() = value,
+            )
+            .unwrap();
+        cell
+    }
+
+    let mut doc = new_doc();
+    let notes = doc.put_object(ROOT, "notes", ObjType::Text).unwrap();
+    doc.splice_text(&notes, 0, 0, "hello").unwrap();
+    let items = doc.put_object(ROOT, "items", ObjType::List).unwrap();
+    doc.insert(&items, 0, "first").unwrap();
+    doc.insert(&items, 1, "second").unwrap();
+
+    let mut bindings = Bindings::new();
+    let notes_cell = cell(&mut bindings, "/notes");
+    let item_cell = cell(&mut bindings, "/items/1");
+    let nested_cell = cell(&mut bindings, "/settings/theme");
+    assert!(bindings.bind("notes", || None, |_| {}).is_err());
+    assert!(bindings.bind("", || None, |_| {}).is_err());
+    bindings.pull(&doc);
+    assert_eq!(*notes_cell.borrow(), Some("hello".into()));
+    assert_eq!(*item_cell.borrow(), Some("second".into()));
+    assert_eq!(*nested_cell.borrow(), None);
+
+    // nothing changed so nothing is written back
+    assert_eq!(bindings.commit(&mut doc).unwrap(), 0);
+
+    *notes_cell.borrow_mut() = Some("hello world".into());
+    *nested_cell.borrow_mut() = Some("dark".into());
+    assert_eq!(bindings.commit(&mut doc).unwrap(), 2);
+    assert_eq!(doc.text(&notes).unwrap(), "hello world");
+    let (_, settings) = doc.get(ROOT, "settings").unwrap().unwrap();
+    assert_eq!(
+        doc.get(&settings, "theme").unwrap().unwrap().0,
+        Value::from("dark")
+    );
+
+    doc.update_diff_cursor();
+    let mut remote = doc.fork();
+    remote.delete(&items, 0).unwrap();
+    remote.splice_text(&notes, 0, 1, "J").unwrap();
+    doc.merge(&mut remote).unwrap();
+    let patches = doc.diff_incremental();
+    bindings.apply_patches(&doc, &patches);
+    assert_eq!(*notes_cell.borrow(), Some("Jello world".into()));
+    assert_eq!(*item_cell.borrow(), None);
+    assert_eq!(*nested_cell.borrow(), Some("dark".into()));
+}
+
+#[test]
+fn put_path_creates_missing_objects() {
+    let mut doc = new_doc();
+    doc.put_path("config.users[0].name", "alice").unwrap();
+    doc.put_path("config.users[1].name", "bob").unwrap();
+    doc.put_path("config.users[0].name", "carol").unwrap();
+    doc.put_path("config.tags[0]", "admin").unwrap();
+    let quoted = doc
+        .put_object_path("files[\"a.txt\"]", ObjType::Text)
+        .unwrap();
+
+    let (config, _) = doc.get(ROOT, "config").unwrap().unwrap();
+    assert_eq!(config, Value::Object(ObjType::Map));
+    let (_, users) = doc.get_path("config.users").unwrap().unwrap();
+    assert_eq!(doc.object_type(&users).unwrap(), ObjType::List);
+    assert_eq!(doc.length(&users), 2);
+    assert_eq!(
+        doc.get_path("config.users[0].name").unwrap().unwrap().0,
+        Value::from("carol")
+    );
+    assert_eq!(
+        doc.get_path("config.users[1].name").unwrap().unwrap().0,
+        Value::from("bob")
+    );
+    assert_eq!(
+        doc.get_path("config.tags[0]").unwrap().unwrap().0,
+        Value::from("admin")
+    );
+    assert_eq!(doc.get_path("files[\"a.txt\"]").unwrap().unwrap().1, quoted);
+
+    assert!(doc.get_path("config.missing.key").unwrap().is_none());
+    assert!(matches!(
+        doc.put_path("config.users[5].name", "dave"),
+        Err(AutomergeError::InvalidIndex(5))
+    ));
+    assert!(matches!(
+        doc.get_path("config.tags[0].length"),
+        Err(AutomergeError::NotAnObject)
+    ));
+    assert!(matches!(
+        doc.put_path("config..users", 1),
+        Err(AutomergeError::InvalidPath(_))
+    ));
+}
+
+#[test]
+fn merge_read_only_needs_a_shared_reference() {
+    let mut doc1 = new_doc();
+    doc1.put(ROOT, "a", 1).unwrap();
+    let mut doc2 = doc1.fork();
+    doc2.put(ROOT, "b", 2).unwrap();
+    doc2.commit();
+    // the open transaction of `doc2` is not merged
+    doc2.put(ROOT, "c", 3).unwrap();
+
+    let other = &doc2;
+    doc1.merge_read(other).unwrap();
+    assert_eq!(doc1.get(ROOT, "b").unwrap().unwrap().0, Value::int(2));
+    assert!(doc1.get(ROOT, "c").unwrap().is_none());
+
+    let shared = std::sync::Arc::new(doc2.document().clone());
+    let mut doc3 = Automerge::new();
+    doc3.merge_read(&shared).unwrap();
+    assert_eq!(doc3.get_heads(), shared.get_heads());
+    doc1.merge_doc(&shared).unwrap();
+    assert_eq!(doc1.get(ROOT, "c").unwrap().unwrap().0, Value::int(3));
+}
+
+#[test]
+fn jsonpath_queries_select_values_from_the_document() {
+    use automerge::jsonpath::JsonPath;
+
+    let mut doc = new_doc();
+    let todos = doc.put_object(ROOT, "todos", ObjType::List).unwrap();
+    for (i, (title, done, priority)) in [("shop", true, 1), ("cook", false, 3), ("read", false, 2)]
+        .into_iter()
+        .enumerate()
+    {
+        let todo = doc.insert_object(&todos, i, ObjType::Map).unwrap();
+        let text = doc.put_object(&todo, "title", ObjType::Text).unwrap();
+        doc.splice_text(&text, 0, 0, title).unwrap();
+        doc.put(&todo, "done", done).unwrap();
+        doc.put(&todo, "priority", priority).unwrap();
+    }
+    doc.put(ROOT, "owner", "alice").unwrap();
+    let heads = doc.get_heads();
+    let (first, _) = doc.get(&todos, 0).unwrap().unwrap();
+    assert_eq!(first, Value::Object(ObjType::Map));
+    doc.delete(&todos, 0).unwrap();
+
+    let titles = |expr: &str| {
+        let query = JsonPath::parse(expr).unwrap();
+        query
+            .query(&doc)
+            .unwrap()
+            .into_iter()
+            .map(|m| doc.text(&m.id).unwrap())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(titles("$.todos[*].title"), vec!["cook", "read"]);
+    assert_eq!(
+        titles("$.todos[?(@.done==false)].title"),
+        vec!["cook", "read"]
+    );
+    assert_eq!(
+        titles("$.todos[?(@.priority >= 3 || @.title == 'read')].title"),
+        vec!["cook", "read"]
+    );
+    assert_eq!(
+        titles("$.todos[?(!(@.priority > 2) && @.missing != 1)].title"),
+        vec!["read"]
+    );
+    assert_eq!(titles("$.todos[-1].title"), vec!["read"]);
+    assert_eq!(titles("$['todos'][0:1]['title']"), vec!["cook"]);
+    assert_eq!(titles("$..title"), vec!["cook", "read"]);
+    assert!(titles("$.todos[?(@.missing)].title").is_empty());
+
+    let owner = JsonPath::parse("$.owner").unwrap().query(&doc).unwrap();
+    assert_eq!(owner[0].value, Value::from("alice"));
+    assert_eq!(owner[0].path, vec!["owner".into()]);
+    assert_eq!(
+        JsonPath::parse("$").unwrap().query(&doc).unwrap()[0].id,
+        ROOT
+    );
+
+    let done = JsonPath::parse("$.todos[?(@.done==true)]").unwrap();
+    assert!(done.query(&doc).unwrap().is_empty());
+    let matches = done.query_at(&doc, &heads).unwrap();
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].path, vec!["todos".into(), 0.into()]);
+
+    for invalid in ["", "todos", "$.", "$[", "$[?(@.a ==)]", "$['a]", "$.a b"] {
+        assert!(
+            JsonPath::parse(invalid).is_err(),
+            "{:?} should not parse",
+            invalid
+        );
+    }
+}
+
+#[test]
+fn actor_rotation_is_only_linked_when_requested() {
+    let mut doc = new_doc();
+    let original = doc.get_actor().clone();
+    doc.rotate_actor_every(2);
+    for i in 0..5 {
+        doc.put(ROOT, "count", i).unwrap();
+        doc.commit();
+    }
+    let changes = doc.get_changes(&[]);
+    let actors = changes
+        .iter()
+        .map(|c| c.actor_id().clone())
+        .collect::<Vec<_>>();
+    assert_eq!(actors[0], original);
+    assert_eq!(actors[0], actors[1]);
+    assert_ne!(actors[1], actors[2]);
+    assert_eq!(actors[2], actors[3]);
+    assert_ne!(actors[3], actors[4]);
+    assert!(changes.iter().all(|c| c.rotated_from().is_none()));
+
+    let mut doc = new_doc();
+    doc.link_actor_rotations(true);
+    doc.put(ROOT, "a", 1).unwrap();
+    let first = doc.get_actor().clone();
+    doc.rotate_actor();
+    // rotating again before making a change still links back to the last actor with changes
+    doc.rotate_actor();
+    doc.put(ROOT, "b", 2).unwrap();
+    let hash = doc.commit().unwrap();
+    assert_ne!(doc.get_actor(), &first);
+    assert_eq!(
+        doc.get_change_by_hash(&hash).unwrap().rotated_from(),
+        Some(first)
+    );
+
+    doc.put(ROOT, "c", 3).unwrap();
+    let hash = doc.commit().unwrap();
+    assert_eq!(doc.get_change_by_hash(&hash).unwrap().rotated_from(), None);
+
+    let loaded = Automerge::load(&doc.save()).unwrap();
+    let linked = loaded
+        .get_changes(&[])
+        .into_iter()
+        .filter(|c| c.rotated_from().is_some())
+        .count();
+    assert_eq!(linked, 1);
+}
+
+#[test]
+fn json_pointers_address_values() {
+    let mut doc = new_doc();
+    let todos = doc.put_object(ROOT, "todos", ObjType::List).unwrap();
+    let todo = doc.put_object_pointer("/todos/-", ObjType::Map).unwrap();
+    doc.put_pointer("/todos/0/title", "shop").unwrap();
+    doc.put_pointer("/todos/-", "cook").unwrap();
+    doc.put_pointer("/a~1b", 1).unwrap();
+
+    assert_eq!(doc.length(&todos), 2);
+    assert_eq!(
+        doc.resolve_pointer("/todos/0/title").unwrap(),
+        Some((todo.clone(), Prop::Map("title".into())))
+    );
+    assert_eq!(
+        doc.get_pointer("/todos/0/title").unwrap().unwrap().0,
+        Value::from("shop")
+    );
+    assert_eq!(
+        doc.get_pointer("/todos/1").unwrap().unwrap().0,
+        Value::from("cook")
+    );
+    assert_eq!(doc.get(ROOT, "a/b").unwrap().unwrap().0, Value::int(1));
+    assert_eq!(doc.get_pointer("").unwrap().unwrap().1, ROOT);
+
+    assert_eq!(doc.resolve_pointer("/missing/key").unwrap(), None);
+    assert!(matches!(
+        doc.put_pointer("/missing/key", 1),
+        Err(AutomergeError::InvalidPath(_))
+    ));
+    assert!(matches!(
+        doc.get_pointer("/todos/01"),
+        Err(AutomergeError::InvalidPath(_))
+    ));
+    assert!(matches!(
+        doc.get_pointer("todos"),
+        Err(AutomergeError::InvalidPath(_))
+    ));
+
+    doc.delete_pointer("/todos/0").unwrap();
+    assert_eq!(
+        doc.get_pointer("/todos/0").unwrap().unwrap().0,
+        Value::from("cook")
+    );
+}
+
+#[test]
+fn change_deps_only_include_the_minimal_frontier() {
+    let mut doc1 = new_doc();
+    doc1.set_deps_strategy(DepsStrategy::Minimal);
+    doc1.put(ROOT, "a", 1).unwrap();
+    let a1 = doc1.commit().unwrap();
+    let mut doc2 = doc1.fork();
+    doc2.put(ROOT, "b", 1).unwrap();
+    let b1 = doc2.commit().unwrap();
+    doc1.merge(&mut doc2).unwrap();
+
+    // a1 is implied by b1
+    doc1.put(ROOT, "a", 2).unwrap();
+    let a2 = doc1.commit().unwrap();
+    assert_eq!(doc1.get_change_by_hash(&a2).unwrap().deps(), &[b1]);
+
+    doc2.merge(&mut doc1).unwrap();
+    doc2.put(ROOT, "b", 2).unwrap();
+    let b2 = doc2.commit().unwrap();
+    doc1.merge(&mut doc2).unwrap();
+    doc1.set_deps_strategy(DepsStrategy::IncludeOwnPrevious);
+    doc1.put(ROOT, "a", 3).unwrap();
+    let a3 = doc1.commit().unwrap();
+    let mut expected = vec![b2, a2];
+    expected.sort();
+    assert_eq!(doc1.get_change_by_hash(&a3).unwrap().deps(), expected);
+
+    let mut doc = Automerge::load(&doc1.save()).unwrap();
+    doc.set_deps_strategy(DepsStrategy::Minimal);
+    let mut tx = doc.transaction_at(PatchLog::null(), &[a1, b1]);
+    tx.put(ROOT, "c", 1).unwrap();
+    let (c, _) = tx.commit();
+    assert_eq!(doc.get_change_by_hash(&c.unwrap()).unwrap().deps(), &[b1]);
+}
+
+#[test]
+fn json_patch_ops_reproduce_the_document() {
+    use automerge::patches::{to_json_patch, JsonPatchOp};
+
+    fn apply(json: &mut serde_json::Value, op: &JsonPatchOp) {
+        let (parent, last) = op.path().rsplit_once('/').unwrap();
+        let last = last.replace("~1", "/").replace("~0", "~");
+        let target = json.pointer_mut(parent).unwrap();
+        match (op, target) {
+            (JsonPatchOp::Add { value, .. }, serde_json::Value::Array(a)) => {
+                a.insert(last.parse().unwrap(), serde_json::to_value(value).unwrap())
+            }
+            (JsonPatchOp::Replace { value, .. }, serde_json::Value::Array(a)) => {
+                a[last.parse::<usize>().unwrap()] = serde_json::to_value(value).unwrap()
+            }
+            (JsonPatchOp::Add { value, .. } | JsonPatchOp::Replace { value, .. }, target) => {
+                target[last.as_str()] = serde_json::to_value(value).unwrap()
+            }
+            (JsonPatchOp::Remove { .. }, serde_json::Value::Array(a)) => {
+                a.remove(last.parse().unwrap());
+            }
+            (JsonPatchOp::Remove { .. }, serde_json::Value::Object(o)) => {
+                o.remove(&last);
+            }
+            (op, _) => panic!("cannot apply {:?}", op),
+        }
+    }
+
+    let mut doc = new_doc();
+    let mut json = serde_json::json!({});
+    let todos = doc.put_object(ROOT, "todos", ObjType::List).unwrap();
+    let todo = doc.insert_object(&todos, 0, ObjType::Map).unwrap();
+    doc.put(&todo, "title", "shop").unwrap();
+    doc.put(&todo, "done", false).unwrap();
+    doc.put(ROOT, "a/b", ScalarValue::counter(1)).unwrap();
+    let notes = doc.put_object(ROOT, "notes", ObjType::Text).unwrap();
+    doc.splice_text(&notes, 0, 0, "hello").unwrap();
+
+    let patches = doc.diff_incremental();
+    let ops = to_json_patch(&doc, &patches);
+    ops.iter().for_each(|op| apply(&mut json, op));
+    assert_eq!(json, serde_json::to_value(AutoSerde::from(&doc)).unwrap());
+
+    doc.insert(&todos, 1, "cook").unwrap();
+    doc.put(&todo, "done", true).unwrap();
+    doc.increment(ROOT, "a/b", 2).unwrap();
+    doc.increment(ROOT, "a/b", 3).unwrap();
+    doc.splice_text(&notes, 5, 0, " world").unwrap();
+    doc.delete(&todos, 0).unwrap();
+
+    let patches = doc.diff_incremental();
+    let ops = to_json_patch(&doc, &patches);
+    assert!(ops.contains(&JsonPatchOp::Replace {
+        path: "/a~1b".to_string(),
+        value: ScalarValue::counter(6).into()
+    }));
+    ops.iter().for_each(|op| apply(&mut json, op));
+    assert_eq!(json, serde_json::to_value(AutoSerde::from(&doc)).unwrap());
+    let notes = ops.iter().find(|op| op.path() == "/notes").unwrap();
+    assert_eq!(
+        serde_json::to_value(notes).unwrap(),
+        serde_json::json!({"op": "replace", "path": "/notes", "value": "hello world"})
+    );
+
+    // text and counters are replaced at their final paths once the structure is in place
+    let lines = doc.put_object(ROOT, "lines", ObjType::List).unwrap();
+    let line = doc.insert_object(&lines, 0, ObjType::Text).unwrap();
+    doc.splice_text(&line, 0, 0, "first").unwrap();
+    let patches = doc.diff_incremental();
+    to_json_patch(&doc, &patches)
+        .iter()
+        .for_each(|op| apply(&mut json, op));
+
+    doc.splice_text(&line, 5, 0, "!").unwrap();
+    doc.insert(&lines, 0, "zeroth").unwrap();
+    doc.increment(ROOT, "a/b", 1).unwrap();
+    doc.delete(ROOT, "a/b").unwrap();
+    let patches = doc.diff_incremental();
+    let ops = to_json_patch(&doc, &patches);
+    assert_eq!(
+        ops.last(),
+        Some(&JsonPatchOp::Replace {
+            path: "/lines/1".to_string(),
+            value: automerge::hydrate::Value::Text("first!".into())
+        })
+    );
+    assert!(ops
+        .iter()
+        .all(|op| !matches!(op, JsonPatchOp::Replace { path, .. } if path == "/a~1b")));
+    ops.iter().for_each(|op| apply(&mut json, op));
+    assert_eq!(json, serde_json::to_value(AutoSerde::from(&doc)).unwrap());
+}
+
+#[test]
+fn value_refs_borrow_from_the_document() {
+    let mut doc = new_doc();
+    doc.put(ROOT, "name", "automerge").unwrap();
+    doc.put(ROOT, "data", vec![1u8, 2, 3]).unwrap();
+    doc.put(ROOT, "count", ScalarValue::counter(1)).unwrap();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc.insert(&list, 0, "a").unwrap();
+    doc.insert(&list, 1, 2).unwrap();
+    doc.increment(ROOT, "count", 4).unwrap();
+
+    let (name, _) = doc.get_ref(ROOT, "name").unwrap().unwrap();
+    assert_eq!(name.to_str(), Some("automerge"));
+    assert_eq!(name, doc.get(ROOT, "name").unwrap().unwrap().0);
+    let (data, _) = doc.get_ref(ROOT, "data").unwrap().unwrap();
+    assert_eq!(data, ValueRef::Bytes(&[1, 2, 3]));
+    assert_eq!(
+        doc.get_ref(ROOT, "count").unwrap().unwrap().0,
+        ValueRef::Counter(5)
+    );
+    assert_eq!(
+        doc.get_ref(ROOT, "list").unwrap().unwrap(),
+        (ValueRef::Object(ObjType::List), list.clone())
+    );
+    assert_eq!(doc.get_ref(ROOT, "missing").unwrap(), None);
+
+    let values = doc.values_ref(&list).map(|(v, _)| v).collect::<Vec<_>>();
+    assert_eq!(values, vec![ValueRef::Str("a"), ValueRef::Int(2)]);
+    assert_eq!(values[1].to_owned(), Value::int(2));
+
+    let heads = doc.get_heads();
+    doc.increment(ROOT, "count", 1).unwrap();
+    doc.isolate(&heads);
+    assert_eq!(
+        doc.get_ref(ROOT, "count").unwrap().unwrap().0,
+        ValueRef::Counter(5)
+    );
+}
+
+#[test]
+fn changes_round_trip_through_the_javascript_json_format() {
+    let json = serde_json::json!({
+        "actor": "aabbccdd",
+        "seq": 1,
+        "startOp": 1,
+        "time": 1700000000,
+        "message": "init",
+        "deps": [],
+        "ops": [
+            {"action": "makeMap", "obj": "_root", "key": "config", "pred": []},
+            {
+                "action": "set",
+                "obj": "1@aabbccdd",
+                "key": "count",
+                "value": 3,
+                "datatype": "counter",
+                "pred": []
+            }
+        ]
+    });
+    let change: Change = serde_json::from_value::<ExpandedChange>(json.clone())
+        .unwrap()
+        .into();
+
+    let mut doc = Automerge::new();
+    doc.apply_changes([change.clone()]).unwrap();
+    let (_, config) = doc.get(ROOT, "config").unwrap().unwrap();
+    assert_eq!(
+        doc.get(&config, "count").unwrap().unwrap().0,
+        Value::counter(3)
+    );
+
+    let mut encoded = serde_json::to_value(change.decode()).unwrap();
+    assert_eq!(
+        encoded.as_object_mut().unwrap().remove("hash"),
+        Some(serde_json::to_value(change.hash()).unwrap())
+    );
+    assert_eq!(encoded, json);
+}
+
+#[test]
+fn change_graph_is_visualised_as_dot() {
+    let mut doc1 = new_doc();
+    doc1.put(ROOT, "a", 1).unwrap();
+    let a = doc1
+        .commit_with(CommitOptions::default().with_message("say \"hi\""))
+        .unwrap();
+    let mut doc2 = doc1.fork();
+    doc2.put(ROOT, "b", 1).unwrap();
+    let b = doc2.commit().unwrap();
+    doc1.put(ROOT, "c", 1).unwrap();
+    let c = doc1.commit().unwrap();
+    doc1.merge(&mut doc2).unwrap();
+
+    let dot = doc1.visualise_change_graph();
+    assert!(dot.starts_with("digraph changes {"));
+    assert!(dot.contains(&format!("\"{}\" -> \"{}\";", b, a)));
+    assert!(dot.contains(&format!("\"{}\" -> \"{}\";", c, a)));
+    assert!(dot.contains("say \\\"hi\\\""));
+    assert_eq!(dot.matches("peripheries=2").count(), 2);
+    assert_eq!(dot.matches(" -> ").count(), 2);
+}
+
+#[test]
+fn conflicts_are_counted_when_tracked() {
+    let mut doc1 = new_doc();
+    let list = doc1.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc1.insert(&list, 0, "a").unwrap();
+    doc1.commit();
+    let mut doc2 = doc1.fork();
+    doc1.set_track_conflicts(true);
+
+    doc1.put(ROOT, "title", "one").unwrap();
+    doc1.put(&list, 0, "b").unwrap();
+    doc2.put(ROOT, "title", "two").unwrap();
+    doc2.put(&list, 0, "c").unwrap();
+    doc2.put(ROOT, "other", 1).unwrap();
+    doc1.merge(&mut doc2).unwrap();
+
+    let stats = doc1.conflict_stats();
+    assert_eq!(stats.for_key(&ROOT, "title").created, 1);
+    assert_eq!(stats.for_key(&ROOT, "other").created, 0);
+    assert_eq!(stats.for_object(&list).created, 1);
+    assert_eq!(stats.total().created, 2);
+    assert_eq!(stats.total().resolved, 0);
+
+    doc1.put(ROOT, "title", "three").unwrap();
+    doc1.delete(&list, 0).unwrap();
+    let stats = doc1.take_conflict_stats();
+    assert_eq!(stats.for_key(&ROOT, "title").resolved, 1);
+    assert_eq!(stats.for_object(&list).resolved, 1);
+    assert_eq!(stats.keys().count(), 1);
+    assert_eq!(doc1.conflict_stats().total(), Default::default());
+
+    // doc2 sees the conflicts appear and then be resolved as it applies the changes of doc1
+    doc2.set_track_conflicts(true);
+    doc2.merge(&mut doc1).unwrap();
+    let total = doc2.conflict_stats().total();
+    assert_eq!((total.created, total.resolved), (2, 2));
+}
+
+#[test]
+fn history_lists_changes_in_causal_order() {
+    let mut doc1 = new_doc();
+    doc1.put(ROOT, "a", 1).unwrap();
+    let a = doc1.commit().unwrap();
+    let mut doc2 = doc1.fork();
+    doc2.put(ROOT, "b", 1).unwrap();
+    doc2.put(ROOT, "c", 1).unwrap();
+    let b = doc2
+        .commit_with(CommitOptions::default().with_message("b and c"))
+        .unwrap();
+    doc1.put(ROOT, "d", 1).unwrap();
+    let d = doc1.commit().unwrap();
+    doc1.merge(&mut doc2).unwrap();
+    doc1.put(ROOT, "e", 1).unwrap();
+    let e = doc1.commit().unwrap();
+
+    let history = doc1.history().collect::<Vec<_>>();
+    assert_eq!(history.len(), 4);
+    let position = |hash| history.iter().position(|c| c.hash == hash).unwrap();
+    assert_eq!(position(a), 0);
+    assert_eq!(position(e), 3);
+    let summary = &history[position(b)];
+    assert_eq!(summary.message, Some("b and c"));
+    assert_eq!(summary.num_ops, 2);
+    assert_eq!(summary.actor, doc2.get_actor());
+    assert_eq!(summary.deps, &[a]);
+    assert_eq!(doc1.history().next_back().unwrap().hash, e);
+
+    let hashes = doc1.history_at(&[d]).map(|c| c.hash).collect::<Vec<_>>();
+    assert_eq!(hashes, vec![a, d]);
+    assert_eq!(doc1.history_at(&[b, d]).len(), 3);
+}
+
+#[test]
+fn checkout_reads_the_document_at_its_heads() {
+    let mut doc = new_doc();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc.insert(&list, 0, "a").unwrap();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello").unwrap();
+    let before = doc.get_heads();
+    doc.insert(&list, 1, "b").unwrap();
+    doc.splice_text(&text, 5, 0, " world").unwrap();
+    doc.put(ROOT, "later", true).unwrap();
+
+    let checkout = doc.checkout(&before).unwrap();
+    assert_eq!(checkout.heads(), &before[..]);
+    assert_eq!(checkout.length(&list), 1);
+    assert_eq!(checkout.text(&text).unwrap(), "hello");
+    assert_eq!(checkout.get(ROOT, "later").unwrap(), None);
+    assert_eq!(
+        checkout.keys(ROOT).collect::<Vec<_>>(),
+        vec!["list", "text"]
+    );
+    assert_eq!(checkout.values(&list).count(), 1);
+    assert_eq!(
+        checkout.hydrate(ROOT, None).unwrap(),
+        doc.hydrate(ROOT, Some(&before)).unwrap()
+    );
+    let current = doc.get_heads();
+    let checkout = doc.checkout(&current).unwrap();
+    assert_eq!(checkout.text(&text).unwrap(), "hello world");
+    assert_eq!(checkout.text_at(&text, &before).unwrap(), "hello");
+
+    let unknown = automerge::ChangeHash([0; 32]);
+    assert!(matches!(
+        doc.checkout(&[unknown]),
+        Err(AutomergeError::MissingHash(h)) if h == unknown
+    ));
+}
+
+#[test]
+fn list_edits_can_be_reported_as_splices() {
+    let mut doc = new_doc();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    for (i, item) in ["a", "b", "c", "d"].into_iter().enumerate() {
+        doc.insert(&list, i, item).unwrap();
+    }
+    doc.commit();
+    let before = doc.hydrate(ROOT, None).unwrap();
+    doc.update_diff_cursor();
+    doc.set_splice_patches(true);
+
+    doc.delete(&list, 1).unwrap();
+    doc.delete(&list, 1).unwrap();
+    doc.insert(&list, 1, "x").unwrap();
+    doc.insert(&list, 2, "y").unwrap();
+    doc.insert(&list, 2, "z").unwrap();
+    let patches = doc.diff_incremental();
+
+    assert_eq!(patches.len(), 1);
+    let PatchAction::Splice {
+        index,
+        deleted,
+        inserted,
+    } = &patches[0].action
+    else {
+        panic!("expected a splice, got {:?}", patches[0].action);
+    };
+    assert_eq!((*index, *deleted), (1, 2));
+    let inserted = inserted
+        .iter()
+        .map(|(v, _, _)| v.to_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(inserted, vec!["x", "z", "y"]);
+    let mut hydrated = before;
+    hydrated.apply_patches(patches).unwrap();
+    assert_eq!(hydrated, doc.hydrate(ROOT, None).unwrap());
+
+    doc.set_splice_patches(false);
+    doc.delete(&list, 0).unwrap();
+    let patches = doc.diff_incremental();
+    assert!(matches!(
+        patches[0].action,
+        PatchAction::DeleteSeq {
+            index: 0,
+            length: 1
+        }
+    ));
+}
+
+#[test]
+fn splices_keep_whether_inserted_values_are_conflicted() {
+    let mut doc = new_doc();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc.commit();
+    let mut doc1 = doc.fork();
+    doc1.insert(&list, 0, "a").unwrap();
+    doc1.commit();
+    let mut doc2 = doc1.fork();
+    doc1.put(&list, 0, "x").unwrap();
+    doc2.put(&list, 0, "y").unwrap();
+    doc1.merge(&mut doc2).unwrap();
+
+    doc.set_splice_patches(true);
+    let before = doc.get_heads();
+    let mut hydrated = doc.hydrate(ROOT, None).unwrap();
+    doc.merge(&mut doc1).unwrap();
+    let after = doc.get_heads();
+    let patches = doc.diff(&before, &after);
+    assert_eq!(patches.len(), 1);
+    let PatchAction::Splice { inserted, .. } = &patches[0].action else {
+        panic!("expected a splice, got {:?}", patches[0].action);
+    };
+    assert_eq!(inserted.len(), 1);
+    assert!(inserted[0].2);
+
+    hydrated.apply_patches(patches).unwrap();
+    let hydrated_list = hydrated
+        .as_map()
+        .and_then(|root| root.get_mut("list"))
+        .and_then(|list| list.as_list())
+        .unwrap();
+    assert!(hydrated_list.iter().next().unwrap().conflict);
+    assert_eq!(hydrated, doc.hydrate(ROOT, None).unwrap());
+}
+
+#[test]
+fn obj_handles_read_the_same_values_as_the_document() {
+    let mut doc = new_doc();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    for i in 0..20 {
+        doc.insert(&list, i, i as i64).unwrap();
+    }
+    let nested = doc.insert_object(&list, 5, ObjType::Map).unwrap();
+    doc.put(&nested, "name", "five").unwrap();
+    doc.delete(&list, 10).unwrap();
+
+    let handle = doc.handle(&list).unwrap();
+    assert_eq!(handle.id(), &list);
+    assert_eq!(handle.obj_type(), ObjType::List);
+    assert_eq!(handle.len(), doc.length(&list));
+    for index in [0, 1, 2, 3, 10, 11, 4, 5, 6, 19, 20, 21, 0] {
+        assert_eq!(
+            handle.get(index).unwrap(),
+            doc.get(&list, index).unwrap(),
+            "index {}",
+            index
+        );
+    }
+    assert_eq!(
+        handle.iter().collect::<Vec<_>>(),
+        doc.values(&list).collect::<Vec<_>>()
+    );
+    let child = handle.handle(5).unwrap().unwrap();
+    assert_eq!(child.keys().collect::<Vec<_>>(), vec!["name"]);
+    assert_eq!(child.get("name").unwrap().unwrap().0, Value::from("five"));
+    assert!(handle.handle(6).unwrap().is_none());
+
+    let heads = doc.get_heads();
+    doc.put(&nested, "name", "six").unwrap();
+    let checkout = doc.checkout(&heads).unwrap();
+    let old = checkout.handle(&nested).unwrap();
+    assert_eq!(old.get("name").unwrap().unwrap().0, Value::from("five"));
+    assert!(doc.handle(ROOT).unwrap().get("missing").unwrap().is_none());
+}
+
+#[test]
+fn sealed_documents_are_verified() {
+    let mut doc = new_doc();
+    doc.put(ROOT, "contract", "agreed terms").unwrap();
+    let sign = |payload: &[u8]| payload.iter().rev().copied().collect::<Vec<_>>();
+    let check = |payload: &[u8], signature: &[u8]| sign(payload) == signature;
+
+    let sealed = doc.seal_signed(sign);
+    let verified = Automerge::verify_sealed(&sealed).unwrap();
+    assert_eq!(verified.heads(), doc.get_heads());
+    assert_eq!(
+        verified.doc().get(ROOT, "contract").unwrap().unwrap().0,
+        "agreed terms".into()
+    );
+    verified.verify_signature(check).unwrap();
+    assert!(matches!(
+        verified.verify_signature(|_, _| false),
+        Err(automerge::SealError::InvalidSignature)
+    ));
+
+    let unsigned = Automerge::verify_sealed(&doc.seal()).unwrap();
+    assert!(matches!(
+        unsigned.verify_signature(check),
+        Err(automerge::SealError::Unsigned)
+    ));
+
+    let mut tampered = sealed.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert!(matches!(
+        Automerge::verify_sealed(&tampered),
+        Err(automerge::SealError::ContentHashMismatch)
+    ));
+    assert!(matches!(
+        Automerge::verify_sealed(&doc.save()),
+        Err(automerge::SealError::NotSealed)
+    ));
+    assert!(matches!(
+        Automerge::verify_sealed(&sealed[..40]),
+        Err(automerge::SealError::Truncated)
+    ));
+}
+
+#[test]
+fn tags_are_synced_and_can_be_moved() {
+    let mut doc = new_doc();
+    doc.put(ROOT, "version", "1.0").unwrap();
+    let v1 = doc.get_heads();
+    let tag = doc.tag("release");
+    assert_eq!(doc.get_change_by_hash(&tag).unwrap().tag(), Some("release"));
+    assert_eq!(doc.heads_for_tag("release"), Some(v1.clone()));
+    assert_eq!(doc.heads_for_tag("missing"), None);
+
+    let mut other = AutoCommit::load(&doc.save()).unwrap();
+    assert_eq!(other.heads_for_tag("release"), Some(v1.clone()));
+
+    other.put(ROOT, "version", "2.0").unwrap();
+    let v2 = other.get_heads();
+    other.tag("release");
+    doc.merge(&mut other).unwrap();
+    assert_eq!(doc.heads_for_tag("release"), Some(v2.clone()));
+
+    // concurrent tags resolve to the same heads on every peer
+    let mut a = doc.fork();
+    let mut b = doc.fork();
+    a.put(ROOT, "a", 1).unwrap();
+    a.tag("latest");
+    b.put(ROOT, "b", 1).unwrap();
+    b.tag("latest");
+    a.merge(&mut b.clone()).unwrap();
+    b.merge(&mut a.clone()).unwrap();
+    assert_eq!(a.heads_for_tag("latest"), b.heads_for_tag("latest"));
+    assert_eq!(a.tags().len(), 2);
+    assert_eq!(a.tags()["release"], v2);
+}
+
+#[test]
+fn extra_bytes_are_stored_with_changes() {
+    let mut doc = new_doc();
+    doc.link_actor_rotations(true);
+    doc.put(ROOT, "a", 1).unwrap();
+    doc.commit();
+    let first_actor = doc.get_actor().clone();
+    doc.rotate_actor();
+
+    doc.put(ROOT, "b", 2).unwrap();
+    let with_extra = doc
+        .commit_with(CommitOptions::default().with_extra_bytes(b"review:42".to_vec()))
+        .unwrap();
+    doc.put(ROOT, "c", 3).unwrap();
+    let next = doc.commit().unwrap();
+
+    let loaded = AutoCommit::load(&doc.save()).unwrap();
+    let change = loaded.get_change_by_hash(&with_extra).unwrap();
+    // the change records both the application bytes and the rotation
+    assert_eq!(change.app_extra_bytes(), b"review:42");
+    assert_eq!(change.rotated_from(), Some(first_actor));
+    assert!(change.extra_bytes().starts_with(b"automerge:extra:"));
+    let change = loaded.get_change_by_hash(&next).unwrap();
+    assert_eq!(change.rotated_from(), None);
+    assert_eq!(change.app_extra_bytes(), b"");
+
+    // application bytes on their own are stored as they are
+    let mut doc = Automerge::new();
+    let hash = doc.empty_commit(CommitOptions::default().with_extra_bytes(vec![1, 2, 3]));
+    let change = doc.get_change_by_hash(&hash).unwrap();
+    assert_eq!(change.extra_bytes(), &[1, 2, 3]);
+    assert_eq!(change.app_extra_bytes(), &[1, 2, 3]);
+
+    // unless they start with the reserved prefix
+    let reserved = b"automerge:extra:\x02\x04v1.0".to_vec();
+    let hash = doc.empty_commit(CommitOptions::default().with_extra_bytes(reserved.clone()));
+    let change = doc.get_change_by_hash(&hash).unwrap();
+    assert_eq!(change.app_extra_bytes(), reserved.as_slice());
+    assert_eq!(change.tag(), None);
+    assert!(doc.tags().is_empty());
+}
+
+#[test]
+fn change_ops_are_decoded() {
+    use automerge::{
+        ExpandedElementId, ExpandedKey, ExpandedObjectId, ExpandedOpId, ExpandedOpType,
+    };
+
+    let mut doc = new_doc();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc.insert(&list, 0, "a").unwrap();
+    doc.commit();
+    doc.put(&list, 0, "b").unwrap();
+    let hash = doc.commit().unwrap();
+    let actor = doc.get_actor().clone();
+
+    let first = doc.get_changes(&[])[0].iter_ops().collect::<Vec<_>>();
+    assert_eq!(first.len(), 2);
+    assert_eq!(first[0].action, ExpandedOpType::Make(ObjType::List));
+    assert_eq!(
+        first[1].obj,
+        ExpandedObjectId::Id(ExpandedOpId::new(1, &actor))
+    );
+    assert_eq!(first[1].key, ExpandedKey::Seq(ExpandedElementId::Head));
+    assert!(first[1].insert);
+
+    let change = doc.get_change_by_hash(&hash).unwrap();
+    let ops = change.iter_ops().collect::<Vec<_>>();
+    assert_eq!(ops.len(), 1);
+    let insert = ExpandedOpId::new(2, &actor);
+    assert_eq!(
+        ops[0].key,
+        ExpandedKey::Seq(ExpandedElementId::Id(insert.clone()))
+    );
+    assert_eq!(ops[0].pred.iter().collect::<Vec<_>>(), vec![&insert]);
+    assert_eq!(ops[0].primitive_value(), Some("b".into()));
+    assert_eq!(ExpandedChange::from(change).operations, ops);
+}
+
+#[test]
+fn changes_can_be_found_by_actor_and_seq() {
+    let mut doc1 = new_doc();
+    let mut doc2 = new_doc();
+    doc1.put(ROOT, "a", 1).unwrap();
+    let a1 = doc1.commit().unwrap();
+    doc1.put(ROOT, "a", 2).unwrap();
+    let a2 = doc1.commit().unwrap();
+    doc2.put(ROOT, "b", 1).unwrap();
+    let b1 = doc2.commit().unwrap();
+    doc1.merge(&mut doc2).unwrap();
+
+    let actor1 = doc1.get_actor().clone();
+    let actor2 = doc2.get_actor().clone();
+    let hash = |doc: &mut AutoCommit, actor: &ActorId, seq| {
+        doc.get_change_by_actor_seq(actor, seq).map(|c| c.hash())
+    };
+    assert_eq!(hash(&mut doc1, &actor1, 1), Some(a1));
+    assert_eq!(hash(&mut doc1, &actor1, 2), Some(a2));
+    assert_eq!(hash(&mut doc1, &actor2, 1), Some(b1));
+    assert_eq!(hash(&mut doc1, &actor1, 0), None);
+    assert_eq!(hash(&mut doc1, &actor2, 2), None);
+    assert_eq!(hash(&mut doc1, &ActorId::random(), 1), None);
+}
+
+#[test]
+fn actors_can_describe_themselves() {
+    let mut alice = new_doc();
+    alice.put(ROOT, "a", 1).unwrap();
+    alice.set_actor_info(&ActorInfo::default().with_name("Alice"));
+    let mut bob = new_doc();
+    bob.set_actor_info(
+        &ActorInfo::default()
+            .with_name("Bob")
+            .with_device("phone")
+            .with_public_key(vec![1, 2, 3]),
+    );
+    bob.set_actor_info(&ActorInfo::default().with_name("Robert"));
+    alice.merge(&mut bob).unwrap();
+
+    let loaded = AutoCommit::load(&alice.save()).unwrap();
+    assert_eq!(
+        loaded.actor_info(alice.get_actor()),
+        Some(ActorInfo::default().with_name("Alice"))
+    );
+    let infos = loaded.actor_infos();
+    assert_eq!(infos.len(), 2);
+    assert_eq!(infos[bob.get_actor()].name.as_deref(), Some("Robert"));
+    assert_eq!(infos[bob.get_actor()].device, None);
+    assert_eq!(loaded.actor_info(&ActorId::random()), None);
+}
+
+#[test]
+fn merge_reports_changes_objects_and_conflicts() {
+    let mut doc = new_doc();
+    doc.set_track_conflicts(true);
+    let settings = doc.put_object(ROOT, "settings", ObjType::Map).unwrap();
+    doc.put(&settings, "theme", "light").unwrap();
+    doc.commit();
+    let mut alice = doc.fork();
+    doc.put(&settings, "theme", "dark").unwrap();
+    doc.commit();
+    alice.put(&settings, "theme", "solarized").unwrap();
+    alice.commit();
+    alice.put(ROOT, "title", "notes").unwrap();
+    alice.commit();
+
+    let report = doc.merge_with_report(&mut alice).unwrap();
+    assert_eq!(report.changes.len(), 2);
+    assert_eq!(report.changes_by_actor.len(), 1);
+    assert_eq!(report.changes_by_actor[alice.get_actor()], 2);
+    assert_eq!(
+        report.objects.iter().cloned().collect::<Vec<_>>(),
+        vec![ROOT, settings.clone()]
+    );
+    assert_eq!(report.conflicts.for_object(&settings).created, 1);
+    assert_eq!(report.total_conflicts().created, 1);
+    assert_eq!(report.heads, doc.get_heads());
+    // the merge is also counted by conflict tracking
+    assert_eq!(doc.conflict_stats().for_key(&settings, "theme").created, 1);
+
+    assert!(doc.merge_with_report(&mut alice).unwrap().is_empty());
+}
+
+#[test]
+fn conflict_policy_chooses_the_value_read() {
+    let mut doc = new_doc();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc.insert(&list, 0, 1).unwrap();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "abc").unwrap();
+    doc.commit();
+    let mut other = doc.fork();
+    doc.put(ROOT, "n", 3).unwrap();
+    doc.put(&list, 0, 30).unwrap();
+    doc.put(&text, 1, "x").unwrap();
+    other.put(ROOT, "n", 7).unwrap();
+    other.put(&list, 0, 70).unwrap();
+    other.put(&text, 1, "y").unwrap();
+    doc.merge(&mut other).unwrap();
+
+    let read = |doc: &AutoCommit| {
+        (
+            doc.get(ROOT, "n").unwrap().unwrap().0.to_i64().unwrap(),
+            doc.values(&list)
+                .map(|(v, _)| v.to_i64().unwrap())
+                .collect::<Vec<_>>(),
+            doc.text(&text).unwrap(),
+        )
+    };
+    let default = read(&doc);
+    let iter = |doc: &AutoCommit| {
+        doc.handle(&list)
+            .unwrap()
+            .iter()
+            .map(|(v, _)| v.to_i64().unwrap())
+            .collect::<Vec<_>>()
+    };
+    let local = doc.get_actor().clone();
+    doc.set_conflict_policy(automerge::ConflictPolicy::PreferActor(local));
+    // text always reads the default winner, so that it agrees with index based edits
+    assert_eq!(read(&doc), (3, vec![30], default.2.clone()));
+    assert_eq!(iter(&doc), vec![30]);
+    assert_eq!(
+        doc.get(&text, 1).unwrap().unwrap().0.to_str(),
+        default.2.get(1..2)
+    );
+    doc.set_conflict_policy(automerge::ConflictPolicy::MaxNumeric);
+    assert_eq!(read(&doc).0, 7);
+    assert_eq!(read(&doc).1, vec![70]);
+    let (n, _) = doc.get_ref(ROOT, "n").unwrap().unwrap();
+    assert_eq!(n.to_owned().to_i64(), Some(7));
+    assert_eq!(
+        doc.values_ref(&list)
+            .map(|(v, _)| v.to_owned().to_i64().unwrap())
+            .collect::<Vec<_>>(),
+        vec![70]
+    );
+    // ranges walk the op set and keep the default winner
+    assert_eq!(
+        doc.map_range(ROOT, "n".to_string()..="n".to_string())
+            .map(|item| item.value.to_i64().unwrap())
+            .collect::<Vec<_>>(),
+        vec![default.0]
+    );
+    assert_eq!(
+        doc.handle(&list)
+            .unwrap()
+            .get(0)
+            .unwrap()
+            .unwrap()
+            .0
+            .to_i64(),
+        Some(70)
+    );
+    assert_eq!(iter(&doc), vec![70]);
+    doc.set_conflict_policy(automerge::ConflictPolicy::custom(|_| 0));
+    let first = read(&doc);
+    doc.set_conflict_policy(automerge::ConflictPolicy::custom(|values| values.len()));
+    assert_eq!(read(&doc), default);
+    assert_ne!(first, default);
+}
+
+#[test]
+fn conflicts_are_enumerated_across_the_document() {
+    let mut doc = new_doc();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc.insert(&list, 0, "a").unwrap();
+    doc.insert(&list, 1, "b").unwrap();
+    let nested = doc.put_object(ROOT, "nested", ObjType::Map).unwrap();
+    doc.commit();
+    assert_eq!(doc.conflicts().count(), 0);
+
+    let mut other = doc.fork();
+    doc.put(&list, 1, "x").unwrap();
+    doc.put(&nested, "k", 1).unwrap();
+    doc.put(ROOT, "top", 1).unwrap();
+    other.put(&list, 1, "y").unwrap();
+    other.put(&nested, "k", 2).unwrap();
+    other.put(ROOT, "top", 2).unwrap();
+    doc.merge(&mut other).unwrap();
+
+    let found = doc
+        .conflicts()
+        .map(|c| (c.obj, c.prop, c.values.len()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        found,
+        vec![
+            (ROOT, Prop::from("top"), 2),
+            (list.clone(), Prop::Seq(1), 2),
+            (nested.clone(), Prop::from("k"), 2),
+        ]
+    );
+    let in_nested = doc.conflicts_in(&nested).unwrap().collect::<Vec<_>>();
+    assert_eq!(in_nested.len(), 1);
+    assert_eq!(in_nested[0].values, doc.get_all(&nested, "k").unwrap());
+}
+
+#[test]
+fn last_modified_is_the_change_which_wrote_the_value() {
+    let mut alice = new_doc();
+    alice.put(ROOT, "title", "draft").unwrap();
+    alice.put(ROOT, "body", "text").unwrap();
+    alice.commit_with(CommitOptions::default().with_time(1000));
+    let mut bob = alice.fork();
+    bob.put(ROOT, "title", "final").unwrap();
+    let edit = bob
+        .commit_with(
+            CommitOptions::default()
+                .with_time(2000)
+                .with_message("rename"),
+        )
+        .unwrap();
+    alice.merge(&mut bob).unwrap();
+
+    let title = alice.last_modified(ROOT, "title").unwrap().unwrap();
+    assert_eq!(title.hash, edit);
+    assert_eq!(title.timestamp, 2000);
+    assert_eq!(title.message, Some("rename"));
+    let bob_actor = bob.get_actor().clone();
+    let title = alice.last_modified(ROOT, "title").unwrap().unwrap();
+    assert_eq!(title.actor, &bob_actor);
+    let body = alice.last_modified(ROOT, "body").unwrap().unwrap();
+    assert_eq!(body.timestamp, 1000);
+    assert!(alice.last_modified(ROOT, "missing").unwrap().is_none());
+    alice.put(ROOT, "body", "pending").unwrap();
+    let alice_actor = alice.get_actor().clone();
+    let body = alice.last_modified(ROOT, "body").unwrap().unwrap();
+    assert_eq!(body.actor, &alice_actor);
+}
+
+#[test]
+fn table_rows_added_concurrently_keep_their_ids() {
+    use automerge::table::Table;
+
+    let mut doc = new_doc();
+    let table = doc.put_object(ROOT, "people", ObjType::Table).unwrap();
+    doc.commit();
+    let mut other = doc.fork();
+    let (alice, row) = doc.add_row(&table).unwrap();
+    doc.put(&row, "name", "Alice").unwrap();
+    doc.put(&row, "age", 36).unwrap();
+    let (bob, row) = other.add_row(&table).unwrap();
+    other.put(&row, "name", "Bob").unwrap();
+    other.put(&row, "age", "unknown").unwrap();
+    doc.merge(&mut other).unwrap();
+
+    let view = Table::new(&doc, &table).unwrap();
+    assert_eq!(view.len(), 2);
+    assert_eq!(view.get::<String>(&alice, "name").as_deref(), Some("Alice"));
+    assert_eq!(view.get::<String>(&bob, "name").as_deref(), Some("Bob"));
+    let mut ages = view.column::<i64>("age").collect::<Vec<_>>();
+    ages.sort();
+    let mut expected = vec![(alice.clone(), Some(36)), (bob.clone(), None)];
+    expected.sort();
+    assert_eq!(ages, expected);
+    assert!(view.row("missing").is_none());
+
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    assert!(matches!(
+        doc.add_row(&list),
+        Err(AutomergeError::InvalidOp(ObjType::List))
+    ));
+    assert!(Table::new(&doc, ROOT).is_err());
+}
+
+#[test]
+fn bounded_counters_are_clamped_the_same_way_everywhere() {
+    let mut doc1 = new_doc();
+    doc1.put(
+        ROOT,
+        "stock",
+        ScalarValue::bounded_counter(2, Some(0), Some(5)),
+    )
+    .unwrap();
+    doc1.commit();
+    let mut doc2 = doc1.fork();
+    let mut doc3 = doc1.fork();
+    doc1.increment(ROOT, "stock", -2).unwrap();
+    doc2.increment(ROOT, "stock", -1).unwrap();
+    doc3.increment(ROOT, "stock", -1).unwrap();
+    doc3.increment(ROOT, "stock", 1).unwrap();
+
+    let mut a = doc1.fork();
+    a.merge(&mut doc2).unwrap();
+    a.merge(&mut doc3).unwrap();
+    let mut b = doc3.fork();
+    b.merge(&mut doc2).unwrap();
+    let stock = |doc: &AutoCommit| doc.get(ROOT, "stock").unwrap().unwrap().0.to_i64();
+    let before = stock(&b).unwrap();
+    b.update_diff_cursor();
+    b.merge(&mut doc1).unwrap();
+    assert_eq!(stock(&a), stock(&b));
+    assert!(stock(&a).unwrap() >= 0);
+
+    let patched = b
+        .diff_incremental()
+        .into_iter()
+        .map(|patch| match patch.action {
+            PatchAction::Increment { value, .. } => value,
+            other => panic!("unexpected patch {:?}", other),
+        })
+        .sum::<i64>();
+    assert_eq!(before + patched, stock(&b).unwrap());
+
+    a.increment(ROOT, "stock", 10).unwrap();
+    assert_eq!(stock(&a), Some(5));
+
+    let loaded = AutoCommit::load(&a.save()).unwrap();
+    assert_eq!(stock(&loaded), Some(5));
+    let value = loaded.get(ROOT, "stock").unwrap().unwrap().0;
+    match value {
+        Value::Scalar(s) => match s.as_ref() {
+            ScalarValue::Counter(c) => {
+                assert_eq!((c.min(), c.max()), (Some(0), Some(5)));
+            }
+            other => panic!("expected a counter, got {:?}", other),
+        },
+        other => panic!("expected a counter, got {:?}", other),
+    }
+}
+
+#[test]
+fn datetimes_keep_their_precision_and_offset() {
+    use automerge::DateTime;
+    use std::time::{Duration, SystemTime};
+
+    let mut doc = new_doc();
+    let created = DateTime::new(1_700_000_000, 123_456_789)
+        .unwrap()
+        .with_offset(-4 * 3600)
+        .unwrap();
+    let now = SystemTime::now();
+    doc.put(ROOT, "created", created).unwrap();
+    doc.put(ROOT, "seen", DateTime::from(now)).unwrap();
+    doc.put(ROOT, "legacy", ScalarValue::Timestamp(1_700_000_000_123))
+        .unwrap();
+
+    let loaded = AutoCommit::load(&doc.save()).unwrap();
+    let read = |key: &str| {
+        let (value, _) = loaded.get(ROOT, key).unwrap().unwrap();
+        value.to_datetime().unwrap()
+    };
+    assert_eq!(read("created"), created);
+    assert_eq!(read("created").offset(), Some(-4 * 3600));
+    assert_eq!(SystemTime::try_from(read("seen")), Ok(now));
+    assert_eq!(
+        read("legacy"),
+        DateTime::from_unix_millis(1_700_000_000_123)
+    );
+    assert!(read("legacy") < read("created"));
+
+    let mut other = doc.fork();
+    let moved = created.checked_add(Duration::from_micros(1)).unwrap();
+    other.put(ROOT, "created", moved).unwrap();
+    doc.merge(&mut other).unwrap();
+    let (value, _) = doc.get(ROOT, "created").unwrap().unwrap();
+    assert_eq!(value.to_datetime(), Some(moved));
+    assert_eq!(
+        value.to_datetime().unwrap().to_string(),
+        "2023-11-14T18:13:20.123457789-04:00"
+    );
+}
+
+#[test]
+fn big_numbers_are_stored_exactly() {
+    use automerge::{BigInt, Decimal};
+
+    let mut doc = new_doc();
+    let big: BigInt = "123456789012345678901234567890".parse().unwrap();
+    let price: Decimal = "19.990".parse().unwrap();
+    doc.put(ROOT, "big", big.clone()).unwrap();
+    doc.put(ROOT, "price", price.clone()).unwrap();
+    doc.put(ROOT, "small", BigInt::from(-42)).unwrap();
+
+    let loaded = AutoCommit::load(&doc.save()).unwrap();
+    let read = |doc: &AutoCommit, key: &str| doc.get(ROOT, key).unwrap().unwrap().0.into_owned();
+    assert_eq!(read(&loaded, "big"), Value::from(big.clone()));
+    assert_eq!(read(&loaded, "price").to_decimal(), Some(price.clone()));
+    assert_eq!(read(&loaded, "price").to_string(), "Decimal: 19.990");
+    assert_eq!(read(&loaded, "small").to_i64(), Some(-42));
+    assert_eq!(read(&loaded, "big").to_i64(), None);
+
+    // the two values round to the same f64, so only an exact comparison can choose
+    let mut other = doc.fork();
+    let bigger: BigInt = "123456789012345678901234567891".parse().unwrap();
+    assert_eq!(big.to_f64(), bigger.to_f64());
+    other.put(ROOT, "big", bigger.clone()).unwrap();
+    doc.put(ROOT, "big", big).unwrap();
+    doc.merge(&mut other).unwrap();
+    doc.set_conflict_policy(automerge::ConflictPolicy::MaxNumeric);
+    assert_eq!(read(&doc, "big"), Value::from(bigger));
+}
+
+#[test]
+fn chunked_bytes_only_store_new_segments() {
+    use automerge::ChunkedBytes;
+
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let original = (0..2_000_000)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect::<Vec<_>>();
+    let mut edited = original.clone();
+    edited.splice(1_000_000..1_000_000, *b"edit");
+
+    let mut doc = new_doc();
+    doc.put(ROOT, "b_original", ChunkedBytes::new(&original))
+        .unwrap();
+    doc.commit();
+    doc.put(ROOT, "a_edited", ChunkedBytes::new(&edited))
+        .unwrap();
+    doc.commit();
+    let changes = doc
+        .get_changes(&[])
+        .into_iter()
+        .cloned()
+        .collect::<Vec<_>>();
+    assert!(changes[0].raw_bytes().len() > original.len());
+    assert!(changes[1].raw_bytes().len() < original.len() / 4);
+
+    let read = |doc: &AutoCommit, key: &str| {
+        let (value, _) = doc.get(ROOT, key).unwrap().unwrap();
+        value.to_scalar().unwrap().to_chunked().unwrap().to_vec()
+    };
+    // the edited copy comes first in the saved document, so it refers to segments which are
+    // loaded after it
+    let saved = doc.save();
+    assert!(saved.len() < original.len() + original.len() / 4);
+    let loaded = AutoCommit::load(&saved).unwrap();
+    assert_eq!(read(&loaded, "b_original").as_ref(), Some(&original));
+    assert_eq!(read(&loaded, "a_edited").as_ref(), Some(&edited));
+
+    let mut other = AutoCommit::new();
+    other.apply_changes(changes).unwrap();
+    assert_eq!(read(&other, "a_edited").as_ref(), Some(&edited));
+}
+
+#[test]
+fn rolled_back_chunked_bytes_are_not_referred_to() {
+    use automerge::ChunkedBytes;
+
+    let data = (0..100_000)
+        .map(|i| (i * 7 % 251) as u8)
+        .collect::<Vec<_>>();
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    tx.put(ROOT, "data", ChunkedBytes::new(&data)).unwrap();
+    tx.rollback();
+    let mut tx = doc.transaction();
+    tx.put(ROOT, "data", ChunkedBytes::new(&data)).unwrap();
+    tx.commit();
+
+    let change = doc.get_changes(&[]).pop().unwrap();
+    assert!(change.raw_bytes().len() > data.len() / 2);
+    let mut other = Automerge::new();
+    other.apply_changes([change.clone()]).unwrap();
+    let (value, _) = other.get(ROOT, "data").unwrap().unwrap();
+    let chunked = value.to_scalar().unwrap().to_chunked().unwrap();
+    assert_eq!(chunked.to_vec(), Some(data));
+}
+
+#[test]
+fn blob_refs_are_read_from_the_store() {
+    use automerge::blob::{BlobError, BlobHash, BlobRef, BlobStore, MemoryBlobStore};
+    use std::sync::Arc;
+
+    struct Tampered;
+
+    impl BlobStore for Tampered {
+        fn get(&self, _hash: &BlobHash) -> std::io::Result<Option<Vec<u8>>> {
+            Ok(Some(b"the wrong data!".to_vec()))
+        }
+
+        fn put(&self, _hash: &BlobHash, _data: &[u8]) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let data = b"the right data!";
+    let mut doc = new_doc();
+    assert!(matches!(doc.store_blob(data), Err(BlobError::NoStore)));
+
+    let store = Arc::new(MemoryBlobStore::default());
+    doc.set_blob_store(store.clone());
+    let blob = doc.store_blob(data).unwrap().with_hint("text/plain");
+    assert_eq!(store.len(), 1);
+    doc.put(ROOT, "blob", blob.clone()).unwrap();
+
+    let mut loaded = AutoCommit::load(&doc.save()).unwrap();
+    let (value, _) = loaded.get(ROOT, "blob").unwrap().unwrap();
+    assert_eq!(value.to_scalar().unwrap().to_blob_ref(), Some(&blob));
+    assert!(matches!(loaded.read_blob(&blob), Err(BlobError::NoStore)));
+
+    loaded.set_blob_store(store.clone());
+    assert_eq!(loaded.read_blob(&blob).unwrap(), data);
+    let forked = loaded.fork();
+    assert_eq!(forked.read_blob(&blob).unwrap(), data);
+
+    let missing = BlobRef::new(b"never stored");
+    assert!(matches!(loaded.read_blob(&missing), Err(BlobError::Missing(h)) if h == missing.hash));
+
+    loaded.set_blob_store(Arc::new(Tampered));
+    assert!(matches!(loaded.read_blob(&blob), Err(BlobError::HashMismatch(h)) if h == blob.hash));
+
+    loaded.clear_blob_store();
+    assert!(matches!(loaded.read_blob(&blob), Err(BlobError::NoStore)));
+}
+
+#[test]
+fn transactions_which_violate_the_schema_are_rolled_back() {
+    use automerge::schema::{ScalarType, Schema};
+
+    let schema: Schema = Schema::map()
+        .required("title", Schema::text())
+        .optional(
+            "tags",
+            Schema::list(Schema::one_of([
+                ScalarType::Str.into(),
+                Schema::map().required("name", ScalarType::Str).into(),
+            ])),
+        )
+        .no_other_keys()
+        .into();
+    let mut doc = Automerge::new();
+    doc.set_schema(schema.clone());
+
+    let mut tx = doc.transaction();
+    tx.put(ROOT, "tags", "not a list").unwrap();
+    let err = tx.try_commit().unwrap_err();
+    assert_eq!(err.to_string(), "missing required key `title` at the root");
+    assert_eq!(doc.get(ROOT, "tags").unwrap(), None);
+
+    let mut tx = doc.transaction();
+    tx.put_object(ROOT, "title", ObjType::Text).unwrap();
+    let tags = tx.put_object(ROOT, "tags", ObjType::List).unwrap();
+    tx.insert(&tags, 0, "rust").unwrap();
+    let (hash, _) = tx.try_commit().unwrap();
+    assert!(hash.is_some());
+
+    let mut tx = doc.transaction();
+    let tag = tx.insert_object(&tags, 1, ObjType::Map).unwrap();
+    tx.put(&tag, "name", 1).unwrap();
+    let Err(AutomergeError::SchemaViolation(violation)) = tx.try_commit() else {
+        panic!("expected a schema violation");
+    };
+    assert_eq!(
+        violation.path,
+        vec![
+            Prop::Map("tags".into()),
+            Prop::Seq(1),
+            Prop::Map("name".into())
+        ]
+    );
+    assert_eq!(
+        violation.to_string(),
+        "expected a str but found an int at /tags/1/name"
+    );
+    assert_eq!(doc.length(&tags), 1);
+
+    // commit() rolls back too, it just doesn't say why
+    let mut tx = doc.transaction();
+    tx.put(ROOT, "extra", true).unwrap();
+    assert_eq!(tx.commit().0, None);
+    assert_eq!(doc.get(ROOT, "extra").unwrap(), None);
+    assert!(schema.validate(&doc).is_ok());
+
+    let mut auto = AutoCommit::load(&doc.save()).unwrap();
+    auto.set_schema(schema);
+    auto.delete(ROOT, "title").unwrap();
+    assert!(auto.try_commit().is_err());
+    assert!(auto.get(ROOT, "title").unwrap().is_some());
+    auto.insert(&tags, 1, "crdt").unwrap();
+    assert!(auto.try_commit().unwrap().is_some());
+}
+
+#[test]
+fn received_changes_which_violate_the_schema_follow_the_policy() {
+    use automerge::schema::{ScalarType, Schema, SchemaAction};
+    use std::sync::{Arc, Mutex};
+
+    let mut doc = new_doc();
+    let count = doc.put_object(ROOT, "counts", ObjType::Map).unwrap();
+    doc.commit();
+    doc.set_schema(Schema::map().required("counts", Schema::map().other_keys(ScalarType::Int)));
+    let action = Arc::new(Mutex::new(SchemaAction::Reject));
+    let policy_action = action.clone();
+    doc.set_schema_policy(move |_, _| *policy_action.lock().unwrap());
+
+    // forks keep the schema, so the other peer needs to drop it to make a bad change
+    let mut other = doc.fork();
+    other.clear_schema();
+    other.put(&count, "a", 1).unwrap();
+    let good = other.commit().unwrap();
+    other.put(&count, "b", "two").unwrap();
+    let bad = other.commit().unwrap();
+    other.put(&count, "c", 3).unwrap();
+    let after_bad = other.commit().unwrap();
+
+    doc.merge(&mut other).unwrap();
+    assert_eq!(doc.get_heads(), vec![good]);
+    assert_eq!(doc.schema_violations(), &[bad]);
+    assert_eq!(doc.keys(&count).collect::<Vec<_>>(), vec!["a"]);
+    assert_eq!(doc.get_missing_deps(&[]), vec![bad]);
+
+    *action.lock().unwrap() = SchemaAction::Quarantine;
+    doc.merge(&mut other).unwrap();
+    assert!(doc.is_quarantined(&bad));
+    assert_eq!(doc.take_schema_violations(), vec![bad, bad]);
+
+    doc.accept_quarantined(&[bad]).unwrap();
+    assert_eq!(doc.get_heads(), vec![after_bad]);
+    assert_eq!(doc.keys(&count).collect::<Vec<_>>(), vec!["a", "b", "c"]);
+    assert!(doc.schema_violations().is_empty());
+
+    let mut applied = new_doc();
+    applied.set_schema(Schema::map().optional("n", ScalarType::Int));
+    applied.set_schema_policy(|_, _| SchemaAction::Apply);
+    let mut source = applied.fork();
+    source.clear_schema();
+    source.put(ROOT, "n", "one").unwrap();
+    applied.merge(&mut source).unwrap();
+    assert_eq!(applied.schema_violations().len(), 1);
+    assert_eq!(
+        applied.get(ROOT, "n").unwrap().unwrap().0,
+        Value::str("one")
+    );
+}
+
+automerge::typed_record! {
+    struct Author {
+        name: automerge::typed::Text,
+        followers: Option<u64>,
+    }
+}
+
+automerge::typed_record! {
+    struct Blog {
+        title: String,
+        authors: automerge::typed::Map<Author>,
+        tags: automerge::typed::List<String>,
+    }
+}
+
+#[test]
+fn typed_views_read_the_expected_shape() {
+    use automerge::typed::{Shape, TypedDoc, TypedError};
+
+    let mut doc = new_doc();
+    doc.put(ROOT, "title", "notes").unwrap();
+    let authors = doc.put_object(ROOT, "authors", ObjType::Map).unwrap();
+    let alex = doc.put_object(&authors, "alex", ObjType::Map).unwrap();
+    let name = doc.put_object(&alex, "name", ObjType::Text).unwrap();
+    doc.splice_text(&name, 0, 0, "Alex").unwrap();
+    let sam_obj = doc.put_object(&authors, "sam", ObjType::Map).unwrap();
+    doc.put(&sam_obj, "name", "Sam").unwrap();
+    let tags = doc.put_object(ROOT, "tags", ObjType::List).unwrap();
+    doc.insert(&tags, 0, "rust").unwrap();
+    doc.insert(&tags, 1, 7).unwrap();
+
+    let mut typed = TypedDoc::<_, Blog>::new(doc);
+    let root = typed.root().unwrap();
+    assert_eq!(root.get(Blog::title()).unwrap(), "notes");
+
+    let authors = root.get(Blog::authors()).unwrap();
+    assert_eq!(authors.keys().collect::<Vec<_>>(), vec!["alex", "sam"]);
+    let alex = authors.get("alex").unwrap().unwrap();
+    assert_eq!(alex.get(Author::name()).unwrap().text(), "Alex");
+    assert_eq!(alex.get(Author::followers()).unwrap(), None);
+    assert!(authors.get("kim").is_none());
+    let sam = authors.get("sam").unwrap().unwrap();
+    assert_eq!(
+        sam.get(Author::name()).unwrap_err(),
+        TypedError::WrongType {
+            path: vec![
+                Prop::Map("authors".into()),
+                Prop::Map("sam".into()),
+                Prop::Map("name".into())
+            ],
+            expected: "a text object".into(),
+            found: "a str".into(),
+        }
+    );
+
+    let tags = root.get(Blog::tags()).unwrap();
+    let tags = tags.iter().collect::<Vec<_>>();
+    assert_eq!(tags[0], Ok("rust".to_string()));
+    assert_eq!(
+        tags[1].as_ref().unwrap_err().to_string(),
+        "expected a str but found an int at /tags/1"
+    );
+    assert_eq!(
+        root.get(Blog::tags()).unwrap().get(2).unwrap_err(),
+        TypedError::Missing {
+            path: vec![Prop::Map("tags".into()), Prop::Seq(2)]
+        }
+    );
+
+    // the shape gives the schema which the document is checked against
+    let violation = typed.validate().unwrap_err();
+    assert_eq!(
+        violation.to_string(),
+        "expected a text object but found a str at /authors/sam/name"
+    );
+    let doc = typed.doc_mut();
+    doc.put_object(&sam_obj, "name", ObjType::Text).unwrap();
+    doc.commit();
+    doc.set_schema(Blog::schema());
+    doc.put(ROOT, "title", 1).unwrap();
+    assert!(doc.try_commit().is_err());
+    assert_eq!(typed.root().unwrap().get(Blog::title()).unwrap(), "notes");
+
+    let empty = TypedDoc::<_, Blog>::new(new_doc());
+    assert_eq!(
+        empty
+            .root()
+            .unwrap()
+            .get(Blog::title())
+            .unwrap_err()
+            .to_string(),
+        "missing value at /title"
+    );
+}
+
+#[test]
+fn rolling_back_to_a_savepoint_keeps_the_rest_of_the_transaction() {
+    let mut doc = new_doc();
+    doc.put(ROOT, "count", ScalarValue::counter(1)).unwrap();
+    doc.commit();
+    doc.update_diff_cursor();
+
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello").unwrap();
+    let savepoint = doc.savepoint();
+    doc.splice_text(&text, 5, 0, " world").unwrap();
+    doc.increment(ROOT, "count", 2).unwrap();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc.insert(&list, 0, "a").unwrap();
+    let later = doc.savepoint();
+    doc.delete(ROOT, "text").unwrap();
+
+    assert_eq!(doc.rollback_to(savepoint).unwrap(), 10);
+    assert_eq!(
+        doc.rollback_to(later),
+        Err(AutomergeError::InvalidSavepoint)
+    );
+    assert_eq!(doc.text(&text).unwrap(), "hello");
+    assert_eq!(
+        doc.get(ROOT, "count").unwrap().unwrap().0,
+        Value::counter(1)
+    );
+    assert_eq!(doc.get(ROOT, "list").unwrap(), None);
+
+    // the transaction carries on from the savepoint, which can be used again
+    doc.splice_text(&text, 5, 0, "!").unwrap();
+    doc.put(ROOT, "flag", true).unwrap();
+    assert_eq!(doc.rollback_to(savepoint).unwrap(), 2);
+    doc.splice_text(&text, 0, 1, "H").unwrap();
+    doc.commit();
+    assert_eq!(
+        doc.rollback_to(savepoint),
+        Err(AutomergeError::InvalidSavepoint)
+    );
+
+    assert_eq!(doc.text(&text).unwrap(), "Hello");
+    assert_eq!(doc.get_changes(&[]).len(), 2);
+    let loaded = AutoCommit::load(&doc.save()).unwrap();
+    assert_eq!(loaded.text(&text).unwrap(), "Hello");
+    assert_eq!(loaded.keys(ROOT).collect::<Vec<_>>(), vec!["count", "text"]);
+
+    let patches = doc.diff_incremental();
+    assert_eq!(patches.len(), 2);
+    assert_eq!(patches[0].path, vec![]);
+    assert_eq!(
+        patches[1].action,
+        PatchAction::SpliceText {
+            index: 0,
+            value: "Hello".into(),
+            marks: None
+        }
+    );
+
+    let mut doc = automerge::Automerge::new();
+    let mut tx = doc.transaction_log_patches(PatchLog::active(TextRepresentation::String));
+    tx.put(ROOT, "a", 1).unwrap();
+    let savepoint = tx.savepoint();
+    let map = tx.put_object(ROOT, "b", ObjType::Map).unwrap();
+    tx.put(&map, "c", 2).unwrap();
+    assert_eq!(tx.rollback_to(savepoint).unwrap(), 2);
+    let (_, mut patch_log) = tx.commit();
+    assert_eq!(doc.keys(ROOT).collect::<Vec<_>>(), vec!["a"]);
+    assert_eq!(doc.make_patches(&mut patch_log).len(), 1);
+}
+
+#[test]
+fn transact_rolls_back_only_the_failed_closure() {
+    let mut doc = new_doc();
+    doc.put(ROOT, "pending", 1).unwrap();
+    let result: Result<(), AutomergeError> = doc.transact(|doc| {
+        doc.put(ROOT, "a", 1)?;
+        doc.delete(ROOT, "pending")?;
+        Err(AutomergeError::Fail)
+    });
+    assert_eq!(result, Err(AutomergeError::Fail));
+    assert_eq!(doc.keys(ROOT).collect::<Vec<_>>(), vec!["pending"]);
+    assert_eq!(doc.get_changes(&[]).len(), 1);
+
+    let list = doc
+        .transact(|doc| {
+            let list = doc.put_object(ROOT, "list", ObjType::List)?;
+            doc.insert(&list, 0, "x")?;
+            Ok::<_, AutomergeError>(list)
+        })
+        .unwrap();
+    assert_eq!(doc.length(&list), 1);
+    assert_eq!(doc.get_changes(&[]).len(), 2);
+}
+
+#[test]
+fn committed_history_can_be_read_without_committing() {
+    let mut doc = new_doc();
+    doc.put(ROOT, "a", 1).unwrap();
+    doc.commit();
+    doc.put(ROOT, "b", 2).unwrap();
+
+    let shared = &doc;
+    assert_eq!(shared.committed_changes(&[]).len(), 1);
+    assert_eq!(shared.committed_history().count(), 1);
+    let actor = shared.get_actor().clone();
+    assert!(shared.committed_change_by_actor_seq(&actor, 1).is_some());
+    assert!(shared.committed_change_by_actor_seq(&actor, 2).is_none());
+    assert_eq!(shared.get(ROOT, "b").unwrap().unwrap().0, Value::int(2));
+    assert_eq!(doc.pending_ops(), 1);
+
+    assert_eq!(doc.get_changes(&[]).len(), 2);
+}
+
+#[test]
+fn committed_heads_do_not_split_the_pending_change() {
+    let mut doc = new_doc();
+    doc.put(ROOT, "a", 1).unwrap();
+    assert_eq!(doc.committed_heads(), vec![]);
+    doc.put(ROOT, "b", 2).unwrap();
+    let heads = doc.get_heads();
+    assert_eq!(doc.committed_heads(), heads);
+    assert_eq!(doc.get_changes(&[]).len(), 1);
+
+    doc.isolate(&[]);
+    doc.put(ROOT, "c", 3).unwrap();
+    assert_eq!(doc.committed_heads(), vec![]);
+    let isolated = doc.get_heads();
+    assert_eq!(doc.committed_heads(), isolated);
+    doc.integrate();
+    assert_eq!(doc.committed_heads().len(), 2);
+}
+
+#[test]
+fn merge_with_reports_the_patches_of_each_change() {
+    struct Batches(Vec<Vec<Patch>>);
+    impl automerge::patches::PatchObserver for Batches {
+        fn observe(&mut self, patches: &[Patch]) {
+            self.0.push(patches.to_vec());
+        }
+    }
+
+    let mut doc = new_doc();
+    doc.put(ROOT, "a", 1).unwrap();
+    let mut other = doc.fork();
+    other.put(ROOT, "a", 2).unwrap();
+    other.commit();
+    other.put(ROOT, "b", 3).unwrap();
+
+    doc.update_diff_cursor();
+    let mut batches = Batches(Vec::new());
+    let heads = doc.merge_with(&mut other, &mut batches).unwrap();
+    assert_eq!(heads, other.get_heads());
+    assert_eq!(batches.0.len(), 2);
+    assert_eq!(batches.0[0].len(), 1);
+    assert_eq!(batches.0[0][0].path, vec![]);
+    assert!(matches!(
+        &batches.0[0][0].action,
+        PatchAction::PutMap { key, .. } if key == "a"
+    ));
+    assert!(matches!(
+        &batches.0[1][0].action,
+        PatchAction::PutMap { key, .. } if key == "b"
+    ));
+    // the document's own patches are still logged
+    assert_eq!(doc.diff_incremental().len(), 2);
+
+    let mut doc = automerge::Automerge::new();
+    let mut patches = Vec::new();
+    doc.apply_changes_with(other.get_changes(&[]).into_iter().cloned(), &mut patches)
+        .unwrap();
+    assert_eq!(patches.len(), 3);
+}
+
+#[test]
+fn changes_are_applied_as_they_are_read() {
+    struct Trickle<'a>(&'a [u8]);
+    impl std::io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(1);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    let mut source = new_doc();
+    source.put(ROOT, "a", 1).unwrap();
+    let mut stream = source.save();
+    source.put(ROOT, "b", 2).unwrap();
+    source.commit();
+    source.put(ROOT, "c", 3).unwrap();
+    stream.extend(source.save_incremental());
+
+    let mut doc = new_doc();
+    assert_eq!(doc.apply_changes_from(Trickle(&stream)).unwrap(), 3);
+    assert_eq!(doc.get_heads(), source.get_heads());
+
+    // the changes before a truncated chunk are still applied
+    let mut doc = new_doc();
+    let result = doc.apply_changes_from(&stream[..stream.len() - 1]);
+    assert!(matches!(result, Err(AutomergeError::Io(_))));
+    assert_eq!(doc.get_changes(&[]).len(), 2);
+
+    let changes = automerge::ChangeReader::new(stream.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let mut doc = new_doc();
+    doc.apply_changes_iter(changes.into_iter().rev()).unwrap();
+    assert_eq!(doc.get_heads(), source.get_heads());
+}
+
+#[test]
+fn compacted_history_is_decompressed_when_it_is_read() {
+    let mut doc = new_doc();
+    doc.set_compact_history(true);
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    for i in 0..5 {
+        doc.splice_text(&text, 0, 0, &format!("edit {} ", i).repeat(50))
+            .unwrap();
+        doc.commit();
+    }
+    let compacted = doc.resident_history_bytes();
+
+    // saving doesn't need the ops of the history
+    let saved = doc.save();
+    assert_eq!(doc.resident_history_bytes(), compacted);
+    let mut plain = AutoCommit::load(&saved).unwrap();
+    assert!(compacted < plain.resident_history_bytes());
+    assert_eq!(plain.save(), saved);
+    let mut changes = doc
+        .get_changes(&[])
+        .into_iter()
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut expected = plain
+        .get_changes(&[])
+        .into_iter()
+        .cloned()
+        .collect::<Vec<_>>();
+    for (change, expected) in changes.iter_mut().zip(expected.iter_mut()) {
+        assert_eq!(change.hash(), expected.hash());
+        assert_eq!(change.bytes(), expected.bytes());
+        assert_eq!(change.decode(), expected.decode());
+    }
+    // but decoding them does
+    doc.get_changes(&[])[0].decode();
+    assert!(doc.resident_history_bytes() > compacted);
+
+    doc.compact_history();
+    assert_eq!(doc.resident_history_bytes(), compacted);
+    assert_eq!(doc.save(), saved);
+
+    // changes applied from other peers are compacted as well
+    let mut other = AutoCommit::new();
+    other.set_compact_history(true);
+    other.merge(&mut plain).unwrap();
+    assert_eq!(other.resident_history_bytes(), compacted);
+    assert_eq!(other.text(&text).unwrap(), doc.text(&text).unwrap());
+}
+
+#[test]
+fn optree_stats_report_the_shape_of_each_tree() {
+    let mut doc = new_doc();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, &"a".repeat(1000)).unwrap();
+    doc.put(ROOT, "title", "stats").unwrap();
+
+    let stats = doc.optree_stats(None);
+    assert_eq!(stats.objects.len(), 2);
+    assert_eq!(stats.ops, 1002);
+    assert_eq!(stats.objects[0].obj, ROOT);
+    assert_eq!(stats.objects[0].ops, 2);
+    assert_eq!(stats.objects[0].depth, 1);
+
+    let text_stats = &stats.objects[1];
+    assert_eq!(text_stats.obj, text);
+    assert_eq!(text_stats.obj_type, ObjType::Text);
+    assert_eq!(text_stats.ops, 1000);
+    assert!(text_stats.depth > 1);
+    assert_eq!(stats.max_depth, text_stats.depth);
+    assert!(text_stats.leaves < text_stats.nodes);
+    assert!(text_stats.occupancy > 0.0 && text_stats.occupancy <= 1.0);
+
+    let json = serde_json::to_value(&stats).unwrap();
+    assert_eq!(json["objects"][1]["ops"], 1000);
+    assert_eq!(json["objects"][1]["objType"], "text");
+
+    let only_text = doc.optree_stats(Some(vec![text.clone()]));
+    assert_eq!(only_text.objects, vec![text_stats.clone()]);
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn tracing_spans_record_sizes_and_durations() {
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::Layer;
+
+    type Spans = Arc<Mutex<BTreeMap<u64, (&'static str, BTreeMap<String, String>)>>>;
+
+    struct Fields<'a>(&'a mut BTreeMap<String, String>);
+
+    impl<'a> Visit for Fields<'a> {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    struct Recorder(Spans);
+
+    impl<S: tracing::Subscriber> Layer<S> for Recorder {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _: Context<'_, S>) {
+            let mut fields = BTreeMap::new();
+            attrs.record(&mut Fields(&mut fields));
+            let mut spans = self.0.lock().unwrap();
+            spans.insert(id.into_u64(), (attrs.metadata().name(), fields));
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, _: Context<'_, S>) {
+            let mut spans = self.0.lock().unwrap();
+            if let Some((_, fields)) = spans.get_mut(&id.into_u64()) {
+                values.record(&mut Fields(fields));
+            }
+        }
+    }
+
+    let spans = Spans::default();
+    let subscriber = tracing_subscriber::registry().with(Recorder(spans.clone()));
+    tracing::subscriber::with_default(subscriber, || {
+        let mut doc = new_doc();
+        doc.put(ROOT, "key", "value").unwrap();
+        let saved = doc.save();
+        Automerge::load(&saved).unwrap();
+    });
+
+    let spans = spans.lock().unwrap();
+    let find = |name: &str| {
+        spans
+            .values()
+            .find(|(n, _)| *n == name)
+            .map(|(_, fields)| fields.clone())
+            .unwrap_or_else(|| panic!("no {} span", name))
+    };
+
+    let commit = find("commit_impl");
+    assert_eq!(commit["ops"], "1");
+    assert!(commit.contains_key("bytes"));
+    assert!(commit.contains_key("duration_us"));
+
+    let save = find("save_with_options");
+    assert_eq!(save["changes"], "1");
+    assert_eq!(save["ops"], "1");
+    assert!(save.contains_key("bytes"));
+
+    let load = find("load_with_options");
+    assert_eq!(load["changes"], "1");
+    assert_eq!(load["ops"], "1");
+    assert!(load.contains_key("duration_us"));
+}
+
+#[test]
+fn metrics_count_ops_changes_sync_messages_and_saves() {
+    use automerge::metrics::Counters;
+    use automerge::sync::{self, SyncDoc};
+
+    let counters = std::sync::Arc::new(Counters::default());
+    let mut doc = new_doc();
+    doc.set_metrics(counters.clone());
+    doc.put(ROOT, "a", 1).unwrap();
+    doc.put(ROOT, "b", 2).unwrap();
+    doc.commit();
+    assert_eq!(counters.ops_applied(), 2);
+    assert_eq!(counters.changes_received(), 0);
+
+    let mut other = new_doc();
+    other.put(ROOT, "c", 3).unwrap();
+    doc.merge(&mut other).unwrap();
+    assert_eq!(counters.ops_applied(), 3);
+    assert_eq!(counters.changes_received(), 1);
+
+    // changes which are already in the document are not counted again
+    doc.merge(&mut other).unwrap();
+    assert_eq!(counters.changes_received(), 1);
+
+    let saved = doc.save();
+    let incremental = doc.save_after(&[]);
+    assert_eq!(
+        counters.bytes_saved(),
+        (saved.len() + incremental.len()) as u64
+    );
+
+    let mut state = sync::State::new();
+    let message = doc.sync().generate_sync_message(&mut state).unwrap();
+    assert_eq!(counters.sync_messages_generated(), 1);
+    assert_eq!(
+        counters.sync_bytes_generated(),
+        message.changes.iter().map(|c| c.len()).sum::<usize>() as u64
+    );
+
+    doc.clear_metrics();
+    doc.put(ROOT, "d", 4).unwrap();
+    doc.commit();
+    assert_eq!(counters.ops_applied(), 3);
+}
+
+#[cfg(feature = "proptest")]
+proptest::proptest! {
+    #![proptest_config(proptest::prelude::ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn generated_documents_survive_a_save_and_load(doc in automerge::strategies::document(0..30)) {
+        let mut doc = doc;
+        let loaded = Automerge::load(&doc.save()).unwrap();
+        proptest::prop_assert_eq!(loaded.get_heads(), doc.get_heads());
+        proptest::prop_assert_eq!(realize(&loaded), realize(doc.document()));
+    }
+
+    #[test]
+    fn generated_sync_schedules_converge(
+        schedule in automerge::strategies::sync_schedule(2..4, 0..40)
+    ) {
+        let mut network = schedule.run().unwrap();
+        network.settle().unwrap();
+        proptest::prop_assert_eq!(network.in_flight(), 0);
+        let (first, rest) = network.docs.split_first_mut().unwrap();
+        for doc in rest {
+            proptest::prop_assert_eq!(doc.get_heads(), first.get_heads());
+            proptest::prop_assert_eq!(realize(doc.document()), realize(first.document()));
+        }
+    }
+}
+
+#[test]
+fn content_equality_ignores_history_and_reports_differences() {
+    let mut doc1 = new_doc();
+    let text = doc1.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc1.splice_text(&text, 0, 0, "hello").unwrap();
+    let list = doc1.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc1.insert(&list, 0, 1).unwrap();
+    doc1.put(ROOT, "title", "first").unwrap();
+
+    // the same content, written in a different order by another actor
+    let mut doc2 = new_doc();
+    doc2.put(ROOT, "title", "draft").unwrap();
+    let list2 = doc2.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc2.insert(&list2, 0, 1).unwrap();
+    let text2 = doc2.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc2.splice_text(&text2, 0, 0, "help").unwrap();
+    doc2.splice_text(&text2, 3, 1, "lo").unwrap();
+    doc2.put(ROOT, "title", "first").unwrap();
+
+    assert!(doc1.equals_content(&doc2));
+    assert!(doc2.equals_content(&doc1));
+    assert_eq!(doc1.content_differences(&doc2), vec![]);
+    assert!(doc1.document().equals_content(doc2.document()));
+
+    doc2.splice_text(&text2, 5, 0, "!").unwrap();
+    doc2.put_object(&list2, 0, ObjType::Map).unwrap();
+    doc1.put(ROOT, "extra", true).unwrap();
+    assert!(!doc1.equals_content(&doc2));
+    assert_eq!(
+        doc1.content_differences(&doc2),
+        vec![
+            ContentDifference::OnlyInSelf(vec!["extra".into()]),
+            ContentDifference::Changed(vec!["list".into(), 0.into()]),
+            ContentDifference::Changed(vec!["text".into()]),
+        ]
+    );
+    assert_eq!(
+        doc1.content_differences(&doc2)[0].to_string(),
+        "only this document has /extra"
+    );
+}
+
+#[test]
+fn state_hash_depends_only_on_content() {
+    use automerge::marks::{ExpandMark, Mark};
+
+    // the native index of the character at `chars` in "héllo"
+    let at = |chars: usize| {
+        automerge::TextUnit::Native.len(&"héllo".chars().take(chars).collect::<String>())
+    };
+    let mut doc1 = new_doc();
+    doc1.put(ROOT, "count", ScalarValue::counter(1)).unwrap();
+    let text = doc1.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc1.splice_text(&text, 0, 0, "héllo").unwrap();
+    doc1.mark(
+        &text,
+        Mark::new("bold".to_string(), true, at(1), at(3)),
+        ExpandMark::After,
+    )
+    .unwrap();
+    doc1.increment(ROOT, "count", 2).unwrap();
+
+    let mut doc2 = new_doc();
+    doc2.merge(&mut doc1).unwrap();
+    assert_eq!(doc1.state_hash(), doc2.state_hash());
+
+    // a counter which was incremented has the same state as one which started at its value
+    let mut doc3 = new_doc();
+    doc3.put(ROOT, "count", ScalarValue::counter(3)).unwrap();
+    let text3 = doc3.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc3.splice_text(&text3, 0, 0, "héllo").unwrap();
+    doc3.mark(
+        &text3,
+        Mark::new("bold".to_string(), true, at(1), at(3)),
+        ExpandMark::After,
+    )
+    .unwrap();
+    assert!(doc1.equals_content(&doc3));
+    assert_eq!(doc1.state_hash(), doc3.state_hash());
+
+    doc3.unmark(&text3, "bold", at(2), at(3), ExpandMark::After)
+        .unwrap();
+    assert_ne!(doc1.state_hash(), doc3.state_hash());
+
+    // the encoding is fixed, so the hash of a document never changes
+    let mut doc4 = new_doc();
+    doc4.put(ROOT, "a", 1).unwrap();
+    assert_eq!(
+        doc4.state_hash().to_string(),
+        "98b82a3fc0d78b3c9f8c68bcd9956a20ec01715de0540a01a0cd81bd0b393d80"
+    );
+}
+
+#[test]
+fn canonical_saves_do_not_depend_on_the_order_changes_were_applied() {
+    let mut doc1 = new_doc();
+    doc1.put(ROOT, "x", 1).unwrap();
+    let mut doc2 = new_doc();
+    let list = doc2.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc2.insert(&list, 0, "b").unwrap();
+    doc2.commit();
+    doc2.insert(&list, 1, "c").unwrap();
+    let mut doc3 = new_doc();
+    doc3.put(ROOT, "zz", "c").unwrap();
+    doc3.commit();
+    doc3.put(ROOT, "aa", "c").unwrap();
+
+    let mut changes = [&mut doc1, &mut doc2, &mut doc3]
+        .into_iter()
+        .flat_map(|doc| {
+            doc.get_changes(&[])
+                .into_iter()
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut forwards = Automerge::new();
+    forwards.apply_changes(changes.clone()).unwrap();
+    changes.reverse();
+    let mut backwards = Automerge::new();
+    backwards.apply_changes(changes).unwrap();
+
+    assert_ne!(forwards.save(), backwards.save());
+    let canonical = forwards.save_canonical();
+    assert_eq!(canonical, backwards.save_canonical());
+
+    // loading a canonical save and saving it again gives the same bytes
+    let loaded = Automerge::load(&canonical).unwrap();
+    assert_eq!(loaded.get_heads(), forwards.get_heads());
+    assert_eq!(loaded.save_canonical(), canonical);
+    assert_eq!(
+        Automerge::load(&forwards.save()).unwrap().save_canonical(),
+        canonical
+    );
+}
+
+#[test]
+fn save_to_writes_the_same_bytes_as_save() {
+    let mut doc = new_doc();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    for i in 0..200 {
+        doc.splice_text(&text, i, 0, "a").unwrap();
+        doc.put(ROOT, format!("key{}", i % 20), i as i64).unwrap();
+        if i % 50 == 0 {
+            doc.commit();
+        }
+    }
+
+    // some of the columns are big enough to be compressed
+    assert!(doc.save().len() < doc.save_nocompress().len());
+    let mut out = Vec::new();
+    let written = doc.save_to(&mut out).unwrap();
+    assert_eq!(written, out.len());
+    assert_eq!(out, doc.save());
+
+    let options = || automerge::SaveOptions {
+        deflate: false,
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    doc.save_to_with_options(options(), &mut out).unwrap();
+    assert_eq!(out, doc.save_with_options(options()));
+
+    // orphaned changes are written after the document
+    let mut other = doc.fork();
+    other.put(ROOT, "a", 1).unwrap();
+    other.commit();
+    other.put(ROOT, "b", 2).unwrap();
+    let orphan = other.get_last_local_change().unwrap().clone();
+    let mut with_orphan = Automerge::load(&doc.save()).unwrap();
+    with_orphan.apply_changes([orphan]).unwrap();
+    let mut out = Vec::new();
+    with_orphan.save_to(&mut out).unwrap();
+    assert_eq!(out, with_orphan.save());
+
+    // saving to a writer moves the save cursor like save() does
+    doc.put(ROOT, "after", true).unwrap();
+    let mut out = Vec::new();
+    doc.save_to(&mut out).unwrap();
+    assert!(doc.save_incremental().is_empty());
+    assert_eq!(AutoCommit::load(&out).unwrap().get_heads(), doc.get_heads());
+
+    struct Full;
+    impl std::io::Write for Full {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::WriteZero.into())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    assert_eq!(
+        doc.save_to(Full).unwrap_err().kind(),
+        std::io::ErrorKind::WriteZero
+    );
+}
+
+#[test]
+fn load_from_reads_a_document_one_chunk_at_a_time() {
+    let mut doc = new_doc();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    for i in 0..10 {
+        doc.insert(&list, i, i as i64).unwrap();
+    }
+    let mut bytes = doc.save();
+    let heads = doc.get_heads();
+    doc.put(ROOT, "after", "save").unwrap();
+    doc.commit();
+    doc.put(ROOT, "and", "again").unwrap();
+    bytes.extend(doc.save_after(&heads));
+
+    // a reader which returns a few bytes at a time, like a socket
+    struct Trickle<'a>(&'a [u8]);
+    impl std::io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+    let mut loaded = AutoCommit::load_from(Trickle(&bytes)).unwrap();
+    assert_eq!(loaded.get_heads(), doc.get_heads());
+    assert_eq!(realize(&loaded), realize(&doc));
+    assert_eq!(
+        AutoCommit::load_from(std::io::empty()).unwrap().get_heads(),
+        vec![]
+    );
+
+    // changes may arrive before the changes they depend on
+    let heads = doc.get_heads();
+    let changes = doc.get_changes(&[]);
+    let mut reversed = Vec::new();
+    for change in changes.iter().rev() {
+        reversed.extend(change.raw_bytes());
+    }
+    let loaded = Automerge::load_from(reversed.as_slice()).unwrap();
+    assert_eq!(loaded.get_heads(), heads);
+    let mut missing = Vec::new();
+    for change in &changes[1..] {
+        missing.extend(change.raw_bytes());
+    }
+    assert!(matches!(
+        Automerge::load_from(missing.as_slice()),
+        Err(AutomergeError::MissingDeps)
+    ));
+
+    // a truncated stream is an error unless partial loads are allowed
+    let truncated = &bytes[..bytes.len() - 1];
+    assert!(Automerge::load_from(truncated).is_err());
+    let partial = Automerge::load_from_with_options(
+        truncated,
+        automerge::LoadOptions::new().on_partial_load(automerge::OnPartialLoad::Ignore),
+    )
+    .unwrap();
+    assert_eq!(partial.get_heads().len(), 1);
+    assert_eq!(partial.get_heads(), vec![changes[changes.len() - 2].hash()]);
+    let mut corrupt = bytes.clone();
+    corrupt[20] ^= 0xff;
+    assert!(Automerge::load_from(corrupt.as_slice()).is_err());
+}
+
+#[test]
+fn extract_copies_the_content_of_a_map_into_a_new_document() {
+    let mut doc = new_doc();
+    let project = doc.put_object(ROOT, "project", ObjType::Map).unwrap();
+    doc.put(ROOT, "other", "not extracted").unwrap();
+    doc.put(&project, "name", "automerge").unwrap();
+    doc.put(&project, "stars", ScalarValue::counter(10))
+        .unwrap();
+    doc.increment(&project, "stars", 5).unwrap();
+    doc.put(
+        &project,
+        "votes",
+        ScalarValue::bounded_counter(3, Some(0), Some(10)),
+    )
+    .unwrap();
+    doc.increment(&project, "votes", 20).unwrap();
+    let tags = doc.put_object(&project, "tags", ObjType::List).unwrap();
+    doc.insert(&tags, 0, "crdt").unwrap();
+    let nested = doc.insert_object(&tags, 1, ObjType::Map).unwrap();
+    doc.put(&nested, "deep", true).unwrap();
+    let readme = doc.put_object(&project, "readme", ObjType::Text).unwrap();
+    doc.splice_text(&readme, 0, 0, "hello world").unwrap();
+    doc.mark(
+        &readme,
+        Mark::new("bold".to_string(), true, 0, 5),
+        ExpandMark::After,
+    )
+    .unwrap();
+    doc.commit();
+
+    let mut extracted = doc.extract(&project).unwrap();
+    assert_eq!(
+        realize(&extracted),
+        realize_obj(&doc, &project, ObjType::Map)
+    );
+    assert_eq!(
+        extracted.get(ROOT, "stars").unwrap().unwrap().0,
+        Value::counter(15)
+    );
+    match extracted.get(ROOT, "votes").unwrap().unwrap().0 {
+        Value::Scalar(value) => match value.as_ref() {
+            ScalarValue::Counter(votes) => {
+                assert_eq!(i64::from(votes), 10);
+                assert_eq!((votes.min(), votes.max()), (Some(0), Some(10)));
+            }
+            other => panic!("expected a counter, got {:?}", other),
+        },
+        other => panic!("expected a counter, got {:?}", other),
+    }
+    let new_readme = extracted.get(ROOT, "readme").unwrap().unwrap().1;
+    assert_eq!(
+        extracted.marks(&new_readme).unwrap(),
+        doc.marks(&readme).unwrap()
+    );
+    assert_ne!(extracted.get_actor(), doc.get_actor());
+    assert_eq!(extracted.get_changes(&[]).len(), 1);
+
+    assert!(matches!(
+        doc.extract(&tags),
+        Err(AutomergeError::InvalidOp(ObjType::List))
+    ));
+}
+
+#[test]
+fn links_survive_sync_and_are_resolved_by_the_resolver() {
+    use automerge::link::{DocId, LinkError, MemoryLinkResolver};
+    use automerge::schema::{ScalarType, Schema};
+    use automerge::sync;
+    use automerge::typed::Shape;
+    use std::sync::Arc;
+
+    let mut target = new_doc();
+    target.put(ROOT, "title", "linked").unwrap();
+    let id = DocId::random();
+    let resolver = Arc::new(MemoryLinkResolver::default());
+    resolver.insert(id.clone(), target.document().clone());
+
+    let mut doc = new_doc();
+    doc.put(ROOT, "link", id.clone()).unwrap();
+    doc.put(ROOT, "string", id.to_string()).unwrap();
+
+    // sync the link to another peer
+    let mut other = new_doc();
+    let (mut s1, mut s2) = (sync::State::new(), sync::State::new());
+    loop {
+        let m1 = doc.sync().generate_sync_message(&mut s1);
+        if let Some(m) = &m1 {
+            other
+                .sync()
+                .receive_sync_message(&mut s2, m.clone())
+                .unwrap();
+        }
+        let m2 = other.sync().generate_sync_message(&mut s2);
+        if let Some(m) = &m2 {
+            doc.sync().receive_sync_message(&mut s1, m.clone()).unwrap();
+        }
+        if m1.is_none() && m2.is_none() {
+            break;
+        }
+    }
+    let other = AutoCommit::load(&other.save()).unwrap();
+    let (value, _) = other.get(ROOT, "link").unwrap().unwrap();
+    assert_eq!(value.to_scalar().unwrap().to_link(), Some(&id));
+    let (value, _) = other.get(ROOT, "string").unwrap().unwrap();
+    assert!(!value.to_scalar().unwrap().is_link());
+    assert_eq!(id.to_string().parse::<DocId>().unwrap(), id);
+
+    // links are a distinct type in schemas and typed views
+    let schema = |key| Schema::from(Schema::map().required(key, ScalarType::Link));
+    assert!(schema("link").validate(&other).is_ok());
+    assert!(schema("string").validate(&other).is_err());
+    assert_eq!(
+        DocId::read(&other, other.get(ROOT, "link").unwrap(), &[]).unwrap(),
+        id
+    );
+    assert!(DocId::read(&other, other.get(ROOT, "string").unwrap(), &[]).is_err());
+
+    assert!(matches!(
+        other.resolve_link(&id),
+        Err(LinkError::NoResolver)
+    ));
+    doc.set_link_resolver(resolver);
+    let linked = doc.resolve_link(&id).unwrap();
+    assert_eq!(
+        linked.get(ROOT, "title").unwrap().unwrap().0,
+        Value::str("linked")
+    );
+    let forked = doc.fork();
+    assert!(forked.resolve_link(&id).is_ok());
+    let unknown = DocId::random();
+    assert!(matches!(doc.resolve_link(&unknown), Err(LinkError::Missing(d)) if d == unknown));
+    doc.clear_link_resolver();
+    assert!(matches!(doc.resolve_link(&id), Err(LinkError::NoResolver)));
+}
+
+#[test]
+fn repo_syncs_documents_with_peers_and_persists_them() {
+    use automerge::link::DocId;
+    use automerge::repo::{MemoryStorage, Origin, PeerId, Repo, Storage};
+    use std::sync::Arc;
+
+    let storage = Arc::new(MemoryStorage::default());
+    let alice = Repo::with_storage(storage.clone());
+    let bob = Repo::new();
+    let (to_alice, to_bob) = (PeerId::from("alice"), PeerId::from("bob"));
+
+    let notes = alice.create();
+    let events = notes.subscribe();
+    notes
+        .transact(|tx| tx.put(ROOT, "title", "Notes"))
+        .unwrap()
+        .unwrap();
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.origin, Origin::Local);
+    assert_eq!(event.heads, notes.get_heads());
+
+    let id = DocId::random();
+    let mut todo = AutoCommit::new();
+    todo.put(ROOT, "done", false).unwrap();
+    alice.insert(id.clone(), todo.document().clone()).unwrap();
+
+    let sync = |a: &Repo, b: &Repo| loop {
+        let to_b = a.generate_sync_messages(&to_bob);
+        let to_a = b.generate_sync_messages(&to_alice);
+        if to_a.is_empty() && to_b.is_empty() {
+            break;
+        }
+        for (doc, message) in to_b {
+            b.receive_sync_message(&to_alice, &doc, message).unwrap();
+        }
+        for (doc, message) in to_a {
+            a.receive_sync_message(&to_bob, &doc, message).unwrap();
+        }
+    };
+    sync(&alice, &bob);
+    assert_eq!(bob.ids().unwrap(), alice.ids().unwrap());
+    assert_eq!(
+        bob.find(&id).unwrap().unwrap().get_heads(),
+        todo.get_heads()
+    );
+
+    // changes from peers are announced with the peer they came from
+    bob.find(notes.id())
+        .unwrap()
+        .unwrap()
+        .transact(|tx| tx.put(ROOT, "title", "Shared notes"))
+        .unwrap()
+        .unwrap();
+    sync(&alice, &bob);
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.origin, Origin::Peer(to_bob.clone()));
+    assert!(events.try_recv().is_err());
+
+    // a fresh repo on the same storage loads the documents with every change
+    let reloaded = Repo::with_storage(storage.clone());
+    assert_eq!(reloaded.ids().unwrap(), alice.ids().unwrap());
+    let handle = reloaded.find(notes.id()).unwrap().unwrap();
+    assert_eq!(handle.get_heads(), notes.get_heads());
+    assert_eq!(
+        handle.read().get(ROOT, "title").unwrap().unwrap().0,
+        Value::str("Shared notes")
+    );
+
+    handle.compact().unwrap();
+    let compacted = Repo::with_storage(storage.clone());
+    let compacted = compacted.find(notes.id()).unwrap().unwrap();
+    assert_eq!(compacted.get_heads(), notes.get_heads());
+
+    alice.disconnect(&to_bob);
+    assert!(alice.peer_ids().is_empty());
+    assert!(alice.remove(&id).unwrap().is_some());
+    assert!(alice.find(&id).unwrap().is_none());
+    assert_eq!(storage.ids().unwrap(), vec![notes.id().clone()]);
+}
+#[test]
+fn file_storage_lets_one_writer_at_a_time_append_to_a_document() {
+    use automerge::link::DocId;
+    use automerge::repo::{FileStorage, Repo, Storage};
+    use std::io::ErrorKind;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    let dir = std::env::temp_dir().join(format!("automerge-storage-{}", DocId::random()));
+    // two storages on one directory stand in for two processes
+    let first = FileStorage::open(&dir).unwrap();
+    let second = FileStorage::open(&dir).unwrap();
+
+    let repo = Repo::with_storage(Arc::new(first.clone()));
+    let doc = repo.create();
+    doc.transact(|tx| tx.put(ROOT, "title", "Notes"))
+        .unwrap()
+        .unwrap();
+    let id = doc.id().clone();
+    assert_eq!(second.ids().unwrap(), vec![id.clone()]);
+    let reloaded = Repo::with_storage(Arc::new(second.clone()));
+    assert_eq!(
+        reloaded.find(&id).unwrap().unwrap().get_heads(),
+        doc.get_heads()
+    );
+
+    let lock = first.lock(&id).unwrap();
+    assert_eq!(
+        second.append(&id, &[]).unwrap_err().kind(),
+        ErrorKind::WouldBlock
+    );
+    assert_eq!(second.lock(&id).unwrap_err().kind(), ErrorKind::WouldBlock);
+    assert_eq!(first.lock(&id).unwrap_err().kind(), ErrorKind::WouldBlock);
+    doc.transact(|tx| tx.put(ROOT, "title", "Locked notes"))
+        .unwrap()
+        .unwrap();
+    drop(lock);
+    second.append(&id, &[]).unwrap();
+
+    // a lock left behind by a writer which crashed is removed once it is stale
+    let lock_file = dir.join(format!("{}.lock", id));
+    std::fs::write(&lock_file, "crashed").unwrap();
+    assert!(second.append(&id, &[]).is_err());
+    std::fs::File::options()
+        .write(true)
+        .open(&lock_file)
+        .unwrap()
+        .set_modified(SystemTime::now() - Duration::from_secs(60))
+        .unwrap();
+    let held = second.lock(&id).unwrap();
+
+    // and a writer whose lock was taken over can no longer write
+    let impatient = FileStorage::open(&dir)
+        .unwrap()
+        .with_stale_lock_timeout(Duration::ZERO);
+    std::thread::sleep(Duration::from_millis(10));
+    let taken = impatient.lock(&id).unwrap();
+    assert!(second.append(&id, &[]).is_err());
+    drop(held);
+    assert_eq!(
+        first.append(&id, &[]).unwrap_err().kind(),
+        ErrorKind::WouldBlock
+    );
+    drop(taken);
+
+    let reloaded = Repo::with_storage(Arc::new(FileStorage::open(&dir).unwrap()));
+    assert_eq!(
+        reloaded.find(&id).unwrap().unwrap().get_heads(),
+        doc.get_heads()
+    );
+    repo.remove(&id).unwrap();
+    assert!(second.ids().unwrap().is_empty());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+#[test]
+fn repo_documents_can_be_opened_read_only_or_exclusively() {
+    use automerge::link::DocId;
+    use automerge::repo::{FileStorage, Repo, RepoError};
+    use std::sync::Arc;
+
+    let dir = std::env::temp_dir().join(format!("automerge-open-{}", DocId::random()));
+    let repo = Repo::with_storage(Arc::new(FileStorage::open(&dir).unwrap()));
+    let doc = repo.create();
+    let id = doc.id().clone();
+    assert!(repo.open_readonly(&DocId::random()).unwrap().is_none());
+
+    let reader = repo.open_readonly(&id).unwrap().unwrap();
+    let events = reader.subscribe();
+    let writer = repo.open_exclusive(&id).unwrap().unwrap();
+    writer
+        .transact(|tx| tx.put(ROOT, "title", "Notes"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(reader.get_heads(), writer.get_heads());
+    assert!(events.try_recv().is_ok());
+
+    // nothing else can write whilst the exclusive handle exists, here or in another process
+    assert!(matches!(
+        doc.transact(|tx| tx.put(ROOT, "title", "Other")),
+        Err(RepoError::Locked(locked)) if locked == id
+    ));
+    assert!(matches!(
+        repo.open_exclusive(&id),
+        Err(RepoError::Locked(_))
+    ));
+    let elsewhere = Repo::with_storage(Arc::new(FileStorage::open(&dir).unwrap()));
+    assert!(matches!(
+        elsewhere.open_exclusive(&id),
+        Err(RepoError::Locked(_))
+    ));
+    assert!(elsewhere
+        .find(&id)
+        .unwrap()
+        .unwrap()
+        .transact(|tx| tx.put(ROOT, "title", "Other"))
+        .is_err());
+
+    drop(writer);
+    doc.transact(|tx| tx.put(ROOT, "title", "Shared"))
+        .unwrap()
+        .unwrap();
+    let writer = elsewhere.open_exclusive(&id).unwrap().unwrap();
+    writer.compact().unwrap();
+    drop(writer);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "websocket")]
+#[test]
+fn repos_sync_over_websockets() {
+    use automerge::link::DocId;
+    use automerge::repo::websocket::{tungstenite, Connection, Frame, Received};
+    use automerge::repo::Repo;
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::Arc;
+
+    let frame = Frame::Ephemeral {
+        sender_id: "a".into(),
+        target_id: "b".into(),
+        document_id: DocId::random(),
+        session_id: "session".to_string(),
+        count: 3,
+        data: vec![1, 2, 3],
+    };
+    assert_eq!(Frame::decode(&frame.encode()).unwrap(), frame);
+
+    let server_repo = Arc::new(Repo::new());
+    let server_doc = server_repo.create();
+    server_doc
+        .transact(|tx| tx.put(ROOT, "from", "server"))
+        .unwrap()
+        .unwrap();
+    let client_repo = Arc::new(Repo::new());
+    let client_doc = client_repo.create();
+    client_doc
+        .transact(|tx| tx.put(ROOT, "from", "client"))
+        .unwrap()
+        .unwrap();
+    let missing = DocId::random();
+    client_repo
+        .insert(missing.clone(), Automerge::new())
+        .unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (client_id, client_heads) = (client_doc.id().clone(), client_doc.get_heads());
+    let server = std::thread::spawn(move || {
+        let socket = tungstenite::accept(listener.accept().unwrap().0).unwrap();
+        let mut connection =
+            Connection::accept(server_repo.clone(), "server".into(), socket).unwrap();
+        assert_eq!(connection.remote().as_str(), "client");
+        let mut told_done = false;
+        let mut ephemeral = Vec::new();
+        loop {
+            match connection.receive().unwrap() {
+                Received::Closed => break,
+                Received::Ephemeral { data, .. } => ephemeral.push(data),
+                _ => {}
+            }
+            let synced = server_repo
+                .find(&client_id)
+                .unwrap()
+                .map(|doc| doc.get_heads() == client_heads)
+                .unwrap_or(false);
+            if synced && !told_done {
+                connection
+                    .send_ephemeral(&client_id, b"done".to_vec())
+                    .unwrap();
+                told_done = true;
+            }
+        }
+        (server_repo, ephemeral)
+    });
+
+    let stream = TcpStream::connect(addr).unwrap();
+    let (socket, _) = tungstenite::client(format!("ws://{}", addr), stream).unwrap();
+    let mut connection = Connection::connect(client_repo.clone(), "client".into(), socket).unwrap();
+    assert_eq!(connection.remote().as_str(), "server");
+    let (mut done, mut unavailable) = (false, Vec::new());
+    let server_synced = || {
+        client_repo
+            .find(server_doc.id())
+            .unwrap()
+            .map(|doc| doc.get_heads() == server_doc.get_heads())
+            .unwrap_or(false)
+    };
+    while !(done && server_synced()) {
+        match connection.receive().unwrap() {
+            Received::Ephemeral { data, .. } => done = data == b"done",
+            Received::Unavailable(id) => unavailable.push(id),
+            Received::Closed => panic!("the server closed the connection"),
+            _ => {}
+        }
+    }
+    connection
+        .send_ephemeral(client_doc.id(), b"cursor".to_vec())
+        .unwrap();
+    connection.close().unwrap();
+
+    let (server_repo, ephemeral) = server.join().unwrap();
+    assert_eq!(unavailable, vec![missing.clone()]);
+    assert!(server_repo.find(&missing).unwrap().is_none());
+    assert_eq!(ephemeral, vec![b"cursor".to_vec()]);
+    let synced = client_repo.find(server_doc.id()).unwrap().unwrap();
+    assert_eq!(
+        synced.read().get(ROOT, "from").unwrap().unwrap().0,
+        Value::str("server")
+    );
+}
+
+#[cfg(feature = "gossip")]
+#[test]
+fn gossip_applies_changes_in_any_order_and_once() {
+    use automerge::repo::gossip::libp2p::{self, gossipsub};
+    use automerge::repo::gossip::{message_id, Gossip, GossipEvent};
+    use automerge::repo::{Origin, Repo};
+
+    let (source, forwarder) = (libp2p::PeerId::random(), libp2p::PeerId::random());
+    let gossip = Gossip::new("notes");
+    let message = |data: Vec<u8>| gossipsub::Message {
+        source: Some(source),
+        data,
+        sequence_number: None,
+        topic: gossip.topic().hash(),
+    };
+
+    let alice = Repo::new();
+    let doc = alice.create();
+    doc.transact(|tx| tx.put(ROOT, "n", 1)).unwrap().unwrap();
+    let first = message(gossip.changes(&doc).unwrap());
+    let after_first = doc.get_heads();
+    doc.transact(|tx| tx.put(ROOT, "n", 2)).unwrap().unwrap();
+    // nothing has been published, so this has both changes
+    let both = message(gossip.changes(&doc).unwrap());
+    let mut data = vec![doc.id().as_bytes().len() as u8];
+    data.extend_from_slice(doc.id().as_bytes());
+    data.extend(doc.read().save_after(&after_first));
+    let second = message(data);
+    assert_ne!(message_id(&first), message_id(&second));
+    assert_eq!(message_id(&first), message_id(&first.clone()));
+
+    let bob = Repo::new();
+    let events = bob
+        .insert(doc.id().clone(), Automerge::new())
+        .unwrap()
+        .subscribe();
+    match gossip.receive(&bob, &forwarder, &second).unwrap() {
+        GossipEvent::MissingDeps { peer, missing, .. } => {
+            assert_eq!(peer.as_str(), forwarder.to_string());
+            assert_eq!(missing, after_first);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    match gossip.receive(&bob, &forwarder, &first).unwrap() {
+        GossipEvent::Applied(handle) => assert_eq!(handle.get_heads(), doc.get_heads()),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(matches!(
+        gossip.receive(&bob, &forwarder, &both).unwrap(),
+        GossipEvent::Duplicate(_)
+    ));
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.origin, Origin::Peer(forwarder.to_string().into()));
+    assert!(events.try_recv().is_err());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn patches_keep_a_json_mirror_in_sync() {
+    use automerge::patches::{apply_patches, ApplyPatchError};
+
+    let mut doc = AutoCommit::new();
+    let mut mirror = serde_json::json!({});
+    let mut check = |doc: &mut AutoCommit| {
+        apply_patches(&mut mirror, &doc.diff_incremental()).unwrap();
+        assert_eq!(
+            mirror,
+            serde_json::to_value(automerge::AutoSerde::from(&*doc)).unwrap()
+        );
+    };
+
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.put(ROOT, "count", ScalarValue::counter(1)).unwrap();
+    doc.put(ROOT, "bytes", vec![1, 2]).unwrap();
+    doc.insert(&list, 0, 1).unwrap();
+    doc.insert(&list, 1, "two").unwrap();
+    let nested = doc.insert_object(&list, 2, ObjType::Map).unwrap();
+    doc.put(&nested, "deep", true).unwrap();
+    doc.splice_text(&text, 0, 0, "héllo 🐻 world").unwrap();
+    check(&mut doc);
+
+    doc.increment(ROOT, "count", 5).unwrap();
+    doc.delete(&list, 0).unwrap();
+    doc.put(&list, 0, 2.5).unwrap();
+    doc.put(&nested, "deep", ScalarValue::Null).unwrap();
+    doc.splice_text(&text, 6, 2, "🐻‍❄️").unwrap();
+    doc.splice_text(&text, 0, 1, "H").unwrap();
+    doc.delete(ROOT, "bytes").unwrap();
+    check(&mut doc);
+
+    // changes merged from another actor arrive as patches too
+    let mut other = doc.fork();
+    other.splice_text(&text, 0, 0, "> ").unwrap();
+    other.insert(&list, 0, "first").unwrap();
+    doc.merge(&mut other).unwrap();
+    check(&mut doc);
+
+    let mut wrong = serde_json::json!([]);
+    doc.put(ROOT, "more", 1).unwrap();
+    assert!(matches!(
+        apply_patches(&mut wrong, &doc.diff_incremental()),
+        Err(ApplyPatchError::Mismatch { .. })
+    ));
+}
+
+#[test]
+fn coalescing_observer_merges_per_character_patches() {
+    use automerge::patches::CoalescingObserver;
+
+    let mut doc = new_doc();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc.commit();
+    let mut other = doc.fork();
+    for (index, ch) in "hello world".chars().enumerate() {
+        other.splice_text(&text, index, 0, &ch.to_string()).unwrap();
+        other.commit();
+    }
+    // backspace over "world"
+    for index in (6..11).rev() {
+        other.delete(&text, index).unwrap();
+        other.commit();
+    }
+    for value in 0..3 {
+        other.put(ROOT, "status", value).unwrap();
+        other.commit();
+    }
+    other.insert(&list, 0, "a").unwrap();
+    other.insert(&list, 1, "b").unwrap();
+    other.delete(&list, 0).unwrap();
+    other.delete(&list, 0).unwrap();
+    other.commit();
+
+    doc.update_diff_cursor();
+    let mut observer = CoalescingObserver::new();
+    doc.merge_with(&mut other, &mut observer).unwrap();
+    let patches = observer.take_patches();
+    assert_eq!(patches.len(), 2);
+    assert!(matches!(
+        &patches[0].action,
+        PatchAction::SpliceText { index: 0, value, .. } if value.make_string() == "hello "
+    ));
+    assert!(matches!(
+        &patches[1].action,
+        PatchAction::PutMap { key, value: (Value::Scalar(v), _), .. }
+            if key == "status" && v.as_ref() == &ScalarValue::Int(2)
+    ));
+    assert!(observer.is_empty());
+
+    // a put to an object which a later patch changes is not replaced
+    let nested = other.put_object(ROOT, "nested", ObjType::Map).unwrap();
+    other.commit();
+    other.put(&nested, "key", 1).unwrap();
+    other.commit();
+    other.put(ROOT, "nested", 2).unwrap();
+    other.commit();
+    other.splice_text(&text, 0, 0, "a").unwrap();
+    other.commit();
+    other.put(ROOT, "status", 3).unwrap();
+    other.commit();
+    other.splice_text(&text, 1, 0, "b").unwrap();
+    other.commit();
+    let mut patches = Vec::new();
+    doc.merge_with(&mut other, &mut patches).unwrap();
+    assert_eq!(patches.len(), 6);
+    let patches = CoalescingObserver::coalesce(&patches);
+    let actions = patches.iter().map(|p| p.action.kind()).collect::<Vec<_>>();
+    use automerge::patches::ActionKind::*;
+    assert_eq!(actions, vec![Put, Put, Put, SpliceText, Put]);
+}
+
+#[test]
+fn filtered_observer_only_sees_patches_inside_an_object() {
+    use automerge::patches::{FilteredObserver, PatchFilter};
+
+    let mut doc = new_doc();
+    let todos = doc.put_object(ROOT, "todos", ObjType::List).unwrap();
+    let first = doc.insert_object(&todos, 0, ObjType::Map).unwrap();
+    let tags = doc.put_object(&first, "tags", ObjType::List).unwrap();
+    let second = doc.insert_object(&todos, 1, ObjType::Map).unwrap();
+    doc.commit();
+    doc.update_diff_cursor();
+
+    let mut other = doc.fork();
+    other.put(ROOT, "title", "my list").unwrap();
+    other.commit();
+    // moves `first` to index 1, which a path filter would lose track of
+    other.insert(&todos, 0, "new").unwrap();
+    other.commit();
+    other.put(&first, "done", true).unwrap();
+    other.insert(&tags, 0, "home").unwrap();
+    other.commit();
+    other.put(&second, "done", false).unwrap();
+    other.commit();
+
+    let mut observer = FilteredObserver::new(PatchFilter::object(&first), Vec::new());
+    doc.merge_with(&mut other, &mut observer).unwrap();
+    let patches = observer.into_observer();
+    assert_eq!(patches.len(), 2);
+    assert_eq!(patches[0].obj, first);
+    assert_eq!(
+        patches[0].path,
+        vec![(ROOT, "todos".into()), (todos.clone(), 1.into())]
+    );
+    assert_eq!(patches[1].obj, tags);
+
+    // changes which produce no matching patches aren't observed at all
+    struct Calls(usize);
+    impl automerge::patches::PatchObserver for Calls {
+        fn observe(&mut self, _patches: &[Patch]) {
+            self.0 += 1;
+        }
+    }
+    let mut doc = new_doc();
+    let mut calls = FilteredObserver::new(PatchFilter::object(&second).or_object(&tags), Calls(0));
+    doc.merge_with(&mut other, &mut calls).unwrap();
+    assert_eq!(calls.observer().0, 2);
+}
+
+#[test]
+fn composed_observers_each_see_every_patch() {
+    use automerge::patches::{CoalescingObserver, FilteredObserver, PatchFilter, PatchObserver};
+
+    struct Batches(usize);
+    impl PatchObserver for Batches {
+        fn observe(&mut self, _patches: &[Patch]) {
+            self.0 += 1;
+        }
+    }
+
+    let mut doc = new_doc();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.commit();
+    let mut other = doc.fork();
+    for (index, ch) in "abc".chars().enumerate() {
+        other.splice_text(&text, index, 0, &ch.to_string()).unwrap();
+        other.commit();
+    }
+    other.put(ROOT, "title", "letters").unwrap();
+    other.commit();
+
+    let mut all = Vec::new();
+    let mut observers = (
+        &mut all,
+        CoalescingObserver::new(),
+        FilteredObserver::new(PatchFilter::path("/title").unwrap(), Vec::new()),
+        Batches(0),
+    );
+    doc.merge_with(&mut other, &mut observers).unwrap();
+    let (_, mut coalesced, title, batches) = observers;
+    assert_eq!(all.len(), 4);
+    assert_eq!(coalesced.take_patches().len(), 2);
+    assert_eq!(title.into_observer().len(), 1);
+    assert_eq!(batches.0, 4);
+
+    // observers chosen at runtime
+    let mut doc = new_doc();
+    let mut counters = vec![Batches(0), Batches(0)];
+    doc.merge_with(&mut other, counters.as_mut_slice()).unwrap();
+    assert!(counters.iter().all(|counter| counter.0 == 5));
+}
+
+/// Give `target` every change in `source`, either with `load_incremental()` or with the sync
+/// protocol
+///
+/// When `target` quarantines a change the peers never reach the same heads, so they keep
+/// exchanging messages. The number of rounds is limited so that this still returns.
+fn deliver(source: &mut AutoCommit, target: &mut AutoCommit, via_sync: bool) {
+    use automerge::sync;
+
+    if !via_sync {
+        target.load_incremental(&source.save()).unwrap();
+        return;
+    }
+    let (mut s1, mut s2) = (sync::State::new(), sync::State::new());
+    for _ in 0..10 {
+        let m1 = source.sync().generate_sync_message(&mut s1);
+        if let Some(m) = &m1 {
+            target
+                .sync()
+                .receive_sync_message(&mut s2, m.clone())
+                .unwrap();
+        }
+        let m2 = target.sync().generate_sync_message(&mut s2);
+        if let Some(m) = &m2 {
+            source
+                .sync()
+                .receive_sync_message(&mut s1, m.clone())
+                .unwrap();
+        }
+        if m1.is_none() && m2.is_none() {
+            break;
+        }
+    }
+}
+
+#[test]
+fn the_authorizer_survives_loading_into_an_empty_document() {
+    for via_sync in [false, true] {
+        let mut source = new_doc();
+        source.put(ROOT, "title", "hello").unwrap();
+        let mut target = AutoCommit::new();
+        target.set_authorizer(|_| Err("read only".to_string()));
+        deliver(&mut source, &mut target, via_sync);
+        assert_eq!(target.get_heads(), source.get_heads());
+        assert!(matches!(
+            target.put(ROOT, "title", "changed"),
+            Err(AutomergeError::Unauthorized { .. })
+        ));
+    }
+}
+
+#[test]
+fn the_schema_survives_loading_into_an_empty_document() {
+    use automerge::schema::{ScalarType, Schema, SchemaAction};
+
+    for via_sync in [false, true] {
+        let mut source = new_doc();
+        source.put(ROOT, "count", "many").unwrap();
+        let bad = source.commit().unwrap();
+        let mut target = AutoCommit::new();
+        target.set_schema(Schema::map().optional("count", ScalarType::Int));
+        target.set_schema_policy(|_, _| SchemaAction::Quarantine);
+        deliver(&mut source, &mut target, via_sync);
+        assert!(target.schema().is_some());
+        assert!(target.is_quarantined(&bad));
+        assert_eq!(target.get(ROOT, "count").unwrap(), None);
+    }
+}
+
+#[test]
+fn the_deps_strategy_survives_loading_into_an_empty_document() {
+    for via_sync in [false, true] {
+        let mut source = new_doc();
+        source.put(ROOT, "title", "hello").unwrap();
+        let mut target = AutoCommit::new();
+        target.set_deps_strategy(DepsStrategy::Minimal);
+        deliver(&mut source, &mut target, via_sync);
+        assert_eq!(target.document().deps_strategy(), DepsStrategy::Minimal);
+    }
+}
+
+#[test]
+fn conflict_tracking_survives_loading_into_an_empty_document() {
+    for via_sync in [false, true] {
+        let mut source = new_doc();
+        let mut other = source.fork();
+        source.put(ROOT, "title", "one").unwrap();
+        other.put(ROOT, "title", "two").unwrap();
+        source.merge(&mut other).unwrap();
+        let mut target = AutoCommit::new();
+        target.set_track_conflicts(true);
+        deliver(&mut source, &mut target, via_sync);
+        assert_eq!(target.conflict_stats().for_key(&ROOT, "title").created, 1);
+    }
+}
+
+#[test]
+fn the_conflict_policy_survives_loading_into_an_empty_document() {
+    for via_sync in [false, true] {
+        let mut source = AutoCommit::new().with_actor(ActorId::from(b"bb".as_slice()));
+        let mut other = AutoCommit::new().with_actor(ActorId::from(b"aa".as_slice()));
+        source.put(ROOT, "n", 3).unwrap();
+        other.put(ROOT, "n", 7).unwrap();
+        source.merge(&mut other).unwrap();
+        assert_eq!(source.get(ROOT, "n").unwrap().unwrap().0.to_i64(), Some(3));
+        let mut target = AutoCommit::new();
+        target.set_conflict_policy(automerge::ConflictPolicy::MaxNumeric);
+        deliver(&mut source, &mut target, via_sync);
+        assert_eq!(target.get(ROOT, "n").unwrap().unwrap().0.to_i64(), Some(7));
+    }
+}
+
+#[test]
+fn the_blob_store_survives_loading_into_an_empty_document() {
+    use automerge::blob::MemoryBlobStore;
+    use std::sync::Arc;
+
+    let store = Arc::new(MemoryBlobStore::default());
+    for via_sync in [false, true] {
+        let mut source = new_doc();
+        source.set_blob_store(store.clone());
+        let blob = source.store_blob(b"some data").unwrap();
+        source.put(ROOT, "blob", blob.clone()).unwrap();
+        let mut target = AutoCommit::new();
+        target.set_blob_store(store.clone());
+        deliver(&mut source, &mut target, via_sync);
+        assert_eq!(target.read_blob(&blob).unwrap(), b"some data");
+    }
+}
+
+#[test]
+fn the_link_resolver_survives_loading_into_an_empty_document() {
+    use automerge::link::{DocId, MemoryLinkResolver};
+    use std::sync::Arc;
+
+    let id = DocId::random();
+    let resolver = Arc::new(MemoryLinkResolver::default());
+    resolver.insert(id.clone(), new_doc().document().clone());
+    for via_sync in [false, true] {
+        let mut source = new_doc();
+        source.put(ROOT, "link", id.clone()).unwrap();
+        let mut target = AutoCommit::new();
+        target.set_link_resolver(resolver.clone());
+        deliver(&mut source, &mut target, via_sync);
+        assert!(target.resolve_link(&id).is_ok());
+    }
+}
+
+#[test]
+fn history_compaction_survives_loading_into_an_empty_document() {
+    for via_sync in [false, true] {
+        let mut source = new_doc();
+        let text = source.put_object(ROOT, "text", ObjType::Text).unwrap();
+        for i in 0..5 {
+            source
+                .splice_text(&text, 0, 0, &format!("edit {} ", i).repeat(50))
+                .unwrap();
+            source.commit();
+        }
+        let mut plain = AutoCommit::new();
+        deliver(&mut source, &mut plain, via_sync);
+        let mut target = AutoCommit::new();
+        target.set_compact_history(true);
+        deliver(&mut source, &mut target, via_sync);
+        assert!(target.resident_history_bytes() < plain.resident_history_bytes());
+        assert_eq!(target.text(&text).unwrap(), source.text(&text).unwrap());
+    }
+}
+
+#[test]
+fn frozen_objects_survive_loading_into_an_empty_document() {
+    for via_sync in [false, true] {
+        let mut source = new_doc();
+        source.put(ROOT, "title", "hello").unwrap();
+        let hash = source.commit().unwrap();
+        let mut target = AutoCommit::new();
+        target.freeze(ROOT).unwrap();
+        target.set_quarantine_frozen_violations(true);
+        deliver(&mut source, &mut target, via_sync);
+        assert_eq!(target.frozen_violations(), &[hash]);
+        assert!(target.is_quarantined(&hash));
+        assert_eq!(target.get(ROOT, "title").unwrap(), None);
+        assert!(matches!(
+            target.put(ROOT, "title", "changed"),
+            Err(AutomergeError::Frozen(_))
+        ));
+    }
+}
+
+#[test]
+fn metrics_survive_loading_into_an_empty_document() {
+    use automerge::metrics::Counters;
+    use std::sync::Arc;
+
+    for via_sync in [false, true] {
+        let mut source = new_doc();
+        source.put(ROOT, "a", 1).unwrap();
+        source.commit();
+        source.put(ROOT, "b", 2).unwrap();
+        source.commit();
+        let counters = Arc::new(Counters::default());
+        let mut target = AutoCommit::new();
+        target.set_metrics(counters.clone());
+        deliver(&mut source, &mut target, via_sync);
+        assert_eq!(counters.changes_received(), 2);
+        assert_eq!(counters.ops_applied(), 2);
+    }
+}
+
+#[test]
+fn forks_and_the_original_document_diverge_after_sharing_their_op_set() {
+    let mut doc = new_doc();
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    for i in 0..3000 {
+        doc.insert(&list, i, i as i64).unwrap();
+    }
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello").unwrap();
+    doc.put(ROOT, "counter", ScalarValue::counter(1)).unwrap();
+    doc.commit();
+
+    let mut fork = doc.fork();
+    fork.put(&list, 1, "changed").unwrap();
+    fork.delete(&list, 2999).unwrap();
+    fork.splice_text(&text, 5, 0, " world").unwrap();
+    fork.increment(ROOT, "counter", 2).unwrap();
+    fork.commit();
+
+    doc.put(&list, 0, "original").unwrap();
+    doc.commit();
+
+    assert_eq!(doc.length(&list), 3000);
+    assert_eq!(fork.length(&list), 2999);
+    assert_eq!(doc.get(&list, 1).unwrap().unwrap().0, Value::int(1));
+    assert_eq!(
+        fork.get(&list, 1).unwrap().unwrap().0,
+        Value::str("changed")
+    );
+    assert_eq!(
+        doc.get(&list, 0).unwrap().unwrap().0,
+        Value::str("original")
+    );
+    assert_eq!(fork.get(&list, 0).unwrap().unwrap().0, Value::int(0));
+    assert_eq!(doc.text(&text).unwrap(), "hello");
+    assert_eq!(fork.text(&text).unwrap(), "hello world");
+    assert_eq!(
+        doc.get(ROOT, "counter").unwrap().unwrap().0,
+        Value::counter(1)
+    );
+    assert_eq!(
+        fork.get(ROOT, "counter").unwrap().unwrap().0,
+        Value::counter(3)
+    );
+
+    doc.merge(&mut fork).unwrap();
+    assert_eq!(doc.length(&list), 2999);
+    assert_eq!(doc.text(&text).unwrap(), "hello world");
+    assert_eq!(
+        doc.get(ROOT, "counter").unwrap().unwrap().0,
+        Value::counter(3)
+    );
+}
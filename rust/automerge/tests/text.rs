@@ -27,6 +27,84 @@ fn simple_update_text() {
     assert_eq!(doc.text(&text).unwrap(), "Goodbye, friends!");
 }
 
+#[test]
+fn annotations_follow_the_text_they_annotate() {
+    use automerge::annotations::Annotations;
+
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    let store = doc.put_object(ROOT, "comments", ObjType::Map).unwrap();
+    doc.splice_text(&text, 0, 0, "the quick brown fox").unwrap();
+    let annotations = Annotations::new(store, text.clone());
+    annotations.add(&mut doc, "quick", 4..9, "fast?").unwrap();
+    annotations.add(&mut doc, "fox", 16..19, "animal").unwrap();
+    assert!(annotations.add(&mut doc, "empty", 3..3, "").is_err());
+    assert!(annotations.add(&mut doc, "past", 16..20, "").is_err());
+
+    let mut other = doc.fork();
+    // an insert at the start of a range is not part of it
+    doc.splice_text(&text, 4, 0, "very ").unwrap();
+    other.splice_text(&text, 10, 6, "").unwrap();
+    doc.merge(&mut other).unwrap();
+    assert_eq!(doc.text(&text).unwrap(), "the very quick fox");
+
+    let quick = annotations.get(&doc, "quick").unwrap().unwrap();
+    assert_eq!(quick.range, 9..14);
+    assert_eq!(quick.value, ScalarValue::from("fast?"));
+    let fox = annotations.get(&doc, "fox").unwrap().unwrap();
+    assert_eq!(fox.range, 15..18);
+
+    let ids = |found: Vec<automerge::annotations::Annotation>| {
+        found.into_iter().map(|a| a.id).collect::<Vec<_>>()
+    };
+    assert_eq!(
+        ids(annotations.overlapping(&doc, 0..9).unwrap()),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        ids(annotations.overlapping(&doc, 13..16).unwrap()),
+        vec!["fox", "quick"]
+    );
+    assert_eq!(
+        ids(annotations.overlapping(&doc, 17..17).unwrap()),
+        vec!["fox"]
+    );
+    assert_eq!(
+        ids(annotations
+            .overlapping(&doc, usize::MAX..usize::MAX)
+            .unwrap()),
+        Vec::<String>::new()
+    );
+
+    // deleting the last character of a range shrinks it, deleting all of it empties it
+    doc.splice_text(&text, 17, 1, "").unwrap();
+    assert_eq!(annotations.get(&doc, "fox").unwrap().unwrap().range, 15..17);
+    doc.splice_text(&text, 8, 6, "").unwrap();
+    assert_eq!(annotations.get(&doc, "quick").unwrap().unwrap().range, 8..8);
+
+    annotations.remove(&mut doc, "quick").unwrap();
+    assert_eq!(ids(annotations.all(&doc).unwrap()), vec!["fox"]);
+
+    let loaded = automerge::Automerge::load(&doc.save()).unwrap();
+    assert_eq!(
+        annotations.all(&loaded).unwrap(),
+        annotations.all(&doc).unwrap()
+    );
+}
+
+#[test]
+fn update_text_only_touches_the_changed_text() {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "The quick brown fox").unwrap();
+    doc.commit();
+
+    doc.update_text(&text, "The quick red fox").unwrap();
+    // "brown" -> "red" keeps the shared "r": 4 deletes and 2 inserts
+    assert_eq!(doc.pending_ops(), 6);
+    assert_eq!(doc.text(&text).unwrap(), "The quick red fox");
+}
+
 #[test]
 fn update_text_big_ole_graphemes() {
     let actor1 = ActorId::from_str("aaaaaa").unwrap();